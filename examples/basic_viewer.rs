@@ -0,0 +1,30 @@
+// the renderer with nothing added: the default scene, or a model given on the command line in
+// its place. the keys, mouse and console work as they do in the main binary
+//
+//   cargo run --example basic_viewer -- src/assets/models/bunny2.obj
+
+use graphics_fundamentals::{State, Viewer};
+
+struct BasicViewer {
+    path: Option<String>,
+}
+
+impl Viewer for BasicViewer {
+    fn setup(&mut self, state: &mut State) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        for id in state.model_ids() {
+            state.unload_model(id)?;
+        }
+        let id = state.load_model(path)?;
+        log::info!("loaded {} as model {}", path, id);
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    graphics_fundamentals::run_viewer(BasicViewer {
+        path: std::env::args().nth(1),
+    })
+}
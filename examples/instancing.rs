@@ -0,0 +1,84 @@
+// a stress test for per object drawing: a grid of spinning cubes, 1000 unless another count is
+// given. every cube is a copy of one loaded model sharing its buffers, but still gets its own
+// transform slot and draw call, so the counts in the title bar grow with the grid. M batches
+// the materials, which changes the bind group switches but not the draws
+//
+//   cargo run --release --example instancing -- 4000
+
+use cgmath::Rotation3;
+use graphics_fundamentals::{ModelId, State, Viewer, math::Transform};
+
+const SPACING: f32 = 2.0;
+// degrees per second, varied a little per cube so they don't all turn in step
+const SPIN_SPEED: f32 = 45.0;
+
+struct Instancing {
+    count: usize,
+    cubes: Vec<(ModelId, Transform)>,
+    time: f32,
+}
+
+impl Viewer for Instancing {
+    fn setup(&mut self, state: &mut State) -> anyhow::Result<()> {
+        for id in state.model_ids() {
+            state.unload_model(id)?;
+        }
+        let cube = state.load_model("src/assets/models/cube-smooth.obj")?;
+
+        // as close to a cube of cubes as the count allows, centered on the origin
+        let side = (self.count as f32).cbrt().ceil() as usize;
+        let offset = (side - 1) as f32 * SPACING / 2.0;
+        let transforms = (0..self.count)
+            .map(|i| {
+                let (x, y, z) = (i % side, i / side % side, i / (side * side));
+                Transform::from_translation([
+                    x as f32 * SPACING - offset,
+                    y as f32 * SPACING - offset,
+                    z as f32 * SPACING - offset,
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        // the loaded cube is the first one, the copies fill the rest of the grid
+        state.set_transform(cube, transforms[0].clone());
+        let copies = state.add_instances(cube, &transforms[1..])?;
+        self.cubes = std::iter::once(cube)
+            .chain(copies)
+            .zip(transforms)
+            .collect();
+
+        state.set_camera(
+            [0.0, 0.0, offset * 3.0 + 5.0],
+            cgmath::Deg(-90.0),
+            cgmath::Deg(0.0),
+        );
+        log::info!("drawing {} cubes", self.cubes.len());
+        Ok(())
+    }
+
+    fn update(&mut self, state: &mut State, dt: std::time::Duration) {
+        self.time += dt.as_secs_f32();
+        for (i, (id, transform)) in self.cubes.iter_mut().enumerate() {
+            let speed = SPIN_SPEED * (1.0 + (i % 7) as f32 / 7.0);
+            transform.set_rotation(cgmath::Quaternion::from_axis_angle(
+                cgmath::Vector3::unit_y(),
+                cgmath::Deg(self.time * speed),
+            ));
+            state.set_transform(*id, transform.clone());
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let count = match std::env::args().nth(1) {
+        Some(count) => count
+            .parse()
+            .map_err(|_| anyhow::anyhow!("expected a number of cubes, got {}", count))?,
+        None => 1000,
+    };
+    graphics_fundamentals::run_viewer(Instancing {
+        count: count.max(1),
+        cubes: Vec::new(),
+        time: 0.0,
+    })
+}
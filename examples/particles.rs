@@ -0,0 +1,89 @@
+// a fountain of particles simulated on the gpu, bouncing off a floor and a ball, and a stream of
+// sparks circling above them which go out wherever they land. each emitter only gives where,
+// how fast and how often its particles start; moving them, colliding them with whatever's on
+// screen and drawing them all happens on the gpu, so there can be tens of thousands at once.
+// the sparks' emitter follows its circle through set_emitter
+//
+//   cargo run --release --example particles
+
+use cgmath::Rotation3;
+use graphics_fundamentals::{Collision, Emitter, EmitterId, State, Viewer, math::Transform};
+
+// the sparks' circle, and how long they take to go round
+const SPARKS_RADIUS: f32 = 3.0;
+const SPARKS_HEIGHT: f32 = 5.0;
+const SPARKS_PERIOD: f32 = 6.0;
+
+struct Particles {
+    sparks: Option<(EmitterId, Emitter)>,
+    time: f32,
+}
+
+impl Viewer for Particles {
+    fn setup(&mut self, state: &mut State) -> anyhow::Result<()> {
+        for id in state.model_ids() {
+            state.unload_model(id)?;
+        }
+
+        // the plane is 10 units square in xy from the origin, so it's laid flat facing up and
+        // moved to be centered under the fountain
+        let floor = state.load_model("src/assets/models/plane.obj")?;
+        let mut transform =
+            Transform::from_rotation(cgmath::Quaternion::from_angle_x(cgmath::Deg(-90.0)));
+        transform.set_scale([2.0, 2.0, 2.0]);
+        transform.set_translation([-10.0, -2.0, 10.0]);
+        state.set_transform(floor, transform);
+        let ball = state.load_model("src/assets/models/ball.obj")?;
+        state.set_transform(ball, Transform::from_translation([1.5, -1.0, 0.0]));
+
+        state.add_emitter(Emitter {
+            position: [0.0, -2.0, 0.0],
+            velocity: [0.0, 8.0, 0.0],
+            spread: 2.0,
+            rate: 2000.0,
+            lifetime: 4.0,
+            size: 0.06,
+            color: [0.4, 0.7, 1.0, 1.0],
+            collision: Collision::Bounce { restitution: 0.4 },
+            ..Default::default()
+        })?;
+
+        let sparks = Emitter {
+            velocity: [0.0, -1.0, 0.0],
+            spread: 0.5,
+            rate: 500.0,
+            lifetime: 2.0,
+            size: 0.04,
+            color: [1.0, 0.6, 0.2, 1.0],
+            collision: Collision::Kill,
+            ..Default::default()
+        };
+        self.sparks = Some((state.add_emitter(sparks)?, sparks));
+
+        state.set_camera([0.0, 3.0, 14.0], cgmath::Deg(-90.0), cgmath::Deg(-15.0));
+        Ok(())
+    }
+
+    fn update(&mut self, state: &mut State, dt: std::time::Duration) {
+        self.time += dt.as_secs_f32();
+        let Some((id, mut sparks)) = self.sparks else {
+            return;
+        };
+        let angle = self.time / SPARKS_PERIOD * std::f32::consts::TAU;
+        sparks.position = [
+            SPARKS_RADIUS * angle.cos(),
+            SPARKS_HEIGHT,
+            SPARKS_RADIUS * angle.sin(),
+        ];
+        if let Err(e) = state.set_emitter(id, sparks) {
+            log::error!("{:#}", e);
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    graphics_fundamentals::run_viewer(Particles {
+        sparks: None,
+        time: 0.0,
+    })
+}
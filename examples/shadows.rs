@@ -0,0 +1,70 @@
+// ray traced shadows: a few shapes on a floor under a point light circling overhead. the shadows
+// come from ray queries against the scene's acceleration structures, so they need an adapter
+// with ray query support; without one the sun's shadow cascades and the point light's cube of
+// shadow maps take over. T switches between the ray traced and plain pipelines to compare, and
+// K colors the sun's shadow cascades. J cycles the shadow filtering between hard, pcf and pcss
+//
+//   cargo run --release --example shadows
+
+use cgmath::Rotation3;
+use graphics_fundamentals::{State, Viewer, math::Transform};
+
+// the light's circle, and how long it takes to go round
+const LIGHT_RADIUS: f32 = 6.0;
+const LIGHT_HEIGHT: f32 = 8.0;
+const LIGHT_PERIOD: f32 = 8.0;
+
+struct Shadows {
+    time: f32,
+}
+
+impl Viewer for Shadows {
+    fn setup(&mut self, state: &mut State) -> anyhow::Result<()> {
+        for id in state.model_ids() {
+            state.unload_model(id)?;
+        }
+
+        // the plane is 10 units square in xy from the origin, so it's laid flat facing up and
+        // moved to be centered under the shapes
+        let floor = state.load_model("src/assets/models/plane.obj")?;
+        let mut transform =
+            Transform::from_rotation(cgmath::Quaternion::from_angle_x(cgmath::Deg(-90.0)));
+        transform.set_scale([2.0, 2.0, 2.0]);
+        transform.set_translation([-10.0, -1.0, 10.0]);
+        state.set_transform(floor, transform);
+
+        for (path, position) in [
+            ("src/assets/models/ball.obj", [-3.0, 0.5, 0.0]),
+            ("src/assets/models/cube-smooth.obj", [0.0, 0.0, -2.0]),
+            ("src/assets/models/ball.obj", [3.0, 1.5, 1.0]),
+        ] {
+            let id = state.load_model(path)?;
+            state.set_transform(id, Transform::from_translation(position));
+        }
+
+        state.set_camera([0.0, 6.0, 14.0], cgmath::Deg(-90.0), cgmath::Deg(-25.0));
+        if let Err(e) = state.set_ray_tracing(true) {
+            log::warn!("{:#}, so only the sun casts shadows", e);
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, state: &mut State, dt: std::time::Duration) {
+        self.time += dt.as_secs_f32();
+        let angle = self.time / LIGHT_PERIOD * std::f32::consts::TAU;
+        state.set_point_light(
+            0,
+            [
+                angle.cos() * LIGHT_RADIUS,
+                LIGHT_HEIGHT,
+                angle.sin() * LIGHT_RADIUS,
+            ],
+            [1.0; 3],
+            1.0,
+        );
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    graphics_fundamentals::run_viewer(Shadows { time: 0.0 })
+}
@@ -0,0 +1,348 @@
+// moves lights and models over time, along keyframed tracks or along parametric paths. lights
+// are animated by index from scene files (see scene_file.rs), and models by the Animator on
+// their scene object. both are played in State::update while playback is on, which L toggles
+
+use std::f32::consts::TAU;
+
+use cgmath::{Deg, Euler, InnerSpace, Quaternion, Vector3};
+
+use crate::{json::JsonValue, math};
+
+// how a track gets from one key to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    // holds each key until the next one
+    Step,
+    #[default]
+    Linear,
+    // a catmull rom spline through the keys, which eases through them instead of turning
+    // sharply at each one
+    Cubic,
+}
+
+// values at times in seconds, interpolated between. the times are kept sorted
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    keys: Vec<(f32, [f32; 3])>,
+    interpolation: Interpolation,
+    // starts over after the last key, instead of holding it
+    is_looping: bool,
+}
+
+impl Track {
+    pub fn new(
+        mut keys: Vec<(f32, [f32; 3])>,
+        interpolation: Interpolation,
+        is_looping: bool,
+    ) -> Self {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            keys,
+            interpolation,
+            is_looping,
+        }
+    }
+
+    pub fn sample(&self, time: f32) -> [f32; 3] {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return [0.0; 3];
+        };
+        let time = if self.is_looping && last.0 > first.0 {
+            first.0 + (time - first.0).rem_euclid(last.0 - first.0)
+        } else {
+            time
+        };
+
+        let next = self.keys.partition_point(|key| key.0 <= time);
+        if next == 0 {
+            return first.1;
+        }
+        if next == self.keys.len() {
+            return last.1;
+        }
+        let (start_time, start) = self.keys[next - 1];
+        let (end_time, end) = self.keys[next];
+        let t = (time - start_time) / (end_time - start_time);
+
+        match self.interpolation {
+            Interpolation::Step => start,
+            Interpolation::Linear => std::array::from_fn(|i| start[i] + (end[i] - start[i]) * t),
+            Interpolation::Cubic => {
+                // the keys either side, repeating the end keys where there are none
+                let before = self.keys[next.saturating_sub(2)].1;
+                let after = self.keys[(next + 1).min(self.keys.len() - 1)].1;
+                let (t2, t3) = (t * t, t * t * t);
+                std::array::from_fn(|i| {
+                    0.5 * (2.0 * start[i]
+                        + (end[i] - before[i]) * t
+                        + (2.0 * before[i] - 5.0 * start[i] + 4.0 * end[i] - after[i]) * t2
+                        + (3.0 * start[i] - before[i] - 3.0 * end[i] + after[i]) * t3)
+                })
+            }
+        }
+    }
+
+    // {"keyframes": [[time, [x, y, z]], ...], "interpolation": "linear", "loop": true}, where
+    // the interpolation is step, linear or cubic. linear and looping unless told otherwise
+    fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let keys = json
+            .get("keyframes")
+            .and_then(JsonValue::as_array)
+            .ok_or("a track needs a keyframes array")?
+            .iter()
+            .map(|key| match key.as_array() {
+                Some([time, value]) => time
+                    .as_f64()
+                    .zip(value.as_f32_array::<3>())
+                    .map(|(time, value)| (time as f32, value)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or("keyframes are [time, [x, y, z]]")?;
+        if keys.is_empty() {
+            return Err("a track needs at least one keyframe".to_string());
+        }
+        let interpolation = match json.get("interpolation").map(|i| i.as_str()) {
+            None => Interpolation::default(),
+            Some(Some("step")) => Interpolation::Step,
+            Some(Some("linear")) => Interpolation::Linear,
+            Some(Some("cubic")) => Interpolation::Cubic,
+            Some(_) => return Err("interpolation should be step, linear or cubic".to_string()),
+        };
+        let is_looping = json
+            .get("loop")
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(true);
+        Ok(Self::new(keys, interpolation, is_looping))
+    }
+}
+
+// positions given by a formula of time, which repeat forever
+#[derive(Debug, Clone, PartialEq)]
+pub enum Path {
+    // around the axis through the center, once per period seconds
+    Circle {
+        center: [f32; 3],
+        axis: [f32; 3],
+        radius: f32,
+        period: f32,
+    },
+    // each axis swings sinusoidally at its own frequency, in cycles per second
+    Lissajous {
+        center: [f32; 3],
+        amplitude: [f32; 3],
+        frequency: [f32; 3],
+        // in cycles, so 0.25 starts an axis a quarter of the way through
+        phase: [f32; 3],
+    },
+}
+
+impl Path {
+    pub fn sample(&self, time: f32) -> [f32; 3] {
+        match *self {
+            Path::Circle {
+                center,
+                axis,
+                radius,
+                period,
+            } => {
+                let axis = Vector3::from(axis).normalize();
+                let helper = if axis.x.abs() > 0.9 {
+                    Vector3::unit_y()
+                } else {
+                    Vector3::unit_x()
+                };
+                let u = helper.cross(axis).normalize();
+                let v = axis.cross(u);
+                let angle = time / period.max(f32::EPSILON) * TAU;
+                let (sin, cos) = angle.sin_cos();
+                (Vector3::from(center) + (u * cos + v * sin) * radius).into()
+            }
+            Path::Lissajous {
+                center,
+                amplitude,
+                frequency,
+                phase,
+            } => std::array::from_fn(|i| {
+                center[i] + amplitude[i] * ((frequency[i] * time + phase[i]) * TAU).sin()
+            }),
+        }
+    }
+
+    // {"path": "circle", "center": [x, y, z], "radius": r, "period": seconds, "axis": [x, y, z]}
+    // or {"path": "lissajous", "center": ..., "amplitude": ..., "frequency": ..., "phase": ...},
+    // where the axis defaults to up and the center and phase to zero
+    fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let vector = |key: &str, default: [f32; 3]| match json.get(key) {
+            None => Ok(default),
+            Some(value) => value
+                .as_f32_array::<3>()
+                .ok_or_else(|| format!("{} should be [x, y, z]", key)),
+        };
+        let number = |key: &str| {
+            json.get(key)
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as f32)
+                .ok_or_else(|| format!("missing or mistyped field {}", key))
+        };
+
+        match json.get("path").and_then(JsonValue::as_str) {
+            Some("circle") => Ok(Path::Circle {
+                center: vector("center", [0.0; 3])?,
+                axis: vector("axis", [0.0, 1.0, 0.0])?,
+                radius: number("radius")?,
+                period: number("period")?,
+            }),
+            Some("lissajous") => Ok(Path::Lissajous {
+                center: vector("center", [0.0; 3])?,
+                amplitude: vector("amplitude", [1.0; 3])?,
+                frequency: vector("frequency", [1.0; 3])?,
+                phase: vector("phase", [0.0; 3])?,
+            }),
+            Some(other) => Err(format!(
+                "unknown path {}, expected circle or lissajous",
+                other
+            )),
+            None => Err("a path needs a path type".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Motion {
+    Track(Track),
+    Path(Path),
+}
+
+impl Motion {
+    pub fn sample(&self, time: f32) -> [f32; 3] {
+        match self {
+            Motion::Track(track) => track.sample(time),
+            Motion::Path(path) => path.sample(time),
+        }
+    }
+
+    // a track if it has keyframes, otherwise a path
+    pub fn from_json(json: &JsonValue) -> Result<Self, String> {
+        if json.get("keyframes").is_some() {
+            Track::from_json(json).map(Motion::Track)
+        } else {
+            Path::from_json(json).map(Motion::Path)
+        }
+    }
+}
+
+// a point light's animation, played on the scene's clock
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightAnimation {
+    pub index: usize,
+    pub position: Option<Motion>,
+    pub color: Option<Track>,
+}
+
+impl LightAnimation {
+    // {"position": motion, "color": track}, either of which can be left out
+    pub fn from_json(index: usize, json: &JsonValue) -> Result<Self, String> {
+        Ok(Self {
+            index,
+            position: json.get("position").map(Motion::from_json).transpose()?,
+            color: json.get("color").map(Track::from_json).transpose()?,
+        })
+    }
+}
+
+// poses a model from its tracks, on a clock of its own so models can play at different speeds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animator {
+    pub position: Option<Motion>,
+    // euler angles in degrees, applied x then y then z
+    pub rotation: Option<Track>,
+    pub scale: Option<Track>,
+    // how many seconds of the tracks play per second
+    pub speed: f32,
+    time: f32,
+}
+
+impl Animator {
+    pub fn new(position: Option<Motion>, rotation: Option<Track>, scale: Option<Track>) -> Self {
+        Self {
+            position,
+            rotation,
+            scale,
+            speed: 1.0,
+            time: 0.0,
+        }
+    }
+
+    // a turn around the y axis every period seconds
+    pub fn spin(period: f32) -> Self {
+        let rotation = Track::new(
+            vec![(0.0, [0.0; 3]), (period, [0.0, 360.0, 0.0])],
+            Interpolation::Linear,
+            true,
+        );
+        Self::new(None, Some(rotation), None)
+    }
+
+    // moves the clock on and sets the parts of the transform that have tracks
+    pub fn advance(&mut self, dt: f32, transform: &mut math::Transform) {
+        self.time += dt * self.speed;
+        if let Some(position) = &self.position {
+            transform.set_translation(position.sample(self.time));
+        }
+        if let Some(rotation) = &self.rotation {
+            let [x, y, z] = rotation.sample(self.time);
+            transform.set_rotation(Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))));
+        }
+        if let Some(scale) = &self.scale {
+            transform.set_scale(scale.sample(self.time));
+        }
+    }
+
+    // {"position": motion, "rotation": track, "scale": track, "speed": 1}, where any of them
+    // can be left out
+    pub fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let mut animator = Self::new(
+            json.get("position").map(Motion::from_json).transpose()?,
+            json.get("rotation").map(Track::from_json).transpose()?,
+            json.get("scale").map(Track::from_json).transpose()?,
+        );
+        if let Some(speed) = json.get("speed") {
+            animator.speed = speed.as_f64().ok_or("speed should be a number")? as f32;
+        }
+        Ok(animator)
+    }
+}
+
+// the scene's light animations, and the switch for playing them and the models' animators
+#[derive(Debug, Default)]
+pub struct Animations {
+    pub lights: Vec<LightAnimation>,
+    pub is_playing: bool,
+    time: f32,
+}
+
+impl Animations {
+    // replaces the light's animation, if it had one
+    pub fn set_light(&mut self, animation: LightAnimation) {
+        self.lights.retain(|a| a.index != animation.index);
+        self.lights.push(animation);
+    }
+
+    // moves the clock on, returning whether there is anything to apply
+    pub fn advance(&mut self, dt: f32) -> bool {
+        if !self.is_playing || self.lights.is_empty() {
+            return false;
+        }
+        self.time += dt;
+        true
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.is_playing { "[ANIMATING]" } else { "" }
+    }
+}
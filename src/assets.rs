@@ -0,0 +1,263 @@
+// where asset files come from. natively that's the disk, while on the web there's no file system,
+// so files are fetched over http instead, relative to the page, eg src/assets/models/ball.obj
+// next to index.html. fetching can only be waited on asynchronously there, so fetch and
+// fetch_text are async on both platforms, and report their progress for a loading bar.
+//
+// the loaders (obj, mtl, ply and textures) read synchronously, through read and open below.
+// those serve files put in memory by preload first, and otherwise read from the disk, so on the
+// web everything a loader will ask for has to be preloaded beforehand
+
+// natively the loaders read from the disk as they go, so only the web preloads anything
+#![cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Cursor},
+    sync::{Arc, Mutex},
+};
+
+use crate::error::AssetError;
+
+// files fetched by preload, by the path they were asked for with
+static PRELOADED: Mutex<BTreeMap<String, Arc<[u8]>>> = Mutex::new(BTreeMap::new());
+
+// how far through fetching a file is, in bytes
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub loaded: u64,
+    // unknown when the server doesn't send the content's length
+    pub total: Option<u64>,
+}
+
+impl Progress {
+    // from 0 to 1, for the loading bar
+    pub fn fraction(&self) -> Option<f32> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.loaded as f64 / total as f64).min(1.0) as f32)
+    }
+}
+
+// the file's contents, with on_progress called as they arrive
+pub async fn fetch(
+    path: &str,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, AssetError> {
+    if let Some(data) = preloaded(path) {
+        let total = data.len() as u64;
+        on_progress(Progress {
+            loaded: total,
+            total: Some(total),
+        });
+        return Ok(data.to_vec());
+    }
+    platform::fetch(path, &mut on_progress).await
+}
+
+pub async fn fetch_text(
+    path: &str,
+    on_progress: impl FnMut(Progress),
+) -> Result<String, AssetError> {
+    let data = fetch(path, on_progress).await?;
+    String::from_utf8(data).map_err(|e| AssetError::BadData {
+        path: path.to_string(),
+        message: format!("not utf-8: {}", e),
+    })
+}
+
+// fetches the files one after the other into memory, where read and open find them. the
+// progress is given over all of them together, and its total is only known once every file's
+// length is, which on the web is when the last one has started to arrive
+pub async fn preload(
+    paths: &[&str],
+    mut on_progress: impl FnMut(&str, Progress),
+) -> Result<(), AssetError> {
+    let mut loaded = 0;
+    let mut totals = vec![None; paths.len()];
+    for (i, &path) in paths.iter().enumerate() {
+        let data = fetch(path, |progress| {
+            totals[i] = progress.total;
+            let total = totals.iter().copied().sum::<Option<u64>>();
+            on_progress(
+                path,
+                Progress {
+                    loaded: loaded + progress.loaded,
+                    total,
+                },
+            );
+        })
+        .await?;
+        loaded += data.len() as u64;
+        PRELOADED
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), data.into());
+    }
+    Ok(())
+}
+
+fn preloaded(path: &str) -> Option<Arc<[u8]>> {
+    PRELOADED.lock().unwrap().get(path).cloned()
+}
+
+// the whole file, from memory if it was preloaded
+pub fn read(path: &str) -> std::io::Result<Vec<u8>> {
+    match preloaded(path) {
+        Some(data) => Ok(data.to_vec()),
+        None => platform::read(path),
+    }
+}
+
+// the file to be parsed line by line, from memory if it was preloaded
+pub fn open(path: &str) -> std::io::Result<Box<dyn BufRead>> {
+    match preloaded(path) {
+        Some(data) => Ok(Box::new(Cursor::new(data))),
+        None => platform::open(path),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::io::{BufRead, Read};
+
+    use super::Progress;
+    use crate::error::AssetError;
+
+    // what's read from the disk between progress reports
+    const CHUNK_SIZE: usize = 1 << 20;
+
+    // reads in chunks so there's progress to show as on the web, although it blocks while it
+    // reads. the disk is quick enough that nothing waits on it for long
+    pub async fn fetch(
+        path: &str,
+        on_progress: &mut impl FnMut(Progress),
+    ) -> Result<Vec<u8>, AssetError> {
+        let mut file = std::fs::File::open(path).map_err(|source| AssetError::MissingFile {
+            path: path.to_string(),
+            source,
+        })?;
+        let total = file.metadata().ok().map(|m| m.len());
+        let read_error = |source: std::io::Error| AssetError::Read {
+            path: path.to_string(),
+            source,
+        };
+
+        let mut data = Vec::with_capacity(total.unwrap_or(0) as usize);
+        let mut chunk = vec![0; CHUNK_SIZE];
+        loop {
+            let count = file.read(&mut chunk).map_err(read_error)?;
+            if count == 0 {
+                break;
+            }
+            data.extend_from_slice(&chunk[..count]);
+            on_progress(Progress {
+                loaded: data.len() as u64,
+                total,
+            });
+        }
+        Ok(data)
+    }
+
+    pub fn read(path: &str) -> std::io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    pub fn open(path: &str) -> std::io::Result<Box<dyn BufRead>> {
+        Ok(Box::new(std::io::BufReader::new(std::fs::File::open(
+            path,
+        )?)))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use std::io::BufRead;
+
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    use super::Progress;
+    use crate::error::AssetError;
+
+    // fetched relative to the page's url. the body is read as a stream, a chunk at a time, so
+    // there's progress to report, falling back to reading it all at once where streams aren't
+    // supported
+    pub async fn fetch(
+        path: &str,
+        on_progress: &mut impl FnMut(Progress),
+    ) -> Result<Vec<u8>, AssetError> {
+        let error = |message: String| AssetError::Fetch {
+            path: path.to_string(),
+            message,
+        };
+        let js_error = |e: wasm_bindgen::JsValue| error(format!("{:?}", e));
+
+        let window = web_sys::window().ok_or_else(|| error("there's no window".into()))?;
+        let response: web_sys::Response = JsFuture::from(window.fetch_with_str(path))
+            .await
+            .map_err(js_error)?
+            .dyn_into()
+            .map_err(js_error)?;
+        if !response.ok() {
+            return Err(error(format!(
+                "{} {}",
+                response.status(),
+                response.status_text()
+            )));
+        }
+        let total = response
+            .headers()
+            .get("content-length")
+            .ok()
+            .flatten()
+            .and_then(|length| length.parse().ok());
+
+        let Some(body) = response.body() else {
+            let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+                .await
+                .map_err(js_error)?;
+            let data = js_sys::Uint8Array::new(&buffer).to_vec();
+            on_progress(Progress {
+                loaded: data.len() as u64,
+                total: Some(data.len() as u64),
+            });
+            return Ok(data);
+        };
+
+        let reader: web_sys::ReadableStreamDefaultReader = body.get_reader().unchecked_into();
+        let mut data = Vec::with_capacity(total.unwrap_or(0) as usize);
+        loop {
+            // each read gives { done, value }, where value is the next chunk as a Uint8Array
+            let result = JsFuture::from(reader.read()).await.map_err(js_error)?;
+            let done = js_sys::Reflect::get(&result, &"done".into()).map_err(js_error)?;
+            if done.as_bool().unwrap_or(true) {
+                break;
+            }
+            let chunk = js_sys::Reflect::get(&result, &"value".into()).map_err(js_error)?;
+            data.extend(js_sys::Uint8Array::new(&chunk).to_vec());
+            on_progress(Progress {
+                loaded: data.len() as u64,
+                total,
+            });
+        }
+        Ok(data)
+    }
+
+    fn not_preloaded(path: &str) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "{} wasn't preloaded, and the web has no files to read",
+                path
+            ),
+        )
+    }
+
+    pub fn read(path: &str) -> std::io::Result<Vec<u8>> {
+        Err(not_preloaded(path))
+    }
+
+    pub fn open(path: &str) -> std::io::Result<Box<dyn BufRead>> {
+        Err(not_preloaded(path))
+    }
+}
@@ -0,0 +1,453 @@
+// bloom as a post process: whatever is brighter than the threshold, like emissive materials
+// (Ke and map_Ke), glows into its surroundings. the frame is drawn into a source texture, its
+// bright part is downsampled through a chain of half sized levels and added back up them, and
+// the composite adds the result over the frame wherever it would have gone. the frame is low
+// dynamic range, so the threshold sits just under white
+
+use crate::texture;
+
+pub const DEFAULT_THRESHOLD: f32 = 0.8;
+pub const DEFAULT_KNEE: f32 = 0.2;
+pub const DEFAULT_INTENSITY: f32 = 0.6;
+// from half size down to a 32nd, wide enough for a soft glow
+const LEVEL_COUNT: u32 = 5;
+// the chain is blurred and summed, so it keeps values above 1 until the composite
+const CHAIN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+pub struct Bloom {
+    uniform: BloomUniform,
+    uniform_buffer: wgpu::Buffer,
+    // what the frame is drawn into before the passes
+    pub source_view: wgpu::TextureView,
+    // each level of the chain, half the size of the one before
+    level_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    pass_layout: wgpu::BindGroupLayout,
+    composite_layout: wgpu::BindGroupLayout,
+    // the first reads the source, each later one the level before
+    downsample_bind_groups: Vec<wgpu::BindGroup>,
+    // reads level i + 1 to add onto level i
+    upsample_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_group: wgpu::BindGroup,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl Bloom {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let uniform = BloomUniform {
+            threshold: DEFAULT_THRESHOLD,
+            knee: DEFAULT_KNEE,
+            intensity: DEFAULT_INTENSITY,
+            _padding: 0.0,
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("bloom uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let sampler_entry = wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let pass_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom pass bind group layout"),
+            entries: &[uniform_entry, texture_entry(1), sampler_entry],
+        });
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom composite bind group layout"),
+            entries: &[
+                uniform_entry,
+                // the frame
+                texture_entry(1),
+                sampler_entry,
+                // the top of the chain
+                texture_entry(3),
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/bloom.wgsl"));
+        let create_pipeline = |label, layout, entry_point, format, blend| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                immediate_size: 0,
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vertex_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                // a single triangle covering the screen, generated in the vertex shader
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let prefilter_pipeline = create_pipeline(
+            "bloom prefilter pipeline",
+            &pass_layout,
+            "prefilter_main",
+            CHAIN_FORMAT,
+            wgpu::BlendState::REPLACE,
+        );
+        let downsample_pipeline = create_pipeline(
+            "bloom downsample pipeline",
+            &pass_layout,
+            "downsample_main",
+            CHAIN_FORMAT,
+            wgpu::BlendState::REPLACE,
+        );
+        let upsample_pipeline = create_pipeline(
+            "bloom upsample pipeline",
+            &pass_layout,
+            "upsample_main",
+            CHAIN_FORMAT,
+            additive,
+        );
+        let composite_pipeline = create_pipeline(
+            "bloom composite pipeline",
+            &composite_layout,
+            "composite_main",
+            config.format,
+            wgpu::BlendState::REPLACE,
+        );
+
+        let (source_view, level_views) = Self::create_views(device, config);
+        let (downsample_bind_groups, upsample_bind_groups, composite_bind_group) =
+            Self::create_bind_groups(
+                device,
+                &pass_layout,
+                &composite_layout,
+                &uniform_buffer,
+                &source_view,
+                &level_views,
+                &sampler,
+            );
+
+        Self {
+            uniform,
+            uniform_buffer,
+            source_view,
+            level_views,
+            sampler,
+            pass_layout,
+            composite_layout,
+            downsample_bind_groups,
+            upsample_bind_groups,
+            composite_bind_group,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    // the source matching the surface, and the chain's levels as views of one mipmapped texture
+    fn create_views(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (wgpu::TextureView, Vec<wgpu::TextureView>) {
+        let source_view =
+            texture::Texture::builder("bloom source texture", config.width, config.height)
+                .format(config.format)
+                .usage(
+                    wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                )
+                .build(device)
+                .view;
+
+        let width = (config.width / 2).max(1);
+        let height = (config.height / 2).max(1);
+        let level_count = LEVEL_COUNT.min(width.min(height).ilog2() + 1);
+        let chain = texture::Texture::builder("bloom chain", width, height)
+            .format(CHAIN_FORMAT)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .mip_levels(level_count)
+            .build(device);
+        let level_views = (0..level_count)
+            .map(|level| {
+                chain.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("bloom chain level"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        (source_view, level_views)
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        pass_layout: &wgpu::BindGroupLayout,
+        composite_layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        source_view: &wgpu::TextureView,
+        level_views: &[wgpu::TextureView],
+        sampler: &wgpu::Sampler,
+    ) -> (Vec<wgpu::BindGroup>, Vec<wgpu::BindGroup>, wgpu::BindGroup) {
+        let pass_bind_group = |label, input| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: pass_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            })
+        };
+
+        let downsample_bind_groups = std::iter::once(source_view)
+            .chain(&level_views[..level_views.len() - 1])
+            .map(|input| pass_bind_group("bloom downsample bind group", input))
+            .collect();
+        let upsample_bind_groups = level_views[1..]
+            .iter()
+            .map(|input| pass_bind_group("bloom upsample bind group", input))
+            .collect();
+
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom composite bind group"),
+            layout: composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&level_views[0]),
+                },
+            ],
+        });
+
+        (
+            downsample_bind_groups,
+            upsample_bind_groups,
+            composite_bind_group,
+        )
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.source_view, self.level_views) = Self::create_views(device, config);
+        (
+            self.downsample_bind_groups,
+            self.upsample_bind_groups,
+            self.composite_bind_group,
+        ) = Self::create_bind_groups(
+            device,
+            &self.pass_layout,
+            &self.composite_layout,
+            &self.uniform_buffer,
+            &self.source_view,
+            &self.level_views,
+            &self.sampler,
+        );
+    }
+
+    // the brightness, as the largest channel, where bloom starts
+    pub fn set_threshold(&mut self, threshold: f32, queue: &wgpu::Queue) {
+        self.uniform.threshold = threshold.max(0.0);
+        self.write_uniform(queue);
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32, queue: &wgpu::Queue) {
+        self.uniform.intensity = intensity.max(0.0);
+        self.write_uniform(queue);
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // blooms the source into the target, replacing what was there
+    pub fn encode(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) {
+        command_encoder.push_debug_group("bloom");
+
+        for (level, (view, bind_group)) in self
+            .level_views
+            .iter()
+            .zip(&self.downsample_bind_groups)
+            .enumerate()
+        {
+            let pipeline = if level == 0 {
+                &self.prefilter_pipeline
+            } else {
+                &self.downsample_pipeline
+            };
+            Self::encode_pass(
+                command_encoder,
+                "bloom downsample pass",
+                view,
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                pipeline,
+                bind_group,
+            );
+        }
+
+        // from the smallest level back up, each adding itself onto the one above
+        for (view, bind_group) in self
+            .level_views
+            .iter()
+            .zip(&self.upsample_bind_groups)
+            .rev()
+        {
+            Self::encode_pass(
+                command_encoder,
+                "bloom upsample pass",
+                view,
+                wgpu::LoadOp::Load,
+                &self.upsample_pipeline,
+                bind_group,
+            );
+        }
+
+        Self::encode_pass(
+            command_encoder,
+            "bloom composite pass",
+            target_view,
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            &self.composite_pipeline,
+            &self.composite_bind_group,
+        );
+
+        command_encoder.pop_debug_group();
+    }
+
+    fn encode_pass(
+        command_encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        target_view: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn describe(&self) -> &'static str {
+        "[BLOOM]"
+    }
+}
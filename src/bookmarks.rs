@@ -0,0 +1,443 @@
+// numbered camera viewpoints for coming back to exactly the same view, eg for before and after
+// comparisons. ctrl and a number key stores the camera in that slot, and alt and the number
+// flies back to it. the slots are kept in a small json file next to where the viewer was
+// started from, so they survive restarts, eg
+//
+//     {"bookmarks": [{"slot": 1, "position": [0, 2, 8], "yaw": -90, "pitch": -10, "fov": 90}]}
+//
+// with the angles in degrees and the fov taken the way camera::Projection::set_fov takes it.
+//
+// a camera path is recorded the same way, as poses sampled while flying around, and played back
+// along a catmull-rom spline through them. Y starts and stops recording and Z starts and stops
+// playing, and `set path.fixed_step 1` plays it at one video frame per frame drawn, for
+// recording. the last path recorded is kept in a file of its own, eg
+//
+//     {"path": [{"time": 0, "position": [0, 2, 8], "yaw": -90, "pitch": -10, "fov": 90}, ...]}
+
+use cgmath::{Angle, Deg, Point3, Rad};
+
+use crate::{camera, json::JsonValue};
+
+pub const SLOT_COUNT: usize = 9;
+// how long flying to a bookmark takes
+const TRANSITION_SECONDS: f32 = 0.6;
+// how often a pose is taken while recording a path, in seconds
+const PATH_SAMPLE_INTERVAL: f32 = 0.1;
+
+// everything needed to put the camera back where it was
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub fov: f32,
+}
+
+impl CameraPose {
+    pub fn new(camera: &camera::Camera, projection: &camera::Projection) -> Self {
+        Self {
+            position: camera.position,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fov: projection.fov(),
+        }
+    }
+
+    pub fn apply(&self, camera: &mut camera::Camera, projection: &mut camera::Projection) {
+        camera.position = self.position;
+        camera.set_yaw_pitch(self.yaw, self.pitch);
+        projection.set_fov(self.fov);
+    }
+
+    // t from 0 at self to 1 at other. the yaw goes the short way round
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let yaw_change = (other.yaw - self.yaw).normalize_signed();
+        Self {
+            position: self.position + (other.position - self.position) * t,
+            yaw: self.yaw + yaw_change * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            fov: self.fov + (other.fov - self.fov) * t,
+        }
+    }
+
+    fn to_json(self) -> Vec<(String, JsonValue)> {
+        let number = |n: f32| JsonValue::Number(n as f64);
+        let position = [self.position.x, self.position.y, self.position.z];
+        vec![
+            (
+                "position".into(),
+                JsonValue::Array(position.map(number).to_vec()),
+            ),
+            ("yaw".into(), number(Deg::from(self.yaw).0)),
+            ("pitch".into(), number(Deg::from(self.pitch).0)),
+            ("fov".into(), number(self.fov)),
+        ]
+    }
+
+    fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let number = |key: &str| {
+            json.get(key)
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as f32)
+                .ok_or_else(|| format!("{} should be a number", key))
+        };
+        Ok(Self {
+            position: json
+                .get("position")
+                .and_then(JsonValue::as_f32_array::<3>)
+                .ok_or("position should be [x, y, z]")?
+                .into(),
+            yaw: Deg(number("yaw")?).into(),
+            pitch: Deg(number("pitch")?).into(),
+            fov: number("fov")?,
+        })
+    }
+}
+
+pub struct CameraBookmarks {
+    slots: [Option<CameraPose>; SLOT_COUNT],
+    // where the slots are saved whenever one is stored, or None to keep them for this run only
+    path: Option<String>,
+}
+
+impl CameraBookmarks {
+    // the bookmarks saved at the path before, or none if there's no file there yet
+    pub fn load(path: Option<&str>) -> Self {
+        let mut bookmarks = Self {
+            slots: [None; SLOT_COUNT],
+            path: path.map(str::to_string),
+        };
+        let Some(path) = path else {
+            return bookmarks;
+        };
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return bookmarks,
+            Err(e) => {
+                log::warn!("could not read camera bookmarks {}: {}", path, e);
+                return bookmarks;
+            }
+        };
+        if let Err(e) = bookmarks.read(&text) {
+            log::warn!("camera bookmarks {}: {}", path, e);
+        }
+        bookmarks
+    }
+
+    fn read(&mut self, text: &str) -> Result<(), String> {
+        let json = JsonValue::parse(text).map_err(|e| e.to_string())?;
+        let entries = json
+            .get("bookmarks")
+            .and_then(JsonValue::as_array)
+            .ok_or("bookmarks should be an array")?;
+        for entry in entries {
+            let slot = entry
+                .get("slot")
+                .and_then(JsonValue::as_f64)
+                .filter(|slot| (1.0..=SLOT_COUNT as f64).contains(slot))
+                .ok_or_else(|| format!("every bookmark needs a slot from 1 to {}", SLOT_COUNT))?;
+            self.slots[slot as usize - 1] = Some(CameraPose::from_json(entry)?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, slot: usize) -> Option<CameraPose> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    // stores the pose in the slot, counted from 0, and saves every slot to the file
+    pub fn set(&mut self, slot: usize, pose: CameraPose) {
+        let Some(stored) = self.slots.get_mut(slot) else {
+            return;
+        };
+        *stored = Some(pose);
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = std::fs::write(path, self.to_text()) {
+            log::warn!("could not save camera bookmarks {}: {}", path, e);
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let entries = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, pose)| {
+                let mut fields = vec![("slot".to_string(), JsonValue::Number(slot as f64 + 1.0))];
+                fields.extend(pose.as_ref()?.to_json());
+                Some(JsonValue::Object(fields))
+            })
+            .collect();
+        format!(
+            "{}\n",
+            JsonValue::Object(vec![("bookmarks".into(), JsonValue::Array(entries))])
+        )
+    }
+}
+
+// the camera easing from one pose to another
+pub struct CameraTransition {
+    from: CameraPose,
+    to: CameraPose,
+    elapsed: f32,
+}
+
+impl CameraTransition {
+    pub fn new(from: CameraPose, to: CameraPose) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: 0.0,
+        }
+    }
+
+    // moves the transition on by dt seconds, returning the pose to show and whether it has
+    // arrived
+    pub fn advance(&mut self, dt: f32) -> (CameraPose, bool) {
+        self.elapsed += dt;
+        let t = (self.elapsed / TRANSITION_SECONDS).min(1.0);
+        // smoothstep, so it sets off and arrives gently
+        let eased = t * t * (3.0 - 2.0 * t);
+        (self.from.lerp(&self.to, eased), t >= 1.0)
+    }
+}
+
+// poses in time order, with their times in seconds from the start
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    keys: Vec<(f32, CameraPose)>,
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |&(time, _)| time)
+    }
+
+    // the pose on a catmull-rom spline through the keys, which passes through every key and
+    // leaves the ends straight. before the first key or after the last it stays there
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let last = self.keys.len().checked_sub(1)?;
+        let next = self.keys.partition_point(|&(key_time, _)| key_time <= time);
+        if next == 0 {
+            return Some(self.keys[0].1);
+        }
+        if next > last {
+            return Some(self.keys[last].1);
+        }
+
+        let (start_time, _) = self.keys[next - 1];
+        let (end_time, _) = self.keys[next];
+        let t = (time - start_time) / (end_time - start_time).max(f32::EPSILON);
+        let [p0, p1, p2, p3] = [
+            next as isize - 2,
+            next as isize - 1,
+            next as isize,
+            next as isize + 1,
+        ]
+        .map(|i| self.keys[i.clamp(0, last as isize) as usize].1);
+        let spline =
+            |value: fn(&CameraPose) -> f32| catmull_rom([&p0, &p1, &p2, &p3].map(value), t);
+        Some(CameraPose {
+            position: Point3::new(
+                spline(|pose| pose.position.x),
+                spline(|pose| pose.position.y),
+                spline(|pose| pose.position.z),
+            ),
+            yaw: Rad(spline(|pose| pose.yaw.0)),
+            pitch: Rad(spline(|pose| pose.pitch.0)),
+            fov: spline(|pose| pose.fov),
+        })
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read camera path {}: {}", path, e))?;
+        Self::read(&text).map_err(|e| anyhow::anyhow!("camera path {}: {}", path, e))
+    }
+
+    fn read(text: &str) -> Result<Self, String> {
+        let json = JsonValue::parse(text).map_err(|e| e.to_string())?;
+        let keys = json
+            .get("path")
+            .and_then(JsonValue::as_array)
+            .ok_or("path should be an array")?
+            .iter()
+            .map(|key| -> Result<(f32, CameraPose), String> {
+                let time = key
+                    .get("time")
+                    .and_then(JsonValue::as_f64)
+                    .ok_or("every pose needs a time")?;
+                Ok((time as f32, CameraPose::from_json(key)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if keys.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+            return Err("the poses should be in time order".into());
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        let keys = self
+            .keys
+            .iter()
+            .map(|&(time, pose)| {
+                let mut fields = vec![("time".to_string(), JsonValue::Number(time as f64))];
+                fields.extend(pose.to_json());
+                JsonValue::Object(fields)
+            })
+            .collect();
+        let json = JsonValue::Object(vec![("path".into(), JsonValue::Array(keys))]);
+        std::fs::write(path, format!("{}\n", json))
+            .map_err(|e| anyhow::anyhow!("could not write camera path {}: {}", path, e))
+    }
+}
+
+enum PathState {
+    Idle,
+    // the path so far, the time since recording started and since the last pose was taken
+    Recording {
+        path: CameraPath,
+        time: f32,
+        since_key: f32,
+    },
+    Playing {
+        time: f32,
+    },
+}
+
+// records and plays back the camera path, keeping the last one recorded
+pub struct CameraPathPlayer {
+    path: CameraPath,
+    state: PathState,
+    // where the path is kept between runs, or None to forget it on exit
+    file: Option<String>,
+    // plays back at exactly one video frame per frame drawn, however long they take, so a
+    // recording of the playback comes out the same every time
+    pub fixed_step: bool,
+}
+
+impl CameraPathPlayer {
+    // with the path saved at the file before, if there is one
+    pub fn load(file: Option<&str>) -> Self {
+        let path = match file {
+            Some(file) if std::path::Path::new(file).exists() => CameraPath::load(file)
+                .inspect_err(|e| log::warn!("{:#}", e))
+                .unwrap_or_default(),
+            _ => CameraPath::default(),
+        };
+        Self {
+            path,
+            state: PathState::Idle,
+            file: file.map(str::to_string),
+            fixed_step: false,
+        }
+    }
+
+    // starts a new path from the pose, or finishes the one being recorded, keeping and saving it.
+    // returns what happened
+    pub fn toggle_recording(&mut self, pose: CameraPose) -> String {
+        match std::mem::replace(&mut self.state, PathState::Idle) {
+            PathState::Recording { mut path, time, .. } => {
+                path.keys.push((time, pose));
+                self.path = path;
+                if let Some(file) = &self.file
+                    && let Err(e) = self.path.save(file)
+                {
+                    log::warn!("{:#}", e);
+                }
+                format!("recorded a {:.1}s camera path", self.path.duration())
+            }
+            _ => {
+                self.state = PathState::Recording {
+                    path: CameraPath {
+                        keys: vec![(0.0, pose)],
+                    },
+                    time: 0.0,
+                    since_key: 0.0,
+                };
+                "recording the camera path".to_string()
+            }
+        }
+    }
+
+    // plays the path from the start, or stops playing it. returns what happened
+    pub fn toggle_playback(&mut self) -> String {
+        match self.state {
+            PathState::Playing { .. } => {
+                self.state = PathState::Idle;
+                "stopped the camera path".to_string()
+            }
+            _ if self.path.keys.is_empty() => {
+                "there is no camera path, press Y to record one".to_string()
+            }
+            _ => {
+                self.state = PathState::Playing { time: 0.0 };
+                format!("playing a {:.1}s camera path", self.path.duration())
+            }
+        }
+    }
+
+    // moves recording or playback on by dt seconds. while recording, the pose is the camera's,
+    // and while playing, returns where the camera should be
+    pub fn advance(&mut self, dt: f32, pose: CameraPose) -> Option<CameraPose> {
+        let step = self.step(dt);
+        match &mut self.state {
+            PathState::Idle => None,
+            PathState::Recording {
+                path,
+                time,
+                since_key,
+            } => {
+                *time += dt;
+                *since_key += dt;
+                if *since_key >= PATH_SAMPLE_INTERVAL {
+                    *since_key = 0.0;
+                    path.keys.push((*time, pose));
+                }
+                None
+            }
+            // the first frame is at the start and the last at the end
+            PathState::Playing { time } => {
+                let pose = self.path.sample(*time);
+                if *time >= self.path.duration() {
+                    self.state = PathState::Idle;
+                } else {
+                    *time = (*time + step).min(self.path.duration());
+                }
+                pose
+            }
+        }
+    }
+
+    fn step(&self, dt: f32) -> f32 {
+        if self.fixed_step {
+            1.0 / crate::recording::VIDEO_FPS as f32
+        } else {
+            dt
+        }
+    }
+
+    // eg [REC PATH 2.5s] or [PATH 1.0/4.2s]
+    pub fn describe(&self) -> String {
+        match &self.state {
+            PathState::Idle => String::new(),
+            PathState::Recording { time, .. } => format!("[REC PATH {:.1}s]", time),
+            PathState::Playing { time } => {
+                format!("[PATH {:.1}/{:.1}s]", time, self.path.duration())
+            }
+        }
+    }
+}
+
+// the uniform catmull-rom spline between values[1] at t = 0 and values[2] at t = 1
+fn catmull_rom(values: [f32; 4], t: f32) -> f32 {
+    let [p0, p1, p2, p3] = values;
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
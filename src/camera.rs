@@ -1,7 +1,15 @@
 use std::time::Duration;
 
-use cgmath::{Deg, InnerSpace, Matrix4, Point3, Rad, Vector3, Vector4, perspective};
-use winit::{event::MouseScrollDelta, keyboard::KeyCode};
+use cgmath::{
+    Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation, Rotation3,
+    SquareMatrix, Vector3, Vector4, perspective,
+};
+use winit::event::MouseScrollDelta;
+
+use crate::{
+    input::Action,
+    math::{Aabb, Ray, Transform},
+};
 
 // wgpu expects NDC where x and y are in [-1, 1] and z in [0, 1]
 // whereas opengl has z in [-1, 1]
@@ -12,57 +20,382 @@ pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::from_cols(
     Vector4::new(0.0, 0.0, 0.5, 1.0),
 );
 
+// how depth is stored. reversed puts the near plane at 1 and the far plane, at infinity, at 0,
+// which matches the float precision of the depth buffer to the precision perspective leaves,
+// so distant surfaces in large scenes stop z-fighting
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DepthMode {
+    Standard,
+    Reversed,
+}
+
+impl DepthMode {
+    // the depth of the far plane, which the depth buffer is cleared to
+    pub fn clear_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::Reversed => 0.0,
+        }
+    }
+
+    // how a fragment is tested against the depth buffer, passing when it's closer
+    pub fn compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => wgpu::CompareFunction::Less,
+            DepthMode::Reversed => wgpu::CompareFunction::Greater,
+        }
+    }
+
+    // the depth of the near plane in ndc
+    pub fn near_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 0.0,
+            DepthMode::Reversed => 1.0,
+        }
+    }
+}
+
+// the fov is kept as it's set, and divided by the aspect ratio when it's used, rather than the
+// vertical fov being kept and recalculated on every resize, which drifted as rounding built up
 pub struct Projection {
     aspect_ratio: f32,
-    fov_vertical: f32,
+    fov: f32,
     z_plane_near: f32,
+    // ignored when the depth is reversed, which has no far plane
     z_plane_far: f32,
+    depth_mode: DepthMode,
 }
 
 impl Projection {
-    pub fn new(width: u32, height: u32, fov: f32, z_plane_near: f32, z_plane_far: f32) -> Self {
+    pub fn new(
+        width: u32,
+        height: u32,
+        fov: f32,
+        z_plane_near: f32,
+        z_plane_far: f32,
+        depth_mode: DepthMode,
+    ) -> Self {
         Self {
             aspect_ratio: width as f32 / height as f32,
-            fov_vertical: fov / (width as f32 / height as f32),
+            fov,
             z_plane_near,
             z_plane_far,
+            depth_mode,
         }
     }
 
+    // the narrowest and widest the vertical fov goes, in degrees
+    const MIN_FOV_VERTICAL: f32 = 1.0;
+    const MAX_FOV_VERTICAL: f32 = 170.0;
+    // the height of a 35mm film frame, which focal lengths are usually given for
+    const FILM_HEIGHT: f32 = 24.0;
+    // the closest the near plane comes, and how close it comes compared to the far plane when
+    // the camera is inside what it's fitted to, past which depth precision runs out
+    const MIN_Z_PLANE_NEAR: f32 = 0.001;
+    const MIN_NEAR_FAR_RATIO: f32 = 0.0005;
+
+    // takes the fov the same way as new, before it is divided by the aspect ratio
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = self.clamp_fov(fov);
+    }
+
+    // the nearest fov to the one given which set_fov keeps as it is
+    pub fn clamp_fov(&self, fov: f32) -> f32 {
+        fov.clamp(
+            Self::MIN_FOV_VERTICAL * self.aspect_ratio,
+            Self::MAX_FOV_VERTICAL * self.aspect_ratio,
+        )
+    }
+
+    // the fov as set_fov takes it
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    // in degrees
+    pub fn fov_vertical(&self) -> f32 {
+        (self.fov / self.aspect_ratio).clamp(Self::MIN_FOV_VERTICAL, Self::MAX_FOV_VERTICAL)
+    }
+
+    // the 35mm equivalent focal length of the vertical fov, in millimetres
+    pub fn focal_length(&self) -> f32 {
+        Self::FILM_HEIGHT / 2.0 / (Rad::from(Deg(self.fov_vertical())).0 / 2.0).tan()
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
-        let fov = self.fov_vertical * self.aspect_ratio;
         self.aspect_ratio = width as f32 / height as f32;
-        self.fov_vertical = fov / self.aspect_ratio;
+    }
+
+    // eg [FOV 45° 29mm]
+    pub fn describe(&self) -> String {
+        format!(
+            "[FOV {:.0}° {:.0}mm]",
+            self.fov_vertical(),
+            self.focal_length()
+        )
+    }
+
+    // a square 90 degree projection with the same depth range, for rendering cube faces
+    pub fn cube_face(&self) -> Projection {
+        Projection {
+            aspect_ratio: 1.0,
+            fov: 90.0,
+            z_plane_near: self.z_plane_near,
+            z_plane_far: self.z_plane_far,
+            depth_mode: self.depth_mode,
+        }
+    }
+
+    // the world space corners of the slice of the view frustum between two view distances,
+    // the near face's four first. either depth mode has the same frustum, so this works for both
+    pub fn frustum_corners(&self, camera: &Camera, near: f32, far: f32) -> [Point3<f32>; 8] {
+        let transform = camera.transform();
+        let tan_half_fov = (Rad::from(Deg(self.fov_vertical())).0 / 2.0).tan();
+
+        let mut corners = [Point3::new(0.0, 0.0, 0.0); 8];
+        for (i, distance) in [near, near, near, near, far, far, far, far]
+            .into_iter()
+            .enumerate()
+        {
+            let half_height = distance * tan_half_fov;
+            let half_width = half_height * self.aspect_ratio;
+            let x = if i % 2 == 0 { -half_width } else { half_width };
+            let y = if i % 4 < 2 { -half_height } else { half_height };
+            // the camera looks along its local -z
+            let corner = transform.transform_point(Vector3::new(x, y, -distance));
+            corners[i] = Point3::new(corner.x, corner.y, corner.z);
+        }
+        corners
+    }
+
+    pub fn z_plane_near(&self) -> f32 {
+        self.z_plane_near
+    }
+
+    pub fn z_plane_far(&self) -> f32 {
+        self.z_plane_far
+    }
+
+    // the far plane is kept beyond the near one
+    pub fn set_z_planes(&mut self, near: f32, far: f32) {
+        self.z_plane_near = near.max(Self::MIN_Z_PLANE_NEAR);
+        self.z_plane_far = far.max(self.z_plane_near * 2.0);
+    }
+
+    // pulls the planes in as close as they go around the bounds, eg the scene's, so none of the
+    // depth range is spent on empty space in front of or behind it. leaves them alone when the
+    // bounds are all behind the camera
+    pub fn fit_z_planes(&mut self, camera: &Camera, bounds: &Aabb) {
+        let view = camera.transform().inverse();
+        // the camera looks along its local -z
+        let (nearest, farthest) = bounds
+            .corners()
+            .map(|corner| -view.transform_point(corner).z)
+            .into_iter()
+            .fold(
+                (f32::INFINITY, f32::NEG_INFINITY),
+                |(nearest, farthest), depth| (nearest.min(depth), farthest.max(depth)),
+            );
+        if farthest <= 0.0 {
+            return;
+        }
+        // a little room either side, so surfaces on the box don't flicker at the planes
+        let far = farthest * 1.01;
+        let near = (nearest * 0.99).max(far * Self::MIN_NEAR_FAR_RATIO);
+        self.set_z_planes(near, far);
+    }
+
+    // eg [Z 0.5-40]
+    pub fn describe_z_planes(&self) -> String {
+        match self.depth_mode {
+            DepthMode::Standard => format!("[Z {:.2}-{:.1}]", self.z_plane_near, self.z_plane_far),
+            DepthMode::Reversed => format!("[Z {:.2}-inf]", self.z_plane_near),
+        }
+    }
+
+    // an orthographic projection at the same aspect ratio and in the same depth mode, which
+    // shows at least `radius` either side of the middle both across and up. unlike the
+    // perspective one, even a reversed depth has a far plane here
+    pub fn orthographic_matrix(&self, radius: f32, near: f32, far: f32) -> Matrix4<f32> {
+        let half_height = radius * (1.0 / self.aspect_ratio).max(1.0);
+        let half_width = half_height * self.aspect_ratio;
+        // the depth is linear in view distance d, which is -z in view space
+        let (depth_scale, depth_offset) = match self.depth_mode {
+            // d = near at 0, d = far at 1
+            DepthMode::Standard => (-1.0 / (far - near), -near / (far - near)),
+            // d = near at 1, d = far at 0
+            DepthMode::Reversed => (1.0 / (far - near), far / (far - near)),
+        };
+        Matrix4::from_cols(
+            Vector4::new(1.0 / half_width, 0.0, 0.0, 0.0),
+            Vector4::new(0.0, 1.0 / half_height, 0.0, 0.0),
+            Vector4::new(0.0, 0.0, depth_scale, 0.0),
+            Vector4::new(0.0, 0.0, depth_offset, 1.0),
+        )
     }
 
     pub fn perspective_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX
-            * perspective(
-                Deg(self.fov_vertical),
-                self.aspect_ratio,
-                self.z_plane_near,
-                self.z_plane_far,
-            )
+        match self.depth_mode {
+            DepthMode::Standard => {
+                OPENGL_TO_WGPU_MATRIX
+                    * perspective(
+                        Deg(self.fov_vertical()),
+                        self.aspect_ratio,
+                        self.z_plane_near,
+                        self.z_plane_far,
+                    )
+            }
+            // clip z is the near distance and w the view distance, so depth is near / distance:
+            // 1 at the near plane, falling towards 0 at infinity
+            DepthMode::Reversed => {
+                let focal_length = 1.0 / (Rad::from(Deg(self.fov_vertical())).0 / 2.0).tan();
+                Matrix4::from_cols(
+                    Vector4::new(focal_length / self.aspect_ratio, 0.0, 0.0, 0.0),
+                    Vector4::new(0.0, focal_length, 0.0, 0.0),
+                    Vector4::new(0.0, 0.0, 0.0, -1.0),
+                    Vector4::new(0.0, 0.0, self.z_plane_near, 0.0),
+                )
+            }
+        }
     }
 }
 
+// looks along its yaw and pitch with the horizon level, or tilted by its roll. for looking at
+// things from any angle it can be switched to turning freely about its own axes instead, kept
+// as a quaternion, which can go straight over the top. yaw and pitch follow along either way, so
+// whatever reads them, eg bookmarks, still gets where the camera is looking
 #[derive(Debug)]
 pub struct Camera {
     pub position: Point3<f32>,
     pub yaw: Rad<f32>,
     pub pitch: Rad<f32>,
+    // about the view direction, clockwise as seen from behind the camera
+    pub roll: Rad<f32>,
+    // when set, the camera is turned by this rather than by yaw, pitch and roll
+    pub orientation: Option<Quaternion<f32>>,
 }
 
 impl Camera {
-    pub fn view_matrix(&self) -> Matrix4<f32> {
+    // the most pitch a level camera has, just short of straight up or down where its yaw would
+    // stop meaning anything
+    const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.0001;
+
+    pub fn direction(&self) -> Vector3<f32> {
+        if let Some(orientation) = self.orientation {
+            return orientation.rotate_vector(-Vector3::unit_z());
+        }
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
 
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        )
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    // the camera's placement in the world; the view matrix is its inverse
+    pub fn transform(&self) -> Transform {
+        if let Some(orientation) = self.orientation {
+            return Transform::new(
+                self.position.to_vec(),
+                orientation,
+                Vector3::new(1.0, 1.0, 1.0),
+            );
+        }
+        let direction = self.direction();
+        let up = Quaternion::from_axis_angle(direction, self.roll).rotate_vector(Vector3::unit_y());
+        Transform::look_to(self.position, direction, up)
+    }
+
+    // the camera moved sideways along its right vector, eg to one eye for stereo
+    pub fn offset_sideways(&self, distance: f32) -> Camera {
+        let right = self.transform().right();
+        Camera {
+            position: self.position + right * distance,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            roll: self.roll,
+            orientation: self.orientation,
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.orientation.is_some()
+    }
+
+    // switches between turning freely and keeping to yaw and pitch. going back to yaw and pitch
+    // levels the horizon
+    pub fn set_free(&mut self, free: bool) {
+        if free {
+            self.orientation = Some(self.transform().rotation());
+        } else {
+            self.level_horizon();
+            self.orientation = None;
+        }
+    }
+
+    // takes out any roll, keeping the direction the camera looks in, apart from easing off
+    // from looking straight up or down. a free camera stays free
+    pub fn level_horizon(&mut self) {
+        self.sync_yaw_pitch();
+        self.roll = Rad(0.0);
+        if self.orientation.is_some() {
+            self.orientation = None;
+            self.orientation = Some(self.transform().rotation());
+        }
+    }
+
+    // points a level camera, or a free one without any roll
+    pub fn set_yaw_pitch(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = Rad(0.0);
+        if self.orientation.is_some() {
+            self.orientation = None;
+            self.orientation = Some(self.transform().rotation());
+        }
+    }
+
+    // the yaw and pitch of the way the camera looks, after it has turned freely
+    fn sync_yaw_pitch(&mut self) {
+        let direction = self.direction();
+        self.yaw = Rad(direction.z.atan2(direction.x));
+        let pitch = direction.y.clamp(-1.0, 1.0).asin();
+        self.pitch = Rad(pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH));
+    }
+
+    // eg [FREE CAM] or [ROLL 15°], or nothing for a level camera
+    pub fn describe(&self) -> String {
+        if self.is_free() {
+            "[FREE CAM]".to_string()
+        } else if self.roll.0.abs() > 1e-3 {
+            format!("[ROLL {:.0}°]", Deg::from(self.roll).0)
+        } else {
+            String::new()
+        }
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        self.transform().inverse().matrix()
+    }
+
+    // the world space ray under a point on the screen, given in pixels from the top left
+    pub fn picking_ray(
+        &self,
+        projection: &Projection,
+        screen_position: (f32, f32),
+        screen_size: (u32, u32),
+    ) -> Ray {
+        let ndc_x = screen_position.0 / screen_size.0 as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - screen_position.1 / screen_size.1 as f32 * 2.0;
+
+        // unproject the point on the near plane, since a reversed depth's far plane is at
+        // infinity; the ray starts at the camera itself
+        let inverse_view_proj = (projection.perspective_matrix() * self.view_matrix())
+            .invert()
+            .unwrap_or(Matrix4::identity());
+        let near_depth = projection.depth_mode.near_depth();
+        let near = inverse_view_proj * Vector4::new(ndc_x, ndc_y, near_depth, 1.0);
+        let near = Point3::from_homogeneous(near);
+
+        Ray::new(self.position, near - self.position)
     }
 
     pub fn new<V: Into<Point3<f32>>, Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(
@@ -74,10 +407,18 @@ impl Camera {
             position: position.into(),
             yaw: yaw.into(),
             pitch: pitch.into(),
+            roll: Rad(0.0),
+            orientation: None,
         }
     }
 }
 
+// the keys and mouse don't move the camera directly: its velocity speeds up towards what the
+// keys ask for and slows down again once they're let go, and the mouse's turning is smoothed
+// over a few frames, so motion eases in and out rather than starting and stopping dead. left
+// shift is already down, so ctrl is the one which speeds it up. q and e are taken, so comma and
+// period roll the camera. page up and down zoom, as does scrolling with alt held, easing the fov
+// towards where they leave it
 pub struct CameraController {
     amount_left: f32,
     amount_right: f32,
@@ -88,13 +429,49 @@ pub struct CameraController {
     amount_yaw: f32,
     amount_pitch: f32,
     amount_scroll: f32,
+    amount_roll_left: f32,
+    amount_roll_right: f32,
+    amount_zoom_in: f32,
+    amount_zoom_out: f32,
+    // scrolling zooms rather than moving while alt is held
+    is_zooming: bool,
+    amount_scroll_zoom: f32,
+    // the fov the projection is easing towards, in the units of Projection::fov, until it's
+    // reached
+    target_fov: Option<f32>,
+    is_fast: bool,
     speed: f32,
     sensitivity: f32,
+    // in world space, per second
+    velocity: Vector3<f32>,
+    // the mouse's turning after smoothing, in the same units as amount_yaw and amount_pitch
+    smoothed_yaw: f32,
+    smoothed_pitch: f32,
+    // how quickly the velocity closes the gap to the keys' speed while they're held, and to
+    // rest after they're let go, as rates per second. higher is snappier
+    acceleration: f32,
+    deceleration: f32,
+    // about how long the mouse's turning takes to catch up with it, in seconds. 0 turns
+    // exactly with the mouse
+    mouse_smoothing: f32,
 }
 
-
 impl CameraController {
     const SAFE_FRAC_PI_2: f32 = std::f32::consts::FRAC_PI_2 - 0.0001;
+    // how much faster the camera moves while ctrl is held
+    const FAST_MULTIPLIER: f32 = 4.0;
+    // radians per second
+    const ROLL_SPEED: f32 = 1.2;
+    // how much the fov narrows per second while zooming in with the keys, as a factor, and for
+    // each line scrolled
+    const ZOOM_SPEED: f32 = 2.0;
+    const ZOOM_STEP: f32 = 1.15;
+    // the rate the fov closes in on its target, as a rate per second
+    const ZOOM_RATE: f32 = 12.0;
+    const DEFAULT_ACCELERATION: f32 = 10.0;
+    const DEFAULT_DECELERATION: f32 = 6.0;
+    const DEFAULT_MOUSE_SMOOTHING: f32 = 0.04;
+
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
             amount_left: 0.0,
@@ -106,34 +483,87 @@ impl CameraController {
             amount_yaw: 0.0,
             amount_pitch: 0.0,
             amount_scroll: 0.0,
+            amount_roll_left: 0.0,
+            amount_roll_right: 0.0,
+            amount_zoom_in: 0.0,
+            amount_zoom_out: 0.0,
+            is_zooming: false,
+            amount_scroll_zoom: 0.0,
+            target_fov: None,
+            is_fast: false,
             speed,
             sensitivity,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            smoothed_yaw: 0.0,
+            smoothed_pitch: 0.0,
+            acceleration: Self::DEFAULT_ACCELERATION,
+            deceleration: Self::DEFAULT_DECELERATION,
+            mouse_smoothing: Self::DEFAULT_MOUSE_SMOOTHING,
         }
     }
 
-    pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) {
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn set_acceleration(&mut self, acceleration: f32) {
+        self.acceleration = acceleration.max(0.0);
+    }
+
+    pub fn set_deceleration(&mut self, deceleration: f32) {
+        self.deceleration = deceleration.max(0.0);
+    }
+
+    pub fn set_mouse_smoothing(&mut self, seconds: f32) {
+        self.mouse_smoothing = seconds.max(0.0);
+    }
+
+    // the held actions, as they're pressed and released. see input.rs
+    pub fn handle_action(&mut self, action: Action, is_pressed: bool) {
         let amount = if is_pressed {1.0} else {0.0};
 
-        match code {
-            KeyCode::KeyW | KeyCode::ArrowUp => {
+        match action {
+            Action::MoveForward => {
                 self.amount_forward = amount;
             }
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
+            Action::MoveLeft => {
                 self.amount_left = amount;
             }
-            KeyCode::KeyS | KeyCode::ArrowDown => {
+            Action::MoveBackward => {
                 self.amount_backward = amount;
             }
-            KeyCode::KeyD | KeyCode::ArrowRight => {
+            Action::MoveRight => {
                 self.amount_right = amount;
             }
-            KeyCode::Space => {
+            Action::MoveUp => {
                 self.amount_up = amount;
             }
-            KeyCode::ShiftLeft => {
+            Action::MoveDown => {
                 self.amount_down = amount;
             }
-            _ => {},
+            Action::MoveFast => {
+                self.is_fast = is_pressed;
+            }
+            Action::RollLeft => {
+                self.amount_roll_left = amount;
+            }
+            Action::RollRight => {
+                self.amount_roll_right = amount;
+            }
+            Action::ZoomIn => {
+                self.amount_zoom_in = amount;
+            }
+            Action::ZoomOut => {
+                self.amount_zoom_out = amount;
+            }
+            Action::ScrollZoom => {
+                self.is_zooming = is_pressed;
+            }
+            _ => {}
         }
     }
 
@@ -143,7 +573,7 @@ impl CameraController {
     }
 
     pub fn handle_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.amount_scroll = match delta {
+        let amount = match delta {
             MouseScrollDelta::LineDelta(_, amount) => {
                 amount * 1.0
             },
@@ -154,39 +584,110 @@ impl CameraController {
                 *amount as f32
             }
         };
+        if self.is_zooming {
+            self.amount_scroll_zoom += amount;
+        } else {
+            self.amount_scroll = amount;
+        }
+    }
+
+    // zooms the projection with the keys and alt scrolling, the fov easing towards the zoom
+    pub fn update_projection(&mut self, projection: &mut Projection, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        let zoom = self.amount_zoom_in - self.amount_zoom_out;
+        if zoom != 0.0 || self.amount_scroll_zoom != 0.0 {
+            // zooming by factors rather than degrees keeps it as quick narrow as it is wide
+            let factor =
+                Self::ZOOM_SPEED.powf(zoom * dt) * Self::ZOOM_STEP.powf(self.amount_scroll_zoom);
+            let target = self.target_fov.unwrap_or(projection.fov()) / factor;
+            self.target_fov = Some(projection.clamp_fov(target));
+            self.amount_scroll_zoom = 0.0;
+        }
+
+        if let Some(target) = self.target_fov {
+            let fov = projection.fov();
+            if (target - fov).abs() < 0.01 {
+                projection.set_fov(target);
+                self.target_fov = None;
+            } else {
+                projection.set_fov(fov + (target - fov) * approach(Self::ZOOM_RATE, dt));
+            }
+        }
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
 
-        let (sin_yaw, cos_yaw) = camera.yaw.0.sin_cos();
-        // calculate the camera's local forward and right vectors
-        let forward = Vector3::new(cos_yaw, 0.0, sin_yaw).normalize();
-        let right = Vector3::new(-sin_yaw, 0.0, cos_yaw).normalize();
-        
-        // move the camera with wasd
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
-    
-        let (sin_pitch, cos_pitch) = camera.pitch.0.sin_cos();
-        // calculate the vector along the camera's line of sight
-        let eye_direction = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize();
-
-        // move the camera in/out with scrolling
-        camera.position += eye_direction * self.amount_scroll * self.speed * self.sensitivity * dt;
-
-        // move the camera up and down (absolute)
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
-
-        // rotate the camera
-        camera.yaw += Rad(self.amount_yaw) * self.sensitivity * dt;
-        camera.pitch += Rad(self.amount_pitch) * self.sensitivity * dt;
+        // calculate the camera's local forward and right vectors. a level camera moves along
+        // the ground whichever way it's pitched, a free one along where it's looking
+        let (forward, right) = if camera.is_free() {
+            let transform = camera.transform();
+            (transform.forward(), transform.right())
+        } else {
+            let (sin_yaw, cos_yaw) = camera.yaw.0.sin_cos();
+            (
+                Vector3::new(cos_yaw, 0.0, sin_yaw).normalize(),
+                Vector3::new(-sin_yaw, 0.0, cos_yaw).normalize(),
+            )
+        };
+
+        // the velocity wasd and up and down (absolute) ask for, sped up while ctrl is held,
+        // which the camera's velocity eases towards
+        let multiplier = if self.is_fast {
+            Self::FAST_MULTIPLIER
+        } else {
+            1.0
+        };
+        let target = (forward * (self.amount_forward - self.amount_backward)
+            + right * (self.amount_right - self.amount_left)
+            + Vector3::unit_y() * (self.amount_up - self.amount_down))
+            * self.speed
+            * multiplier;
+        let rate = if target.magnitude2() > 0.0 {
+            self.acceleration
+        } else {
+            self.deceleration
+        };
+        self.velocity += (target - self.velocity) * approach(rate, dt);
+        camera.position += self.velocity * dt;
+
+        // move the camera in/out along its line of sight with scrolling
+        camera.position +=
+            camera.direction() * self.amount_scroll * self.speed * self.sensitivity * dt;
+
+        // rotate the camera, following the mouse a little behind
+        let smoothing = if self.mouse_smoothing > 0.0 {
+            approach(1.0 / self.mouse_smoothing, dt)
+        } else {
+            1.0
+        };
+        self.smoothed_yaw += (self.amount_yaw - self.smoothed_yaw) * smoothing;
+        self.smoothed_pitch += (self.amount_pitch - self.smoothed_pitch) * smoothing;
+        let yaw = Rad(self.smoothed_yaw) * self.sensitivity * dt;
+        let pitch = Rad(self.smoothed_pitch) * self.sensitivity * dt;
+        let roll = Rad(self.amount_roll_right - self.amount_roll_left) * Self::ROLL_SPEED * dt;
 
         // mouse amounts are only called on deltas so they need to be reset
         self.amount_scroll = 0.0;
         self.amount_yaw = 0.0;
         self.amount_pitch = 0.0;
 
+        if let Some(orientation) = camera.orientation {
+            // turn about the camera's own axes, which has no top or bottom to stop at. it looks
+            // along its local -z, so rolling clockwise is turning backwards about z
+            let turn = Quaternion::from_angle_y(-yaw)
+                * Quaternion::from_angle_x(pitch)
+                * Quaternion::from_angle_z(-roll);
+            camera.orientation = Some((orientation * turn).normalize());
+            camera.sync_yaw_pitch();
+            return;
+        }
+
+        camera.yaw += yaw;
+        camera.pitch += pitch;
+        camera.roll += roll;
+
         // avoid gimbal lock by constraining pitch
         if camera.pitch < -Rad(Self::SAFE_FRAC_PI_2) {
             camera.pitch = -Rad(Self::SAFE_FRAC_PI_2);
@@ -195,3 +696,9 @@ impl CameraController {
         }
     }
 }
+
+// how much of the gap to a target to close over dt seconds, when closing it at the rate per
+// second. independent of the frame rate, unlike closing a fixed fraction every frame
+fn approach(rate: f32, dt: f32) -> f32 {
+    1.0 - (-rate * dt).exp()
+}
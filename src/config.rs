@@ -0,0 +1,386 @@
+// renderer settings read from a toml file at startup (config.toml unless --config names another),
+// which is written out with the defaults when it's missing so there's something to edit. the
+// command line's --set overrides single entries by their table and key, eg
+//
+//     cargo run -- --set window.vsync false --set bindings.move_forward '["KeyZ", "ArrowUp"]'
+//
+// values given with --set are read as toml, or as a string if they aren't toml. entries left
+// out keep their defaults, and ones which aren't known are warned about and skipped. the file
+// written with the defaults looks like
+//
+//     [window]
+//     width = 1280
+//     height = 720
+//     vsync = true
+//     transparent = false
+//
+//     [render]
+//     msaa_samples = 1
+//     fxaa = false
+//     shadow_quality = "pcf3x3"
+//     reverse_z = false
+//
+//     [camera]
+//     speed = 10
+//     sensitivity = 1.3
+//
+//     [remote]
+//     address = ""
+//
+//     [live_reload]
+//     directory = ""
+//
+//     [bindings]
+//     move_forward = ["KeyW", "ArrowUp"]
+//     ...
+//
+// msaa_samples is how many samples each of the scene's pixels is drawn with, 1 for none. a
+// count the adapter can't draw with falls back to the most it can below that. every action in
+// input.rs has a binding, to the names of its keys and mouse buttons (see input::Input::name),
+// and an empty array unbinds it
+
+use crate::{
+    camera, input, shadows,
+    toml::{self, TomlValue},
+};
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // the window's inner size at startup, in logical pixels
+    pub window_size: [u32; 2],
+    // presents with fifo, waiting for the display, rather than the lowest latency mode the
+    // surface supports. F5 still switches between them
+    pub vsync: bool,
+    // a transparent, undecorated, always on top window for using the renderer as a desktop
+    // overlay. only takes effect where the compositor supports alpha compositing
+    pub transparent: bool,
+    // 1, 2, 4, 8 or 16. see msaa.rs
+    pub msaa_samples: u32,
+    pub fxaa: bool,
+    pub shadow_quality: shadows::ShadowQuality,
+    // reversed depth with an infinite far plane, for large scenes whose distant surfaces
+    // z-fight. see camera::DepthMode
+    pub reverse_z: bool,
+    pub camera_speed: f32,
+    pub camera_sensitivity: f32,
+    // where to listen for remote control connections (see remote.rs), eg "127.0.0.1:7878", or
+    // "" in the file for none. off by default, since anyone who can connect can load files and
+    // write screenshots
+    pub remote_address: Option<String>,
+    // a directory to reload exported models from (see live_reload.rs), eg "exports", or "" in
+    // the file for none
+    pub live_reload_directory: Option<String>,
+    pub bindings: input::Bindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window_size: [1280, 720],
+            vsync: true,
+            transparent: false,
+            msaa_samples: 1,
+            fxaa: false,
+            shadow_quality: shadows::ShadowQuality::Pcf3x3,
+            reverse_z: false,
+            camera_speed: 10.0,
+            camera_sensitivity: 1.3,
+            remote_address: None,
+            live_reload_directory: None,
+            bindings: input::Bindings::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn depth_mode(&self) -> camera::DepthMode {
+        if self.reverse_z {
+            camera::DepthMode::Reversed
+        } else {
+            camera::DepthMode::Standard
+        }
+    }
+
+    // the file at the path, written with the defaults first if there's nothing there, with the
+    // overrides from the command line applied over it
+    pub fn load(path: &str, overrides: &[(String, String)]) -> anyhow::Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let text = Self::default().to_text();
+                match std::fs::write(path, &text) {
+                    Ok(()) => log::info!("wrote the default config to {}", path),
+                    Err(e) => log::warn!("could not write the default config {}: {}", path, e),
+                }
+                text
+            }
+            Err(e) => anyhow::bail!("could not read config {}: {}", path, e),
+        };
+        Self::from_text(&text, overrides).map_err(|e| anyhow::anyhow!("config {}: {}", path, e))
+    }
+
+    // the settings in the text with the overrides applied over it
+    fn from_text(text: &str, overrides: &[(String, String)]) -> Result<Self, String> {
+        let mut entries = toml::parse(text)?;
+
+        for (name, value) in overrides {
+            let value =
+                TomlValue::parse(value).unwrap_or_else(|_| TomlValue::String(value.clone()));
+            match entries.iter_mut().find(|(entry, _)| entry == name) {
+                Some((_, entry)) => *entry = value,
+                None => entries.push((name.clone(), value)),
+            }
+        }
+
+        let mut config = Self::default();
+        for (name, value) in &entries {
+            config
+                .set(name, value)
+                .map_err(|e| format!("{}: {}", name, e))?;
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, name: &str, value: &TomlValue) -> Result<(), String> {
+        let number = || value.as_f64().ok_or("expected a number");
+        let boolean = || value.as_bool().ok_or("expected true or false");
+        // an empty string for none
+        let optional_string = || {
+            let string = value.as_str().ok_or("expected a string")?;
+            Ok::<_, &str>((!string.is_empty()).then(|| string.to_string()))
+        };
+        let inputs = || -> Result<Vec<input::Input>, String> {
+            let names = match value {
+                TomlValue::String(_) => std::slice::from_ref(value),
+                _ => value
+                    .as_array()
+                    .ok_or("expected a key or an array of keys")?,
+            };
+            names
+                .iter()
+                .map(|name| {
+                    let name = name.as_str().ok_or("expected a key's name")?;
+                    input::Input::from_name(name)
+                        .ok_or_else(|| format!("unknown key or button {:?}", name))
+                })
+                .collect()
+        };
+
+        if let Some(action) = name
+            .strip_prefix("bindings.")
+            .and_then(input::Action::from_name)
+        {
+            self.bindings.set(action, inputs()?);
+            return Ok(());
+        }
+        match name {
+            "window.width" => self.window_size[0] = number()?.max(1.0) as u32,
+            "window.height" => self.window_size[1] = number()?.max(1.0) as u32,
+            "window.vsync" => self.vsync = boolean()?,
+            "window.transparent" => self.transparent = boolean()?,
+            "render.msaa_samples" => {
+                let samples = number()?;
+                self.msaa_samples = [1, 2, 4, 8, 16]
+                    .into_iter()
+                    .find(|&count| count as f64 == samples)
+                    .ok_or("expected 1, 2, 4, 8 or 16")?;
+            }
+            "render.fxaa" => self.fxaa = boolean()?,
+            "render.shadow_quality" => {
+                let name = value.as_str().ok_or("expected a string")?;
+                self.shadow_quality = shadows::ShadowQuality::from_name(name)
+                    .ok_or("expected one of hard, pcf3x3, pcf5x5 and pcss")?;
+            }
+            "render.reverse_z" => self.reverse_z = boolean()?,
+            "camera.speed" => self.camera_speed = number()? as f32,
+            "camera.sensitivity" => self.camera_sensitivity = number()? as f32,
+            "remote.address" => self.remote_address = optional_string()?,
+            "live_reload.directory" => self.live_reload_directory = optional_string()?,
+            _ => log::warn!("unknown config entry {}", name),
+        }
+        Ok(())
+    }
+
+    // laid out like the example above
+    fn to_text(&self) -> String {
+        // through the shortest decimal for the f32, so 1.3 isn't written as 1.2999999523162842
+        let number = |n: f32| TomlValue::Number(n.to_string().parse().unwrap_or(n as f64));
+        let inputs = |inputs: &[input::Input]| {
+            TomlValue::Array(
+                inputs
+                    .iter()
+                    .map(|input| TomlValue::String(input.name()))
+                    .collect(),
+            )
+        };
+        let tables = [
+            (
+                "window",
+                vec![
+                    ("width", TomlValue::Number(self.window_size[0] as f64)),
+                    ("height", TomlValue::Number(self.window_size[1] as f64)),
+                    ("vsync", TomlValue::Bool(self.vsync)),
+                    ("transparent", TomlValue::Bool(self.transparent)),
+                ],
+            ),
+            (
+                "render",
+                vec![
+                    ("msaa_samples", TomlValue::Number(self.msaa_samples as f64)),
+                    ("fxaa", TomlValue::Bool(self.fxaa)),
+                    (
+                        "shadow_quality",
+                        TomlValue::String(self.shadow_quality.name().to_string()),
+                    ),
+                    ("reverse_z", TomlValue::Bool(self.reverse_z)),
+                ],
+            ),
+            (
+                "camera",
+                vec![
+                    ("speed", number(self.camera_speed)),
+                    ("sensitivity", number(self.camera_sensitivity)),
+                ],
+            ),
+            (
+                "remote",
+                vec![(
+                    "address",
+                    TomlValue::String(self.remote_address.clone().unwrap_or_default()),
+                )],
+            ),
+            (
+                "live_reload",
+                vec![(
+                    "directory",
+                    TomlValue::String(self.live_reload_directory.clone().unwrap_or_default()),
+                )],
+            ),
+            (
+                "bindings",
+                self.bindings
+                    .iter()
+                    .map(|(action, bound)| (action.name(), inputs(bound)))
+                    .collect(),
+            ),
+        ];
+
+        let mut text = String::from("# graphics-fundamentals settings, see src/config.rs\n");
+        for (table, entries) in tables {
+            text += &format!("\n[{}]\n", table);
+            for (key, value) in entries {
+                text += &format!("{} = {}\n", key, value);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::KeyCode;
+
+    fn overrides(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn bound(config: &Config, action: input::Action) -> Vec<input::Input> {
+        config
+            .bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, inputs)| inputs.to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn the_defaults_read_back_the_same() {
+        let defaults = Config::default();
+        let config = Config::from_text(&defaults.to_text(), &[]).unwrap();
+        assert_eq!(config.to_text(), defaults.to_text());
+        assert_eq!(config.camera_sensitivity, 1.3);
+    }
+
+    #[test]
+    fn entries_replace_the_defaults() {
+        let text = "[window]\nwidth = 800\nvsync = false\n\n[render]\nmsaa_samples = 4\nshadow_quality = \"pcss\"\nreverse_z = true\n\n[remote]\naddress = \"127.0.0.1:7878\"\n";
+        let config = Config::from_text(text, &[]).unwrap();
+        assert_eq!(config.window_size, [800, 720]);
+        assert!(!config.vsync);
+        assert_eq!(config.msaa_samples, 4);
+        assert_eq!(config.shadow_quality, shadows::ShadowQuality::Pcss);
+        assert_eq!(config.depth_mode(), camera::DepthMode::Reversed);
+        assert_eq!(config.remote_address.as_deref(), Some("127.0.0.1:7878"));
+        assert_eq!(config.live_reload_directory, None);
+    }
+
+    #[test]
+    fn bindings_take_a_key_or_an_array() {
+        let text = "[bindings]\nmove_forward = [\"KeyZ\", \"MouseBack\"]\nmove_up = \"KeyE\"\nmove_down = []\n";
+        let config = Config::from_text(text, &[]).unwrap();
+        assert_eq!(
+            bound(&config, input::Action::MoveForward),
+            [
+                input::Input::Key(KeyCode::KeyZ),
+                input::Input::Mouse(winit::event::MouseButton::Back)
+            ]
+        );
+        assert_eq!(
+            bound(&config, input::Action::MoveUp),
+            [input::Input::Key(KeyCode::KeyE)]
+        );
+        assert_eq!(bound(&config, input::Action::MoveDown), []);
+        // the rest keep their defaults
+        assert_eq!(
+            bound(&config, input::Action::MoveBackward),
+            [
+                input::Input::Key(KeyCode::KeyS),
+                input::Input::Key(KeyCode::ArrowDown)
+            ]
+        );
+
+        let text = "[bindings]\nmove_forward = [\"NotAKey\"]\n";
+        assert!(Config::from_text(text, &[]).is_err());
+    }
+
+    #[test]
+    fn overrides_merge_over_the_file() {
+        let text = "[window]\nwidth = 800\nvsync = false\n\n[camera]\nspeed = 4\n";
+        let config = Config::from_text(
+            text,
+            &overrides(&[
+                ("window.vsync", "true"),
+                ("camera.sensitivity", "0.5"),
+                ("live_reload.directory", "exports"),
+                ("bindings.move_forward", "[\"KeyZ\"]"),
+            ]),
+        )
+        .unwrap();
+        // overridden, or added where the file leaves them out
+        assert!(config.vsync);
+        assert_eq!(config.camera_sensitivity, 0.5);
+        assert_eq!(config.live_reload_directory.as_deref(), Some("exports"));
+        assert_eq!(
+            bound(&config, input::Action::MoveForward),
+            [input::Input::Key(KeyCode::KeyZ)]
+        );
+        // kept from the file
+        assert_eq!(config.window_size, [800, 720]);
+        assert_eq!(config.camera_speed, 4.0);
+    }
+
+    #[test]
+    fn bad_values_name_their_entry() {
+        let error = Config::from_text("[window]\nvsync = 1\n", &[]).unwrap_err();
+        assert_eq!(error, "window.vsync: expected true or false");
+        let error =
+            Config::from_text("", &overrides(&[("render.shadow_quality", "blurry")])).unwrap_err();
+        assert!(error.starts_with("render.shadow_quality:"));
+        let error = Config::from_text("[render]\nmsaa_samples = 3\n", &[]).unwrap_err();
+        assert_eq!(error, "render.msaa_samples: expected 1, 2, 4, 8 or 16");
+    }
+}
@@ -0,0 +1,387 @@
+// a one line command console, opened with `:` or F1 and shown in the window title.
+// commands are only parsed here; State carries them out through the same methods that code
+// can call directly, eg `set light.intensity 5` is State::set("light.intensity", 5.0)
+
+use winit::{
+    event::KeyEvent,
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{recording::RecordingFormat, scene::ModelId};
+
+pub const HELP: &str = "commands: load <path|primitive:name|terrain:heightmap>, unload <model id>, save <path.json|path.obj>, set <name> <value>, \
+     frame [model id], group <model id> <name> <show|hide|move x y z>, screenshot [path], panorama [path] [face size], bench <frames>, scene new, scene <number>, reference <path|off>, environment <path>, uvgrid <material|all>, record [png|video], script <path|stop>, present <fifo|mailbox|immediate>, help";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Load(String),
+    Unload(ModelId),
+    // the scene as a scene file, or its meshes as one obj when the path ends in .obj
+    Save(String),
+    Set(String, f32),
+    // flies the camera to the model, or the whole scene with None
+    Frame(Option<ModelId>),
+    // one of a model's obj groups, by the name of its `o` or `g` line
+    Group(ModelId, String, GroupEdit),
+    Screenshot(Option<String>),
+    // saved as radiance hdr when the path ends in .hdr
+    Panorama(Option<String>, Option<u32>),
+    Bench(u32),
+    NewScene,
+    // counted from 1, like the number keys which switch scenes
+    SwitchScene(usize),
+    // None turns the overlay off
+    Reference(Option<String>),
+    // an equirectangular image to light the scene with
+    Environment(String),
+    // toggles the uv debug grid in place of a material's diffuse map, or of every material's
+    UvGrid(String),
+    // starts recording in the format, or stops the recording in progress
+    Record(RecordingFormat),
+    // runs a rhai script (see scripting.rs), None stops the one running
+    Script(Option<String>),
+    // how frames are handed to the display, which decides between tearing and latency
+    Present(wgpu::PresentMode),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupEdit {
+    Show,
+    Hide,
+    // to where the group sits relative to where it was loaded, in the model's space
+    Move([f32; 3]),
+}
+
+#[derive(Debug)]
+pub enum CommandParseError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument(&'static str, &'static str),
+    BadNumber(String, String),
+    TooManyArguments(&'static str),
+}
+
+impl std::fmt::Display for CommandParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandParseError::Empty => write!(f, "no command given"),
+            CommandParseError::UnknownCommand(command) => {
+                write!(f, "unknown command {} ({})", command, HELP)
+            }
+            CommandParseError::MissingArgument(command, argument) => {
+                write!(f, "{} needs a {}", command, argument)
+            }
+            CommandParseError::BadNumber(command, value) => {
+                write!(f, "{} expected a number, got {}", command, value)
+            }
+            CommandParseError::TooManyArguments(command) => {
+                write!(f, "too many arguments for {}", command)
+            }
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, CommandParseError> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Err(CommandParseError::Empty);
+        };
+
+        let command = match name {
+            "load" => {
+                // paths may contain spaces, so everything after the command is the path
+                let path = line.trim_start()["load".len()..].trim();
+                if path.is_empty() {
+                    return Err(CommandParseError::MissingArgument("load", "path"));
+                }
+                return Ok(Command::Load(path.to_string()));
+            }
+            "unload" => {
+                let id = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("unload", "model id"))?;
+                let id = id
+                    .parse()
+                    .map_err(|_| CommandParseError::BadNumber("unload".into(), id.into()))?;
+                Command::Unload(id)
+            }
+            "frame" => Command::Frame(match words.next() {
+                Some(id) => Some(
+                    id.parse()
+                        .map_err(|_| CommandParseError::BadNumber("frame".into(), id.into()))?,
+                ),
+                None => None,
+            }),
+            "group" => {
+                let id = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("group", "model id"))?;
+                let id = id
+                    .parse()
+                    .map_err(|_| CommandParseError::BadNumber("group".into(), id.into()))?;
+                let name = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("group", "name"))?;
+                let edit = match words.next() {
+                    Some("show") => GroupEdit::Show,
+                    Some("hide") => GroupEdit::Hide,
+                    Some("move") => {
+                        let mut position = [0.0; 3];
+                        for coordinate in &mut position {
+                            let value = words
+                                .next()
+                                .ok_or(CommandParseError::MissingArgument("group", "x y z"))?;
+                            *coordinate = value.parse().map_err(|_| {
+                                CommandParseError::BadNumber("group".into(), value.into())
+                            })?;
+                        }
+                        GroupEdit::Move(position)
+                    }
+                    Some(edit) => {
+                        return Err(CommandParseError::UnknownCommand(format!("group {}", edit)));
+                    }
+                    None => {
+                        return Err(CommandParseError::MissingArgument(
+                            "group",
+                            "show, hide or move",
+                        ));
+                    }
+                };
+                Command::Group(id, name.to_string(), edit)
+            }
+            "save" => {
+                // like load, the path may contain spaces
+                let path = line.trim_start()["save".len()..].trim();
+                if path.is_empty() {
+                    return Err(CommandParseError::MissingArgument("save", "path"));
+                }
+                return Ok(Command::Save(path.to_string()));
+            }
+            "set" => {
+                let setting = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("set", "name"))?;
+                let value = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("set", "value"))?;
+                let value = value
+                    .parse()
+                    .map_err(|_| CommandParseError::BadNumber("set".into(), value.into()))?;
+                Command::Set(setting.to_string(), value)
+            }
+            "screenshot" => Command::Screenshot(words.next().map(str::to_string)),
+            "panorama" => {
+                let path = words.next().map(str::to_string);
+                let face_size = match words.next() {
+                    Some(size) => Some(size.parse().map_err(|_| {
+                        CommandParseError::BadNumber("panorama".into(), size.into())
+                    })?),
+                    None => None,
+                };
+                Command::Panorama(path, face_size)
+            }
+            "bench" => {
+                let frames = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("bench", "frame count"))?;
+                let frames = frames
+                    .parse()
+                    .map_err(|_| CommandParseError::BadNumber("bench".into(), frames.into()))?;
+                Command::Bench(frames)
+            }
+            "scene" => {
+                let scene = words
+                    .next()
+                    .ok_or(CommandParseError::MissingArgument("scene", "number or new"))?;
+                if scene == "new" {
+                    Command::NewScene
+                } else {
+                    let number = scene
+                        .parse()
+                        .map_err(|_| CommandParseError::BadNumber("scene".into(), scene.into()))?;
+                    Command::SwitchScene(number)
+                }
+            }
+            "reference" => {
+                // like load, the path may contain spaces
+                let path = line.trim_start()["reference".len()..].trim();
+                return match path {
+                    "" => Err(CommandParseError::MissingArgument(
+                        "reference",
+                        "path or off",
+                    )),
+                    "off" => Ok(Command::Reference(None)),
+                    _ => Ok(Command::Reference(Some(path.to_string()))),
+                };
+            }
+            "environment" => {
+                // like load, the path may contain spaces
+                let path = line.trim_start()["environment".len()..].trim();
+                if path.is_empty() {
+                    return Err(CommandParseError::MissingArgument("environment", "path"));
+                }
+                return Ok(Command::Environment(path.to_string()));
+            }
+            "script" => {
+                // like load, the path may contain spaces
+                let path = line.trim_start()["script".len()..].trim();
+                return match path {
+                    "" => Err(CommandParseError::MissingArgument("script", "path or stop")),
+                    "stop" => Ok(Command::Script(None)),
+                    _ => Ok(Command::Script(Some(path.to_string()))),
+                };
+            }
+            "uvgrid" => {
+                let material = words.next().ok_or(CommandParseError::MissingArgument(
+                    "uvgrid",
+                    "material or all",
+                ))?;
+                Command::UvGrid(material.to_string())
+            }
+            "record" => Command::Record(match words.next() {
+                None | Some("png") => RecordingFormat::PngSequence,
+                Some("video" | "mp4") => RecordingFormat::Video,
+                Some(format) => {
+                    return Err(CommandParseError::UnknownCommand(format!(
+                        "record {}",
+                        format
+                    )));
+                }
+            }),
+            "present" => Command::Present(match words.next() {
+                Some("fifo" | "vsync") => wgpu::PresentMode::Fifo,
+                Some("relaxed") => wgpu::PresentMode::FifoRelaxed,
+                Some("mailbox") => wgpu::PresentMode::Mailbox,
+                Some("immediate") => wgpu::PresentMode::Immediate,
+                Some(mode) => {
+                    return Err(CommandParseError::UnknownCommand(format!(
+                        "present {}",
+                        mode
+                    )));
+                }
+                None => {
+                    return Err(CommandParseError::MissingArgument(
+                        "present",
+                        "fifo, mailbox or immediate",
+                    ));
+                }
+            }),
+            "help" | "?" => Command::Help,
+            _ => return Err(CommandParseError::UnknownCommand(name.to_string())),
+        };
+
+        if words.next().is_some() {
+            return Err(CommandParseError::TooManyArguments(match command {
+                Command::Unload(_) => "unload",
+                Command::Set(..) => "set",
+                Command::Frame(_) => "frame",
+                Command::Group(..) => "group",
+                Command::Screenshot(_) => "screenshot",
+                Command::Panorama(..) => "panorama",
+                Command::Bench(_) => "bench",
+                Command::NewScene | Command::SwitchScene(_) => "scene",
+                Command::UvGrid(_) => "uvgrid",
+                Command::Record(_) => "record",
+                Command::Present(_) => "present",
+                _ => "help",
+            }));
+        }
+
+        Ok(command)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Console {
+    is_open: bool,
+    input: String,
+    // the previously entered lines, the most recent last
+    history: Vec<String>,
+    history_index: Option<usize>,
+    // the outcome of the last command, shown while the console is closed
+    message: String,
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
+    // takes every key event while open, plus the ones that open it. returns a line when enter
+    // is pressed, which also closes the console
+    pub fn handle_key(&mut self, event: &KeyEvent) -> Option<String> {
+        if !event.state.is_pressed() {
+            return None;
+        }
+
+        let code = match event.physical_key {
+            PhysicalKey::Code(code) => Some(code),
+            _ => None,
+        };
+
+        if !self.is_open {
+            if code == Some(KeyCode::F1) || event.text.as_deref() == Some(":") {
+                self.is_open = true;
+                self.input.clear();
+                self.history_index = None;
+            }
+            return None;
+        }
+
+        match code {
+            Some(KeyCode::Escape | KeyCode::F1) => self.is_open = false,
+            Some(KeyCode::Enter | KeyCode::NumpadEnter) => {
+                self.is_open = false;
+                let line = std::mem::take(&mut self.input);
+                if line.trim().is_empty() {
+                    return None;
+                }
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                return Some(line);
+            }
+            Some(KeyCode::Backspace) => {
+                self.input.pop();
+            }
+            Some(KeyCode::ArrowUp | KeyCode::ArrowDown) => {
+                if self.history.is_empty() {
+                    return None;
+                }
+                let last = self.history.len() - 1;
+                self.history_index = match (self.history_index, code == Some(KeyCode::ArrowUp)) {
+                    (None, true) => Some(last),
+                    (None, false) => None,
+                    (Some(i), true) => Some(i.saturating_sub(1)),
+                    (Some(i), false) => (i < last).then_some(i + 1),
+                };
+                self.input = self
+                    .history_index
+                    .map(|i| self.history[i].clone())
+                    .unwrap_or_default();
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    self.input.extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+
+        None
+    }
+
+    // the console line for the window title
+    pub fn describe(&self) -> String {
+        if self.is_open {
+            format!("> {}_", self.input)
+        } else {
+            self.message.clone()
+        }
+    }
+}
@@ -0,0 +1,292 @@
+// lines drawn for one frame only, for seeing vectors, volumes and anything else while working
+// on it. anything can add lines while the frame is being updated, eg
+//
+//     state.debug_draw.line([0.0; 3], [0.0, 5.0, 0.0], [1.0, 1.0, 0.0]);
+//     state.debug_draw.aabb(min, max, [0.0, 1.0, 0.0]);
+//
+// and they are all uploaded together at the end of State::update, then forgotten once drawn,
+// so something that should stay on screen has to be added every frame. positions are in
+// world space, except for overlay lines, which are in pixels from the top left of the window
+// and drawn over everything, eg for graphs
+
+use std::f32::consts::TAU;
+
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+
+// how many straight pieces make up a circle
+const CIRCLE_SEGMENTS: u32 = 32;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+pub struct DebugDraw {
+    // two for every line, waiting for the next upload
+    vertices: Vec<LineVertex>,
+    overlay_vertices: Vec<LineVertex>,
+    // the world lines, then the overlay lines
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    // how many of each were uploaded, to be drawn this frame
+    vertex_count: u32,
+    overlay_vertex_count: u32,
+    pipeline: wgpu::RenderPipeline,
+    overlay_pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugDraw {
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        depth_compare: wgpu::CompareFunction,
+        sample_count: u32,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("debug draw pipeline layout"),
+            bind_group_layouts: &[per_frame_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "debug_draw.wgsl",
+            include_str!("shaders/debug_draw.wgsl"),
+        ));
+        let create_pipeline = |label: &str, entry_point: &str, depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                    }],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fragment_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                // not written, so lines don't hide each other
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: depth_format,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+        // world lines are hidden by the scene, and the overlay is on top of it
+        let pipeline = create_pipeline("debug draw pipeline", "vertex_main", depth_compare);
+        let overlay_pipeline = create_pipeline(
+            "debug overlay pipeline",
+            "overlay_vertex_main",
+            wgpu::CompareFunction::Always,
+        );
+
+        let vertex_capacity = 1024;
+        Self {
+            vertices: Vec::new(),
+            overlay_vertices: Vec::new(),
+            vertex_buffer: create_vertex_buffer(device, vertex_capacity),
+            vertex_capacity,
+            vertex_count: 0,
+            overlay_vertex_count: 0,
+            pipeline,
+            overlay_pipeline,
+        }
+    }
+
+    pub fn line(&mut self, start: impl Into<[f32; 3]>, end: impl Into<[f32; 3]>, color: [f32; 3]) {
+        self.vertices.extend_from_slice(&[
+            LineVertex {
+                position: start.into(),
+                color,
+            },
+            LineVertex {
+                position: end.into(),
+                color,
+            },
+        ]);
+    }
+
+    // a circle around the axis through the center
+    pub fn circle(
+        &mut self,
+        center: impl Into<[f32; 3]>,
+        axis: impl Into<[f32; 3]>,
+        radius: f32,
+        color: [f32; 3],
+    ) {
+        let center = Vector3::from(center.into());
+        let axis = Vector3::from(axis.into()).normalize();
+        let helper = if axis.x.abs() > 0.9 {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_x()
+        };
+        let u = helper.cross(axis).normalize() * radius;
+        let v = axis.cross(u);
+        let point = |segment: u32| {
+            let (sin, cos) = (segment as f32 / CIRCLE_SEGMENTS as f32 * TAU).sin_cos();
+            center + u * cos + v * sin
+        };
+        for segment in 0..CIRCLE_SEGMENTS {
+            self.line(point(segment), point(segment + 1), color);
+        }
+    }
+
+    // a circle around each axis, which reads as a sphere from any direction
+    pub fn sphere(&mut self, center: impl Into<[f32; 3]>, radius: f32, color: [f32; 3]) {
+        let center = center.into();
+        for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            self.circle(center, axis, radius, color);
+        }
+    }
+
+    pub fn aabb(&mut self, min: impl Into<[f32; 3]>, max: impl Into<[f32; 3]>, color: [f32; 3]) {
+        let (min, max) = (min.into(), max.into());
+        // the corners are numbered by which axes are at their max, x in bit 0 up to z in bit 2
+        let corners: [[f32; 3]; 8] = std::array::from_fn(|i| {
+            std::array::from_fn(|axis| {
+                if i >> axis & 1 == 1 {
+                    max[axis]
+                } else {
+                    min[axis]
+                }
+            })
+        });
+        self.cuboid(corners, color);
+    }
+
+    // the volume a view projection matrix sees, eg a camera's or a shadow cascade's. it needs
+    // a far plane, so not an infinite reversed z projection
+    pub fn frustum(&mut self, view_projection: Matrix4<f32>, color: [f32; 3]) {
+        let Some(inverse) = view_projection.invert() else {
+            return;
+        };
+        let corners: [[f32; 3]; 8] = std::array::from_fn(|i| {
+            let ndc = Vector4::new(
+                if i & 1 == 1 { 1.0 } else { -1.0 },
+                if i & 2 == 2 { 1.0 } else { -1.0 },
+                if i & 4 == 4 { 1.0 } else { 0.0 },
+                1.0,
+            );
+            let corner = inverse * ndc;
+            (corner.truncate() / corner.w).into()
+        });
+        self.cuboid(corners, color);
+    }
+
+    // the 12 edges between corners numbered like aabb's, eg a box that has been transformed
+    pub fn cuboid(&mut self, corners: [[f32; 3]; 8], color: [f32; 3]) {
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corners[i], corners[i | axis], color);
+                }
+            }
+        }
+    }
+
+    // a line over everything else, in pixels from the top left of the window
+    pub fn overlay_line(&mut self, start: [f32; 2], end: [f32; 2], color: [f32; 3]) {
+        self.overlay_vertices.extend_from_slice(&[
+            LineVertex {
+                position: [start[0], start[1], 0.0],
+                color,
+            },
+            LineVertex {
+                position: [end[0], end[1], 0.0],
+                color,
+            },
+        ]);
+    }
+
+    // sends this frame's lines to the gpu and starts collecting the next frame's, growing the
+    // buffer when there are more than it holds. the overlay's pixels are turned into clip space
+    // for a window of the size given
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        uploads: &mut crate::uploads::UploadBelt,
+        window_size: [u32; 2],
+    ) {
+        let [width, height] = window_size.map(|s| s.max(1) as f32);
+        for vertex in &mut self.overlay_vertices {
+            let [x, y, _] = vertex.position;
+            vertex.position = [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0, 0.0];
+        }
+
+        self.vertex_count = self.vertices.len() as u32;
+        self.overlay_vertex_count = self.overlay_vertices.len() as u32;
+        self.vertices.append(&mut self.overlay_vertices);
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        if self.vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(device, self.vertex_capacity);
+        }
+        uploads.write(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+    }
+
+    // draws into a pass which has the scene's depth attached
+    pub fn draw(
+        &self,
+        render_pass: &mut crate::render_stats::CountingRenderPass,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+    ) {
+        if self.vertex_count + self.overlay_vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if self.vertex_count > 0 {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.draw(0..self.vertex_count, 0..1);
+        }
+        if self.overlay_vertex_count > 0 {
+            let overlay = self.vertex_count..self.vertex_count + self.overlay_vertex_count;
+            render_pass.set_pipeline(&self.overlay_pipeline);
+            render_pass.draw(overlay, 0..1);
+        }
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("debug draw vertex buffer"),
+        size: (capacity * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
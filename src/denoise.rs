@@ -0,0 +1,516 @@
+// a denoiser for the ray traced ambient occlusion, whose few rays per pixel turn with every
+// frame (see ray_queries.wgsl), which trades banding for noise. a prepass draws the opaque
+// meshes into targets of their own: where each pixel's surface is, which way it faces, where
+// it was on screen the last time the denoiser ran (only the camera's movement is followed),
+// and its noisy ambient occlusion. then
+//
+// - a temporal pass blends each pixel's ao into its history, found by following its motion
+//   vector back into the last frame's, unless the surface there has moved or was uncovered
+// - three a-trous passes blur what's left with a 5x5 kernel spread 1, 2 and 4 pixels apart,
+//   weighted down across edges in normal and depth so the blur stays on each surface
+//
+// the scene pass reads the result from the per frame bind group, for surfaces the prepass saw
+// at their pixel, see raytraced.wgsl. only the main view is denoised, so the quad view and
+// stereo trace their own rays as before. anything else noisy, eg ssao or a path tracer's
+// preview, would go through the same filters by writing into a target next to the ao's
+
+use crate::{camera, model, render_stats, scene, texture};
+
+const POSITION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const MOTION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+const NOISY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+// what the filters write, as storage
+const FILTERED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+const PREPASS_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DenoiserUniform {
+    previous_view_proj: [[f32; 4]; 4],
+    frame_index: u32,
+    is_enabled: u32,
+    is_history_valid: u32,
+    _padding: u32,
+}
+
+struct Targets {
+    // the current frame's and the last one's, which swap every frame
+    positions: [texture::Texture; 2],
+    histories: [texture::Texture; 2],
+    normal: texture::Texture,
+    motion: texture::Texture,
+    noisy: texture::Texture,
+    depth: texture::Texture,
+    // the first two a-trous passes write into these in turn
+    filtered: [texture::Texture; 2],
+    // and the last one here, for the scene pass
+    output: texture::Texture,
+}
+
+pub struct Denoiser {
+    uniform: DenoiserUniform,
+    pub uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    prepass_pipeline: wgpu::RenderPipeline,
+    temporal_pipeline: wgpu::ComputePipeline,
+    a_trous_pipelines: [wgpu::ComputePipeline; 3],
+    temporal_layout: wgpu::BindGroupLayout,
+    a_trous_layout: wgpu::BindGroupLayout,
+    targets: Targets,
+    // by which of the positions and histories is the current frame's
+    temporal_bind_groups: [wgpu::BindGroup; 2],
+    a_trous_bind_groups: [[wgpu::BindGroup; 3]; 2],
+    current: usize,
+    clear_depth: f32,
+}
+
+impl Denoiser {
+    // the prepass traces through the per frame bind group's tlas, so this is only made where
+    // the adapter supports ray queries
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        per_object_layout: &wgpu::BindGroupLayout,
+        size: wgpu::Extent3d,
+        depth_mode: camera::DepthMode,
+    ) -> Self {
+        let uniform = DenoiserUniform {
+            previous_view_proj: cgmath::Matrix4::from_scale(1.0).into(),
+            frame_index: 0,
+            is_enabled: 0,
+            is_history_valid: 0,
+            _padding: 0,
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("denoiser uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let uniform_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("denoiser uniform bind group layout"),
+            entries: &[uniform_entry(
+                0,
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            )],
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("denoiser uniform bind group"),
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let prepass_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("denoiser prepass pipeline layout"),
+            bind_group_layouts: &[per_frame_layout, per_object_layout, &uniform_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "denoise_prepass.wgsl",
+            include_str!("shaders/denoise_prepass.wgsl"),
+        ));
+        let target = |format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        };
+        let prepass_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("denoiser prepass pipeline"),
+            layout: Some(&prepass_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[<model::ModelVertex as model::Vertex>::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[
+                    target(POSITION_FORMAT),
+                    target(NORMAL_FORMAT),
+                    target(MOTION_FORMAT),
+                    target(NOISY_FORMAT),
+                ],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: PREPASS_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let output_entry = wgpu::BindGroupLayoutEntry {
+            binding: 4,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: FILTERED_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        // the positions, normals, input and output, and for the temporal pass the uniform,
+        // motion vectors, and last frame's positions and history too
+        let temporal_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("denoiser temporal bind group layout"),
+            entries: &[
+                uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                texture_entry(1),
+                texture_entry(2),
+                texture_entry(3),
+                output_entry,
+                texture_entry(5),
+                texture_entry(6),
+                texture_entry(7),
+            ],
+        });
+        let a_trous_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("denoiser a-trous bind group layout"),
+            entries: &[
+                texture_entry(1),
+                texture_entry(2),
+                texture_entry(3),
+                output_entry,
+            ],
+        });
+
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "denoise_filter.wgsl",
+            include_str!("shaders/denoise_filter.wgsl"),
+        ));
+        let create_pipeline = |layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("denoiser filter pipeline layout"),
+                bind_group_layouts: &[layout],
+                immediate_size: 0,
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let temporal_pipeline = create_pipeline(&temporal_layout, "temporal_main");
+        let a_trous_pipelines = ["a_trous_1", "a_trous_2", "a_trous_4"]
+            .map(|entry_point| create_pipeline(&a_trous_layout, entry_point));
+
+        let targets = create_targets(device, size);
+        let (temporal_bind_groups, a_trous_bind_groups) = create_filter_bind_groups(
+            device,
+            &temporal_layout,
+            &a_trous_layout,
+            &uniform_buffer,
+            &targets,
+        );
+
+        Self {
+            uniform,
+            uniform_buffer,
+            uniform_bind_group,
+            prepass_pipeline,
+            temporal_pipeline,
+            a_trous_pipelines,
+            temporal_layout,
+            a_trous_layout,
+            targets,
+            temporal_bind_groups,
+            a_trous_bind_groups,
+            current: 0,
+            clear_depth: depth_mode.clear_depth(),
+        }
+    }
+
+    // recreates the targets when the scene is drawn at another size, eg after a resize or
+    // for a panorama's faces. the history starts over, and the output is a new texture, so
+    // true means the per frame bind groups have to be made again
+    pub fn fit(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) -> bool {
+        if self.targets.output.texture.size() == size {
+            return false;
+        }
+        self.targets = create_targets(device, size);
+        (self.temporal_bind_groups, self.a_trous_bind_groups) = create_filter_bind_groups(
+            device,
+            &self.temporal_layout,
+            &self.a_trous_layout,
+            &self.uniform_buffer,
+            &self.targets,
+        );
+        self.reset_history();
+        true
+    }
+
+    // for a frame which doesn't follow on from the last one denoised
+    pub fn reset_history(&mut self) {
+        self.uniform.is_history_valid = 0;
+    }
+
+    // the denoised ao and the distance from the camera it was traced at, for the scene pass
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.targets.output.view
+    }
+
+    // for frames drawn without the denoiser, whose scene pass traces its own rays. the history
+    // is stale by the time it runs again
+    pub fn skip(&mut self, queue: &wgpu::Queue) {
+        if self.uniform.is_enabled == 0 {
+            return;
+        }
+        self.uniform.is_enabled = 0;
+        self.reset_history();
+        self.write_uniform(queue);
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // traces and denoises the frame's ao before the scene pass, drawing the meshes (by object
+    // and mesh index) seen from the camera in slot 0 of the per frame bind group, which is at
+    // `view_proj`
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode(
+        &mut self,
+        queue: &wgpu::Queue,
+        command_encoder: &mut wgpu::CommandEncoder,
+        stats: &mut render_stats::RenderStats,
+        per_frame_bind_group: &wgpu::BindGroup,
+        scene: &scene::Scene,
+        meshes: &[(usize, usize)],
+        view_proj: cgmath::Matrix4<f32>,
+        frame_index: u32,
+    ) {
+        self.current = 1 - self.current;
+        self.uniform.frame_index = frame_index;
+        self.uniform.is_enabled = 1;
+        self.write_uniform(queue);
+        // the next frame reprojects into this one
+        self.uniform.previous_view_proj = view_proj.into();
+        self.uniform.is_history_valid = 1;
+
+        command_encoder.push_debug_group("denoiser");
+        {
+            let clear = |view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            };
+            let render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("denoiser prepass"),
+                color_attachments: &[
+                    clear(&self.targets.positions[self.current].view),
+                    clear(&self.targets.normal.view),
+                    clear(&self.targets.motion.view),
+                    // unoccluded where nothing is drawn
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.targets.noisy.view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.targets.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_depth),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            let mut render_pass = render_stats::CountingRenderPass::new(render_pass, stats);
+
+            render_pass.set_pipeline(&self.prepass_pipeline);
+            render_pass.set_bind_group(0, per_frame_bind_group, &[0]);
+            render_pass.set_bind_group(2, &self.uniform_bind_group, &[]);
+            for &(i, mesh) in meshes {
+                let mesh = &scene.objects()[i].model.meshes[mesh];
+                render_pass.set_bind_group(1, &scene.bind_group, &[scene.transform_offset(i)]);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+                render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+            }
+        }
+
+        let size = self.targets.output.texture.size();
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("denoiser filter pass"),
+            timestamp_writes: None,
+        });
+        let passes = std::iter::once((
+            &self.temporal_pipeline,
+            &self.temporal_bind_groups[self.current],
+        ))
+        .chain(
+            self.a_trous_pipelines
+                .iter()
+                .zip(&self.a_trous_bind_groups[self.current]),
+        );
+        for (pipeline, bind_group) in passes {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                size.width.div_ceil(WORKGROUP_SIZE),
+                size.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        drop(compute_pass);
+        command_encoder.pop_debug_group();
+    }
+}
+
+fn create_targets(device: &wgpu::Device, size: wgpu::Extent3d) -> Targets {
+    let target = |label, format| {
+        texture::Texture::builder(label, size.width, size.height)
+            .format(format)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .build(device)
+    };
+    let filtered = |label| {
+        texture::Texture::builder(label, size.width, size.height)
+            .format(FILTERED_FORMAT)
+            .usage(wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING)
+            .build(device)
+    };
+    Targets {
+        positions: [
+            "denoiser position texture",
+            "denoiser last position texture",
+        ]
+        .map(|label| target(label, POSITION_FORMAT)),
+        histories: ["denoiser history texture", "denoiser last history texture"].map(filtered),
+        normal: target("denoiser normal texture", NORMAL_FORMAT),
+        motion: target("denoiser motion texture", MOTION_FORMAT),
+        noisy: target("denoiser noisy texture", NOISY_FORMAT),
+        depth: texture::Texture::builder("denoiser depth texture", size.width, size.height)
+            .format(PREPASS_DEPTH_FORMAT)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            .build(device),
+        filtered: ["denoiser filtered texture", "denoiser filtered texture"].map(filtered),
+        output: filtered("denoiser output texture"),
+    }
+}
+
+// the temporal pass's and the a-trous passes' bind groups for either of the frames being the
+// current one
+fn create_filter_bind_groups(
+    device: &wgpu::Device,
+    temporal_layout: &wgpu::BindGroupLayout,
+    a_trous_layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    targets: &Targets,
+) -> ([wgpu::BindGroup; 2], [[wgpu::BindGroup; 3]; 2]) {
+    fn view(binding: u32, texture: &texture::Texture) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(&texture.view),
+        }
+    }
+
+    let temporal = [0, 1].map(|current| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("denoiser temporal bind group"),
+            layout: temporal_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                view(1, &targets.positions[current]),
+                view(2, &targets.normal),
+                view(3, &targets.noisy),
+                view(4, &targets.histories[current]),
+                view(5, &targets.motion),
+                view(6, &targets.positions[1 - current]),
+                view(7, &targets.histories[1 - current]),
+            ],
+        })
+    });
+
+    let a_trous = [0, 1].map(|current| {
+        // each pass reads what the one before it wrote, starting from the history
+        let inputs = [
+            &targets.histories[current],
+            &targets.filtered[0],
+            &targets.filtered[1],
+        ];
+        let outputs = [&targets.filtered[0], &targets.filtered[1], &targets.output];
+        std::array::from_fn(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("denoiser a-trous bind group"),
+                layout: a_trous_layout,
+                entries: &[
+                    view(1, &targets.positions[current]),
+                    view(2, &targets.normal),
+                    view(3, inputs[i]),
+                    view(4, outputs[i]),
+                ],
+            })
+        })
+    });
+
+    (temporal, a_trous)
+}
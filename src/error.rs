@@ -0,0 +1,102 @@
+use crate::{
+    obj_parse::{MTLLoadError, OBJLoadError},
+    ply_parse::PLYLoadError,
+};
+
+// everything that can go wrong while loading models, materials and textures from disk.
+// each variant keeps the path (and material, where there is one) so the error says what to fix
+#[derive(Debug, thiserror::Error)]
+pub enum AssetError {
+    #[error("could not open {path}: {source}")]
+    MissingFile {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("could not read {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("could not decode image {path}: {source}")]
+    BadImage {
+        path: String,
+        source: image::ImageError,
+    },
+    #[error("{path}:{line}: {message}")]
+    Parse {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    // fetching over http on the web, where there's no io::Error to give
+    #[error("could not fetch {path}: {message}")]
+    Fetch { path: String, message: String },
+    // malformed binary data, where there are no lines to point at
+    #[error("{path}: {message}")]
+    BadData { path: String, message: String },
+    #[error("material {name} not found in {path}")]
+    MissingMaterial { name: String, path: String },
+    #[error("could not upload {label} to the gpu: {message}")]
+    GpuUpload { label: String, message: String },
+    #[error("in material {material}: {source}")]
+    InMaterial {
+        material: String,
+        source: Box<AssetError>,
+    },
+}
+
+impl AssetError {
+    // wraps an error with the material which referenced the failing file
+    pub fn in_material(self, material: &str) -> Self {
+        AssetError::InMaterial {
+            material: material.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl From<OBJLoadError> for AssetError {
+    fn from(error: OBJLoadError) -> Self {
+        match error {
+            OBJLoadError::FileNotFound(path, source) => AssetError::MissingFile { path, source },
+            OBJLoadError::Read(path, source) => AssetError::Read { path, source },
+            // the parsers count lines from 0
+            OBJLoadError::Parse(path, line, message) => AssetError::Parse {
+                path,
+                line: line + 1,
+                message,
+            },
+        }
+    }
+}
+
+impl From<MTLLoadError> for AssetError {
+    fn from(error: MTLLoadError) -> Self {
+        match error {
+            MTLLoadError::FileNotFound(path, source) => AssetError::MissingFile { path, source },
+            MTLLoadError::Read(path, source) => AssetError::Read { path, source },
+            MTLLoadError::Parse(path, line, message) => AssetError::Parse {
+                path,
+                line: line + 1,
+                message,
+            },
+            MTLLoadError::MtlNotFound(name, path) => AssetError::MissingMaterial { name, path },
+        }
+    }
+}
+
+impl From<PLYLoadError> for AssetError {
+    fn from(error: PLYLoadError) -> Self {
+        match error {
+            PLYLoadError::FileNotFound(path, source) => AssetError::MissingFile { path, source },
+            PLYLoadError::Read(path, source) => AssetError::Read { path, source },
+            // the ply parser counts lines from 1, since the header is read line by line
+            PLYLoadError::Parse(path, line, message) => AssetError::Parse {
+                path,
+                line,
+                message,
+            },
+            PLYLoadError::Data(path, message) => AssetError::BadData { path, message },
+        }
+    }
+}
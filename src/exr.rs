@@ -0,0 +1,349 @@
+// a reader for single part, scanline OpenEXR images, which is how most renderers and painting
+// tools save environment maps. uncompressed, RLE, ZIPS and ZIP files are read; the wavelet
+// and lossy compressions (PIZ, PXR24, B44, DWA) and tiled, deep or multipart files are not.
+// the R, G, B and A channels (or Y, for greyscale) are read as half or float and returned as
+// linear rgba floats, with a missing alpha being 1
+
+use std::io::Read;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const TILED_FLAG: u32 = 0x200;
+const DEEP_FLAG: u32 = 0x800;
+const MULTIPART_FLAG: u32 = 0x1000;
+
+pub struct ExrImage {
+    pub width: u32,
+    pub height: u32,
+    // rgba, row by row from the top
+    pub pixels: Vec<[f32; 4]>,
+    // whether any channel was stored as 32 bit float, so the data has more precision than a
+    // half float texture would keep
+    pub has_float_channels: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PixelType {
+    Uint,
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn size(self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            PixelType::Uint | PixelType::Float => 4,
+        }
+    }
+}
+
+struct Channel {
+    name: String,
+    pixel_type: PixelType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Rle,
+    Zips,
+    Zip,
+}
+
+impl Compression {
+    fn lines_per_block(self) -> usize {
+        match self {
+            Compression::None | Compression::Rle | Compression::Zips => 1,
+            Compression::Zip => 16,
+        }
+    }
+}
+
+pub fn is_exr(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+// a cursor over the file which fails instead of panicking when the data runs out
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let data = self
+            .offset
+            .checked_add(count)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or_else(|| "the file is cut off".to_string())?;
+        self.offset += count;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        let b = self.take(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes([
+            b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+        ]))
+    }
+
+    // a null terminated string, as used for attribute and channel names
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.bytes[self.offset..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| "the file is cut off".to_string())?;
+        let name = String::from_utf8_lossy(self.take(length)?).into_owned();
+        self.offset += 1;
+        Ok(name)
+    }
+}
+
+fn parse_channels(data: &[u8]) -> Result<Vec<Channel>, String> {
+    let mut reader = Reader {
+        bytes: data,
+        offset: 0,
+    };
+    let mut channels = Vec::new();
+    loop {
+        let name = reader.name()?;
+        if name.is_empty() {
+            return Ok(channels);
+        }
+        let pixel_type = match reader.i32()? {
+            0 => PixelType::Uint,
+            1 => PixelType::Half,
+            2 => PixelType::Float,
+            other => return Err(format!("channel {} has unknown pixel type {}", name, other)),
+        };
+        // pLinear and three reserved bytes
+        reader.take(4)?;
+        let x_sampling = reader.i32()?;
+        let y_sampling = reader.i32()?;
+        if x_sampling != 1 || y_sampling != 1 {
+            return Err(format!(
+                "channel {} is subsampled, which isn't supported",
+                name
+            ));
+        }
+        channels.push(Channel { name, pixel_type });
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Result<ExrImage, String> {
+    if !is_exr(bytes) {
+        return Err("not an openexr file".to_string());
+    }
+    let mut reader = Reader { bytes, offset: 4 };
+
+    let version = reader.i32()? as u32;
+    if version & TILED_FLAG != 0 {
+        return Err("tiled exr files aren't supported, save it as scanlines".to_string());
+    }
+    if version & (DEEP_FLAG | MULTIPART_FLAG) != 0 {
+        return Err("deep and multipart exr files aren't supported".to_string());
+    }
+
+    let mut channels = None;
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = reader.name()?;
+        if name.is_empty() {
+            break;
+        }
+        let _type_name = reader.name()?;
+        let size = reader.i32()?;
+        let value = reader.take(size.max(0) as usize)?;
+
+        match name.as_str() {
+            "channels" => channels = Some(parse_channels(value)?),
+            "compression" => {
+                compression = Some(match value.first() {
+                    Some(0) => Compression::None,
+                    Some(1) => Compression::Rle,
+                    Some(2) => Compression::Zips,
+                    Some(3) => Compression::Zip,
+                    Some(other) => {
+                        return Err(format!(
+                            "compression {} isn't supported, save it with zip or no compression",
+                            other
+                        ));
+                    }
+                    None => return Err("empty compression attribute".to_string()),
+                })
+            }
+            "dataWindow" => {
+                let mut window = Reader {
+                    bytes: value,
+                    offset: 0,
+                };
+                data_window = Some([window.i32()?, window.i32()?, window.i32()?, window.i32()?]);
+            }
+            _ => {}
+        }
+    }
+
+    let channels = channels.ok_or_else(|| "missing channels attribute".to_string())?;
+    let compression = compression.ok_or_else(|| "missing compression attribute".to_string())?;
+    let [x_min, y_min, x_max, y_max] =
+        data_window.ok_or_else(|| "missing dataWindow attribute".to_string())?;
+    if x_max < x_min || y_max < y_min {
+        return Err("the data window is empty".to_string());
+    }
+    let width = (x_max - x_min + 1) as usize;
+    let height = (y_max - y_min + 1) as usize;
+
+    // where each channel's value goes in the rgba pixel. channels are stored sorted by name,
+    // which is the order they were listed in
+    let targets = channels
+        .iter()
+        .map(|c| match c.name.as_str() {
+            "R" => Some(&[0][..]),
+            "G" => Some(&[1][..]),
+            "B" => Some(&[2][..]),
+            "A" => Some(&[3][..]),
+            "Y" => Some(&[0, 1, 2][..]),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if targets.iter().all(Option::is_none) {
+        return Err("no R, G, B, A or Y channels".to_string());
+    }
+
+    let line_size: usize = channels.iter().map(|c| c.pixel_type.size() * width).sum();
+    let lines_per_block = compression.lines_per_block();
+    let block_count = height.div_ceil(lines_per_block);
+
+    let mut offsets = Vec::with_capacity(block_count);
+    for _ in 0..block_count {
+        offsets.push(reader.u64()? as usize);
+    }
+
+    let mut pixels = vec![[0.0, 0.0, 0.0, 1.0]; width * height];
+    for offset in offsets {
+        let mut block = Reader { bytes, offset };
+        let first_line = block.i32()? - y_min;
+        let size = block.i32()?.max(0) as usize;
+        let data = block.take(size)?;
+
+        if first_line < 0 || first_line as usize >= height {
+            return Err(format!(
+                "a block starts at line {}, outside the image",
+                first_line
+            ));
+        }
+        let first_line = first_line as usize;
+        let line_count = lines_per_block.min(height - first_line);
+        let expected = line_size * line_count;
+
+        // blocks which didn't get any smaller are stored uncompressed
+        let data = if compression == Compression::None || size == expected {
+            data.to_vec()
+        } else {
+            decompress(compression, data, expected)?
+        };
+        if data.len() != expected {
+            return Err(format!(
+                "the block at line {} has {} bytes instead of {}",
+                first_line,
+                data.len(),
+                expected
+            ));
+        }
+
+        // each line holds all of its first channel's values, then the second's, and so on
+        for (line, line_data) in data.chunks_exact(line_size).enumerate() {
+            let row = &mut pixels[(first_line + line) * width..][..width];
+            let mut start = 0;
+            for (channel, targets) in channels.iter().zip(&targets) {
+                let size = channel.pixel_type.size();
+                let values = &line_data[start..start + size * width];
+                start += size * width;
+
+                let Some(targets) = targets else {
+                    continue;
+                };
+                for (pixel, value) in row.iter_mut().zip(values.chunks_exact(size)) {
+                    let value = match channel.pixel_type {
+                        PixelType::Half => half::f16::from_le_bytes([value[0], value[1]]).to_f32(),
+                        PixelType::Float => {
+                            f32::from_le_bytes([value[0], value[1], value[2], value[3]])
+                        }
+                        PixelType::Uint => {
+                            u32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f32
+                        }
+                    };
+                    for &target in *targets {
+                        pixel[target] = value;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(ExrImage {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+        has_float_channels: channels
+            .iter()
+            .zip(&targets)
+            .any(|(c, t)| t.is_some() && c.pixel_type == PixelType::Float),
+    })
+}
+
+fn decompress(compression: Compression, data: &[u8], expected: usize) -> Result<Vec<u8>, String> {
+    let mut packed = Vec::with_capacity(expected);
+    match compression {
+        Compression::None => return Ok(data.to_vec()),
+        Compression::Rle => {
+            // a negative count is followed by that many literal bytes, otherwise the next
+            // byte is repeated count + 1 times
+            let mut reader = Reader {
+                bytes: data,
+                offset: 0,
+            };
+            while reader.offset < data.len() {
+                let count = reader.u8()? as i8;
+                if count < 0 {
+                    packed.extend_from_slice(reader.take(-(count as i32) as usize)?);
+                } else {
+                    let value = reader.u8()?;
+                    packed.extend(std::iter::repeat_n(value, count as usize + 1));
+                }
+            }
+        }
+        Compression::Zips | Compression::Zip => {
+            flate2::read::ZlibDecoder::new(data)
+                .read_to_end(&mut packed)
+                .map_err(|e| format!("could not inflate a block: {}", e))?;
+        }
+    }
+
+    // the bytes were delta encoded...
+    for i in 1..packed.len() {
+        packed[i] = (packed[i - 1] as i32 + packed[i] as i32 - 128) as u8;
+    }
+
+    // ...after splitting the even and odd bytes into two halves
+    let (first, second) = packed.split_at(packed.len().div_ceil(2));
+    let mut unpacked = Vec::with_capacity(packed.len());
+    for i in 0..second.len() {
+        unpacked.push(first[i]);
+        unpacked.push(second[i]);
+    }
+    if first.len() > second.len() {
+        unpacked.push(first[first.len() - 1]);
+    }
+    Ok(unpacked)
+}
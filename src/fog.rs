@@ -0,0 +1,549 @@
+// volumetric fog lit by the scene's lights, in a grid of froxels: cells of the camera's frustum,
+// evenly spread across the screen and spaced further apart with distance. every frame
+//
+//   - the inject pass gives each froxel the fog's density there, a height fog thinning with
+//     altitude and broken up by noise drifting on the wind, and the light it scatters towards
+//     the camera. that's every light's, shadowed by the shadow maps (see shadows.rs), and the
+//     generated sky's all around it (see ibl.rs)
+//   - the integrate pass marches each column of froxels away from the camera, adding up the
+//     light scattered towards the camera and how much of what's behind still gets through
+//   - the resolve pass looks up each pixel's depth in that, and adds the fog over the frame
+//
+// the transparent meshes and particles are drawn before it, so they're fogged as far as
+// whatever's behind them. toggled with F9
+
+use cgmath::SquareMatrix;
+
+use crate::{camera, texture, uploads::UploadBelt};
+
+// the froxels across the screen, down it and away from the camera
+const GRID_SIZE: [u32; 3] = [160, 90, 64];
+const WORKGROUP_SIZE: u32 = 4;
+const VOLUME_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// where the first slice of froxels starts; nearer than this there's no fog
+const NEAR: f32 = 0.5;
+// the radiance all around in place of the generated sky's, when a loaded environment or the
+// flat ambient color is used instead
+const FLAT_AMBIENT: f32 = 0.3;
+// the first of the spherical harmonics is the constant 0.282095, so this over it is 1 everywhere
+const UNIT_SH: f32 = 1.0 / 0.282095;
+
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+}
+
+// set through the console as fog.density, fog.height and so on
+#[derive(Debug, Clone, Copy)]
+pub struct FogSettings {
+    // the fraction of light lost per world unit at the fog's base height
+    pub density: f32,
+    // linear rgb, scattering that much of each channel's light
+    pub color: [f32; 3],
+    // the height the fog is densest below
+    pub height: f32,
+    // how quickly it thins above that height, per world unit. 0 fills the whole world evenly
+    pub height_falloff: f32,
+    // from 0 for smooth fog to 1 for fog broken into patches
+    pub noise: f32,
+    // the patches' size in world units
+    pub noise_scale: f32,
+    // world units per second the patches drift along
+    pub wind: [f32; 3],
+    // henyey greenstein's g, from -1 scattering light back towards the light to 1 scattering
+    // it on forwards, which makes halos around lights seen through the fog
+    pub anisotropy: f32,
+    // how far the froxels reach; everything further is fogged as if it were this far
+    pub distance: f32,
+    // how much of the light from all around is scattered, so fog out of every light's reach
+    // isn't black
+    pub ambient: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            density: 0.05,
+            color: [1.0; 3],
+            height: 0.0,
+            height_falloff: 0.2,
+            noise: 0.5,
+            noise_scale: 8.0,
+            wind: [1.0, 0.0, 0.5],
+            anisotropy: 0.4,
+            distance: 80.0,
+            ambient: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogUniform {
+    inverse_view_proj: [[f32; 4]; 4],
+    camera_position: [f32; 3],
+    // the scene's seconds, which the noise drifts by
+    time: f32,
+    color: [f32; 3],
+    density: f32,
+    wind: [f32; 3],
+    noise: f32,
+    height: f32,
+    height_falloff: f32,
+    anisotropy: f32,
+    noise_scale: f32,
+    near: f32,
+    far: f32,
+    ambient: f32,
+    _padding: f32,
+    // the sky's radiance as spherical harmonics, in rgb
+    sky: [[f32; 4]; 9],
+}
+
+pub struct VolumetricFog {
+    pub settings: FogSettings,
+    // off until toggled, since it changes how every scene looks
+    pub is_enabled: bool,
+    uniform: FogUniform,
+    uniform_buffer: wgpu::Buffer,
+    inject_pipeline: wgpu::ComputePipeline,
+    inject_bind_group: wgpu::BindGroup,
+    integrate_pipeline: wgpu::ComputePipeline,
+    integrate_bind_group: wgpu::BindGroup,
+    resolve_pipeline: wgpu::RenderPipeline,
+    resolve_layout: wgpu::BindGroupLayout,
+    // reads the depth texture, so it's made again whenever that is
+    resolve_bind_group: wgpu::BindGroup,
+    // the accumulated light and transmittance, read by the resolve pass
+    integrated_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl VolumetricFog {
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        depth_texture: &texture::Texture,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        let settings = FogSettings::default();
+        let uniform = FogUniform {
+            inverse_view_proj: cgmath::Matrix4::identity().into(),
+            camera_position: [0.0; 3],
+            time: 0.0,
+            color: settings.color,
+            density: settings.density,
+            wind: settings.wind,
+            noise: settings.noise,
+            height: settings.height,
+            height_falloff: settings.height_falloff,
+            anisotropy: settings.anisotropy,
+            noise_scale: settings.noise_scale,
+            near: NEAR,
+            far: settings.distance,
+            ambient: settings.ambient,
+            _padding: 0.0,
+            sky: sky_uniform(None),
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("fog uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        // the light scattered in each froxel and its extinction, then the same added up from
+        // the camera
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING;
+        let scattering = create_volume(device, "fog scattering volume", usage);
+        let integrated = create_volume(device, "fog integrated volume", usage);
+        let scattering_view = scattering.create_view(&Default::default());
+        let integrated_view = integrated.create_view(&Default::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("fog sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let volume_entry = |binding, visibility, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D3,
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+            },
+            count: None,
+        };
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: VOLUME_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: None,
+        };
+
+        let inject_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog inject bind group layout"),
+            entries: &[uniform_entry(wgpu::ShaderStages::COMPUTE), storage_entry(1)],
+        });
+        let integrate_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog integrate bind group layout"),
+            entries: &[
+                uniform_entry(wgpu::ShaderStages::COMPUTE),
+                volume_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                storage_entry(2),
+            ],
+        });
+        let resolve_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog resolve bind group layout"),
+            entries: &[
+                uniform_entry(wgpu::ShaderStages::FRAGMENT),
+                volume_entry(1, wgpu::ShaderStages::FRAGMENT, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let inject_pipeline = crate::vxgi::create_compute_pipeline(
+            device,
+            "fog inject pipeline",
+            &[per_frame_layout, &inject_layout],
+            crate::shaders::wgsl("fog_inject.wgsl", include_str!("shaders/fog_inject.wgsl")),
+        );
+        let integrate_pipeline = crate::vxgi::create_compute_pipeline(
+            device,
+            "fog integrate pipeline",
+            &[&integrate_layout],
+            crate::shaders::wgsl(
+                "fog_integrate.wgsl",
+                include_str!("shaders/fog_integrate.wgsl"),
+            ),
+        );
+
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "fog_resolve.wgsl",
+            include_str!("shaders/fog_resolve.wgsl"),
+        ));
+        let resolve_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("fog resolve pipeline layout"),
+                bind_group_layouts: &[&resolve_layout],
+                immediate_size: 0,
+            });
+        let resolve_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fog resolve pipeline"),
+            layout: Some(&resolve_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                // the fog's light in rgb and its transmittance in alpha, so the frame is
+                // dimmed by the transmittance and the light added on top
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::SrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // a single triangle covering the screen, generated in the vertex shader
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let inject_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog inject bind group"),
+            layout: &inject_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&scattering_view),
+                },
+            ],
+        });
+        let integrate_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog integrate bind group"),
+            layout: &integrate_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&scattering_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&integrated_view),
+                },
+            ],
+        });
+        let resolve_bind_group = Self::create_resolve_bind_group(
+            device,
+            &resolve_layout,
+            &uniform_buffer,
+            &integrated_view,
+            &sampler,
+            depth_texture,
+        );
+
+        Self {
+            settings,
+            is_enabled: false,
+            uniform,
+            uniform_buffer,
+            inject_pipeline,
+            inject_bind_group,
+            integrate_pipeline,
+            integrate_bind_group,
+            resolve_pipeline,
+            resolve_layout,
+            resolve_bind_group,
+            integrated_view,
+            sampler,
+        }
+    }
+
+    fn create_resolve_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        integrated_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        depth_texture: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        // the stencil can't be read along with the depth
+        let depth_view = depth_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                label: Some("fog depth view"),
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog resolve bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(integrated_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, depth_texture: &texture::Texture) {
+        self.resolve_bind_group = Self::create_resolve_bind_group(
+            device,
+            &self.resolve_layout,
+            &self.uniform_buffer,
+            &self.integrated_view,
+            &self.sampler,
+            depth_texture,
+        );
+    }
+
+    // `seconds` is the scene's time, so the fog holds still while it's paused.
+    // `sky_radiance_sh` is the generated sky's, see ImageBasedLighting::sky_radiance_sh
+    pub fn update(
+        &mut self,
+        uploads: &mut UploadBelt,
+        camera: &camera::Camera,
+        projection: &camera::Projection,
+        seconds: f32,
+        sky_radiance_sh: Option<[[f32; 3]; 9]>,
+    ) {
+        let settings = &self.settings;
+        let view_proj = projection.perspective_matrix() * camera.view_matrix();
+        self.uniform = FogUniform {
+            inverse_view_proj: view_proj
+                .invert()
+                .unwrap_or(cgmath::Matrix4::identity())
+                .into(),
+            camera_position: camera.position.into(),
+            time: seconds,
+            color: settings.color,
+            density: settings.density.max(0.0),
+            wind: settings.wind,
+            noise: settings.noise.clamp(0.0, 1.0),
+            height: settings.height,
+            height_falloff: settings.height_falloff.max(0.0),
+            anisotropy: settings.anisotropy.clamp(-0.99, 0.99),
+            noise_scale: settings.noise_scale.max(1e-3),
+            near: NEAR,
+            far: settings.distance.max(NEAR * 2.0),
+            ambient: settings.ambient.max(0.0),
+            _padding: 0.0,
+            sky: sky_uniform(sky_radiance_sh),
+        };
+        uploads.write(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // fills the froxels and adds them up, after the shadow maps are drawn
+    pub fn encode(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        per_frame_bind_group: &wgpu::BindGroup,
+    ) {
+        let [width, height, depth] = GRID_SIZE;
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("fog pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.inject_pipeline);
+        compute_pass.set_bind_group(0, per_frame_bind_group, &[0]);
+        compute_pass.set_bind_group(1, &self.inject_bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            depth.div_ceil(WORKGROUP_SIZE),
+        );
+
+        // a column of froxels per invocation
+        compute_pass.set_pipeline(&self.integrate_pipeline);
+        compute_pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE * 2),
+            height.div_ceil(WORKGROUP_SIZE * 2),
+            1,
+        );
+    }
+
+    // adds the fog over the scene in `target_view`, after everything else is drawn into it
+    pub fn resolve(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("fog resolve pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.resolve_pipeline);
+        render_pass.set_bind_group(0, &self.resolve_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.is_enabled { "[FOG]" } else { "" }
+    }
+}
+
+fn sky_uniform(sky_radiance_sh: Option<[[f32; 3]; 9]>) -> [[f32; 4]; 9] {
+    let mut flat = [[0.0; 3]; 9];
+    flat[0] = [FLAT_AMBIENT * UNIT_SH; 3];
+    sky_radiance_sh
+        .unwrap_or(flat)
+        .map(|[r, g, b]| [r, g, b, 0.0])
+}
+
+fn create_volume(device: &wgpu::Device, label: &str, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    let [width, height, depth] = GRID_SIZE;
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: depth,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: VOLUME_FORMAT,
+        usage,
+        view_formats: &[],
+    })
+}
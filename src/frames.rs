@@ -0,0 +1,80 @@
+// frames in flight: the cpu prepares the next frame while the gpu may still be drawing the
+// previous one, so anything rewritten every frame gets a copy per frame in flight. each copy is
+// fenced with the submission that last used it, and the cpu only waits for the gpu when it
+// comes back around to a copy which is still being read
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+struct Fence {
+    // set from on_submitted_work_done, once the gpu has finished the frame's work
+    is_done: Arc<AtomicBool>,
+    submission: Option<wgpu::SubmissionIndex>,
+}
+
+pub struct FramesInFlight<T> {
+    frames: Vec<T>,
+    fences: Vec<Fence>,
+    current: usize,
+}
+
+impl<T> FramesInFlight<T> {
+    pub fn new(mut create: impl FnMut(usize) -> T) -> Self {
+        Self {
+            frames: (0..FRAMES_IN_FLIGHT).map(&mut create).collect(),
+            fences: (0..FRAMES_IN_FLIGHT)
+                .map(|_| Fence {
+                    is_done: Arc::new(AtomicBool::new(true)),
+                    submission: None,
+                })
+                .collect(),
+            current: 0,
+        }
+    }
+
+    // the copy the frame being prepared reads and writes
+    pub fn current(&self) -> &T {
+        &self.frames[self.current]
+    }
+
+    // every copy, eg to rebuild bind groups when something they all share is replaced
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.frames.iter_mut()
+    }
+
+    // moves on to the next copy before any of the frame's writes, waiting until the gpu is
+    // done with it if the frame which last used it is still in flight
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        self.current = (self.current + 1) % self.frames.len();
+
+        // callbacks only run while the device is polled
+        if let Err(e) = device.poll(wgpu::PollType::Poll) {
+            log::error!("polling for finished frames: {}", e);
+        }
+        let fence = &mut self.fences[self.current];
+        if fence.is_done.load(Ordering::Acquire) {
+            return;
+        }
+        // polling also runs the callback, which sets is_done
+        if let Err(e) = device.poll(wgpu::PollType::Wait {
+            submission_index: fence.submission.take(),
+            timeout: None,
+        }) {
+            log::error!("waiting for frame {} to finish: {}", self.current, e);
+        }
+    }
+
+    // fences the current copy with the frame's last submission
+    pub fn end_frame(&mut self, queue: &wgpu::Queue, submission: wgpu::SubmissionIndex) {
+        let fence = &mut self.fences[self.current];
+        fence.is_done.store(false, Ordering::Release);
+        fence.submission = Some(submission);
+
+        let is_done = fence.is_done.clone();
+        queue.on_submitted_work_done(move || is_done.store(true, Ordering::Release));
+    }
+}
@@ -0,0 +1,407 @@
+// culls the opaque meshes against the camera's frustum on the gpu and draws them indirectly,
+// so a scene with tens of thousands of objects doesn't cost a draw call and a frustum test on
+// the cpu for each of them. every frame, each mesh of each object to be drawn becomes an
+// instance with the mesh's bounding sphere, and instances of meshes which share their buffers
+// and material are grouped into one draw. a compute pass moves each sphere into the world with
+// its object's transform, tests it against the frustum and packs the survivors at the start of
+// their draw's range of the argument buffer, as an indirect draw with the object's index for
+// its first instance, which the culled pipelines' vertex shader reads the transform with.
+// each draw is then one multi_draw_indexed_indirect_count over its range, or where that isn't
+// supported, multi_draw_indexed_indirect over all of it, with no instances past the survivors
+
+use crate::{
+    model::{self, Vertex},
+    pipeline_cache::PipelineCache,
+    render_stats, scene,
+};
+
+// indirect draws have to be able to start at another instance than 0
+pub const FEATURES: wgpu::Features = wgpu::Features::INDIRECT_FIRST_INSTANCE;
+// requested when the adapter has it, otherwise each draw goes through its whole range
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::MULTI_DRAW_INDIRECT_COUNT;
+
+const ARGUMENTS_SIZE: wgpu::BufferAddress =
+    std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as wgpu::BufferAddress;
+const WORKGROUP_SIZE: u32 = 64;
+
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    let flags = wgpu::DownlevelFlags::COMPUTE_SHADERS
+        | wgpu::DownlevelFlags::INDIRECT_EXECUTION
+        | wgpu::DownlevelFlags::VERTEX_STORAGE;
+    adapter.features().contains(FEATURES)
+        && adapter.get_downlevel_capabilities().flags.contains(flags)
+}
+
+// one mesh of one object, as the compute pass sees it
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullInstance {
+    // the mesh's bounding sphere, in model space
+    center: [f32; 3],
+    radius: f32,
+    // the object's slot in the scene's transform buffer
+    object: u32,
+    index_count: u32,
+    // which draw it's part of, and where in the argument buffer that draw's range starts
+    draw: u32,
+    first_argument: u32,
+}
+
+// the instances of one mesh's buffers and material, drawn together
+struct CulledDraw {
+    // the first instance, whose mesh is drawn
+    object: usize,
+    mesh: usize,
+    first_argument: u32,
+    instance_count: u32,
+}
+
+pub struct GpuCulling {
+    cull_pipeline: wgpu::ComputePipeline,
+    cull_bind_group_layout: wgpu::BindGroupLayout,
+    // the scene's transforms, bound in place of the per object bind group while drawing
+    transforms_bind_group_layout: wgpu::BindGroupLayout,
+    transforms_bind_group: Option<wgpu::BindGroup>,
+    pipeline: wgpu::RenderPipeline,
+    pipeline_raytraced: Option<wgpu::RenderPipeline>,
+    // an argument for every instance, and a count for every draw
+    instance_buffer: wgpu::Buffer,
+    argument_buffer: wgpu::Buffer,
+    count_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    draw_capacity: usize,
+    // the last encode's, which the next draw draws
+    instances: Vec<CullInstance>,
+    draws: Vec<CulledDraw>,
+    has_draw_count: bool,
+}
+
+impl GpuCulling {
+    // the render pipelines are the opaque scene pipelines, reading the transforms from storage
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &mut PipelineCache,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        per_pass_layout: &wgpu::BindGroupLayout,
+        voxel_gi_layout: &wgpu::BindGroupLayout,
+        transform_stride: u32,
+        has_ray_tracing: bool,
+        color_format: wgpu::TextureFormat,
+        blend: wgpu::BlendState,
+    ) -> anyhow::Result<Self> {
+        let stride = transform_stride.to_string();
+        let storage_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let cull_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("gpu culling bind group layout"),
+                entries: &[
+                    // transforms
+                    storage_entry(0, wgpu::ShaderStages::COMPUTE, true),
+                    // instances
+                    storage_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                    // arguments
+                    storage_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                    // counts
+                    storage_entry(3, wgpu::ShaderStages::COMPUTE, false),
+                ],
+            });
+        let cull_pipeline = crate::vxgi::create_compute_pipeline(
+            device,
+            "gpu culling pipeline",
+            &[per_frame_layout, &cull_bind_group_layout],
+            crate::shaders::wgsl_with_defines(
+                "gpu_culling.wgsl",
+                include_str!("shaders/gpu_culling.wgsl"),
+                &[("TRANSFORM_STRIDE", &stride)],
+            ),
+        );
+
+        let transforms_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("gpu culled transforms bind group layout"),
+                entries: &[storage_entry(0, wgpu::ShaderStages::VERTEX, true)],
+            });
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("gpu culled render pipeline layout"),
+                bind_group_layouts: &[
+                    per_frame_layout,
+                    per_pass_layout,
+                    &transforms_bind_group_layout,
+                    voxel_gi_layout,
+                ],
+                immediate_size: 0,
+            });
+
+        let source = crate::shaders::preprocess(
+            "shader.wgsl",
+            include_str!("shaders/shader.wgsl"),
+            &[("GPU_CULLING", ""), ("TRANSFORM_STRIDE", &stride)],
+        )?;
+        let mut create_pipeline = |label, source: String| {
+            pipeline_cache.render_pipeline(
+                device,
+                &render_pipeline_layout,
+                color_format,
+                Some(crate::texture::Texture::DEPTH_FORMAT),
+                &[model::ModelVertex::desc()],
+                wgpu::ShaderModuleDescriptor {
+                    label: Some(label),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                },
+                wgpu::PolygonMode::Fill,
+                blend,
+                true,
+            )
+        };
+        let raytraced_source = if has_ray_tracing {
+            crate::raytracing::shader_source(&source)
+        } else {
+            None
+        };
+        let pipeline = create_pipeline("gpu culled shader", source);
+        let pipeline_raytraced =
+            raytraced_source.map(|source| create_pipeline("raytraced gpu culled shader", source));
+
+        let (instance_capacity, draw_capacity) = (1024, 64);
+        let (instance_buffer, argument_buffer) = create_instance_buffers(device, instance_capacity);
+        Ok(Self {
+            cull_pipeline,
+            cull_bind_group_layout,
+            transforms_bind_group_layout,
+            transforms_bind_group: None,
+            pipeline,
+            pipeline_raytraced,
+            instance_buffer,
+            argument_buffer,
+            count_buffer: create_count_buffer(device, draw_capacity),
+            instance_capacity,
+            draw_capacity,
+            instances: Vec::new(),
+            draws: Vec::new(),
+            has_draw_count: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+        })
+    }
+
+    pub fn pipeline(&self, raytraced: bool) -> &wgpu::RenderPipeline {
+        match &self.pipeline_raytraced {
+            Some(pipeline) if raytraced => pipeline,
+            _ => &self.pipeline,
+        }
+    }
+
+    // groups the meshes, given as object and mesh indices sorted by material and then by mesh,
+    // into draws and culls them, after the scene's transforms are uploaded
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        render_stats: &mut render_stats::RenderStats,
+        command_encoder: &mut wgpu::CommandEncoder,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+        scene: &scene::Scene,
+        meshes: &[(usize, usize)],
+    ) {
+        self.instances.clear();
+        self.draws.clear();
+        for &(object, mesh_index) in meshes {
+            let mesh = &scene.objects()[object].model.meshes[mesh_index];
+            let is_shared = self.draws.last().is_some_and(|draw| {
+                let first = &scene.objects()[draw.object].model.meshes[draw.mesh];
+                first.vertex_buffer == mesh.vertex_buffer && first.material == mesh.material
+            });
+            if !is_shared {
+                self.draws.push(CulledDraw {
+                    object,
+                    mesh: mesh_index,
+                    first_argument: self.instances.len() as u32,
+                    instance_count: 0,
+                });
+            }
+
+            let draw_index = self.draws.len() - 1;
+            let draw = &mut self.draws[draw_index];
+            draw.instance_count += 1;
+            self.instances.push(CullInstance {
+                center: mesh.bounds.center().into(),
+                radius: mesh.bounding_radius,
+                object: object as u32,
+                index_count: mesh.index_count,
+                draw: draw_index as u32,
+                first_argument: draw.first_argument,
+            });
+        }
+        if self.instances.is_empty() {
+            return;
+        }
+
+        if self.instances.len() > self.instance_capacity {
+            self.instance_capacity = self.instances.len().next_power_of_two();
+            (self.instance_buffer, self.argument_buffer) =
+                create_instance_buffers(device, self.instance_capacity);
+        }
+        if self.draws.len() > self.draw_capacity {
+            self.draw_capacity = self.draws.len().next_power_of_two();
+            self.count_buffer = create_count_buffer(device, self.draw_capacity);
+        }
+
+        let instance_bytes = bytemuck::cast_slice(&self.instances);
+        render_stats.write_buffer(queue, &self.instance_buffer, 0, instance_bytes);
+        // no instances in any argument, so whatever isn't filled in draws nothing
+        command_encoder.clear_buffer(
+            &self.argument_buffer,
+            0,
+            Some(self.instances.len() as wgpu::BufferAddress * ARGUMENTS_SIZE),
+        );
+        command_encoder.clear_buffer(
+            &self.count_buffer,
+            0,
+            Some(self.draws.len() as wgpu::BufferAddress * 4),
+        );
+
+        // the transform buffer is replaced when the scene outgrows it, so these are made again
+        // every frame
+        let cull_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gpu culling bind group"),
+            layout: &self.cull_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(scene.transforms()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.instance_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(instance_bytes.len() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.argument_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.count_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        self.transforms_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gpu culled transforms bind group"),
+            layout: &self.transforms_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(scene.transforms()),
+            }],
+        }));
+
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("gpu culling pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.cull_pipeline);
+        compute_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        compute_pass.set_bind_group(1, &cull_bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            (self.instances.len() as u32).div_ceil(WORKGROUP_SIZE),
+            1,
+            1,
+        );
+    }
+
+    // draws what the last encode let through, with one of the pipelines and the voxel gi bind
+    // group already set
+    pub fn draw(
+        &self,
+        render_pass: &mut render_stats::CountingRenderPass,
+        scene: &scene::Scene,
+        materials: &[model::Material],
+    ) {
+        let Some(transforms_bind_group) = &self.transforms_bind_group else {
+            return;
+        };
+
+        render_pass.set_bind_group(2, transforms_bind_group, &[]);
+        for (i, draw) in self.draws.iter().enumerate() {
+            let mesh = &scene.objects()[draw.object].model.meshes[draw.mesh];
+            let material = &materials[mesh.material];
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+            render_pass.set_bind_group(1, &material.bind_group, &[]);
+
+            render_pass.insert_debug_marker(&format!(
+                "{} ({}) x{}",
+                mesh.name, material.name, draw.instance_count
+            ));
+            let offset = draw.first_argument as wgpu::BufferAddress * ARGUMENTS_SIZE;
+            if self.has_draw_count {
+                render_pass.multi_draw_indexed_indirect_count(
+                    &self.argument_buffer,
+                    offset,
+                    &self.count_buffer,
+                    i as wgpu::BufferAddress * 4,
+                    draw.instance_count,
+                );
+            } else {
+                render_pass.multi_draw_indexed_indirect(
+                    &self.argument_buffer,
+                    offset,
+                    draw.instance_count,
+                );
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "[GPU CULL {} inst in {} draws]",
+            self.instances.len(),
+            self.draws.len()
+        )
+    }
+}
+
+fn create_instance_buffers(device: &wgpu::Device, capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+    let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("gpu culling instance buffer"),
+        size: (capacity * std::mem::size_of::<CullInstance>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let argument_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("gpu culling argument buffer"),
+        size: capacity as wgpu::BufferAddress * ARGUMENTS_SIZE,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::INDIRECT
+            | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    (instance_buffer, argument_buffer)
+}
+
+fn create_count_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("gpu culling count buffer"),
+        size: capacity as wgpu::BufferAddress * 4,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::INDIRECT
+            | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
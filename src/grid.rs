@@ -0,0 +1,104 @@
+// a grid on the ground plane and the world axes at the origin, to see which way is up and how
+// big things are in an otherwise empty scene. toggled with H.
+// the grid is one big square under the camera, which moves with it so it never runs out, with
+// the lines drawn in its fragment shader so they stay sharp at any distance. the x and z axes
+// show on the grid in red and blue, and a gizmo of three debug lines marks the origin
+
+const AXIS_LENGTH: f32 = 2.0;
+
+pub struct Grid {
+    pub is_visible: bool,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Grid {
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        depth_compare: wgpu::CompareFunction,
+        sample_count: u32,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("grid pipeline layout"),
+            bind_group_layouts: &[per_frame_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "grid.wgsl",
+            include_str!("shaders/grid.wgsl"),
+        ));
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("grid pipeline"),
+            layout: Some(&pipeline_layout),
+            // the square's corners come from the vertex index, so there are no buffers
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // seen from below too
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // tested against the scene so models hide the grid, but not written so the grid's
+            // faded out parts don't hide anything
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            is_visible: false,
+            pipeline,
+        }
+    }
+
+    // x red, y green and z blue, from the origin out along each positive axis
+    pub fn draw_axes(&self, debug_draw: &mut crate::debug_draw::DebugDraw) {
+        for axis in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] {
+            debug_draw.line([0.0; 3], axis.map(|c| c * AXIS_LENGTH), axis);
+        }
+    }
+
+    // draws into a pass which has the scene's depth attached
+    pub fn draw(
+        &self,
+        render_pass: &mut crate::render_stats::CountingRenderPass,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+    ) {
+        if !self.is_visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
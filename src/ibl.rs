@@ -0,0 +1,571 @@
+// image based lighting: ambient light from an environment instead of the flat ambient color.
+// an equirectangular environment (an exr, hdr or plain image, or a generated sky until one is
+// loaded) is convolved once at load into a small irradiance cube for diffuse light and a cube
+// whose mips hold the environment prefiltered for increasing roughness, for specular light.
+// a brdf lookup table, which doesn't depend on the environment, completes the split sum.
+//
+// the generated sky is lit by the first directional light: it has a sun where the light comes
+// from, and dims as the sun sets. it's baked again, and convolved again, whenever the light
+// turns, along with its spherical harmonics, which the fog is lit by
+
+use cgmath::InnerSpace;
+
+use crate::{DirectionalLight, error::AssetError, resources, texture, vxgi};
+
+pub const IRRADIANCE_SIZE: u32 = 32;
+pub const SPECULAR_SIZE: u32 = 128;
+// down to 8x8, where the roughest level is blurry enough already
+pub const SPECULAR_MIP_COUNT: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+const IRRADIANCE_SAMPLE_COUNT: u32 = 1024;
+const SPECULAR_SAMPLE_COUNT: u32 = 512;
+const WORKGROUP_SIZE: u32 = 8;
+// the generated sky's size; it's smooth, so it doesn't need many texels
+const SKY_WIDTH: u32 = 256;
+// how far the sun turns before the sky is baked again, as the cosine of the angle
+const SUN_TOLERANCE: f32 = 0.99985;
+// the sun's angular radius in the generated sky. much larger than the real one's, which would
+// be a fraction of a texel
+const SUN_RADIUS: f32 = 0.035;
+// the sun disc's radiance over the light's color. it's kept dim, since the light itself lights
+// the scene directly and this only adds its reflection in smooth surfaces
+const SUN_RADIANCE: f32 = 20.0;
+
+const CUBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// only two channels are used, but two channel float formats can't be storage textures everywhere
+const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+pub const DEFAULT_INTENSITY: f32 = 1.0;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentUniform {
+    intensity: f32,
+    is_enabled: u32,
+    specular_mip_count: u32,
+    // how wet the weather has made surfaces, see weather.rs
+    wetness: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PrefilterUniform {
+    // 0 irradiance, 1 specular
+    kind: u32,
+    roughness: f32,
+    sample_count: u32,
+    _padding: u32,
+}
+
+pub struct ImageBasedLighting {
+    uniform: EnvironmentUniform,
+    // read by the scene shader through the per frame bind group
+    pub uniform_buffer: wgpu::Buffer,
+    pub irradiance: texture::Texture,
+    pub specular: texture::Texture,
+    pub brdf_lut: texture::Texture,
+    // filters across cube faces and between the specular mips
+    pub sampler: wgpu::Sampler,
+    prefilter_layout: wgpu::BindGroupLayout,
+    prefilter_pipeline: wgpu::ComputePipeline,
+    // while the environment is the generated sky, which load() replaces
+    sky: Option<GeneratedSky>,
+}
+
+// the sun the sky was baked with, and the sky's radiance projected onto the first nine real
+// spherical harmonics, in the order of sh_basis
+struct GeneratedSky {
+    sun: Option<Sun>,
+    radiance_sh: [[f32; 3]; 9],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sun {
+    // towards the sun
+    direction: cgmath::Vector3<f32>,
+    color: [f32; 3],
+}
+
+impl Sun {
+    fn of(light: Option<&DirectionalLight>) -> Option<Self> {
+        light.map(|light| Self {
+            direction: -light.transform.forward().normalize(),
+            color: light.color,
+        })
+    }
+
+    fn is_near(&self, other: &Self) -> bool {
+        self.direction.dot(other.direction) > SUN_TOLERANCE && self.color == other.color
+    }
+}
+
+impl ImageBasedLighting {
+    // starts with the generated sky under the light's sun, which load() replaces
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sun_light: Option<&DirectionalLight>,
+    ) -> Result<Self, AssetError> {
+        let uniform = EnvironmentUniform {
+            intensity: DEFAULT_INTENSITY,
+            is_enabled: 1,
+            specular_mip_count: SPECULAR_MIP_COUNT,
+            wetness: 0.0,
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("environment uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let storage_usage =
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING;
+        let irradiance =
+            texture::Texture::builder("environment irradiance", IRRADIANCE_SIZE, IRRADIANCE_SIZE)
+                .format(CUBE_FORMAT)
+                .usage(storage_usage)
+                .cube()
+                .build(device);
+        let specular =
+            texture::Texture::builder("environment specular", SPECULAR_SIZE, SPECULAR_SIZE)
+                .format(CUBE_FORMAT)
+                .usage(storage_usage)
+                .mip_levels(SPECULAR_MIP_COUNT)
+                .cube()
+                .build(device);
+        let brdf_lut = texture::Texture::builder("brdf lut", BRDF_LUT_SIZE, BRDF_LUT_SIZE)
+            .format(BRDF_LUT_FORMAT)
+            .usage(storage_usage)
+            .build(device);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Linear,
+            ..Default::default()
+        });
+
+        let prefilter_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("environment prefilter bind group layout"),
+            entries: &[
+                // the equirectangular environment
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // one mip of a cube's faces
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: CUBE_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let prefilter_pipeline = vxgi::create_compute_pipeline(
+            device,
+            "environment prefilter pipeline",
+            &[&prefilter_layout],
+            wgpu::include_wgsl!("shaders/ibl_prefilter.wgsl"),
+        );
+
+        let mut ibl = Self {
+            uniform,
+            uniform_buffer,
+            irradiance,
+            specular,
+            brdf_lut,
+            sampler,
+            prefilter_layout,
+            prefilter_pipeline,
+            sky: None,
+        };
+        ibl.build_brdf_lut(device, queue);
+        ibl.bake_sky(device, queue, Sun::of(sun_light))?;
+        Ok(ibl)
+    }
+
+    // bakes the generated sky again when the light has turned or changed color since it was
+    // last baked. a loaded environment stays as it is
+    pub fn follow_sun(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sun_light: Option<&DirectionalLight>,
+    ) -> Result<(), AssetError> {
+        let Some(sky) = &self.sky else {
+            return Ok(());
+        };
+        let sun = Sun::of(sun_light);
+        let is_same = match (sky.sun, sun) {
+            (Some(baked), Some(sun)) => baked.is_near(&sun),
+            (baked, sun) => baked.is_none() && sun.is_none(),
+        };
+        if is_same {
+            return Ok(());
+        }
+        self.bake_sky(device, queue, sun)
+    }
+
+    // the generated sky's radiance as spherical harmonics, scaled by the intensity, or None for
+    // a loaded environment or while the flat ambient color is used instead
+    pub fn sky_radiance_sh(&self) -> Option<[[f32; 3]; 9]> {
+        let sky = self.sky.as_ref().filter(|_| self.is_enabled())?;
+        let intensity = self.uniform.intensity;
+        Some(sky.radiance_sh.map(|c| c.map(|c| c * intensity)))
+    }
+
+    fn bake_sky(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sun: Option<Sun>,
+    ) -> Result<(), AssetError> {
+        let pixels = generate_sky(SKY_WIDTH, sun);
+        let environment = texture::Texture::from_float_pixels(
+            device,
+            queue,
+            (SKY_WIDTH, SKY_WIDTH / 2),
+            &pixels,
+            false,
+            "generated sky",
+            wgpu::AddressMode::ClampToEdge,
+        )?;
+        self.prefilter(device, queue, &environment);
+        self.sky = Some(GeneratedSky {
+            sun,
+            radiance_sh: project_sh(&pixels, SKY_WIDTH),
+        });
+        Ok(())
+    }
+
+    // replaces the environment with an equirectangular image, convolving it again
+    pub fn load(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &str,
+    ) -> Result<(), AssetError> {
+        let bytes = resources::load_binary(path)?;
+        let environment = texture::Texture::from_bytes(
+            device,
+            queue,
+            &bytes,
+            path,
+            false,
+            wgpu::AddressMode::ClampToEdge,
+        )?;
+        self.prefilter(device, queue, &environment);
+        self.sky = None;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.uniform.is_enabled == 1
+    }
+
+    // the flat ambient color is used instead while this is off
+    pub fn set_enabled(&mut self, is_enabled: bool, queue: &wgpu::Queue) {
+        self.uniform.is_enabled = is_enabled as u32;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32, queue: &wgpu::Queue) {
+        self.uniform.intensity = intensity.max(0.0);
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // in the environment's uniform since every surface is shaded by it. through the upload belt,
+    // since it changes every frame while it rains
+    pub fn set_wetness(&mut self, wetness: f32, uploads: &mut crate::uploads::UploadBelt) {
+        if self.uniform.wetness != wetness {
+            self.uniform.wetness = wetness;
+            uploads.write(
+                &self.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[self.uniform]),
+            );
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.is_enabled() {
+            ""
+        } else {
+            "[FLAT AMBIENT]"
+        }
+    }
+
+    fn prefilter(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        environment: &texture::Texture,
+    ) {
+        // the longitude wraps around, the latitude stops at the poles
+        let environment_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("equirectangular environment sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // the irradiance cube, then each mip of the specular cube from sharpest to roughest
+        let targets = std::iter::once((
+            &self.irradiance,
+            0,
+            PrefilterUniform {
+                kind: 0,
+                roughness: 1.0,
+                sample_count: IRRADIANCE_SAMPLE_COUNT,
+                _padding: 0,
+            },
+        ))
+        .chain((0..SPECULAR_MIP_COUNT).map(|level| {
+            (
+                &self.specular,
+                level,
+                PrefilterUniform {
+                    kind: 1,
+                    roughness: level as f32 / (SPECULAR_MIP_COUNT - 1) as f32,
+                    sample_count: SPECULAR_SAMPLE_COUNT,
+                    _padding: 0,
+                },
+            )
+        }));
+
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("environment prefilter encoder"),
+        });
+        for (target, level, uniform) in targets {
+            let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+                device,
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("environment prefilter uniform buffer"),
+                    contents: bytemuck::cast_slice(&[uniform]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                },
+            );
+            let target_view = target.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("environment prefilter target"),
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("environment prefilter bind group"),
+                layout: &self.prefilter_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&environment.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&environment_sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&target_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let size = (target.texture.width() >> level).max(1);
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("environment prefilter pass"),
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&self.prefilter_pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            let groups = size.div_ceil(WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(groups, groups, 6);
+        }
+        queue.submit([command_encoder.finish()]);
+    }
+
+    fn build_brdf_lut(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("brdf lut bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: BRDF_LUT_FORMAT,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("brdf lut bind group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&self.brdf_lut.view),
+            }],
+        });
+        let pipeline = vxgi::create_compute_pipeline(
+            device,
+            "brdf lut pipeline",
+            &[&layout],
+            wgpu::include_wgsl!("shaders/ibl_brdf.wgsl"),
+        );
+
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("brdf lut encoder"),
+        });
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("brdf lut pass"),
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            let groups = BRDF_LUT_SIZE.div_ceil(WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(groups, groups, 1);
+        }
+        queue.submit([command_encoder.finish()]);
+    }
+}
+
+// the direction through the center of a texel of an equirectangular image, the inverse of the
+// mapping ibl_prefilter.wgsl samples with
+fn equirectangular_direction(x: u32, y: u32, width: u32, height: u32) -> cgmath::Vector3<f32> {
+    let longitude = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+    let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * std::f32::consts::PI;
+    cgmath::Vector3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+// a clear sky over a plain ground, in linear floats: a bright horizon fading to blue overhead,
+// and a dim ground, so surfaces facing up, sideways and down all get distinct light. with a sun
+// there's its disc and a glow around it, and the sky darkens as the sun goes down
+fn generate_sky(width: u32, sun: Option<Sun>) -> Vec<[f32; 4]> {
+    let height = (width / 2).max(1);
+    let zenith = [0.25, 0.45, 0.9];
+    let horizon = [0.9, 0.9, 0.95];
+    let ground = [0.2, 0.18, 0.15];
+    // from full daylight with the sun well up to a tenth of it once it has set
+    let daylight = sun.map_or(1.0, |sun| {
+        let t = ((sun.direction.y + 0.1) / 0.4).clamp(0.0, 1.0);
+        0.1 + 0.9 * t * t * (3.0 - 2.0 * t)
+    });
+
+    (0..height)
+        .flat_map(|y| {
+            (0..width).map(move |x| {
+                let direction = equirectangular_direction(x, y, width, height);
+                let mut color: [f32; 3] = if direction.y >= 0.0 {
+                    let t = direction.y.powf(0.5);
+                    std::array::from_fn(|i| horizon[i] + (zenith[i] - horizon[i]) * t)
+                } else {
+                    // a quick fade from the horizon into the ground
+                    let t = (-direction.y * 8.0).min(1.0);
+                    std::array::from_fn(|i| horizon[i] + (ground[i] - horizon[i]) * t)
+                };
+                color = color.map(|c| c * daylight);
+                // the sun and its glow are above the ground only
+                if let Some(sun) = sun.filter(|_| direction.y >= 0.0) {
+                    let cos_angle = direction.dot(sun.direction).max(0.0);
+                    let disc = 1.0 - ((cos_angle.acos() - SUN_RADIUS) / SUN_RADIUS).clamp(0.0, 1.0);
+                    let glow = cos_angle.powi(8) * 0.3 + cos_angle.powi(64) * 0.7;
+                    for (c, light) in color.iter_mut().zip(sun.color) {
+                        *c += light * (disc * SUN_RADIANCE + glow);
+                    }
+                }
+                [color[0], color[1], color[2], 1.0]
+            })
+        })
+        .collect()
+}
+
+// the first nine real spherical harmonics, bands 0 to 2, at a direction
+fn sh_basis(direction: cgmath::Vector3<f32>) -> [f32; 9] {
+    let cgmath::Vector3 { x, y, z } = direction;
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+// the equirectangular image's radiance projected onto sh_basis, each texel weighted by the
+// solid angle it covers, which shrinks towards the poles
+fn project_sh(pixels: &[[f32; 4]], width: u32) -> [[f32; 3]; 9] {
+    let height = pixels.len() as u32 / width;
+    let texel_angle = std::f32::consts::TAU / width as f32 * std::f32::consts::PI / height as f32;
+    let mut coefficients = [[0.0; 3]; 9];
+    for (i, pixel) in pixels.iter().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let direction = equirectangular_direction(x, y, width, height);
+        // cos(latitude) is the length of the direction's horizontal part
+        let solid_angle = texel_angle * direction.x.hypot(direction.z);
+        for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(direction)) {
+            for channel in 0..3 {
+                coefficient[channel] += pixel[channel] * basis * solid_angle;
+            }
+        }
+    }
+    coefficients
+}
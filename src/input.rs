@@ -0,0 +1,519 @@
+// named actions which the keys and mouse buttons are bound to, rather than matching on keys
+// where they're handled, so they can be rebound in the config file (see config.rs) for other
+// keyboard layouts or to taste. an action can have any number of inputs and an input any number
+// of actions. State::handle_input runs the ones pressed, and the held ones, like moving, go on
+// to the camera controller, which sees them released too. the number keys switching scenes and
+// storing and recalling camera bookmarks stay as they are, see NUMBER_KEYS in lib.rs
+
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // held, for the camera controller
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    // jumps while walking
+    MoveUp,
+    MoveDown,
+    MoveFast,
+    RollLeft,
+    RollRight,
+    ZoomIn,
+    ZoomOut,
+    // scrolling zooms rather than moving while it's held
+    ScrollZoom,
+    // held to turn the camera with the mouse, and clicked to select what's under the cursor
+    Look,
+    // held to place the light under the cursor as it moves
+    PlaceLight,
+
+    Quit,
+    ToggleGeometryDebug,
+    CycleDebugView,
+    CycleVoxelGi,
+    ToggleRaytracing,
+    ToggleRedrawOnChange,
+    ToggleAnimations,
+    ToggleOit,
+    ToggleGpuCulling,
+    ToggleLightVolumes,
+    ToggleBoundsDebug,
+    ToggleFrameGraph,
+    ToggleGrid,
+    ToggleShadowCascades,
+    ToggleIbl,
+    ToggleBloom,
+    ToggleFxaa,
+    ToggleFog,
+    CycleShadowQuality,
+    PreviousParameter,
+    NextParameter,
+    DecreaseParameter,
+    IncreaseParameter,
+    CycleStereo,
+    RecordCameraPath,
+    PlayCameraPath,
+    ToggleFreeCamera,
+    LevelHorizon,
+    ToggleWalk,
+    CyclePresentMode,
+    TogglePause,
+    StepFrame,
+    CycleTimeScale,
+    ToggleQuadView,
+    ToggleFitZPlanes,
+    ToggleRecording,
+    CaptureFrame,
+    ToggleMaterialAtlas,
+    ToggleReference,
+    ToggleSpin,
+    // flies the camera to the selected model, or the whole scene with nothing selected
+    FrameModel,
+}
+
+// every action, its name in the config file and what it's bound to by default
+const ACTIONS: &[(Action, &str, &[Input])] = &[
+    (
+        Action::MoveForward,
+        "move_forward",
+        &[Input::Key(KeyCode::KeyW), Input::Key(KeyCode::ArrowUp)],
+    ),
+    (
+        Action::MoveBackward,
+        "move_backward",
+        &[Input::Key(KeyCode::KeyS), Input::Key(KeyCode::ArrowDown)],
+    ),
+    (
+        Action::MoveLeft,
+        "move_left",
+        &[Input::Key(KeyCode::KeyA), Input::Key(KeyCode::ArrowLeft)],
+    ),
+    (
+        Action::MoveRight,
+        "move_right",
+        &[Input::Key(KeyCode::KeyD), Input::Key(KeyCode::ArrowRight)],
+    ),
+    (Action::MoveUp, "move_up", &[Input::Key(KeyCode::Space)]),
+    (
+        Action::MoveDown,
+        "move_down",
+        &[Input::Key(KeyCode::ShiftLeft)],
+    ),
+    (
+        Action::MoveFast,
+        "move_fast",
+        &[
+            Input::Key(KeyCode::ControlLeft),
+            Input::Key(KeyCode::ControlRight),
+        ],
+    ),
+    (Action::RollLeft, "roll_left", &[Input::Key(KeyCode::Comma)]),
+    (
+        Action::RollRight,
+        "roll_right",
+        &[Input::Key(KeyCode::Period)],
+    ),
+    (Action::ZoomIn, "zoom_in", &[Input::Key(KeyCode::PageUp)]),
+    (
+        Action::ZoomOut,
+        "zoom_out",
+        &[Input::Key(KeyCode::PageDown)],
+    ),
+    (
+        Action::ScrollZoom,
+        "scroll_zoom",
+        &[Input::Key(KeyCode::AltLeft), Input::Key(KeyCode::AltRight)],
+    ),
+    (Action::Look, "look", &[Input::Mouse(MouseButton::Left)]),
+    (
+        Action::PlaceLight,
+        "place_light",
+        &[Input::Mouse(MouseButton::Right)],
+    ),
+    (Action::Quit, "quit", &[Input::Key(KeyCode::Escape)]),
+    (
+        Action::ToggleGeometryDebug,
+        "toggle_geometry_debug",
+        &[Input::Key(KeyCode::KeyG)],
+    ),
+    (
+        Action::CycleDebugView,
+        "cycle_debug_view",
+        &[Input::Key(KeyCode::KeyC)],
+    ),
+    (
+        Action::CycleVoxelGi,
+        "cycle_voxel_gi",
+        &[Input::Key(KeyCode::KeyV)],
+    ),
+    (
+        Action::ToggleRaytracing,
+        "toggle_raytracing",
+        &[Input::Key(KeyCode::KeyT)],
+    ),
+    (
+        Action::ToggleRedrawOnChange,
+        "toggle_redraw_on_change",
+        &[Input::Key(KeyCode::KeyP)],
+    ),
+    (
+        Action::ToggleAnimations,
+        "toggle_animations",
+        &[Input::Key(KeyCode::KeyL)],
+    ),
+    (
+        Action::ToggleOit,
+        "toggle_oit",
+        &[Input::Key(KeyCode::KeyI)],
+    ),
+    (
+        Action::ToggleGpuCulling,
+        "toggle_gpu_culling",
+        &[Input::Key(KeyCode::KeyQ)],
+    ),
+    (
+        Action::ToggleLightVolumes,
+        "toggle_light_volumes",
+        &[Input::Key(KeyCode::KeyF)],
+    ),
+    (
+        Action::ToggleBoundsDebug,
+        "toggle_bounds_debug",
+        &[Input::Key(KeyCode::KeyU)],
+    ),
+    (
+        Action::ToggleFrameGraph,
+        "toggle_frame_graph",
+        &[Input::Key(KeyCode::F3)],
+    ),
+    (
+        Action::ToggleGrid,
+        "toggle_grid",
+        &[Input::Key(KeyCode::KeyH)],
+    ),
+    (
+        Action::ToggleShadowCascades,
+        "toggle_shadow_cascades",
+        &[Input::Key(KeyCode::KeyK)],
+    ),
+    (
+        Action::ToggleIbl,
+        "toggle_ibl",
+        &[Input::Key(KeyCode::KeyE)],
+    ),
+    (
+        Action::ToggleBloom,
+        "toggle_bloom",
+        &[Input::Key(KeyCode::KeyN)],
+    ),
+    (
+        Action::ToggleFxaa,
+        "toggle_fxaa",
+        &[Input::Key(KeyCode::KeyX)],
+    ),
+    (Action::ToggleFog, "toggle_fog", &[Input::Key(KeyCode::F9)]),
+    (
+        Action::CycleShadowQuality,
+        "cycle_shadow_quality",
+        &[Input::Key(KeyCode::KeyJ)],
+    ),
+    (
+        Action::PreviousParameter,
+        "previous_parameter",
+        &[Input::Key(KeyCode::BracketLeft)],
+    ),
+    (
+        Action::NextParameter,
+        "next_parameter",
+        &[Input::Key(KeyCode::BracketRight)],
+    ),
+    (
+        Action::DecreaseParameter,
+        "decrease_parameter",
+        &[Input::Key(KeyCode::Minus)],
+    ),
+    (
+        Action::IncreaseParameter,
+        "increase_parameter",
+        &[Input::Key(KeyCode::Equal)],
+    ),
+    (
+        Action::CycleStereo,
+        "cycle_stereo",
+        &[Input::Key(KeyCode::KeyB)],
+    ),
+    (
+        Action::RecordCameraPath,
+        "record_camera_path",
+        &[Input::Key(KeyCode::KeyY)],
+    ),
+    (
+        Action::PlayCameraPath,
+        "play_camera_path",
+        &[Input::Key(KeyCode::KeyZ)],
+    ),
+    (
+        Action::ToggleFreeCamera,
+        "toggle_free_camera",
+        &[Input::Key(KeyCode::Slash)],
+    ),
+    (
+        Action::LevelHorizon,
+        "level_horizon",
+        &[Input::Key(KeyCode::Home)],
+    ),
+    (
+        Action::ToggleWalk,
+        "toggle_walk",
+        &[Input::Key(KeyCode::Tab)],
+    ),
+    (
+        Action::CyclePresentMode,
+        "cycle_present_mode",
+        &[Input::Key(KeyCode::F5)],
+    ),
+    (
+        Action::TogglePause,
+        "toggle_pause",
+        &[Input::Key(KeyCode::F6)],
+    ),
+    (Action::StepFrame, "step_frame", &[Input::Key(KeyCode::F7)]),
+    (
+        Action::CycleTimeScale,
+        "cycle_time_scale",
+        &[Input::Key(KeyCode::F8)],
+    ),
+    (
+        Action::ToggleQuadView,
+        "toggle_quad_view",
+        &[Input::Key(KeyCode::F4)],
+    ),
+    (
+        Action::ToggleFitZPlanes,
+        "toggle_fit_z_planes",
+        &[Input::Key(KeyCode::End)],
+    ),
+    (
+        Action::ToggleRecording,
+        "toggle_recording",
+        &[Input::Key(KeyCode::F10)],
+    ),
+    (
+        Action::CaptureFrame,
+        "capture_frame",
+        &[Input::Key(KeyCode::F12)],
+    ),
+    (
+        Action::ToggleMaterialAtlas,
+        "toggle_material_atlas",
+        &[Input::Key(KeyCode::KeyM)],
+    ),
+    (
+        Action::ToggleReference,
+        "toggle_reference",
+        &[Input::Key(KeyCode::KeyO)],
+    ),
+    (
+        Action::ToggleSpin,
+        "toggle_spin",
+        &[Input::Key(KeyCode::KeyR)],
+    ),
+    // F is the usual key for this, but it's had the light volumes since before
+    (
+        Action::FrameModel,
+        "frame_model",
+        &[Input::Key(KeyCode::Backslash)],
+    ),
+];
+
+impl Action {
+    // as it's written in the config file, eg move_forward
+    pub fn name(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(action, _, _)| *action == self)
+            .map_or("", |(_, name, _)| name)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+// the mouse buttons which can be bound, and their names
+const MOUSE_BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "MouseLeft"),
+    (MouseButton::Right, "MouseRight"),
+    (MouseButton::Middle, "MouseMiddle"),
+    (MouseButton::Back, "MouseBack"),
+    (MouseButton::Forward, "MouseForward"),
+];
+
+impl Input {
+    // keys by the names winit gives them, eg KeyW or ArrowUp, and mouse buttons as in
+    // MOUSE_BUTTONS
+    pub fn name(self) -> String {
+        match self {
+            Input::Key(key) => format!("{:?}", key),
+            Input::Mouse(button) => MOUSE_BUTTONS
+                .iter()
+                .find(|(b, _)| *b == button)
+                .map_or_else(|| format!("{:?}", button), |(_, name)| name.to_string()),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some((button, _)) = MOUSE_BUTTONS.iter().find(|(_, n)| *n == name) {
+            return Some(Input::Mouse(*button));
+        }
+        KEYS.iter()
+            .find(|key| format!("{:?}", key) == name)
+            .map(|key| Input::Key(*key))
+    }
+}
+
+// what each action is bound to
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    // in the order of ACTIONS
+    bindings: Vec<(Action, Vec<Input>)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            bindings: ACTIONS
+                .iter()
+                .map(|(action, _, inputs)| (*action, inputs.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl Bindings {
+    // the actions the input is bound to
+    pub fn actions(&self, input: Input) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, inputs)| inputs.contains(&input))
+            .map(|(action, _)| *action)
+    }
+
+    // replaces what the action is bound to, none leaving it unbound
+    pub fn set(&mut self, action: Action, inputs: Vec<Input>) {
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            *bound = inputs;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[Input])> {
+        self.bindings
+            .iter()
+            .map(|(action, inputs)| (*action, inputs.as_slice()))
+    }
+}
+
+// the keys which can be bound
+const KEYS: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Enter,
+    KeyCode::Escape,
+    KeyCode::Backspace,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Backslash,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::Backquote,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+];
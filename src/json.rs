@@ -0,0 +1,463 @@
+// a small json reader and writer for the remote control protocol and scene files. numbers are
+// kept as f64 and objects keep their keys in order, which is all either needs
+
+// how deep arrays and objects can be nested in each other. far more than a scene file or
+// command needs, but the remote control reads whatever its clients send, which mustn't be able
+// to nest deep enough to run the parser out of stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+#[derive(Debug)]
+pub enum JsonParseError {
+    UnexpectedEnd,
+    // the byte offset and what was found there
+    Unexpected(usize, char),
+    BadNumber(usize, String),
+    BadEscape(usize),
+    TrailingCharacters(usize),
+    // the byte offset of the array or object past MAX_DEPTH
+    TooDeep(usize),
+}
+
+impl std::fmt::Display for JsonParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonParseError::UnexpectedEnd => write!(f, "unexpected end of json"),
+            JsonParseError::Unexpected(offset, c) => {
+                write!(f, "unexpected {:?} at byte {}", c, offset)
+            }
+            JsonParseError::BadNumber(offset, number) => {
+                write!(f, "bad number {} at byte {}", number, offset)
+            }
+            JsonParseError::BadEscape(offset) => write!(f, "bad escape at byte {}", offset),
+            JsonParseError::TrailingCharacters(offset) => {
+                write!(
+                    f,
+                    "trailing characters after the json value at byte {}",
+                    offset
+                )
+            }
+            JsonParseError::TooDeep(offset) => write!(
+                f,
+                "arrays and objects nested more than {} deep at byte {}",
+                MAX_DEPTH, offset
+            ),
+        }
+    }
+}
+
+impl JsonValue {
+    pub fn parse(text: &str) -> Result<Self, JsonParseError> {
+        let mut parser = Parser {
+            text,
+            offset: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.offset < text.len() {
+            return Err(JsonParseError::TrailingCharacters(parser.offset));
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    // an array of exactly N numbers, eg a vec3
+    pub fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+        let JsonValue::Array(items) = self else {
+            return None;
+        };
+        if items.len() != N {
+            return None;
+        }
+        let mut array = [0.0; N];
+        for (value, item) in array.iter_mut().zip(items) {
+            *value = item.as_f64()? as f32;
+        }
+        Some(array)
+    }
+}
+
+impl std::fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            // json has no infinities or nans
+            JsonValue::Number(n) if !n.is_finite() => write!(f, "null"),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+    // the arrays and objects the parser is inside
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, JsonParseError> {
+        let c = self.peek().ok_or(JsonParseError::UnexpectedEnd)?;
+        self.offset += c.len_utf8();
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonParseError> {
+        let offset = self.offset;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(JsonParseError::Unexpected(offset, c)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_whitespace() {
+                break;
+            }
+            self.offset += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, JsonParseError> {
+        self.skip_whitespace();
+        let offset = self.offset;
+        match self.peek().ok_or(JsonParseError::UnexpectedEnd)? {
+            c @ ('{' | '[') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(JsonParseError::TooDeep(offset));
+                }
+                self.depth += 1;
+                let value = if c == '{' {
+                    self.object()
+                } else {
+                    self.array()
+                };
+                self.depth -= 1;
+                value
+            }
+            '"' => self.string().map(JsonValue::String),
+            '-' | '0'..='9' => self.number(),
+            _ => {
+                for (word, value) in [
+                    ("null", JsonValue::Null),
+                    ("true", JsonValue::Bool(true)),
+                    ("false", JsonValue::Bool(false)),
+                ] {
+                    if self.text[offset..].starts_with(word) {
+                        self.offset += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(JsonParseError::Unexpected(offset, self.next()?))
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, JsonParseError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.offset += 1;
+            return Ok(JsonValue::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+
+            self.skip_whitespace();
+            let offset = self.offset;
+            match self.next()? {
+                ',' => continue,
+                '}' => return Ok(JsonValue::Object(entries)),
+                c => return Err(JsonParseError::Unexpected(offset, c)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, JsonParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.offset += 1;
+            return Ok(JsonValue::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+
+            self.skip_whitespace();
+            let offset = self.offset;
+            match self.next()? {
+                ',' => continue,
+                ']' => return Ok(JsonValue::Array(items)),
+                c => return Err(JsonParseError::Unexpected(offset, c)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonParseError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            let offset = self.offset;
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => match self.next()? {
+                    '"' => string.push('"'),
+                    '\\' => string.push('\\'),
+                    '/' => string.push('/'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'n' => string.push('\n'),
+                    'r' => string.push('\r'),
+                    't' => string.push('\t'),
+                    'u' => {
+                        // surrogate pairs aren't needed for paths and names, so they come out
+                        // as replacement characters
+                        let hex = self
+                            .text
+                            .get(self.offset..self.offset + 4)
+                            .ok_or(JsonParseError::BadEscape(offset))?;
+                        let code = u32::from_str_radix(hex, 16)
+                            .map_err(|_| JsonParseError::BadEscape(offset))?;
+                        self.offset += 4;
+                        string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    _ => return Err(JsonParseError::BadEscape(offset)),
+                },
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue, JsonParseError> {
+        let start = self.offset;
+        while let Some(c) = self.peek() {
+            if !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9') {
+                break;
+            }
+            self.offset += 1;
+        }
+
+        let number = &self.text[start..self.offset];
+        number
+            .parse()
+            .map(JsonValue::Number)
+            .map_err(|_| JsonParseError::BadNumber(start, number.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_unescape() {
+        let value = JsonValue::parse(r#""a \"quoted\" \\ path/\/ \n\t\u00e9 \u0041""#).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::String("a \"quoted\" \\ path// \n\t\u{e9} A".to_string())
+        );
+        // and the writer escapes them again
+        assert_eq!(JsonValue::parse(&value.to_string()).unwrap(), value);
+
+        assert!(matches!(
+            JsonValue::parse(r#""\q""#),
+            Err(JsonParseError::BadEscape(1))
+        ));
+        assert!(matches!(
+            JsonValue::parse(r#""\u12""#),
+            Err(JsonParseError::BadEscape(1))
+        ));
+    }
+
+    #[test]
+    fn numbers_parse_as_f64() {
+        for (text, number) in [
+            ("0", 0.0),
+            ("-12", -12.0),
+            ("3.25", 3.25),
+            ("1e3", 1000.0),
+            ("-2.5E-1", -0.25),
+        ] {
+            assert_eq!(JsonValue::parse(text).unwrap(), JsonValue::Number(number));
+        }
+        assert!(matches!(
+            JsonValue::parse("1.2.3"),
+            Err(JsonParseError::BadNumber(0, _))
+        ));
+        assert!(matches!(
+            JsonValue::parse("-"),
+            Err(JsonParseError::BadNumber(0, _))
+        ));
+    }
+
+    #[test]
+    fn nested_values_keep_their_order() {
+        let value = JsonValue::parse(
+            r#" {"command": "set_transform", "id": 3, "scale": [1, 2.5, -1],
+                "nested": {"b": [true, false, null], "a": []}, "empty": {}} "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value.get("command").and_then(JsonValue::as_str),
+            Some("set_transform")
+        );
+        assert_eq!(value.get("id").and_then(JsonValue::as_f64), Some(3.0));
+        assert_eq!(
+            value.get("scale").and_then(JsonValue::as_f32_array),
+            Some([1.0, 2.5, -1.0])
+        );
+        let JsonValue::Object(nested) = value.get("nested").unwrap() else {
+            panic!("nested isn't an object");
+        };
+        assert_eq!(nested[0].0, "b");
+        assert_eq!(
+            nested[0].1,
+            JsonValue::Array(vec![
+                JsonValue::Bool(true),
+                JsonValue::Bool(false),
+                JsonValue::Null
+            ])
+        );
+        assert_eq!(nested[1], ("a".to_string(), JsonValue::Array(Vec::new())));
+        assert_eq!(value.get("empty"), Some(&JsonValue::Object(Vec::new())));
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        for text in [
+            "",
+            "{",
+            "[1, 2",
+            "[1 2]",
+            r#"{"a" 1}"#,
+            r#"{"a": 1,}"#,
+            r#"{a: 1}"#,
+            r#""unterminated"#,
+            "nul",
+            "[1] 2",
+        ] {
+            assert!(JsonValue::parse(text).is_err(), "{:?} was accepted", text);
+        }
+        assert!(matches!(
+            JsonValue::parse("[1] 2"),
+            Err(JsonParseError::TrailingCharacters(4))
+        ));
+    }
+
+    #[test]
+    fn nesting_is_capped() {
+        let nested = |depth: usize| "[".repeat(depth) + "]".repeat(depth).as_str();
+        assert!(JsonValue::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(matches!(
+            JsonValue::parse(&nested(MAX_DEPTH + 1)),
+            Err(JsonParseError::TooDeep(offset)) if offset == MAX_DEPTH
+        ));
+
+        // far deeper than the stack could take without the cap
+        let deep = "[{\"a\":".repeat(1_000_000);
+        assert!(matches!(
+            JsonValue::parse(&deep),
+            Err(JsonParseError::TooDeep(_))
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+// a reader for KTX2 texture containers holding 2d textures which are already in a gpu format,
+// eg bc7 or etc2 from `toktx` or `ktx create`. the levels are sliced out of the file as they
+// are and uploaded without decoding.
+// basis universal (etc1s/uastc) needs transcoding and zstd/zlib supercompression needs
+// inflating, neither of which is available here, so those files are rejected with a message
+// saying how to re-encode them
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+const BASIS_UNSUPPORTED: &str = "basis universal textures need transcoding, which isn't \
+     supported; encode the file as bc7 or etc2 instead";
+
+pub struct Ktx2<'a> {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    // the largest level first
+    pub levels: Vec<&'a [u8]>,
+}
+
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(&IDENTIFIER)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    read_u32(bytes, offset) as u64 | (read_u32(bytes, offset + 4) as u64) << 32
+}
+
+// the vulkan formats with a wgpu equivalent which are worth shipping as ktx2
+fn format_from_vk(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat};
+
+    Some(match vk_format {
+        37 => TextureFormat::Rgba8Unorm,
+        43 => TextureFormat::Rgba8UnormSrgb,
+        133 => TextureFormat::Bc1RgbaUnorm,
+        134 => TextureFormat::Bc1RgbaUnormSrgb,
+        137 => TextureFormat::Bc3RgbaUnorm,
+        138 => TextureFormat::Bc3RgbaUnormSrgb,
+        139 => TextureFormat::Bc4RUnorm,
+        141 => TextureFormat::Bc5RgUnorm,
+        145 => TextureFormat::Bc7RgbaUnorm,
+        146 => TextureFormat::Bc7RgbaUnormSrgb,
+        147 => TextureFormat::Etc2Rgb8Unorm,
+        148 => TextureFormat::Etc2Rgb8UnormSrgb,
+        151 => TextureFormat::Etc2Rgba8Unorm,
+        152 => TextureFormat::Etc2Rgba8UnormSrgb,
+        153 => TextureFormat::EacR11Unorm,
+        155 => TextureFormat::EacRg11Unorm,
+        157 => TextureFormat::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::Unorm,
+        },
+        158 => TextureFormat::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    })
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Ktx2<'_>, String> {
+    if !is_ktx2(bytes) {
+        return Err("not a ktx2 file".to_string());
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err("the ktx2 header is cut off".to_string());
+    }
+
+    let vk_format = read_u32(bytes, 12);
+    let width = read_u32(bytes, 20);
+    let height = read_u32(bytes, 24);
+    let depth = read_u32(bytes, 28);
+    let layer_count = read_u32(bytes, 32);
+    let face_count = read_u32(bytes, 36);
+    // 0 asks the loader to generate the mips, which the renderer doesn't use anyway
+    let level_count = read_u32(bytes, 40).max(1) as usize;
+    let supercompression = read_u32(bytes, 44);
+
+    match supercompression {
+        0 => {}
+        1 => return Err(BASIS_UNSUPPORTED.to_string()),
+        2 | 3 => {
+            return Err(
+                "zstd and zlib supercompressed ktx2 files aren't supported; save it without \
+                 supercompression"
+                    .to_string(),
+            );
+        }
+        scheme => return Err(format!("unknown supercompression scheme {}", scheme)),
+    }
+
+    // uastc is stored with an undefined vkFormat and no supercompression
+    let format = match (vk_format, format_from_vk(vk_format)) {
+        (0, _) => return Err(BASIS_UNSUPPORTED.to_string()),
+        (_, Some(format)) => format,
+        (_, None) => return Err(format!("unsupported vkFormat {}", vk_format)),
+    };
+
+    if height == 0 || depth > 1 || layer_count > 1 || face_count != 1 {
+        return Err(format!(
+            "only single 2d textures are supported, this one is {}x{}x{} with {} layers and {} faces",
+            width, height, depth, layer_count, face_count
+        ));
+    }
+
+    let index_end = HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE;
+    if bytes.len() < index_end {
+        return Err("the ktx2 level index is cut off".to_string());
+    }
+
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+        let offset = read_u64(bytes, entry) as usize;
+        let length = read_u64(bytes, entry + 8) as usize;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or_else(|| format!("level {} lies outside the file", level))?;
+        levels.push(data);
+    }
+
+    Ok(Ktx2 {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
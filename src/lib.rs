@@ -4,7 +4,8 @@ use std::{
     time::{Duration, Instant},
 };
 
-use cgmath::{One, Rotation3, SquareMatrix};
+use anyhow::Context;
+use cgmath::{EuclideanSpace, InnerSpace, One, SquareMatrix};
 use wgpu::util::DeviceExt;
 use winit::{
     application::ApplicationHandler,
@@ -18,16 +19,124 @@ use winit::{
 use wasm_bindgen::prelude::*;
 
 use crate::model::{DrawModel, Vertex};
-
+pub use crate::particles::{Collision, Emitter, EmitterId};
+pub use crate::scene::{ModelId, PickResult};
+pub use crate::weather::Precipitation;
+
+mod animation;
+mod antialiasing;
+mod assets;
+mod bloom;
+mod bookmarks;
 mod camera;
+mod config;
+mod console;
+mod debug_draw;
+mod denoise;
+mod error;
+mod exr;
+mod fog;
+mod frames;
+mod gpu_culling;
+mod grid;
+mod ibl;
+mod input;
+mod json;
+mod ktx2;
+mod light_volumes;
+mod live_reload;
+mod material_atlas;
+pub mod math;
+mod mesh_tools;
 mod model;
+mod msaa;
+mod obj_export;
 mod obj_parse;
+mod oit;
+mod options;
+mod outline;
+mod panorama;
+mod particles;
+mod pipeline_cache;
+mod ply_parse;
+mod primitives;
+mod quad_view;
+mod raytracing;
+mod readback;
+mod recording;
+mod reference;
+mod remote;
+mod render_stats;
 mod resources;
+mod scene;
+mod scene_file;
+mod screenshot;
+#[cfg(feature = "scripting")]
+mod scripting;
+pub mod shader_params;
+mod shaders;
+mod shadows;
+mod stereo;
 mod texture;
 mod timing;
+mod toml;
 mod uniforms;
-
-const ENABLE_DEBUG_TBN: bool = true;
+mod uploads;
+mod vxgi;
+mod walk;
+mod weather;
+
+// how far off the surface a light placed with the right mouse button ends up, so it isn't
+// buried in the geometry it was placed on
+const LIGHT_PLACEMENT_OFFSET: f32 = 0.5;
+// how far in pixels the cursor can move between pressing and releasing the left button for it
+// to still select what's under it, rather than having turned the camera
+const CLICK_DISTANCE: f64 = 4.0;
+// the keys which switch to the first nine scenes, and with ctrl or alt store and recall the
+// camera bookmarks (see bookmarks.rs)
+const NUMBER_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+// the smallest the window can be made, in logical pixels, so the views and overlays in it stay
+// usable
+#[cfg(not(target_arch = "wasm32"))]
+const MIN_WINDOW_SIZE: [u32; 2] = [320, 240];
+// what State::new loads, which on the web is fetched before it starts (see assets.rs)
+#[cfg(target_arch = "wasm32")]
+const STARTUP_ASSETS: [&str; 8] = [
+    "src/assets/materials/all_materials.mtl",
+    "src/assets/materials/debug_diffuse.png",
+    "src/assets/materials/debug_normal.png",
+    "src/assets/materials/stone_brick_diffuse.jpg",
+    "src/assets/materials/stone_brick_normal.png",
+    "src/assets/materials/wood_diffuse.png",
+    "src/assets/materials/wood_normal.png",
+    "src/assets/models/sball3.obj",
+];
+// the id of a <progress> element on the page, filled in as the startup assets arrive. pages
+// without one just don't show the progress
+#[cfg(target_arch = "wasm32")]
+const LOADING_BAR_ID: &str = "loading";
+// the settings read at startup (see config.rs), unless --config names another file
+const CONFIG_FILE: &str = "config.toml";
+// where the camera bookmarks are kept between runs, or None to forget them on exit
+const CAMERA_BOOKMARKS_FILE: Option<&str> = Some("camera_bookmarks.json");
+// where the last camera path recorded is kept between runs, or None to forget it on exit
+const CAMERA_PATH_FILE: Option<&str> = Some("camera_path.json");
+// where the driver's compiled pipelines are kept between runs (see pipeline_cache.rs), or None
+// to compile them from scratch every time
+const PIPELINE_CACHE_DIRECTORY: Option<&str> = Some("pipeline_cache");
+// the size of each cube face rendered for a panorama when none is given, which makes the
+// panorama 2048x1024
+const PANORAMA_FACE_SIZE: u32 = 512;
 
 /*
 TODO:
@@ -36,7 +145,7 @@ X clean up debug pipelines
 X rewrite material loading and remove tobj dependence
 - generally just reconsider the mesh/model organization
 - add multiple lights
-- add proper material batching
+X add proper material batching
 - add shadows
 - improve lighting
 - add egui
@@ -44,46 +153,131 @@ X rewrite material loading and remove tobj dependence
 
 struct Pipelines {
     render: wgpu::RenderPipeline, // object which describes the various rendering phases to use
-    render_alt: wgpu::RenderPipeline, // object which describes the various rendering phases to use
+    // every mesh added up without depth testing, for the overdraw debug view
+    render_overdraw: wgpu::RenderPipeline,
+    render_raytraced: Option<wgpu::RenderPipeline>, // only when the adapter supports ray queries
+    // the same two for transparent materials, drawn after everything else
+    render_transparent: wgpu::RenderPipeline,
+    render_transparent_raytraced: Option<wgpu::RenderPipeline>,
+    // the same two, drawing batched materials from the material atlas
+    render_atlas: wgpu::RenderPipeline,
+    render_atlas_raytraced: Option<wgpu::RenderPipeline>,
     light_debug: wgpu::RenderPipeline,
     geometry_debug: wgpu::RenderPipeline,
 }
 
+// the cpu side of the per frame bind group, written into the current FrameUniforms every frame
 struct Uniforms {
     camera: uniforms::CameraUniform,
-    camera_buffer: wgpu::Buffer,
-
+    // the quad view's, written after the camera's while it's on
+    orthographic_cameras: [uniforms::CameraUniform; quad_view::CAMERA_SLOTS - 1],
     lights: Vec<uniforms::LightUniform>,
-    light_buffer: wgpu::Buffer,
-
     light_metadata: uniforms::LightMetadataUniform,
-    light_metadata_buffer: wgpu::Buffer,
-
     timestamp: uniforms::TimestampUniform,
-    timestamp_buffer: wgpu::Buffer,
+}
 
-    model_transform_buffer: wgpu::Buffer,
+// one frame in flight's copy of the per frame buffers, so writing the next frame's camera never
+// changes what a frame the gpu is still drawing reads
+struct FrameUniforms {
+    // a slot per view for quad_view::CAMERA_SLOTS views, camera_stride apart, the camera's first
+    camera_buffer: wgpu::Buffer,
+    camera_stride: u32,
+    light_buffer: wgpu::Buffer,
+    light_metadata_buffer: wgpu::Buffer,
+    timestamp_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup, // uniforms like camera, lights, etc
 }
 
 struct Layouts {
     per_frame: wgpu::BindGroupLayout,
     per_pass: wgpu::BindGroupLayout,
     per_object: wgpu::BindGroupLayout,
+    material_atlas: wgpu::BindGroupLayout,
 }
 
 struct Variables {
     is_mouse_pressed: bool,
+    // the modifier keys held down, for the number keys' ctrl and alt meanings
+    modifiers: winit::keyboard::ModifiersState,
+    // where the left button went down, so releasing it close by is a click rather than the
+    // end of turning the camera
+    click_start: Option<(f64, f64)>,
+    // the model clicked on last, outlined until something else or nothing is clicked
+    selected: Option<ModelId>,
+    // in physical pixels from the top left of the window, None until the cursor enters it
+    cursor_position: Option<(f64, f64)>,
+    // while the right mouse button is held, the first point light follows the cursor
+    is_placing_light: bool,
+    parameter_editor: shader_params::ParameterEditor,
     enable_geometry_debug: bool,
-    swap_pipelines: bool,
-    enable_light_rotation: bool,
+    // boxes and spheres around every mesh, toggled with U
+    enable_bounds_debug: bool,
+    // the last frame times as a graph in the corner rather than numbers in the title, toggled
+    // with F3
+    show_frame_graph: bool,
+    debug_view: uniforms::DebugView,
+    enable_raytracing: bool,
+    // draw transparent meshes with weighted blended transparency rather than sorting them
+    enable_oit: bool,
+    // cull and draw the opaque meshes with materials of their own on the gpu, toggled with Q
+    enable_gpu_culling: bool,
+    // fit the near and far planes around the scene every frame, toggled with End
+    fit_z_planes: bool,
+    // the camera in one quarter of the window and orthographic views in the others, toggled
+    // with F4
+    enable_quad_view: bool,
+    // only draw the scene when something has changed, re-presenting the last frame otherwise
+    redraw_on_change: bool,
+    needs_redraw: bool,
+    // set when models have moved, so the voxels are rebuilt once in the next update however
+    // many of them moved
+    needs_revoxelize: bool,
+    // started by the console's bench command, timing every frame until it's finished
+    benchmark: Option<timing::Benchmark>,
+    // the distance between the eyes for stereo output, in world units
+    eye_separation: f32,
+}
+
+// how the scene pass treats what was drawn before it. for a partial redraw, load the color
+// (which keeps the previous frame outside the scissor), clear the depth and set the scissor
+struct PassOps {
+    color_load: wgpu::LoadOp<wgpu::Color>,
+    depth_load: wgpu::LoadOp<f32>,
+    store: wgpu::StoreOp,
+    // x, y, width, height in pixels; only drawing inside this region when set
+    scissor: Option<[u32; 4]>,
+}
+
+impl PassOps {
+    // clearing the depth to the far plane of the depth mode
+    fn new(depth_mode: camera::DepthMode) -> Self {
+        Self {
+            color_load: wgpu::LoadOp::Clear(wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            }),
+            depth_load: wgpu::LoadOp::Clear(depth_mode.clear_depth()),
+            store: wgpu::StoreOp::Store,
+            scissor: None,
+        }
+    }
 }
 
 struct Diagnostics {
-    start_time: std::time::Instant,
     frame_count: u64,
     frame_time_avg: timing::RollingAverage,
+    // the slowest and fastest of the same frames, for hitches the average hides
+    frame_stats: timing::FrameStats,
     render_time_avg: timing::RollingAverage,
     update_time_avg: timing::RollingAverage,
+    // update_time_avg split up by the parts of update()
+    update_breakdown: timing::SubsystemTimings,
+    // counted from the start of update() to the end of render()
+    render_stats: render_stats::RenderStats,
+    // what the loaded meshes and textures hold on the gpu, as of the start of update()
+    memory_stats: render_stats::MemoryStats,
 }
 
 pub struct State {
@@ -92,70 +286,153 @@ pub struct State {
     queue: wgpu::Queue,   // the command queue to send things to the device
     surface: wgpu::Surface<'static>, // the target of the rendering
     surface_config: wgpu::SurfaceConfiguration, // configuring the surface (size, colour format, etc)
+    // false while the window is minimized, when the surface has no size to be configured with
     is_surface_configured: bool,
+    // covered by other windows or on another workspace, where the platform can tell
+    is_occluded: bool,
+    // what the surface can present with, for switching between them
+    present_modes: Vec<wgpu::PresentMode>,
+    // caps the frame rate on the cpu, for present modes which don't wait for the display
+    frame_limiter: timing::FrameLimiter,
+    // the time animations and shaders follow, which can be slowed down, paused and stepped
+    scene_clock: timing::SceneClock,
 
     camera: camera::Camera,
     projection: camera::Projection,
-    model: model::Model,
+    scene: scene::Scene,
     materials: Vec<model::Material>,
     material_map: HashMap<String, usize>,
+    texture_cache: resources::TextureCache,
+    // from the command line, applied to every model loaded
+    options: options::StartupOptions,
 
     point_lights: Vec<PointLight>,
     directional_lights: Vec<DirectionalLight>,
     spot_lights: Vec<SpotLight>,
+    animations: animation::Animations,
 
     depth_texture: texture::Texture,
-    debug_tbn_extras: Option<DebugTBNStateExtras>,
+    // only when the surface can be copied into, see texture::Texture::create_frame_texture
+    frame_texture: Option<texture::Texture>,
+    pass_ops: PassOps,
     debug_light_model: model::Model,
-
+    // how far each light reaches, toggled with F
+    light_volumes: light_volumes::LightVolumes,
+    // the ground grid and world axes, toggled with H
+    grid: grid::Grid,
+    // lines added while updating, drawn over the frame
+    debug_draw: debug_draw::DebugDraw,
+    // the outline around the selected model
+    outline: outline::Outline,
+    // the first directional and point lights' shadows, when there are any
+    shadows: shadows::CascadedShadows,
+    // ambient light from the environment, toggled with E
+    ibl: ibl::ImageBasedLighting,
+
+    // what the keys and mouse buttons do, from the config file
+    bindings: input::Bindings,
     camera_controller: camera::CameraController,
+    bookmarks: bookmarks::CameraBookmarks,
+    // flying to a bookmark, which overrides the controller until it lands
+    camera_transition: Option<bookmarks::CameraTransition>,
+    // recorded with Y and played with Z, overriding the controller while it plays
+    camera_path: bookmarks::CameraPathPlayer,
+    // walking on the ground under gravity rather than flying, toggled with Tab
+    walk: Option<walk::Walk>,
 
     layouts: Layouts,
 
-    per_frame_bind_group: wgpu::BindGroup, // uniforms like camera, lights, etc
-    per_object_bind_group: wgpu::BindGroup, // local things like model position or rotation, etc
+    frames: frames::FramesInFlight<FrameUniforms>,
+    // update()'s buffer writes, sent together at its end
+    uploads: uploads::UploadBelt,
 
     pipelines: Pipelines,
+    // what the pipelines were made through, for making more without compiling duplicates
+    pipeline_cache: pipeline_cache::PipelineCache,
+    // transparency without sorting, used instead of the sorted pipelines while enable_oit is set
+    oit: oit::WeightedBlendedOit,
+    // only when the adapter supports it, used for the opaque meshes while enable_gpu_culling
+    // is set
+    gpu_culling: Option<gpu_culling::GpuCulling>,
+    voxel_gi: vxgi::VoxelGI,
+    ray_tracing: Option<raytracing::RayTracing>,
+    // denoises the ray traced ambient occlusion, only along with ray_tracing
+    denoiser: Option<denoise::Denoiser>,
+    shader_parameters: shader_params::ParameterLayout,
     uniforms: Uniforms,
     diagnostics: Diagnostics,
     variables: Variables,
+    console: console::Console,
+    remote: Option<remote::RemoteServer>,
+    // the script started with the console's script command, kept while its update runs
+    #[cfg(feature = "scripting")]
+    script: Option<scripting::Script>,
+    export_watcher: Option<live_reload::ExportWatcher>,
+    // every open scene by tab number. the active one is None here, since its parts are the
+    // fields above
+    scene_tabs: Vec<Option<SceneTab>>,
+    active_scene: usize,
+    reference: Option<reference::ReferenceOverlay>,
+    // only while a stereo mode is on
+    stereo: Option<stereo::StereoOutput>,
+    // post processes, applied in this order. bloom is toggled with N and fxaa with X
+    bloom: Option<bloom::Bloom>,
+    fxaa: Option<antialiasing::Fxaa>,
+    // the scene pass's multisampled targets, when render.msaa_samples is over 1
+    msaa: Option<msaa::Multisampling>,
+    // only when the adapter has compute shaders, see add_emitter
+    particles: Option<particles::ParticleSystem>,
+    // rain or snow, falling as particles and wetting the scene, see set_weather
+    weather: weather::Weather,
+    // froxel fog lit by the lights, only when the adapter has compute shaders. toggled with F9
+    fog: Option<fog::VolumetricFog>,
+    // only while material batching is on, rebuilt whenever materials are added or replaced
+    material_atlas: Option<material_atlas::MaterialAtlas>,
+    // frames captured with F12 which are still being copied back, with the paths to save them to
+    captures: Vec<(String, readback::Readback)>,
+    // only while recording, see recording.rs
+    recording: Option<recording::Recording>,
 }
 
-struct DebugTBNStateExtras {
-    tangent_bind_group: wgpu::BindGroup,
-    bitangent_bind_group: wgpu::BindGroup,
-    normal_bind_group: wgpu::BindGroup,
-    debug_tbn_render_pipeline: wgpu::RenderPipeline,
-    debug_tbn_uniforms: [Vec<model::VectorDebugUniform>; 3],
-    debug_tangent_buffer: wgpu::Buffer,
-    debug_bitangent_buffer: wgpu::Buffer,
-    debug_normal_buffer: wgpu::Buffer,
-    debug_vector_model: model::Model,
+// a scene which isn't being shown: its models and lights, and the voxels and acceleration
+// structures built from them, kept as they were so that switching back rebuilds nothing
+struct SceneTab {
+    scene: scene::Scene,
+    point_lights: Vec<PointLight>,
+    directional_lights: Vec<DirectionalLight>,
+    spot_lights: Vec<SpotLight>,
+    animations: animation::Animations,
+    voxel_gi: vxgi::VoxelGI,
+    ray_tracing: Option<raytracing::RayTracing>,
 }
 
-#[derive(Debug, Copy, Clone)]
+// lights are placed with a transform; directional and spot lights shine along its forward (-z) axis
+#[derive(Debug, Clone)]
 struct PointLight {
-    position: [f32; 3],
+    transform: math::Transform,
     color: [f32; 3],
+    intensity: f32,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 struct DirectionalLight {
-    direction: [f32; 3],
+    transform: math::Transform,
     color: [f32; 3],
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 struct SpotLight {
-    position: [f32; 3],
-    direction: [f32; 3],
+    transform: math::Transform,
     color: [f32; 3],
     inner_angular_radius: f32,
     outer_angular_radius: f32,
 }
 
 impl State {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
+    pub async fn new(
+        window: Arc<Window>,
+        options: options::StartupOptions,
+    ) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
         // MARK: DEVICE CONFIG
@@ -169,7 +446,7 @@ impl State {
             ..Default::default()
         });
 
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = instance.create_surface(window.clone())?;
 
         // adapter is the handle to the gpu
         // notably this basically returns a random adapter (eg could be either DX or Vulkan)
@@ -181,11 +458,28 @@ impl State {
             })
             .await?;
 
+        // ray queries are optional, the renderer falls back to no shadows/ao without them
+        let supports_ray_tracing = raytracing::is_supported(&adapter);
+
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: Some("main_device"),
-                required_features: wgpu::Features::POLYGON_MODE_LINE, // allows use of specific extensions (eg float 64 support)
-                experimental_features: wgpu::ExperimentalFeatures::disabled(),
+                required_features: if supports_ray_tracing {
+                    wgpu::Features::POLYGON_MODE_LINE | raytracing::FEATURES
+                } else {
+                    wgpu::Features::POLYGON_MODE_LINE // allows use of specific extensions (eg float 64 support)
+                } | (adapter.features()
+                    & (texture::OPTIONAL_FEATURES
+                        | pipeline_cache::OPTIONAL_FEATURES
+                        | gpu_culling::FEATURES
+                        | gpu_culling::OPTIONAL_FEATURES)),
+                experimental_features: if supports_ray_tracing {
+                    // SAFETY: ray queries are the only experimental feature requested, and they are
+                    // only used through the validated wgpu api in raytracing.rs
+                    unsafe { wgpu::ExperimentalFeatures::enabled() }
+                } else {
+                    wgpu::ExperimentalFeatures::disabled()
+                },
                 required_limits: if cfg!(target_arch = "wasm32") {
                     // sets resource limits for compatibility with different devices
                     wgpu::Limits::downlevel_webgl2_defaults()
@@ -207,150 +501,260 @@ impl State {
             .copied()
             .unwrap_or(surface_capabilities.formats[0]);
 
+        // a transparent window needs the compositor to blend the surface using its alpha
+        let alpha_mode = if options.config.transparent {
+            [
+                wgpu::CompositeAlphaMode::PreMultiplied,
+                wgpu::CompositeAlphaMode::PostMultiplied,
+            ]
+            .into_iter()
+            .find(|mode| surface_capabilities.alpha_modes.contains(mode))
+            .unwrap_or_else(|| {
+                log::warn!(
+                    "the surface doesn't support alpha compositing, the window will be opaque"
+                );
+                surface_capabilities.alpha_modes[0]
+            })
+        } else {
+            surface_capabilities.alpha_modes[0]
+        };
+
         // configure the surface. this is also used later to get width/height of the screen
+        // keeping frames around for partial redraws means copying them onto the surface
+        let can_keep_frames = surface_capabilities
+            .usages
+            .contains(wgpu::TextureUsages::COPY_DST);
+
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: if can_keep_frames {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_capabilities.present_modes[0], // this essentially controls vsync
-            alpha_mode: surface_capabilities.alpha_modes[0],
+            // fifo waits for the display, so there's no tearing. every surface supports it,
+            // and the others can be switched to with F5 or the console's present command, or
+            // started with by turning vsync off in the config
+            present_mode: if options.config.vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                [wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate]
+                    .into_iter()
+                    .find(|mode| surface_capabilities.present_modes.contains(mode))
+                    .unwrap_or(wgpu::PresentMode::Fifo)
+            },
+            alpha_mode,
             desired_maximum_frame_latency: 2,
             view_formats: vec![],
         };
 
-        let camera_controller = camera::CameraController::new(10.0, 1.3);
+        log::info!(
+            "present modes supported: {:?}",
+            surface_capabilities.present_modes
+        );
+
+        let camera_controller = camera::CameraController::new(
+            options.config.camera_speed,
+            options.config.camera_sensitivity,
+        );
 
-        let (camera, projection, camera_uniform, camera_buffer) =
-            Self::create_camera(&device, &surface_config);
+        let (camera, projection, camera_uniform) = Self::create_camera(&surface_config, &options);
 
         // MARK: HIGH LEVEL CONFIG
 
         let point_lights = vec![PointLight {
-            position: [15.0, 15.0, 15.0],
+            transform: math::Transform::from_translation([15.0, 15.0, 15.0]),
             color: [1.0; 3],
+            intensity: 1.0,
         }];
 
-        let directional_lights = vec![];
+        // the sun, low enough in the sky for long shadows
+        let directional_lights = vec![DirectionalLight {
+            transform: math::Transform::look_to(
+                cgmath::Point3::origin(),
+                cgmath::Vector3::new(-0.4, -1.0, -0.6),
+                cgmath::Vector3::unit_y(),
+            ),
+            color: [0.6, 0.58, 0.5],
+        }];
 
         let spot_lights = vec![];
 
+        // the first point light circles the z axis, paused until L is pressed
+        let mut animations = animation::Animations::default();
+        animations.set_light(animation::LightAnimation {
+            index: 0,
+            position: Some(animation::Motion::Path(animation::Path::Circle {
+                center: [0.0, 0.0, 15.0],
+                axis: [0.0, 0.0, 1.0],
+                radius: 15.0 * std::f32::consts::SQRT_2,
+                period: 60.0,
+            })),
+            color: None,
+        });
+
         let (light_uniforms, light_metadata_uniform) =
             uniforms::create_light_uniforms(&point_lights, &directional_lights, &spot_lights);
 
-        let timestamp_uniform = uniforms::TimestampUniform { time: 0 };
+        let timestamp_uniform = uniforms::TimestampUniform::new();
 
         let depth_texture =
             texture::Texture::create_depth_texture(&device, &surface_config, "depth texture");
+        let frame_texture = can_keep_frames.then(|| {
+            texture::Texture::create_frame_texture(&device, &surface_config, "frame texture")
+        });
 
         // MARK: BIND GROUP LAYOUTS
 
         // a BindGroup describes a set of resources and how they can be accessed by the shader(s)
 
         let (per_frame_bind_group_layout, per_pass_bind_group_layout, per_object_bind_group_layout) =
-            Self::create_bind_group_layouts(&device);
-
-        // MARK: BUFFERS
-
-        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("light buffer"),
-            contents: bytemuck::cast_slice(light_uniforms.as_slice()),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let light_metadata_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("light metadata buffer"),
-            contents: bytemuck::cast_slice(&[light_metadata_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let timestamp_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("timestamp buffer"),
-            contents: bytemuck::cast_slice(&[timestamp_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let model_transform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("model transform buffer"),
-            contents: bytemuck::cast_slice(&[model::ModelTransformationUniform::identity()]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        // MARK: BIND GROUPS
-
-        // bind group layouts can be be reused with various different bind groups to allow swapping the data on the fly
-        let per_frame_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &per_frame_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: light_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: light_metadata_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: timestamp_buffer.as_entire_binding(),
-                },
-            ],
-            label: Some("camera_bind_group"),
-        });
-
-        // the per pass bind group is created by materials
-
-        let per_object_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("per object bind group"),
-            layout: &per_object_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: model_transform_buffer.as_entire_binding(),
-            }],
-        });
+            Self::create_bind_group_layouts(&device, supports_ray_tracing);
 
         // MARK: MODEL LOADING
 
         let mut materials = Vec::new();
         let mut material_map = HashMap::new();
+        let mut texture_cache = resources::TextureCache::new(&device, &queue);
 
         resources::load_all_materials(
             "src/assets/materials/all_materials.mtl",
             &mut materials,
             &mut material_map,
+            &mut texture_cache,
             &device,
             &queue,
             &per_pass_bind_group_layout,
-        );
+        )?;
 
-        let model = resources::load_obj_model(
-            "src/assets/models/sball3.obj",
+        // obj or ply
+        let model_path = "src/assets/models/sball3.obj";
+        let model = resources::load_model(
+            model_path,
             &mut materials,
             &mut material_map,
+            &mut texture_cache,
             &device,
             &queue,
             &per_pass_bind_group_layout,
-        )
-        .unwrap();
+        )?;
+        let model = Self::prepare_loaded_model(&device, &options, model);
         // model.scale = 16.0;
 
-        let debug_light_model = resources::load_obj_model(
-            "src/assets/models/octahedron.obj",
-            &mut materials,
-            &mut material_map,
+        let debug_light_model =
+            primitives::icosphere(1.0, 1).into_model(&device, "debug light marker", 0);
+
+        // local things like model position or rotation go in each model's own bind group
+        let mut scene = scene::Scene::new(&device, &per_object_bind_group_layout);
+        scene.add(&device, &per_object_bind_group_layout, model, model_path);
+
+        let ray_tracing =
+            supports_ray_tracing.then(|| raytracing::RayTracing::new(&device, &scene.models()));
+        let denoiser = supports_ray_tracing.then(|| {
+            denoise::Denoiser::new(
+                &device,
+                &per_frame_bind_group_layout,
+                &per_object_bind_group_layout,
+                depth_texture.texture.size(),
+                options.config.depth_mode(),
+            )
+        });
+
+        let mut shadows = shadows::CascadedShadows::new(&device, &per_object_bind_group_layout);
+        shadows.set_quality(options.config.shadow_quality);
+        let ibl = ibl::ImageBasedLighting::new(&device, &queue, directional_lights.first())?;
+
+        // MARK: BIND GROUPS
+
+        let uniforms = Uniforms {
+            camera: camera_uniform,
+            orthographic_cameras: [uniforms::CameraUniform::new(); quad_view::CAMERA_SLOTS - 1],
+            timestamp: timestamp_uniform,
+            lights: light_uniforms,
+            light_metadata: light_metadata_uniform,
+        };
+
+        let uploads = uploads::UploadBelt::new(&device);
+        // bind group layouts can be be reused with various different bind groups to allow swapping the data on the fly
+        let frames = frames::FramesInFlight::new(|_| {
+            Self::create_frame_uniforms(
+                &device,
+                &per_frame_bind_group_layout,
+                &uniforms,
+                ray_tracing.as_ref(),
+                denoiser.as_ref(),
+                &shadows,
+                &ibl,
+            )
+        });
+
+        // the per pass bind group is created by materials
+
+        let voxel_gi = vxgi::VoxelGI::new(
             &device,
             &queue,
-            &per_pass_bind_group_layout,
-        )
-        .unwrap();
+            &scene.models(),
+            &materials,
+            &per_frame_bind_group_layout,
+        );
 
         // MARK: RENDER PIPELINES
 
-        let render_pipeline = {
+        let depth_mode = options.config.depth_mode();
+        // every pipeline drawing in the scene pass is made with the scene's sample count
+        let sample_count = msaa::supported_sample_count(
+            &adapter,
+            surface_config.format,
+            options.config.msaa_samples,
+        );
+        let msaa = (sample_count > 1).then(|| {
+            msaa::Multisampling::new(
+                &device,
+                surface_config.format,
+                depth_texture.texture.size(),
+                sample_count,
+            )
+        });
+        let mut pipeline_cache = pipeline_cache::PipelineCache::new(
+            &device,
+            &adapter,
+            PIPELINE_CACHE_DIRECTORY,
+            depth_mode,
+            sample_count,
+        );
+        // with its includes pasted in, for the variants which are built by editing its source
+        let scene_shader =
+            shaders::preprocess("shader.wgsl", include_str!("shaders/shader.wgsl"), &[])?;
+
+        // premultiplied compositing wants the frame to hold premultiplied color, which alpha
+        // blending over a transparent clear gives. otherwise the shader's output is kept as is
+        let scene_blend = match alpha_mode {
+            wgpu::CompositeAlphaMode::PreMultiplied => wgpu::BlendState::ALPHA_BLENDING,
+            _ => wgpu::BlendState::REPLACE,
+        };
+
+        // and the background should show through where nothing is drawn
+        let pass_ops = match alpha_mode {
+            wgpu::CompositeAlphaMode::PreMultiplied | wgpu::CompositeAlphaMode::PostMultiplied => {
+                PassOps {
+                    color_load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    ..PassOps::new(depth_mode)
+                }
+            }
+            _ => PassOps::new(depth_mode),
+        };
+
+        // the opaque pipelines, and the same again for transparent materials: blended over
+        // what's behind them, without writing depth so they don't hide each other
+        let (
+            render_pipeline,
+            render_pipeline_raytraced,
+            render_pipeline_transparent,
+            render_pipeline_transparent_raytraced,
+            oit,
+        ) = {
             let render_pipeline_layout =
                 device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("render pipeline layout"),
@@ -358,46 +762,195 @@ impl State {
                         &per_frame_bind_group_layout,
                         &per_pass_bind_group_layout,
                         &per_object_bind_group_layout,
+                        &voxel_gi.bind_group_layout,
                     ],
                     immediate_size: 0,
                 });
 
-            let shader_descriptor = wgpu::include_wgsl!("shaders/shader.wgsl");
+            let mut create_pipeline = |shader_descriptor, blend, depth_write| {
+                pipeline_cache.render_pipeline(
+                    &device,
+                    &render_pipeline_layout,
+                    surface_config.format,
+                    Some(texture::Texture::DEPTH_FORMAT),
+                    &[model::ModelVertex::desc()],
+                    shader_descriptor,
+                    wgpu::PolygonMode::Fill,
+                    blend,
+                    depth_write,
+                )
+            };
+
+            let render_pipeline = create_pipeline(
+                shaders::wgsl("shader.wgsl", include_str!("shaders/shader.wgsl")),
+                scene_blend,
+                true,
+            );
+            let render_pipeline_transparent = create_pipeline(
+                shaders::wgsl("shader.wgsl", include_str!("shaders/shader.wgsl")),
+                wgpu::BlendState::ALPHA_BLENDING,
+                false,
+            );
+
+            // same shader with its visibility hook swapped for ray queries
+            let raytraced_source = ray_tracing
+                .as_ref()
+                .and_then(|_| raytracing::shader_source(&scene_shader));
+            let raytraced_descriptor = |source: &String| wgpu::ShaderModuleDescriptor {
+                label: Some("raytraced shader"),
+                source: wgpu::ShaderSource::Wgsl(source.clone().into()),
+            };
+            let render_pipeline_raytraced = raytraced_source
+                .as_ref()
+                .map(|source| create_pipeline(raytraced_descriptor(source), scene_blend, true));
+            let render_pipeline_transparent_raytraced = raytraced_source.as_ref().map(|source| {
+                create_pipeline(
+                    raytraced_descriptor(source),
+                    wgpu::BlendState::ALPHA_BLENDING,
+                    false,
+                )
+            });
 
-            Self::create_render_pipeline(
+            // the order independent alternative to the sorted transparent pipelines
+            let oit = oit::WeightedBlendedOit::new(
                 &device,
                 &render_pipeline_layout,
+                &scene_shader,
+                raytraced_source.as_deref(),
                 surface_config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc()],
-                shader_descriptor,
-                wgpu::PolygonMode::Fill,
+                depth_texture.texture.size(),
+                depth_mode,
+            )?;
+
+            (
+                render_pipeline,
+                render_pipeline_raytraced,
+                render_pipeline_transparent,
+                render_pipeline_transparent_raytraced,
+                oit,
             )
         };
 
-        let render_pipeline_alt = {
+        let material_atlas_bind_group_layout =
+            material_atlas::MaterialAtlas::create_bind_group_layout(&device);
+        let (render_pipeline_atlas, render_pipeline_atlas_raytraced) = {
             let render_pipeline_layout =
                 device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("render pipeline layout"),
+                    label: Some("material atlas render pipeline layout"),
                     bind_group_layouts: &[
                         &per_frame_bind_group_layout,
-                        &per_pass_bind_group_layout,
+                        &material_atlas_bind_group_layout,
                         &per_object_bind_group_layout,
+                        &voxel_gi.bind_group_layout,
                     ],
                     immediate_size: 0,
                 });
 
-            let shader_descriptor = wgpu::include_wgsl!("shaders/shader2.wgsl");
+            let base = &scene_shader;
+            let mut create_pipeline = |label, source: String| {
+                pipeline_cache.render_pipeline(
+                    &device,
+                    &render_pipeline_layout,
+                    surface_config.format,
+                    Some(texture::Texture::DEPTH_FORMAT),
+                    &[model::ModelVertex::desc()],
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some(label),
+                        source: wgpu::ShaderSource::Wgsl(source.into()),
+                    },
+                    wgpu::PolygonMode::Fill,
+                    scene_blend,
+                    true,
+                )
+            };
+
+            let render_pipeline_atlas = create_pipeline(
+                "material atlas shader",
+                material_atlas::shader_source(base).context("shader.wgsl has no material hook")?,
+            );
+            let render_pipeline_atlas_raytraced = ray_tracing
+                .as_ref()
+                .and_then(|_| raytracing::shader_source(base))
+                .and_then(|source| material_atlas::shader_source(&source))
+                .map(|source| create_pipeline("raytraced material atlas shader", source));
+
+            (render_pipeline_atlas, render_pipeline_atlas_raytraced)
+        };
+
+        // without it, every mesh is tested and drawn from the cpu
+        let gpu_culling = gpu_culling::is_supported(&adapter)
+            .then(|| {
+                gpu_culling::GpuCulling::new(
+                    &device,
+                    &mut pipeline_cache,
+                    &per_frame_bind_group_layout,
+                    &per_pass_bind_group_layout,
+                    &voxel_gi.bind_group_layout,
+                    scene.transform_stride(),
+                    ray_tracing.is_some(),
+                    surface_config.format,
+                    scene_blend,
+                )
+            })
+            .transpose()?;
 
-            Self::create_render_pipeline(
+        let render_pipeline_overdraw = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("overdraw pipeline layout"),
+                bind_group_layouts: &[
+                    &per_frame_bind_group_layout,
+                    &per_pass_bind_group_layout,
+                    &per_object_bind_group_layout,
+                ],
+                immediate_size: 0,
+            });
+            let shader = pipeline_cache.shader(
                 &device,
-                &render_pipeline_layout,
-                surface_config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc()],
-                shader_descriptor,
-                wgpu::PolygonMode::Fill,
-            )
+                shaders::wgsl("shader.wgsl", include_str!("shaders/shader.wgsl")),
+            );
+            let additive = wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            };
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("overdraw pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vertex_main"),
+                    buffers: &[model::ModelVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fragment_overdraw"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_config.format,
+                        blend: Some(wgpu::BlendState {
+                            color: additive,
+                            alpha: additive,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                // back faces too, since they cost as much to draw when they aren't culled
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview_mask: None,
+                cache: pipeline_cache.driver_cache(),
+            })
         };
 
         let debug_light_render_pipeline = {
@@ -406,9 +959,10 @@ impl State {
                 bind_group_layouts: &[&per_frame_bind_group_layout],
                 immediate_size: 0,
             });
-            let shader_descriptor = wgpu::include_wgsl!("shaders/debug_light.wgsl");
+            let shader_descriptor =
+                shaders::wgsl("debug_light.wgsl", include_str!("shaders/debug_light.wgsl"));
 
-            Self::create_render_pipeline(
+            pipeline_cache.render_pipeline(
                 &device,
                 &layout,
                 surface_config.format,
@@ -416,9 +970,62 @@ impl State {
                 &[model::ModelVertex::desc()],
                 shader_descriptor,
                 wgpu::PolygonMode::Fill,
+                wgpu::BlendState::REPLACE,
+                true,
             )
         };
 
+        let light_volumes = light_volumes::LightVolumes::new(
+            &device,
+            &per_frame_bind_group_layout,
+            surface_config.format,
+            texture::Texture::DEPTH_FORMAT,
+            depth_mode.compare(),
+            sample_count,
+        );
+        let grid = grid::Grid::new(
+            &device,
+            &per_frame_bind_group_layout,
+            surface_config.format,
+            texture::Texture::DEPTH_FORMAT,
+            depth_mode.compare(),
+            sample_count,
+        );
+        let debug_draw = debug_draw::DebugDraw::new(
+            &device,
+            &per_frame_bind_group_layout,
+            surface_config.format,
+            texture::Texture::DEPTH_FORMAT,
+            depth_mode.compare(),
+            sample_count,
+        );
+        let particles = particles::is_supported(&adapter).then(|| {
+            particles::ParticleSystem::new(
+                &device,
+                &per_frame_bind_group_layout,
+                &depth_texture,
+                surface_config.format,
+                depth_mode,
+                sample_count,
+            )
+        });
+        let fog = fog::is_supported(&adapter).then(|| {
+            fog::VolumetricFog::new(
+                &device,
+                &per_frame_bind_group_layout,
+                &depth_texture,
+                surface_config.format,
+            )
+        });
+        let outline = outline::Outline::new(
+            &device,
+            &per_frame_bind_group_layout,
+            &per_object_bind_group_layout,
+            surface_config.format,
+            texture::Texture::DEPTH_FORMAT,
+            sample_count,
+        );
+
         let debug_polygon_render_pipeline = {
             let render_pipeline_layout =
                 device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -431,9 +1038,9 @@ impl State {
                     immediate_size: 0,
                 });
 
-            let shader_descriptor = wgpu::include_wgsl!("shaders/black.wgsl");
+            let shader_descriptor = shaders::wgsl("black.wgsl", include_str!("shaders/black.wgsl"));
 
-            Self::create_render_pipeline(
+            pipeline_cache.render_pipeline(
                 &device,
                 &render_pipeline_layout,
                 surface_config.format,
@@ -441,70 +1048,156 @@ impl State {
                 &[model::ModelVertex::desc()],
                 shader_descriptor,
                 wgpu::PolygonMode::Line,
+                wgpu::BlendState::REPLACE,
+                true,
             )
         };
 
+        let shader_parameters = shader_params::ParameterLayout::reflect(&scene_shader)
+            .unwrap_or_else(|e| {
+                log::error!("{}", e);
+                shader_params::ParameterLayout::default()
+            });
+
+        let fxaa = options
+            .config
+            .fxaa
+            .then(|| antialiasing::Fxaa::new(&device, &surface_config));
+
         let mut state = Self {
             window,
+            shader_parameters,
+            voxel_gi,
+            ray_tracing,
+            denoiser,
             device,
             queue,
             surface,
             surface_config,
             is_surface_configured: true,
+            is_occluded: false,
+            present_modes: surface_capabilities.present_modes.clone(),
+            frame_limiter: timing::FrameLimiter::new(None),
+            scene_clock: timing::SceneClock::default(),
+            oit,
+            gpu_culling,
+            pipeline_cache,
             pipelines: Pipelines {
                 render: render_pipeline,
-                render_alt: render_pipeline_alt,
+                render_overdraw: render_pipeline_overdraw,
+                render_raytraced: render_pipeline_raytraced,
+                render_transparent: render_pipeline_transparent,
+                render_transparent_raytraced: render_pipeline_transparent_raytraced,
+                render_atlas: render_pipeline_atlas,
+                render_atlas_raytraced: render_pipeline_atlas_raytraced,
                 light_debug: debug_light_render_pipeline,
                 geometry_debug: debug_polygon_render_pipeline,
             },
             camera,
             projection,
-            model,
+            scene,
             debug_light_model,
+            light_volumes,
+            grid,
+            debug_draw,
+            outline,
+            shadows,
+            ibl,
             layouts: Layouts {
                 per_frame: per_frame_bind_group_layout,
                 per_pass: per_pass_bind_group_layout,
                 per_object: per_object_bind_group_layout,
+                material_atlas: material_atlas_bind_group_layout,
             },
-            per_frame_bind_group,
-            per_object_bind_group,
+            uploads,
+            frames,
+            bindings: options.config.bindings.clone(),
             camera_controller,
-            uniforms: Uniforms {
-                camera: camera_uniform,
-                camera_buffer,
-                light_buffer,
-                timestamp: timestamp_uniform,
-                timestamp_buffer,
-                model_transform_buffer,
-                lights: light_uniforms,
-                light_metadata: light_metadata_uniform,
-                light_metadata_buffer: light_metadata_buffer,
-            },
+            bookmarks: bookmarks::CameraBookmarks::load(CAMERA_BOOKMARKS_FILE),
+            camera_transition: None,
+            camera_path: bookmarks::CameraPathPlayer::load(CAMERA_PATH_FILE),
+            walk: None,
+            uniforms,
             depth_texture,
+            frame_texture,
+            pass_ops,
+            console: console::Console::default(),
+            remote: options
+                .config
+                .remote_address
+                .as_deref()
+                .and_then(|address| {
+                    remote::RemoteServer::start(address)
+                        .inspect_err(|e| log::error!("could not listen on {}: {}", address, e))
+                        .ok()
+                }),
+            #[cfg(feature = "scripting")]
+            script: None,
+            export_watcher: options
+                .config
+                .live_reload_directory
+                .as_deref()
+                .map(live_reload::ExportWatcher::new),
+            scene_tabs: vec![None],
+            active_scene: 0,
+            reference: None,
+            stereo: None,
+            bloom: None,
+            fxaa,
+            msaa,
+            particles,
+            weather: weather::Weather::default(),
+            fog,
+            material_atlas: None,
+            captures: Vec::new(),
+            recording: None,
             diagnostics: Diagnostics {
-                start_time: std::time::Instant::now(),
                 frame_count: 0,
                 frame_time_avg: timing::RollingAverage::new(200),
+                frame_stats: timing::FrameStats::new(200),
                 render_time_avg: timing::RollingAverage::new(200),
                 update_time_avg: timing::RollingAverage::new(200),
+                update_breakdown: timing::SubsystemTimings::new(200),
+                render_stats: render_stats::RenderStats::default(),
+                memory_stats: render_stats::MemoryStats::default(),
             },
             variables: Variables {
                 is_mouse_pressed: false,
+                modifiers: winit::keyboard::ModifiersState::empty(),
+                click_start: None,
+                selected: None,
+                cursor_position: None,
+                is_placing_light: false,
+                parameter_editor: shader_params::ParameterEditor::default(),
                 enable_geometry_debug: false,
-                swap_pipelines: false,
-                enable_light_rotation: false,
+                enable_bounds_debug: false,
+                show_frame_graph: false,
+                debug_view: uniforms::DebugView::default(),
+                enable_raytracing: false,
+                enable_oit: false,
+                enable_gpu_culling: false,
+                fit_z_planes: options.fit_z_planes,
+                enable_quad_view: false,
+                redraw_on_change: false,
+                needs_redraw: true,
+                needs_revoxelize: false,
+                benchmark: None,
+                eye_separation: 0.1,
             },
-            debug_tbn_extras: None,
+            options,
             materials: materials,
             material_map: material_map,
+            texture_cache,
             point_lights,
             directional_lights,
             spot_lights,
+            animations,
         };
 
-        if ENABLE_DEBUG_TBN {
-            state.debug_tbn_extras = Some(Self::create_debug_extras(&mut state));
+        for material in state.materials.iter_mut() {
+            material.parameters.reset(&state.shader_parameters);
         }
+        state.pipeline_cache.save();
 
         Ok(state)
     }
@@ -512,81 +1205,357 @@ impl State {
     // MARK: NEW DONE
 
     fn create_camera(
-        device: &wgpu::Device,
         surface_config: &wgpu::SurfaceConfiguration,
-    ) -> (
-        camera::Camera,
-        camera::Projection,
-        uniforms::CameraUniform,
-        wgpu::Buffer,
-    ) {
+        options: &options::StartupOptions,
+    ) -> (camera::Camera, camera::Projection, uniforms::CameraUniform) {
         let camera = camera::Camera::new([0.0, 0.0, 10.0], cgmath::Deg(-90.0), cgmath::Deg(0.0));
-        let projection = camera::Projection::new(
+        let mut projection = camera::Projection::new(
             surface_config.width,
             surface_config.height,
             80.0,
             0.1,
             100.0,
+            options.config.depth_mode(),
         );
+        if let Some((near, far)) = options.z_planes {
+            projection.set_z_planes(near, far);
+        }
 
         let mut camera_uniform = uniforms::CameraUniform::new();
         camera_uniform.update_view_proj(&camera, &projection);
 
+        (camera, projection, camera_uniform)
+    }
+
+    // the buffers for one frame in flight, starting out with the current uniforms
+    fn create_frame_uniforms(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: &Uniforms,
+        ray_tracing: Option<&raytracing::RayTracing>,
+        denoiser: Option<&denoise::Denoiser>,
+        shadows: &shadows::CascadedShadows,
+        ibl: &ibl::ImageBasedLighting,
+    ) -> FrameUniforms {
+        let camera_stride = (uniforms::CameraUniform::SIZE as u32)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let mut camera_contents = vec![0; camera_stride as usize * quad_view::CAMERA_SLOTS];
+        camera_contents[..uniforms::CameraUniform::SIZE as usize]
+            .copy_from_slice(bytemuck::bytes_of(&uniforms.camera));
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("camera buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
+            contents: &camera_contents,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("light buffer"),
+            contents: bytemuck::cast_slice(uniforms.lights.as_slice()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let light_metadata_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("light metadata buffer"),
+            contents: bytemuck::cast_slice(&[uniforms.light_metadata]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let timestamp_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("timestamp buffer"),
+            contents: bytemuck::cast_slice(&[uniforms.timestamp]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        (camera, projection, camera_uniform, camera_buffer)
+        let bind_group = Self::create_per_frame_bind_group(
+            device,
+            layout,
+            [
+                &camera_buffer,
+                &light_buffer,
+                &light_metadata_buffer,
+                &timestamp_buffer,
+            ],
+            ray_tracing,
+            denoiser,
+            shadows,
+            ibl,
+        );
+
+        FrameUniforms {
+            camera_buffer,
+            camera_stride,
+            light_buffer,
+            light_metadata_buffer,
+            timestamp_buffer,
+            bind_group,
+        }
+    }
+
+    // buffers are the camera, lights, light metadata and timestamp, in binding order
+    fn create_per_frame_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffers: [&wgpu::Buffer; 4],
+        ray_tracing: Option<&raytracing::RayTracing>,
+        denoiser: Option<&denoise::Denoiser>,
+        shadows: &shadows::CascadedShadows,
+        ibl: &ibl::ImageBasedLighting,
+    ) -> wgpu::BindGroup {
+        let mut entries = buffers
+            .into_iter()
+            .zip(0..)
+            .map(|(buffer, binding)| wgpu::BindGroupEntry {
+                binding,
+                resource: match binding {
+                    // one slot of the camera buffer, picked by the dynamic offset
+                    0 => wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(uniforms::CameraUniform::SIZE),
+                    }),
+                    _ => buffer.as_entire_binding(),
+                },
+            })
+            .collect::<Vec<_>>();
+        if let Some(ray_tracing) = ray_tracing {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 4,
+                resource: ray_tracing.tlas.as_binding(),
+            });
+        }
+        entries.extend([
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: shadows.uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(&shadows.depth_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::Sampler(&shadows.depth_texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: wgpu::BindingResource::TextureView(&shadows.point_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: ibl.uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: wgpu::BindingResource::TextureView(&ibl.irradiance.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::TextureView(&ibl.specular.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 12,
+                resource: wgpu::BindingResource::TextureView(&ibl.brdf_lut.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 13,
+                resource: wgpu::BindingResource::Sampler(&ibl.sampler),
+            },
+        ]);
+        if let Some(denoiser) = denoiser {
+            entries.extend([
+                wgpu::BindGroupEntry {
+                    binding: 14,
+                    resource: wgpu::BindingResource::TextureView(denoiser.output_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 15,
+                    resource: denoiser.uniform_buffer.as_entire_binding(),
+                },
+            ]);
+        }
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &entries,
+            label: Some("camera_bind_group"),
+        })
     }
 
     fn create_bind_group_layouts(
         device: &wgpu::Device,
+        supports_ray_tracing: bool,
     ) -> (
         wgpu::BindGroupLayout,
         wgpu::BindGroupLayout,
         wgpu::BindGroupLayout,
     ) {
-        let per_frame = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                // camera uniform
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                // light uniform
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+        let mut per_frame_entries = vec![
+            // camera uniform, offset to the slot of the view being drawn (see quad_view.rs)
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX
+                    | wgpu::ShaderStages::FRAGMENT
+                    | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(uniforms::CameraUniform::SIZE),
+                },
+                count: None,
+            },
+            // light uniform
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX
+                    | wgpu::ShaderStages::FRAGMENT
+                    | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // light metadata uniform
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX
+                    | wgpu::ShaderStages::FRAGMENT
+                    | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // timestamp uniform
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // shadow uniform. the shadows are read by the fog's inject pass as well, see fog.rs
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // the shadow cascades
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            // the shadow comparison sampler
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            // the point light's shadow map faces
+            wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            // environment uniform
+            wgpu::BindGroupLayoutEntry {
+                binding: 9,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // the irradiance and prefiltered specular cubes
+            wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 11,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // the brdf lookup table
+            wgpu::BindGroupLayoutEntry {
+                binding: 12,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 13,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+
+        // the scene's acceleration structure, for ray queries
+        if supports_ray_tracing {
+            per_frame_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::AccelerationStructure {
+                    vertex_return: false,
                 },
-                // light metadata uniform
+                count: None,
+            });
+            // the denoised ambient occlusion and the denoiser's uniform, see denoise.rs
+            per_frame_entries.extend([
                 wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                    binding: 14,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
                     },
                     count: None,
                 },
-                // timestamp uniform
                 wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    binding: 15,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -594,7 +1563,11 @@ impl State {
                     },
                     count: None,
                 },
-            ],
+            ]);
+        }
+
+        let per_frame = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &per_frame_entries,
             label: Some("per frame bind group layout"),
         });
 
@@ -647,6 +1620,143 @@ impl State {
                     },
                     count: None,
                 },
+                // the reflected shader parameters
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // the specular texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // the shininess texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // the alpha texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // the emissive texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 12,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 13,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // the roughness texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 14,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 15,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // the metallic texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 16,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 17,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // the height texture data binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 18,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the sampler binding layout
+                wgpu::BindGroupLayoutEntry {
+                    binding: 19,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
             ],
             label: Some("per pass bind group layout"),
         });
@@ -658,8 +1768,9 @@ impl State {
                 visibility: wgpu::ShaderStages::VERTEX,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                    // every model's transform is in the scene's one buffer
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(scene::Scene::TRANSFORM_SIZE),
                 },
                 count: None,
             }],
@@ -668,457 +1779,2919 @@ impl State {
         (per_frame, per_pass, per_object)
     }
 
-    fn create_debug_extras(state: &mut Self) -> DebugTBNStateExtras {
-        let per_object_debug_bind_group_layout =
-            state
-                .device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: Some("debug TBN per object bind group layout"),
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                    ],
-                });
+    pub fn update(&mut self, dt: Duration) {
+        self.diagnostics.render_stats = render_stats::RenderStats::default();
+        self.diagnostics.memory_stats = render_stats::MemoryStats::current();
+        self.frames.begin_frame(&self.device);
+        self.save_finished_captures();
+        if let Some(recording) = &mut self.recording {
+            recording.collect_frames();
+        }
 
-        let debug_tbn_uniforms = model::VectorDebugUniform::from_mesh_tbn(&state.model.meshes[0]);
+        let requests = self
+            .remote
+            .as_ref()
+            .map_or(Vec::new(), |remote| remote.poll().collect());
+        for request in requests {
+            let result = self.run_remote_command(request.command.clone());
+            request.respond(result);
+        }
 
-        println!("t count: {}", debug_tbn_uniforms[0].len());
-        println!("b count: {}", debug_tbn_uniforms[1].len());
-        println!("n count: {}", debug_tbn_uniforms[2].len());
+        #[cfg(feature = "scripting")]
+        self.update_script();
 
-        println!("vertex count: {}", state.model.meshes[0].verts.len());
+        let exported = self
+            .export_watcher
+            .as_mut()
+            .map_or(Vec::new(), |watcher| watcher.poll());
+        for path in exported {
+            self.import_export(&path);
+        }
 
-        let debug_tangent_buffer =
-            state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("debug TBN buffer"),
-                    contents: bytemuck::cast_slice(&debug_tbn_uniforms[0][..]),
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                });
+        if std::mem::take(&mut self.variables.needs_revoxelize) {
+            let start = Instant::now();
+            self.voxel_gi
+                .revoxelize(&self.queue, &self.scene.models(), &self.materials);
+            self.diagnostics
+                .update_breakdown
+                .push("voxels", start.elapsed());
+        }
 
-        let debug_bitangent_buffer =
-            state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("debug TBN buffer"),
-                    contents: bytemuck::cast_slice(&debug_tbn_uniforms[1][..]),
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                });
+        if let Some(benchmark) = &mut self.variables.benchmark {
+            benchmark.push(dt);
+            if benchmark.is_finished() {
+                log::info!("{}", benchmark.describe());
+                self.console.set_message(benchmark.describe());
+                self.variables.benchmark = None;
+            } else {
+                // every frame has to be drawn for the timings to mean anything
+                self.variables.needs_redraw = true;
+            }
+        }
 
-        let debug_normal_buffer =
-            state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("debug TBN buffer"),
-                    contents: bytemuck::cast_slice(&debug_tbn_uniforms[2][..]),
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                });
+        let start = Instant::now();
+        let previous_camera = self.uniforms.camera;
+        self.camera_controller.update_camera(&mut self.camera, dt);
+        if let Some(walk) = &mut self.walk {
+            walk.update(&mut self.camera, &self.scene, dt.as_secs_f32());
+        }
+        self.camera_controller
+            .update_projection(&mut self.projection, dt);
+        if self.variables.fit_z_planes
+            && let Some(bounds) = self.scene.bounds()
+        {
+            self.projection.fit_z_planes(&self.camera, &bounds);
+        }
+        // the controller still runs, so mouse movement during the flight is used up rather than
+        // saved for when it lands
+        if let Some(transition) = &mut self.camera_transition {
+            let (pose, has_arrived) = transition.advance(dt.as_secs_f32());
+            pose.apply(&mut self.camera, &mut self.projection);
+            if has_arrived {
+                self.camera_transition = None;
+            }
+        }
+        let pose = bookmarks::CameraPose::new(&self.camera, &self.projection);
+        if let Some(pose) = self.camera_path.advance(dt.as_secs_f32(), pose) {
+            pose.apply(&mut self.camera, &mut self.projection);
+        }
+        self.uniforms
+            .camera
+            .update_view_proj(&self.camera, &self.projection);
+        if bytemuck::bytes_of(&previous_camera) != bytemuck::bytes_of(&self.uniforms.camera) {
+            self.variables.needs_redraw = true;
+        }
+        if self.variables.enable_quad_view {
+            // around the origin when there's nothing to look at
+            let bounds = self.scene.bounds().unwrap_or(math::Aabb {
+                min: cgmath::Vector3::new(-1.0, -1.0, -1.0),
+                max: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            });
+            let cameras = quad_view::orthographic_cameras(&bounds, &self.projection);
+            let previous = std::mem::replace(&mut self.uniforms.orthographic_cameras, cameras);
+            if bytemuck::bytes_of(&previous) != bytemuck::bytes_of(&cameras) {
+                self.variables.needs_redraw = true;
+            }
+        }
+        self.diagnostics
+            .update_breakdown
+            .push("camera", start.elapsed());
+
+        // everything from here on follows the scene's time rather than real time
+        let scene_dt = self.scene_clock.advance(dt);
+        if self.animations.is_playing && !scene_dt.is_zero() {
+            let dt = scene_dt.as_secs_f32();
+            if self.animations.advance(dt) {
+                self.apply_light_animations();
+            }
+            if self.scene.animate(dt) {
+                // like moving them through the remote, the voxels have to follow
+                self.variables.needs_revoxelize = true;
+                self.variables.needs_redraw = true;
+            }
+        }
 
-        let tangent_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("debug tbn tangent bind group"),
-            layout: &per_object_debug_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: state.uniforms.model_transform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: debug_tangent_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        self.weather.update(
+            &mut self.uploads,
+            self.particles.as_mut(),
+            &self.camera,
+            scene_dt.as_secs_f32(),
+        );
+        self.ibl
+            .set_wetness(self.weather.wetness(), &mut self.uploads);
+        if self.weather.is_active() && !scene_dt.is_zero() {
+            self.variables.needs_redraw = true;
+        }
+        if let Some(particles) = &mut self.particles {
+            particles.update(
+                &mut self.uploads,
+                &self.camera,
+                &self.projection,
+                scene_dt.as_secs_f32(),
+            );
+            // they keep moving without anything else changing
+            if particles.is_active() && !scene_dt.is_zero() {
+                self.variables.needs_redraw = true;
+            }
+        }
+        // the generated sky follows the sun, however the light was moved
+        if let Err(e) =
+            self.ibl
+                .follow_sun(&self.device, &self.queue, self.directional_lights.first())
+        {
+            log::error!("{}", e);
+        }
+        if let Some(fog) = self.fog.as_mut().filter(|fog| fog.is_enabled) {
+            fog.update(
+                &mut self.uploads,
+                &self.camera,
+                &self.projection,
+                self.scene_clock.elapsed().as_secs_f32(),
+                self.ibl.sky_radiance_sh(),
+            );
+            // drifting on the wind
+            if !scene_dt.is_zero() {
+                self.variables.needs_redraw = true;
+            }
+        }
 
-        let bitangent_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("debug tbn bitangent bind group"),
-            layout: &per_object_debug_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: state.uniforms.model_transform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: debug_bitangent_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let start = Instant::now();
+        self.shadows.update(
+            &mut self.uploads,
+            &self.camera,
+            &self.projection,
+            self.directional_lights.first(),
+            self.point_lights.first(),
+        );
+        self.diagnostics
+            .update_breakdown
+            .push("shadows", start.elapsed());
 
-        let normal_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("debug tbn normal bind group"),
-            layout: &per_object_debug_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: state.uniforms.model_transform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: debug_normal_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let start = Instant::now();
+        self.uploads.write(
+            &self.frames.current().camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms.camera]),
+        );
+        if self.variables.enable_quad_view {
+            let camera_stride = self.frames.current().camera_stride;
+            for (camera, slot) in self.uniforms.orthographic_cameras.iter().zip(1..) {
+                self.uploads.write(
+                    &self.frames.current().camera_buffer,
+                    (slot * camera_stride) as wgpu::BufferAddress,
+                    bytemuck::bytes_of(camera),
+                );
+            }
+        }
+        // the lights are rewritten every frame too, since the other frames' copies don't have
+        // whatever changed while this copy was in flight
+        self.uploads.write(
+            &self.frames.current().light_buffer,
+            0,
+            bytemuck::cast_slice(self.uniforms.lights.as_slice()),
+        );
+        if self.light_volumes.is_visible {
+            self.light_volumes.update(
+                &self.device,
+                &mut self.uploads,
+                &self.point_lights,
+                &self.spot_lights,
+            );
+        }
 
-        let debug_vector_model = resources::load_obj_model(
-            "src/assets/models/arrow.obj",
-            &mut state.materials,
-            &mut state.material_map,
-            &state.device,
-            &state.queue,
-            &state.layouts.per_pass,
-        )
-        .unwrap();
+        if self.grid.is_visible {
+            self.grid.draw_axes(&mut self.debug_draw);
+        }
+        if self.variables.enable_geometry_debug {
+            self.draw_tangent_frames();
+        }
+        if self.variables.enable_bounds_debug {
+            self.draw_bounds();
+        }
+        if self.variables.show_frame_graph {
+            self.draw_frame_time_graph();
+        }
+        self.debug_draw.upload(
+            &self.device,
+            &mut self.uploads,
+            [self.surface_config.width, self.surface_config.height],
+        );
 
-        let debug_tbn_render_pipeline = {
-            let render_pipeline_layout =
-                state
-                    .device
-                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                        label: Some("debug TBN layout"),
-                        bind_group_layouts: &[
-                            &state.layouts.per_frame,
-                            &state.layouts.per_pass,
-                            &per_object_debug_bind_group_layout,
-                        ],
-                        immediate_size: 0,
+        for material in self.materials.iter_mut() {
+            material.parameters.upload(&mut self.uploads);
+        }
+
+        // frozen while paused, so effects driven by time hold still too
+        self.uniforms.timestamp.update(
+            self.scene_clock.elapsed(),
+            scene_dt,
+            self.scene_clock.frame_index(),
+        );
+        self.uploads.write(
+            &self.frames.current().timestamp_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms.timestamp]),
+        );
+        self.uploads.flush(
+            &self.device,
+            &self.queue,
+            &mut self.diagnostics.render_stats,
+        );
+        self.diagnostics
+            .update_breakdown
+            .push("uniforms", start.elapsed());
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.surface_config.width = width;
+            self.surface_config.height = height;
+
+            self.surface.configure(&self.device, &self.surface_config);
+            self.is_surface_configured = true;
+
+            self.depth_texture = texture::Texture::create_depth_texture(
+                &self.device,
+                &self.surface_config,
+                "depth texture",
+            );
+            if self.frame_texture.is_some() {
+                self.frame_texture = Some(texture::Texture::create_frame_texture(
+                    &self.device,
+                    &self.surface_config,
+                    "frame texture",
+                ));
+            }
+            if let Some(stereo) = &mut self.stereo {
+                stereo.resize(&self.device, &self.surface_config);
+            }
+            if let Some(bloom) = &mut self.bloom {
+                bloom.resize(&self.device, &self.surface_config);
+            }
+            if let Some(fxaa) = &mut self.fxaa {
+                fxaa.resize(&self.device, &self.surface_config);
+            }
+            if let Some(particles) = &mut self.particles {
+                particles.resize(&self.device, &self.depth_texture);
+            }
+            if let Some(fog) = &mut self.fog {
+                fog.resize(&self.device, &self.depth_texture);
+            }
+            self.weather.resize(&self.device, &self.surface_config);
+            self.variables.needs_redraw = true;
+
+            self.projection.resize(width, height);
+        } else {
+            // minimized. there's nothing to draw to until it's restored, see is_hidden
+            self.is_surface_configured = false;
+        }
+    }
+
+    // minimized or covered, so there's no point drawing. the app stops asking for frames until
+    // the window is shown again rather than presenting to a surface no one can see
+    pub fn is_hidden(&self) -> bool {
+        !self.is_surface_configured || self.is_occluded
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.window.request_redraw();
+
+        if !self.is_surface_configured {
+            log::warn!("render called while surface is not configured");
+            return Ok(());
+        }
+
+        // wait for the surface to provide a new texture to which to render
+        let target_surface = self.surface.get_current_texture()?;
+
+        // TextureView controls how the rendering code interacts with the texture
+        let target_view = target_surface
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        // create a command encoder to send commands to the gpu
+        let mut command_encoder =
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("render command encoder"),
+                });
+
+        // with redraw on change, an unchanged frame is re-presented from the frame texture
+        // instead of being drawn again
+        let is_scene_drawn = !self.variables.redraw_on_change
+            || self.variables.needs_redraw
+            || self.frame_texture.is_none();
+
+        if is_scene_drawn {
+            if self.stereo.is_some() {
+                self.encode_stereo(&mut command_encoder, &target_view);
+            } else {
+                self.encode_scene(&mut command_encoder, &target_view);
+            }
+            self.variables.needs_redraw = false;
+        }
+
+        if let Some(frame_texture) = &self.frame_texture {
+            command_encoder.insert_debug_marker("copy frame texture to surface");
+            command_encoder.copy_texture_to_texture(
+                frame_texture.texture.as_image_copy(),
+                target_surface.texture.as_image_copy(),
+                frame_texture.texture.size(),
+            );
+
+            if let Some(recording) = &mut self.recording {
+                recording.copy_frame(&self.device, &mut command_encoder, &frame_texture.texture);
+            }
+        }
+
+        if let Some(reference) = &self.reference {
+            reference.encode(&mut command_encoder, &target_view);
+        }
+
+        // close the command encoder and submit the instructions to the gpu's render queue
+        let submission = self.queue.submit(std::iter::once(command_encoder.finish()));
+        self.frames.end_frame(&self.queue, submission);
+        if let Some(recording) = &mut self.recording {
+            recording.submitted();
+        }
+
+        self.diagnostics.frame_count += 1;
+
+        // put the output from the rendering onto the window
+        target_surface.present();
+        Ok(())
+    }
+
+    // draws the scene into the frame texture if there is one, otherwise straight to the surface.
+    // with post processes on it goes into the first one's source, and they put it there
+    fn encode_scene(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) {
+        let color_view = match &self.frame_texture {
+            Some(frame_texture) => frame_texture.view.clone(),
+            None => target_view.clone(),
+        };
+        let scene_view = self.post_process_source(&color_view);
+        self.encode_scene_into(command_encoder, &scene_view);
+        self.encode_post_processes(command_encoder, &color_view);
+    }
+
+    // where the frame is drawn for the post processes to take it on to `color_view`
+    fn post_process_source(&self, color_view: &wgpu::TextureView) -> wgpu::TextureView {
+        match (&self.bloom, self.weather.droplets(), &self.fxaa) {
+            (Some(bloom), _, _) => bloom.source_view.clone(),
+            (None, Some(droplets), _) => droplets.source_view.clone(),
+            (None, None, Some(fxaa)) => fxaa.source_view.clone(),
+            (None, None, None) => color_view.clone(),
+        }
+    }
+
+    // each post process reads the previous one's output, the last writing to `color_view`
+    fn encode_post_processes(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+    ) {
+        let fxaa_source = self
+            .fxaa
+            .as_ref()
+            .map_or(color_view, |fxaa| &fxaa.source_view);
+        let droplets = self.weather.droplets();
+        let droplets_source = droplets.map_or(fxaa_source, |droplets| &droplets.source_view);
+        if let Some(bloom) = &self.bloom {
+            bloom.encode(command_encoder, droplets_source);
+        }
+        if let Some(droplets) = droplets {
+            droplets.encode(command_encoder, fxaa_source);
+        }
+        if let Some(fxaa) = &self.fxaa {
+            fxaa.encode(command_encoder, color_view);
+        }
+    }
+
+    // draws each eye into its own texture and combines them where encode_scene would draw.
+    // the eyes only differ in the camera uniform, so each is submitted on its own after
+    // writing its camera, and the center camera is written back for whatever comes after
+    fn encode_stereo(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) {
+        let Some(eye_views) = self.stereo.as_ref().map(|s| s.eye_views.clone()) else {
+            return;
+        };
+
+        for (side, eye_view) in [-0.5, 0.5].into_iter().zip(&eye_views) {
+            let eye = self
+                .camera
+                .offset_sideways(side * self.variables.eye_separation);
+            let mut eye_uniform = self.uniforms.camera;
+            eye_uniform.update_view_proj(&eye, &self.projection);
+            self.diagnostics.render_stats.write_buffer(
+                &self.queue,
+                &self.frames.current().camera_buffer,
+                0,
+                bytemuck::cast_slice(&[eye_uniform]),
+            );
+
+            let mut eye_encoder =
+                self.device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("stereo eye command encoder"),
                     });
+            eye_encoder.push_debug_group(if side < 0.0 { "left eye" } else { "right eye" });
+            self.encode_scene_into(&mut eye_encoder, eye_view);
+            eye_encoder.pop_debug_group();
+            self.queue.submit(std::iter::once(eye_encoder.finish()));
+        }
 
-            let shader_descriptor = wgpu::include_wgsl!("shaders/debug_vector.wgsl");
+        self.diagnostics.render_stats.write_buffer(
+            &self.queue,
+            &self.frames.current().camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms.camera]),
+        );
 
-            Self::create_render_pipeline(
-                &state.device,
-                &render_pipeline_layout,
-                state.surface_config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc()],
-                shader_descriptor,
-                wgpu::PolygonMode::Line,
-            )
+        if let Some(stereo) = &self.stereo {
+            let color_view = match &self.frame_texture {
+                Some(frame_texture) => &frame_texture.view,
+                None => target_view,
+            };
+            stereo.encode(command_encoder, &self.post_process_source(color_view));
+            self.encode_post_processes(command_encoder, color_view);
+        }
+    }
+
+    fn encode_scene_into(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+    ) {
+        command_encoder.push_debug_group("scene");
+
+        self.voxel_gi
+            .encode(command_encoder, &self.frames.current().bind_group, 0);
+
+        // the per frame bind group holds the tlas, so it has to be built even when unused
+        if let Some(ray_tracing) = &mut self.ray_tracing {
+            ray_tracing.encode(command_encoder, &self.scene.models());
+        }
+
+        self.scene
+            .upload_transforms(&self.queue, &mut self.diagnostics.render_stats);
+
+        // the ray traced ambient occlusion is traced and denoised before the scene pass reads
+        // it. only for the one camera, which its history follows
+        let is_denoised = self.variables.enable_raytracing
+            && self.variables.debug_view != uniforms::DebugView::Overdraw
+            && self.quad_viewports().is_none()
+            && self.stereo.is_none();
+        let resized = self
+            .denoiser
+            .as_mut()
+            .is_some_and(|denoiser| denoiser.fit(&self.device, self.depth_texture.texture.size()));
+        if resized {
+            self.rebind_frame_uniforms();
+        }
+        let opaque = if is_denoised && self.denoiser.is_some() {
+            self.meshes_by_material(|mesh| !self.materials[mesh.material].is_transparent())
+        } else {
+            Vec::new()
+        };
+        if let Some(denoiser) = &mut self.denoiser {
+            if is_denoised {
+                denoiser.encode(
+                    &self.queue,
+                    command_encoder,
+                    &mut self.diagnostics.render_stats,
+                    &self.frames.current().bind_group,
+                    &self.scene,
+                    &opaque,
+                    self.projection.perspective_matrix() * self.camera.view_matrix(),
+                    self.diagnostics.frame_count as u32,
+                );
+            } else {
+                denoiser.skip(&self.queue);
+            }
+        }
+        self.shadows.encode(
+            command_encoder,
+            &mut self.diagnostics.render_stats,
+            &self.scene,
+            &self.materials,
+        );
+        let transparent = self.transparent_meshes_back_to_front();
+
+        // overdraw counts everything in one go, with the same pipeline, transparent or not
+        let is_overdraw = self.variables.debug_view == uniforms::DebugView::Overdraw;
+        let atlas = self.material_atlas.as_ref().filter(|_| !is_overdraw);
+        let unbatched = self.meshes_by_material(|mesh| {
+            atlas.is_none_or(|atlas| atlas.offset(mesh.material).is_none())
+                && (is_overdraw || !self.materials[mesh.material].is_transparent())
+        });
+        let batched = match atlas {
+            Some(atlas) => self.meshes_by_material(|mesh| {
+                atlas.offset(mesh.material).is_some()
+                    && !self.materials[mesh.material].is_transparent()
+            }),
+            None => Vec::new(),
+        };
+
+        // each view's part of the target, or None for all of it, and its camera's offset in the
+        // camera buffer
+        let camera_stride = self.frames.current().camera_stride;
+        let views = match self.quad_viewports() {
+            Some(viewports) => viewports
+                .map(|v| (Some(v.rect), v.camera_slot as u32 * camera_stride))
+                .to_vec(),
+            None => vec![(None, 0)],
+        };
+        let is_quad_view = views.len() > 1;
+        // the transparent meshes' separate passes only cover the whole target, so the quad
+        // view blends them in each view, sorted for the camera
+        let is_oit = self.variables.enable_oit && !is_overdraw && !is_quad_view;
+
+        // the meshes with materials of their own are culled in a compute pass first. it culls
+        // to the camera's view, which the quad view's other views would see past
+        let is_gpu_culled = self.variables.enable_gpu_culling && !is_overdraw && !is_quad_view;
+        if let Some(gpu_culling) = self.gpu_culling.as_mut().filter(|_| is_gpu_culled) {
+            gpu_culling.encode(
+                &self.device,
+                &self.queue,
+                &mut self.diagnostics.render_stats,
+                command_encoder,
+                &self.frames.current().bind_group,
+                0,
+                &self.scene,
+                &unbatched,
+            );
+        }
+        let gpu_culling = self.gpu_culling.as_ref().filter(|_| is_gpu_culled);
+
+        // while the depth buffer still holds the last frame, which they collide with
+        if let Some(particles) = &mut self.particles {
+            particles.encode(command_encoder, !is_quad_view);
+        }
+
+        // the fog is looked up by distance from the one camera, so the quad view goes without
+        let fog = self
+            .fog
+            .as_ref()
+            .filter(|fog| fog.is_enabled && !is_quad_view);
+        if let Some(fog) = fog {
+            fog.encode(command_encoder, &self.frames.current().bind_group);
+        }
+
+        // multisampled, the pass draws into targets of its own and resolves the color into
+        // `color_view`
+        if let Some(msaa) = &mut self.msaa {
+            msaa.fit(&self.device, self.depth_texture.texture.size());
+        }
+        let (pass_color_view, resolve_target, pass_depth_view) = match &self.msaa {
+            Some(msaa) => (msaa.color_view(), Some(color_view), msaa.depth_view()),
+            None => (color_view, None, &self.depth_texture.view),
         };
 
-        DebugTBNStateExtras {
-            tangent_bind_group,
-            bitangent_bind_group,
-            normal_bind_group,
-            debug_tbn_render_pipeline,
-            debug_tbn_uniforms,
-            debug_tangent_buffer,
-            debug_bitangent_buffer,
-            debug_normal_buffer,
-            debug_vector_model,
-        }
+        // encode the rendering pass:
+        {
+            let render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("render pass"),
+                color_attachments: &[
+                    // location[0] refers to this color attachment
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: pass_color_view,
+                        resolve_target,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: self.pass_ops.color_load,
+                            store: self.pass_ops.store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: pass_depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: self.pass_ops.depth_load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    // only the outline uses the stencil, within this pass
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+
+            let scissor = self.scissor_rect();
+            let mut render_pass = render_stats::CountingRenderPass::new(
+                render_pass,
+                &mut self.diagnostics.render_stats,
+            );
+
+            // everything is drawn again for each view, in its own part of the target
+            for &(viewport, camera_offset) in &views {
+                if let Some(viewport) = viewport {
+                    render_pass.set_viewport(viewport);
+                    render_pass.push_debug_group(&format!(
+                        "{} view",
+                        quad_view::name((camera_offset / camera_stride) as usize)
+                    ));
+                }
+                if let Some(scissor) = Self::intersect_rects(viewport, scissor) {
+                    render_pass.set_scissor_rect(scissor);
+                }
+
+                if is_overdraw {
+                    render_pass.set_pipeline(&self.pipelines.render_overdraw);
+                } else if let Some(gpu_culling) = gpu_culling {
+                    render_pass
+                        .set_pipeline(gpu_culling.pipeline(self.variables.enable_raytracing));
+                    render_pass.set_bind_group(3, &self.voxel_gi.bind_group, &[]);
+                } else {
+                    match &self.pipelines.render_raytraced {
+                        Some(pipeline) if self.variables.enable_raytracing => {
+                            render_pass.set_pipeline(pipeline)
+                        }
+                        _ => render_pass.set_pipeline(&self.pipelines.render),
+                    }
+                    render_pass.set_bind_group(3, &self.voxel_gi.bind_group, &[]);
+                }
+
+                render_pass.set_bind_group(0, &self.frames.current().bind_group, &[camera_offset]);
+                // render_pass.set_bind_group(1, &self.per_pass_bind_group, &[]);
+                // render_pass.set_bind_group(2, &self.per_object_bind_group, &[]);
+
+                render_pass.push_debug_group("models");
+                // the meshes with materials of their own first, then all of the batched ones with
+                // the atlas bound once
+                match gpu_culling {
+                    Some(gpu_culling) => {
+                        gpu_culling.draw(&mut render_pass, &self.scene, &self.materials)
+                    }
+                    None => {
+                        for &(i, mesh) in &unbatched {
+                            let mesh = &self.scene.objects()[i].model.meshes[mesh];
+                            render_pass.draw_mesh(
+                                mesh,
+                                &self.materials[mesh.material],
+                                &self.scene.bind_group,
+                                &[self.scene.transform_offset(i)],
+                            );
+                        }
+                    }
+                }
+                if let Some(atlas) = atlas
+                    && !batched.is_empty()
+                {
+                    render_pass.push_debug_group("material atlas batch");
+                    match &self.pipelines.render_atlas_raytraced {
+                        Some(pipeline) if self.variables.enable_raytracing => {
+                            render_pass.set_pipeline(pipeline)
+                        }
+                        _ => render_pass.set_pipeline(&self.pipelines.render_atlas),
+                    }
+                    for &(i, mesh) in &batched {
+                        let mesh = &self.scene.objects()[i].model.meshes[mesh];
+                        render_pass.draw_mesh_batched(
+                            mesh,
+                            &atlas.bind_group,
+                            atlas.offset(mesh.material).unwrap_or(0),
+                            &self.scene.bind_group,
+                            &[self.scene.transform_offset(i)],
+                        );
+                    }
+                    render_pass.pop_debug_group();
+                }
+                render_pass.pop_debug_group();
+
+                // transparent meshes last, farthest first, so each blends over everything behind it
+                if !is_overdraw && !is_oit && !transparent.is_empty() {
+                    render_pass.push_debug_group("transparent meshes");
+                    match &self.pipelines.render_transparent_raytraced {
+                        Some(pipeline) if self.variables.enable_raytracing => {
+                            render_pass.set_pipeline(pipeline)
+                        }
+                        _ => render_pass.set_pipeline(&self.pipelines.render_transparent),
+                    }
+                    render_pass.set_bind_group(3, &self.voxel_gi.bind_group, &[]);
+                    for &(i, mesh) in &transparent {
+                        let mesh = &self.scene.objects()[i].model.meshes[mesh];
+                        render_pass.draw_mesh(
+                            mesh,
+                            &self.materials[mesh.material],
+                            &self.scene.bind_group,
+                            &[self.scene.transform_offset(i)],
+                        );
+                    }
+                    render_pass.pop_debug_group();
+                }
+
+                if let Some(particles) = self.particles.as_ref().filter(|p| p.is_active()) {
+                    render_pass.push_debug_group("particles");
+                    particles.draw(
+                        &mut render_pass,
+                        &self.frames.current().bind_group,
+                        camera_offset,
+                    );
+                    render_pass.pop_debug_group();
+                }
+
+                render_pass.push_debug_group("light markers");
+                render_pass.set_pipeline(&self.pipelines.light_debug);
+
+                // render_pass.set_bind_group(0, &self.frames.current().bind_group, &[]);
+                // render_pass.set_bind_group(1, &self.per_pass_bind_group, &[]);
+                // render_pass.set_bind_group(2, &self.per_object_bind_group, &[]);
+
+                render_pass.draw_model(
+                    &self.debug_light_model,
+                    &self.materials,
+                    &self.frames.current().bind_group,
+                    &[camera_offset],
+                );
+                render_pass.pop_debug_group();
+
+                if self.grid.is_visible {
+                    render_pass.push_debug_group("grid");
+                    self.grid.draw(
+                        &mut render_pass,
+                        &self.frames.current().bind_group,
+                        camera_offset,
+                    );
+                    render_pass.pop_debug_group();
+                }
+
+                if self.light_volumes.is_visible {
+                    render_pass.push_debug_group("light volumes");
+                    self.light_volumes.draw(
+                        &mut render_pass,
+                        &self.frames.current().bind_group,
+                        camera_offset,
+                    );
+                    render_pass.pop_debug_group();
+                }
+
+                if self.variables.enable_geometry_debug {
+                    render_pass.push_debug_group("geometry debug");
+                    render_pass.set_pipeline(&self.pipelines.geometry_debug);
+                    for (i, object) in self.scene.objects().iter().enumerate() {
+                        render_pass.draw_model(
+                            &object.model,
+                            &self.materials,
+                            &self.scene.bind_group,
+                            &[self.scene.transform_offset(i)],
+                        );
+                    }
+                    render_pass.pop_debug_group();
+                }
+
+                if let Some(selected) = self.variables.selected {
+                    render_pass.push_debug_group("selection outline");
+                    self.outline.draw(
+                        &mut render_pass,
+                        &self.frames.current().bind_group,
+                        camera_offset,
+                        &self.scene,
+                        selected,
+                    );
+                    render_pass.pop_debug_group();
+                }
+
+                render_pass.push_debug_group("debug lines");
+                self.debug_draw.draw(
+                    &mut render_pass,
+                    &self.frames.current().bind_group,
+                    camera_offset,
+                );
+                render_pass.pop_debug_group();
+
+                if viewport.is_some() {
+                    render_pass.pop_debug_group();
+                }
+            }
+        }
+
+        // the passes after this read the depth single sampled
+        if let Some(msaa) = &self.msaa {
+            msaa.resolve_depth(command_encoder, &self.depth_texture.view);
+        }
+
+        // or all of them at once in passes of their own, in any order, after everything else
+        if is_oit && !transparent.is_empty() {
+            command_encoder.push_debug_group("weighted blended transparency");
+            self.oit
+                .fit(&self.device, self.depth_texture.texture.size());
+            {
+                let mut render_pass = self
+                    .oit
+                    .begin_accumulation(command_encoder, &self.depth_texture.view);
+                if let Some([x, y, width, height]) = self.scissor_rect() {
+                    render_pass.set_scissor_rect(x, y, width, height);
+                }
+                let mut render_pass = render_stats::CountingRenderPass::new(
+                    render_pass,
+                    &mut self.diagnostics.render_stats,
+                );
+
+                render_pass.set_pipeline(
+                    self.oit
+                        .accumulate_pipeline(self.variables.enable_raytracing),
+                );
+                render_pass.set_bind_group(0, &self.frames.current().bind_group, &[0]);
+                render_pass.set_bind_group(3, &self.voxel_gi.bind_group, &[]);
+                for (i, mesh) in transparent {
+                    let mesh = &self.scene.objects()[i].model.meshes[mesh];
+                    render_pass.draw_mesh(
+                        mesh,
+                        &self.materials[mesh.material],
+                        &self.scene.bind_group,
+                        &[self.scene.transform_offset(i)],
+                    );
+                }
+            }
+            self.oit.resolve(command_encoder, color_view);
+            command_encoder.pop_debug_group();
+        }
+
+        // over everything, transparent or not, once it's all drawn
+        if let Some(fog) = fog {
+            fog.resolve(command_encoder, color_view);
+        }
+
+        command_encoder.pop_debug_group();
+    }
+
+    // the quarters of the target the quad view draws in, while it's on. stereo draws its eyes
+    // with the whole of the camera's view
+    fn quad_viewports(&self) -> Option<[quad_view::Viewport; 4]> {
+        if !self.variables.enable_quad_view || self.stereo.is_some() {
+            return None;
+        }
+        Some(quad_view::viewports(
+            self.surface_config.width,
+            self.surface_config.height,
+        ))
+    }
+
+    // the overlap of two x, y, width, height rects, either of which can be unlimited. an empty
+    // overlap is kept as a rect with no area, so nothing is drawn rather than everything
+    fn intersect_rects(a: Option<[u32; 4]>, b: Option<[u32; 4]>) -> Option<[u32; 4]> {
+        match (a, b) {
+            (Some([ax, ay, aw, ah]), Some([bx, by, bw, bh])) => {
+                let (x, y) = (ax.max(bx), ay.max(by));
+                let right = (ax + aw).min(bx + bw).max(x);
+                let bottom = (ay + ah).min(by + bh).max(y);
+                Some([x, y, right - x, bottom - y])
+            }
+            (a, b) => a.or(b),
+        }
+    }
+
+    // the region a partial redraw is limited to, clamped to the surface. the surface texture's
+    // previous contents are undefined, so a partial redraw is only possible when drawing into
+    // the frame texture
+    fn scissor_rect(&self) -> Option<[u32; 4]> {
+        let [x, y, width, height] = self.pass_ops.scissor?;
+        self.frame_texture.as_ref()?;
+        let x = x.min(self.surface_config.width);
+        let y = y.min(self.surface_config.height);
+        Some([
+            x,
+            y,
+            width.min(self.surface_config.width - x),
+            height.min(self.surface_config.height - y),
+        ])
+    }
+
+    // the object and mesh indices of the meshes `is_drawn` picks, sorted by material and then by
+    // mesh, so each material's bind group is only set once and copies of a mesh sharing its
+    // buffers are drawn one after another
+    fn meshes_by_material(&self, is_drawn: impl Fn(&model::Mesh) -> bool) -> Vec<(usize, usize)> {
+        let mut meshes = Vec::new();
+        for (i, object) in self.scene.objects().iter().enumerate() {
+            for (m, mesh) in object.model.meshes.iter().enumerate() {
+                if mesh.is_visible && is_drawn(mesh) {
+                    meshes.push((i, m));
+                }
+            }
+        }
+
+        let mesh = |&(i, m): &(usize, usize)| &self.scene.objects()[i].model.meshes[m];
+        meshes.sort_by(|a, b| {
+            let (a, b) = (mesh(a), mesh(b));
+            a.material
+                .cmp(&b.material)
+                .then_with(|| a.vertex_buffer.cmp(&b.vertex_buffer))
+        });
+        meshes
+    }
+
+    // the object and mesh indices of the meshes with transparent materials, sorted by the
+    // distance from the camera to their centers. sorting whole meshes can't untangle meshes
+    // which overlap each other, but is right for separate panes, leaves and the like
+    fn transparent_meshes_back_to_front(&self) -> Vec<(usize, usize)> {
+        let mut meshes = Vec::new();
+        for (i, object) in self.scene.objects().iter().enumerate() {
+            for (m, mesh) in object.model.meshes.iter().enumerate() {
+                if !mesh.is_visible || !self.materials[mesh.material].is_transparent() {
+                    continue;
+                }
+                let center = object.model.transform.transform_point(mesh.bounds.center());
+                let distance = (center - self.camera.position.to_vec()).magnitude2();
+                meshes.push((distance, i, m));
+            }
+        }
+        meshes.sort_by(|a, b| b.0.total_cmp(&a.0));
+        meshes.into_iter().map(|(_, i, m)| (i, m)).collect()
+    }
+
+    // the tangent, bitangent and normal at every vertex, in blue, green and red. only for the
+    // first mesh of the first model, since there can be a lot of them
+    fn draw_tangent_frames(&mut self) {
+        let Some(object) = self.scene.objects().first() else {
+            return;
+        };
+        let Some(mesh) = object.model.meshes.first() else {
+            return;
+        };
+
+        // long enough to see, in proportion to the mesh
+        let length = (mesh.bounds.max - mesh.bounds.min).magnitude() * 0.02;
+
+        let transform = &object.model.transform;
+        for v in &mesh.verts {
+            let normal = cgmath::Vector3::from(v.normal);
+            let tangent = cgmath::Vector3::new(v.tangent[0], v.tangent[1], v.tangent[2]);
+            let bitangent = normal.cross(tangent) * v.tangent[3];
+            let position = transform.transform_point(v.position.into());
+            for (vector, color) in [
+                (tangent, [0.0, 0.0, 1.0]),
+                (bitangent, [0.0, 1.0, 0.0]),
+                (normal, [1.0, 0.0, 0.0]),
+            ] {
+                let direction = transform.transform_vector(vector).normalize_to(length);
+                self.debug_draw.line(position, position + direction, color);
+            }
+        }
+    }
+
+    // every mesh's bounding box and sphere in world space, green where the camera's frustum
+    // accepts the box and red where it would be culled, with the spheres a darker shade
+    fn draw_bounds(&mut self) {
+        let frustum = math::Frustum::from_matrix(
+            self.projection.perspective_matrix() * self.camera.view_matrix(),
+        );
+        for object in self.scene.objects() {
+            let transform = &object.model.transform;
+            let scale = transform.scale();
+            let largest_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+            for mesh in object.model.meshes.iter().filter(|mesh| mesh.is_visible) {
+                let corners = mesh.bounds.corners().map(|c| transform.transform_point(c));
+                let is_visible = frustum.intersects_aabb(&mesh.bounds.transformed(transform));
+                let color = if is_visible {
+                    [0.2, 1.0, 0.2]
+                } else {
+                    [1.0, 0.2, 0.2]
+                };
+                self.debug_draw.cuboid(corners.map(Into::into), color);
+                self.debug_draw.sphere(
+                    transform.transform_point(mesh.bounds.center()),
+                    mesh.bounding_radius * largest_scale,
+                    color.map(|c| c * 0.5),
+                );
+            }
+        }
+    }
+
+    // a bar for each frame time in the stats' window in the top left, newest on the right,
+    // with lines for 60 and 30 fps in grey and the 95th and 99th percentiles in yellow and
+    // orange. frames slower than the 99th percentile are spikes and drawn in red
+    fn draw_frame_time_graph(&mut self) {
+        const ORIGIN: [f32; 2] = [10.0, 10.0];
+        const SIZE: [f32; 2] = [400.0, 120.0];
+
+        let frame_stats = &self.diagnostics.frame_stats;
+        let [p95, p99] = frame_stats.percentiles([95.0, 99.0]);
+        // always shows up to 30 fps, and further when frames are slower
+        let max_ms = (p99 * 1.2).max(1000.0 / 30.0);
+        let [left, top] = ORIGIN;
+        let [right, bottom] = [ORIGIN[0] + SIZE[0], ORIGIN[1] + SIZE[1]];
+        let height_of = |ms: f32| bottom - (ms / max_ms).min(1.0) * SIZE[1];
+
+        // the window fills up from the right
+        let samples = frame_stats.samples().collect::<Vec<_>>();
+        let bar_width = SIZE[0] / frame_stats.window_size() as f32;
+        for (i, &ms) in samples.iter().enumerate() {
+            let x = right - (samples.len() - i) as f32 * bar_width + bar_width * 0.5;
+            let color = if ms > p99 {
+                [1.0, 0.2, 0.2]
+            } else {
+                [0.2, 0.8, 0.2]
+            };
+            self.debug_draw
+                .overlay_line([x, bottom], [x, height_of(ms)], color);
+        }
+
+        let mut horizontal = |ms: f32, color| {
+            let y = height_of(ms);
+            self.debug_draw.overlay_line([left, y], [right, y], color);
+        };
+        horizontal(1000.0 / 60.0, [0.4, 0.4, 0.4]);
+        horizontal(1000.0 / 30.0, [0.4, 0.4, 0.4]);
+        horizontal(p95, [1.0, 0.9, 0.2]);
+        horizontal(p99, [1.0, 0.5, 0.1]);
+
+        let corners = [[left, top], [right, top], [right, bottom], [left, bottom]];
+        for i in 0..4 {
+            self.debug_draw
+                .overlay_line(corners[i], corners[(i + 1) % 4], [0.8, 0.8, 0.8]);
+        }
+
+        // the graph moves every frame
+        self.variables.needs_redraw = true;
+    }
+
+    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
+        // keys toggle modes, edit parameters and move things, so any of them can change the frame
+        self.variables.needs_redraw = true;
+
+        // the number keys are only what they are when they aren't bound to anything
+        if self
+            .bindings
+            .actions(input::Input::Key(code))
+            .next()
+            .is_none()
+            && let Some(index) = NUMBER_KEYS.iter().position(|&key| key == code)
+        {
+            self.handle_number_key(index, is_pressed);
+            return;
+        }
+        self.handle_input(event_loop, input::Input::Key(code), is_pressed);
+    }
+
+    // switches scenes, or with ctrl stores a camera bookmark and with alt recalls one
+    fn handle_number_key(&mut self, index: usize, is_pressed: bool) {
+        if !is_pressed {
+            return;
+        }
+        if self.variables.modifiers.control_key() {
+            self.bookmarks.set(
+                index,
+                bookmarks::CameraPose::new(&self.camera, &self.projection),
+            );
+            self.console
+                .set_message(format!("stored camera bookmark {}", index + 1));
+        } else if self.variables.modifiers.alt_key() {
+            match self.bookmarks.get(index) {
+                Some(pose) => {
+                    self.camera_transition = Some(bookmarks::CameraTransition::new(
+                        bookmarks::CameraPose::new(&self.camera, &self.projection),
+                        pose,
+                    ))
+                }
+                None => log::warn!("there is no camera bookmark {}", index + 1),
+            }
+        } else if !self.switch_scene(index) {
+            log::warn!("there is no scene {}", index + 1);
+        }
+    }
+
+    // runs the actions bound to a key or mouse button, see input.rs
+    fn handle_input(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        input: input::Input,
+        is_pressed: bool,
+    ) {
+        let actions: Vec<input::Action> = self.bindings.actions(input).collect();
+        for action in actions {
+            self.handle_action(event_loop, action, is_pressed);
+        }
+    }
+
+    fn handle_action(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        action: input::Action,
+        is_pressed: bool,
+    ) {
+        use input::Action;
+
+        match (action, is_pressed) {
+            (Action::Quit, true) => event_loop.exit(),
+            (Action::ToggleGeometryDebug, true) => {
+                self.variables.enable_geometry_debug = !self.variables.enable_geometry_debug
+            }
+            (Action::CycleDebugView, true) => {
+                self.variables.debug_view = self.variables.debug_view.next();
+                self.uniforms
+                    .timestamp
+                    .set_debug_view(self.variables.debug_view);
+            }
+            (Action::CycleVoxelGi, true) => {
+                let mode = self.voxel_gi.mode().next();
+                self.voxel_gi.set_mode(mode, &self.queue);
+            }
+            (Action::ToggleRaytracing, true) => {
+                if self.pipelines.render_raytraced.is_some() {
+                    self.variables.enable_raytracing = !self.variables.enable_raytracing;
+                } else {
+                    log::warn!("ray tracing is not supported by this adapter");
+                }
+            }
+            (Action::ToggleRedrawOnChange, true) => {
+                if self.frame_texture.is_some() {
+                    self.variables.redraw_on_change = !self.variables.redraw_on_change;
+                } else {
+                    log::warn!("the surface can't be copied to, so every frame is redrawn");
+                }
+            }
+            (Action::ToggleAnimations, true) => {
+                self.animations.is_playing = !self.animations.is_playing;
+            }
+            (Action::ToggleOit, true) => {
+                self.variables.enable_oit = !self.variables.enable_oit;
+            }
+            (Action::ToggleGpuCulling, true) => {
+                if self.gpu_culling.is_some() {
+                    self.variables.enable_gpu_culling = !self.variables.enable_gpu_culling;
+                } else {
+                    log::warn!("gpu culling is not supported by this adapter");
+                }
+            }
+            (Action::ToggleLightVolumes, true) => {
+                self.light_volumes.is_visible = !self.light_volumes.is_visible;
+            }
+            (Action::ToggleBoundsDebug, true) => {
+                self.variables.enable_bounds_debug = !self.variables.enable_bounds_debug;
+            }
+            (Action::ToggleFrameGraph, true) => {
+                self.variables.show_frame_graph = !self.variables.show_frame_graph;
+            }
+            (Action::ToggleGrid, true) => {
+                self.grid.is_visible = !self.grid.is_visible;
+            }
+            (Action::ToggleShadowCascades, true) => {
+                self.shadows.is_debug_visible = !self.shadows.is_debug_visible;
+            }
+            (Action::ToggleIbl, true) => {
+                let is_enabled = !self.ibl.is_enabled();
+                self.ibl.set_enabled(is_enabled, &self.queue);
+            }
+            (Action::ToggleBloom, true) => {
+                self.bloom = match self.bloom {
+                    Some(_) => None,
+                    None => Some(bloom::Bloom::new(&self.device, &self.surface_config)),
+                };
+            }
+            (Action::ToggleFxaa, true) => {
+                self.fxaa = match self.fxaa {
+                    Some(_) => None,
+                    None => Some(antialiasing::Fxaa::new(&self.device, &self.surface_config)),
+                };
+            }
+            (Action::ToggleFog, true) => {
+                if let Some(fog) = &mut self.fog {
+                    fog.is_enabled = !fog.is_enabled;
+                }
+            }
+            (Action::CycleShadowQuality, true) => {
+                let quality = self.shadows.quality().next();
+                self.shadows.set_quality(quality);
+            }
+            (Action::PreviousParameter | Action::NextParameter, true) => self
+                .variables
+                .parameter_editor
+                .select_next(&self.shader_parameters, action == Action::NextParameter),
+            (Action::DecreaseParameter | Action::IncreaseParameter, true) => {
+                if let Some(material) = self.edited_material() {
+                    self.variables.parameter_editor.step(
+                        &self.shader_parameters,
+                        &mut self.materials[material].parameters,
+                        action == Action::IncreaseParameter,
+                    );
+                    self.rebuild_material_atlas();
+                }
+            }
+            (Action::CycleStereo, true) => {
+                let mode = stereo::StereoMode::next(self.stereo.as_ref().map(|s| s.mode()));
+                match (mode, &mut self.stereo) {
+                    (None, _) => self.stereo = None,
+                    (Some(mode), Some(stereo)) => stereo.set_mode(mode, &self.queue),
+                    (Some(mode), None) => {
+                        self.stereo = Some(stereo::StereoOutput::new(
+                            &self.device,
+                            &self.surface_config,
+                            mode,
+                        ))
+                    }
+                }
+            }
+            (Action::RecordCameraPath, true) => {
+                let message = self
+                    .camera_path
+                    .toggle_recording(bookmarks::CameraPose::new(&self.camera, &self.projection));
+                log::info!("{}", message);
+                self.console.set_message(message);
+            }
+            (Action::PlayCameraPath, true) => {
+                self.camera_transition = None;
+                let message = self.camera_path.toggle_playback();
+                log::info!("{}", message);
+                self.console.set_message(message);
+            }
+            // free orientation turns about the camera's own axes and can go over the top
+            (Action::ToggleFreeCamera, true) => {
+                let free = !self.camera.is_free();
+                self.camera.set_free(free);
+                self.variables.needs_redraw = true;
+            }
+            (Action::LevelHorizon, true) => {
+                self.camera.level_horizon();
+                self.variables.needs_redraw = true;
+            }
+            (Action::ToggleWalk, true) => {
+                self.walk = match self.walk {
+                    Some(_) => None,
+                    None => Some(walk::Walk::new(
+                        &self.camera,
+                        walk::Walk::DEFAULT_EYE_HEIGHT,
+                        walk::Walk::DEFAULT_GRAVITY,
+                    )),
+                };
+            }
+            // jumps while walking, rather than flying up
+            (Action::MoveUp, true) if self.walk.is_some() => {
+                if let Some(walk) = &mut self.walk {
+                    walk.jump();
+                }
+            }
+            (Action::CyclePresentMode, true) => {
+                let message = match self.cycle_present_mode() {
+                    Ok(mode) => format!("presenting with {:?}", mode),
+                    Err(e) => format!("{:#}", e),
+                };
+                log::info!("{}", message);
+                self.console.set_message(message);
+            }
+            (Action::TogglePause, true) => {
+                self.scene_clock.toggle_pause();
+            }
+            (Action::StepFrame, true) => {
+                self.scene_clock.step();
+                self.variables.needs_redraw = true;
+            }
+            (Action::CycleTimeScale, true) => {
+                self.scene_clock.cycle_scale();
+            }
+            (Action::ToggleQuadView, true) => {
+                self.variables.enable_quad_view = !self.variables.enable_quad_view;
+            }
+            (Action::ToggleFitZPlanes, true) => {
+                self.variables.fit_z_planes = !self.variables.fit_z_planes;
+            }
+            (Action::ToggleRecording, true) => {
+                match self.toggle_recording(recording::RecordingFormat::PngSequence) {
+                    Ok(message) => {
+                        log::info!("{}", message);
+                        self.console.set_message(message);
+                    }
+                    Err(e) => log::error!("could not record: {:#}", e),
+                }
+            }
+            (Action::CaptureFrame, true) => {
+                if let Err(e) = self.capture_frame() {
+                    log::error!("could not capture the frame: {:#}", e);
+                }
+            }
+            (Action::ToggleMaterialAtlas, true) => {
+                self.material_atlas = match self.material_atlas {
+                    Some(_) => None,
+                    None => Some(material_atlas::MaterialAtlas::new(
+                        &self.device,
+                        &self.queue,
+                        &self.layouts.material_atlas,
+                        &self.materials,
+                    )),
+                };
+            }
+            (Action::ToggleReference, true) => match &mut self.reference {
+                Some(reference) => reference.is_visible = !reference.is_visible,
+                None => log::warn!("no reference image is loaded, see the reference command"),
+            },
+            (Action::ToggleSpin, true) => {
+                // spins every model around its y axis, or stops them where they are
+                let is_spinning = self.scene.objects().iter().any(|o| o.animator.is_some());
+                for id in self.model_ids() {
+                    let animator = (!is_spinning).then(|| animation::Animator::spin(10.0));
+                    self.scene.set_animator(id, animator);
+                }
+                if !is_spinning {
+                    self.animations.is_playing = true;
+                }
+            }
+            (Action::FrameModel, true) => {
+                let is_framed = match self.variables.selected {
+                    Some(id) => self.frame_model(id),
+                    None => self.frame_scene(),
+                };
+                if !is_framed {
+                    log::warn!("there's nothing in the scene to frame");
+                }
+            }
+            (Action::Look, _) => {
+                self.variables.is_mouse_pressed = is_pressed;
+                if is_pressed {
+                    self.variables.click_start = self.variables.cursor_position;
+                } else if let Some(start) = self.variables.click_start.take() {
+                    self.select_at_cursor(start);
+                }
+            }
+            (Action::PlaceLight, _) => {
+                self.variables.is_placing_light = is_pressed;
+                if is_pressed {
+                    self.place_light_at_cursor();
+                }
+            }
+            _ => {
+                self.camera_controller.handle_action(action, is_pressed);
+            }
+        }
+    }
+
+    fn handle_mouse_button(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        button: MouseButton,
+        pressed: bool,
+    ) {
+        self.handle_input(event_loop, input::Input::Mouse(button), pressed);
+    }
+
+    fn handle_cursor_moved(&mut self, x: f64, y: f64) {
+        self.variables.cursor_position = Some((x, y));
+        if self.variables.is_placing_light {
+            self.place_light_at_cursor();
+        }
+    }
+
+    // the model under a point in the window, given in pixels from the top left like the
+    // cursor's position, or None when the ray through it misses everything
+    pub fn pick(&self, cursor_pos: (f32, f32)) -> Option<PickResult> {
+        let (cursor_pos, screen_size) = match self.quad_viewports() {
+            // only the camera's quarter of the quad view can be picked in
+            Some([viewport, ..]) => {
+                if !viewport.contains(cursor_pos) {
+                    return None;
+                }
+                let [x, y, width, height] = viewport.rect;
+                (
+                    (cursor_pos.0 - x as f32, cursor_pos.1 - y as f32),
+                    (width, height),
+                )
+            }
+            None => (
+                cursor_pos,
+                (self.surface_config.width, self.surface_config.height),
+            ),
+        };
+        let ray = self
+            .camera
+            .picking_ray(&self.projection, cursor_pos, screen_size);
+        self.scene.raycast(&ray)
+    }
+
+    // the model outlined as selected, if any. it's chosen by clicking on it
+    pub fn selected(&self) -> Option<ModelId> {
+        self.variables.selected
+    }
+
+    // selects the model, or clears the selection with None
+    pub fn select(&mut self, id: Option<ModelId>) {
+        if self.variables.selected != id {
+            self.variables.selected = id;
+            self.variables.needs_redraw = true;
+        }
+    }
+
+    // selects what's under the cursor when the left button is released where it went down,
+    // which clicking on nothing clears. further away, the press was turning the camera
+    fn select_at_cursor(&mut self, click_start: (f64, f64)) {
+        let Some((x, y)) = self.variables.cursor_position else {
+            return;
+        };
+        if (x - click_start.0).hypot(y - click_start.1) > CLICK_DISTANCE {
+            return;
+        }
+        let hit = self.pick((x as f32, y as f32));
+        self.select(hit.map(|hit| hit.model));
+    }
+
+    // moves the first point light to where the cursor's picking ray hits the model, leaving it
+    // where it was if the ray misses
+    fn place_light_at_cursor(&mut self) {
+        let Some((x, y)) = self.variables.cursor_position else {
+            return;
+        };
+        if self.point_lights.is_empty() {
+            return;
+        }
+        let Some(hit) = self.pick((x as f32, y as f32)) else {
+            return;
+        };
+
+        let position = hit.position + hit.normal * LIGHT_PLACEMENT_OFFSET;
+        self.point_lights[0]
+            .transform
+            .set_translation(position.to_vec());
+        self.upload_lights();
+    }
+
+    // updates the light uniforms after the lights have been edited in place. they reach the
+    // gpu with the next frame's writes in update
+    fn upload_lights(&mut self) {
+        // the number of lights hasn't changed, so the light counts in the metadata are still right
+        (self.uniforms.lights, _) = uniforms::create_light_uniforms(
+            &self.point_lights,
+            &self.directional_lights,
+            &self.spot_lights,
+        );
+        self.variables.needs_redraw = true;
+    }
+
+    // MARK: COMMANDS
+
+    // runs a line from the console, returning what to show in its place
+    fn run_command(&mut self, line: &str) -> String {
+        let result = match console::Command::parse(line) {
+            Ok(console::Command::Load(path)) if path.ends_with(".json") => self
+                .load_scene_file(&path)
+                .map(|count| format!("loaded {} with {} models", path, count)),
+            Ok(console::Command::Load(path)) => self
+                .load_model(&path)
+                .map(|id| format!("loaded {} as model {}", path, id))
+                .map_err(anyhow::Error::from),
+            Ok(console::Command::Save(path)) if path.ends_with(".obj") => self
+                .export_obj(&path)
+                .map(|count| format!("exported {} meshes to {}", count, path)),
+            Ok(console::Command::Save(path)) => self
+                .save_scene(&path)
+                .map(|count| format!("saved {} with {} models", path, count)),
+            Ok(console::Command::Frame(Some(id))) => match self.frame_model(id) {
+                true => Ok(format!("framing model {}", id)),
+                false => Err(anyhow::anyhow!("there is no model {}", id)),
+            },
+            Ok(console::Command::Frame(None)) => match self.frame_scene() {
+                true => Ok("framing the scene".to_string()),
+                false => Err(anyhow::anyhow!("there's nothing in the scene to frame")),
+            },
+            Ok(console::Command::Group(id, name, edit)) => {
+                let verb = match edit {
+                    console::GroupEdit::Show => "shown",
+                    console::GroupEdit::Hide => "hidden",
+                    console::GroupEdit::Move(_) => "moved",
+                };
+                self.edit_group(id, &name, edit)
+                    .map(|count| format!("{} {} of model {} ({} meshes)", verb, name, id, count))
+            }
+            Ok(console::Command::Unload(id)) => match self.unload_model(id) {
+                Ok(true) => Ok(format!("unloaded model {}", id)),
+                Ok(false) => Err(anyhow::anyhow!("there is no model {}", id)),
+                Err(e) => Err(e.into()),
+            },
+            Ok(console::Command::Set(name, value)) => self
+                .set(&name, value)
+                .map(|_| format!("{} = {}", name, value)),
+            Ok(console::Command::Screenshot(path)) => {
+                let path = path.unwrap_or_else(|| screenshot::default_path("screenshot"));
+                self.screenshot(&path).map(|_| format!("saved {}", path))
+            }
+            Ok(console::Command::Panorama(path, face_size)) => {
+                let path = path.unwrap_or_else(|| screenshot::default_path("panorama"));
+                self.panorama(&path, face_size.unwrap_or(PANORAMA_FACE_SIZE))
+                    .map(|_| format!("saved {}", path))
+            }
+            Ok(console::Command::Bench(frames)) => {
+                self.start_benchmark(frames);
+                Ok(format!("benchmarking {} frames", frames))
+            }
+            Ok(console::Command::NewScene) => {
+                let index = self.new_scene();
+                Ok(format!("opened scene {}", index + 1))
+            }
+            Ok(console::Command::SwitchScene(number)) => {
+                if number > 0 && self.switch_scene(number - 1) {
+                    Ok(format!("switched to scene {}", number))
+                } else {
+                    Err(anyhow::anyhow!("there is no scene {}", number))
+                }
+            }
+            Ok(console::Command::Reference(Some(path))) => self
+                .load_reference(&path)
+                .map(|_| format!("overlaying {}", path))
+                .map_err(anyhow::Error::from),
+            Ok(console::Command::Environment(path)) => self
+                .ibl
+                .load(&self.device, &self.queue, &path)
+                .map(|_| format!("lighting with {}", path))
+                .map_err(anyhow::Error::from),
+            Ok(console::Command::Reference(None)) => {
+                self.reference = None;
+                Ok("reference image off".to_string())
+            }
+            Ok(console::Command::UvGrid(material)) => self.toggle_uv_grid(&material).map(|shown| {
+                format!(
+                    "uv grid {} for {}",
+                    if shown { "on" } else { "off" },
+                    material
+                )
+            }),
+            Ok(console::Command::Record(format)) => self.toggle_recording(format),
+            Ok(console::Command::Script(path)) => self.run_script(path.as_deref()),
+            Ok(console::Command::Present(mode)) => self
+                .set_present_mode(mode)
+                .map(|_| format!("presenting with {:?}", mode)),
+            Ok(console::Command::Help) => Ok(console::HELP.to_string()),
+            Err(e) => Err(anyhow::anyhow!("{}", e)),
+        };
+
+        match result {
+            Ok(message) => {
+                log::info!("{}", message);
+                message
+            }
+            Err(e) => {
+                log::error!("{}: {:#}", line, e);
+                format!("error: {:#}", e)
+            }
+        }
+    }
+
+    fn run_remote_command(
+        &mut self,
+        command: remote::RemoteCommand,
+    ) -> Result<json::JsonValue, String> {
+        match command {
+            remote::RemoteCommand::Load { path } => self
+                .load_model(&path)
+                .map(|id| json::JsonValue::Number(u32::from(id) as f64))
+                .map_err(|e| e.to_string()),
+            remote::RemoteCommand::Unload { id } => match self.unload_model(id) {
+                Ok(true) => Ok(json::JsonValue::Null),
+                Ok(false) => Err(format!("there is no model {}", id)),
+                Err(e) => Err(e.to_string()),
+            },
+            remote::RemoteCommand::SetTransform {
+                id,
+                translation,
+                rotation,
+                scale,
+            } => {
+                let Some(model) = self.scene.get_mut(id) else {
+                    return Err(format!("there is no model {}", id));
+                };
+                if let Some(translation) = translation {
+                    model.transform.set_translation(translation);
+                }
+                if let Some([x, y, z, w]) = rotation {
+                    model
+                        .transform
+                        .set_rotation(cgmath::Quaternion::new(w, x, y, z).normalize());
+                }
+                if let Some(scale) = scale {
+                    model.transform.set_scale(scale);
+                }
+                // the voxels are in world space. the ray tracing instances follow the
+                // transforms by themselves
+                self.variables.needs_revoxelize = true;
+                self.variables.needs_redraw = true;
+                Ok(json::JsonValue::Null)
+            }
+            remote::RemoteCommand::SetLight {
+                index,
+                position,
+                color,
+                intensity,
+            } => {
+                let Some(light) = self.point_lights.get_mut(index) else {
+                    return Err(format!("there is no point light {}", index));
+                };
+                if let Some(position) = position {
+                    light.transform.set_translation(position);
+                }
+                if let Some(color) = color {
+                    light.color = color;
+                }
+                if let Some(intensity) = intensity {
+                    light.intensity = intensity;
+                }
+                self.upload_lights();
+                Ok(json::JsonValue::Null)
+            }
+            remote::RemoteCommand::Set { name, value } => self
+                .set(&name, value)
+                .map(|_| json::JsonValue::Null)
+                .map_err(|e| format!("{:#}", e)),
+            remote::RemoteCommand::Screenshot { path } => {
+                let path = path.unwrap_or_else(|| screenshot::default_path("screenshot"));
+                self.screenshot(&path)
+                    .map(|_| json::JsonValue::String(path))
+                    .map_err(|e| format!("{:#}", e))
+            }
+        }
+    }
+
+    // runs a script's top level, keeping it to call every frame if it has an update function.
+    // None stops the running one
+    #[cfg(feature = "scripting")]
+    fn run_script(&mut self, path: Option<&str>) -> anyhow::Result<String> {
+        let Some(path) = path else {
+            return Ok(match self.script.take() {
+                Some(script) => format!("stopped {}", script.path),
+                None => "no script is running".to_string(),
+            });
+        };
+
+        self.script = Some(scripting::Script::load(path)?);
+        self.run_script_commands();
+        Ok(match &self.script {
+            Some(script) if script.is_animated() => format!("running {}", path),
+            _ => format!("ran {}", path),
+        })
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn run_script(&mut self, _path: Option<&str>) -> anyhow::Result<String> {
+        anyhow::bail!("scripts need the scripting feature, eg cargo run --features scripting")
+    }
+
+    #[cfg(feature = "scripting")]
+    fn update_script(&mut self) {
+        let Some(script) = self.script.as_mut().filter(|s| s.is_animated()) else {
+            return;
+        };
+        if let Err(e) = script.update() {
+            log::error!("{:#}", e);
+            self.script = None;
+            return;
+        }
+        self.run_script_commands();
+    }
+
+    // carries out what the script queued. the script is taken out of State meanwhile, and
+    // dropped afterwards unless it still has an update to run
+    #[cfg(feature = "scripting")]
+    fn run_script_commands(&mut self) {
+        let Some(mut script) = self.script.take() else {
+            return;
+        };
+
+        for command in script.take_commands() {
+            let result = match command {
+                scripting::ScriptCommand::Load(path) => {
+                    let id = self.load_model(&path);
+                    script.push_model(id.as_ref().ok().copied());
+                    id.map(|_| ()).map_err(anyhow::Error::from)
+                }
+                scripting::ScriptCommand::Unload(model) => script
+                    .model(model)
+                    .and_then(|id| self.unload_model(id).map_err(anyhow::Error::from))
+                    .map(|_| ()),
+                scripting::ScriptCommand::Transform {
+                    model,
+                    translation,
+                    rotation,
+                    scale,
+                } => script.model(model).and_then(|id| {
+                    self.run_remote_command(remote::RemoteCommand::SetTransform {
+                        id,
+                        translation,
+                        rotation,
+                        scale,
+                    })
+                    .map(|_| ())
+                    .map_err(|e| anyhow::anyhow!(e))
+                }),
+                scripting::ScriptCommand::Camera {
+                    position,
+                    yaw,
+                    pitch,
+                } => {
+                    self.camera = camera::Camera::new(
+                        position,
+                        cgmath::Deg(yaw),
+                        cgmath::Deg(pitch.clamp(-89.0, 89.0)),
+                    );
+                    self.variables.needs_redraw = true;
+                    Ok(())
+                }
+                scripting::ScriptCommand::Remote(command) => self
+                    .run_remote_command(command)
+                    .map(|_| ())
+                    .map_err(|e| anyhow::anyhow!(e)),
+                scripting::ScriptCommand::Stop => {
+                    script.stop();
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                log::error!("{}: {:#}", script.path, e);
+            }
+        }
+
+        if script.is_animated() {
+            self.script = Some(script);
+        }
+    }
+
+    // the material which the parameter editor and `set` work on: the first mesh's material of
+    // the first model
+    fn edited_material(&self) -> Option<usize> {
+        let object = self.scene.objects().first()?;
+        object.model.meshes.first().map(|mesh| mesh.material)
+    }
+
+    // a model as it came from the loader, changed the way the startup options ask for. broken
+    // normals are replaced either way
+    fn prepare_loaded_model(
+        device: &wgpu::Device,
+        options: &options::StartupOptions,
+        model: model::Model,
+    ) -> model::Model {
+        let model = match options.recompute_normals {
+            Some(angle) => mesh_tools::recompute_model_normals(device, model, angle),
+            None if model
+                .meshes
+                .iter()
+                .any(|mesh| mesh_tools::has_broken_normals(&mesh.verts)) =>
+            {
+                log::warn!("the model has broken normals, recomputing them");
+                mesh_tools::recompute_model_normals(device, model, mesh_tools::CREASE_ANGLE)
+            }
+            None => model,
+        };
+        match options.simplify {
+            Some(ratio) => mesh_tools::simplify_model(device, model, ratio),
+            None => model,
+        }
+    }
+
+    // adds an obj or ply file, primitive or terrain to the scene, registering its materials
+    // and rebuilding everything derived from the scene geometry
+    pub fn load_model(&mut self, path: &str) -> Result<ModelId, error::AssetError> {
+        let first_new_material = self.materials.len();
+        let model = resources::load_model(
+            path,
+            &mut self.materials,
+            &mut self.material_map,
+            &mut self.texture_cache,
+            &self.device,
+            &self.queue,
+            &self.layouts.per_pass,
+        )?;
+        let model = Self::prepare_loaded_model(&self.device, &self.options, model);
+        for material in &mut self.materials[first_new_material..] {
+            material.parameters.reset(&self.shader_parameters);
+        }
+        self.rebuild_material_atlas();
+
+        let id = self
+            .scene
+            .add(&self.device, &self.layouts.per_object, model, path);
+        self.rebuild_scene_geometry()?;
+        Ok(id)
+    }
+
+    // reads a model's file again, keeping its id and transform. the materials it uses are
+    // read again too, replacing the old ones in place so other models sharing them by name
+    // pick up the changes
+    pub fn reload_model(&mut self, id: ModelId) -> Result<bool, error::AssetError> {
+        let Some(object) = self.scene.objects().iter().find(|o| o.id == id) else {
+            return Ok(false);
+        };
+        let path = object.source.clone();
+
+        // forgetting the names makes the loader read the materials from the mtl again
+        let mut replaced = HashMap::new();
+        for mesh in &object.model.meshes {
+            let name = &self.materials[mesh.material].name;
+            if let Some(index) = self.material_map.remove(name) {
+                replaced.insert(name.clone(), index);
+            }
+        }
+
+        let first_new_material = self.materials.len();
+        let loaded = resources::load_model(
+            &path,
+            &mut self.materials,
+            &mut self.material_map,
+            &mut self.texture_cache,
+            &self.device,
+            &self.queue,
+            &self.layouts.per_pass,
+        );
+        let mut model = match loaded {
+            Ok(model) => Self::prepare_loaded_model(&self.device, &self.options, model),
+            Err(e) => {
+                // keep using the old materials
+                self.materials.truncate(first_new_material);
+                self.material_map.extend(replaced);
+                return Err(e);
+            }
+        };
+
+        // move the new materials into the old ones' slots, and the rest to the end as usual
+        let mut remap = HashMap::new();
+        let new_materials = self.materials.split_off(first_new_material);
+        for (i, mut material) in new_materials.into_iter().enumerate() {
+            material.parameters.reset(&self.shader_parameters);
+            let index = match replaced.remove(&material.name) {
+                Some(index) => {
+                    self.materials[index] = material;
+                    index
+                }
+                None => {
+                    self.materials.push(material);
+                    self.materials.len() - 1
+                }
+            };
+            self.material_map
+                .insert(self.materials[index].name.clone(), index);
+            remap.insert(first_new_material + i, index);
+        }
+        // materials the new export no longer uses stay registered, other models may use them
+        self.material_map.extend(replaced);
+
+        for mesh in &mut model.meshes {
+            mesh.material = remap.get(&mesh.material).copied().unwrap_or(mesh.material);
+        }
+        self.rebuild_material_atlas();
+
+        if let Some(old) = self.scene.get_mut(id) {
+            model.transform = old.transform.clone();
+            *old = model;
+        }
+        self.rebuild_scene_geometry()?;
+        Ok(true)
+    }
+
+    // reloads an exported file if it is already in the scene and adds it otherwise. the camera
+    // is left where it is, so the result can be compared with what was there before
+    fn import_export(&mut self, path: &str) {
+        let result = match self.scene.find_source(path) {
+            Some(id) => self.reload_model(id).map(|_| format!("reloaded {}", path)),
+            None => self
+                .load_model(path)
+                .map(|id| format!("loaded {} as model {}", path, id)),
+        };
+        let message = match result {
+            Ok(message) => {
+                log::info!("{}", message);
+                message
+            }
+            Err(e) => {
+                log::warn!("could not import {}: {}", path, e);
+                format!("could not import {}: {}", path, e)
+            }
+        };
+        self.console.set_message(message);
+    }
+
+    // removes a model from the scene, returning false if it wasn't there. its materials stay
+    // registered, since other models may share them by name
+    pub fn unload_model(&mut self, id: ModelId) -> Result<bool, error::AssetError> {
+        if self.scene.remove(id).is_none() {
+            return Ok(false);
+        }
+        if self.variables.selected == Some(id) {
+            self.select(None);
+        }
+        self.rebuild_scene_geometry()?;
+        Ok(true)
+    }
+
+    // shows, hides or moves the meshes of one of a model's obj groups, returning how many there
+    // were. a group is split into a mesh per material, so a name can match several meshes
+    pub fn edit_group(
+        &mut self,
+        id: ModelId,
+        name: &str,
+        edit: console::GroupEdit,
+    ) -> anyhow::Result<usize> {
+        let Some(model) = self.scene.get_mut(id) else {
+            anyhow::bail!("there is no model {}", id);
+        };
+        // meshes are named "<path> (<group>)" by resources::load_obj_model
+        let suffix = format!("({})", name);
+        let mut count = 0;
+        for mesh in model
+            .meshes
+            .iter_mut()
+            .filter(|m| m.name.ends_with(&suffix))
+        {
+            match edit {
+                console::GroupEdit::Show => mesh.is_visible = true,
+                console::GroupEdit::Hide => mesh.is_visible = false,
+                console::GroupEdit::Move(position) => {
+                    let mut transform = mesh.transform.clone();
+                    transform.set_translation(position);
+                    mesh.set_transform(&self.queue, transform);
+                }
+            }
+            count += 1;
+        }
+        if count == 0 {
+            anyhow::bail!("model {} has no group {}", id, name);
+        }
+        self.rebuild_scene_geometry()?;
+        Ok(count)
+    }
+
+    // a file dropped onto the window, which makes the viewer work as a model previewer: a model
+    // replaces everything in the scene, or is added to it while shift is held, and the camera
+    // flies to where it can see it. scene files are loaded like the console's load command
+    fn load_dropped_file(&mut self, path: &std::path::Path) -> anyhow::Result<String> {
+        let path = path
+            .to_str()
+            .with_context(|| format!("{} isn't a path that can be loaded", path.display()))?;
+        let extension = path
+            .rsplit_once('.')
+            .map_or("", |(_, e)| e)
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => {
+                let count = self.load_scene_file(path)?;
+                return Ok(format!("loaded {} with {} models", path, count));
+            }
+            "obj" | "ply" => {}
+            // there's no gltf loader in resources.rs
+            "gltf" | "glb" => anyhow::bail!("gltf isn't supported yet, only obj and ply models"),
+            _ => anyhow::bail!("{} isn't an obj or ply model or a json scene", path),
+        }
+
+        let id = self.load_model(path)?;
+        if !self.variables.modifiers.shift_key() {
+            let others: Vec<ModelId> = self.model_ids().into_iter().filter(|&o| o != id).collect();
+            for other in &others {
+                self.scene.remove(*other);
+            }
+            if self
+                .variables
+                .selected
+                .is_some_and(|selected| others.contains(&selected))
+            {
+                self.select(None);
+            }
+            self.rebuild_scene_geometry()?;
+        }
+        self.frame_model(id);
+        Ok(format!("loaded {} as model {}", path, id))
+    }
+
+    // flies the camera to where the model fills the view, with some room around it. false if
+    // there's no such model
+    pub fn frame_model(&mut self, id: ModelId) -> bool {
+        match self.scene.model_bounds(id) {
+            Some(bounds) => {
+                self.frame_bounds(&bounds);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // like frame_model, for every model at once. false if the scene is empty
+    pub fn frame_scene(&mut self) -> bool {
+        match self.scene.bounds() {
+            Some(bounds) => {
+                self.frame_bounds(&bounds);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // flies the camera back along the way it's looking until the whole box is in view, with
+    // some room around it. the box's bounding sphere is fitted to the narrower of the fovs
+    fn frame_bounds(&mut self, bounds: &math::Aabb) {
+        const MARGIN: f32 = 1.2;
+        let radius = ((bounds.max - bounds.min).magnitude() / 2.0).max(0.01);
+        let fov = self.projection.fov().min(self.projection.fov_vertical());
+        let distance = radius * MARGIN / (cgmath::Rad::from(cgmath::Deg(fov)).0 / 2.0).sin();
+
+        let from = bookmarks::CameraPose::new(&self.camera, &self.projection);
+        let to = bookmarks::CameraPose {
+            position: cgmath::Point3::from_vec(bounds.center())
+                - self.camera.direction() * distance,
+            ..from
+        };
+        self.camera_transition = Some(bookmarks::CameraTransition::new(from, to));
+        self.variables.needs_redraw = true;
+    }
+
+    // adds a scene file's models and sets up its lights (see scene_file.rs), returning how
+    // many models it added. its animations start playing
+    pub fn load_scene_file(&mut self, path: &str) -> anyhow::Result<usize> {
+        let file = scene_file::SceneFile::load(path)?;
+
+        for entry in &file.models {
+            let id = self.load_model(&entry.path)?;
+            if let Some(model) = self.scene.get_mut(id) {
+                if let Some(position) = entry.position {
+                    model.transform.set_translation(position);
+                }
+                if let Some([x, y, z, w]) = entry.rotation {
+                    model
+                        .transform
+                        .set_rotation(cgmath::Quaternion::new(w, x, y, z).normalize());
+                }
+                if let Some(scale) = entry.scale {
+                    model.transform.set_scale(scale);
+                }
+            }
+            if let Some(animator) = &entry.animator {
+                self.scene.set_animator(id, Some(animator.clone()));
+            }
+        }
+
+        if file.lights.len() > self.point_lights.len() {
+            log::warn!(
+                "{} sets up {} point lights, but there are only {}",
+                path,
+                file.lights.len(),
+                self.point_lights.len()
+            );
+        }
+        for (entry, light) in file.lights.iter().zip(&mut self.point_lights) {
+            if let Some(position) = entry.position {
+                light.transform.set_translation(position);
+            }
+            if let Some(color) = entry.color {
+                light.color = color;
+            }
+            if let Some(intensity) = entry.intensity {
+                light.intensity = intensity;
+            }
+            if let Some(animation) = &entry.animation {
+                self.animations.set_light(animation.clone());
+            }
+        }
+        self.upload_lights();
+
+        if let Some(camera) = &file.camera {
+            self.set_camera(
+                camera.position,
+                cgmath::Deg(camera.yaw),
+                cgmath::Deg(camera.pitch.clamp(-89.0, 89.0)),
+            );
+            if camera.near.is_some() || camera.far.is_some() {
+                self.projection.set_z_planes(
+                    camera.near.unwrap_or(self.projection.z_plane_near()),
+                    camera.far.unwrap_or(self.projection.z_plane_far()),
+                );
+            }
+            if let Some(fit_z_planes) = camera.fit_z_planes {
+                self.variables.fit_z_planes = fit_z_planes;
+            }
+        }
+
+        if let Some(weather) = &file.weather {
+            self.set_weather(weather.precipitation, weather.intensity)?;
+        }
+
+        self.animations.is_playing = true;
+        self.variables.needs_revoxelize = true;
+        Ok(file.models.len())
+    }
+
+    // writes the models where they are now, the point lights and the camera as a scene file
+    // which load_scene_file puts back, returning how many models it holds. models and lights
+    // which are animated are saved where their animations have them, without the animations
+    pub fn save_scene(&self, path: &str) -> anyhow::Result<usize> {
+        let models = self
+            .scene
+            .objects()
+            .iter()
+            .map(|object| {
+                let transform = &object.model.transform;
+                let rotation = transform.rotation();
+                scene_file::ModelEntry {
+                    path: object.source.clone(),
+                    position: Some(transform.translation().into()),
+                    rotation: Some([rotation.v.x, rotation.v.y, rotation.v.z, rotation.s]),
+                    scale: Some(transform.scale().into()),
+                    animator: None,
+                }
+            })
+            .collect::<Vec<_>>();
+        let lights = self
+            .point_lights
+            .iter()
+            .map(|light| scene_file::LightEntry {
+                position: Some(light.transform.translation().into()),
+                color: Some(light.color),
+                intensity: Some(light.intensity),
+                animation: None,
+            })
+            .collect();
+        let camera = scene_file::CameraEntry {
+            position: self.camera.position.into(),
+            yaw: cgmath::Deg::from(self.camera.yaw).0,
+            pitch: cgmath::Deg::from(self.camera.pitch).0,
+            // fitted planes are worked out again on loading rather than saved
+            near: (!self.variables.fit_z_planes).then(|| self.projection.z_plane_near()),
+            far: (!self.variables.fit_z_planes).then(|| self.projection.z_plane_far()),
+            fit_z_planes: Some(self.variables.fit_z_planes),
+        };
+
+        let count = models.len();
+        scene_file::SceneFile {
+            camera: Some(camera),
+            models,
+            lights,
+            weather: Some(scene_file::WeatherEntry {
+                precipitation: self.weather.precipitation(),
+                intensity: self.weather.intensity(),
+            }),
+        }
+        .save(path)?;
+        Ok(count)
+    }
+
+    // writes every model's meshes, moved to where the model is, into one obj file. returns
+    // how many meshes it holds
+    pub fn export_obj(&self, path: &str) -> anyhow::Result<usize> {
+        obj_export::export_obj(path, &self.scene.models())
+            .map_err(|e| anyhow::anyhow!("could not write {}: {}", path, e))
+    }
+
+    // moves and recolors the animated lights to where they are at the animation clock's time
+    fn apply_light_animations(&mut self) {
+        let time = self.animations.time();
+        for animation in &self.animations.lights {
+            let Some(light) = self.point_lights.get_mut(animation.index) else {
+                continue;
+            };
+            if let Some(position) = &animation.position {
+                light.transform.set_translation(position.sample(time));
+            }
+            if let Some(color) = &animation.color {
+                light.color = color.sample(time);
+            }
+        }
+        self.upload_lights();
+    }
+
+    // the models in the scene, in the order they're drawn
+    pub fn model_ids(&self) -> Vec<ModelId> {
+        self.scene
+            .objects()
+            .iter()
+            .map(|object| object.id)
+            .collect()
+    }
+
+    // adds a copy of a model for each transform, sharing its buffers and materials, and
+    // returns their ids. the scene geometry is rebuilt once for all of them rather than once
+    // per copy, which is what makes thousands of them practical
+    pub fn add_instances(
+        &mut self,
+        id: ModelId,
+        transforms: &[math::Transform],
+    ) -> anyhow::Result<Vec<ModelId>> {
+        let Some(object) = self.scene.objects().iter().find(|o| o.id == id) else {
+            anyhow::bail!("there is no model {}", id);
+        };
+        let (model, source) = (object.model.clone(), object.source.clone());
+
+        let ids = transforms
+            .iter()
+            .map(|transform| {
+                let mut model = model.clone();
+                model.transform = transform.clone();
+                self.scene
+                    .add(&self.device, &self.layouts.per_object, model, &source)
+            })
+            .collect();
+        self.rebuild_scene_geometry()?;
+        Ok(ids)
+    }
+
+    // places a model, returning false if it isn't in the scene. the voxels catch up in the
+    // next update, once however many models were moved
+    pub fn set_transform(&mut self, id: ModelId, transform: math::Transform) -> bool {
+        let Some(model) = self.scene.get_mut(id) else {
+            return false;
+        };
+        model.transform = transform;
+        self.variables.needs_revoxelize = true;
+        self.variables.needs_redraw = true;
+        true
+    }
+
+    // starts emitting particles, which are simulated and drawn on the gpu. fails when the
+    // adapter can't, or when there'd be more particles alive at once than there's room for
+    pub fn add_emitter(&mut self, emitter: Emitter) -> anyhow::Result<EmitterId> {
+        let Some(particles) = &mut self.particles else {
+            anyhow::bail!("particles need compute shaders, which this adapter doesn't have");
+        };
+        self.variables.needs_redraw = true;
+        particles.add(emitter)
+    }
+
+    // changes an emitter, eg to follow something, returning false if there's no such emitter
+    pub fn set_emitter(&mut self, id: EmitterId, emitter: Emitter) -> anyhow::Result<bool> {
+        match &mut self.particles {
+            Some(particles) => particles.set(id, emitter),
+            None => Ok(false),
+        }
+    }
+
+    // stops an emitter, taking its particles with it
+    pub fn remove_emitter(&mut self, id: EmitterId) -> bool {
+        self.variables.needs_redraw = true;
+        self.particles
+            .as_mut()
+            .is_some_and(|particles| particles.remove(id))
+    }
+
+    // starts rain or snow falling over the camera, at an intensity from 0 to 1, or stops it
+    // with Precipitation::None. the scene stays wet for a while after rain
+    pub fn set_weather(
+        &mut self,
+        precipitation: Precipitation,
+        intensity: f32,
+    ) -> anyhow::Result<()> {
+        self.variables.needs_redraw = true;
+        self.weather.set(
+            &self.device,
+            &self.surface_config,
+            self.particles.as_mut(),
+            precipitation,
+            intensity,
+        )
+    }
+
+    // lines drawn over the scene for the next frame only, so a viewer draws them again in each
+    // update for as long as they should stay. see debug_draw.rs
+    pub fn debug_line(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 3]) {
+        self.debug_draw.line(start, end, color);
+        self.variables.needs_redraw = true;
+    }
+
+    pub fn debug_sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 3]) {
+        self.debug_draw.sphere(center, radius, color);
+        self.variables.needs_redraw = true;
     }
 
-    pub fn update(&mut self, dt: Duration) {
-        self.camera_controller.update_camera(&mut self.camera, dt);
-        self.uniforms
-            .camera
-            .update_view_proj(&self.camera, &self.projection);
-        self.queue.write_buffer(
-            &self.uniforms.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniforms.camera]),
-        );
+    pub fn debug_aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 3]) {
+        self.debug_draw.aabb(min, max, color);
+        self.variables.needs_redraw = true;
+    }
 
-        // if self.variables.enable_light_rotation {
-        //     self.uniforms.light.position = (cgmath::Quaternion::from_angle_z(cgmath::Deg(0.1))
-        //         * cgmath::Vector3::from(self.uniforms.light.position))
-        //     .into();
-        // }
-        // self.queue.write_buffer(
-        //     &self.uniforms.light_buffer,
-        //     0,
-        //     bytemuck::cast_slice(&[self.uniforms.light]),
-        // );
-
-        self.uniforms.timestamp.time = self.diagnostics.start_time.elapsed().as_millis() as u32;
-        self.queue.write_buffer(
-            &self.uniforms.timestamp_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniforms.timestamp]),
-        );
+    // the volume a view projection matrix sees, which needs a far plane
+    pub fn debug_frustum(&mut self, view_projection: cgmath::Matrix4<f32>, color: [f32; 3]) {
+        self.debug_draw.frustum(view_projection, color);
+        self.variables.needs_redraw = true;
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            self.surface_config.width = width;
-            self.surface_config.height = height;
+    // moves and recolors an existing point light, returning false if there isn't one at the
+    // index. lights can't be added, since every scene tab has to have the same light counts
+    pub fn set_point_light(
+        &mut self,
+        index: usize,
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    ) -> bool {
+        let Some(light) = self.point_lights.get_mut(index) else {
+            return false;
+        };
+        light.transform.set_translation(position);
+        light.color = color;
+        light.intensity = intensity;
+        self.upload_lights();
+        true
+    }
 
-            self.surface.configure(&self.device, &self.surface_config);
-            self.is_surface_configured = true;
+    // puts the camera at a position, looking along the yaw and pitch. the controller keeps
+    // working from there
+    pub fn set_camera(
+        &mut self,
+        position: [f32; 3],
+        yaw: cgmath::Deg<f32>,
+        pitch: cgmath::Deg<f32>,
+    ) {
+        self.camera = camera::Camera::new(position, yaw, pitch);
+        self.variables.needs_redraw = true;
+    }
 
-            self.depth_texture = texture::Texture::create_depth_texture(
-                &self.device,
-                &self.surface_config,
-                "depth texture",
+    // how frames are handed to the display: fifo waits for it, mailbox replaces a waiting frame
+    // with a newer one and immediate presents straight away, tearing. only the modes the
+    // surface supports can be used
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> anyhow::Result<()> {
+        if !self.present_modes.contains(&mode) {
+            anyhow::bail!(
+                "{:?} isn't supported here, only {:?}",
+                mode,
+                self.present_modes
             );
-
-            self.projection.resize(width, height);
-        } else {
-            log::warn!["resize was called with width 0 or height 0"]
         }
+        self.surface_config.present_mode = mode;
+        self.surface.configure(&self.device, &self.surface_config);
+        self.variables.needs_redraw = true;
+        Ok(())
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.window.request_redraw();
+    // on to the next of fifo, mailbox and immediate which the surface supports
+    fn cycle_present_mode(&mut self) -> anyhow::Result<wgpu::PresentMode> {
+        const MODES: [wgpu::PresentMode; 3] = [
+            wgpu::PresentMode::Fifo,
+            wgpu::PresentMode::Mailbox,
+            wgpu::PresentMode::Immediate,
+        ];
+        let current = MODES
+            .iter()
+            .position(|mode| *mode == self.surface_config.present_mode)
+            .unwrap_or(0);
+        let mode = (1..=MODES.len())
+            .map(|i| MODES[(current + i) % MODES.len()])
+            .find(|mode| self.present_modes.contains(mode))
+            .context("the surface supports none of fifo, mailbox and immediate")?;
+        self.set_present_mode(mode)?;
+        Ok(mode)
+    }
 
-        if !self.is_surface_configured {
-            log::warn!("render called while surface is not configured");
-            return Ok(());
+    // switches to the ray traced pipelines, for shadows and ambient occlusion, or back
+    pub fn set_ray_tracing(&mut self, enabled: bool) -> anyhow::Result<()> {
+        if enabled && self.pipelines.render_raytraced.is_none() {
+            anyhow::bail!("ray tracing is not supported by this adapter");
         }
+        self.variables.enable_raytracing = enabled;
+        self.variables.needs_redraw = true;
+        Ok(())
+    }
 
-        // wait for the surface to provide a new texture to which to render
-        let target_surface = self.surface.get_current_texture()?;
+    // overlays an image on the viewport, replacing any previous reference image
+    pub fn load_reference(&mut self, path: &str) -> Result<(), error::AssetError> {
+        self.reference = Some(reference::ReferenceOverlay::load(
+            &self.device,
+            &self.queue,
+            path,
+            self.surface_config.format,
+        )?);
+        Ok(())
+    }
 
-        // TextureView controls how the rendering code interacts with the texture
-        let target_view = target_surface
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    // opens an empty scene in a new tab and switches to it, returning its tab number. it starts
+    // with a copy of the current lights, which also keeps the light counts the same across tabs
+    pub fn new_scene(&mut self) -> usize {
+        let scene = scene::Scene::new(&self.device, &self.layouts.per_object);
+        let voxel_gi = vxgi::VoxelGI::new(
+            &self.device,
+            &self.queue,
+            &[],
+            &self.materials,
+            &self.layouts.per_frame,
+        );
+        let ray_tracing = self
+            .ray_tracing
+            .is_some()
+            .then(|| raytracing::RayTracing::new(&self.device, &[]));
+
+        self.scene_tabs.push(Some(SceneTab {
+            scene,
+            point_lights: self.point_lights.clone(),
+            directional_lights: self.directional_lights.clone(),
+            spot_lights: self.spot_lights.clone(),
+            animations: animation::Animations::default(),
+            voxel_gi,
+            ray_tracing,
+        }));
+        let index = self.scene_tabs.len() - 1;
+        self.switch_scene(index);
+        index
+    }
 
-        // create a command encoder to send commands to the gpu
-        let mut command_encoder =
-            self.device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("render command encoder"),
-                });
+    // swaps the tab's scene in for the current one, returning false if there is no such tab.
+    // only the bind groups that point at the swapped resources are recreated
+    pub fn switch_scene(&mut self, index: usize) -> bool {
+        if index == self.active_scene {
+            return true;
+        }
+        let Some(mut tab) = self.scene_tabs.get_mut(index).and_then(Option::take) else {
+            return false;
+        };
 
-        // encode the rendering pass:
-        {
-            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("render pass"),
-                color_attachments: &[
-                    // location[0] refers to this color attachment
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &target_view,
-                        resolve_target: None,
-                        depth_slice: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    }),
-                ],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-                multiview_mask: None,
-            });
+        // the voxel view mode is a viewer setting rather than part of the scene
+        let mode = self.voxel_gi.mode();
+        std::mem::swap(&mut self.scene, &mut tab.scene);
+        std::mem::swap(&mut self.point_lights, &mut tab.point_lights);
+        std::mem::swap(&mut self.directional_lights, &mut tab.directional_lights);
+        std::mem::swap(&mut self.spot_lights, &mut tab.spot_lights);
+        std::mem::swap(&mut self.animations, &mut tab.animations);
+        std::mem::swap(&mut self.voxel_gi, &mut tab.voxel_gi);
+        std::mem::swap(&mut self.ray_tracing, &mut tab.ray_tracing);
+        self.scene_tabs[self.active_scene] = Some(tab);
+        self.active_scene = index;
+        // ids are only unique within a scene
+        self.select(None);
+
+        self.voxel_gi.set_mode(mode, &self.queue);
+        self.upload_lights();
+        if self.ray_tracing.is_some() {
+            self.rebind_frame_uniforms();
+        }
+        self.variables.needs_redraw = true;
+        true
+    }
 
-            if self.variables.swap_pipelines {
-                render_pass.set_pipeline(&self.pipelines.render_alt);
-            } else {
-                render_pass.set_pipeline(&self.pipelines.render);
-            }
+    // eg [SCENE 2/3], or nothing while there is only one scene
+    fn describe_scene_tabs(&self) -> String {
+        if self.scene_tabs.len() > 1 {
+            format!(
+                "[SCENE {}/{}]",
+                self.active_scene + 1,
+                self.scene_tabs.len()
+            )
+        } else {
+            String::new()
+        }
+    }
 
-            self.queue.write_buffer(
-                &self.uniforms.model_transform_buffer,
-                0,
-                bytemuck::cast_slice(&[model::ModelTransformationUniform::from_model(&self.model)]),
-            );
+    // the voxels, acceleration structures and debug vectors are built from the scene's
+    // geometry, so they are rebuilt whenever a model is added or removed
+    // repacks the material atlas, if it's on, after materials were added or replaced
+    // shows the uv debug grid in place of the named material's diffuse map, or hides it
+    // again. "all" turns it on for every material unless all of them already show it.
+    // returns whether the grid is now shown
+    pub fn toggle_uv_grid(&mut self, material: &str) -> anyhow::Result<bool> {
+        let indices = if material == "all" {
+            (0..self.materials.len()).collect::<Vec<_>>()
+        } else {
+            let index = self
+                .material_map
+                .get(material)
+                .ok_or_else(|| anyhow::anyhow!("there is no material {}", material))?;
+            vec![*index]
+        };
 
-            render_pass.set_bind_group(0, &self.per_frame_bind_group, &[]);
-            // render_pass.set_bind_group(1, &self.per_pass_bind_group, &[]);
-            // render_pass.set_bind_group(2, &self.per_object_bind_group, &[]);
+        let show = !indices.iter().all(|&i| self.materials[i].shows_uv_grid());
+        for i in indices {
+            self.materials[i].set_uv_grid(
+                &self.device,
+                &self.queue,
+                &self.texture_cache.placeholders,
+                &self.layouts.per_pass,
+                show,
+            );
+        }
+        self.rebuild_material_atlas();
+        self.variables.needs_redraw = true;
+        Ok(show)
+    }
 
-            render_pass.draw_model(&self.model, &self.materials, &self.per_object_bind_group);
+    fn rebuild_material_atlas(&mut self) {
+        if self.material_atlas.is_some() {
+            self.material_atlas = Some(material_atlas::MaterialAtlas::new(
+                &self.device,
+                &self.queue,
+                &self.layouts.material_atlas,
+                &self.materials,
+            ));
+        }
+    }
 
-            render_pass.set_pipeline(&self.pipelines.light_debug);
+    // every frame's per frame bind group again, eg for a new tlas
+    fn rebind_frame_uniforms(&mut self) {
+        for frame in self.frames.iter_mut() {
+            frame.bind_group = Self::create_per_frame_bind_group(
+                &self.device,
+                &self.layouts.per_frame,
+                [
+                    &frame.camera_buffer,
+                    &frame.light_buffer,
+                    &frame.light_metadata_buffer,
+                    &frame.timestamp_buffer,
+                ],
+                self.ray_tracing.as_ref(),
+                self.denoiser.as_ref(),
+                &self.shadows,
+                &self.ibl,
+            );
+        }
+    }
 
-            // render_pass.set_bind_group(0, &self.per_frame_bind_group, &[]);
-            // render_pass.set_bind_group(1, &self.per_pass_bind_group, &[]);
-            // render_pass.set_bind_group(2, &self.per_object_bind_group, &[]);
+    fn rebuild_scene_geometry(&mut self) -> Result<(), error::AssetError> {
+        let models = self.scene.models();
+        self.voxel_gi
+            .revoxelize(&self.queue, &models, &self.materials);
 
-            render_pass.draw_model(
-                &self.debug_light_model,
-                &self.materials,
-                &self.per_frame_bind_group,
-            );
+        if self.ray_tracing.is_some() {
+            self.ray_tracing = Some(raytracing::RayTracing::new(&self.device, &models));
+            self.rebind_frame_uniforms();
+        }
 
-            if self.variables.enable_geometry_debug {
-                if let Some(debug_extras) = &self.debug_tbn_extras {
-                    render_pass.set_pipeline(&self.pipelines.geometry_debug);
-                    render_pass.draw_model(
-                        &self.model,
-                        &self.materials,
-                        &self.per_object_bind_group,
-                    );
+        self.variables.needs_redraw = true;
+        Ok(())
+    }
 
-                    render_pass.set_pipeline(&debug_extras.debug_tbn_render_pipeline);
-                    render_pass.draw_mesh_instanced(
-                        &debug_extras.debug_vector_model.meshes[0],
-                        &self.materials[*self.material_map.get("blue").unwrap_or(&0)],
-                        0..(debug_extras.debug_tbn_uniforms[0].len() as u32),
-                        &debug_extras.tangent_bind_group,
-                    );
-                    render_pass.draw_mesh_instanced(
-                        &debug_extras.debug_vector_model.meshes[0],
-                        &self.materials[*self.material_map.get("green").unwrap_or(&0)],
-                        0..(debug_extras.debug_tbn_uniforms[1].len() as u32),
-                        &debug_extras.bitangent_bind_group,
-                    );
-                    render_pass.draw_mesh_instanced(
-                        &debug_extras.debug_vector_model.meshes[0],
-                        &self.materials[*self.material_map.get("red").unwrap_or(&0)],
-                        0..(debug_extras.debug_tbn_uniforms[2].len() as u32),
-                        &debug_extras.normal_bind_group,
-                    );
+    // sets a named value: a light or camera setting, or one of the current material's shader
+    // parameters, with colors addressed per channel as eg `tint.g`
+    pub fn set(&mut self, name: &str, value: f32) -> anyhow::Result<()> {
+        match name {
+            "light.intensity" | "light.x" | "light.y" | "light.z" => {
+                let Some(light) = self.point_lights.first_mut() else {
+                    anyhow::bail!("there are no point lights");
+                };
+                match name {
+                    "light.intensity" => light.intensity = value,
+                    _ => {
+                        let mut position = light.transform.translation();
+                        match name {
+                            "light.x" => position.x = value,
+                            "light.y" => position.y = value,
+                            _ => position.z = value,
+                        }
+                        light.transform.set_translation(position);
+                    }
+                }
+                self.upload_lights();
+            }
+            "camera.speed" => self.camera_controller.set_speed(value),
+            "camera.sensitivity" => self.camera_controller.set_sensitivity(value),
+            "camera.acceleration" => self.camera_controller.set_acceleration(value),
+            "camera.deceleration" => self.camera_controller.set_deceleration(value),
+            "camera.smoothing" => self.camera_controller.set_mouse_smoothing(value),
+            "camera.fov" => self.projection.set_fov(value),
+            "camera.near" | "camera.far" => {
+                let (near, far) = match name {
+                    "camera.near" => (value, self.projection.z_plane_far()),
+                    _ => (self.projection.z_plane_near(), value),
+                };
+                self.projection.set_z_planes(near, far);
+                // set by hand, so they're kept rather than fitted over
+                self.variables.fit_z_planes = false;
+            }
+            "camera.fit_z_planes" => self.variables.fit_z_planes = value != 0.0,
+            "walk.eye_height" | "walk.gravity" => {
+                let Some(walk) = &mut self.walk else {
+                    anyhow::bail!("walking is off, press Tab to turn it on");
+                };
+                if name == "walk.eye_height" {
+                    walk.eye_height = value.max(0.0);
+                } else {
+                    walk.gravity = value;
+                }
+            }
+            // in frames per second, 0 for no cap
+            "frame.cap" => self.frame_limiter.set_max_fps(Some(value)),
+            // as a fraction of real time
+            "time.scale" => self.scene_clock.set_scale(value),
+            "path.fixed_step" => self.camera_path.fixed_step = value != 0.0,
+            "stereo.separation" => self.variables.eye_separation = value,
+            "bloom.threshold" | "bloom.intensity" => {
+                let Some(bloom) = &mut self.bloom else {
+                    anyhow::bail!("bloom is off, press N to turn it on");
+                };
+                if name == "bloom.threshold" {
+                    bloom.set_threshold(value, &self.queue);
+                } else {
+                    bloom.set_intensity(value, &self.queue);
                 }
             }
+            "fxaa.threshold" | "fxaa.subpixel" => {
+                let Some(fxaa) = &mut self.fxaa else {
+                    anyhow::bail!("fxaa is off, press X to turn it on");
+                };
+                if name == "fxaa.threshold" {
+                    fxaa.set_threshold(value, &self.queue);
+                } else {
+                    fxaa.set_subpixel_blend(value, &self.queue);
+                }
+            }
+            "fog.density" | "fog.height" | "fog.falloff" | "fog.noise" | "fog.anisotropy"
+            | "fog.distance" | "fog.ambient" => {
+                let Some(fog) = &mut self.fog else {
+                    anyhow::bail!("fog needs compute shaders, which this adapter doesn't have");
+                };
+                // taken up by the next frame's update
+                let settings = &mut fog.settings;
+                match name {
+                    "fog.density" => settings.density = value,
+                    "fog.height" => settings.height = value,
+                    "fog.falloff" => settings.height_falloff = value,
+                    "fog.noise" => settings.noise = value,
+                    "fog.anisotropy" => settings.anisotropy = value,
+                    "fog.distance" => settings.distance = value,
+                    _ => settings.ambient = value,
+                }
+            }
+            "shadow.distance" => self.shadows.set_distance(value),
+            "shadow.point_bias" => self.shadows.set_point_bias(value),
+            "shadow.sun_size" => self.shadows.set_sun_size(value),
+            "shadow.point_size" => self.shadows.set_point_size(value),
+            "environment.intensity" => self.ibl.set_intensity(value, &self.queue),
+            "parallax.steps" => {
+                for material in self.materials.iter_mut() {
+                    material.set_parallax_steps(value as u32, &self.queue);
+                }
+            }
+            "reference.opacity" | "reference.split" => {
+                let Some(reference) = &mut self.reference else {
+                    anyhow::bail!("no reference image is loaded");
+                };
+                if name == "reference.opacity" {
+                    reference.set_opacity(value, &self.queue);
+                } else {
+                    reference.set_split(value, &self.queue);
+                }
+            }
+            _ => {
+                let (parameter_name, component) = match name.rsplit_once('.') {
+                    Some((parameter_name, channel)) => {
+                        match ["r", "g", "b", "a"].iter().position(|c| *c == channel) {
+                            Some(component) => (parameter_name, component),
+                            None => anyhow::bail!("unknown channel {} in {}", channel, name),
+                        }
+                    }
+                    None => (name, 0),
+                };
+                let Some(parameter) = self
+                    .shader_parameters
+                    .parameters
+                    .iter()
+                    .find(|p| p.name == parameter_name)
+                else {
+                    anyhow::bail!("unknown setting {}", name);
+                };
+
+                let Some(material) = self.edited_material() else {
+                    anyhow::bail!("there is no material to set {} on", name);
+                };
+                self.materials[material]
+                    .parameters
+                    .set(parameter, component, value);
+                self.rebuild_material_atlas();
+            }
         }
 
-        // close the command encoder and submit the instructions to the gpu's render queue
-        self.queue.submit(std::iter::once(command_encoder.finish()));
+        self.variables.needs_redraw = true;
+        Ok(())
+    }
 
-        self.diagnostics.frame_count += 1;
+    // saves the last drawn frame, drawing one just for the screenshot when frames aren't kept
+    pub fn screenshot(&mut self, path: &str) -> anyhow::Result<()> {
+        let image = match &self.frame_texture {
+            Some(frame_texture) => {
+                screenshot::capture(&self.device, &self.queue, &frame_texture.texture)?
+            }
+            None => {
+                let target = texture::Texture::create_frame_texture(
+                    &self.device,
+                    &self.surface_config,
+                    "screenshot texture",
+                );
+                let mut command_encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("screenshot render command encoder"),
+                        });
+                self.encode_scene(&mut command_encoder, &target.view);
+                self.queue.submit(std::iter::once(command_encoder.finish()));
+                screenshot::capture(&self.device, &self.queue, &target.texture)?
+            }
+        };
 
-        // put the output from the rendering onto the window
-        target_surface.present();
+        image
+            .save(path)
+            .with_context(|| format!("could not write {}", path))?;
         Ok(())
     }
 
-    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-        match (code, is_pressed) {
-            (KeyCode::Escape, true) => event_loop.exit(),
-            (KeyCode::KeyG, true) => {
-                self.variables.enable_geometry_debug = !self.variables.enable_geometry_debug
-            }
-            (KeyCode::KeyC, true) => {
-                self.variables.swap_pipelines = !self.variables.swap_pipelines;
+    // starts copying the last frame back without waiting for it, to be saved as a timestamped
+    // png by save_finished_captures. without a frame texture to copy from, the scene is drawn
+    // again into a texture of its own
+    pub fn capture_frame(&mut self) -> anyhow::Result<()> {
+        let mut command_encoder =
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("capture command encoder"),
+                });
+        let target = match &self.frame_texture {
+            Some(frame_texture) => frame_texture.clone(),
+            None => {
+                let target = texture::Texture::create_frame_texture(
+                    &self.device,
+                    &self.surface_config,
+                    "capture texture",
+                );
+                self.encode_scene(&mut command_encoder, &target.view);
+                target
             }
-            (KeyCode::KeyL, true) => {
-                self.variables.enable_light_rotation = !self.variables.enable_light_rotation
+        };
+
+        let mut readback =
+            readback::Readback::encode(&self.device, &mut command_encoder, &target.texture)?;
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+        readback.map();
+        self.captures
+            .push((screenshot::default_path("capture"), readback));
+        Ok(())
+    }
+
+    // starts recording every presented frame, or stops and finishes writing the recording.
+    // recording copies the frame texture, so it isn't available without one
+    pub fn toggle_recording(
+        &mut self,
+        format: recording::RecordingFormat,
+    ) -> anyhow::Result<String> {
+        if let Some(recording) = self.recording.take() {
+            return recording.finish(&self.device);
+        }
+
+        anyhow::ensure!(
+            self.frame_texture.is_some(),
+            "the surface can't be copied to, so there are no frames to record"
+        );
+        let destination = recording::Recording::default_destination(format);
+        self.recording = Some(recording::Recording::start(format, destination.clone())?);
+        Ok(format!("recording to {}", destination))
+    }
+
+    // saves the captures whose copies have come back, on a thread of their own since encoding
+    // a png takes a few frames' worth of time
+    fn save_finished_captures(&mut self) {
+        let mut index = 0;
+        while index < self.captures.len() {
+            let Some(result) = self.captures[index].1.try_read() else {
+                index += 1;
+                continue;
+            };
+            let (path, _) = self.captures.remove(index);
+            match result {
+                Ok(image) => {
+                    std::thread::spawn(move || match image.save(&path) {
+                        Ok(()) => log::info!("saved {}", path),
+                        Err(e) => log::error!("could not write {}: {}", path, e),
+                    });
+                }
+                Err(e) => log::error!("could not capture {}: {:#}", path, e),
             }
-            (KeyCode::KeyR, true) => {
-                self.model.rotation = cgmath::Quaternion::from_axis_angle(
-                    cgmath::Vector3::unit_y(),
-                    cgmath::Deg(self.diagnostics.frame_count as f32 * 0.1),
-                )
+        }
+    }
+
+    // renders the six cube faces around the camera and saves them resampled as an
+    // equirectangular panorama, four faces wide. the depth texture is swapped for one the size
+    // of a face while capturing, and the camera is written back afterwards
+    pub fn panorama(&mut self, path: &str, face_size: u32) -> anyhow::Result<()> {
+        let face_size = face_size.clamp(1, self.device.limits().max_texture_dimension_2d);
+        let mut face_config = self.surface_config.clone();
+        face_config.width = face_size;
+        face_config.height = face_size;
+
+        let target =
+            texture::Texture::create_frame_texture(&self.device, &face_config, "panorama texture");
+        let face_depth = texture::Texture::create_depth_texture(
+            &self.device,
+            &face_config,
+            "panorama depth texture",
+        );
+        let screen_depth = std::mem::replace(&mut self.depth_texture, face_depth);
+        let scissor = self.pass_ops.scissor.take();
+        // each face is the camera's view alone
+        let is_quad_view = std::mem::replace(&mut self.variables.enable_quad_view, false);
+        let projection = self.projection.cube_face();
+
+        let mut faces = Vec::with_capacity(panorama::FACES.len());
+        let mut result = Ok(());
+        for (forward, up) in panorama::FACES {
+            let mut face_uniform = self.uniforms.camera;
+            face_uniform.update_view_proj_to(
+                self.camera.position,
+                forward.into(),
+                up.into(),
+                &projection,
+            );
+            self.diagnostics.render_stats.write_buffer(
+                &self.queue,
+                &self.frames.current().camera_buffer,
+                0,
+                bytemuck::cast_slice(&[face_uniform]),
+            );
+
+            let mut command_encoder =
+                self.device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("panorama face command encoder"),
+                    });
+            command_encoder.push_debug_group(&format!("panorama face looking along {:?}", forward));
+            // the faces don't follow on from each other, so each is denoised without a history
+            if let Some(denoiser) = &mut self.denoiser {
+                denoiser.reset_history();
             }
-            _ => {
-                self.camera_controller.handle_key(code, is_pressed);
+            self.encode_scene_into(&mut command_encoder, &target.view);
+            command_encoder.pop_debug_group();
+            self.queue.submit(std::iter::once(command_encoder.finish()));
+            match screenshot::capture(&self.device, &self.queue, &target.texture) {
+                Ok(face) => faces.push(face),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
             }
         }
+
+        self.depth_texture = screen_depth;
+        self.pass_ops.scissor = scissor;
+        self.variables.enable_quad_view = is_quad_view;
+        self.diagnostics.render_stats.write_buffer(
+            &self.queue,
+            &self.frames.current().camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms.camera]),
+        );
+        self.variables.needs_redraw = true;
+        result?;
+
+        panorama::save(&panorama::equirectangular(&faces, face_size * 4), path)
     }
 
-    fn handle_mouse_button(&mut self, button: MouseButton, pressed: bool) {
-        match button {
-            MouseButton::Left => self.variables.is_mouse_pressed = pressed,
-            _ => {}
-        }
+    pub fn start_benchmark(&mut self, frames: u32) {
+        self.variables.benchmark = Some(timing::Benchmark::new(frames));
     }
 
     fn handle_mouse_scroll(&mut self, delta: &MouseScrollDelta) {
         self.camera_controller.handle_scroll(delta);
     }
+}
 
-    fn create_render_pipeline(
-        device: &wgpu::Device,
-        layout: &wgpu::PipelineLayout,
-        color_format: wgpu::TextureFormat,
-        depth_format: Option<wgpu::TextureFormat>,
-        vertex_layouts: &[wgpu::VertexBufferLayout],
-        shader_descriptor: wgpu::ShaderModuleDescriptor,
-        polygon_mode: wgpu::PolygonMode,
-    ) -> wgpu::RenderPipeline {
-        let shader = device.create_shader_module(shader_descriptor);
-
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render pipeline"),
-            layout: Some(layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vertex_main"),
-                buffers: vertex_layouts,
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fragment_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: color_format,
-                    blend: Some(wgpu::BlendState {
-                        alpha: wgpu::BlendComponent::REPLACE,
-                        color: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode,
-                // true requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // true requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
-                format,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview_mask: None,
-            cache: None,
-        })
+// what a program built on the renderer adds to it, see examples/. setup runs once the renderer
+// is ready, with the default scene loaded, and update runs every frame before the renderer's
+// own update. the keyboard, mouse and console all keep working alongside it
+pub trait Viewer {
+    fn setup(&mut self, _state: &mut State) -> anyhow::Result<()> {
+        Ok(())
     }
+
+    fn update(&mut self, _state: &mut State, _dt: Duration) {}
 }
 
+// the viewer run() shows: the default scene, as it's loaded
+struct DefaultScene;
+
+impl Viewer for DefaultScene {}
+
 pub struct App {
     // if compiling for wasm, the callback needs this proxy
     #[cfg(target_arch = "wasm32")]
     proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     state: Option<State>,
+    viewer: Box<dyn Viewer>,
+    options: options::StartupOptions,
+    // set when the state fails to initialise, and returned from run() once the loop exits
+    error: Option<anyhow::Error>,
     last_instant: Instant,
 }
 
 impl App {
-    pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
+    pub fn new(
+        viewer: Box<dyn Viewer>,
+        options: options::StartupOptions,
+        #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>,
+    ) -> Self {
         #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
         Self {
             state: None,
+            viewer,
+            options,
+            error: None,
             #[cfg(target_arch = "wasm32")]
             proxy,
             last_instant: Instant::now(),
@@ -1132,6 +4705,24 @@ impl ApplicationHandler<State> for App {
         let mut window_attributes =
             winit::window::WindowAttributes::default().with_title("graphics fundamentals - dpb4");
 
+        // the canvas is sized by the page
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let [width, height] = self.options.config.window_size;
+            let [min_width, min_height] = MIN_WINDOW_SIZE;
+            window_attributes = window_attributes
+                .with_inner_size(winit::dpi::LogicalSize::new(width, height))
+                .with_min_inner_size(winit::dpi::LogicalSize::new(min_width, min_height))
+                .with_window_icon(window_icon());
+        }
+
+        if self.options.config.transparent {
+            window_attributes = window_attributes
+                .with_transparent(true)
+                .with_decorations(false)
+                .with_window_level(winit::window::WindowLevel::AlwaysOnTop);
+        }
+
         #[cfg(target_arch = "wasm32")]
         {
             use wasm_bindgen::JsCast;
@@ -1152,7 +4743,15 @@ impl ApplicationHandler<State> for App {
         {
             // If we are not on web we can use pollster to
             // await the
-            self.state = Some(pollster::block_on(State::new(window)).unwrap());
+            match pollster::block_on(State::new(window, self.options.clone()))
+                .and_then(|mut state| self.viewer.setup(&mut state).map(|_| state))
+            {
+                Ok(state) => self.state = Some(state),
+                Err(e) => {
+                    self.error = Some(e);
+                    event_loop.exit();
+                }
+            }
         }
 
         #[cfg(target_arch = "wasm32")]
@@ -1160,11 +4759,30 @@ impl ApplicationHandler<State> for App {
             // Run the future asynchronously and use the
             // proxy to send the results to the event loop
             if let Some(proxy) = self.proxy.take() {
+                let options = self.options.clone();
                 wasm_bindgen_futures::spawn_local(async move {
+                    let loading_bar = wgpu::web_sys::window()
+                        .and_then(|window| window.document())
+                        .and_then(|document| document.get_element_by_id(LOADING_BAR_ID));
+                    let preloaded = assets::preload(&STARTUP_ASSETS, |path, progress| {
+                        if let Some(bar) = &loading_bar
+                            && let Some(fraction) = progress.fraction()
+                        {
+                            let _ = bar.set_attribute("value", &fraction.to_string());
+                        }
+                        log::debug!("fetching {}: {} bytes", path, progress.loaded);
+                    })
+                    .await;
+                    if let Err(e) = preloaded {
+                        log::error!("{}", e);
+                    }
+                    if let Some(bar) = loading_bar {
+                        bar.remove();
+                    }
                     assert!(
                         proxy
                             .send_event(
-                                State::new(window)
+                                State::new(window, options)
                                     .await
                                     .expect("Unable to create canvas!!!")
                             )
@@ -1175,7 +4793,6 @@ impl ApplicationHandler<State> for App {
         }
     }
 
-    #[allow(unused_mut)]
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: State) {
         #[cfg(target_arch = "wasm32")]
         {
@@ -1185,6 +4802,9 @@ impl ApplicationHandler<State> for App {
                 event.window.inner_size().height,
             );
         }
+        if let Err(e) = self.viewer.setup(&mut event) {
+            log::error!("setting up the viewer: {:#}", e);
+        }
         self.state = Some(event);
     }
 
@@ -1224,12 +4844,32 @@ impl ApplicationHandler<State> for App {
 
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            WindowEvent::Resized(size) => {
+                let was_hidden = state.is_hidden();
+                state.resize(size.width, size.height);
+                if was_hidden && !state.is_hidden() {
+                    self.last_instant = Instant::now();
+                    state.window.request_redraw();
+                }
+            }
+            WindowEvent::Occluded(is_occluded) => {
+                let was_hidden = state.is_hidden();
+                state.is_occluded = is_occluded;
+                if was_hidden && !state.is_hidden() {
+                    self.last_instant = Instant::now();
+                    state.window.request_redraw();
+                }
+            }
+            // no redraw is asked for again until the window is shown, so nothing runs meanwhile,
+            // and the time it was hidden for isn't counted as one long frame when it comes back
+            WindowEvent::RedrawRequested if state.is_hidden() => {}
             WindowEvent::RedrawRequested => {
+                state.frame_limiter.wait();
                 let dt = self.last_instant.elapsed();
                 self.last_instant = Instant::now();
 
                 let before_update = Instant::now();
+                self.viewer.update(state, dt);
                 state.update(dt);
 
                 let update_time = before_update.elapsed();
@@ -1252,39 +4892,116 @@ impl ApplicationHandler<State> for App {
                     .update_time_avg
                     .push(update_time.as_micros() as f32);
                 state.diagnostics.frame_time_avg.push(dt.as_secs_f32());
+                state.diagnostics.frame_stats.push(dt);
                 state
                     .diagnostics
                     .render_time_avg
                     .push(before_render.elapsed().as_micros() as f32);
 
+                // the frame graph shows the timings instead
+                let timings = if state.variables.show_frame_graph {
+                    "[FRAME GRAPH]".to_string()
+                } else {
+                    format!(
+                        "fps {: >3}   |   mspf {: >3} ms ({})   |   rt {: >6} us   |   ru {: >3} %  |   ut {: >6} us   |   uu {: >3} % ({})",
+                        (1.0 / state.diagnostics.frame_time_avg.get()) as u32,
+                        (state.diagnostics.frame_time_avg.get() * 1000.0) as u32,
+                        state.diagnostics.frame_stats.describe(),
+                        state.diagnostics.render_time_avg.get() as u32,
+                        (state.diagnostics.render_time_avg.get() / (1.0 / 240.0 * 1000000.0))
+                            as u32,
+                        state.diagnostics.update_time_avg.get() as u32,
+                        (state.diagnostics.update_time_avg.get() / (1.0 / 240.0 * 1000000.0))
+                            as u32,
+                        state.diagnostics.update_breakdown.describe(),
+                    )
+                };
                 state.window.set_title(&format!(
-                    "graphics fundamentals - dpb4        |  fps {: >3}   |   mspf {: >3} ms   |   rt {: >6} us   |   ru {: >3} %  |   ut {: >6} us   |   uu {: >3} %  |   {}",
-                    (1.0 / state.diagnostics.frame_time_avg.get()) as u32,
-                    (state.diagnostics.frame_time_avg.get() * 1000.0) as u32,
-
-                    state.diagnostics.render_time_avg.get() as u32,
-                    (state.diagnostics.render_time_avg.get() / (1.0 / 240.0 * 1000000.0)) as u32,
-
-                    state.diagnostics.update_time_avg.get() as u32,
-                    (state.diagnostics.update_time_avg.get() / (1.0 / 240.0 * 1000000.0)) as u32,
-
-                    if state.variables.swap_pipelines { "[ALT PIPELINE]" } else {""}
+                    "graphics fundamentals - dpb4        |  {}  |   {}  {}   |   {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}   {}   {}",
+                    timings,
+
+                    state.diagnostics.render_stats.describe(),
+                    state.diagnostics.memory_stats.describe(),
+
+                    state.variables.debug_view.describe(),
+                    if state.variables.enable_raytracing { "[RT]" } else {""},
+                    if state.variables.enable_oit { "[OIT]" } else {""},
+                    match &state.gpu_culling {
+                        Some(gpu_culling) if state.variables.enable_gpu_culling => gpu_culling.describe(),
+                        _ => String::new(),
+                    },
+                    if state.variables.redraw_on_change { "[ON CHANGE]" } else {""},
+                    state.shadows.describe(),
+                    state.ibl.describe(),
+                    state.voxel_gi.mode().describe(),
+                    state.describe_scene_tabs(),
+                    state.animations.describe(),
+                    state.stereo.as_ref().map_or("", |s| s.mode().describe()),
+                    state.bloom.as_ref().map_or("", |b| b.describe()),
+                    state.fxaa.as_ref().map_or("", |f| f.describe()),
+                    state.msaa.as_ref().map_or(String::new(), |m| m.describe()),
+                    state.weather.describe(),
+                    state.fog.as_ref().map_or("", |f| f.describe()),
+                    state.reference.as_ref().map_or(String::new(), |r| r.describe()),
+                    state.material_atlas.as_ref().map_or(String::new(), |a| a.describe()),
+                    state.recording.as_ref().map_or(String::new(), |r| r.describe()),
+                    state.camera_path.describe(),
+                    state.camera.describe(),
+                    state.projection.describe(),
+                    state.projection.describe_z_planes(),
+                    state.walk.as_ref().map_or("", |w| w.describe()),
+                    if state.quad_viewports().is_some() { "[QUAD]" } else {""},
+                    match state.surface_config.present_mode {
+                        wgpu::PresentMode::Fifo => String::new(),
+                        mode => format!("[{:?}]", mode).to_uppercase(),
+                    },
+                    state.frame_limiter.describe(),
+                    state.scene_clock.describe(),
+                    state.edited_material().map_or(String::new(), |material| {
+                        state.variables.parameter_editor.describe(
+                            &state.shader_parameters,
+                            &state.materials[material].parameters,
+                        )
+                    }),
+                    match &state.variables.benchmark {
+                        Some(benchmark) => benchmark.describe(),
+                        None => state.console.describe(),
+                    },
                 ));
             }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(code),
-                        state: key_state,
-                        ..
-                    },
-                ..
-            } => state.handle_key(event_loop, code, key_state.is_pressed()),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                state.variables.modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                // the console takes the keyboard while it's open
+                let was_console_open = state.console.is_open();
+                if let Some(line) = state.console.handle_key(&event) {
+                    let message = state.run_command(&line);
+                    state.console.set_message(message);
+                } else if !was_console_open
+                    && !state.console.is_open()
+                    && let PhysicalKey::Code(code) = event.physical_key
+                {
+                    state.handle_key(event_loop, code, event.state.is_pressed());
+                }
+            }
             WindowEvent::MouseInput {
                 state: button_state,
                 button,
                 ..
-            } => state.handle_mouse_button(button, button_state.is_pressed()),
+            } => state.handle_mouse_button(event_loop, button, button_state.is_pressed()),
+            WindowEvent::CursorMoved { position, .. } => {
+                state.handle_cursor_moved(position.x, position.y)
+            }
+            WindowEvent::CursorLeft { .. } => state.variables.cursor_position = None,
+            WindowEvent::DroppedFile(path) => {
+                let message = match state.load_dropped_file(&path) {
+                    Ok(message) => message,
+                    Err(e) => format!("{:#}", e),
+                };
+                log::info!("{}", message);
+                state.console.set_message(message);
+            }
             WindowEvent::MouseWheel { delta, .. } => {
                 state.handle_mouse_scroll(&delta);
             }
@@ -1293,7 +5010,29 @@ impl ApplicationHandler<State> for App {
     }
 }
 
+// decoded from the png built into the binary. only some platforms show it: windows and x11 do,
+// while macos takes the icon from the app bundle and wayland from the desktop file
+#[cfg(not(target_arch = "wasm32"))]
+fn window_icon() -> Option<winit::window::Icon> {
+    let image = match image::load_from_memory(include_bytes!("assets/icon.png")) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => {
+            log::warn!("could not decode the window icon: {}", e);
+            return None;
+        }
+    };
+    let (width, height) = image.dimensions();
+    winit::window::Icon::from_rgba(image.into_raw(), width, height)
+        .inspect_err(|e| log::warn!("could not use the window icon: {}", e))
+        .ok()
+}
+
 pub fn run() -> anyhow::Result<()> {
+    run_viewer(DefaultScene)
+}
+
+// runs the renderer with a viewer's additions, until the window is closed
+pub fn run_viewer(viewer: impl Viewer + 'static) -> anyhow::Result<()> {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
@@ -1303,8 +5042,23 @@ pub fn run() -> anyhow::Result<()> {
         console_log::init_with_level(log::Level::Info).unwrap_throw();
     }
 
+    // the web page has no command line
+    #[cfg(not(target_arch = "wasm32"))]
+    let options = {
+        let mut options = options::StartupOptions::parse(std::env::args().skip(1))?;
+        options.config = config::Config::load(
+            options.config_file.as_deref().unwrap_or(CONFIG_FILE),
+            &options.config_overrides,
+        )?;
+        options
+    };
+    #[cfg(target_arch = "wasm32")]
+    let options = options::StartupOptions::default();
+
     let event_loop = EventLoop::with_user_event().build()?;
     let mut app = App::new(
+        Box::new(viewer),
+        options,
         #[cfg(target_arch = "wasm32")]
         &event_loop,
     );
@@ -1312,7 +5066,10 @@ pub fn run() -> anyhow::Result<()> {
     log::info!("yep logging is working");
     event_loop.run_app(&mut app)?;
 
-    Ok(())
+    match app.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -0,0 +1,378 @@
+// translucent volumes showing how far each light reaches: a sphere around every point light and a
+// cone along every spot light, out to where the light falls below LIGHT_CUTOFF. anything outside
+// its volume gets nothing noticeable from a light, so the volumes show which objects a light can
+// affect, and what a light culling pass is allowed to skip.
+// the reach follows the inverse square law, the falloff the shading is meant to have. the
+// shaders don't attenuate yet, so for now the volumes are what culling would have to match
+
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+use crate::{PointLight, SpotLight};
+
+// the brightness below which a light stops counting, about one step of an 8 bit channel
+pub const LIGHT_CUTOFF: f32 = 1.0 / 256.0;
+// cones wider than this are drawn at this angle, since their base grows without bound
+const MAX_CONE_ANGLE: f32 = 80.0 * std::f32::consts::PI / 180.0;
+const SPHERE_SEGMENTS: u32 = 24;
+const SPHERE_RINGS: u32 = 12;
+const CONE_SEGMENTS: u32 = 24;
+// the most opaque a volume gets, at its silhouette
+const OPACITY: f32 = 0.35;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumeVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+// the scale is kept apart from the rest of the transform so the shader can scale normals by
+// its inverse, rather than needing a normal matrix per volume
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumeInstance {
+    transform: [[f32; 4]; 4],
+    scale: [f32; 4],
+    color: [f32; 4],
+}
+
+// a unit shape, indexed
+struct Shape {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+pub struct LightVolumes {
+    pub is_visible: bool,
+    sphere: Shape,
+    cone: Shape,
+    // the spheres first, then the cones
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    sphere_count: u32,
+    cone_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl LightVolumes {
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        depth_compare: wgpu::CompareFunction,
+        sample_count: u32,
+    ) -> Self {
+        let (vertices, indices) = sphere();
+        let sphere = Shape::new(device, "light volume sphere", &vertices, &indices);
+        let (vertices, indices) = cone();
+        let cone = Shape::new(device, "light volume cone", &vertices, &indices);
+
+        let instance_capacity = 16;
+        let instance_buffer = create_instance_buffer(device, instance_capacity);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("light volume pipeline layout"),
+            bind_group_layouts: &[per_frame_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "light_volume.wgsl",
+            include_str!("shaders/light_volume.wgsl"),
+        ));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("light volume pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<VolumeVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<VolumeInstance>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            2 => Float32x4,
+                            3 => Float32x4,
+                            4 => Float32x4,
+                            5 => Float32x4,
+                            6 => Float32x4,
+                            7 => Float32x4,
+                        ],
+                    },
+                ],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // both sides, so a volume the camera is inside is still drawn
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // tested against the scene so objects cut through the volumes, but not written, so
+            // volumes behind other volumes still show through them
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            is_visible: false,
+            sphere,
+            cone,
+            instance_buffer,
+            instance_capacity,
+            sphere_count: 0,
+            cone_count: 0,
+            pipeline,
+        }
+    }
+
+    // rebuilds the volumes from the lights, growing the instance buffer when there are more
+    // lights than it holds
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        uploads: &mut crate::uploads::UploadBelt,
+        point_lights: &[PointLight],
+        spot_lights: &[SpotLight],
+    ) {
+        let instances: Vec<VolumeInstance> = point_lights
+            .iter()
+            .map(point_light_volume)
+            .chain(spot_lights.iter().map(spot_light_volume))
+            .collect();
+        self.sphere_count = point_lights.len() as u32;
+        self.cone_count = spot_lights.len() as u32;
+        if instances.is_empty() {
+            return;
+        }
+
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(device, self.instance_capacity);
+        }
+        uploads.write(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    // draws into a pass which has the scene's depth attached
+    pub fn draw(
+        &self,
+        render_pass: &mut crate::render_stats::CountingRenderPass,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+    ) {
+        if !self.is_visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+
+        let total = self.sphere_count + self.cone_count;
+        for (shape, instances) in [
+            (&self.sphere, 0..self.sphere_count),
+            (&self.cone, self.sphere_count..total),
+        ] {
+            if instances.is_empty() {
+                continue;
+            }
+            render_pass.set_vertex_buffer(0, shape.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(shape.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..shape.index_count, 0, instances);
+        }
+    }
+}
+
+impl Shape {
+    fn new(device: &wgpu::Device, label: &str, vertices: &[VolumeVertex], indices: &[u32]) -> Self {
+        let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} vertex buffer", label)),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        );
+        let index_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} index buffer", label)),
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        );
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+}
+
+// the distance at which a light of this brightness falls to LIGHT_CUTOFF, from
+// brightness / distance^2 = LIGHT_CUTOFF
+pub fn effective_radius(brightness: f32) -> f32 {
+    (brightness.max(0.0) / LIGHT_CUTOFF).sqrt()
+}
+
+fn point_light_volume(light: &PointLight) -> VolumeInstance {
+    let brightness = max_component(light.color) * light.intensity;
+    let radius = effective_radius(brightness);
+    let transform = Matrix4::from_translation(light.transform.translation());
+    volume_instance(transform, Vector3::new(radius, radius, radius), light.color)
+}
+
+// the cone's apex is at the light and its base covers the outer angle at the light's reach
+fn spot_light_volume(light: &SpotLight) -> VolumeInstance {
+    let range = effective_radius(max_component(light.color));
+    let base_radius = light.outer_angular_radius.clamp(0.0, MAX_CONE_ANGLE).tan() * range;
+    let transform = Matrix4::from_translation(light.transform.translation())
+        * Matrix4::from(light.transform.rotation());
+    volume_instance(
+        transform,
+        Vector3::new(base_radius, base_radius, range),
+        light.color,
+    )
+}
+
+// tinted like the light, at full brightness so dim lights' volumes can still be seen
+fn volume_instance(
+    transform: Matrix4<f32>,
+    scale: Vector3<f32>,
+    color: [f32; 3],
+) -> VolumeInstance {
+    let brightest = max_component(color).max(f32::EPSILON);
+    VolumeInstance {
+        transform: transform.into(),
+        scale: [scale.x, scale.y, scale.z, 0.0],
+        color: [
+            color[0] / brightest,
+            color[1] / brightest,
+            color[2] / brightest,
+            OPACITY,
+        ],
+    }
+}
+
+fn max_component(color: [f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2])
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("light volume instance buffer"),
+        size: (capacity * std::mem::size_of::<VolumeInstance>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// a unit sphere around the origin
+fn sphere() -> (Vec<VolumeVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for ring in 0..=SPHERE_RINGS {
+        let polar = std::f32::consts::PI * ring as f32 / SPHERE_RINGS as f32;
+        for segment in 0..=SPHERE_SEGMENTS {
+            let azimuth = std::f32::consts::TAU * segment as f32 / SPHERE_SEGMENTS as f32;
+            let position = [
+                polar.sin() * azimuth.cos(),
+                polar.cos(),
+                polar.sin() * azimuth.sin(),
+            ];
+            vertices.push(VolumeVertex {
+                position,
+                normal: position,
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    let row = SPHERE_SEGMENTS + 1;
+    for ring in 0..SPHERE_RINGS {
+        for segment in 0..SPHERE_SEGMENTS {
+            let a = ring * row + segment;
+            let b = a + row;
+            indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+    (vertices, indices)
+}
+
+// a unit cone with its apex at the origin and its base, of radius 1, at z = -1, so it opens
+// along a light's forward axis
+fn cone() -> (Vec<VolumeVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    // the sides get an apex vertex per segment, so each has the normal of its own slope
+    for segment in 0..CONE_SEGMENTS {
+        let azimuth = std::f32::consts::TAU * segment as f32 / CONE_SEGMENTS as f32;
+        let next = std::f32::consts::TAU * (segment + 1) as f32 / CONE_SEGMENTS as f32;
+        let middle = (azimuth + next) / 2.0;
+        // the slope is 45 degrees for a unit cone
+        let normal = |angle: f32| {
+            let normal = Vector3::new(angle.cos(), angle.sin(), 1.0).normalize();
+            [normal.x, normal.y, normal.z]
+        };
+
+        let first = vertices.len() as u32;
+        vertices.push(VolumeVertex {
+            position: [0.0, 0.0, 0.0],
+            normal: normal(middle),
+        });
+        vertices.push(VolumeVertex {
+            position: [azimuth.cos(), azimuth.sin(), -1.0],
+            normal: normal(azimuth),
+        });
+        vertices.push(VolumeVertex {
+            position: [next.cos(), next.sin(), -1.0],
+            normal: normal(next),
+        });
+        indices.extend_from_slice(&[first, first + 1, first + 2]);
+    }
+
+    let center = vertices.len() as u32;
+    vertices.push(VolumeVertex {
+        position: [0.0, 0.0, -1.0],
+        normal: [0.0, 0.0, -1.0],
+    });
+    for segment in 0..=CONE_SEGMENTS {
+        let azimuth = std::f32::consts::TAU * segment as f32 / CONE_SEGMENTS as f32;
+        vertices.push(VolumeVertex {
+            position: [azimuth.cos(), azimuth.sin(), -1.0],
+            normal: [0.0, 0.0, -1.0],
+        });
+    }
+    for segment in 0..CONE_SEGMENTS {
+        indices.extend_from_slice(&[center, center + segment + 2, center + segment + 1]);
+    }
+    (vertices, indices)
+}
@@ -0,0 +1,76 @@
+// watches a directory which blender (or any other exporter) writes models into, so that an
+// export shows up in the running viewer without restarting it. the exporter writes its files
+// first and then the handshake file, listing the files it just wrote one per line relative to
+// the directory, eg from blender's python console after an obj export:
+//
+//     open("exports/export_done.txt", "w").write("scene.obj\n")
+//
+// waiting for the handshake instead of watching the models themselves means a half written
+// export is never read. the directory is polled, so there is nothing to install. it's watched
+// when the config names it, eg
+//
+//     cargo run -- --set live_reload.directory exports
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub const HANDSHAKE_FILE: &str = "export_done.txt";
+
+pub struct ExportWatcher {
+    directory: PathBuf,
+    // the handshake's modification time when it was last read, None before the first read so
+    // that an export from before the viewer started is loaded too
+    last_handshake: Option<SystemTime>,
+    last_check: Option<Instant>,
+}
+
+impl ExportWatcher {
+    const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn new(directory: &str) -> Self {
+        log::info!(
+            "watching {} for exports",
+            Path::new(directory).join(HANDSHAKE_FILE).display()
+        );
+        Self {
+            directory: PathBuf::from(directory),
+            last_handshake: None,
+            last_check: None,
+        }
+    }
+
+    // the files of a new export, or nothing if the handshake hasn't changed since last time
+    pub fn poll(&mut self) -> Vec<String> {
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < Self::CHECK_INTERVAL)
+        {
+            return Vec::new();
+        }
+        self.last_check = Some(Instant::now());
+
+        let handshake = self.directory.join(HANDSHAKE_FILE);
+        let Ok(modified) = std::fs::metadata(&handshake).and_then(|m| m.modified()) else {
+            return Vec::new();
+        };
+        if self.last_handshake == Some(modified) {
+            return Vec::new();
+        }
+        self.last_handshake = Some(modified);
+
+        match std::fs::read_to_string(&handshake) {
+            Ok(contents) => contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| self.directory.join(line).to_string_lossy().into_owned())
+                .collect(),
+            Err(e) => {
+                log::warn!("could not read {}: {}", handshake.display(), e);
+                Vec::new()
+            }
+        }
+    }
+}
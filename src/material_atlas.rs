@@ -0,0 +1,371 @@
+// material batching through texture arrays. the diffuse and normal maps of the most common size
+// are copied into the layers of two D2Array textures, and every batched material's uniform,
+// along with the layers its maps are in, gets a slot in one buffer. all of those materials
+// then share a single bind group, and a draw only picks its material's slot with a dynamic
+// offset, the same way objects pick their transforms. materials with maps of another size or
+// format, or with any maps besides diffuse and normal, keep drawing with their own bind group.
+//
+// the arrays only hold each map's base level and are sampled with one repeating sampler. the
+// batched draws all bind the shader parameters of the first batched material, so materials
+// whose parameter values differ from its values keep their own bind group too
+
+use crate::{model, shader_params, texture};
+
+// the block of shader.wgsl which is replaced by material_atlas.wgsl
+const HOOK_START: &str = "// @hook material";
+const HOOK_END: &str = "// @end hook";
+
+// builds the texture array variant of a shader which has a `@hook material` block
+pub fn shader_source(base: &str) -> Option<String> {
+    let start = base.find(HOOK_START)?;
+    let end = base[start..].find(HOOK_END)? + start + HOOK_END.len();
+
+    Some(format!(
+        "{}{}{}",
+        &base[..start],
+        include_str!("shaders/material_atlas.wgsl"),
+        &base[end..]
+    ))
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AtlasMaterialUniform {
+    material: model::MaterialUniform,
+    // the diffuse and normal maps' layers, then padding
+    layers: [i32; 4],
+}
+
+pub struct MaterialAtlas {
+    // each material's offset into the uniform buffer, None when it isn't batched
+    offsets: Vec<Option<u32>>,
+    layer_count: usize,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl MaterialAtlas {
+    pub const ENTRY_SIZE: wgpu::BufferSize =
+        wgpu::BufferSize::new(std::mem::size_of::<AtlasMaterialUniform>() as u64).unwrap();
+    // the formats texture::Texture::from_image gives color and linear maps
+    const DIFFUSE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    // the per pass layout with texture arrays for the diffuse and normal maps and a dynamic
+    // offset into the material buffer
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let mut entries = vec![
+            texture_entry(0, wgpu::TextureViewDimension::D2Array),
+            sampler_entry(1),
+            texture_entry(2, wgpu::TextureViewDimension::D2Array),
+            sampler_entry(3),
+            // the batched materials, one slot each
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(Self::ENTRY_SIZE),
+                },
+                count: None,
+            },
+            // the reflected shader parameters
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        // the specular, shininess, alpha, emissive, roughness, metallic and height maps, which
+        // batched materials don't have
+        for binding in (6..20).step_by(2) {
+            entries.push(texture_entry(binding, wgpu::TextureViewDimension::D2));
+            entries.push(sampler_entry(binding + 1));
+        }
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("material atlas bind group layout"),
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        materials: &[model::Material],
+    ) -> Self {
+        let fits = |texture: &wgpu::Texture, format| {
+            texture.format() == format
+                && texture.usage().contains(wgpu::TextureUsages::COPY_SRC)
+                && texture.depth_or_array_layers() == 1
+        };
+
+        // the layers all have to be one size, so go with the size most maps have
+        let mut sizes: Vec<((u32, u32), usize)> = Vec::new();
+        for material in materials.iter().filter(|m| !m.uniform.has_other_textures()) {
+            for (_, texture, format) in Self::maps(material).into_iter().filter(|m| m.0) {
+                if !fits(texture, format) {
+                    continue;
+                }
+                let size = (texture.width(), texture.height());
+                match sizes.iter_mut().find(|(s, _)| *s == size) {
+                    Some((_, count)) => *count += 1,
+                    None => sizes.push((size, 1)),
+                }
+            }
+        }
+        let (width, height) = sizes
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(size, _)| *size)
+            .unwrap_or((1, 1));
+
+        // the textures going into each array, which materials sharing a map share a layer of
+        let max_layers = device.limits().max_texture_array_layers as usize;
+        let mut layers: [Vec<wgpu::Texture>; 2] = [Vec::new(), Vec::new()];
+        let mut material_layers = Vec::with_capacity(materials.len());
+        // the parameter values of the first batched material, which the rest have to match
+        let mut parameters: Option<&[u32]> = None;
+        for material in materials {
+            let maps = Self::maps(material);
+            let is_batchable = !material.uniform.has_other_textures()
+                && maps.iter().filter(|m| m.0).all(|(_, texture, format)| {
+                    fits(texture, *format) && (texture.width(), texture.height()) == (width, height)
+                })
+                && *parameters.get_or_insert(material.parameters.values())
+                    == material.parameters.values();
+            if !is_batchable {
+                material_layers.push(None);
+                continue;
+            }
+
+            let mut material_layer = [0; 2];
+            let mut has_room = true;
+            for ((array, (is_present, texture, _)), layer) in
+                layers.iter_mut().zip(maps).zip(&mut material_layer)
+            {
+                if !is_present {
+                    continue;
+                }
+                *layer = match array.iter().position(|t| t == texture) {
+                    Some(index) => index,
+                    None if array.len() < max_layers => {
+                        array.push(texture.clone());
+                        array.len() - 1
+                    }
+                    None => {
+                        has_room = false;
+                        break;
+                    }
+                } as i32;
+            }
+            material_layers.push(has_room.then_some(material_layer));
+        }
+
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("material atlas command encoder"),
+        });
+        let [diffuse_array, normal_array] = [
+            (
+                &layers[0],
+                Self::DIFFUSE_FORMAT,
+                "material atlas diffuse array",
+            ),
+            (
+                &layers[1],
+                Self::NORMAL_FORMAT,
+                "material atlas normal array",
+            ),
+        ]
+        .map(|(textures, format, label)| {
+            let array = texture::Texture::builder(label, width, height)
+                .format(format)
+                .array_layers(textures.len().max(1) as u32)
+                .sampler(texture::Texture::material_sampler_descriptor(
+                    wgpu::AddressMode::Repeat,
+                ))
+                .build(device);
+            for (layer, texture) in textures.iter().enumerate() {
+                command_encoder.copy_texture_to_texture(
+                    texture.as_image_copy(),
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &array.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+            array
+        });
+        queue.submit(std::iter::once(command_encoder.finish()));
+
+        // material i's slot is at i * stride, which keeps the offsets aligned for binding
+        let stride = (Self::ENTRY_SIZE.get() as u32)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let mut contents = vec![0u8; stride as usize * materials.len().max(1)];
+        let mut offsets = Vec::with_capacity(materials.len());
+        for (i, (material, layers)) in materials.iter().zip(&material_layers).enumerate() {
+            let Some([diffuse_layer, normal_layer]) = *layers else {
+                offsets.push(None);
+                continue;
+            };
+            let offset = i as u32 * stride;
+            let entry = AtlasMaterialUniform {
+                material: material.uniform,
+                layers: [diffuse_layer, normal_layer, 0, 0],
+            };
+            contents[offset as usize..][..Self::ENTRY_SIZE.get() as usize]
+                .copy_from_slice(bytemuck::bytes_of(&entry));
+            offsets.push(Some(offset));
+        }
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("material atlas uniform buffer"),
+                contents: &contents,
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+
+        let parameter_buffer = match materials
+            .iter()
+            .zip(&offsets)
+            .find(|(_, offset)| offset.is_some())
+        {
+            Some((material, _)) => material.parameters.buffer.clone(),
+            None => shader_params::ParameterBlock::new(device, "material atlas").buffer,
+        };
+        // batched materials have none of the other maps, so these are never sampled
+        let unused = texture::Texture::placeholder(
+            device,
+            queue,
+            texture::Placeholder::White,
+            "material atlas unused map",
+        );
+
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&diffuse_array.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&diffuse_array.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&normal_array.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&normal_array.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &uniform_buffer,
+                    offset: 0,
+                    size: Some(Self::ENTRY_SIZE),
+                }),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: parameter_buffer.as_entire_binding(),
+            },
+        ];
+        for binding in (6..20).step_by(2) {
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&unused.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&unused.sampler),
+            });
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("material atlas bind group"),
+            layout,
+            entries: &entries,
+        });
+
+        let atlas = Self {
+            offsets,
+            layer_count: layers[0].len() + layers[1].len(),
+            bind_group,
+        };
+        log::info!(
+            "material atlas: {} of {} materials batched, {} {}x{} layers",
+            atlas.batched_count(),
+            materials.len(),
+            atlas.layer_count,
+            width,
+            height
+        );
+        atlas
+    }
+
+    // whether the material has each map, the map's texture and the format it needs in the atlas
+    fn maps(material: &model::Material) -> [(bool, &wgpu::Texture, wgpu::TextureFormat); 2] {
+        [
+            (
+                material.uniform.has_diffuse_texture(),
+                &material.diffuse_texture.texture,
+                Self::DIFFUSE_FORMAT,
+            ),
+            (
+                material.uniform.has_normal_texture(),
+                &material.normal_texture.texture,
+                Self::NORMAL_FORMAT,
+            ),
+        ]
+    }
+
+    // the dynamic offset to draw the material with, or None if it isn't batched
+    pub fn offset(&self, material: usize) -> Option<u32> {
+        self.offsets.get(material).copied().flatten()
+    }
+
+    fn batched_count(&self) -> usize {
+        self.offsets.iter().filter(|o| o.is_some()).count()
+    }
+
+    // eg [ATLAS 12/15], for the window title
+    pub fn describe(&self) -> String {
+        format!("[ATLAS {}/{}]", self.batched_count(), self.offsets.len())
+    }
+}
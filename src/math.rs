@@ -0,0 +1,490 @@
+use std::cell::Cell;
+
+use cgmath::{
+    ElementWise, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rotation, SquareMatrix, Vector3,
+    Zero,
+};
+
+// a translation/rotation/scale transform, applied in the order scale -> rotate -> translate
+// the composed matrix is cached and only rebuilt after one of the components changes
+// scale is per axis; composition and inversion are exact only while the scale is uniform
+#[derive(Debug, Clone)]
+pub struct Transform {
+    translation: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    scale: Vector3<f32>,
+    cached_matrix: Cell<Option<Matrix4<f32>>>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self::new(
+            Vector3::zero(),
+            Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    pub fn new(translation: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32>) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+            cached_matrix: Cell::new(None),
+        }
+    }
+
+    pub fn from_translation<V: Into<Vector3<f32>>>(translation: V) -> Self {
+        let mut transform = Self::identity();
+        transform.translation = translation.into();
+        transform
+    }
+
+    pub fn from_rotation(rotation: Quaternion<f32>) -> Self {
+        let mut transform = Self::identity();
+        transform.rotation = rotation;
+        transform
+    }
+
+    // places the transform at `eye` with its local -z axis pointing in `direction`
+    // (the same convention as a right handed camera)
+    pub fn look_to(eye: Point3<f32>, direction: Vector3<f32>, up: Vector3<f32>) -> Self {
+        let forward = direction.normalize();
+        let right = forward.cross(up).normalize();
+        let up = right.cross(forward);
+
+        let rotation = Quaternion::from(Matrix3::from_cols(right, up, -forward)).normalize();
+        Self::new(
+            eye - Point3::new(0.0, 0.0, 0.0),
+            rotation,
+            Vector3::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    // lossy conversion from an arbitrary affine matrix; any shear is dropped with a warning
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+        match decompose(matrix) {
+            Some(decomposed) => {
+                if decomposed.has_shear() {
+                    log::warn!(
+                        "discarding shear {:?} while converting matrix to a transform",
+                        decomposed.shear
+                    );
+                }
+                decomposed.transform
+            }
+            None => {
+                log::warn!("matrix is not affine, falling back to the identity transform");
+                Self::identity()
+            }
+        }
+    }
+
+    pub fn look_at(eye: Point3<f32>, target: Point3<f32>, up: Vector3<f32>) -> Self {
+        Self::look_to(eye, target - eye, up)
+    }
+
+    pub fn translation(&self) -> Vector3<f32> {
+        self.translation
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> Vector3<f32> {
+        self.scale
+    }
+
+    pub fn is_uniformly_scaled(&self) -> bool {
+        const EPSILON: f32 = 1e-6;
+        (self.scale.x - self.scale.y).abs() < EPSILON
+            && (self.scale.x - self.scale.z).abs() < EPSILON
+    }
+
+    pub fn set_translation<V: Into<Vector3<f32>>>(&mut self, translation: V) {
+        self.translation = translation.into();
+        self.cached_matrix.set(None);
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.rotation = rotation;
+        self.cached_matrix.set(None);
+    }
+
+    pub fn set_scale<V: Into<Vector3<f32>>>(&mut self, scale: V) {
+        self.scale = scale.into();
+        self.cached_matrix.set(None);
+    }
+
+    pub fn set_uniform_scale(&mut self, scale: f32) {
+        self.set_scale(Vector3::new(scale, scale, scale));
+    }
+
+    pub fn translate<V: Into<Vector3<f32>>>(&mut self, offset: V) {
+        self.set_translation(self.translation + offset.into());
+    }
+
+    // applies `rotation` on top of the current orientation (in world space)
+    pub fn rotate(&mut self, rotation: Quaternion<f32>) {
+        self.set_rotation((rotation * self.rotation).normalize());
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        if let Some(matrix) = self.cached_matrix.get() {
+            return matrix;
+        }
+
+        let matrix = Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
+        self.cached_matrix.set(Some(matrix));
+        matrix
+    }
+
+    // inverse transpose of the upper 3x3 of the matrix, used to transform normals
+    pub fn normal_matrix(&self) -> Matrix3<f32> {
+        let inverse_scale = Vector3::new(1.0, 1.0, 1.0).div_element_wise(self.scale);
+        // (R * S)^-T = R * S^-1 since R is orthonormal and S is diagonal
+        Matrix3::from(self.rotation) * Matrix3::from_diagonal(inverse_scale)
+    }
+
+    // exact for any scale, unlike `inverse` which has to stay representable as TRS
+    pub fn inverse_matrix(&self) -> Matrix4<f32> {
+        self.matrix().invert().unwrap_or(Matrix4::identity())
+    }
+
+    pub fn inverse(&self) -> Self {
+        let inverse_rotation = self.rotation.invert();
+        let inverse_scale = Vector3::new(1.0, 1.0, 1.0).div_element_wise(self.scale);
+        Self::new(
+            -inverse_rotation
+                .rotate_vector(self.translation)
+                .mul_element_wise(inverse_scale),
+            inverse_rotation,
+            inverse_scale,
+        )
+    }
+
+    // returns the transform equivalent to applying `child` first and then `self`
+    pub fn then(&self, child: &Transform) -> Self {
+        Self::new(
+            self.transform_point(child.translation),
+            (self.rotation * child.rotation).normalize(),
+            self.scale.mul_element_wise(child.scale),
+        )
+    }
+
+    pub fn transform_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.rotation
+            .rotate_vector(point.mul_element_wise(self.scale))
+            + self.translation
+    }
+
+    pub fn transform_vector(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        self.rotation
+            .rotate_vector(vector.mul_element_wise(self.scale))
+    }
+
+    pub fn transform_normal(&self, normal: Vector3<f32>) -> Vector3<f32> {
+        (self.normal_matrix() * normal).normalize()
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(-Vector3::unit_z())
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(Vector3::unit_x())
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(Vector3::unit_y())
+    }
+}
+
+impl std::ops::Mul for &Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: &Transform) -> Transform {
+        self.then(rhs)
+    }
+}
+
+// the result of splitting an affine matrix into translation/rotation/scale plus whatever shear
+// is left over. `transform` reproduces the matrix exactly only when `shear` is zero
+#[derive(Debug, Clone)]
+pub struct Decomposition {
+    pub transform: Transform,
+    // xy, xz and yz shear factors, relative to the scale of the sheared axis
+    pub shear: Vector3<f32>,
+}
+
+impl Decomposition {
+    const SHEAR_EPSILON: f32 = 1e-4;
+
+    pub fn has_shear(&self) -> bool {
+        self.shear.x.abs() > Self::SHEAR_EPSILON
+            || self.shear.y.abs() > Self::SHEAR_EPSILON
+            || self.shear.z.abs() > Self::SHEAR_EPSILON
+    }
+}
+
+// decomposes an affine matrix (as written by DCC tools and scene files) into TRS + shear
+// returns None for projective or degenerate (zero scale) matrices
+pub fn decompose(matrix: Matrix4<f32>) -> Option<Decomposition> {
+    const EPSILON: f32 = 1e-6;
+
+    let is_affine = matrix.x.w.abs() < EPSILON
+        && matrix.y.w.abs() < EPSILON
+        && matrix.z.w.abs() < EPSILON
+        && (matrix.w.w - 1.0).abs() < EPSILON;
+    if !is_affine {
+        return None;
+    }
+
+    let translation = matrix.w.truncate();
+    let (column_x, column_y, column_z) = (
+        matrix.x.truncate(),
+        matrix.y.truncate(),
+        matrix.z.truncate(),
+    );
+
+    // gram schmidt the basis columns; the projections removed along the way are the shear
+    let mut scale_x = column_x.magnitude();
+    if scale_x < EPSILON {
+        return None;
+    }
+    let mut axis_x = column_x / scale_x;
+
+    let shear_xy = axis_x.dot(column_y);
+    let column_y = column_y - axis_x * shear_xy;
+    let scale_y = column_y.magnitude();
+    if scale_y < EPSILON {
+        return None;
+    }
+    let axis_y = column_y / scale_y;
+
+    let shear_xz = axis_x.dot(column_z);
+    let shear_yz = axis_y.dot(column_z);
+    let column_z = column_z - axis_x * shear_xz - axis_y * shear_yz;
+    let scale_z = column_z.magnitude();
+    if scale_z < EPSILON {
+        return None;
+    }
+    let axis_z = column_z / scale_z;
+
+    // a mirrored basis can't be expressed as a rotation, so fold the flip into the x scale
+    if axis_x.dot(axis_y.cross(axis_z)) < 0.0 {
+        scale_x = -scale_x;
+        axis_x = -axis_x;
+    }
+
+    let rotation = Quaternion::from(Matrix3::from_cols(axis_x, axis_y, axis_z)).normalize();
+
+    Some(Decomposition {
+        transform: Transform::new(
+            translation,
+            rotation,
+            Vector3::new(scale_x, scale_y, scale_z),
+        ),
+        shear: Vector3::new(shear_xy / scale_y, shear_xz / scale_z, shear_yz / scale_z),
+    })
+}
+
+// a half line from `origin` along `direction`, which is kept normalized
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // the ray in the space of `transform`'s children, eg from world into model space.
+    // distances along the result are only comparable to the original's under uniform scale
+    pub fn into_space(&self, transform: &Transform) -> Self {
+        let inverse = transform.inverse_matrix();
+        Self::new(
+            Point3::from_homogeneous(inverse * self.origin.to_homogeneous()),
+            (inverse * self.direction.extend(0.0)).truncate(),
+        )
+    }
+
+    // moller-trumbore, hitting both sides of the triangle. returns the distance to the hit
+    pub fn intersect_triangle(
+        &self,
+        a: Point3<f32>,
+        b: Point3<f32>,
+        c: Point3<f32>,
+    ) -> Option<f32> {
+        const EPSILON: f32 = 1e-7;
+
+        let edge_ab = b - a;
+        let edge_ac = c - a;
+        let p = self.direction.cross(edge_ac);
+        let determinant = edge_ab.dot(p);
+        if determinant.abs() < EPSILON {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = self.origin - a;
+        let u = to_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = to_origin.cross(edge_ab);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge_ac.dot(q) * inverse_determinant;
+        (distance > EPSILON).then_some(distance)
+    }
+
+    // the slab test. returns the distance to where the ray enters the box, which is 0 when it
+    // starts inside it
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            // a ray parallel to the slab gives infinities here, which miss unless it starts
+            // between the slab's sides
+            let inverse_direction = 1.0 / self.direction[axis];
+            let to_min = (aabb.min[axis] - self.origin[axis]) * inverse_direction;
+            let to_max = (aabb.max[axis] - self.origin[axis]) * inverse_direction;
+            near = near.max(to_min.min(to_max));
+            far = far.min(to_min.max(to_max));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+// an axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    // the smallest box around the points, or None when there are none
+    pub fn from_points(points: impl IntoIterator<Item = Vector3<f32>>) -> Option<Self> {
+        points.into_iter().fold(None, |aabb, point| {
+            Some(match aabb {
+                None => Self {
+                    min: point,
+                    max: point,
+                },
+                Some(Self { min, max }) => Self {
+                    min: Vector3::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z)),
+                    max: Vector3::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z)),
+                },
+            })
+        })
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    // numbered by which axes are at their max, x in bit 0 up to z in bit 2
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        std::array::from_fn(|i| {
+            Vector3::new(
+                if i & 1 == 1 { self.max.x } else { self.min.x },
+                if i & 2 == 2 { self.max.y } else { self.min.y },
+                if i & 4 == 4 { self.max.z } else { self.min.z },
+            )
+        })
+    }
+
+    // the box around this one after it has been transformed, which is looser than the original
+    // when the transform rotates it
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        Self::from_points(self.corners().map(|c| transform.transform_point(c))).unwrap_or(*self)
+    }
+}
+
+// the planes of a view projection's clip space, each facing inwards, for testing whether
+// bounds can be seen. a reversed z projection's plane at infinity is left out
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    // the normal in xyz and the distance in w, so a point is inside when dot(plane, p, 1) >= 0
+    planes: Vec<cgmath::Vector4<f32>>,
+}
+
+impl Frustum {
+    // gribb and hartmann's planes from the rows of the matrix, with depth from 0 to w
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let row = |i: usize| {
+            cgmath::Vector4::new(
+                view_projection.x[i],
+                view_projection.y[i],
+                view_projection.z[i],
+                view_projection.w[i],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z]
+            .into_iter()
+            .filter_map(|plane| {
+                let length = plane.truncate().magnitude();
+                (length > 1e-6).then(|| plane / length)
+            })
+            .collect();
+        Self { planes }
+    }
+
+    // false only when the sphere is entirely outside one of the planes
+    pub fn intersects_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+
+    // false only when the box is entirely outside one of the planes, tested at the corner
+    // furthest along each plane's normal
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let corner = Vector3::new(
+                if normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
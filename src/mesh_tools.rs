@@ -0,0 +1,345 @@
+// operations on a mesh's vertices and indices, before they're turned into a Mesh
+
+use std::collections::HashMap;
+
+use cgmath::{Angle, Deg, InnerSpace, Vector3};
+
+use crate::model;
+
+// the sum of the squared distances to a set of planes, weighted by the area of the triangles
+// they came from (garland and heckbert). kept as the upper half of the symmetric 4x4 matrix
+// made from each plane's (a, b, c, d)
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_triangle(positions: [Vector3<f64>; 3]) -> Self {
+        let [p0, p1, p2] = positions;
+        let cross = (p1 - p0).cross(p2 - p0);
+        let length = cross.magnitude();
+        if length <= f64::EPSILON {
+            return Self::default();
+        }
+        let normal = cross / length;
+        let [a, b, c] = [normal.x, normal.y, normal.z];
+        let d = -normal.dot(p0);
+        let area = length / 2.0;
+        Self([
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ])
+        .scaled(area)
+    }
+
+    fn scaled(self, scale: f64) -> Self {
+        Self(self.0.map(|q| q * scale))
+    }
+
+    fn error(&self, position: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let Vector3 { x, y, z } = position;
+        q[0] * x * x
+            + q[4] * y * y
+            + q[7] * z * z
+            + 2.0 * (q[1] * x * y + q[2] * x * z + q[5] * y * z)
+            + 2.0 * (q[3] * x + q[6] * y + q[8] * z)
+            + q[9]
+    }
+}
+
+impl std::ops::Add for Quadric {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl std::ops::AddAssign for Quadric {
+    fn add_assign(&mut self, other: Self) {
+        for (q, o) in self.0.iter_mut().zip(other.0) {
+            *q += o;
+        }
+    }
+}
+
+// the mesh with about target_ratio of its triangles, eg 0.25 keeps a quarter. edges are
+// collapsed cheapest first by the quadric error metric, each moving one vertex onto the other,
+// so the vertices which are left keep their own uvs and normals. a vertex on a border, which
+// includes the uv seams where a loader split the vertices, is never moved, so nothing tears
+// open, and collapses which would flip a triangle over are skipped. meshes with a lot of seams
+// may stop short of the target for that. the vertices are compacted, keeping their order
+pub fn simplify(
+    verts: &[model::ModelVertex],
+    inds: &[u32],
+    target_ratio: f32,
+) -> (Vec<model::ModelVertex>, Vec<u32>) {
+    let mut triangles: Vec<[u32; 3]> = inds
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|&[a, b, c]| a != b && b != c && a != c)
+        .collect();
+    let target = (triangles.len() as f32 * target_ratio.clamp(0.0, 1.0)).ceil() as usize;
+    let position = |i: u32| Vector3::from(verts[i as usize].position.map(f64::from));
+
+    let mut quadrics = vec![Quadric::default(); verts.len()];
+    for &triangle in &triangles {
+        let quadric = Quadric::from_triangle(triangle.map(position));
+        for i in triangle {
+            quadrics[i as usize] += quadric;
+        }
+    }
+
+    // an edge of only one triangle is on the border, and so are edges of more than two, where
+    // the surface isn't a surface
+    let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in &triangles {
+        for (a, b) in edges(triangle) {
+            *edge_counts.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    let mut is_locked = vec![false; verts.len()];
+    for (&(a, b), &count) in &edge_counts {
+        if count != 2 {
+            is_locked[a as usize] = true;
+            is_locked[b as usize] = true;
+        }
+    }
+
+    // in passes, each collapsing the cheapest edges whose surroundings no earlier collapse in
+    // the pass has touched, until there are few enough triangles or nothing more can go
+    let mut remap: Vec<u32> = (0..verts.len() as u32).collect();
+    while triangles.len() > target {
+        let mut vertex_triangles = vec![Vec::new(); verts.len()];
+        for (t, triangle) in triangles.iter().enumerate() {
+            for &i in triangle {
+                vertex_triangles[i as usize].push(t);
+            }
+        }
+
+        // (cost, from, to), for moving from onto to
+        let mut collapses: Vec<(f64, u32, u32)> = triangles
+            .iter()
+            .flat_map(edges)
+            .flat_map(|(a, b)| [(a, b), (b, a)])
+            .filter(|&(from, _)| !is_locked[from as usize])
+            .map(|(from, to)| {
+                let quadric = quadrics[from as usize] + quadrics[to as usize];
+                (quadric.error(position(to)), from, to)
+            })
+            .collect();
+        collapses.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut is_touched = vec![false; verts.len()];
+        let mut remaining = triangles.len();
+        for (_, from, to) in collapses {
+            if remaining <= target {
+                break;
+            }
+            if is_touched[from as usize] || is_touched[to as usize] {
+                continue;
+            }
+
+            let around = &vertex_triangles[from as usize];
+            let flips = around.iter().any(|&t| {
+                let triangle = triangles[t];
+                if triangle.contains(&to) {
+                    return false;
+                }
+                let before = triangle.map(position);
+                let after = triangle.map(|i| if i == from { position(to) } else { position(i) });
+                normal(before).dot(normal(after)) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            remaining -= around
+                .iter()
+                .filter(|&&t| triangles[t].contains(&to))
+                .count();
+            remap[from as usize] = to;
+            let merged = quadrics[from as usize];
+            quadrics[to as usize] += merged;
+            for &t in around {
+                for i in triangles[t] {
+                    is_touched[i as usize] = true;
+                }
+            }
+        }
+
+        let count = triangles.len();
+        triangles = triangles
+            .into_iter()
+            .map(|triangle| triangle.map(|i| remap[i as usize]))
+            .filter(|&[a, b, c]| a != b && b != c && a != c)
+            .collect();
+        if triangles.len() == count {
+            break;
+        }
+    }
+
+    // the vertices still used, in their old order
+    let mut new_index = vec![u32::MAX; verts.len()];
+    for &i in triangles.iter().flatten() {
+        new_index[i as usize] = 0;
+    }
+    let mut new_verts = Vec::new();
+    for (i, vertex) in verts.iter().enumerate() {
+        if new_index[i] == 0 {
+            new_index[i] = new_verts.len() as u32;
+            new_verts.push(*vertex);
+        }
+    }
+    let new_inds = triangles
+        .iter()
+        .flatten()
+        .map(|&i| new_index[i as usize])
+        .collect();
+    (new_verts, new_inds)
+}
+
+// every mesh of the model simplified, see simplify
+pub fn simplify_model(
+    device: &wgpu::Device,
+    model: model::Model,
+    target_ratio: f32,
+) -> model::Model {
+    map_meshes(device, model, |mesh| {
+        let (verts, inds) = simplify(&mesh.verts, &mesh.indices, target_ratio);
+        log::info!(
+            "simplified {} from {} to {} triangles",
+            mesh.name,
+            mesh.indices.len() / 3,
+            inds.len() / 3
+        );
+        (verts, inds)
+    })
+}
+
+// between faces meeting at a sharper angle than this, recomputed normals are creased when no
+// other angle is asked for
+pub const CREASE_ANGLE: Deg<f32> = Deg(60.0);
+
+// new normals from the triangles around each vertex, weighted by their areas. triangles meet
+// smoothly across an edge unless their faces are further apart than the angle threshold, in
+// which case the vertices along it are split so each side gets its own normal, eg 180 degrees
+// smooths everything and 0 gives every face its own. vertices at the same position are treated
+// as one, so uv seams don't show up as creases
+pub fn recompute_normals(
+    verts: &[model::ModelVertex],
+    inds: &[u32],
+    angle_threshold: Deg<f32>,
+) -> (Vec<model::ModelVertex>, Vec<u32>) {
+    let triangles: Vec<[u32; 3]> = inds.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+    let position = |i: u32| Vector3::from(verts[i as usize].position);
+    // twice the area long, which weights them by area when they're added up
+    let face_normals: Vec<Vector3<f32>> = triangles
+        .iter()
+        .map(|&[a, b, c]| (position(b) - position(a)).cross(position(c) - position(a)))
+        .collect();
+    let min_cos = angle_threshold.cos();
+
+    // the triangles around each position
+    let mut position_triangles: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (t, triangle) in triangles.iter().enumerate() {
+        for &i in triangle {
+            let key = verts[i as usize].position.map(f32::to_bits);
+            position_triangles.entry(key).or_default().push(t);
+        }
+    }
+
+    // every corner gets the normals of the faces around it which are close enough to its own
+    // face, and corners of one vertex which end up different become separate vertices
+    let mut new_verts = Vec::with_capacity(verts.len());
+    let mut new_index: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    let mut new_inds = Vec::with_capacity(inds.len());
+    for (t, triangle) in triangles.iter().enumerate() {
+        let face = face_normals[t].normalize();
+        for &i in triangle {
+            let vertex = verts[i as usize];
+            let sum: Vector3<f32> = position_triangles[&vertex.position.map(f32::to_bits)]
+                .iter()
+                .map(|&other| face_normals[other])
+                .filter(|other| other.normalize().dot(face) >= min_cos)
+                .sum();
+            let normal = if sum.magnitude2() > 0.0 {
+                sum.normalize()
+            } else if face.magnitude2().is_finite() {
+                face
+            } else {
+                // a degenerate triangle on its own, which has no direction to face
+                Vector3::from(vertex.normal)
+            };
+
+            let normal = [normal.x, normal.y, normal.z];
+            let key = (i, normal.map(f32::to_bits));
+            let index = *new_index.entry(key).or_insert_with(|| {
+                new_verts.push(model::ModelVertex { normal, ..vertex });
+                (new_verts.len() - 1) as u32
+            });
+            new_inds.push(index);
+        }
+    }
+    (new_verts, new_inds)
+}
+
+// every mesh of the model with new normals, see recompute_normals
+pub fn recompute_model_normals(
+    device: &wgpu::Device,
+    model: model::Model,
+    angle_threshold: Deg<f32>,
+) -> model::Model {
+    map_meshes(device, model, |mesh| {
+        recompute_normals(&mesh.verts, &mesh.indices, angle_threshold)
+    })
+}
+
+// whether any normal is zero or not a number, eg from a degenerate triangle in a file without
+// normals, which light as black or not at all
+pub fn has_broken_normals(verts: &[model::ModelVertex]) -> bool {
+    verts.iter().any(|vertex| {
+        let length = Vector3::from(vertex.normal).magnitude2();
+        !length.is_finite() || length < 1e-12
+    })
+}
+
+// the model with each mesh's vertices and indices replaced by what f makes of them
+fn map_meshes(
+    device: &wgpu::Device,
+    model: model::Model,
+    f: impl Fn(&model::Mesh) -> (Vec<model::ModelVertex>, Vec<u32>),
+) -> model::Model {
+    let meshes = model
+        .meshes
+        .iter()
+        .map(|mesh| {
+            let (verts, inds) = f(mesh);
+            model::Mesh::from_verts_inds(device, mesh.name.clone(), verts, inds, mesh.material)
+        })
+        .collect();
+    model::Model {
+        meshes,
+        transform: model.transform,
+    }
+}
+
+fn edges(triangle: &[u32; 3]) -> [(u32, u32); 3] {
+    let [a, b, c] = *triangle;
+    [(a, b), (b, c), (c, a)]
+}
+
+// not normalized, since only its direction is compared
+fn normal(positions: [Vector3<f64>; 3]) -> Vector3<f64> {
+    let [p0, p1, p2] = positions;
+    (p1 - p0).cross(p2 - p0)
+}
@@ -1,8 +1,9 @@
-use cgmath::InnerSpace;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, SquareMatrix};
 use wgpu::util::DeviceExt;
 
-use crate::texture;
+use crate::{math, render_stats, shader_params, texture};
 use std::ops::Range;
+use std::sync::Arc;
 
 const DET_EPSILON: f32 = 0.00000001;
 
@@ -16,8 +17,11 @@ pub struct ModelVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
-    pub tangent: [f32; 3],
-    pub bitangent: [f32; 3],
+    // xyz is the tangent, w is the bitangent's handedness: the shaders rebuild the bitangent as
+    // cross(normal, tangent) * w, which keeps mirrored uv islands bumping the right way
+    pub tangent: [f32; 4],
+    // linear rgb, white when the mesh has no vertex colors
+    pub color: [f32; 3],
 }
 
 impl Vertex for ModelVertex {
@@ -44,10 +48,10 @@ impl Vertex for ModelVertex {
                 wgpu::VertexAttribute {
                     offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
                     shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x4,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
@@ -56,63 +60,79 @@ impl Vertex for ModelVertex {
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct VectorDebugUniform {
-    pub position: [f32; 4],
-    pub vector: [f32; 4],
+// clones share the gpu buffers, see State::add_instances
+#[derive(Clone)]
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub transform: math::Transform,
 }
 
-impl VectorDebugUniform {
-    pub fn from_mesh_tbn(m: &Mesh) -> [Vec<VectorDebugUniform>; 3] {
-        let tangents = m
-            .verts
-            .iter()
-            .map(|v| {
-                let [px, py, pz] = v.position;
-                let [tx, ty, tz] = v.tangent;
-                Self {
-                    position: [px, py, pz, 1.0],
-                    vector: [tx, ty, tz, 1.0],
-                }
-            })
-            .collect();
+// the closest triangle hit by a ray, in world space
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub distance: f32,
+    pub position: cgmath::Point3<f32>,
+    // the geometric normal of the hit triangle, facing back towards the ray
+    pub normal: cgmath::Vector3<f32>,
+    // which of the model's meshes was hit, and which of its triangles, counting in threes
+    // through the indices
+    pub mesh: usize,
+    pub triangle: usize,
+}
 
-        let bitangents = m
-            .verts
-            .iter()
-            .map(|v| {
-                let [px, py, pz] = v.position;
-                let [bx, by, bz] = v.bitangent;
-                Self {
-                    position: [px, py, pz, 1.0],
-                    vector: [bx, by, bz, 1.0],
-                }
-            })
-            .collect();
+impl Model {
+    // brute force over every triangle of the meshes whose bounds the ray goes through, on the
+    // cpu side copies, which is fine for picking at mouse rate but not for anything per frame
+    pub fn raycast(&self, ray: &math::Ray) -> Option<RayHit> {
+        let local_ray = ray.into_space(&self.transform);
 
-        let normals = m
-            .verts
+        // nearest box first, so meshes behind the closest hit so far can be skipped whole
+        let mut meshes: Vec<(f32, usize)> = self
+            .meshes
             .iter()
-            .map(|v| {
-                let [px, py, pz] = v.position;
-                let [nx, ny, nz] = v.normal;
-                Self {
-                    position: [px, py, pz, 1.0],
-                    vector: [nx, ny, nz, 1.0],
-                }
-            })
+            .enumerate()
+            .filter(|(_, mesh)| mesh.is_visible)
+            .filter_map(|(i, mesh)| Some((local_ray.intersect_aabb(&mesh.bounds)?, i)))
             .collect();
+        meshes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // (distance, normal, mesh, triangle)
+        let mut closest: Option<(f32, cgmath::Vector3<f32>, usize, usize)> = None;
+        for (entry_distance, m) in meshes {
+            if closest.is_some_and(|(closest_distance, ..)| entry_distance > closest_distance) {
+                break;
+            }
+            let mesh = &self.meshes[m];
+            for (t, triangle) in mesh.indices.chunks(3).enumerate() {
+                let [a, b, c] = [0, 1, 2]
+                    .map(|i| cgmath::Point3::from(mesh.verts[triangle[i] as usize].position));
+                let Some(distance) = local_ray.intersect_triangle(a, b, c) else {
+                    continue;
+                };
+                if closest.is_none_or(|(closest_distance, ..)| distance < closest_distance) {
+                    closest = Some((distance, (b - a).cross(c - a), m, t));
+                }
+            }
+        }
 
-        [tangents, bitangents, normals]
-    }
-}
+        let (local_distance, local_normal, mesh, triangle) = closest?;
+        let position = cgmath::Point3::from_vec(
+            self.transform
+                .transform_point(local_ray.at(local_distance).to_vec()),
+        );
+        let mut normal = self.transform.transform_normal(local_normal);
+        if normal.dot(ray.direction) > 0.0 {
+            normal = -normal;
+        }
 
-pub struct Model {
-    pub meshes: Vec<Mesh>,
-    pub position: [f32; 3],
-    pub rotation: cgmath::Quaternion<f32>,
-    pub scale: f32,
+        Some(RayHit {
+            distance: (position - ray.origin).magnitude(),
+            position,
+            normal,
+            mesh,
+            triangle,
+        })
+    }
 }
 
 #[repr(C)]
@@ -122,27 +142,145 @@ pub struct ModelTransformationUniform {
     model_transformation_col1: [f32; 4],
     model_transformation_col2: [f32; 4],
     model_transformation_col3: [f32; 4],
+    // the normal matrix is a mat3x3 but its columns are padded to vec4s in the uniform
+    normal_transformation_col0: [f32; 4],
+    normal_transformation_col1: [f32; 4],
+    normal_transformation_col2: [f32; 4],
 }
 
 impl ModelTransformationUniform {
-    pub fn identity() -> Self {
-        Self {
-            model_transformation_col0: [1.0, 0.0, 0.0, 0.0],
-            model_transformation_col1: [0.0, 1.0, 0.0, 0.0],
-            model_transformation_col2: [0.0, 0.0, 1.0, 0.0],
-            model_transformation_col3: [0.0, 0.0, 0.0, 1.0],
-        }
-    }
-
     pub fn from_model(model: &Model) -> Self {
-        let matrix = cgmath::Matrix4::from_translation(model.position.into())
-            * cgmath::Matrix4::from(model.rotation)
-            * cgmath::Matrix4::from_scale(model.scale);
+        let matrix = model.transform.matrix();
+        let normal_matrix = model.transform.normal_matrix();
         Self {
             model_transformation_col0: matrix.x.into(),
             model_transformation_col1: matrix.y.into(),
             model_transformation_col2: matrix.z.into(),
             model_transformation_col3: matrix.w.into(),
+            normal_transformation_col0: normal_matrix.x.extend(0.0).into(),
+            normal_transformation_col1: normal_matrix.y.extend(0.0).into(),
+            normal_transformation_col2: normal_matrix.z.extend(0.0).into(),
+        }
+    }
+}
+
+// the optional texture maps a material can have, matching the MTL `map_*` statements
+#[derive(Default)]
+pub struct MaterialTextures {
+    pub diffuse: Option<texture::Texture>,
+    pub normal: Option<texture::Texture>,
+    pub specular: Option<texture::Texture>,
+    pub shininess: Option<texture::Texture>,
+    pub alpha: Option<texture::Texture>,
+    pub emissive: Option<texture::Texture>,
+    pub roughness: Option<texture::Texture>,
+    pub metallic: Option<texture::Texture>,
+    pub height: Option<texture::Texture>,
+}
+
+// which way a normal map's green channel points. the shader expects opengl style maps, whose
+// +y is towards +v (up in the image), while directx style maps point it down, so their green
+// is flipped before use
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NormalConvention {
+    #[default]
+    OpenGl,
+    DirectX,
+}
+
+impl NormalConvention {
+    // a tangent space normal map is the gradient of some height field, so the rate at which its
+    // x slope changes down the image has to match the rate at which its y slope changes across
+    // it. with opengl's y those two are opposite, and with directx's they agree. returns None
+    // for maps too flat (or too noisy) to tell
+    pub fn guess(image: &image::RgbaImage) -> Option<Self> {
+        let (width, height) = image.dimensions();
+        if width < 3 || height < 3 {
+            return None;
+        }
+        let slopes = |x: u32, y: u32| {
+            let [r, g, b, _] = image
+                .get_pixel(x, y)
+                .0
+                .map(|c| c as f32 / 255.0 * 2.0 - 1.0);
+            let z = b.max(0.1);
+            (r / z, g / z)
+        };
+
+        // every pixel would be slow for large maps and doesn't change the answer
+        let step = (width.max(height) / 512).max(1) as usize;
+        let mut agreement = 0.0;
+        let mut total = 0.0;
+        for y in (1..height - 1).step_by(step) {
+            for x in (1..width - 1).step_by(step) {
+                let x_slope_down = slopes(x, y + 1).0 - slopes(x, y - 1).0;
+                let y_slope_across = slopes(x + 1, y).1 - slopes(x - 1, y).1;
+                let product = x_slope_down * y_slope_across;
+                agreement += product;
+                total += product.abs();
+            }
+        }
+
+        if total < 1e-3 {
+            return None;
+        }
+        match agreement / total {
+            ratio if ratio < -0.1 => Some(NormalConvention::OpenGl),
+            ratio if ratio > 0.1 => Some(NormalConvention::DirectX),
+            _ => None,
+        }
+    }
+}
+
+// the scalar and color values of a material, which the texture maps (if present) modulate
+#[derive(Debug, Copy, Clone)]
+pub struct MaterialProperties {
+    pub ambient_color: [f32; 3],
+    pub diffuse_color: [f32; 3],
+    pub specular_color: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    // scales the tangent space xy of the normal map, from the bump map's -bm option
+    pub bump_multiplier: f32,
+    // applied to every texture lookup as uv * scale + offset
+    pub uv_offset: [f32; 2],
+    pub uv_scale: [f32; 2],
+    pub emissive_color: [f32; 3],
+    // when set, the shader derives the specular exponent from roughness instead of shininess
+    pub roughness: Option<f32>,
+    pub metallic: f32,
+    pub sheen: f32,
+    // not used by the shader yet, kept so materials round trip
+    pub anisotropy: f32,
+    pub anisotropy_rotation: f32,
+    pub normal_convention: NormalConvention,
+    // how deep the height map's black is below its white, in uv units
+    pub height_scale: f32,
+    // how many layers the parallax occlusion search steps through the height map in
+    pub parallax_steps: u32,
+}
+
+impl Default for MaterialProperties {
+    fn default() -> Self {
+        Self {
+            ambient_color: [0.0; 3],
+            // magenta makes materials with a missing diffuse color obvious
+            diffuse_color: [1.0, 0.0, 1.0],
+            specular_color: [1.0; 3],
+            shininess: 64.0,
+            dissolve: 1.0,
+            bump_multiplier: 1.0,
+            uv_offset: [0.0; 2],
+            uv_scale: [1.0; 2],
+            emissive_color: [0.0; 3],
+            roughness: None,
+            metallic: 0.0,
+            sheen: 0.0,
+            anisotropy: 0.0,
+            anisotropy_rotation: 0.0,
+            normal_convention: NormalConvention::OpenGl,
+            height_scale: 0.05,
+            parallax_steps: 16,
         }
     }
 }
@@ -151,9 +289,20 @@ pub struct Material {
     pub name: String,
     pub diffuse_texture: texture::Texture,
     pub normal_texture: texture::Texture,
-    pub ambient_color: [f32; 3],
-    pub diffuse_color: [f32; 3],
-    pub specular_color: [f32; 3],
+    pub specular_texture: texture::Texture,
+    pub shininess_texture: texture::Texture,
+    pub alpha_texture: texture::Texture,
+    pub emissive_texture: texture::Texture,
+    pub roughness_texture: texture::Texture,
+    pub metallic_texture: texture::Texture,
+    pub height_texture: texture::Texture,
+    pub properties: MaterialProperties,
+    pub parameters: shader_params::ParameterBlock,
+    // what the bind group's material buffer holds, kept for the material atlas
+    pub uniform: MaterialUniform,
+    buffer: wgpu::Buffer,
+    // the diffuse map and uniform the uv grid replaced, while it's shown
+    uv_grid_hidden: Option<(texture::Texture, MaterialUniform)>,
     pub bind_group: wgpu::BindGroup,
 }
 
@@ -161,36 +310,95 @@ impl Material {
     pub fn new(
         device: &wgpu::Device,
         name: &str,
-        diffuse_texture: Option<texture::Texture>,
-        normal_texture: Option<texture::Texture>,
-        ambient_color: [f32; 3],
-        diffuse_color: [f32; 3],
-        specular_color: [f32; 3],
+        textures: MaterialTextures,
+        placeholders: &texture::Placeholders,
+        properties: MaterialProperties,
         layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        let material_uniform = MaterialUniform::new(
-            ambient_color,
-            diffuse_color,
-            specular_color,
-            diffuse_texture.is_some(),
-            normal_texture.is_some(),
-        );
+        let material_uniform = MaterialUniform::new(&properties, &textures);
         let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(name),
             contents: bytemuck::cast_slice(&[material_uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let diffuse_texture = diffuse_texture.unwrap_or(texture::Texture::dummy(
-            device,
-            &(name.to_string() + " diffuse dummy"),
-        ));
-        let normal_texture = normal_texture.unwrap_or(texture::Texture::dummy(
+        let or_placeholder = |texture: Option<texture::Texture>, map: &str| {
+            texture.unwrap_or_else(|| placeholders.for_map(map).clone())
+        };
+
+        let parameters = shader_params::ParameterBlock::new(device, name);
+
+        let diffuse_texture = or_placeholder(textures.diffuse, "diffuse");
+        let normal_texture = or_placeholder(textures.normal, "normal");
+        let specular_texture = or_placeholder(textures.specular, "specular");
+        let shininess_texture = or_placeholder(textures.shininess, "shininess");
+        let alpha_texture = or_placeholder(textures.alpha, "alpha");
+        let emissive_texture = or_placeholder(textures.emissive, "emissive");
+        let roughness_texture = or_placeholder(textures.roughness, "roughness");
+        let metallic_texture = or_placeholder(textures.metallic, "metallic");
+        let height_texture = or_placeholder(textures.height, "height");
+
+        let bind_group = Self::create_bind_group(
             device,
-            &(name.to_string() + " normal dummy"),
-        ));
+            layout,
+            name,
+            [
+                &diffuse_texture,
+                &normal_texture,
+                &specular_texture,
+                &shininess_texture,
+                &alpha_texture,
+                &emissive_texture,
+                &roughness_texture,
+                &metallic_texture,
+                &height_texture,
+            ],
+            &material_buffer,
+            &parameters,
+        );
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        Self {
+            name: String::from(name),
+            diffuse_texture,
+            normal_texture,
+            specular_texture,
+            shininess_texture,
+            alpha_texture,
+            emissive_texture,
+            roughness_texture,
+            metallic_texture,
+            height_texture,
+            properties,
+            parameters,
+            uniform: material_uniform,
+            buffer: material_buffer,
+            uv_grid_hidden: None,
+            bind_group,
+        }
+    }
+
+    // maps are in the order of MaterialTextures
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: &str,
+        maps: [&texture::Texture; 9],
+        buffer: &wgpu::Buffer,
+        parameters: &shader_params::ParameterBlock,
+    ) -> wgpu::BindGroup {
+        let [
+            diffuse_texture,
+            normal_texture,
+            specular_texture,
+            shininess_texture,
+            alpha_texture,
+            emissive_texture,
+            roughness_texture,
+            metallic_texture,
+            height_texture,
+        ] = maps;
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout,
             entries: &[
                 wgpu::BindGroupEntry {
@@ -211,21 +419,136 @@ impl Material {
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: material_buffer.as_entire_binding(),
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: parameters.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&specular_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Sampler(&specular_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&shininess_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::Sampler(&shininess_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: wgpu::BindingResource::TextureView(&alpha_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: wgpu::BindingResource::Sampler(&alpha_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: wgpu::BindingResource::TextureView(&emissive_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 13,
+                    resource: wgpu::BindingResource::Sampler(&emissive_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 14,
+                    resource: wgpu::BindingResource::TextureView(&roughness_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 15,
+                    resource: wgpu::BindingResource::Sampler(&roughness_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 16,
+                    resource: wgpu::BindingResource::TextureView(&metallic_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 17,
+                    resource: wgpu::BindingResource::Sampler(&metallic_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 18,
+                    resource: wgpu::BindingResource::TextureView(&height_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 19,
+                    resource: wgpu::BindingResource::Sampler(&height_texture.sampler),
                 },
             ],
             label: Some(name),
-        });
+        })
+    }
 
-        Self {
-            name: String::from(name),
-            diffuse_texture,
-            normal_texture,
-            bind_group,
-            ambient_color,
-            diffuse_color,
-            specular_color,
+    // whether the material is see through, from its dissolve (d or Tr) or an alpha map. these
+    // are blended in a pass of their own after everything opaque
+    pub fn is_transparent(&self) -> bool {
+        self.uniform.dissolve < 1.0 || self.uniform.has_alpha_texture == 1
+    }
+
+    pub fn shows_uv_grid(&self) -> bool {
+        self.uv_grid_hidden.is_some()
+    }
+
+    // swaps the diffuse map for the uv debug grid, or back to what it was
+    pub fn set_uv_grid(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        placeholders: &texture::Placeholders,
+        layout: &wgpu::BindGroupLayout,
+        show: bool,
+    ) {
+        if show == self.shows_uv_grid() {
+            return;
+        }
+
+        if show {
+            let diffuse =
+                std::mem::replace(&mut self.diffuse_texture, placeholders.uv_grid.clone());
+            self.uv_grid_hidden = Some((diffuse, self.uniform));
+            self.uniform.has_diffuse_texture = 1;
+        } else if let Some((diffuse, uniform)) = self.uv_grid_hidden.take() {
+            self.diffuse_texture = diffuse;
+            self.uniform = uniform;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        self.bind_group = Self::create_bind_group(
+            device,
+            layout,
+            &self.name,
+            [
+                &self.diffuse_texture,
+                &self.normal_texture,
+                &self.specular_texture,
+                &self.shininess_texture,
+                &self.alpha_texture,
+                &self.emissive_texture,
+                &self.roughness_texture,
+                &self.metallic_texture,
+                &self.height_texture,
+            ],
+            &self.buffer,
+            &self.parameters,
+        );
+    }
+
+    // the parallax occlusion quality, which only matters to materials with a height map
+    pub fn set_parallax_steps(&mut self, steps: u32, queue: &wgpu::Queue) {
+        let steps = steps.clamp(1, 128);
+        self.properties.parallax_steps = steps;
+        self.uniform.parallax_steps = steps;
+        if let Some((_, uniform)) = &mut self.uv_grid_hidden {
+            uniform.parallax_steps = steps;
         }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
     }
 }
 
@@ -237,41 +560,111 @@ pub struct MaterialUniform {
     diffuse_color: [f32; 3],
     _padding1: u32,
     specular_color: [f32; 3],
-    _padding2: u32,
-    has_diffuse_texture: u32, // these are u32 to avoid any padding confusion while using bytemuck
-    has_normal_texture: u32,  // these are u32 to avoid any padding confusion while using bytemuck
-    _padding3: [u32; 2],
+    shininess: f32,
+    // these are u32 to avoid any padding confusion while using bytemuck
+    has_diffuse_texture: u32,
+    has_normal_texture: u32,
+    has_specular_texture: u32,
+    has_shininess_texture: u32,
+    has_alpha_texture: u32,
+    has_emissive_texture: u32,
+    dissolve: f32,
+    bump_multiplier: f32,
+    uv_offset: [f32; 2],
+    uv_scale: [f32; 2],
+    emissive_color: [f32; 3],
+    roughness: f32,
+    metallic: f32,
+    sheen: f32,
+    has_roughness_texture: u32,
+    has_metallic_texture: u32,
+    use_roughness: u32,
+    flip_normal_y: u32,
+    has_height_texture: u32,
+    height_scale: f32,
+    parallax_steps: u32,
+    _padding2: [u32; 3],
 }
 
 impl MaterialUniform {
-    fn new(
-        ambient_color: [f32; 3],
-        diffuse_color: [f32; 3],
-        specular_color: [f32; 3],
-        has_diffuse_texture: bool,
-        has_normal_texture: bool,
-    ) -> Self {
+    fn new(properties: &MaterialProperties, textures: &MaterialTextures) -> Self {
         Self {
-            ambient_color,
+            ambient_color: properties.ambient_color,
             _padding0: 0,
-            diffuse_color,
+            diffuse_color: properties.diffuse_color,
             _padding1: 0,
-            specular_color,
-            _padding2: 0,
-            has_diffuse_texture: if has_diffuse_texture { 1 } else { 0 },
-            has_normal_texture: if has_normal_texture { 1 } else { 0 },
-            _padding3: [0, 0],
+            specular_color: properties.specular_color,
+            shininess: properties.shininess,
+            has_diffuse_texture: textures.diffuse.is_some() as u32,
+            has_normal_texture: textures.normal.is_some() as u32,
+            has_specular_texture: textures.specular.is_some() as u32,
+            has_shininess_texture: textures.shininess.is_some() as u32,
+            has_alpha_texture: textures.alpha.is_some() as u32,
+            has_emissive_texture: textures.emissive.is_some() as u32,
+            dissolve: properties.dissolve,
+            bump_multiplier: properties.bump_multiplier,
+            uv_offset: properties.uv_offset,
+            uv_scale: properties.uv_scale,
+            emissive_color: properties.emissive_color,
+            roughness: properties.roughness.unwrap_or(1.0),
+            metallic: properties.metallic,
+            sheen: properties.sheen,
+            has_roughness_texture: textures.roughness.is_some() as u32,
+            has_metallic_texture: textures.metallic.is_some() as u32,
+            use_roughness: properties.roughness.is_some() as u32,
+            flip_normal_y: (properties.normal_convention == NormalConvention::DirectX) as u32,
+            has_height_texture: textures.height.is_some() as u32,
+            height_scale: properties.height_scale,
+            parallax_steps: properties.parallax_steps,
+            _padding2: [0; 3],
         }
     }
+
+    pub fn has_diffuse_texture(&self) -> bool {
+        self.has_diffuse_texture == 1
+    }
+
+    pub fn has_normal_texture(&self) -> bool {
+        self.has_normal_texture == 1
+    }
+
+    // any map besides the diffuse and normal ones
+    pub fn has_other_textures(&self) -> bool {
+        [
+            self.has_specular_texture,
+            self.has_shininess_texture,
+            self.has_alpha_texture,
+            self.has_emissive_texture,
+            self.has_roughness_texture,
+            self.has_metallic_texture,
+            self.has_height_texture,
+        ]
+        .contains(&1)
+    }
 }
 
+#[derive(Clone)]
 pub struct Mesh {
     pub name: String,
     pub verts: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
+    // Uint16 whenever every vertex can be indexed with it, which halves the index buffer
+    pub index_format: wgpu::IndexFormat,
     pub material: usize,
+    // in model space. transparent meshes are sorted by the box's center
+    pub bounds: math::Aabb,
+    // around the box's center, reaching the furthest vertex
+    pub bounding_radius: f32,
+    // an obj's o/g groups become meshes, which can be hidden and moved apart from the rest of
+    // their model. hidden meshes aren't drawn, cast no shadows and can't be picked
+    pub is_visible: bool,
+    // where the mesh has been moved to within its model, already baked into the verts
+    pub transform: math::Transform,
+    // counts the buffers' memory while any clone of the mesh is alive
+    _allocation: Arc<render_stats::Allocation>,
 }
 
 impl Mesh {
@@ -290,6 +683,11 @@ impl Mesh {
         let mut arb_counter = 0;
         let mut usual_counter = 0;
 
+        // the accumulated, area weighted tangents and bitangents of every vertex. the bitangents
+        // only decide each vertex's handedness and are not stored
+        let mut tangents = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); verts.len()];
+        let mut bitangents = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); verts.len()];
+
         // source for this: https://terathon.com/blog/tangent-space.html
 
         for ti in inds.chunks(3) {
@@ -313,7 +711,7 @@ impl Mesh {
 
             let det_denom = delta_uv_0_1.x * delta_uv_0_2.y - delta_uv_0_1.y * delta_uv_0_2.x;
 
-            let tangent = if det_denom.abs() <= DET_EPSILON {
+            let (tangent, bitangent) = if det_denom.abs() <= DET_EPSILON {
                 // in this case the triangle is degenerate somehow; same UVs, 0 UVs, idk but it needs to be fixed
                 // pick an arbitrary vector which isn't parallel to the normal
                 let normal = cgmath::Vector3::from(v0.normal);
@@ -324,23 +722,24 @@ impl Mesh {
                 };
 
                 arb_counter += 1;
-                arb.cross(normal).normalize()
+                let tangent = arb.cross(normal).normalize();
+                (tangent, normal.cross(tangent))
             } else {
                 usual_counter += 1;
-                (delta_pos_0_1 * delta_uv_0_2.y - delta_pos_0_2 * delta_uv_0_1.y) / det_denom
+                (
+                    (delta_pos_0_1 * delta_uv_0_2.y - delta_pos_0_2 * delta_uv_0_1.y) / det_denom,
+                    (delta_pos_0_2 * delta_uv_0_1.x - delta_pos_0_1 * delta_uv_0_2.x) / det_denom,
+                )
             };
             let area = delta_pos_0_1.cross(delta_pos_0_2).magnitude();
-            let weighted_tangent = tangent * area;
 
             // each vertex in the triangle uses the same tangent/bitangent
             // note the addition instead of assignment, because multiple faces
             // could be calculating different T/Bs, hence the need for the average
-            verts[ti[0] as usize].tangent =
-                (weighted_tangent + cgmath::Vector3::from(verts[ti[0] as usize].tangent)).into();
-            verts[ti[1] as usize].tangent =
-                (weighted_tangent + cgmath::Vector3::from(verts[ti[1] as usize].tangent)).into();
-            verts[ti[2] as usize].tangent =
-                (weighted_tangent + cgmath::Vector3::from(verts[ti[2] as usize].tangent)).into();
+            for &i in ti {
+                tangents[i as usize] += tangent * area;
+                bitangents[i as usize] += bitangent * area;
+            }
         }
 
         println!(
@@ -350,38 +749,117 @@ impl Mesh {
             arb_counter as f32 / usual_counter as f32
         );
 
-        for v in verts.iter_mut() {
+        for ((v, vt), vb) in verts.iter_mut().zip(tangents).zip(bitangents) {
             let vn = cgmath::Vector3::from(v.normal);
-            let vt = cgmath::Vector3::from(v.tangent);
 
             // use gram schmidt process to orthogonalize the tangent vec
             let tangent_gs = (vt - (vn * vn.dot(vt))).normalize();
-            v.tangent = tangent_gs.into();
-            v.bitangent = vn.cross(tangent_gs).normalize().into();
+
+            // a mirrored uv island has a bitangent pointing against n x t
+            let handedness = if vn.cross(tangent_gs).dot(vb) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            v.tangent = tangent_gs.extend(handedness).into();
         }
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&(name.clone() + " vertex buffer")),
             contents: bytemuck::cast_slice(&verts),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
 
+        // the index buffer keeps u32s on the cpu side, since ray tracing and voxelization use them
+        let (index_format, index_bytes) = if verts.len() <= u16::MAX as usize {
+            let inds_u16 = inds.iter().map(|&i| i as u16).collect::<Vec<_>>();
+            (
+                wgpu::IndexFormat::Uint16,
+                bytemuck::cast_slice(&inds_u16).to_vec(),
+            )
+        } else {
+            (
+                wgpu::IndexFormat::Uint32,
+                bytemuck::cast_slice(&inds).to_vec(),
+            )
+        };
+
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&(name.clone() + " index buffer")),
-            contents: bytemuck::cast_slice(&inds),
+            contents: &index_bytes,
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        log::info!("loaded mesh: {}", name);
+        let (bounds, bounding_radius) = bounds_of(&verts);
+
+        let allocation = render_stats::Allocation::buffers(&[&vertex_buffer, &index_buffer]);
+
+        log::info!("loaded mesh: {} ({:?} indices)", name, index_format);
         Self {
             name,
             verts,
             vertex_buffer,
             index_buffer,
             index_count: inds.len() as u32,
+            index_format,
+            indices: inds,
             material,
+            bounds,
+            bounding_radius,
+            is_visible: true,
+            transform: math::Transform::identity(),
+            _allocation: allocation,
         }
     }
+
+    // moves the mesh within its model by rewriting its verts, so nothing drawing it needs to know.
+    // the cpu side copies are moved too, for picking, ray tracing and voxelization
+    pub fn set_transform(&mut self, queue: &wgpu::Queue, transform: math::Transform) {
+        let matrix = transform.matrix() * self.transform.inverse_matrix();
+        let linear = cgmath::Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+        let normal_matrix = linear
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear);
+        // a mirroring transform flips which way every bitangent points
+        let handedness = linear.determinant().signum();
+
+        for v in &mut self.verts {
+            v.position = cgmath::Point3::from_homogeneous(
+                matrix * cgmath::Point3::from(v.position).to_homogeneous(),
+            )
+            .into();
+            v.normal = (normal_matrix * cgmath::Vector3::from(v.normal))
+                .normalize()
+                .into();
+            let tangent = cgmath::Vector4::from(v.tangent);
+            v.tangent = (linear * tangent.truncate())
+                .normalize()
+                .extend(tangent.w * handedness)
+                .into();
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.verts));
+        (self.bounds, self.bounding_radius) = bounds_of(&self.verts);
+        self.transform = transform;
+    }
+}
+
+// the verts' box and the radius around its center reaching the furthest one
+fn bounds_of(verts: &[ModelVertex]) -> (math::Aabb, f32) {
+    let bounds =
+        math::Aabb::from_points(verts.iter().map(|v| v.position.into())).unwrap_or(math::Aabb {
+            min: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            max: cgmath::Vector3::new(0.0, 0.0, 0.0),
+        });
+    let bounding_radius = verts
+        .iter()
+        .map(|v| (cgmath::Vector3::from(v.position) - bounds.center()).magnitude())
+        .fold(0.0, f32::max);
+    (bounds, bounding_radius)
 }
 
 pub trait DrawModel<'a> {
@@ -390,6 +868,7 @@ pub trait DrawModel<'a> {
         mesh: &'a Mesh,
         material: &'a Material,
         per_object_bind_group: &'a wgpu::BindGroup,
+        per_object_offsets: &[u32],
     );
     fn draw_mesh_instanced(
         &mut self,
@@ -397,6 +876,17 @@ pub trait DrawModel<'a> {
         material: &'a Material,
         instances: Range<u32>,
         per_object_bind_group: &'a wgpu::BindGroup,
+        per_object_offsets: &[u32],
+    );
+    // draws with a material from the material atlas, which only needs the atlas's bind group
+    // at the material's offset
+    fn draw_mesh_batched(
+        &mut self,
+        mesh: &'a Mesh,
+        atlas_bind_group: &'a wgpu::BindGroup,
+        material_offset: u32,
+        per_object_bind_group: &'a wgpu::BindGroup,
+        per_object_offsets: &[u32],
     );
 
     fn draw_model(
@@ -404,6 +894,7 @@ pub trait DrawModel<'a> {
         model: &'a Model,
         materials: &'a Vec<Material>,
         per_object_bind_group: &'a wgpu::BindGroup,
+        per_object_offsets: &[u32],
     );
     fn draw_model_instanced(
         &mut self,
@@ -411,10 +902,11 @@ pub trait DrawModel<'a> {
         instances: Range<u32>,
         materials: &'a Vec<Material>,
         per_object_bind_group: &'a wgpu::BindGroup,
+        per_object_offsets: &[u32],
     );
 }
 
-impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
+impl<'a, 'b> DrawModel<'b> for crate::render_stats::CountingRenderPass<'a>
 where
     'b: 'a,
 {
@@ -423,8 +915,15 @@ where
         mesh: &'b Mesh,
         material: &'b Material,
         per_object_bind_group: &'b wgpu::BindGroup,
+        per_object_offsets: &[u32],
     ) {
-        self.draw_mesh_instanced(mesh, material, 0..1, per_object_bind_group);
+        self.draw_mesh_instanced(
+            mesh,
+            material,
+            0..1,
+            per_object_bind_group,
+            per_object_offsets,
+        );
     }
 
     fn draw_mesh_instanced(
@@ -433,18 +932,51 @@ where
         material: &'b Material,
         instances: Range<u32>,
         per_object_bind_group: &'b wgpu::BindGroup,
+        per_object_offsets: &[u32],
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
 
         self.set_bind_group(1, &material.bind_group, &[]);
-        self.set_bind_group(2, per_object_bind_group, &[]);
+        // offsets into the scene's transform buffer, or empty for bind groups without any
+        self.set_bind_group(2, per_object_bind_group, per_object_offsets);
 
+        self.insert_debug_marker(&format!("{} ({})", mesh.name, material.name));
         self.draw_indexed(0..mesh.index_count, 0, instances);
     }
 
-    fn draw_model(&mut self, model: &'b Model, materials: &'b Vec<Material>, per_object_bind_group: &'b wgpu::BindGroup) {
-        self.draw_model_instanced(model, 0..1, materials, per_object_bind_group);
+    fn draw_mesh_batched(
+        &mut self,
+        mesh: &'b Mesh,
+        atlas_bind_group: &'b wgpu::BindGroup,
+        material_offset: u32,
+        per_object_bind_group: &'b wgpu::BindGroup,
+        per_object_offsets: &[u32],
+    ) {
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        self.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+
+        self.set_bind_group(1, atlas_bind_group, &[material_offset]);
+        self.set_bind_group(2, per_object_bind_group, per_object_offsets);
+
+        self.insert_debug_marker(&format!("{} (atlas offset {})", mesh.name, material_offset));
+        self.draw_indexed(0..mesh.index_count, 0, 0..1);
+    }
+
+    fn draw_model(
+        &mut self,
+        model: &'b Model,
+        materials: &'b Vec<Material>,
+        per_object_bind_group: &'b wgpu::BindGroup,
+        per_object_offsets: &[u32],
+    ) {
+        self.draw_model_instanced(
+            model,
+            0..1,
+            materials,
+            per_object_bind_group,
+            per_object_offsets,
+        );
     }
 
     fn draw_model_instanced(
@@ -453,10 +985,17 @@ where
         instances: Range<u32>,
         materials: &'b Vec<Material>,
         per_object_bind_group: &'b wgpu::BindGroup,
+        per_object_offsets: &[u32],
     ) {
-        for mesh in &model.meshes {
+        for mesh in model.meshes.iter().filter(|mesh| mesh.is_visible) {
             let material = &materials[mesh.material];
-            self.draw_mesh_instanced(mesh, material, instances.clone(), per_object_bind_group);
+            self.draw_mesh_instanced(
+                mesh,
+                material,
+                instances.clone(),
+                per_object_bind_group,
+                per_object_offsets,
+            );
         }
     }
 }
@@ -0,0 +1,230 @@
+// multisample antialiasing for the scene pass, with render.msaa_samples in the config. the pass
+// draws into multisampled color and depth targets instead of the usual ones, and the color is
+// resolved into where the scene would have gone as the pass ends. every pipeline drawing in
+// the pass is made with the same sample count.
+// the passes after it which read the depth (the transparency, the fog, the particles'
+// collisions) want it single sampled, so resolve_depth copies it back into the depth texture
+// in a pass of its own. see fxaa in antialiasing.rs for a cheaper, blurrier alternative
+
+use crate::texture;
+
+// the most samples the adapter can draw the color format and the depth with, up to the count
+// asked for
+pub fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    color_format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let color = adapter.get_texture_format_features(color_format).flags;
+    let depth = adapter
+        .get_texture_format_features(texture::Texture::DEPTH_FORMAT)
+        .flags;
+    let count = [16, 8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| {
+            color.sample_count_supported(count)
+                && color.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+                && depth.sample_count_supported(count)
+        })
+        .unwrap_or(1);
+    if count != requested {
+        log::warn!(
+            "{} samples aren't supported for {:?}, using {}",
+            requested,
+            color_format,
+            count
+        );
+    }
+    count
+}
+
+pub struct Multisampling {
+    pub sample_count: u32,
+    color_format: wgpu::TextureFormat,
+    color: texture::Texture,
+    depth: texture::Texture,
+    depth_resolve_bind_group_layout: wgpu::BindGroupLayout,
+    depth_resolve_bind_group: wgpu::BindGroup,
+    depth_resolve_pipeline: wgpu::RenderPipeline,
+}
+
+impl Multisampling {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        sample_count: u32,
+    ) -> Self {
+        let (color, depth) = create_targets(device, color_format, size, sample_count);
+
+        let depth_resolve_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("msaa depth resolve bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: true,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                }],
+            });
+        let depth_resolve_bind_group =
+            create_depth_resolve_bind_group(device, &depth_resolve_bind_group_layout, &depth);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("msaa depth resolve pipeline layout"),
+            bind_group_layouts: &[&depth_resolve_bind_group_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "msaa_depth_resolve.wgsl",
+            include_str!("shaders/msaa_depth_resolve.wgsl"),
+        ));
+        let depth_resolve_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("msaa depth resolve pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vertex_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                // only the depth is written
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fragment_main"),
+                    targets: &[],
+                    compilation_options: Default::default(),
+                }),
+                // a single triangle covering the screen, generated in the vertex shader
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache: None,
+            });
+
+        Self {
+            sample_count,
+            color_format,
+            color,
+            depth,
+            depth_resolve_bind_group_layout,
+            depth_resolve_bind_group,
+            depth_resolve_pipeline,
+        }
+    }
+
+    // recreates the targets when the scene is drawn at another size, eg after a resize or
+    // for a panorama's faces
+    pub fn fit(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        if self.color.texture.size() == size {
+            return;
+        }
+        (self.color, self.depth) =
+            create_targets(device, self.color_format, size, self.sample_count);
+        self.depth_resolve_bind_group = create_depth_resolve_bind_group(
+            device,
+            &self.depth_resolve_bind_group_layout,
+            &self.depth,
+        );
+    }
+
+    // what the scene pass draws into, the color resolving into the scene's usual target
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color.view
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth.view
+    }
+
+    // the scene pass's depth into the single sampled depth texture, replacing what was there
+    pub fn resolve_depth(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("msaa depth resolve pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                // the stencil is only used within the scene pass
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.depth_resolve_pipeline);
+        render_pass.set_bind_group(0, &self.depth_resolve_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("[MSAA {}x]", self.sample_count)
+    }
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    sample_count: u32,
+) -> (texture::Texture, texture::Texture) {
+    let color = texture::Texture::builder("msaa color texture", size.width, size.height)
+        .format(color_format)
+        .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        .sample_count(sample_count)
+        .build(device);
+    let depth = texture::Texture::builder("msaa depth texture", size.width, size.height)
+        .format(texture::Texture::DEPTH_FORMAT)
+        .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        .sample_count(sample_count)
+        .build(device);
+    (color, depth)
+}
+
+fn create_depth_resolve_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    depth: &texture::Texture,
+) -> wgpu::BindGroup {
+    // the stencil can't be read along with the depth
+    let depth_view = depth.texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("msaa depth view"),
+        aspect: wgpu::TextureAspect::DepthOnly,
+        ..Default::default()
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("msaa depth resolve bind group"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&depth_view),
+        }],
+    })
+}
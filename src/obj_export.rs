@@ -0,0 +1,56 @@
+// writes models out as one obj file, with every mesh moved by its model's transform so the file
+// holds the scene as it was arranged. only the geometry is written: every mesh becomes a group
+// of its own, with the uvs as they were read, but there's no mtl, so the materials are left out
+
+use std::io::Write;
+
+use cgmath::Vector3;
+
+use crate::model;
+
+// returns how many meshes were written
+pub fn export_obj(path: &str, models: &[&model::Model]) -> std::io::Result<usize> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "# exported from graphics-fundamentals")?;
+
+    // obj indices count from 1 across the whole file
+    let mut first_index = 1;
+    let mut mesh_count = 0;
+    for (m, model) in models.iter().enumerate() {
+        for mesh in &model.meshes {
+            writeln!(
+                file,
+                "g {}_{}",
+                m,
+                mesh.name.replace(char::is_whitespace, "_")
+            )?;
+            for vertex in &mesh.verts {
+                let position = model.transform.transform_point(vertex.position.into());
+                let [r, g, b] = vertex.color;
+                writeln!(
+                    file,
+                    "v {} {} {} {} {} {}",
+                    position.x, position.y, position.z, r, g, b
+                )?;
+            }
+            for vertex in &mesh.verts {
+                let [u, v] = vertex.tex_coords;
+                writeln!(file, "vt {} {}", u, v)?;
+            }
+            for vertex in &mesh.verts {
+                let normal = model
+                    .transform
+                    .transform_normal(Vector3::from(vertex.normal));
+                writeln!(file, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+            }
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + first_index);
+                writeln!(file, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+            }
+            first_index += mesh.verts.len() as u32;
+            mesh_count += 1;
+        }
+    }
+    file.flush()?;
+    Ok(mesh_count)
+}
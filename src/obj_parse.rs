@@ -1,29 +1,43 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, io::BufRead};
+
+use cgmath::InnerSpace;
 
 use crate::model;
 
 #[derive(Debug)]
 pub enum OBJLoadError {
-    FileNotFound(std::io::Error),
+    FileNotFound(String, std::io::Error),
+    Read(String, std::io::Error),
     Parse(String, usize, String),
 }
 
 #[derive(Debug)]
 pub enum MTLLoadError {
-    FileNotFound(std::io::Error),
+    FileNotFound(String, std::io::Error),
+    Read(String, std::io::Error),
     Parse(String, usize, String),
-    MtlNotFound(String),
+    // material name, source
+    MtlNotFound(String, String),
 }
 
 #[derive(Debug)]
 pub struct ParsedOBJ {
-    pub model_verts: Vec<model::ModelVertex>,
     pub raw_verts: Vec<(f32, f32, f32)>,
+    // one per raw vert, from the `v x y z r g b` extension (white when a vert has no color)
+    pub raw_colors: Vec<(f32, f32, f32)>,
     pub raw_uvs: Vec<(f32, f32)>,
     pub raw_normals: Vec<(f32, f32, f32)>,
+    pub groups: Vec<ParsedOBJGroup>,
+    pub material_lib: Option<String>,
+}
+
+// one `o`/`g` section of an OBJ file (split further if it switches material part way through)
+#[derive(Debug)]
+pub struct ParsedOBJGroup {
+    pub name: String,
+    pub model_verts: Vec<model::ModelVertex>,
     pub indices: Vec<u32>,
     pub material: Option<String>,
-    pub material_lib: Option<String>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -34,17 +48,79 @@ pub struct ParsedMTL {
     pub ks: Option<[f32; 3]>,
     pub ns: Option<f32>,
     pub d: Option<f32>,
+    // transparency, the inverse of d, which some exporters write instead
+    pub tr: Option<f32>,
     pub ni: Option<f32>,
     pub illum: Option<u16>,
-    pub map_bump: Option<String>,
-    pub map_kd: Option<String>,
+    // the pbr extension statements written by blender, substance etc
+    pub ke: Option<[f32; 3]>,
+    pub pr: Option<f32>,
+    pub pm: Option<f32>,
+    pub ps: Option<f32>,
+    pub aniso: Option<f32>,
+    pub anisor: Option<f32>,
+    pub map_bump: Option<TextureMap>,
+    pub map_kd: Option<TextureMap>,
+    pub map_ks: Option<TextureMap>,
+    pub map_ns: Option<TextureMap>,
+    pub map_d: Option<TextureMap>,
+    pub map_ke: Option<TextureMap>,
+    pub map_pr: Option<TextureMap>,
+    pub map_pm: Option<TextureMap>,
+    // a height map, from disp or map_disp
+    pub map_disp: Option<TextureMap>,
+}
+
+// the options that can come before the file name of a map statement, eg
+// `map_Bump -bm 0.5 -s 2 2 normal.png`. options we don't use are parsed and dropped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureOptions {
+    // -bm, only meaningful on bump and height maps
+    pub bump_multiplier: f32,
+    // -o and -s, applied to the uv coordinates as uv * scale + offset
+    pub offset: [f32; 3],
+    pub scale: [f32; 3],
+    // -clamp, repeat is the default
+    pub clamp: bool,
+    // -normal opengl|directx, only meaningful on bump maps. None leaves it to be guessed
+    // from the image
+    pub normal_convention: Option<model::NormalConvention>,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            bump_multiplier: 1.0,
+            offset: [0.0; 3],
+            scale: [1.0; 3],
+            clamp: false,
+            normal_convention: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureMap {
+    pub file_name: String,
+    pub options: TextureOptions,
 }
 
 impl std::fmt::Display for OBJLoadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            OBJLoadError::FileNotFound(error) => {
-                write!(f, "IO error while loading OBJ file:\n{}", error)
+            OBJLoadError::FileNotFound(filepath, error) => {
+                write!(
+                    f,
+                    "IO error while loading OBJ file {}:\n{}",
+                    filepath, error
+                )
+            }
+            OBJLoadError::Read(source, error) => {
+                write!(
+                    f,
+                    "IO error while reading OBJ data from {}:\n{}",
+                    source, error
+                )
             }
             OBJLoadError::Parse(filepath, line_num, msg) => write!(
                 f,
@@ -55,19 +131,66 @@ impl std::fmt::Display for OBJLoadError {
     }
 }
 
+impl std::fmt::Display for MTLLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MTLLoadError::FileNotFound(filepath, error) => {
+                write!(
+                    f,
+                    "IO error while loading MTL file {}:\n{}",
+                    filepath, error
+                )
+            }
+            MTLLoadError::Read(source, error) => {
+                write!(
+                    f,
+                    "IO error while reading MTL data from {}:\n{}",
+                    source, error
+                )
+            }
+            MTLLoadError::Parse(filepath, line_num, msg) => write!(
+                f,
+                "Error loading MTL file {}:\nline {}: {}",
+                filepath, line_num, msg
+            ),
+            MTLLoadError::MtlNotFound(name, source) => {
+                write!(f, "Material not found: {} not found in {}", name, source)
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for ParsedOBJ {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "model verts: {}\nraw verts: {}\nraw uvs: {}\nraw normals: {}\nindices: {} ({} triangles)\nmaterial: {}\nmaterial lib: {}\n",
-            self.model_verts.len(),
+            "raw verts: {}\nraw colors: {}\nraw uvs: {}\nraw normals: {}\ngroups: {}\nmaterial lib: {}\n",
             self.raw_verts.len(),
+            self.raw_colors.len(),
             self.raw_uvs.len(),
             self.raw_normals.len(),
+            self.groups.len(),
+            self.material_lib.as_ref().unwrap_or(&"none".to_string()),
+        )?;
+
+        for group in &self.groups {
+            write!(f, "{}", group)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ParsedOBJGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "group {}:\n  model verts: {}\n  indices: {} ({} triangles)\n  material: {}\n",
+            self.name,
+            self.model_verts.len(),
             self.indices.len(),
             self.indices.len() / 3,
             self.material.as_ref().unwrap_or(&"none".to_string()),
-            self.material_lib.as_ref().unwrap_or(&"none".to_string()),
         )
     }
 }
@@ -79,74 +202,261 @@ fn parse_vector_line(line: &str) -> Result<Vec<f32>, std::num::ParseFloatError>
         .collect()
 }
 
-fn parse_face_line(line: &str) -> Result<Vec<Vec<u32>>, std::num::ParseIntError> {
-    Ok(line
-        .split_ascii_whitespace()
+// parses the position/uv/normal indices of each of a face's vertices, which obj counts from 1.
+// negative indices count back from the last of their kind read so far, which `counts` gives
+// for positions, uvs and normals. missing uvs and normals (eg the uv in `1//3`) are returned
+// as 0, and anything else which doesn't name a read element is an error
+fn parse_face_line(line: &str, counts: [usize; 3]) -> Result<Vec<[u32; 3]>, String> {
+    line.split_ascii_whitespace()
         .skip(1)
         .map(|ft| {
-            let mut fv = ft
-                .split("/")
-                .map(|i| i.parse::<u32>().unwrap_or(1))
-                .collect::<Vec<u32>>();
-            fv.resize(3, 1);
-            fv
+            let mut face_vert = [0; 3];
+            for (kind, text) in ft.split('/').enumerate() {
+                if kind >= 3 {
+                    return Err(format!("too many indices in {}", ft));
+                }
+                if text.is_empty() && kind > 0 {
+                    continue;
+                }
+
+                let index = text
+                    .parse::<i64>()
+                    .map_err(|e| format!("could not parse index in {}: {e}", ft))?;
+                let count = counts[kind] as i64;
+                let resolved = if index < 0 { count + 1 + index } else { index };
+                if index == 0 || !(1..=count).contains(&resolved) {
+                    return Err(format!("index {} in {} is out of range", index, ft));
+                }
+                face_vert[kind] = resolved as u32;
+            }
+            Ok(face_vert)
         })
-        .collect())
+        .collect()
 }
 
-pub fn parse_obj(filepath: &str) -> Result<ParsedOBJ, OBJLoadError> {
-    let file = std::fs::read_to_string(filepath).map_err(|e| OBJLoadError::FileNotFound(e))?;
+// splits a polygon into triangles, returning indices into `positions`. the output triangles
+// keep the winding order of the polygon. convex polygons (which covers nearly every quad) are
+// fanned from the first vertex, anything else goes through ear clipping
+pub fn triangulate_polygon(positions: &[cgmath::Vector3<f32>]) -> Vec<[usize; 3]> {
+    let n = positions.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // newell's method gives a usable normal even for concave or slightly non-planar polygons
+    let mut normal = cgmath::Vector3::new(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let current = positions[i];
+        let next = positions[(i + 1) % n];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    // how far a corner turns the same way as the polygon as a whole, negative when it's reflex
+    // and 0 when its edges are in line
+    let turn = |prev: usize, curr: usize, next: usize| {
+        (positions[curr] - positions[prev])
+            .cross(positions[next] - positions[curr])
+            .dot(normal)
+    };
+    let is_convex = |prev: usize, curr: usize, next: usize| turn(prev, curr, next) >= 0.0;
+
+    if (0..n).all(|i| is_convex((i + n - 1) % n, i, (i + 1) % n)) {
+        return (1..n - 1).map(|i| [0, i, i + 1]).collect();
+    }
 
+    // ear clipping: repeatedly cut off a convex corner whose triangle contains no other vertex
+    let contains = |a: usize, b: usize, c: usize, p: usize| {
+        let edge_test = |from: usize, to: usize| {
+            (positions[to] - positions[from])
+                .cross(positions[p] - positions[from])
+                .dot(normal)
+                >= 0.0
+        };
+        edge_test(a, b) && edge_test(b, c) && edge_test(c, a)
+    };
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let prev = remaining[(i + count - 1) % count];
+            let curr = remaining[i];
+            let next = remaining[(i + 1) % count];
+
+            // a corner in line with its neighbours would only cut off a triangle with no area
+            turn(prev, curr, next) > 0.0
+                && remaining
+                    .iter()
+                    .filter(|&&p| p != prev && p != curr && p != next)
+                    .all(|&p| !contains(prev, curr, next, p))
+        });
+
+        // degenerate (eg self intersecting) polygons may have no ear, so just take any corner
+        let i = ear.unwrap_or(0);
+        triangles.push([
+            remaining[(i + count - 1) % count],
+            remaining[i],
+            remaining[(i + 1) % count],
+        ]);
+        remaining.remove(i);
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// parses the group number from an `s` line; `s off` and `s 0` both disable smoothing
+fn parse_smoothing_line(line: &str) -> u32 {
+    match line.split_ascii_whitespace().nth(1) {
+        Some("off") | None => 0,
+        Some(group) => group.parse::<u32>().unwrap_or(0),
+    }
+}
+
+// fills in normals for the vertices flagged in `needs_normal` by summing the (area weighted)
+// normals of every face that uses them. `shared` gives each vertex the one whose sum it adds to
+// and takes its normal from, so vertices split by a uv seam can still be smoothed together.
+// vertices are only shared between faces in the same smoothing group, so flat faces end up
+// with just their own face normal
+pub fn generate_normals(
+    model_verts: &mut [model::ModelVertex],
+    indices: &[u32],
+    needs_normal: &[bool],
+    shared: &[usize],
+) {
+    let mut sums = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); model_verts.len()];
+    for tri in indices.chunks(3) {
+        let [i0, i1, i2] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+
+        let p0 = cgmath::Vector3::from(model_verts[i0].position);
+        let p1 = cgmath::Vector3::from(model_verts[i1].position);
+        let p2 = cgmath::Vector3::from(model_verts[i2].position);
+
+        // the cross product's length is twice the triangle's area, which gives the weighting for free
+        let face_normal = (p1 - p0).cross(p2 - p0);
+
+        for i in [i0, i1, i2] {
+            if needs_normal[i] {
+                sums[shared[i]] += face_normal;
+            }
+        }
+    }
+
+    for (i, vert) in model_verts.iter_mut().enumerate() {
+        let normal = sums[shared[i]];
+        if needs_normal[i] && normal.magnitude2() > 0.0 {
+            vert.normal = normal.normalize().into();
+        }
+    }
+}
+
+pub fn parse_obj_file(filepath: &str) -> Result<ParsedOBJ, OBJLoadError> {
+    let file = crate::assets::open(filepath)
+        .map_err(|e| OBJLoadError::FileNotFound(filepath.to_string(), e))?;
+    parse_obj(file, filepath)
+}
+
+// parses OBJ data line by line from any reader (a file, an in memory asset, a network
+// response, ...). `source` only names the data in error messages
+pub fn parse_obj(reader: impl BufRead, source: &str) -> Result<ParsedOBJ, OBJLoadError> {
     let mut raw_verts: Vec<(f32, f32, f32)> = Vec::new();
+    let mut raw_colors: Vec<(f32, f32, f32)> = Vec::new();
     let mut raw_uvs: Vec<(f32, f32)> = Vec::new();
     let mut raw_normals: Vec<(f32, f32, f32)> = Vec::new();
 
-    let mut face_vert_index_map = HashMap::new();
-    let mut indices = Vec::new();
+    // faces before the first `o`/`g` statement go into an unnamed default group
+    let mut groups = Vec::new();
+    let mut current_group = GroupBuilder::new("default".to_string(), None);
 
-    let mut model_verts = Vec::new();
-
-    let mut material = None;
     let mut material_lib = None;
 
-    for (linenum, line) in file.lines().enumerate() {
+    // vertices without a `vn` get a generated normal; they can only be merged with vertices
+    // from the same smoothing group, and never when smoothing is off (group 0)
+    let mut smoothing_group = 0;
+
+    for (linenum, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| OBJLoadError::Read(source.to_string(), e))?;
+        let line = line.as_str();
+
         if line.starts_with("#") {
             continue;
         } else if line.starts_with("f") {
-            if let Ok(vvi) = parse_face_line(line) {
-                for face_vert in vvi {
-                    let key = (face_vert[0], face_vert[1], face_vert[2]);
-
-                    let index = match face_vert_index_map.get(&key) {
-                        Some(&i) => i,
-                        None => {
-                            let i = model_verts.len();
-                            model_verts.push(model::ModelVertex {
-                                position: raw_verts[key.0 as usize - 1].into(),
-                                tex_coords: (*raw_uvs
-                                    .get(key.1 as usize - 1)
-                                    .unwrap_or(&(0.0, 0.0)))
-                                .into(),
-                                normal: (*raw_normals
-                                    .get(key.2 as usize - 1)
-                                    .unwrap_or(&(0.0, 0.0, 0.0)))
-                                .into(),
-                                tangent: [0.0; 3],
-                                bitangent: [0.0; 3],
-                            });
-                            face_vert_index_map.insert(key, i);
+            let counts = [raw_verts.len(), raw_uvs.len(), raw_normals.len()];
+            let vvi = parse_face_line(line, counts)
+                .map_err(|e| OBJLoadError::Parse(source.to_string(), linenum, e))?;
+            let mut face_indices = Vec::with_capacity(vvi.len());
+
+            for face_vert in vvi {
+                let has_normal = face_vert[2] != 0;
+                let group = if has_normal { 0 } else { smoothing_group };
+                let key = (face_vert[0], face_vert[1], face_vert[2], group);
+
+                let existing = if has_normal || group != 0 {
+                    current_group.face_vert_index_map.get(&key).copied()
+                } else {
+                    None
+                };
+
+                let index = match existing {
+                    Some(i) => i,
+                    None => {
+                        let i = current_group.model_verts.len();
+                        current_group.model_verts.push(model::ModelVertex {
+                            position: raw_verts[key.0 as usize - 1].into(),
+                            tex_coords: (*(key.1 as usize)
+                                .checked_sub(1)
+                                .and_then(|i| raw_uvs.get(i))
+                                .unwrap_or(&(0.0, 0.0)))
+                            .into(),
+                            normal: (*(key.2 as usize)
+                                .checked_sub(1)
+                                .and_then(|i| raw_normals.get(i))
+                                .unwrap_or(&(0.0, 0.0, 0.0)))
+                            .into(),
+                            tangent: [0.0; 4],
+                            color: raw_colors[key.0 as usize - 1].into(),
+                        });
+                        current_group.needs_normal.push(!has_normal);
+                        // every vertex at this position in the smoothing group adds up
+                        // its faces' normals together, whatever its uv
+                        let shared = if !has_normal && group != 0 {
+                            *current_group
+                                .smoothed_verts
+                                .entry((key.0, group))
+                                .or_insert(i)
+                        } else {
                             i
-                        }
-                    };
-                    indices.push(index as u32);
-                }
-            } else {
+                        };
+                        current_group.shared_normals.push(shared);
+                        current_group.face_vert_index_map.insert(key, i);
+                        i
+                    }
+                };
+                face_indices.push(index as u32);
+            }
+
+            if face_indices.len() < 3 {
                 return Err(OBJLoadError::Parse(
-                    filepath.to_string(),
+                    source.to_string(),
                     linenum,
-                    "could not parse faces".to_string(),
+                    "face has fewer than 3 vertices".to_string(),
                 ));
             }
+
+            let positions = face_indices
+                .iter()
+                .map(|&i| cgmath::Vector3::from(current_group.model_verts[i as usize].position))
+                .collect::<Vec<_>>();
+
+            for [a, b, c] in triangulate_polygon(&positions) {
+                current_group
+                    .indices
+                    .extend([face_indices[a], face_indices[b], face_indices[c]]);
+            }
         } else if line.starts_with("v") {
             match parse_vector_line(line) {
                 Ok(linevec) => {
@@ -156,16 +466,34 @@ pub fn parse_obj(filepath: &str) -> Result<ParsedOBJ, OBJLoadError> {
                         raw_uvs.push((linevec[0], linevec[1]));
                     } else {
                         raw_verts.push((linevec[0], linevec[1], linevec[2]));
+                        // `v x y z w` has an optional weight rather than a color
+                        if linevec.len() >= 6 {
+                            raw_colors.push((linevec[3], linevec[4], linevec[5]));
+                        } else {
+                            raw_colors.push((1.0, 1.0, 1.0));
+                        }
                     }
                 }
                 Err(e) => {
                     return Err(OBJLoadError::Parse(
-                        file.to_string(),
+                        source.to_string(),
                         linenum,
-                        "could not parse float: ".to_string() + &e.to_string(),
+                        format!("could not parse float: {e}"),
                     ));
                 }
             }
+        } else if line.starts_with("s ") {
+            smoothing_group = parse_smoothing_line(line);
+        } else if line.starts_with("o ") || line.starts_with("g ") {
+            // `g` may list several group names, which together name the group
+            let name = line
+                .split_ascii_whitespace()
+                .skip(1)
+                .collect::<Vec<&str>>()
+                .join(" ");
+            let material = current_group.material.clone();
+            let previous = std::mem::replace(&mut current_group, GroupBuilder::new(name, material));
+            groups.extend(previous.finish());
         } else {
             if line.starts_with("mtllib") {
                 material_lib = line
@@ -174,26 +502,87 @@ pub fn parse_obj(filepath: &str) -> Result<ParsedOBJ, OBJLoadError> {
                     .next()
                     .map(|s| s.to_string());
             } else if line.starts_with("usemtl") {
-                material = line
-                    .split_ascii_whitespace()
-                    .skip(1)
-                    .next()
-                    .map(|s| s.to_string());
+                let material = line.split_ascii_whitespace().nth(1).map(|s| s.to_string());
+
+                // a mesh only has one material, so switching materials part way through
+                // a group splits it into another part with the same name
+                if current_group.indices.is_empty() {
+                    current_group.material = material;
+                } else {
+                    let name = current_group.name.clone();
+                    let previous =
+                        std::mem::replace(&mut current_group, GroupBuilder::new(name, material));
+                    groups.extend(previous.finish());
+                }
             }
         }
     }
 
+    groups.extend(current_group.finish());
+
     Ok(ParsedOBJ {
-        model_verts,
         raw_verts,
+        raw_colors,
         raw_uvs,
         raw_normals,
-        indices,
-        material,
+        groups,
         material_lib,
     })
 }
 
+// accumulates the faces of one object/group while parsing. every group has its own
+// vertex and index lists so that it can become a separate mesh
+struct GroupBuilder {
+    name: String,
+    material: Option<String>,
+    model_verts: Vec<model::ModelVertex>,
+    indices: Vec<u32>,
+    needs_normal: Vec<bool>,
+    // for each vertex, the vertex whose generated normal it shares (see generate_normals)
+    shared_normals: Vec<usize>,
+    face_vert_index_map: HashMap<(u32, u32, u32, u32), usize>,
+    // the first vertex at each position and smoothing group, which the others there share
+    smoothed_verts: HashMap<(u32, u32), usize>,
+}
+
+impl GroupBuilder {
+    fn new(name: String, material: Option<String>) -> Self {
+        Self {
+            name,
+            material,
+            model_verts: Vec::new(),
+            indices: Vec::new(),
+            needs_normal: Vec::new(),
+            shared_normals: Vec::new(),
+            face_vert_index_map: HashMap::new(),
+            smoothed_verts: HashMap::new(),
+        }
+    }
+
+    // groups without any faces (eg an `o` line directly followed by a `g` line) are dropped
+    fn finish(mut self) -> Option<ParsedOBJGroup> {
+        if self.indices.is_empty() {
+            return None;
+        }
+
+        if self.needs_normal.contains(&true) {
+            generate_normals(
+                &mut self.model_verts,
+                &self.indices,
+                &self.needs_normal,
+                &self.shared_normals,
+            );
+        }
+
+        Some(ParsedOBJGroup {
+            name: self.name,
+            model_verts: self.model_verts,
+            indices: self.indices,
+            material: self.material,
+        })
+    }
+}
+
 fn parse_float_line(line: &str) -> Result<f32, std::num::ParseFloatError> {
     line.split_ascii_whitespace()
         .nth(1)
@@ -201,6 +590,93 @@ fn parse_float_line(line: &str) -> Result<f32, std::num::ParseFloatError> {
         .parse::<f32>()
 }
 
+// parses everything after the statement keyword of a map line: any number of options followed
+// by the file name, which may contain spaces
+fn parse_texture_map(line: &str) -> Result<TextureMap, String> {
+    fn next_float<'a>(
+        tokens: &mut impl Iterator<Item = &'a str>,
+        option: &str,
+    ) -> Result<f32, String> {
+        tokens
+            .next()
+            .and_then(|t| t.parse::<f32>().ok())
+            .ok_or_else(|| format!("expected a number after {}", option))
+    }
+
+    let mut tokens = line.split_ascii_whitespace().skip(1).peekable();
+    let mut options = TextureOptions::default();
+
+    while let Some(&option) = tokens.peek() {
+        if !option.starts_with('-') {
+            break;
+        }
+        tokens.next();
+
+        match option {
+            "-bm" => options.bump_multiplier = next_float(&mut tokens, option)?,
+            // u is required, v and w are optional
+            "-o" | "-s" | "-t" => {
+                let mut values = match option {
+                    "-s" => [1.0; 3],
+                    _ => [0.0; 3],
+                };
+                values[0] = next_float(&mut tokens, option)?;
+                for value in values.iter_mut().skip(1) {
+                    match tokens.peek().and_then(|t| t.parse::<f32>().ok()) {
+                        Some(v) => {
+                            *value = v;
+                            tokens.next();
+                        }
+                        None => break,
+                    }
+                }
+                match option {
+                    "-o" => options.offset = values,
+                    "-s" => options.scale = values,
+                    _ => (),
+                }
+            }
+            "-clamp" | "-blendu" | "-blendv" | "-cc" => {
+                let on = match tokens.next() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err(format!("expected on or off after {}", option)),
+                };
+                if option == "-clamp" {
+                    options.clamp = on;
+                }
+            }
+            "-normal" => {
+                options.normal_convention = match tokens.next() {
+                    Some("opengl" | "gl") => Some(model::NormalConvention::OpenGl),
+                    Some("directx" | "dx") => Some(model::NormalConvention::DirectX),
+                    _ => return Err(format!("expected opengl or directx after {}", option)),
+                }
+            }
+            "-boost" | "-texres" => {
+                next_float(&mut tokens, option)?;
+            }
+            "-mm" => {
+                next_float(&mut tokens, option)?;
+                next_float(&mut tokens, option)?;
+            }
+            "-imfchan" | "-type" => {
+                tokens
+                    .next()
+                    .ok_or_else(|| format!("expected a value after {}", option))?;
+            }
+            _ => return Err(format!("unknown texture option {}", option)),
+        }
+    }
+
+    let file_name = tokens.collect::<Vec<_>>().join(" ");
+    if file_name.is_empty() {
+        return Err("missing file name".to_string());
+    }
+
+    Ok(TextureMap { file_name, options })
+}
+
 fn parse_mtl_line(
     parsed: &mut ParsedMTL,
     line: &str,
@@ -214,6 +690,15 @@ fn parse_mtl_line(
             "could not parse".to_string() + s,
         ))
     };
+    let parse_map = |statement: &str| {
+        parse_texture_map(line).map_err(|msg| {
+            MTLLoadError::Parse(
+                filepath.to_string(),
+                linenum,
+                format!("{}: {}", statement, msg),
+            )
+        })
+    };
 
     if line.starts_with("Ka") {
         match parse_vector_line(line) {
@@ -242,6 +727,60 @@ fn parse_mtl_line(
                 return err_closure("Ks");
             }
         }
+    } else if line.starts_with("Ke") {
+        match parse_vector_line(line) {
+            Ok(v) => {
+                parsed.ke = Some([v[0], v[1], v[2]]);
+            }
+            Err(_) => {
+                return err_closure("Ke");
+            }
+        }
+    } else if line.starts_with("Pr") {
+        match parse_float_line(line) {
+            Ok(f) => {
+                parsed.pr = Some(f);
+            }
+            Err(_) => {
+                return err_closure("Pr");
+            }
+        }
+    } else if line.starts_with("Pm") {
+        match parse_float_line(line) {
+            Ok(f) => {
+                parsed.pm = Some(f);
+            }
+            Err(_) => {
+                return err_closure("Pm");
+            }
+        }
+    } else if line.starts_with("Ps") {
+        match parse_float_line(line) {
+            Ok(f) => {
+                parsed.ps = Some(f);
+            }
+            Err(_) => {
+                return err_closure("Ps");
+            }
+        }
+    } else if line.starts_with("anisor") {
+        match parse_float_line(line) {
+            Ok(f) => {
+                parsed.anisor = Some(f);
+            }
+            Err(_) => {
+                return err_closure("anisor");
+            }
+        }
+    } else if line.starts_with("aniso") {
+        match parse_float_line(line) {
+            Ok(f) => {
+                parsed.aniso = Some(f);
+            }
+            Err(_) => {
+                return err_closure("aniso");
+            }
+        }
     } else if line.starts_with("Ns") {
         match parse_float_line(line) {
             Ok(f) => {
@@ -251,6 +790,9 @@ fn parse_mtl_line(
                 return err_closure("Ns");
             }
         }
+    } else if line.starts_with("disp") {
+        // checked before d, which it also starts with
+        parsed.map_disp = Some(parse_map("disp")?);
     } else if line.starts_with("d") {
         match parse_float_line(line) {
             Ok(f) => {
@@ -260,6 +802,15 @@ fn parse_mtl_line(
                 return err_closure("d");
             }
         }
+    } else if line.starts_with("Tr") {
+        match parse_float_line(line) {
+            Ok(f) => {
+                parsed.tr = Some(f);
+            }
+            Err(_) => {
+                return err_closure("Tr");
+            }
+        }
     } else if line.starts_with("ni") {
         match parse_float_line(line) {
             Ok(f) => {
@@ -279,31 +830,49 @@ fn parse_mtl_line(
             }
         }
     } else if line.starts_with("map_Bump") {
-        parsed.map_bump = line
-            .split_ascii_whitespace()
-            .skip(1)
-            .next()
-            .map(|s| s.to_string());
+        parsed.map_bump = Some(parse_map("map_Bump")?);
     } else if line.starts_with("map_Kd") {
-        parsed.map_kd = line
-            .split_ascii_whitespace()
-            .skip(1)
-            .next()
-            .map(|s| s.to_string());
+        parsed.map_kd = Some(parse_map("map_Kd")?);
+    } else if line.starts_with("map_Ks") {
+        parsed.map_ks = Some(parse_map("map_Ks")?);
+    } else if line.starts_with("map_Ns") {
+        parsed.map_ns = Some(parse_map("map_Ns")?);
+    } else if line.starts_with("map_disp") {
+        // checked before map_d, which it also starts with
+        parsed.map_disp = Some(parse_map("map_disp")?);
+    } else if line.starts_with("map_d") {
+        parsed.map_d = Some(parse_map("map_d")?);
+    } else if line.starts_with("map_Ke") {
+        parsed.map_ke = Some(parse_map("map_Ke")?);
+    } else if line.starts_with("map_Pr") {
+        parsed.map_pr = Some(parse_map("map_Pr")?);
+    } else if line.starts_with("map_Pm") {
+        parsed.map_pm = Some(parse_map("map_Pm")?);
     }
 
     Ok(())
 }
 
-pub fn parse_mtl(filepath: &str, name: &str) -> Result<ParsedMTL, MTLLoadError> {
-    let file = std::fs::read_to_string(filepath).map_err(|e| MTLLoadError::FileNotFound(e))?;
+pub fn parse_mtl_file(filepath: &str, name: &str) -> Result<ParsedMTL, MTLLoadError> {
+    let file = crate::assets::open(filepath)
+        .map_err(|e| MTLLoadError::FileNotFound(filepath.to_string(), e))?;
+    parse_mtl(file, filepath, name)
+}
 
+pub fn parse_mtl(
+    reader: impl BufRead,
+    source: &str,
+    name: &str,
+) -> Result<ParsedMTL, MTLLoadError> {
     let mut parsed = ParsedMTL::default();
 
     let mtl_line_match = format!("newmtl {}", name);
     let mut match_found = false;
 
-    for (linenum, line) in file.lines().enumerate() {
+    for (linenum, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| MTLLoadError::Read(source.to_string(), e))?;
+        let line = line.as_str();
+
         if line.starts_with("#") {
             continue;
         } else if line.starts_with("newmtl") {
@@ -316,29 +885,36 @@ pub fn parse_mtl(filepath: &str, name: &str) -> Result<ParsedMTL, MTLLoadError>
                 }
             }
         } else if match_found {
-            parse_mtl_line(&mut parsed, line, linenum, filepath)?;
+            parse_mtl_line(&mut parsed, line, linenum, source)?;
         }
     }
 
     if !match_found {
-        Err(MTLLoadError::MtlNotFound(format!(
-            "{} not found in {}",
-            name, filepath
-        )))
+        Err(MTLLoadError::MtlNotFound(
+            name.to_string(),
+            source.to_string(),
+        ))
     } else {
         Ok(parsed)
     }
 }
 
-pub fn parse_all_mtls(filepath: &str) -> Result<Vec<ParsedMTL>, MTLLoadError> {
-    let file = std::fs::read_to_string(filepath).map_err(|e| MTLLoadError::FileNotFound(e))?;
+pub fn parse_all_mtls_file(filepath: &str) -> Result<Vec<ParsedMTL>, MTLLoadError> {
+    let file = crate::assets::open(filepath)
+        .map_err(|e| MTLLoadError::FileNotFound(filepath.to_string(), e))?;
+    parse_all_mtls(file, filepath)
+}
 
+pub fn parse_all_mtls(reader: impl BufRead, source: &str) -> Result<Vec<ParsedMTL>, MTLLoadError> {
     let mut all_parsed = Vec::new();
     let mut current_parsed = ParsedMTL::default();
 
     let mut first_mtl = true;
 
-    for (linenum, line) in file.lines().enumerate() {
+    for (linenum, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| MTLLoadError::Read(source.to_string(), e))?;
+        let line = line.as_str();
+
         if line.starts_with("#") {
             continue;
         } else if line.starts_with("newmtl") {
@@ -350,7 +926,7 @@ pub fn parse_all_mtls(filepath: &str) -> Result<Vec<ParsedMTL>, MTLLoadError> {
             }
             current_parsed.name = line.split_ascii_whitespace().nth(1).map(|s| s.to_string());
         } else {
-            parse_mtl_line(&mut current_parsed, line, linenum, filepath)?;
+            parse_mtl_line(&mut current_parsed, line, linenum, source)?;
         }
     }
 
@@ -358,3 +934,171 @@ pub fn parse_all_mtls(filepath: &str) -> Result<Vec<ParsedMTL>, MTLLoadError> {
 
     Ok(all_parsed)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    // twice the signed area of the triangle, seen from +z
+    fn signed_area(positions: &[Vector3<f32>], [a, b, c]: [usize; 3]) -> f32 {
+        (positions[b] - positions[a])
+            .cross(positions[c] - positions[a])
+            .z
+    }
+
+    // an arrow pointing along +x, counter clockwise from +z, with a notch cut into its tail
+    // at index 5 so that a fan from the first vertex would cover the notch
+    fn concave_arrow() -> Vec<Vector3<f32>> {
+        vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(3.0, 1.0, 0.0),
+            Vector3::new(2.0, 2.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn quad_is_fanned_from_the_first_vertex() {
+        let quad = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        assert_eq!(triangulate_polygon(&quad), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn concave_polygon_is_ear_clipped() {
+        let arrow = concave_arrow();
+        let triangles = triangulate_polygon(&arrow);
+        assert_eq!(triangles.len(), arrow.len() - 2);
+
+        // a fan from the first vertex would need a flipped triangle across the notch to end up
+        // with the polygon's area (4, from the shoelace formula)
+        let area: f32 = triangles.iter().map(|&t| signed_area(&arrow, t)).sum();
+        assert!((area / 2.0 - 4.0).abs() < 1e-5, "area {}", area / 2.0);
+        for &triangle in &triangles {
+            assert!(
+                signed_area(&arrow, triangle) > 0.0,
+                "{:?} is flipped",
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn winding_is_preserved() {
+        let mut arrow = concave_arrow();
+        arrow.reverse();
+        for triangle in triangulate_polygon(&arrow) {
+            assert!(
+                signed_area(&arrow, triangle) < 0.0,
+                "{:?} is flipped",
+                triangle
+            );
+        }
+
+        let clockwise_quad = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+        ];
+        for triangle in triangulate_polygon(&clockwise_quad) {
+            assert!(
+                signed_area(&clockwise_quad, triangle) < 0.0,
+                "{:?} is flipped",
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn quad_only_obj_is_triangulated() {
+        let obj = "\
+o strip
+s 1
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 2 0 0
+v 2 1 0
+f 1 2 3 4
+f 2 5 6 3
+";
+        let parsed = parse_obj(std::io::Cursor::new(obj), "strip.obj").unwrap();
+        assert_eq!(parsed.groups.len(), 1);
+
+        let group = &parsed.groups[0];
+        assert_eq!(group.name, "strip");
+        // the two quads share an edge, and are smoothed together so its verts are shared too
+        assert_eq!(group.model_verts.len(), 6);
+        assert_eq!(group.indices.len(), 12);
+
+        let positions: Vec<Vector3<f32>> = group
+            .model_verts
+            .iter()
+            .map(|v| Vector3::from(v.position))
+            .collect();
+        for triangle in group.indices.chunks(3) {
+            let triangle = [0, 1, 2].map(|i| triangle[i] as usize);
+            assert!(
+                signed_area(&positions, triangle) > 0.0,
+                "{:?} is flipped",
+                triangle
+            );
+        }
+        for vert in &group.model_verts {
+            assert_eq!(vert.normal, [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn relative_indices_count_back_from_the_last_vertex() {
+        let obj = "\
+v 9 9 9
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+f -3/-3 -2/-2 -1/-1
+";
+        let parsed = parse_obj(std::io::Cursor::new(obj), "relative.obj").unwrap();
+        let group = &parsed.groups[0];
+        let positions: Vec<[f32; 3]> = group.model_verts.iter().map(|v| v.position).collect();
+        assert_eq!(
+            positions,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(group.model_verts[1].tex_coords, [1.0, 0.0]);
+        assert_eq!(group.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn zero_and_out_of_range_indices_are_errors() {
+        for face in [
+            "f 0 1 2",
+            "f 1 2 4",
+            "f -4 -2 -1",
+            "f 1/2 2/2 3/2",
+            "f 1//1 2 3",
+        ] {
+            let obj = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n{}\n", face);
+            assert!(
+                matches!(
+                    parse_obj(std::io::Cursor::new(obj), "bad.obj"),
+                    Err(OBJLoadError::Parse(_, 4, _))
+                ),
+                "{} was accepted",
+                face
+            );
+        }
+    }
+}
@@ -0,0 +1,354 @@
+// weighted blended order independent transparency (mcguire and bavoil 2013), an alternative to
+// drawing transparent meshes sorted back to front. every transparent fragment is added into an
+// accumulation target, weighted so nearer and more opaque fragments count for more, and its
+// alpha is multiplied into a revealage target, the fraction of the background still showing.
+// a resolve pass then blends the average color over the scene by the coverage. the result is
+// an approximation, but one which doesn't depend on draw order, so heavily overlapping
+// transparent surfaces don't pop as the camera moves through them
+
+use crate::{camera, model::Vertex, texture};
+
+// the start of shader.wgsl's fragment entry point, which is renamed so the accumulation entry
+// point can call it
+const FRAGMENT_ENTRY: &str =
+    "@fragment\nfn fragment_main(in: VertexOutput) -> @location(0) vec4f {";
+const SHADE_FUNCTION: &str = "fn shade(in: VertexOutput) -> vec4f {";
+
+const ACCUMULATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+// builds the accumulation variant of a shader with shader.wgsl's fragment entry point
+pub fn shader_source(base: &str) -> Option<String> {
+    base.contains(FRAGMENT_ENTRY).then(|| {
+        format!(
+            "{}{}",
+            base.replacen(FRAGMENT_ENTRY, SHADE_FUNCTION, 1),
+            include_str!("shaders/oit_accumulate.wgsl")
+        )
+    })
+}
+
+pub struct WeightedBlendedOit {
+    accumulation: texture::Texture,
+    revealage: texture::Texture,
+    accumulate_pipeline: wgpu::RenderPipeline,
+    // only when the adapter supports ray queries
+    accumulate_pipeline_raytraced: Option<wgpu::RenderPipeline>,
+    resolve_bind_group_layout: wgpu::BindGroupLayout,
+    resolve_bind_group: wgpu::BindGroup,
+    resolve_pipeline: wgpu::RenderPipeline,
+}
+
+impl WeightedBlendedOit {
+    // the accumulation pipelines share the scene pipelines' layout, and are built from the
+    // scene shader and its ray traced variant
+    pub fn new(
+        device: &wgpu::Device,
+        scene_layout: &wgpu::PipelineLayout,
+        scene_source: &str,
+        raytraced_source: Option<&str>,
+        color_format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        depth_mode: camera::DepthMode,
+    ) -> anyhow::Result<Self> {
+        let (accumulation, revealage) = create_targets(device, size);
+
+        let create_accumulate_pipeline = |label, source: &str| {
+            let source = shader_source(source)
+                .ok_or_else(|| anyhow::anyhow!("{} has no fragment_main to wrap", label))?;
+            Ok::<_, anyhow::Error>(create_accumulate_pipeline(
+                device,
+                scene_layout,
+                wgpu::ShaderModuleDescriptor {
+                    label: Some(label),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                },
+                depth_mode,
+            ))
+        };
+        let accumulate_pipeline =
+            create_accumulate_pipeline("transparency accumulation shader", scene_source)?;
+        let accumulate_pipeline_raytraced = raytraced_source
+            .map(|source| {
+                create_accumulate_pipeline("raytraced transparency accumulation shader", source)
+            })
+            .transpose()?;
+
+        let target_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let resolve_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("transparency resolve bind group layout"),
+                entries: &[target_entry(0), target_entry(1)],
+            });
+        let resolve_bind_group = create_resolve_bind_group(
+            device,
+            &resolve_bind_group_layout,
+            &accumulation,
+            &revealage,
+        );
+
+        let resolve_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("transparency resolve pipeline layout"),
+                bind_group_layouts: &[&resolve_bind_group_layout],
+                immediate_size: 0,
+            });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/oit_resolve.wgsl"));
+        let resolve_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("transparency resolve pipeline"),
+            layout: Some(&resolve_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // a single triangle covering the screen, generated in the vertex shader
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Ok(Self {
+            accumulation,
+            revealage,
+            accumulate_pipeline,
+            accumulate_pipeline_raytraced,
+            resolve_bind_group_layout,
+            resolve_bind_group,
+            resolve_pipeline,
+        })
+    }
+
+    // recreates the targets when the scene is drawn at another size, eg after a resize or
+    // for a panorama's faces
+    pub fn fit(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        if self.accumulation.texture.size() == size {
+            return;
+        }
+        (self.accumulation, self.revealage) = create_targets(device, size);
+        self.resolve_bind_group = create_resolve_bind_group(
+            device,
+            &self.resolve_bind_group_layout,
+            &self.accumulation,
+            &self.revealage,
+        );
+    }
+
+    // the ray traced pipeline when asked for and supported, the plain one otherwise
+    pub fn accumulate_pipeline(&self, raytraced: bool) -> &wgpu::RenderPipeline {
+        match &self.accumulate_pipeline_raytraced {
+            Some(pipeline) if raytraced => pipeline,
+            _ => &self.accumulate_pipeline,
+        }
+    }
+
+    // a pass which clears the targets and tests against the scene's depth without writing it,
+    // for drawing the transparent meshes with accumulate_pipeline in any order
+    pub fn begin_accumulation<'a>(
+        &self,
+        command_encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("transparency accumulation pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.accumulation.view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                // everything is revealed until something is drawn in front of it
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.revealage.view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        })
+    }
+
+    // blends the accumulated transparency over the scene in the target
+    pub fn resolve(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("transparency resolve pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.resolve_pipeline);
+        render_pass.set_bind_group(0, &self.resolve_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    size: wgpu::Extent3d,
+) -> (texture::Texture, texture::Texture) {
+    let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+    let accumulation =
+        texture::Texture::builder("transparency accumulation", size.width, size.height)
+            .format(ACCUMULATION_FORMAT)
+            .usage(usage)
+            .build(device);
+    let revealage = texture::Texture::builder("transparency revealage", size.width, size.height)
+        .format(REVEALAGE_FORMAT)
+        .usage(usage)
+        .build(device);
+    (accumulation, revealage)
+}
+
+fn create_resolve_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    accumulation: &texture::Texture,
+    revealage: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("transparency resolve bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&accumulation.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&revealage.view),
+            },
+        ],
+    })
+}
+
+fn create_accumulate_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader_descriptor: wgpu::ShaderModuleDescriptor,
+    depth_mode: camera::DepthMode,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(shader_descriptor);
+
+    // the weighted colors add up, and each fragment's alpha takes its share of the background
+    // away, whatever order they arrive in
+    let additive = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+    let revealing = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("transparency accumulation pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vertex_main"),
+            buffers: &[crate::model::ModelVertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fragment_main"),
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: ACCUMULATION_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                Some(wgpu::ColorTargetState {
+                    format: REVEALAGE_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: revealing,
+                        alpha: revealing,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+            ],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        // tested against the opaque scene, but never written, so no transparent surface hides
+        // another
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,104 @@
+// options given on the command line when starting natively, eg
+//
+//     cargo run -- --simplify 0.25
+//
+//     --simplify <ratio>    simplifies every model as it's loaded, keeping about that fraction
+//                           of its triangles. see mesh_tools::simplify
+//     --recompute-normals <degrees>
+//                           replaces every loaded model's normals with smooth ones, creased
+//                           where faces meet at more than that angle. see
+//                           mesh_tools::recompute_normals
+//     --z-planes <near> <far>
+//                           where the camera's near and far planes start
+//     --fit-z-planes        fits the near and far planes around the scene every frame, see
+//                           camera::Projection::fit_z_planes
+//     --config <path>       reads the settings from there rather than config.toml
+//     --set <name> <value>  overrides an entry of the config file, eg --set window.vsync false.
+//                           see config.rs
+
+use anyhow::Context;
+
+use crate::config;
+
+#[derive(Debug, Clone, Default)]
+pub struct StartupOptions {
+    pub simplify: Option<f32>,
+    pub recompute_normals: Option<cgmath::Deg<f32>>,
+    pub z_planes: Option<(f32, f32)>,
+    pub fit_z_planes: bool,
+    pub config_file: Option<String>,
+    // the --set entries, as they were given
+    pub config_overrides: Vec<(String, String)>,
+    // the config file's settings with the overrides applied, loaded once the arguments are
+    // parsed (see run_viewer)
+    pub config: config::Config,
+}
+
+impl StartupOptions {
+    // the arguments after the program's name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--simplify" => {
+                    let ratio: f32 = args
+                        .next()
+                        .context("--simplify needs a ratio, eg --simplify 0.25")?
+                        .parse()
+                        .context("--simplify's ratio isn't a number")?;
+                    if !(ratio > 0.0 && ratio <= 1.0) {
+                        anyhow::bail!("--simplify's ratio has to be above 0 and at most 1");
+                    }
+                    options.simplify = Some(ratio);
+                }
+                "--recompute-normals" => {
+                    let degrees: f32 = args
+                        .next()
+                        .context("--recompute-normals needs an angle, eg --recompute-normals 60")?
+                        .parse()
+                        .context("--recompute-normals's angle isn't a number")?;
+                    options.recompute_normals = Some(cgmath::Deg(degrees.clamp(0.0, 180.0)));
+                }
+                "--z-planes" => {
+                    let mut distance = |name: &str| -> anyhow::Result<f32> {
+                        args.next()
+                            .context(
+                                "--z-planes needs a near and far distance, eg --z-planes 0.1 100",
+                            )?
+                            .parse()
+                            .with_context(|| {
+                                format!("--z-planes's {} distance isn't a number", name)
+                            })
+                    };
+                    let near = distance("near")?;
+                    let far = distance("far")?;
+                    if !(near > 0.0 && far > near) {
+                        anyhow::bail!(
+                            "--z-planes's near distance has to be above 0 and below the far one"
+                        );
+                    }
+                    options.z_planes = Some((near, far));
+                }
+                "--fit-z-planes" => options.fit_z_planes = true,
+                "--config" => {
+                    options.config_file = Some(
+                        args.next()
+                            .context("--config needs a path to a toml file")?,
+                    );
+                }
+                "--set" => {
+                    let name = args
+                        .next()
+                        .context("--set needs a name and a value, eg --set window.vsync false")?;
+                    let value = args
+                        .next()
+                        .with_context(|| format!("--set {} needs a value", name))?;
+                    options.config_overrides.push((name, value));
+                }
+                _ => anyhow::bail!("unknown option {}", arg),
+            }
+        }
+        Ok(options)
+    }
+}
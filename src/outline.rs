@@ -0,0 +1,149 @@
+// an outline around the selected model, drawn over everything so it can be found behind other
+// models too. the model is drawn twice with the stencil: once marking its silhouette, then
+// again pushed out along its normals, coloring only what's outside the marks. needs the scene's
+// depth texture to have a stencil, see texture::Texture::DEPTH_FORMAT, which the scene pass
+// clears to 0 every frame
+
+use crate::{model, render_stats, scene};
+
+// what the mark pass leaves in the stencil
+const MARK: u32 = 1;
+
+pub struct Outline {
+    mark_pipeline: wgpu::RenderPipeline,
+    outline_pipeline: wgpu::RenderPipeline,
+}
+
+impl Outline {
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        per_object_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("outline pipeline layout"),
+            bind_group_layouts: &[per_frame_layout, per_object_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "outline.wgsl",
+            include_str!("shaders/outline.wgsl"),
+        ));
+        let create_pipeline = |label: &str, entry_point: &str, write_mask, stencil| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    buffers: &[<model::ModelVertex as model::Vertex>::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fragment_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                // both faces, so open meshes and ones wound the wrong way still mark and outline
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                // not depth tested, so the outline shows through whatever is in front of it
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: depth_format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil,
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+
+        let mark = wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Always,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Replace,
+        };
+        let mark_pipeline = create_pipeline(
+            "outline mark pipeline",
+            "mark_vertex_main",
+            wgpu::ColorWrites::empty(),
+            wgpu::StencilState {
+                front: mark,
+                back: mark,
+                read_mask: 0,
+                write_mask: 0xff,
+            },
+        );
+        let outside = wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::NotEqual,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Keep,
+        };
+        let outline_pipeline = create_pipeline(
+            "outline pipeline",
+            "outline_vertex_main",
+            wgpu::ColorWrites::ALL,
+            wgpu::StencilState {
+                front: outside,
+                back: outside,
+                read_mask: 0xff,
+                write_mask: 0,
+            },
+        );
+
+        Self {
+            mark_pipeline,
+            outline_pipeline,
+        }
+    }
+
+    // draws into a pass which has the scene's depth and stencil attached, with the stencil
+    // cleared. does nothing when the model isn't in the scene
+    pub fn draw(
+        &self,
+        render_pass: &mut render_stats::CountingRenderPass,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+        scene: &scene::Scene,
+        selected: scene::ModelId,
+    ) {
+        let Some(index) = scene.objects().iter().position(|o| o.id == selected) else {
+            return;
+        };
+        let meshes = &scene.objects()[index].model.meshes;
+
+        render_pass.set_stencil_reference(MARK);
+        render_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        render_pass.set_bind_group(1, &scene.bind_group, &[scene.transform_offset(index)]);
+        // every mesh is marked before any is outlined, so meshes of the model don't outline
+        // over each other
+        for pipeline in [&self.mark_pipeline, &self.outline_pipeline] {
+            render_pass.set_pipeline(pipeline);
+            for mesh in meshes.iter().filter(|mesh| mesh.is_visible) {
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+                render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+            }
+        }
+    }
+}
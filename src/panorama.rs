@@ -0,0 +1,93 @@
+// 360 degree panoramas of the scene, for authoring environment maps and sharing views. the
+// scene is rendered into the six faces of a cube around the camera, which are then resampled
+// on the cpu into an equirectangular image twice as wide as it is tall. the panorama is world
+// aligned: +y is up, the center looks along -z and the right half turns towards +x
+
+use anyhow::Context;
+use cgmath::{InnerSpace, Vector3};
+
+// the forward and up directions of each cube face. any up which isn't parallel to forward
+// works, since sampling uses the same basis the face was rendered with
+pub const FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+// a face's forward, right and up as seen by a right handed look_to camera
+fn face_basis(face: usize) -> [Vector3<f32>; 3] {
+    let (forward, up) = FACES[face];
+    let forward = Vector3::from(forward);
+    let right = forward.cross(Vector3::from(up)).normalize();
+    [forward, right, right.cross(forward)]
+}
+
+// resamples the faces, rendered with a 90 degree fov in the order of FACES, into an
+// equirectangular image `width` pixels wide
+pub fn equirectangular(faces: &[image::RgbaImage], width: u32) -> image::RgbaImage {
+    let bases = (0..FACES.len()).map(face_basis).collect::<Vec<_>>();
+    let height = (width / 2).max(1);
+
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let longitude = ((x as f32 + 0.5) / width as f32 * 2.0 - 1.0) * std::f32::consts::PI;
+        let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * std::f32::consts::PI;
+        let direction = Vector3::new(
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+            -latitude.cos() * longitude.cos(),
+        );
+
+        // the face the direction points into most directly
+        let (face, [forward, right, up]) = bases
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| direction.dot(a[0]).total_cmp(&direction.dot(b[0])))
+            .map(|(face, basis)| (face, *basis))
+            .unwrap_or((0, bases[0]));
+
+        // project onto the face, whose edges are at +-1 for a 90 degree fov
+        let depth = direction.dot(forward);
+        let u = (direction.dot(right) / depth + 1.0) * 0.5;
+        let v = (1.0 - direction.dot(up) / depth) * 0.5;
+
+        let image = &faces[face];
+        let px = ((u * image.width() as f32) as u32).min(image.width() - 1);
+        let py = ((v * image.height() as f32) as u32).min(image.height() - 1);
+        *image.get_pixel(px, py)
+    })
+}
+
+// saves as radiance hdr for .hdr paths and by extension otherwise. the frames are 8 bit srgb,
+// so an hdr file is just the same colors in linear floats, without any range above 1
+pub fn save(panorama: &image::RgbaImage, path: &str) -> anyhow::Result<()> {
+    if !path.to_ascii_lowercase().ends_with(".hdr") {
+        return panorama
+            .save(path)
+            .with_context(|| format!("could not write {}", path));
+    }
+
+    let to_linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let pixels = panorama
+        .pixels()
+        .map(|p| image::Rgb([to_linear(p[0]), to_linear(p[1]), to_linear(p[2])]))
+        .collect::<Vec<_>>();
+
+    let file = std::fs::File::create(path).with_context(|| format!("could not create {}", path))?;
+    image::codecs::hdr::HdrEncoder::new(std::io::BufWriter::new(file))
+        .encode(
+            &pixels,
+            panorama.width() as usize,
+            panorama.height() as usize,
+        )
+        .with_context(|| format!("could not write {}", path))
+}
@@ -0,0 +1,612 @@
+// particles simulated and drawn on the gpu, for sparks, fountains, rain and the like. each
+// emitter owns a fixed range of slots in one particle buffer, as many as it can have alive at
+// once (its rate times its lifetime), and emitting respawns the oldest slots of its range in
+// turn, so nothing has to look for free slots or count the living.
+//
+// every frame a compute pass moves the particles and collides them with the scene through the
+// depth buffer. it still holds the last frame, so each particle is projected with the last
+// frame's camera, and one which has gone behind the surface there by less than the thickness
+// surfaces are taken to have has hit it, with the surface's normal rebuilt from the depths
+// around it. only what was on screen can be hit, so particles fall through anything off
+// screen or behind something else. they're then drawn as discs facing the camera, blended
+// over the scene without writing depth
+
+use cgmath::SquareMatrix;
+
+use crate::{camera, texture, uploads::UploadBelt};
+
+// the slots shared by every emitter
+const MAX_PARTICLES: u32 = 1 << 16;
+const MAX_EMITTERS: usize = 16;
+const WORKGROUP_SIZE: u32 = 64;
+// how far behind the depth buffer a particle can be and still be touching the surface, in
+// world units. further behind, it's taken to be passing behind the surface instead
+const COLLISION_THICKNESS: f32 = 0.5;
+// see Particle in particle_simulation.wgsl
+const PARTICLE_SIZE: wgpu::BufferAddress = 64;
+
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    let flags = wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::VERTEX_STORAGE;
+    adapter.get_downlevel_capabilities().flags.contains(flags)
+}
+
+// what a particle does when it reaches the depth buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collision {
+    // passes through everything
+    None,
+    // bounces off, keeping `restitution` of its speed into the surface
+    Bounce { restitution: f32 },
+    // disappears on contact, like rain
+    Kill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emitter {
+    // particles start at random within `extent` either side of the position
+    pub position: [f32; 3],
+    pub extent: [f32; 3],
+    pub velocity: [f32; 3],
+    // how far each particle's velocity can differ from the emitter's along each axis
+    pub spread: f32,
+    // particles per second
+    pub rate: f32,
+    // in seconds
+    pub lifetime: f32,
+    // the discs' diameter in world units
+    pub size: f32,
+    // linear rgb and alpha, which fades out over each particle's life
+    pub color: [f32; 4],
+    // the acceleration along y, eg -9.8
+    pub gravity: f32,
+    pub collision: Collision,
+}
+
+impl Default for Emitter {
+    // a small fountain at the origin, bouncing off whatever it lands on
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            extent: [0.0; 3],
+            velocity: [0.0, 6.0, 0.0],
+            spread: 1.5,
+            rate: 200.0,
+            lifetime: 3.0,
+            size: 0.05,
+            color: [1.0, 0.8, 0.4, 1.0],
+            gravity: -9.8,
+            collision: Collision::Bounce { restitution: 0.5 },
+        }
+    }
+}
+
+// a handle to an emitter. ids aren't reused, like model ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EmitterId(u32);
+
+impl std::fmt::Display for EmitterId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EmitterUniform {
+    position: [f32; 3],
+    lifetime: f32,
+    extent: [f32; 3],
+    size: f32,
+    velocity: [f32; 3],
+    spread: f32,
+    color: [f32; 4],
+    gravity: f32,
+    // 0 none, 1 bounce, 2 kill
+    collision: u32,
+    restitution: f32,
+    // the emitter's range of slots
+    first: u32,
+    capacity: u32,
+    // the slots respawned this frame, counted from the start of the range and wrapping around
+    spawn_start: u32,
+    spawn_count: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimulationUniform {
+    // the camera the depth buffer was drawn with
+    previous_view_proj: [[f32; 4]; 4],
+    previous_inverse_view_proj: [[f32; 4]; 4],
+    previous_camera_position: [f32; 3],
+    delta_seconds: f32,
+    // changes every frame, so respawned particles don't repeat
+    seed: u32,
+    emitter_count: u32,
+    // 0 while the depth buffer holds nothing to collide with, eg straight after a resize
+    has_previous_depth: u32,
+    clear_depth: f32,
+    collision_thickness: f32,
+    _padding: [u32; 3],
+}
+
+// where an emitter's slots are, and how far through them it's emitted
+struct EmitterSlots {
+    id: EmitterId,
+    emitter: Emitter,
+    first: u32,
+    capacity: u32,
+    // the next slot to respawn, from the start of the range
+    cursor: u32,
+    // particles owed from frames too short to emit a whole one
+    owed: f32,
+}
+
+pub struct ParticleSystem {
+    emitters: Vec<EmitterSlots>,
+    next_id: u32,
+    uniform: SimulationUniform,
+    uniform_buffer: wgpu::Buffer,
+    emitter_buffer: wgpu::Buffer,
+    particle_buffer: wgpu::Buffer,
+    simulation_layout: wgpu::BindGroupLayout,
+    // reads the depth texture, so it's made again whenever that is
+    simulation_bind_group: wgpu::BindGroup,
+    render_bind_group: wgpu::BindGroup,
+    simulation_pipeline: wgpu::ComputePipeline,
+    render_pipeline: wgpu::RenderPipeline,
+    // the last update's camera, which the next frame's depth buffer is drawn with
+    view_proj: cgmath::Matrix4<f32>,
+    camera_position: [f32; 3],
+    // set by update, so the particles only move once a frame however many times the scene is
+    // drawn, eg once per eye
+    needs_step: bool,
+    // set when the slots were given out again, so the old particles don't carry over
+    needs_clear: bool,
+    // the slots in use, the end of the last emitter's range
+    slot_count: u32,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        per_frame_layout: &wgpu::BindGroupLayout,
+        depth_texture: &texture::Texture,
+        color_format: wgpu::TextureFormat,
+        depth_mode: camera::DepthMode,
+        sample_count: u32,
+    ) -> Self {
+        let uniform = SimulationUniform {
+            previous_view_proj: cgmath::Matrix4::identity().into(),
+            previous_inverse_view_proj: cgmath::Matrix4::identity().into(),
+            previous_camera_position: [0.0; 3],
+            delta_seconds: 0.0,
+            seed: 0,
+            emitter_count: 0,
+            has_previous_depth: 0,
+            clear_depth: depth_mode.clear_depth(),
+            collision_thickness: COLLISION_THICKNESS,
+            _padding: [0; 3],
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("particle simulation uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+        let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle emitter buffer"),
+            size: (MAX_EMITTERS * std::mem::size_of::<EmitterUniform>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // zeroed, which leaves every particle dead: its age isn't below its lifetime of 0
+        let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle buffer"),
+            size: MAX_PARTICLES as wgpu::BufferAddress * PARTICLE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let simulation_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle simulation bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // emitters
+                storage_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                // particles
+                storage_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                // the last frame's depth
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let render_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle render bind group layout"),
+            entries: &[storage_entry(0, wgpu::ShaderStages::VERTEX, true)],
+        });
+
+        let simulation_pipeline = crate::vxgi::create_compute_pipeline(
+            device,
+            "particle simulation pipeline",
+            &[&simulation_layout],
+            crate::shaders::wgsl(
+                "particle_simulation.wgsl",
+                include_str!("shaders/particle_simulation.wgsl"),
+            ),
+        );
+
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "particles.wgsl",
+            include_str!("shaders/particles.wgsl"),
+        ));
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("particle render pipeline layout"),
+                bind_group_layouts: &[per_frame_layout, &render_layout],
+                immediate_size: 0,
+            });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("particle render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // two triangles per particle, generated in the vertex shader
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // hidden by the scene, but not by each other
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let simulation_bind_group = Self::create_simulation_bind_group(
+            device,
+            &simulation_layout,
+            &uniform_buffer,
+            &emitter_buffer,
+            &particle_buffer,
+            depth_texture,
+        );
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle render bind group"),
+            layout: &render_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: particle_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            emitters: Vec::new(),
+            next_id: 0,
+            uniform,
+            uniform_buffer,
+            emitter_buffer,
+            particle_buffer,
+            simulation_layout,
+            simulation_bind_group,
+            render_bind_group,
+            simulation_pipeline,
+            render_pipeline,
+            view_proj: cgmath::Matrix4::identity(),
+            camera_position: [0.0; 3],
+            needs_step: false,
+            needs_clear: false,
+            slot_count: 0,
+        }
+    }
+
+    fn create_simulation_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        emitter_buffer: &wgpu::Buffer,
+        particle_buffer: &wgpu::Buffer,
+        depth_texture: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        // the stencil can't be read along with the depth
+        let depth_view = depth_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                label: Some("particle collision depth view"),
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle simulation bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: emitter_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+            ],
+        })
+    }
+
+    // the new depth texture starts out empty, so nothing collides until it's been drawn into
+    pub fn resize(&mut self, device: &wgpu::Device, depth_texture: &texture::Texture) {
+        self.simulation_bind_group = Self::create_simulation_bind_group(
+            device,
+            &self.simulation_layout,
+            &self.uniform_buffer,
+            &self.emitter_buffer,
+            &self.particle_buffer,
+            depth_texture,
+        );
+        self.uniform.has_previous_depth = 0;
+    }
+
+    pub fn add(&mut self, emitter: Emitter) -> anyhow::Result<EmitterId> {
+        if self.emitters.len() >= MAX_EMITTERS {
+            anyhow::bail!("there can only be {} particle emitters", MAX_EMITTERS);
+        }
+        let id = EmitterId(self.next_id);
+        self.emitters.push(EmitterSlots {
+            id,
+            emitter,
+            first: 0,
+            capacity: 0,
+            cursor: 0,
+            owed: 0.0,
+        });
+        if let Err(e) = self.allocate_slots() {
+            self.emitters.pop();
+            self.allocate_slots()?;
+            return Err(e);
+        }
+        self.next_id += 1;
+        Ok(id)
+    }
+
+    // changes an emitter, returning false if there's no such emitter. its particles carry on
+    // unless it needs a different number of slots
+    pub fn set(&mut self, id: EmitterId, emitter: Emitter) -> anyhow::Result<bool> {
+        let Some(index) = self.emitters.iter().position(|e| e.id == id) else {
+            return Ok(false);
+        };
+        let previous = std::mem::replace(&mut self.emitters[index].emitter, emitter);
+        if slots_needed(&emitter) != self.emitters[index].capacity
+            && let Err(e) = self.allocate_slots()
+        {
+            self.emitters[index].emitter = previous;
+            self.allocate_slots()?;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    // returns false if there's no such emitter
+    pub fn remove(&mut self, id: EmitterId) -> bool {
+        let count = self.emitters.len();
+        self.emitters.retain(|e| e.id != id);
+        if self.emitters.len() == count {
+            return false;
+        }
+        // can't fail, since fewer slots are needed than before
+        let _ = self.allocate_slots();
+        true
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.emitters.is_empty()
+    }
+
+    // gives every emitter its range again, one after the other. the particles alive are
+    // cleared, since their slots may now be another emitter's
+    fn allocate_slots(&mut self) -> anyhow::Result<()> {
+        let total = self
+            .emitters
+            .iter()
+            .map(|e| slots_needed(&e.emitter) as u64)
+            .sum::<u64>();
+        if total > MAX_PARTICLES as u64 {
+            anyhow::bail!(
+                "the emitters would have {} particles alive at once, more than the {} there's \
+                 room for",
+                total,
+                MAX_PARTICLES
+            );
+        }
+
+        let mut first = 0;
+        for slots in &mut self.emitters {
+            slots.first = first;
+            slots.capacity = slots_needed(&slots.emitter);
+            slots.cursor = 0;
+            slots.owed = 0.0;
+            first += slots.capacity;
+        }
+        self.slot_count = first;
+        self.needs_clear = true;
+        Ok(())
+    }
+
+    // emits this frame's particles and writes what the next step needs. `dt` is the scene's
+    // time, so the particles hold still while it's paused
+    pub fn update(
+        &mut self,
+        uploads: &mut UploadBelt,
+        camera: &camera::Camera,
+        projection: &camera::Projection,
+        dt: f32,
+    ) {
+        let emitters = self
+            .emitters
+            .iter_mut()
+            .map(|slots| {
+                let emitter = &slots.emitter;
+                slots.owed += emitter.rate.max(0.0) * dt;
+                let spawn_count = (slots.owed.floor() as u32).min(slots.capacity);
+                slots.owed -= slots.owed.floor();
+                let spawn_start = slots.cursor;
+                if slots.capacity > 0 {
+                    slots.cursor = (slots.cursor + spawn_count) % slots.capacity;
+                }
+
+                let (collision, restitution) = match emitter.collision {
+                    Collision::None => (0, 0.0),
+                    Collision::Bounce { restitution } => (1, restitution),
+                    Collision::Kill => (2, 0.0),
+                };
+                EmitterUniform {
+                    position: emitter.position,
+                    lifetime: emitter.lifetime,
+                    extent: emitter.extent,
+                    size: emitter.size,
+                    velocity: emitter.velocity,
+                    spread: emitter.spread,
+                    color: emitter.color,
+                    gravity: emitter.gravity,
+                    collision,
+                    restitution,
+                    first: slots.first,
+                    capacity: slots.capacity,
+                    spawn_start,
+                    spawn_count,
+                    _padding: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        if !emitters.is_empty() {
+            uploads.write(&self.emitter_buffer, 0, bytemuck::cast_slice(&emitters));
+        }
+
+        // the depth buffer the step reads was drawn with the camera of the update before
+        self.uniform.previous_view_proj = self.view_proj.into();
+        self.uniform.previous_inverse_view_proj = self
+            .view_proj
+            .invert()
+            .unwrap_or(cgmath::Matrix4::identity())
+            .into();
+        self.uniform.previous_camera_position = self.camera_position;
+        self.view_proj = projection.perspective_matrix() * camera.view_matrix();
+        self.camera_position = camera.position.into();
+        self.uniform.delta_seconds = dt;
+        self.uniform.seed = self.uniform.seed.wrapping_add(1);
+        self.uniform.emitter_count = emitters.len() as u32;
+        uploads.write(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+        self.needs_step = true;
+    }
+
+    // moves the particles on by the last update's time. before the scene is drawn, while the
+    // depth buffer still holds the last frame. `collides_next` is whether this frame's depth
+    // buffer will be drawn with the update's camera alone, and so can be collided with next
+    // frame, which it can't be eg in the quad view
+    pub fn encode(&mut self, command_encoder: &mut wgpu::CommandEncoder, collides_next: bool) {
+        if std::mem::take(&mut self.needs_clear) {
+            command_encoder.clear_buffer(&self.particle_buffer, 0, None);
+        }
+        if !std::mem::take(&mut self.needs_step) {
+            return;
+        }
+        self.uniform.has_previous_depth = collides_next as u32;
+        if self.slot_count == 0 {
+            return;
+        }
+
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("particle simulation pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.simulation_pipeline);
+        compute_pass.set_bind_group(0, &self.simulation_bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.slot_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    // in the scene's render pass, after everything opaque
+    pub fn draw(
+        &self,
+        render_pass: &mut crate::render_stats::CountingRenderPass,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+    ) {
+        if self.slot_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        render_pass.set_bind_group(1, &self.render_bind_group, &[]);
+        // dead slots are drawn too, and collapse to nothing in the vertex shader
+        render_pass.draw(0..6, 0..self.slot_count);
+    }
+}
+
+// as many as can be alive at once, with one to spare for the rate not dividing evenly
+fn slots_needed(emitter: &Emitter) -> u32 {
+    (emitter.rate.max(0.0) * emitter.lifetime.max(0.0)).ceil() as u32 + 1
+}
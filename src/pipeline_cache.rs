@@ -0,0 +1,235 @@
+// keeps the render pipelines and shader modules State makes, keyed by everything they're made
+// from, so asking for the same one twice hands back the first instead of compiling it again.
+// eg the opaque and transparent scene pipelines share one shader module, and material
+// permutations which end up with the same source share their pipelines too.
+// where the adapter supports it (vulkan, for now) it also holds wgpu's pipeline cache, which
+// the driver fills with the compiled pipelines. that is written to disk and read back on the
+// next run, so the drivers' compiles start warm
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::camera;
+
+// requested when the adapter has it
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::PIPELINE_CACHE;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct VertexLayoutKey {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    vertex_layouts: Vec<VertexLayoutKey>,
+    polygon_mode: wgpu::PolygonMode,
+    blend: wgpu::BlendState,
+    // format and whether it's written
+    depth: Option<(wgpu::TextureFormat, bool)>,
+}
+
+pub struct PipelineCache {
+    // by wgsl source
+    shaders: HashMap<String, wgpu::ShaderModule>,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    // how many times something was asked for that was already made
+    reused: u32,
+    // wgpu's cache and the file it's saved to
+    driver_cache: Option<(wgpu::PipelineCache, PathBuf)>,
+    // which way every pipeline's depth test goes
+    depth_mode: camera::DepthMode,
+    // and how many samples they all draw with, see msaa.rs
+    sample_count: u32,
+}
+
+impl PipelineCache {
+    // the driver's cache is loaded from the directory, when there is one and the device has
+    // OPTIONAL_FEATURES
+    pub fn new(
+        device: &wgpu::Device,
+        adapter: &wgpu::Adapter,
+        directory: Option<&str>,
+        depth_mode: camera::DepthMode,
+        sample_count: u32,
+    ) -> Self {
+        let driver_cache = directory
+            .filter(|_| device.features().contains(wgpu::Features::PIPELINE_CACHE))
+            // named after the adapter and driver, since data from any other is useless
+            .zip(wgpu::util::pipeline_cache_key(&adapter.get_info()))
+            .map(|(directory, key)| {
+                let path = Path::new(directory).join(key);
+                let data = std::fs::read(&path).ok();
+                // SAFETY: the data is only ever what get_data wrote for this adapter and driver,
+                // and with fallback set wgpu starts an empty cache if it's stale or corrupt
+                let cache = unsafe {
+                    device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                        label: Some("pipeline cache"),
+                        data: data.as_deref(),
+                        fallback: true,
+                    })
+                };
+                log::info!(
+                    "pipeline cache {} ({})",
+                    path.display(),
+                    if data.is_some() { "loaded" } else { "new" }
+                );
+                (cache, path)
+            });
+
+        Self {
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+            reused: 0,
+            driver_cache,
+            depth_mode,
+            sample_count,
+        }
+    }
+
+    // for pipelines made elsewhere, so the driver can cache them too
+    pub fn driver_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.driver_cache.as_ref().map(|(cache, _)| cache)
+    }
+
+    pub fn shader(
+        &mut self,
+        device: &wgpu::Device,
+        descriptor: wgpu::ShaderModuleDescriptor,
+    ) -> wgpu::ShaderModule {
+        let wgpu::ShaderSource::Wgsl(source) = &descriptor.source else {
+            return device.create_shader_module(descriptor);
+        };
+        if let Some(shader) = self.shaders.get(source.as_ref()) {
+            self.reused += 1;
+            return shader.clone();
+        }
+
+        let source = source.to_string();
+        let shader = device.create_shader_module(descriptor);
+        self.shaders.insert(source, shader.clone());
+        shader
+    }
+
+    // a pipeline with the shader's vertex_main and fragment_main, drawing back face culled
+    // triangles into one color target
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        shader_descriptor: wgpu::ShaderModuleDescriptor,
+        polygon_mode: wgpu::PolygonMode,
+        blend: wgpu::BlendState,
+        // off for blended geometry, so whatever is behind it can still be drawn
+        depth_write: bool,
+    ) -> wgpu::RenderPipeline {
+        let shader = self.shader(device, shader_descriptor);
+        let key = PipelineKey {
+            shader: shader.clone(),
+            layout: layout.clone(),
+            color_format,
+            vertex_layouts: vertex_layouts
+                .iter()
+                .map(|layout| VertexLayoutKey {
+                    array_stride: layout.array_stride,
+                    step_mode: layout.step_mode,
+                    attributes: layout.attributes.to_vec(),
+                })
+                .collect(),
+            polygon_mode,
+            blend,
+            depth: depth_format.map(|format| (format, depth_write)),
+        };
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            self.reused += 1;
+            return pipeline.clone();
+        }
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: vertex_layouts,
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode,
+                // true requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // true requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: depth_write,
+                depth_compare: self.depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: self.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview_mask: None,
+            cache: self.driver_cache(),
+        });
+        self.pipelines.insert(key, pipeline.clone());
+        pipeline
+    }
+
+    // writes the driver's cache out for the next run, once the pipelines are made
+    pub fn save(&self) {
+        log::info!(
+            "pipeline cache: {} pipelines and {} shaders made, {} reused",
+            self.pipelines.len(),
+            self.shaders.len(),
+            self.reused
+        );
+
+        let Some((cache, path)) = &self.driver_cache else {
+            return;
+        };
+        let Some(data) = cache.get_data() else {
+            return;
+        };
+        if let Err(e) = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, data))
+        {
+            log::warn!(
+                "unable to save the pipeline cache to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,476 @@
+// a parser for PLY (stanford polygon) files, in ascii or binary little endian.
+// the vertex element's positions, normals, colors and uvs are read, along with the face
+// element's index lists. any other elements and properties are read past and dropped
+
+use std::io::BufRead;
+
+use crate::{model, obj_parse};
+
+#[derive(Debug)]
+pub enum PLYLoadError {
+    FileNotFound(String, std::io::Error),
+    Read(String, std::io::Error),
+    // errors in the header or in ascii data, which have a line number
+    Parse(String, usize, String),
+    // errors in binary data
+    Data(String, String),
+}
+
+impl std::fmt::Display for PLYLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PLYLoadError::FileNotFound(filepath, error) => {
+                write!(
+                    f,
+                    "IO error while loading PLY file {}:\n{}",
+                    filepath, error
+                )
+            }
+            PLYLoadError::Read(source, error) => {
+                write!(
+                    f,
+                    "IO error while reading PLY data from {}:\n{}",
+                    source, error
+                )
+            }
+            PLYLoadError::Parse(filepath, line_num, msg) => write!(
+                f,
+                "Error loading PLY file {}:\nline {}: {}",
+                filepath, line_num, msg
+            ),
+            PLYLoadError::Data(filepath, msg) => {
+                write!(f, "Error loading PLY file {}:\n{}", filepath, msg)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParsedPLY {
+    pub model_verts: Vec<model::ModelVertex>,
+    pub indices: Vec<u32>,
+    pub has_normals: bool,
+    pub has_colors: bool,
+    pub has_uvs: bool,
+}
+
+impl std::fmt::Display for ParsedPLY {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "model verts: {}\nindices: {} ({} triangles)\nnormals: {}  colors: {}  uvs: {}\n",
+            self.model_verts.len(),
+            self.indices.len(),
+            self.indices.len() / 3,
+            self.has_normals,
+            self.has_colors,
+            self.has_uvs,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::Uint8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::Uint16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::Uint32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    // integer colors are 0-255 (or the type's full range), float colors are 0-1
+    fn color_scale(&self) -> f64 {
+        match self {
+            ScalarType::Int8 => 127.0,
+            ScalarType::Uint8 => 255.0,
+            ScalarType::Int16 => 32767.0,
+            ScalarType::Uint16 => 65535.0,
+            ScalarType::Int32 => 2147483647.0,
+            ScalarType::Uint32 => 4294967295.0,
+            ScalarType::Float32 | ScalarType::Float64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    // the type of the list's length, then the type of its items
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// one row of an element: a value per scalar property and a list per list property, in the
+// order the properties were declared (lists are left empty for scalars and vice versa)
+struct Row {
+    scalars: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+}
+
+// reads the values of the body, either as whitespace separated ascii or as packed binary
+enum Body<R: BufRead> {
+    Ascii {
+        reader: R,
+        tokens: std::collections::VecDeque<String>,
+        line_num: usize,
+    },
+    BinaryLittleEndian(R),
+}
+
+impl<R: BufRead> Body<R> {
+    fn read_value(&mut self, ty: ScalarType, source: &str) -> Result<f64, PLYLoadError> {
+        match self {
+            Body::Ascii {
+                reader,
+                tokens,
+                line_num,
+            } => {
+                while tokens.is_empty() {
+                    let mut line = String::new();
+                    let read = reader
+                        .read_line(&mut line)
+                        .map_err(|e| PLYLoadError::Read(source.to_string(), e))?;
+                    if read == 0 {
+                        return Err(PLYLoadError::Parse(
+                            source.to_string(),
+                            *line_num,
+                            "unexpected end of file".to_string(),
+                        ));
+                    }
+                    *line_num += 1;
+                    tokens.extend(line.split_ascii_whitespace().map(|t| t.to_string()));
+                }
+
+                let token = tokens.pop_front().unwrap_or_default();
+                token.parse::<f64>().map_err(|e| {
+                    PLYLoadError::Parse(
+                        source.to_string(),
+                        *line_num,
+                        format!("could not parse {}: {}", token, e),
+                    )
+                })
+            }
+            Body::BinaryLittleEndian(reader) => {
+                let mut bytes = [0u8; 8];
+                let size = match ty {
+                    ScalarType::Int8 | ScalarType::Uint8 => 1,
+                    ScalarType::Int16 | ScalarType::Uint16 => 2,
+                    ScalarType::Int32 | ScalarType::Uint32 | ScalarType::Float32 => 4,
+                    ScalarType::Float64 => 8,
+                };
+                reader.read_exact(&mut bytes[..size]).map_err(|e| {
+                    PLYLoadError::Data(source.to_string(), format!("unexpected end of data: {}", e))
+                })?;
+
+                Ok(match ty {
+                    ScalarType::Int8 => bytes[0] as i8 as f64,
+                    ScalarType::Uint8 => bytes[0] as f64,
+                    ScalarType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    ScalarType::Uint16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    ScalarType::Int32 => {
+                        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    ScalarType::Uint32 => {
+                        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    ScalarType::Float32 => {
+                        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    ScalarType::Float64 => f64::from_le_bytes(bytes),
+                })
+            }
+        }
+    }
+
+    fn read_row(&mut self, element: &Element, source: &str) -> Result<Row, PLYLoadError> {
+        let mut row = Row {
+            scalars: Vec::with_capacity(element.properties.len()),
+            lists: Vec::with_capacity(element.properties.len()),
+        };
+
+        for property in &element.properties {
+            match property.kind {
+                PropertyKind::Scalar(ty) => {
+                    row.scalars.push(self.read_value(ty, source)?);
+                    row.lists.push(Vec::new());
+                }
+                PropertyKind::List(count_type, item_type) => {
+                    let count = self.read_value(count_type, source)? as usize;
+                    let list = (0..count)
+                        .map(|_| self.read_value(item_type, source))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    row.scalars.push(0.0);
+                    row.lists.push(list);
+                }
+            }
+        }
+
+        Ok(row)
+    }
+}
+
+pub fn parse_ply_file(filepath: &str) -> Result<ParsedPLY, PLYLoadError> {
+    let file = crate::assets::open(filepath)
+        .map_err(|e| PLYLoadError::FileNotFound(filepath.to_string(), e))?;
+    parse_ply(file, filepath)
+}
+
+// parses PLY data from any reader. `source` only names the data in error messages
+pub fn parse_ply(mut reader: impl BufRead, source: &str) -> Result<ParsedPLY, PLYLoadError> {
+    let parse_error = |line_num: usize, msg: &str| {
+        PLYLoadError::Parse(source.to_string(), line_num, msg.to_string())
+    };
+
+    // MARK: HEADER
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut line_num = 0;
+
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| PLYLoadError::Read(source.to_string(), e))?;
+        if read == 0 {
+            return Err(parse_error(line_num, "header has no end_header"));
+        }
+        line_num += 1;
+
+        let mut tokens = line.split_ascii_whitespace();
+        match tokens.next() {
+            Some("ply") if line_num == 1 => {}
+            _ if line_num == 1 => return Err(parse_error(line_num, "not a PLY file")),
+            Some("format") => {
+                format = match tokens.next() {
+                    Some("ascii") => Some(Format::Ascii),
+                    Some("binary_little_endian") => Some(Format::BinaryLittleEndian),
+                    Some(other) => {
+                        return Err(parse_error(
+                            line_num,
+                            &format!("unsupported format {}", other),
+                        ));
+                    }
+                    None => return Err(parse_error(line_num, "format has no type")),
+                }
+            }
+            Some("element") => {
+                let name = tokens.next();
+                let count = tokens.next().and_then(|c| c.parse::<usize>().ok());
+                match (name, count) {
+                    (Some(name), Some(count)) => elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    }),
+                    _ => return Err(parse_error(line_num, "could not parse element")),
+                }
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| parse_error(line_num, "property before any element"))?;
+
+                let tokens = tokens.collect::<Vec<_>>();
+                let property = match tokens.as_slice() {
+                    ["list", count_type, item_type, name] => {
+                        match (ScalarType::parse(count_type), ScalarType::parse(item_type)) {
+                            (Some(count_type), Some(item_type)) => Property {
+                                name: name.to_string(),
+                                kind: PropertyKind::List(count_type, item_type),
+                            },
+                            _ => return Err(parse_error(line_num, "unknown list property type")),
+                        }
+                    }
+                    [ty, name] => match ScalarType::parse(ty) {
+                        Some(ty) => Property {
+                            name: name.to_string(),
+                            kind: PropertyKind::Scalar(ty),
+                        },
+                        None => return Err(parse_error(line_num, "unknown property type")),
+                    },
+                    _ => return Err(parse_error(line_num, "could not parse property")),
+                };
+                element.properties.push(property);
+            }
+            Some("end_header") => break,
+            // comment, obj_info and blank lines
+            _ => {}
+        }
+    }
+
+    let mut body = match format {
+        Some(Format::Ascii) => Body::Ascii {
+            reader,
+            tokens: std::collections::VecDeque::new(),
+            line_num,
+        },
+        Some(Format::BinaryLittleEndian) => Body::BinaryLittleEndian(reader),
+        None => return Err(parse_error(line_num, "header has no format")),
+    };
+
+    // MARK: BODY
+
+    let mut model_verts = Vec::new();
+    let mut indices = Vec::new();
+    let mut has_normals = false;
+    let mut has_colors = false;
+    let mut has_uvs = false;
+
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+        };
+
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+                let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+                let color = [
+                    find(&["red", "r", "diffuse_red"]),
+                    find(&["green", "g", "diffuse_green"]),
+                    find(&["blue", "b", "diffuse_blue"]),
+                ];
+                let uv = [
+                    find(&["u", "s", "texture_u", "texture_s"]),
+                    find(&["v", "t", "texture_v", "texture_t"]),
+                ];
+
+                let [Some(x), Some(y), Some(z)] = position else {
+                    return Err(PLYLoadError::Data(
+                        source.to_string(),
+                        "vertex element has no x, y and z".to_string(),
+                    ));
+                };
+                let normal = match normal {
+                    [Some(nx), Some(ny), Some(nz)] => Some([nx, ny, nz]),
+                    _ => None,
+                };
+                let color = match color {
+                    [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+                    _ => None,
+                };
+                let uv = match uv {
+                    [Some(u), Some(v)] => Some([u, v]),
+                    _ => None,
+                };
+
+                has_normals = normal.is_some();
+                has_colors = color.is_some();
+                has_uvs = uv.is_some();
+
+                let color_scale = |i: usize| match element.properties[i].kind {
+                    PropertyKind::Scalar(ty) => ty.color_scale(),
+                    PropertyKind::List(..) => 1.0,
+                };
+
+                model_verts.reserve(element.count);
+                for _ in 0..element.count {
+                    let row = body.read_row(element, source)?;
+                    let get = |i: usize| row.scalars[i] as f32;
+
+                    model_verts.push(model::ModelVertex {
+                        position: [get(x), get(y), get(z)],
+                        tex_coords: uv.map_or([0.0; 2], |[u, v]| [get(u), get(v)]),
+                        normal: normal.map_or([0.0; 3], |[nx, ny, nz]| [get(nx), get(ny), get(nz)]),
+                        tangent: [0.0; 4],
+                        color: color.map_or([1.0; 3], |rgb| {
+                            rgb.map(|i| (row.scalars[i] / color_scale(i)) as f32)
+                        }),
+                    });
+                }
+            }
+            "face" => {
+                let vertex_indices = find(&["vertex_indices", "vertex_index"]);
+
+                for face_num in 0..element.count {
+                    let row = body.read_row(element, source)?;
+                    let Some(list) = vertex_indices.map(|i| &row.lists[i]) else {
+                        continue;
+                    };
+
+                    let face = list.iter().map(|&i| i as u32).collect::<Vec<_>>();
+                    if face.len() < 3 {
+                        return Err(PLYLoadError::Data(
+                            source.to_string(),
+                            format!("face {} has fewer than 3 vertices", face_num),
+                        ));
+                    }
+                    if let Some(&i) = face.iter().find(|&&i| i as usize >= model_verts.len()) {
+                        return Err(PLYLoadError::Data(
+                            source.to_string(),
+                            format!("face {} uses vertex {} which doesn't exist", face_num, i),
+                        ));
+                    }
+
+                    let positions = face
+                        .iter()
+                        .map(|&i| cgmath::Vector3::from(model_verts[i as usize].position))
+                        .collect::<Vec<_>>();
+
+                    for [a, b, c] in obj_parse::triangulate_polygon(&positions) {
+                        indices.extend([face[a], face[b], face[c]]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    body.read_row(element, source)?;
+                }
+            }
+        }
+    }
+
+    if !has_normals {
+        let needs_normal = vec![true; model_verts.len()];
+        let shared = (0..model_verts.len()).collect::<Vec<_>>();
+        obj_parse::generate_normals(&mut model_verts, &indices, &needs_normal, &shared);
+    }
+
+    Ok(ParsedPLY {
+        model_verts,
+        indices,
+        has_normals,
+        has_colors,
+        has_uvs,
+    })
+}
@@ -0,0 +1,403 @@
+// meshes generated in code, so test scenes and debug markers don't need model files. each
+// generator returns the vertices and indices with positions, normals and uvs filled in; the
+// tangents are worked out from those when it's turned into a Mesh. everything is centered on
+// the origin with y up, faces wind counter clockwise, and v points up like an obj's uvs.
+// models can be loaded by name too, eg `load primitive:torus` in the console
+
+use std::f32::consts::{PI, TAU};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::{error::AssetError, math, model, resources};
+
+pub const NAMES: [&str; 8] = [
+    "plane",
+    "cube",
+    "sphere",
+    "icosphere",
+    "cylinder",
+    "cone",
+    "torus",
+    "arrow",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub verts: Vec<model::ModelVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn push_vertex(&mut self, position: Vector3<f32>, tex_coords: [f32; 2], normal: Vector3<f32>) {
+        self.verts.push(model::ModelVertex {
+            position: position.into(),
+            tex_coords,
+            normal: normal.normalize().into(),
+            tangent: [0.0; 4],
+            color: [1.0; 3],
+        });
+    }
+
+    // a, b, c and d counter clockwise, seen from the front
+    fn push_quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.indices.extend_from_slice(&[a, b, c, a, c, d]);
+    }
+
+    // a grid of quads over vertices laid out row by row, columns + 1 to a row. u runs along
+    // the rows and v from row to row, and u x v has to face out for the winding to be right
+    fn push_grid(&mut self, first: u32, columns: u32, rows: u32) {
+        let stride = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * stride + column;
+                self.push_quad(a, a + 1, a + 1 + stride, a + stride);
+            }
+        }
+    }
+
+    pub fn translated(mut self, offset: [f32; 3]) -> Self {
+        for vertex in &mut self.verts {
+            for (p, o) in vertex.position.iter_mut().zip(offset) {
+                *p += o;
+            }
+        }
+        self
+    }
+
+    pub fn append(&mut self, other: MeshData) {
+        let first = self.verts.len() as u32;
+        self.verts.extend(other.verts);
+        self.indices.extend(other.indices.iter().map(|i| i + first));
+    }
+
+    pub fn into_mesh(self, device: &wgpu::Device, name: &str, material: usize) -> model::Mesh {
+        model::Mesh::from_verts_inds(device, name.to_string(), self.verts, self.indices, material)
+    }
+
+    pub fn into_model(self, device: &wgpu::Device, name: &str, material: usize) -> model::Model {
+        model::Model {
+            meshes: vec![self.into_mesh(device, name, material)],
+            transform: math::Transform::identity(),
+        }
+    }
+}
+
+// a square in the xz plane facing up, split into subdivisions x subdivisions quads
+pub fn plane(size: f32, subdivisions: u32) -> MeshData {
+    let subdivisions = subdivisions.max(1);
+    let mut mesh = MeshData::default();
+    for row in 0..=subdivisions {
+        for column in 0..=subdivisions {
+            let (u, v) = (
+                column as f32 / subdivisions as f32,
+                row as f32 / subdivisions as f32,
+            );
+            mesh.push_vertex(
+                Vector3::new((u - 0.5) * size, 0.0, (0.5 - v) * size),
+                [u, v],
+                Vector3::unit_y(),
+            );
+        }
+    }
+    mesh.push_grid(0, subdivisions, subdivisions);
+    mesh
+}
+
+// each face has its own vertices and the whole of the uv square
+pub fn cube(size: f32) -> MeshData {
+    let half = size / 2.0;
+    // the normal, then the directions u and v run in
+    let faces = [
+        (Vector3::unit_x(), -Vector3::unit_z(), Vector3::unit_y()),
+        (-Vector3::unit_x(), Vector3::unit_z(), Vector3::unit_y()),
+        (Vector3::unit_y(), Vector3::unit_x(), -Vector3::unit_z()),
+        (-Vector3::unit_y(), Vector3::unit_x(), Vector3::unit_z()),
+        (Vector3::unit_z(), Vector3::unit_x(), Vector3::unit_y()),
+        (-Vector3::unit_z(), -Vector3::unit_x(), Vector3::unit_y()),
+    ];
+
+    let mut mesh = MeshData::default();
+    for (normal, u, v) in faces {
+        let first = mesh.verts.len() as u32;
+        for [s, t] in [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]] {
+            let position = (normal + u * (s * 2.0 - 1.0) + v * (t * 2.0 - 1.0)) * half;
+            mesh.push_vertex(position, [s, t], normal);
+        }
+        mesh.push_quad(first, first + 1, first + 2, first + 3);
+    }
+    mesh
+}
+
+// a point on the unit sphere at a longitude around y and a colatitude down from +y, with the
+// longitude turning so that u x v faces out
+fn sphere_direction(longitude: f32, colatitude: f32) -> Vector3<f32> {
+    let (sin_lat, cos_lat) = colatitude.sin_cos();
+    let (sin_long, cos_long) = longitude.sin_cos();
+    Vector3::new(sin_lat * cos_long, cos_lat, -sin_lat * sin_long)
+}
+
+// latitude and longitude lines, with a seam of doubled vertices where u wraps around
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut mesh = MeshData::default();
+    for ring in 0..=rings {
+        for segment in 0..=segments {
+            let (u, v) = (
+                segment as f32 / segments as f32,
+                1.0 - ring as f32 / rings as f32,
+            );
+            let normal = sphere_direction(u * TAU, (1.0 - v) * PI);
+            mesh.push_vertex(normal * radius, [u, v], normal);
+        }
+    }
+
+    // rows go down from the top, so each quad is made from the row below it. the quads
+    // touching the poles would have two corners in the same place, so they are triangles
+    let stride = segments + 1;
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let d = ring * stride + segment;
+            let (a, b, c) = (d + stride, d + stride + 1, d + 1);
+            if ring != rings - 1 {
+                mesh.indices.extend_from_slice(&[a, b, c]);
+            }
+            if ring != 0 {
+                mesh.indices.extend_from_slice(&[a, c, d]);
+            }
+        }
+    }
+    mesh
+}
+
+// an icosahedron with each triangle split into four subdivisions times, pushed out onto the
+// sphere. more even than a uv sphere, but its uvs need the triangles across the seam fixing up
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut directions = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .map(|d| Vector3::from(d).normalize())
+    .to_vec();
+    let mut triangles = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = std::collections::HashMap::new();
+        let mut midpoint = |a: usize, b: usize| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                directions.push((directions[a] + directions[b]).normalize());
+                directions.len() - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // every triangle gets its own corners so the ones across the seam can have u past 1, and
+    // the welding afterwards shares the rest again
+    let mut mesh = MeshData::default();
+    for triangle in &triangles {
+        let corners = triangle.map(|i| directions[i]);
+        let mut uvs = corners.map(|d| {
+            let u = (-d.z).atan2(d.x) / TAU;
+            [u.rem_euclid(1.0), 1.0 - d.y.clamp(-1.0, 1.0).acos() / PI]
+        });
+        if uvs.iter().any(|uv| uv[0] > 0.75) && uvs.iter().any(|uv| uv[0] < 0.25) {
+            for uv in &mut uvs {
+                if uv[0] < 0.5 {
+                    uv[0] += 1.0;
+                }
+            }
+        }
+        // a pole's u is anything, so it takes the middle of the other two
+        for i in 0..3 {
+            if corners[i].y.abs() > 0.9999 {
+                uvs[i][0] = (uvs[(i + 1) % 3][0] + uvs[(i + 2) % 3][0]) / 2.0;
+            }
+        }
+        for (direction, uv) in corners.into_iter().zip(uvs) {
+            mesh.indices.push(mesh.verts.len() as u32);
+            mesh.push_vertex(direction * radius, uv, direction);
+        }
+    }
+
+    let (verts, indices) = resources::weld_vertices(&mesh.verts, &mesh.indices, 0.00001);
+    MeshData { verts, indices }
+}
+
+// a ring of vertices facing along the normal for a disc, counter clockwise seen from it
+fn push_cap(mesh: &mut MeshData, radius: f32, y: f32, segments: u32, normal: Vector3<f32>) {
+    let center = mesh.verts.len() as u32;
+    mesh.push_vertex(Vector3::new(0.0, y, 0.0), [0.5, 0.5], normal);
+    // seen from below the ring turns the other way
+    let side = normal.y.signum();
+    for segment in 0..=segments {
+        let angle = segment as f32 / segments as f32 * TAU;
+        let (sin, cos) = angle.sin_cos();
+        mesh.push_vertex(
+            Vector3::new(cos * radius, y, -sin * radius),
+            [0.5 + 0.5 * cos, 0.5 + 0.5 * sin * side],
+            normal,
+        );
+    }
+    for segment in 0..segments {
+        let (a, b) = (center + 1 + segment, center + 2 + segment);
+        if side > 0.0 {
+            mesh.indices.extend_from_slice(&[center, a, b]);
+        } else {
+            mesh.indices.extend_from_slice(&[center, b, a]);
+        }
+    }
+}
+
+// capped, with u around the side and the caps mapped from above and below
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let mut mesh = MeshData::default();
+    for (v, y) in [(0.0, -half), (1.0, half)] {
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let normal = sphere_direction(u * TAU, PI / 2.0);
+            mesh.push_vertex(normal * radius + Vector3::new(0.0, y, 0.0), [u, v], normal);
+        }
+    }
+    mesh.push_grid(0, segments, 1);
+    push_cap(&mut mesh, radius, half, segments, Vector3::unit_y());
+    push_cap(&mut mesh, radius, -half, segments, -Vector3::unit_y());
+    mesh
+}
+
+// its base is capped. the tip has a vertex per segment, each with that segment's normal, so
+// the side is shaded smoothly around without pinching at the point
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let mut mesh = MeshData::default();
+    let side_normal = |angle: f32| {
+        let (sin, cos) = angle.sin_cos();
+        Vector3::new(cos * height, radius, -sin * height)
+    };
+    for segment in 0..=segments {
+        let u = segment as f32 / segments as f32;
+        let (sin, cos) = (u * TAU).sin_cos();
+        mesh.push_vertex(
+            Vector3::new(cos * radius, -half, -sin * radius),
+            [u, 0.0],
+            side_normal(u * TAU),
+        );
+    }
+    for segment in 0..segments {
+        let u = (segment as f32 + 0.5) / segments as f32;
+        mesh.push_vertex(Vector3::new(0.0, half, 0.0), [u, 1.0], side_normal(u * TAU));
+    }
+    let tip = segments + 1;
+    for segment in 0..segments {
+        mesh.indices
+            .extend_from_slice(&[segment, segment + 1, tip + segment]);
+    }
+    push_cap(&mut mesh, radius, -half, segments, -Vector3::unit_y());
+    mesh
+}
+
+// a ring around the y axis. u goes around the ring and v around the tube
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> MeshData {
+    let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+    let mut mesh = MeshData::default();
+    for minor in 0..=minor_segments {
+        let v = minor as f32 / minor_segments as f32;
+        let (sin_tube, cos_tube) = (v * TAU).sin_cos();
+        for major in 0..=major_segments {
+            let u = major as f32 / major_segments as f32;
+            let outward = sphere_direction(u * TAU, PI / 2.0);
+            let normal = outward * cos_tube + Vector3::unit_y() * sin_tube;
+            mesh.push_vertex(
+                outward * major_radius + normal * minor_radius,
+                [u, v],
+                normal,
+            );
+        }
+    }
+    mesh.push_grid(0, major_segments, minor_segments);
+    mesh
+}
+
+// a shaft and a head pointing up +y from the origin, eg for drawing vectors
+pub fn arrow(
+    shaft_radius: f32,
+    shaft_length: f32,
+    head_radius: f32,
+    head_length: f32,
+    segments: u32,
+) -> MeshData {
+    let mut mesh =
+        cylinder(shaft_radius, shaft_length, segments).translated([0.0, shaft_length / 2.0, 0.0]);
+    mesh.append(cone(head_radius, head_length, segments).translated([
+        0.0,
+        shaft_length + head_length / 2.0,
+        0.0,
+    ]));
+    mesh
+}
+
+// one of NAMES at a size that fits in a unit cube, with the first material
+pub fn load_model(device: &wgpu::Device, name: &str) -> Result<model::Model, AssetError> {
+    let mesh = match name {
+        "plane" => plane(1.0, 1),
+        "cube" => cube(1.0),
+        "sphere" => uv_sphere(0.5, 32, 16),
+        "icosphere" => icosphere(0.5, 3),
+        "cylinder" => cylinder(0.5, 1.0, 32),
+        "cone" => cone(0.5, 1.0, 32),
+        "torus" => torus(0.35, 0.15, 48, 24),
+        "arrow" => arrow(0.05, 0.75, 0.12, 0.25, 16).translated([0.0, -0.5, 0.0]),
+        _ => {
+            return Err(AssetError::BadData {
+                path: format!("primitive:{}", name),
+                message: format!("there is no such primitive, try {}", NAMES.join(", ")),
+            });
+        }
+    };
+    Ok(mesh.into_model(device, &format!("primitive:{}", name), 0))
+}
@@ -0,0 +1,85 @@
+// the window split into four views for inspecting models, as modelling programs do: the camera
+// in the top left, and orthographic views from the top, front and side in the other quarters,
+// looking at the whole scene. they're all drawn in the one scene pass, each limited to its
+// quarter with set_viewport and set_scissor_rect, and each reading its own camera from the
+// per frame camera buffer at a dynamic offset. slot 0 there is always the camera's, which is
+// what everything outside the scene pass (culling, voxelizing, shadows) keeps using
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+use crate::{camera, math, uniforms};
+
+// the camera's slot and the orthographic views' after it
+pub const CAMERA_SLOTS: usize = 1 + ORTHOGRAPHIC_VIEWS.len();
+
+// the way each orthographic view looks and its up, in the order of their quarters
+const ORTHOGRAPHIC_VIEWS: [(&str, [f32; 3], [f32; 3]); 3] = [
+    ("top", [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ("front", [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+    ("side", [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+];
+
+// how much room is left around the scene in the orthographic views
+const MARGIN: f32 = 1.1;
+
+// a quarter of the window and the camera slot it's drawn with
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    // x, y, width, height in pixels
+    pub rect: [u32; 4],
+    pub camera_slot: usize,
+}
+
+impl Viewport {
+    pub fn contains(&self, position: (f32, f32)) -> bool {
+        let [x, y, width, height] = self.rect.map(|v| v as f32);
+        (x..x + width).contains(&position.0) && (y..y + height).contains(&position.1)
+    }
+}
+
+// the camera's quarter first, then the top, front and side views', reading across and then down.
+// an odd pixel left over goes to the right and bottom quarters
+pub fn viewports(width: u32, height: u32) -> [Viewport; 4] {
+    let (left, top) = (width / 2, height / 2);
+    let (right, bottom) = (width - left, height - top);
+    let rects = [
+        [0, 0, left, top],
+        [left, 0, right, top],
+        [0, top, left, bottom],
+        [left, top, right, bottom],
+    ];
+    std::array::from_fn(|i| Viewport {
+        rect: rects[i],
+        camera_slot: i,
+    })
+}
+
+// what the view in the slot is, for debug groups
+pub fn name(camera_slot: usize) -> &'static str {
+    match camera_slot.checked_sub(1) {
+        None => "camera",
+        Some(i) => ORTHOGRAPHIC_VIEWS[i].0,
+    }
+}
+
+// the orthographic views' cameras, for slots 1 onwards, each framing the whole of the bounds at
+// the projection's aspect ratio, which every quarter shares
+pub fn orthographic_cameras(
+    bounds: &math::Aabb,
+    projection: &camera::Projection,
+) -> [uniforms::CameraUniform; ORTHOGRAPHIC_VIEWS.len()] {
+    let center = Point3::from_vec(bounds.center());
+    let radius = ((bounds.max - bounds.min).magnitude() / 2.0).max(0.01) * MARGIN;
+
+    ORTHOGRAPHIC_VIEWS.map(|(_, forward, up)| {
+        let forward = Vector3::from(forward);
+        // far enough back for the whole bounding sphere to be in front of the near plane
+        let position = center - forward * radius * 2.0;
+        let view = Matrix4::look_to_rh(position, forward, Vector3::from(up));
+        let projection = projection.orthographic_matrix(radius, radius, radius * 3.0);
+
+        let mut uniform = uniforms::CameraUniform::new();
+        uniform.set(position, projection * view);
+        uniform
+    })
+}
@@ -0,0 +1,179 @@
+// ray traced shadows and ambient occlusion through wgpu's (experimental) ray queries
+//
+// only available when the adapter supports EXPERIMENTAL_RAY_QUERY; everywhere else the main
+// shader keeps its unshadowed fallback. every mesh of the scene model gets a BLAS, and the TLAS
+// is rebuilt each frame so it follows the model's transform
+
+use crate::{model, shaders};
+
+pub const FEATURES: wgpu::Features = wgpu::Features::EXPERIMENTAL_RAY_QUERY;
+
+// the block of shader.wgsl which is replaced by raytraced.wgsl
+const HOOK_START: &str = "// @hook visibility";
+const HOOK_END: &str = "// @end hook";
+
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter.features().contains(FEATURES)
+}
+
+// builds the ray traced variant of a shader which has a `@hook visibility` block
+pub fn shader_source(base: &str) -> Option<String> {
+    let start = base.find(HOOK_START)?;
+    let end = base[start..].find(HOOK_END)? + start + HOOK_END.len();
+    // its includes are its own, which the base doesn't have
+    let hook = shaders::preprocess(
+        "raytraced.wgsl",
+        include_str!("shaders/raytraced.wgsl"),
+        &[],
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    Some(format!(
+        "enable wgpu_ray_query;\n{}{}{}",
+        &base[..start],
+        hook,
+        &base[end..]
+    ))
+}
+
+struct RayTracedMesh {
+    // which of the models passed to new the mesh belongs to, for its instance transform
+    model_index: usize,
+    blas: wgpu::Blas,
+    size: wgpu::BlasTriangleGeometrySizeDescriptor,
+    // positions only, since BLAS inputs need their own buffer usage
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+}
+
+pub struct RayTracing {
+    pub tlas: wgpu::Tlas,
+    meshes: Vec<RayTracedMesh>,
+    is_blas_built: bool,
+}
+
+impl RayTracing {
+    pub fn new(device: &wgpu::Device, models: &[&model::Model]) -> Self {
+        let meshes = models
+            .iter()
+            .enumerate()
+            .flat_map(|(model_index, model)| {
+                model
+                    .meshes
+                    .iter()
+                    .filter(|m| m.is_visible)
+                    .map(move |m| (model_index, m))
+            })
+            .map(|(model_index, mesh)| {
+                let positions = mesh.verts.iter().map(|v| v.position).collect::<Vec<_>>();
+
+                let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+                    device,
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some(&(mesh.name.clone() + " blas vertex buffer")),
+                        contents: bytemuck::cast_slice(&positions),
+                        usage: wgpu::BufferUsages::BLAS_INPUT,
+                    },
+                );
+
+                let index_buffer = wgpu::util::DeviceExt::create_buffer_init(
+                    device,
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some(&(mesh.name.clone() + " blas index buffer")),
+                        contents: bytemuck::cast_slice(&mesh.indices),
+                        usage: wgpu::BufferUsages::BLAS_INPUT,
+                    },
+                );
+
+                let size = wgpu::BlasTriangleGeometrySizeDescriptor {
+                    vertex_format: wgpu::VertexFormat::Float32x3,
+                    vertex_count: positions.len() as u32,
+                    index_format: Some(wgpu::IndexFormat::Uint32),
+                    index_count: Some(mesh.indices.len() as u32),
+                    flags: wgpu::AccelerationStructureGeometryFlags::OPAQUE,
+                };
+
+                let blas = device.create_blas(
+                    &wgpu::CreateBlasDescriptor {
+                        label: Some(&(mesh.name.clone() + " blas")),
+                        flags: wgpu::AccelerationStructureFlags::PREFER_FAST_TRACE,
+                        update_mode: wgpu::AccelerationStructureUpdateMode::Build,
+                    },
+                    wgpu::BlasGeometrySizeDescriptors::Triangles {
+                        descriptors: vec![size.clone()],
+                    },
+                );
+
+                RayTracedMesh {
+                    model_index,
+                    blas,
+                    size,
+                    vertex_buffer,
+                    index_buffer,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let tlas = device.create_tlas(&wgpu::CreateTlasDescriptor {
+            label: Some("scene tlas"),
+            max_instances: meshes.len().max(1) as u32,
+            flags: wgpu::AccelerationStructureFlags::PREFER_FAST_TRACE,
+            update_mode: wgpu::AccelerationStructureUpdateMode::Build,
+        });
+
+        Self {
+            tlas,
+            meshes,
+            is_blas_built: false,
+        }
+    }
+
+    // moves the instances to their models' current transforms and rebuilds the tlas. the
+    // blases are only built the first time. `models` has to be the same models passed to new
+    pub fn encode(&mut self, command_encoder: &mut wgpu::CommandEncoder, models: &[&model::Model]) {
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let matrix = models[mesh.model_index].transform.matrix();
+            // tlas instances take the top three rows of the matrix, row major
+            let transform = [
+                matrix.x.x, matrix.y.x, matrix.z.x, matrix.w.x, //
+                matrix.x.y, matrix.y.y, matrix.z.y, matrix.w.y, //
+                matrix.x.z, matrix.y.z, matrix.z.z, matrix.w.z,
+            ];
+            if let Some(instance) = self.tlas.get_mut_single(i) {
+                *instance = Some(wgpu::TlasInstance::new(&mesh.blas, transform, 0, 0xff));
+            }
+        }
+
+        let blas_entries = if self.is_blas_built {
+            Vec::new()
+        } else {
+            self.meshes
+                .iter()
+                .map(|mesh| wgpu::BlasBuildEntry {
+                    blas: &mesh.blas,
+                    geometry: wgpu::BlasGeometries::TriangleGeometries(vec![
+                        wgpu::BlasTriangleGeometry {
+                            size: &mesh.size,
+                            vertex_buffer: &mesh.vertex_buffer,
+                            first_vertex: 0,
+                            vertex_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                            index_buffer: Some(&mesh.index_buffer),
+                            first_index: Some(0),
+                            transform_buffer: None,
+                            transform_buffer_offset: None,
+                        },
+                    ]),
+                })
+                .collect()
+        };
+
+        command_encoder.push_debug_group(if self.is_blas_built {
+            "build tlas"
+        } else {
+            "build blas and tlas"
+        });
+        command_encoder.build_acceleration_structures(&blas_entries, std::iter::once(&self.tlas));
+        command_encoder.pop_debug_group();
+        self.is_blas_built = true;
+    }
+}
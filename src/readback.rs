@@ -0,0 +1,157 @@
+// copying textures back to the cpu. the copy is encoded like any other command, and once it's
+// submitted the buffer is mapped in the background: try_read checks on it without blocking,
+// for captures taken while the renderer keeps running, and wait blocks for one-off captures.
+// a readback which has been read can copy another texture of the same size and format, so a
+// few of them can be cycled through rather than creating a buffer every frame
+
+use std::sync::mpsc;
+
+use anyhow::Context;
+
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    // rows of a texture to buffer copy have to be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+    padded_row_bytes: u32,
+    format: wgpu::TextureFormat,
+    // receives the outcome of map_async, None until map is called
+    mapped: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+impl Readback {
+    // encodes a copy of a 2d color texture into a new buffer. the texture needs COPY_SRC and an
+    // 8 bit rgba or bgra format
+    pub fn encode(
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> anyhow::Result<Self> {
+        let mut readback = Self::new(device, texture)?;
+        readback.copy(command_encoder, texture);
+        Ok(readback)
+    }
+
+    // a buffer the size of the texture, without copying anything into it yet
+    pub fn new(device: &wgpu::Device, texture: &wgpu::Texture) -> anyhow::Result<Self> {
+        match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm
+            | wgpu::TextureFormat::Rgba8UnormSrgb
+            | wgpu::TextureFormat::Bgra8Unorm
+            | wgpu::TextureFormat::Bgra8UnormSrgb => {}
+            format => anyhow::bail!("can't read back a {:?} texture", format),
+        }
+
+        let width = texture.width();
+        let height = texture.height();
+        let padded_row_bytes = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback buffer"),
+            size: (padded_row_bytes * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_row_bytes,
+            format: texture.format(),
+            mapped: None,
+        })
+    }
+
+    // whether copy can take the texture
+    pub fn fits(&self, texture: &wgpu::Texture) -> bool {
+        texture.width() == self.width
+            && texture.height() == self.height
+            && texture.format() == self.format
+    }
+
+    // encodes a copy of the texture, which has to fit, into the buffer. the buffer mustn't be
+    // waiting to be read
+    pub fn copy(&mut self, command_encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        command_encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row_bytes),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            texture.size(),
+        );
+    }
+
+    // starts mapping the buffer, once the copy has been submitted. the map only finishes while
+    // the device is being polled
+    pub fn map(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.mapped = Some(receiver);
+    }
+
+    // the image, or None while the buffer is still being mapped. reading it frees the buffer
+    // for another copy
+    pub fn try_read(&mut self) -> Option<anyhow::Result<image::RgbaImage>> {
+        let result = match self.mapped.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.mapped = None;
+                return Some(Err(anyhow::anyhow!(
+                    "the readback buffer was dropped before it was mapped"
+                )));
+            }
+        };
+        self.mapped = None;
+        Some(
+            result
+                .map_err(anyhow::Error::from)
+                .and_then(|_| self.read()),
+        )
+    }
+
+    // maps the buffer if it isn't yet and blocks until the image is ready
+    pub fn wait(mut self, device: &wgpu::Device) -> anyhow::Result<image::RgbaImage> {
+        if self.mapped.is_none() {
+            self.map();
+        }
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+        self.try_read()
+            .context("the readback buffer wasn't mapped after waiting for the device")?
+    }
+
+    fn read(&self) -> anyhow::Result<image::RgbaImage> {
+        let row_bytes = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row_bytes * self.height as usize);
+        {
+            let mapped = self.buffer.slice(..).get_mapped_range();
+            for row in mapped.chunks(self.padded_row_bytes as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.buffer.unmap();
+
+        if matches!(
+            self.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .context("the readback buffer has the wrong size")
+    }
+}
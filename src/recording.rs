@@ -0,0 +1,265 @@
+// recording every presented frame, for turntable videos and the like. frames are copied into a
+// small ring of readback buffers and handed to a writer thread once they've come back, so the
+// renderer never waits for the gpu or the encoder. when every buffer is still in flight the
+// frame is dropped and counted instead. the writer saves a png sequence, or pipes raw frames
+// into ffmpeg for a video
+
+use std::{
+    collections::VecDeque,
+    io::Write,
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread::JoinHandle,
+};
+
+use anyhow::Context;
+
+use crate::readback::Readback;
+
+// enough buffers to cover a few frames of latency between copying a frame and reading it
+const RING_SIZE: usize = 4;
+// how many frames can wait for the writer. encoding pngs is slower than drawing, so without a
+// limit a long recording would fill memory, rather than dropping the frames it can't keep up with
+const WRITER_QUEUE: usize = 32;
+// the frame rate written into videos, whatever the renderer actually ran at
+pub const VIDEO_FPS: u32 = 60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordingFormat {
+    // numbered pngs in a directory of their own
+    PngSequence,
+    // an mp4 encoded by ffmpeg, which has to be on the path
+    Video,
+}
+
+pub struct Recording {
+    format: RecordingFormat,
+    // the directory or video file being written
+    destination: String,
+    // buffers ready for another copy, and the ones waiting to be read in the order they were
+    // copied, so frames reach the writer in order
+    free: Vec<Readback>,
+    in_flight: VecDeque<Readback>,
+    // copied this frame, to be mapped once the copy is submitted
+    copied: Option<Readback>,
+    frames: Option<mpsc::SyncSender<image::RgbaImage>>,
+    writer: Option<JoinHandle<anyhow::Result<()>>>,
+    recorded: u32,
+    dropped: u32,
+}
+
+impl Recording {
+    pub fn start(format: RecordingFormat, destination: String) -> anyhow::Result<Self> {
+        if format == RecordingFormat::PngSequence {
+            std::fs::create_dir_all(&destination)
+                .with_context(|| format!("could not create {}", destination))?;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(WRITER_QUEUE);
+        let writer_destination = destination.clone();
+        let writer = std::thread::spawn(move || match format {
+            RecordingFormat::PngSequence => write_pngs(&writer_destination, receiver),
+            RecordingFormat::Video => write_video(&writer_destination, receiver),
+        });
+
+        Ok(Self {
+            format,
+            destination,
+            free: Vec::new(),
+            in_flight: VecDeque::new(),
+            copied: None,
+            frames: Some(sender),
+            writer: Some(writer),
+            recorded: 0,
+            dropped: 0,
+        })
+    }
+
+    // a new directory or video name in the working directory, like screenshot::default_path
+    pub fn default_destination(format: RecordingFormat) -> String {
+        let path = crate::screenshot::default_path("recording");
+        match format {
+            RecordingFormat::PngSequence => path.trim_end_matches(".png").to_string(),
+            RecordingFormat::Video => path.replace(".png", ".mp4"),
+        }
+    }
+
+    // encodes a copy of the frame into a free buffer, or drops the frame if there isn't one
+    pub fn copy_frame(
+        &mut self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        frame: &wgpu::Texture,
+    ) {
+        // buffers from before a resize won't fit any more
+        self.free.retain(|readback| readback.fits(frame));
+
+        let mut readback = match self.free.pop() {
+            Some(readback) => readback,
+            None if self.in_flight.len() < RING_SIZE => match Readback::new(device, frame) {
+                Ok(readback) => readback,
+                Err(e) => {
+                    log::error!("could not record the frame: {:#}", e);
+                    self.dropped += 1;
+                    return;
+                }
+            },
+            None => {
+                self.dropped += 1;
+                return;
+            }
+        };
+
+        readback.copy(command_encoder, frame);
+        self.copied = Some(readback);
+    }
+
+    // starts reading back the frame copied this frame, after its commands were submitted
+    pub fn submitted(&mut self) {
+        if let Some(mut readback) = self.copied.take() {
+            readback.map();
+            self.in_flight.push_back(readback);
+        }
+    }
+
+    // hands the frames which have come back to the writer, oldest first
+    pub fn collect_frames(&mut self) {
+        while let Some(readback) = self.in_flight.front_mut() {
+            let Some(result) = readback.try_read() else {
+                break;
+            };
+            match result {
+                Ok(image) => match self.frames.as_ref().map(|frames| frames.try_send(image)) {
+                    Some(Ok(())) => self.recorded += 1,
+                    // the writer is behind, or has stopped with an error which finish reports
+                    _ => self.dropped += 1,
+                },
+                Err(e) => {
+                    log::error!("could not read back a recorded frame: {:#}", e);
+                    self.dropped += 1;
+                }
+            }
+            if let Some(readback) = self.in_flight.pop_front() {
+                self.free.push(readback);
+            }
+        }
+    }
+
+    // waits for the frames still in flight and for the writer to finish with them
+    pub fn finish(mut self, device: &wgpu::Device) -> anyhow::Result<String> {
+        if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+            log::error!("waiting for the last recorded frames: {}", e);
+        }
+        self.collect_frames();
+
+        // closing the channel ends the writer's loop
+        self.frames = None;
+        if let Some(writer) = self.writer.take() {
+            writer
+                .join()
+                .map_err(|_| anyhow::anyhow!("the recording writer panicked"))??;
+        }
+
+        Ok(format!(
+            "recorded {} frames to {}{}",
+            self.recorded,
+            self.destination,
+            if self.dropped > 0 {
+                format!(", dropping {}", self.dropped)
+            } else {
+                String::new()
+            }
+        ))
+    }
+
+    // eg [REC PNG 120 +2 DROPPED], for the window title
+    pub fn describe(&self) -> String {
+        let format = match self.format {
+            RecordingFormat::PngSequence => "PNG",
+            RecordingFormat::Video => "MP4",
+        };
+        if self.dropped > 0 {
+            format!(
+                "[REC {} {} +{} DROPPED]",
+                format, self.recorded, self.dropped
+            )
+        } else {
+            format!("[REC {} {}]", format, self.recorded)
+        }
+    }
+}
+
+fn write_pngs(directory: &str, frames: mpsc::Receiver<image::RgbaImage>) -> anyhow::Result<()> {
+    for (i, frame) in frames.into_iter().enumerate() {
+        let path = format!("{}/frame_{:05}.png", directory, i);
+        frame
+            .save(&path)
+            .with_context(|| format!("could not write {}", path))?;
+    }
+    Ok(())
+}
+
+// ffmpeg is started with the first frame's size. later frames of another size, after a resize,
+// are skipped since raw video can't change size midway
+fn write_video(path: &str, frames: mpsc::Receiver<image::RgbaImage>) -> anyhow::Result<()> {
+    let mut ffmpeg: Option<(Child, (u32, u32))> = None;
+    let mut skipped = 0;
+
+    for frame in frames {
+        let (child, size) = match &mut ffmpeg {
+            Some(ffmpeg) => ffmpeg,
+            None => ffmpeg.insert((start_ffmpeg(path, frame.dimensions())?, frame.dimensions())),
+        };
+        if frame.dimensions() != *size {
+            skipped += 1;
+            continue;
+        }
+        child
+            .stdin
+            .as_mut()
+            .context("ffmpeg's input was closed")?
+            .write_all(frame.as_raw())
+            .context("could not write to ffmpeg")?;
+    }
+
+    if skipped > 0 {
+        log::warn!(
+            "skipped {} frames of {} recorded after a resize",
+            skipped,
+            path
+        );
+    }
+    if let Some((mut child, _)) = ffmpeg {
+        // closing its input tells ffmpeg the video is over
+        drop(child.stdin.take());
+        let status = child.wait().context("ffmpeg didn't finish")?;
+        anyhow::ensure!(status.success(), "ffmpeg failed with {}", status);
+    }
+    Ok(())
+}
+
+fn start_ffmpeg(path: &str, (width, height): (u32, u32)) -> anyhow::Result<Child> {
+    Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &VIDEO_FPS.to_string(), "-i", "-"])
+        // yuv420p needs even sizes, and is what players expect
+        .args([
+            "-vf",
+            "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+            "-pix_fmt",
+            "yuv420p",
+            path,
+        ])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("could not start ffmpeg, is it installed?")
+}
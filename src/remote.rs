@@ -0,0 +1,216 @@
+// a tcp server for driving the viewer from other programs. clients send one json object per
+// line and get one json object back per line, eg
+//
+//     {"command": "load", "path": "src/assets/models/ball.obj"}
+//     {"ok": true, "result": 1}
+//
+// connections are served on their own threads, which hand the parsed commands to the event
+// loop and wait for it to reply, so commands always run on the main thread between frames.
+// the server only starts when the config gives it an address to listen on, eg
+//
+//     cargo run -- --set remote.address 127.0.0.1:7878
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+use crate::{json::JsonValue, scene::ModelId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    Load {
+        path: String,
+    },
+    Unload {
+        id: ModelId,
+    },
+    // any of the parts that are left out keep their current value
+    SetTransform {
+        id: ModelId,
+        translation: Option<[f32; 3]>,
+        // a quaternion as x, y, z, w
+        rotation: Option<[f32; 4]>,
+        scale: Option<[f32; 3]>,
+    },
+    SetLight {
+        index: usize,
+        position: Option<[f32; 3]>,
+        color: Option<[f32; 3]>,
+        intensity: Option<f32>,
+    },
+    // the same names the console's set command takes
+    Set {
+        name: String,
+        value: f32,
+    },
+    Screenshot {
+        path: Option<String>,
+    },
+}
+
+impl RemoteCommand {
+    pub fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let string = |key: &str| {
+            json.get(key)
+                .and_then(JsonValue::as_str)
+                .map(str::to_string)
+        };
+        let number = |key: &str| json.get(key).and_then(JsonValue::as_f64);
+        let required = |key: &str| format!("missing or mistyped field {}", key);
+        let id = || {
+            number("id")
+                .map(|id| ModelId::from(id as u32))
+                .ok_or_else(|| required("id"))
+        };
+
+        // the optional fields still have to have the right type when they are given
+        fn optional<T>(
+            json: &JsonValue,
+            key: &str,
+            get: impl Fn(&JsonValue) -> Option<T>,
+        ) -> Result<Option<T>, String> {
+            match json.get(key) {
+                None | Some(JsonValue::Null) => Ok(None),
+                Some(value) => get(value)
+                    .map(Some)
+                    .ok_or_else(|| format!("mistyped field {}", key)),
+            }
+        }
+
+        let command = string("command").ok_or_else(|| required("command"))?;
+        match command.as_str() {
+            "load" => Ok(RemoteCommand::Load {
+                path: string("path").ok_or_else(|| required("path"))?,
+            }),
+            "unload" => Ok(RemoteCommand::Unload { id: id()? }),
+            "set_transform" => Ok(RemoteCommand::SetTransform {
+                id: id()?,
+                translation: optional(json, "translation", JsonValue::as_f32_array)?,
+                rotation: optional(json, "rotation", JsonValue::as_f32_array)?,
+                scale: optional(json, "scale", JsonValue::as_f32_array)?,
+            }),
+            "set_light" => Ok(RemoteCommand::SetLight {
+                index: number("index").unwrap_or(0.0) as usize,
+                position: optional(json, "position", JsonValue::as_f32_array)?,
+                color: optional(json, "color", JsonValue::as_f32_array)?,
+                intensity: optional(json, "intensity", |v| v.as_f64().map(|v| v as f32))?,
+            }),
+            "set" => Ok(RemoteCommand::Set {
+                name: string("name").ok_or_else(|| required("name"))?,
+                value: number("value").ok_or_else(|| required("value"))? as f32,
+            }),
+            "screenshot" => Ok(RemoteCommand::Screenshot {
+                path: optional(json, "path", |v| v.as_str().map(str::to_string))?,
+            }),
+            _ => Err(format!("unknown command {}", command)),
+        }
+    }
+}
+
+// a command waiting for the event loop, which has to answer it with respond
+pub struct RemoteRequest {
+    pub command: RemoteCommand,
+    reply: mpsc::Sender<String>,
+}
+
+impl RemoteRequest {
+    pub fn respond(self, result: Result<JsonValue, String>) {
+        // the client may have disconnected in the meantime, which is fine
+        let _ = self.reply.send(response(result).to_string());
+    }
+}
+
+fn response(result: Result<JsonValue, String>) -> JsonValue {
+    match result {
+        Ok(value) => JsonValue::Object(vec![
+            ("ok".to_string(), JsonValue::Bool(true)),
+            ("result".to_string(), value),
+        ]),
+        Err(error) => JsonValue::Object(vec![
+            ("ok".to_string(), JsonValue::Bool(false)),
+            ("error".to_string(), JsonValue::String(error)),
+        ]),
+    }
+}
+
+pub struct RemoteServer {
+    requests: mpsc::Receiver<RemoteRequest>,
+}
+
+impl RemoteServer {
+    pub fn start(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        log::info!("remote control listening on {}", listener.local_addr()?);
+
+        let (sender, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let sender = sender.clone();
+                        std::thread::spawn(move || serve(stream, sender));
+                    }
+                    Err(e) => log::warn!("remote control connection failed: {}", e),
+                }
+            }
+        });
+
+        Ok(Self { requests })
+    }
+
+    // the requests which arrived since the last call, without blocking
+    pub fn poll(&self) -> impl Iterator<Item = RemoteRequest> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+fn serve(stream: TcpStream, requests: mpsc::Sender<RemoteRequest>) {
+    let peer = stream
+        .peer_addr()
+        .map_or("unknown".to_string(), |a| a.to_string());
+    log::info!("remote control client {} connected", peer);
+
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            log::warn!("remote control client {}: {}", peer, e);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command = JsonValue::parse(&line)
+            .map_err(|e| e.to_string())
+            .and_then(|json| RemoteCommand::from_json(&json));
+
+        let reply = match command {
+            Ok(command) => {
+                let (reply, replies) = mpsc::channel();
+                if requests.send(RemoteRequest { command, reply }).is_err() {
+                    // the event loop has shut down
+                    break;
+                }
+                match replies.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => break,
+                }
+            }
+            Err(error) => response(Err(error)).to_string(),
+        };
+
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+
+    log::info!("remote control client {} disconnected", peer);
+}
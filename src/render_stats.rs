@@ -0,0 +1,277 @@
+// per frame counters for the draw submission path, shown in the window title.
+// everything drawn through a CountingRenderPass is counted, and buffer writes are counted
+// when they go through RenderStats::write_buffer or update()'s UploadBelt.
+// the gpu memory held by meshes and textures is counted too, but for as long as it's alive
+// rather than per frame, see MemoryStats
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub instances: u32,
+    pub triangles: u32,
+    // only counted when the pipeline or bind group actually changes, not for every set call
+    pub pipeline_switches: u32,
+    pub bind_group_switches: u32,
+    // set calls which would have changed nothing, and weren't passed on to wgpu
+    pub redundant_sets: u32,
+    pub buffer_writes: u32,
+}
+
+impl RenderStats {
+    pub fn write_buffer(
+        &mut self,
+        queue: &wgpu::Queue,
+        buffer: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        self.buffer_writes += 1;
+        queue.write_buffer(buffer, offset, data);
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "dc {}  inst {}  tri {}  pso {}  bg {}  skip {}  bw {}",
+            self.draw_calls,
+            self.instances,
+            self.triangles,
+            self.pipeline_switches,
+            self.bind_group_switches,
+            self.redundant_sets,
+            self.buffer_writes,
+        )
+    }
+}
+
+// the buffers and textures allocated by the loaders which are still alive, and their sizes in
+// bytes. only meshes' vertex and index buffers and textures made with Texture::builder are
+// counted, which is the bulk of it, not the small uniform buffers and bind groups
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub buffers: u64,
+    pub buffer_bytes: u64,
+    pub textures: u64,
+    pub texture_bytes: u64,
+}
+
+static BUFFERS: AtomicU64 = AtomicU64::new(0);
+static BUFFER_BYTES: AtomicU64 = AtomicU64::new(0);
+static TEXTURES: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+impl MemoryStats {
+    // the totals so far, from every thread's loads
+    pub fn current() -> Self {
+        Self {
+            buffers: BUFFERS.load(Ordering::Relaxed),
+            buffer_bytes: BUFFER_BYTES.load(Ordering::Relaxed),
+            textures: TEXTURES.load(Ordering::Relaxed),
+            texture_bytes: TEXTURE_BYTES.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mb = |bytes: u64| bytes as f32 / (1024.0 * 1024.0);
+        format!(
+            "buf {} {:.1} MB  tex {} {:.1} MB",
+            self.buffers,
+            mb(self.buffer_bytes),
+            self.textures,
+            mb(self.texture_bytes),
+        )
+    }
+}
+
+// added to the totals when it's made and taken off again when dropped. the mesh or texture
+// which owns the gpu resources keeps it in an Arc, so clones sharing the same resources only
+// count them once, until the last of them is gone
+#[derive(Debug)]
+pub struct Allocation(MemoryStats);
+
+impl Allocation {
+    pub fn buffers(buffers: &[&wgpu::Buffer]) -> Arc<Self> {
+        Self::new(MemoryStats {
+            buffers: buffers.len() as u64,
+            buffer_bytes: buffers.iter().map(|b| b.size()).sum(),
+            ..Default::default()
+        })
+    }
+
+    pub fn texture(texture: &wgpu::Texture) -> Arc<Self> {
+        let format = texture.format();
+        let (block_width, block_height) = format.block_dimensions();
+        // formats with both depth and stencil have no single block size, so count them as 4
+        let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+        let size = texture.size();
+        let level_bytes = (0..texture.mip_level_count())
+            .map(|level| {
+                let width = (size.width >> level).max(1).div_ceil(block_width) as u64;
+                let height = (size.height >> level).max(1).div_ceil(block_height) as u64;
+                width * height * block_size
+            })
+            .sum::<u64>();
+
+        Self::new(MemoryStats {
+            textures: 1,
+            texture_bytes: level_bytes
+                * size.depth_or_array_layers as u64
+                * texture.sample_count() as u64,
+            ..Default::default()
+        })
+    }
+
+    fn new(stats: MemoryStats) -> Arc<Self> {
+        BUFFERS.fetch_add(stats.buffers, Ordering::Relaxed);
+        BUFFER_BYTES.fetch_add(stats.buffer_bytes, Ordering::Relaxed);
+        TEXTURES.fetch_add(stats.textures, Ordering::Relaxed);
+        TEXTURE_BYTES.fetch_add(stats.texture_bytes, Ordering::Relaxed);
+        Arc::new(Self(stats))
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        BUFFERS.fetch_sub(self.0.buffers, Ordering::Relaxed);
+        BUFFER_BYTES.fetch_sub(self.0.buffer_bytes, Ordering::Relaxed);
+        TEXTURES.fetch_sub(self.0.textures, Ordering::Relaxed);
+        TEXTURE_BYTES.fetch_sub(self.0.texture_bytes, Ordering::Relaxed);
+    }
+}
+
+// wraps a render pass, remembering what is bound so that switches can be told apart from
+// redundant set calls, which are dropped instead of being forwarded to wgpu. so drawing meshes
+// sorted by material only costs a bind group switch where the material changes
+pub struct CountingRenderPass<'a> {
+    pass: wgpu::RenderPass<'a>,
+    stats: &'a mut RenderStats,
+    pipeline: Option<wgpu::RenderPipeline>,
+    // with their dynamic offsets, since the same group at another offset is a switch too
+    bind_groups: [Option<(wgpu::BindGroup, Vec<u32>)>; 4],
+}
+
+impl<'a> CountingRenderPass<'a> {
+    pub fn new(pass: wgpu::RenderPass<'a>, stats: &'a mut RenderStats) -> Self {
+        Self {
+            pass,
+            stats,
+            pipeline: None,
+            bind_groups: Default::default(),
+        }
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: &wgpu::RenderPipeline) {
+        if self.pipeline.as_ref() == Some(pipeline) {
+            self.stats.redundant_sets += 1;
+            return;
+        }
+        self.stats.pipeline_switches += 1;
+        self.pipeline = Some(pipeline.clone());
+        self.pass.set_pipeline(pipeline);
+    }
+
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &wgpu::BindGroup, offsets: &[u32]) {
+        let bound = &mut self.bind_groups[index as usize];
+        if bound
+            .as_ref()
+            .is_some_and(|(group, bound_offsets)| group == bind_group && bound_offsets == offsets)
+        {
+            self.stats.redundant_sets += 1;
+            return;
+        }
+        self.stats.bind_group_switches += 1;
+        *bound = Some((bind_group.clone(), offsets.to_vec()));
+        self.pass.set_bind_group(index, bind_group, offsets);
+    }
+
+    pub fn set_vertex_buffer(&mut self, slot: u32, buffer_slice: wgpu::BufferSlice<'_>) {
+        self.pass.set_vertex_buffer(slot, buffer_slice);
+    }
+
+    pub fn set_index_buffer(
+        &mut self,
+        buffer_slice: wgpu::BufferSlice<'_>,
+        format: wgpu::IndexFormat,
+    ) {
+        self.pass.set_index_buffer(buffer_slice, format);
+    }
+
+    pub fn set_stencil_reference(&mut self, reference: u32) {
+        self.pass.set_stencil_reference(reference);
+    }
+
+    // x, y, width, height in pixels
+    pub fn set_viewport(&mut self, [x, y, width, height]: [u32; 4]) {
+        self.pass
+            .set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+    }
+
+    pub fn set_scissor_rect(&mut self, [x, y, width, height]: [u32; 4]) {
+        self.pass.set_scissor_rect(x, y, width, height);
+    }
+
+    // groups and markers name parts of the pass in frame captures, eg in renderdoc
+    pub fn push_debug_group(&mut self, label: &str) {
+        self.pass.push_debug_group(label);
+    }
+
+    pub fn pop_debug_group(&mut self) {
+        self.pass.pop_debug_group();
+    }
+
+    pub fn insert_debug_marker(&mut self, label: &str) {
+        self.pass.insert_debug_marker(label);
+    }
+
+    // all of the pipelines draw triangle lists, so every 3 indices is a triangle
+    pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        let instance_count = instances.len() as u32;
+        self.stats.draw_calls += 1;
+        self.stats.instances += instance_count;
+        self.stats.triangles += indices.len() as u32 / 3 * instance_count;
+        self.pass.draw_indexed(indices, base_vertex, instances);
+    }
+
+    // for shapes made up in the vertex shader, or without an index buffer. counted the same
+    // way, so line lists count a third of their vertices as triangles
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        let instance_count = instances.len() as u32;
+        self.stats.draw_calls += 1;
+        self.stats.instances += instance_count;
+        self.stats.triangles += vertices.len() as u32 / 3 * instance_count;
+        self.pass.draw(vertices, instances);
+    }
+
+    // indirect draws are filled in on the gpu, eg by culling, so only the calls are counted
+    pub fn multi_draw_indexed_indirect(
+        &mut self,
+        indirect_buffer: &wgpu::Buffer,
+        indirect_offset: wgpu::BufferAddress,
+        count: u32,
+    ) {
+        self.stats.draw_calls += 1;
+        self.pass
+            .multi_draw_indexed_indirect(indirect_buffer, indirect_offset, count);
+    }
+
+    pub fn multi_draw_indexed_indirect_count(
+        &mut self,
+        indirect_buffer: &wgpu::Buffer,
+        indirect_offset: wgpu::BufferAddress,
+        count_buffer: &wgpu::Buffer,
+        count_offset: wgpu::BufferAddress,
+        max_count: u32,
+    ) {
+        self.stats.draw_calls += 1;
+        self.pass.multi_draw_indexed_indirect_count(
+            indirect_buffer,
+            indirect_offset,
+            count_buffer,
+            count_offset,
+            max_count,
+        );
+    }
+}
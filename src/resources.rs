@@ -1,160 +1,592 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+    time::SystemTime,
+};
 
-use cgmath::One;
+use cgmath::InnerSpace;
 
 use crate::{
+    assets,
+    error::AssetError,
+    math,
     model::{self, Material},
-    texture,
+    obj_parse::TextureMap,
+    primitives, texture,
 };
 
-pub fn load_text(file_name: &String) -> anyhow::Result<String> {
-    Ok(std::fs::read_to_string(std::path::Path::new(file_name))?)
+// vertices closer than this in position, uv, normal and color are merged when loading models.
+// None keeps the vertices exactly as the OBJ parser produced them
+const WELD_EPSILON: Option<f32> = Some(0.00001);
+
+pub fn load_text(file_name: &String) -> Result<String, AssetError> {
+    let data = load_binary(file_name)?;
+    String::from_utf8(data).map_err(|e| AssetError::Read {
+        path: file_name.clone(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+    })
 }
 
-pub fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
-    Ok(std::fs::read(std::path::Path::new(file_name))?)
+// from the disk, or from memory if it was preloaded, which on the web it has to have been
+pub fn load_binary(file_name: &str) -> Result<Vec<u8>, AssetError> {
+    assets::read(file_name).map_err(|source| AssetError::MissingFile {
+        path: file_name.to_string(),
+        source,
+    })
 }
 
-pub fn load_texture(
-    file_name: &str,
-    device: &wgpu::Device,
-    queue: &wgpu::Queue,
-    is_linear: bool,
-) -> anyhow::Result<texture::Texture> {
-    let data = load_binary(file_name)?;
-    texture::Texture::from_bytes(device, queue, &data, file_name, is_linear)
+// every texture loaded from a file, so that materials which use the same image share one gpu
+// texture. entries are found by path first and then by a hash of the file's contents, which
+// also catches one image saved under two names. samplers are shared by address mode.
+// a path is only reused while the file's modification time is unchanged, so re-exported
+// textures are picked up when their model is reloaded
+pub struct TextureCache {
+    by_path: HashMap<(String, bool, wgpu::AddressMode), (Option<SystemTime>, texture::Texture)>,
+    by_contents: HashMap<(u64, bool, wgpu::AddressMode), texture::Texture>,
+    samplers: HashMap<wgpu::AddressMode, wgpu::Sampler>,
+    // guessed conventions of normal maps, by path and modification time
+    normal_conventions: HashMap<(String, Option<SystemTime>), Option<model::NormalConvention>>,
+    pub placeholders: texture::Placeholders,
 }
 
-pub fn load_material(
-    filepath: &str,
+impl TextureCache {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            by_path: HashMap::new(),
+            by_contents: HashMap::new(),
+            samplers: HashMap::new(),
+            normal_conventions: HashMap::new(),
+            placeholders: texture::Placeholders::new(device, queue),
+        }
+    }
+
+    pub fn load(
+        &mut self,
+        file_name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        is_linear: bool,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<texture::Texture, AssetError> {
+        let path_key = (file_name.to_string(), is_linear, address_mode);
+        let modified = std::fs::metadata(file_name).and_then(|m| m.modified()).ok();
+        if let Some((cached_modified, texture)) = self.by_path.get(&path_key)
+            && *cached_modified == modified
+        {
+            println!("reusing texture {}", file_name);
+            return Ok(texture.clone());
+        }
+
+        let data = load_binary(file_name)?;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        data.hash(&mut hasher);
+        let contents_key = (hasher.finish(), is_linear, address_mode);
+
+        let texture = match self.by_contents.get(&contents_key) {
+            Some(texture) => {
+                println!(
+                    "reusing texture for {}, which has the same contents",
+                    file_name
+                );
+                texture.clone()
+            }
+            None => {
+                let mut texture = texture::Texture::from_bytes(
+                    device,
+                    queue,
+                    &data,
+                    file_name,
+                    is_linear,
+                    address_mode,
+                )?;
+                texture.sampler = self
+                    .samplers
+                    .entry(address_mode)
+                    .or_insert_with(|| texture.sampler.clone())
+                    .clone();
+                self.by_contents.insert(contents_key, texture.clone());
+                texture
+            }
+        };
+
+        self.by_path.insert(path_key, (modified, texture.clone()));
+        Ok(texture)
+    }
+
+    // decodes the image again to look at its pixels, so only for maps with no -normal option.
+    // ktx2 and exr maps, and anything that can't be decoded, are left as None
+    pub fn guess_normal_convention(&mut self, file_name: &str) -> Option<model::NormalConvention> {
+        let modified = std::fs::metadata(file_name).and_then(|m| m.modified()).ok();
+        let key = (file_name.to_string(), modified);
+        if let Some(convention) = self.normal_conventions.get(&key) {
+            return *convention;
+        }
+
+        let convention = load_binary(file_name)
+            .ok()
+            .and_then(|data| image::load_from_memory(&data).ok())
+            .and_then(|image| model::NormalConvention::guess(&image.to_rgba8()));
+        match convention {
+            Some(convention) => println!("{} looks like a {:?} normal map", file_name, convention),
+            None => println!("could not tell which way {}'s green points", file_name),
+        }
+        self.normal_conventions.insert(key, convention);
+        convention
+    }
+}
+
+// builds a material from parsed MTL data, loading every texture map it references.
+// maps which are given but fail to load are an error naming this material, apart from missing
+// files, which are warned about and drawn with a placeholder so the material stands out
+pub fn material_from_parsed(
+    parsed_mtl: &crate::obj_parse::ParsedMTL,
     name: &str,
+    textures: &mut TextureCache,
     device: &wgpu::Device,
     layout: &wgpu::BindGroupLayout,
     queue: &wgpu::Queue,
-) -> Result<model::Material, crate::obj_parse::MTLLoadError> {
-    let parsed_mtl = crate::obj_parse::parse_mtl(filepath, name)?;
+) -> Result<model::Material, AssetError> {
+    // an explicit -normal option wins, otherwise the map is guessed from its image
+    let normal_convention = match &parsed_mtl.map_bump {
+        Some(map) => map.options.normal_convention.or_else(|| {
+            textures.guess_normal_convention(&format!("src/assets/materials/{}", map.file_name))
+        }),
+        None => None,
+    };
 
-    let diffuse_texture = parsed_mtl.map_kd.as_ref().and_then(|dtn| {
-        load_texture(
-            &format!("src/assets/materials/{}", dtn),
-            device,
-            queue,
-            false,
-        )
-        .ok()
-    });
+    // color maps are stored in srgb, everything else is linear data
+    let mut load_map = |map: &Option<TextureMap>, is_linear: bool, kind: &str| {
+        map.as_ref()
+            .map(|map| {
+                let address_mode = if map.options.clamp {
+                    wgpu::AddressMode::ClampToEdge
+                } else {
+                    wgpu::AddressMode::Repeat
+                };
+                let loaded = textures.load(
+                    &format!("src/assets/materials/{}", map.file_name),
+                    device,
+                    queue,
+                    is_linear,
+                    address_mode,
+                );
+                match loaded {
+                    Err(e @ AssetError::MissingFile { .. }) => {
+                        log::warn!("{}, using a placeholder", e.in_material(name));
+                        Ok(textures.placeholders.for_map(kind).clone())
+                    }
+                    loaded => loaded.map_err(|e| e.in_material(name)),
+                }
+            })
+            .transpose()
+    };
 
-    let normal_texture = parsed_mtl.map_bump.as_ref().and_then(|dtn| {
-        load_texture(
-            &format!("src/assets/materials/{}", dtn),
-            device,
-            queue,
-            true,
-        )
-        .ok()
-    });
+    let maps = model::MaterialTextures {
+        diffuse: load_map(&parsed_mtl.map_kd, false, "diffuse")?,
+        normal: load_map(&parsed_mtl.map_bump, true, "normal")?,
+        specular: load_map(&parsed_mtl.map_ks, false, "specular")?,
+        shininess: load_map(&parsed_mtl.map_ns, true, "shininess")?,
+        alpha: load_map(&parsed_mtl.map_d, true, "alpha")?,
+        emissive: load_map(&parsed_mtl.map_ke, false, "emissive")?,
+        roughness: load_map(&parsed_mtl.map_pr, true, "roughness")?,
+        metallic: load_map(&parsed_mtl.map_pm, true, "metallic")?,
+        height: load_map(&parsed_mtl.map_disp, true, "height")?,
+    };
+
+    // the shader has a single uv transform per material, taken from the diffuse map if it has
+    // one and otherwise from whichever map comes first
+    let uv_options = [
+        &parsed_mtl.map_kd,
+        &parsed_mtl.map_bump,
+        &parsed_mtl.map_ks,
+        &parsed_mtl.map_ns,
+        &parsed_mtl.map_d,
+        &parsed_mtl.map_ke,
+        &parsed_mtl.map_pr,
+        &parsed_mtl.map_pm,
+        &parsed_mtl.map_disp,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|map| map.options)
+    .next()
+    .unwrap_or_default();
+
+    let defaults = model::MaterialProperties::default();
+    let properties = model::MaterialProperties {
+        ambient_color: parsed_mtl.ka.unwrap_or(defaults.ambient_color),
+        diffuse_color: parsed_mtl.kd.unwrap_or(defaults.diffuse_color),
+        specular_color: parsed_mtl.ks.unwrap_or(defaults.specular_color),
+        shininess: parsed_mtl.ns.unwrap_or(defaults.shininess),
+        dissolve: parsed_mtl
+            .d
+            .or(parsed_mtl.tr.map(|tr| 1.0 - tr))
+            .unwrap_or(defaults.dissolve),
+        bump_multiplier: parsed_mtl
+            .map_bump
+            .as_ref()
+            .map_or(defaults.bump_multiplier, |map| map.options.bump_multiplier),
+        uv_offset: [uv_options.offset[0], uv_options.offset[1]],
+        uv_scale: [uv_options.scale[0], uv_options.scale[1]],
+        // the maps are multiplied by their scalar, which defaults to 1 when only the map is given
+        emissive_color: parsed_mtl.ke.unwrap_or(if parsed_mtl.map_ke.is_some() {
+            [1.0; 3]
+        } else {
+            defaults.emissive_color
+        }),
+        roughness: parsed_mtl.pr.or(parsed_mtl.map_pr.as_ref().map(|_| 1.0)),
+        metallic: parsed_mtl.pm.unwrap_or(if parsed_mtl.map_pm.is_some() {
+            1.0
+        } else {
+            defaults.metallic
+        }),
+        sheen: parsed_mtl.ps.unwrap_or(defaults.sheen),
+        anisotropy: parsed_mtl.aniso.unwrap_or(defaults.anisotropy),
+        anisotropy_rotation: parsed_mtl.anisor.unwrap_or(defaults.anisotropy_rotation),
+        normal_convention: normal_convention.unwrap_or(defaults.normal_convention),
+        // -bm on the height map scales its depth, as it scales a bump map's slopes
+        height_scale: parsed_mtl
+            .map_disp
+            .as_ref()
+            .map_or(defaults.height_scale, |map| {
+                defaults.height_scale * map.options.bump_multiplier
+            }),
+        parallax_steps: defaults.parallax_steps,
+    };
 
     Ok(model::Material::new(
         device,
         name,
-        diffuse_texture,
-        normal_texture,
-        parsed_mtl.ka.unwrap_or([0.0; 3]),
-        parsed_mtl.kd.unwrap_or([1.0, 0.0, 1.0]),
-        parsed_mtl.ks.unwrap_or([1.0; 3]),
+        maps,
+        &textures.placeholders,
+        properties,
         layout,
     ))
 }
 
+pub fn load_material(
+    filepath: &str,
+    name: &str,
+    textures: &mut TextureCache,
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    queue: &wgpu::Queue,
+) -> Result<model::Material, AssetError> {
+    let parsed_mtl = crate::obj_parse::parse_mtl_file(filepath, name)?;
+
+    material_from_parsed(&parsed_mtl, name, textures, device, layout, queue)
+}
+
 pub fn load_all_materials(
     filepath: &str,
     materials: &mut Vec<model::Material>,
     material_map: &mut HashMap<String, usize>,
+    textures: &mut TextureCache,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
-) {
-    let parsed_mtls = crate::obj_parse::parse_all_mtls(filepath)
-        .unwrap()
-        .into_iter()
-        .map(|pmtl| {
-            let diffuse_texture = pmtl.map_kd.as_ref().and_then(|dtn| {
-                load_texture(
-                    &format!("src/assets/materials/{}", dtn),
-                    device,
-                    queue,
-                    false,
-                )
-                .ok()
-            });
-
-            let normal_texture = pmtl.map_bump.as_ref().and_then(|dtn| {
-                load_texture(
-                    &format!("src/assets/materials/{}", dtn),
-                    device,
-                    queue,
-                    true,
-                )
-                .ok()
-            });
-
-            model::Material::new(
-                device,
-                &pmtl.name.clone().unwrap_or("NONE".to_string()),
-                diffuse_texture,
-                normal_texture,
-                pmtl.ka.unwrap_or([0.0; 3]),
-                pmtl.kd.unwrap_or([1.0, 0.0, 1.0]),
-                pmtl.ks.unwrap_or([1.0; 3]),
-                layout,
-            )
-        });
+) -> Result<(), AssetError> {
+    for pmtl in crate::obj_parse::parse_all_mtls_file(filepath)? {
+        let m = material_from_parsed(
+            &pmtl,
+            &pmtl.name.clone().unwrap_or("NONE".to_string()),
+            textures,
+            device,
+            layout,
+            queue,
+        )?;
 
-    for m in parsed_mtls {
         println!("loaded mtl {}", &m.name);
         material_map.insert(m.name.clone(), materials.len());
         materials.push(m);
     }
+
+    Ok(())
 }
 
 pub fn load_obj_model(
     filepath: &str,
     materials: &mut Vec<model::Material>,
     material_map: &mut HashMap<String, usize>,
+    textures: &mut TextureCache,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
-) -> anyhow::Result<model::Model> {
-    let pobj = crate::obj_parse::parse_obj(filepath).unwrap();
+) -> Result<model::Model, AssetError> {
+    let pobj = crate::obj_parse::parse_obj_file(filepath)?;
+
+    let mut meshes = Vec::with_capacity(pobj.groups.len());
 
-    let material = if let Some(mtl) = pobj.material {
-        if material_map.contains_key(&mtl) {
-            println!("material {} already loaded", &mtl);
-            *material_map.get(&mtl).unwrap()
+    for group in pobj.groups {
+        let material = if let Some(mtl) = group.material {
+            if let Some(&index) = material_map.get(&mtl) {
+                println!("material {} already loaded", &mtl);
+                index
+            } else {
+                println!("loading material {}", &mtl);
+                let material_lib =
+                    pobj.material_lib
+                        .as_ref()
+                        .ok_or_else(|| AssetError::MissingMaterial {
+                            name: mtl.clone(),
+                            path: format!("{} (no mtllib)", filepath),
+                        })?;
+                let new_index = materials.len();
+                materials.push(load_material(
+                    material_lib,
+                    &mtl,
+                    textures,
+                    device,
+                    layout,
+                    queue,
+                )?);
+                material_map.insert(mtl, new_index);
+                new_index
+            }
         } else {
-            println!("loading material {}", &mtl);
-            let new_index = materials.len();
-            materials.push(
-                load_material(&pobj.material_lib.unwrap(), &mtl, device, layout, queue).unwrap(),
-            );
-            material_map.insert(mtl, new_index);
-            new_index
-        }
-    } else {
-        0
+            0
+        };
+
+        let name = format!("{} ({})", filepath, group.name);
+
+        let (verts, indices) = match WELD_EPSILON {
+            Some(epsilon) => {
+                let before = group.model_verts.len();
+                let (verts, indices) = weld_vertices(&group.model_verts, &group.indices, epsilon);
+                println!(
+                    "welded {}: {} -> {} verts, saved {} bytes",
+                    name,
+                    before,
+                    verts.len(),
+                    (before - verts.len()) * std::mem::size_of::<model::ModelVertex>()
+                );
+                (verts, indices)
+            }
+            None => (group.model_verts, group.indices),
+        };
+
+        meshes.push(model::Mesh::from_verts_inds(
+            &device, name, verts, indices, material,
+        ));
+    }
+
+    Ok(model::Model {
+        meshes,
+        transform: math::Transform::identity(),
+    })
+}
+
+// merges vertices whose position, uv, normal and color all match within epsilon, and remaps
+// the indices onto the merged vertices. attributes are snapped to an epsilon sized grid, so two
+// vertices either side of a grid line are kept apart even when they are closer than epsilon
+pub fn weld_vertices(
+    verts: &[model::ModelVertex],
+    indices: &[u32],
+    epsilon: f32,
+) -> (Vec<model::ModelVertex>, Vec<u32>) {
+    let quantize = |values: &[f32]| {
+        values
+            .iter()
+            .map(|v| (v / epsilon).round() as i64)
+            .collect::<Vec<_>>()
     };
 
+    let mut welded = Vec::with_capacity(verts.len());
+    let mut key_map = HashMap::new();
+
+    // where each of the original vertices ended up
+    let remap = verts
+        .iter()
+        .map(|v| {
+            let key = [
+                quantize(&v.position),
+                quantize(&v.tex_coords),
+                quantize(&v.normal),
+                quantize(&v.color),
+            ];
+
+            *key_map.entry(key).or_insert_with(|| {
+                welded.push(*v);
+                (welded.len() - 1) as u32
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let indices = indices.iter().map(|&i| remap[i as usize]).collect();
+
+    (welded, indices)
+}
+
+// PLY files have no materials, so the mesh uses the first loaded material (like OBJ groups
+// without a usemtl). the vertex colors are what usually carries the look of scan data
+pub fn load_ply_model(filepath: &str, device: &wgpu::Device) -> Result<model::Model, AssetError> {
+    let pply = crate::ply_parse::parse_ply_file(filepath)?;
+    println!("loaded ply {}:\n{}", filepath, pply);
+
     let mesh = model::Mesh::from_verts_inds(
-        &device,
+        device,
         filepath.to_string(),
-        pobj.model_verts,
-        pobj.indices,
-        material,
+        pply.model_verts,
+        pply.indices,
+        0,
     );
+
     Ok(model::Model {
         meshes: vec![mesh],
-        position: [0.0, 0.0, 0.0],
-        rotation: cgmath::Quaternion::one(),
-        scale: 1.0,
+        transform: math::Transform::identity(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainSettings {
+    // the width and length of the whole terrain, and the height of white in the heightmap
+    pub scale: [f32; 3],
+    // how far it is in world units before the material's textures repeat
+    pub uv_tile_size: f32,
+    // the most quads along each side of a chunk, so large terrains are split into meshes of
+    // their own. None keeps it as one mesh
+    pub chunk_size: Option<u32>,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            scale: [64.0, 8.0, 64.0],
+            uv_tile_size: 4.0,
+            chunk_size: Some(64),
+        }
+    }
+}
+
+// a grid with a vertex for every pixel of a greyscale heightmap, centered on the origin with
+// the image's top row at -z. it uses the first loaded material, like a ply
+pub fn load_heightmap_terrain(
+    filepath: &str,
+    settings: TerrainSettings,
+    device: &wgpu::Device,
+) -> Result<model::Model, AssetError> {
+    let image = image::load_from_memory(&load_binary(filepath)?)
+        .map_err(|source| AssetError::BadImage {
+            path: filepath.to_string(),
+            source,
+        })?
+        .to_luma16();
+    let (width, depth) = image.dimensions();
+    if width < 2 || depth < 2 {
+        return Err(AssetError::BadData {
+            path: filepath.to_string(),
+            message: format!("a {}x{} heightmap is too small for a terrain", width, depth),
+        });
+    }
+
+    let [size_x, size_y, size_z] = settings.scale;
+    let spacing = [size_x / (width - 1) as f32, size_z / (depth - 1) as f32];
+    // clamped at the edges, so the normals there come from one side's slope
+    let height = |x: i64, z: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let z = z.clamp(0, depth as i64 - 1) as u32;
+        image.get_pixel(x, z).0[0] as f32 / u16::MAX as f32 * size_y
+    };
+
+    let vertex = |x: u32, z: u32| {
+        let position = [
+            x as f32 * spacing[0] - size_x / 2.0,
+            height(x as i64, z as i64),
+            z as f32 * spacing[1] - size_z / 2.0,
+        ];
+        let (x, z) = (x as i64, z as i64);
+        let slope_x = (height(x + 1, z) - height(x - 1, z)) / (2.0 * spacing[0]);
+        let slope_z = (height(x, z + 1) - height(x, z - 1)) / (2.0 * spacing[1]);
+        let normal = cgmath::Vector3::new(-slope_x, 1.0, -slope_z).normalize();
+        model::ModelVertex {
+            position,
+            // v points up like an obj's, which is along -z here
+            tex_coords: [
+                position[0] / settings.uv_tile_size,
+                -position[2] / settings.uv_tile_size,
+            ],
+            normal: normal.into(),
+            tangent: [0.0; 4],
+            color: [1.0; 3],
+        }
+    };
+
+    // chunks share the vertices along their edges, and the normals are worked out from the
+    // whole heightmap, so there are no seams between them
+    let chunk_size = settings
+        .chunk_size
+        .unwrap_or(u32::MAX)
+        .clamp(1, (width - 1).max(depth - 1));
+    let mut meshes = Vec::new();
+    for chunk_z in (0..depth - 1).step_by(chunk_size as usize) {
+        for chunk_x in (0..width - 1).step_by(chunk_size as usize) {
+            let columns = chunk_size.min(width - 1 - chunk_x);
+            let rows = chunk_size.min(depth - 1 - chunk_z);
+
+            let mut verts = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+            for z in chunk_z..=chunk_z + rows {
+                for x in chunk_x..=chunk_x + columns {
+                    verts.push(vertex(x, z));
+                }
+            }
+            let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+            for row in 0..rows {
+                for column in 0..columns {
+                    let a = row * (columns + 1) + column;
+                    let below = a + columns + 1;
+                    indices.extend_from_slice(&[a, below, below + 1, a, below + 1, a + 1]);
+                }
+            }
+
+            meshes.push(model::Mesh::from_verts_inds(
+                device,
+                format!("{} chunk {},{}", filepath, chunk_x, chunk_z),
+                verts,
+                indices,
+                0,
+            ));
+        }
+    }
+    println!(
+        "loaded {}x{} heightmap {} as {} terrain chunks",
+        width,
+        depth,
+        filepath,
+        meshes.len()
+    );
+
+    Ok(model::Model {
+        meshes,
+        transform: math::Transform::identity(),
     })
 }
+
+// loads a model by its file extension, or generates one from a primitive:<name> or
+// terrain:<heightmap path> path
+pub fn load_model(
+    filepath: &str,
+    materials: &mut Vec<model::Material>,
+    material_map: &mut HashMap<String, usize>,
+    textures: &mut TextureCache,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> Result<model::Model, AssetError> {
+    if let Some(name) = filepath.strip_prefix("primitive:") {
+        primitives::load_model(device, name)
+    } else if let Some(heightmap) = filepath.strip_prefix("terrain:") {
+        load_heightmap_terrain(heightmap, TerrainSettings::default(), device)
+    } else if filepath.to_ascii_lowercase().ends_with(".ply") {
+        load_ply_model(filepath, device)
+    } else {
+        load_obj_model(
+            filepath,
+            materials,
+            material_map,
+            textures,
+            device,
+            queue,
+            layout,
+        )
+    }
+}
@@ -0,0 +1,287 @@
+// the models which make up the scene. every model's transform lives in one uniform buffer,
+// a slot per model, and one per object bind group is bound with each model's dynamic offset.
+// the same buffer can be read as storage too, for finding a model's transform by its index
+
+use crate::{animation, math, model, render_stats::RenderStats};
+
+// a handle to a model in the scene. ids aren't reused, so an old id never refers to a model
+// that was added after it was removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModelId(u32);
+
+impl std::fmt::Display for ModelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u32> for ModelId {
+    fn from(id: u32) -> Self {
+        ModelId(id)
+    }
+}
+
+impl From<ModelId> for u32 {
+    fn from(id: ModelId) -> Self {
+        id.0
+    }
+}
+
+impl std::str::FromStr for ModelId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(ModelId)
+    }
+}
+
+// what's under a point on the screen, see State::pick
+#[derive(Debug, Clone, Copy)]
+pub struct PickResult {
+    pub model: ModelId,
+    // the mesh of the model and the triangle of the mesh, see model::RayHit
+    pub mesh: usize,
+    pub triangle: usize,
+    // in world space, with the normal facing back towards the camera
+    pub position: cgmath::Point3<f32>,
+    pub normal: cgmath::Vector3<f32>,
+    pub distance: f32,
+}
+
+pub struct SceneObject {
+    pub id: ModelId,
+    pub model: model::Model,
+    // the file the model was loaded from, for reloading it
+    pub source: String,
+    // poses the model while animations play
+    pub animator: Option<animation::Animator>,
+}
+
+pub struct Scene {
+    objects: Vec<SceneObject>,
+    next_id: u32,
+    // object i's transform is at i * transform_stride, which keeps the offsets aligned for
+    // binding. the slots are rewritten every frame, so removing an object just shifts them
+    transform_buffer: wgpu::Buffer,
+    transform_stride: u32,
+    transform_capacity: usize,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Scene {
+    const INITIAL_CAPACITY: usize = 16;
+    pub const TRANSFORM_SIZE: wgpu::BufferSize =
+        wgpu::BufferSize::new(std::mem::size_of::<model::ModelTransformationUniform>() as u64)
+            .unwrap();
+
+    pub fn new(device: &wgpu::Device, per_object_layout: &wgpu::BindGroupLayout) -> Self {
+        let transform_stride = (Self::TRANSFORM_SIZE.get() as u32)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let (transform_buffer, bind_group) = Self::create_transform_buffer(
+            device,
+            per_object_layout,
+            transform_stride,
+            Self::INITIAL_CAPACITY,
+        );
+
+        Self {
+            objects: Vec::new(),
+            next_id: 0,
+            transform_buffer,
+            transform_stride,
+            transform_capacity: Self::INITIAL_CAPACITY,
+            bind_group,
+        }
+    }
+
+    fn create_transform_buffer(
+        device: &wgpu::Device,
+        per_object_layout: &wgpu::BindGroupLayout,
+        stride: u32,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("model transform buffer"),
+            size: stride as wgpu::BufferAddress * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("per object bind group"),
+            layout: per_object_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: Some(Self::TRANSFORM_SIZE),
+                }),
+            }],
+        });
+
+        (buffer, bind_group)
+    }
+
+    pub fn add(
+        &mut self,
+        device: &wgpu::Device,
+        per_object_layout: &wgpu::BindGroupLayout,
+        model: model::Model,
+        source: &str,
+    ) -> ModelId {
+        let id = ModelId(self.next_id);
+        self.next_id += 1;
+
+        // out of slots, so double the buffer. nothing needs copying over since every
+        // transform is uploaded again before the next draw
+        if self.objects.len() == self.transform_capacity {
+            self.transform_capacity *= 2;
+            (self.transform_buffer, self.bind_group) = Self::create_transform_buffer(
+                device,
+                per_object_layout,
+                self.transform_stride,
+                self.transform_capacity,
+            );
+        }
+
+        self.objects.push(SceneObject {
+            id,
+            model,
+            source: source.to_string(),
+            animator: None,
+        });
+        id
+    }
+
+    // returns the removed model, or None if the id isn't in the scene
+    pub fn remove(&mut self, id: ModelId) -> Option<model::Model> {
+        let index = self.objects.iter().position(|o| o.id == id)?;
+        Some(self.objects.remove(index).model)
+    }
+
+    pub fn get_mut(&mut self, id: ModelId) -> Option<&mut model::Model> {
+        self.objects
+            .iter_mut()
+            .find(|o| o.id == id)
+            .map(|o| &mut o.model)
+    }
+
+    // gives a model an animator, or takes it away with None. false if there is no such model
+    pub fn set_animator(&mut self, id: ModelId, animator: Option<animation::Animator>) -> bool {
+        match self.objects.iter_mut().find(|o| o.id == id) {
+            Some(object) => {
+                object.animator = animator;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // advances every animator and poses its model, returning whether any models moved
+    pub fn animate(&mut self, dt: f32) -> bool {
+        let mut has_moved = false;
+        for object in &mut self.objects {
+            if let Some(animator) = &mut object.animator {
+                animator.advance(dt, &mut object.model.transform);
+                has_moved = true;
+            }
+        }
+        has_moved
+    }
+
+    // the first object loaded from path, comparing the resolved paths when both exist so that
+    // eg `./model.obj` and `model.obj` match
+    pub fn find_source(&self, path: &str) -> Option<ModelId> {
+        let resolved = std::fs::canonicalize(path).ok();
+        self.objects
+            .iter()
+            .find(|o| match (&resolved, std::fs::canonicalize(&o.source)) {
+                (Some(resolved), Ok(source)) => *resolved == source,
+                _ => o.source == path,
+            })
+            .map(|o| o.id)
+    }
+
+    pub fn objects(&self) -> &[SceneObject] {
+        &self.objects
+    }
+
+    // the dynamic offset of the index'th object's transform
+    pub fn transform_offset(&self, index: usize) -> u32 {
+        index as u32 * self.transform_stride
+    }
+
+    // every object's transform at once, each in a slot transform_stride bytes long, for
+    // reading them from storage by the object's index
+    pub fn transforms(&self) -> wgpu::BufferBinding<'_> {
+        self.transform_buffer.as_entire_buffer_binding()
+    }
+
+    pub fn transform_stride(&self) -> u32 {
+        self.transform_stride
+    }
+
+    // writes every object's transform into its slot with a single buffer write
+    pub fn upload_transforms(&self, queue: &wgpu::Queue, stats: &mut RenderStats) {
+        if self.objects.is_empty() {
+            return;
+        }
+
+        let mut data = vec![0u8; self.objects.len() * self.transform_stride as usize];
+        for (slot, object) in data
+            .chunks_exact_mut(self.transform_stride as usize)
+            .zip(&self.objects)
+        {
+            let transform = model::ModelTransformationUniform::from_model(&object.model);
+            slot[..Self::TRANSFORM_SIZE.get() as usize]
+                .copy_from_slice(bytemuck::bytes_of(&transform));
+        }
+        stats.write_buffer(queue, &self.transform_buffer, 0, &data);
+    }
+
+    pub fn models(&self) -> Vec<&model::Model> {
+        self.objects.iter().map(|o| &o.model).collect()
+    }
+
+    // the box around every model where it is now, or None when the scene is empty
+    pub fn bounds(&self) -> Option<math::Aabb> {
+        math::Aabb::from_points(self.objects.iter().flat_map(|o| world_corners(&o.model)))
+    }
+
+    // the box around one model where it is now, or None if the id isn't in the scene or the
+    // model has no shown meshes
+    pub fn model_bounds(&self, id: ModelId) -> Option<math::Aabb> {
+        let object = self.objects.iter().find(|o| o.id == id)?;
+        math::Aabb::from_points(world_corners(&object.model))
+    }
+
+    // the closest hit over every model
+    pub fn raycast(&self, ray: &math::Ray) -> Option<PickResult> {
+        self.objects
+            .iter()
+            .filter_map(|o| {
+                let hit = o.model.raycast(ray)?;
+                Some(PickResult {
+                    model: o.id,
+                    mesh: hit.mesh,
+                    triangle: hit.triangle,
+                    position: hit.position,
+                    normal: hit.normal,
+                    distance: hit.distance,
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+// the corners of each of the model's shown meshes' boxes, moved by its transform
+fn world_corners(model: &model::Model) -> impl Iterator<Item = cgmath::Vector3<f32>> + '_ {
+    model
+        .meshes
+        .iter()
+        .filter(|mesh| mesh.is_visible)
+        .flat_map(|mesh| mesh.bounds.transformed(&model.transform).corners())
+}
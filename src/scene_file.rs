@@ -0,0 +1,349 @@
+// json scene files, which place models, set up the point lights and the camera, and give models
+// and lights animations (see animation.rs). loaded with the console's load command like a model,
+// and written by its save command from the scene as it is, eg
+//
+//     {
+//         "camera": {"position": [0, 2, 8], "yaw": -90, "pitch": -10},
+//         "models": [
+//             {
+//                 "path": "src/assets/models/bunny2.obj",
+//                 "position": [0, 0, 0],
+//                 "rotation": [0, 0.7071, 0, 0.7071],
+//                 "scale": 2,
+//                 "animation": {
+//                     "position": {"path": "lissajous", "amplitude": [2, 0.5, 0], "frequency": [0.2, 0.4, 0]},
+//                     "rotation": {"keyframes": [[0, [0, 0, 0]], [4, [0, 360, 0]]]},
+//                     "scale": {"keyframes": [[0, [2, 2, 2]], [1, [2.5, 2.5, 2.5]], [2, [2, 2, 2]]], "interpolation": "cubic"},
+//                     "speed": 0.5
+//                 }
+//             }
+//         ],
+//         "lights": [
+//             {
+//                 "color": [1, 0.9, 0.8],
+//                 "intensity": 2,
+//                 "animation": {
+//                     "position": {"path": "circle", "center": [0, 8, 0], "radius": 10, "period": 12},
+//                     "color": {"keyframes": [[0, [1, 0.9, 0.8]], [6, [0.4, 0.5, 1]], [12, [1, 0.9, 0.8]]]}
+//                 }
+//             }
+//         ],
+//         "weather": {"precipitation": "rain", "intensity": 0.8}
+//     }
+//
+// the rotation is a quaternion as [x, y, z, w], and the camera's angles are in degrees. a model
+// can give a "matrix" instead of its position, rotation and scale, as 16 numbers a column at a
+// time like gltf's, which is decomposed into them (any shear is dropped with a warning). the
+// camera can also give its "near" and "far" planes, or "fit_z_planes": true to fit them around
+// the scene every frame. the lights are the scene's existing point lights in order, since their
+// count can't change. the weather's precipitation is "clear", "rain" or "snow" (see
+// weather.rs), with an intensity from 0 to 1 which is 1 if left out. anything left out keeps
+// its current value. src/assets/scenes/orbiting_light.json is an example
+
+use crate::{
+    animation::{Animator, LightAnimation},
+    json::JsonValue,
+    math,
+    weather::Precipitation,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelEntry {
+    pub path: String,
+    pub position: Option<[f32; 3]>,
+    pub rotation: Option<[f32; 4]>,
+    pub scale: Option<[f32; 3]>,
+    pub animator: Option<Animator>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightEntry {
+    pub position: Option<[f32; 3]>,
+    pub color: Option<[f32; 3]>,
+    pub intensity: Option<f32>,
+    pub animation: Option<LightAnimation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraEntry {
+    pub position: [f32; 3],
+    // in degrees
+    pub yaw: f32,
+    pub pitch: f32,
+    pub near: Option<f32>,
+    pub far: Option<f32>,
+    pub fit_z_planes: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeatherEntry {
+    pub precipitation: Precipitation,
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneFile {
+    pub camera: Option<CameraEntry>,
+    pub models: Vec<ModelEntry>,
+    pub lights: Vec<LightEntry>,
+    pub weather: Option<WeatherEntry>,
+}
+
+impl SceneFile {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read scene {}: {}", path, e))?;
+        let json = JsonValue::parse(&text).map_err(|e| anyhow::anyhow!("scene {}: {}", path, e))?;
+        Self::from_json(&json).map_err(|e| anyhow::anyhow!("scene {}: {}", path, e))
+    }
+
+    // animations aren't written, so a saved scene holds where everything was when it was saved
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_text())
+            .map_err(|e| anyhow::anyhow!("could not write scene {}: {}", path, e))
+    }
+
+    // laid out like the example above, with an entry per line
+    fn to_text(&self) -> String {
+        let number = |n: f32| JsonValue::Number(n as f64);
+        let array = |values: &[f32]| JsonValue::Array(values.iter().copied().map(number).collect());
+        let entries = |entries: Vec<JsonValue>| {
+            let lines: Vec<String> = entries.iter().map(|e| format!("        {}", e)).collect();
+            format!("[\n{}\n    ]", lines.join(",\n"))
+        };
+
+        let mut sections = Vec::new();
+        if let Some(camera) = &self.camera {
+            let mut fields = vec![
+                ("position".to_string(), array(&camera.position)),
+                ("yaw".into(), number(camera.yaw)),
+                ("pitch".into(), number(camera.pitch)),
+            ];
+            if let Some(near) = camera.near {
+                fields.push(("near".into(), number(near)));
+            }
+            if let Some(far) = camera.far {
+                fields.push(("far".into(), number(far)));
+            }
+            if let Some(fit_z_planes) = camera.fit_z_planes {
+                fields.push(("fit_z_planes".into(), JsonValue::Bool(fit_z_planes)));
+            }
+            sections.push(format!("    \"camera\": {}", JsonValue::Object(fields)));
+        }
+
+        let models = self
+            .models
+            .iter()
+            .map(|model| {
+                let mut fields = vec![("path".to_string(), JsonValue::String(model.path.clone()))];
+                if let Some(position) = &model.position {
+                    fields.push(("position".into(), array(position)));
+                }
+                if let Some(rotation) = &model.rotation {
+                    fields.push(("rotation".into(), array(rotation)));
+                }
+                if let Some(scale) = &model.scale {
+                    fields.push(("scale".into(), array(scale)));
+                }
+                JsonValue::Object(fields)
+            })
+            .collect();
+        sections.push(format!("    \"models\": {}", entries(models)));
+
+        let lights = self
+            .lights
+            .iter()
+            .map(|light| {
+                let mut fields = Vec::new();
+                if let Some(position) = &light.position {
+                    fields.push(("position".to_string(), array(position)));
+                }
+                if let Some(color) = &light.color {
+                    fields.push(("color".into(), array(color)));
+                }
+                if let Some(intensity) = light.intensity {
+                    fields.push(("intensity".into(), number(intensity)));
+                }
+                JsonValue::Object(fields)
+            })
+            .collect();
+        sections.push(format!("    \"lights\": {}", entries(lights)));
+
+        if let Some(weather) = &self.weather {
+            let fields = vec![
+                (
+                    "precipitation".to_string(),
+                    JsonValue::String(weather.precipitation.name().to_string()),
+                ),
+                ("intensity".into(), number(weather.intensity)),
+            ];
+            sections.push(format!("    \"weather\": {}", JsonValue::Object(fields)));
+        }
+
+        format!("{{\n{}\n}}\n", sections.join(",\n"))
+    }
+
+    fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let entries = |key: &str| match json.get(key) {
+            None => Ok(&[][..]),
+            Some(value) => value
+                .as_array()
+                .ok_or_else(|| format!("{} should be an array", key)),
+        };
+
+        let models = entries("models")?
+            .iter()
+            .map(|model| -> Result<ModelEntry, String> {
+                let path = model
+                    .get("path")
+                    .and_then(JsonValue::as_str)
+                    .ok_or("every model needs a path")?
+                    .to_string();
+                let (position, rotation, scale) = match model.get("matrix") {
+                    Some(_)
+                        if ["position", "rotation", "scale"]
+                            .iter()
+                            .any(|key| model.get(key).is_some()) =>
+                    {
+                        return Err(format!(
+                            "{} has a matrix, so it can't also have a position, rotation or scale",
+                            path
+                        ));
+                    }
+                    Some(matrix) => {
+                        let matrix = matrix
+                            .as_f32_array::<16>()
+                            .ok_or("matrix should be 16 numbers, a column at a time")?;
+                        let columns: [[f32; 4]; 4] =
+                            std::array::from_fn(|i| std::array::from_fn(|j| matrix[i * 4 + j]));
+                        let transform = math::Transform::from_matrix(columns.into());
+                        let rotation = transform.rotation();
+                        (
+                            Some(transform.translation().into()),
+                            Some([rotation.v.x, rotation.v.y, rotation.v.z, rotation.s]),
+                            Some(transform.scale().into()),
+                        )
+                    }
+                    None => (
+                        vector(model, "position")?,
+                        match model.get("rotation") {
+                            None => None,
+                            Some(value) => Some(
+                                value
+                                    .as_f32_array::<4>()
+                                    .ok_or("rotation should be [x, y, z, w]")?,
+                            ),
+                        },
+                        // a single number scales every axis the same
+                        match model.get("scale").and_then(JsonValue::as_f64) {
+                            Some(scale) => Some([scale as f32; 3]),
+                            None => vector(model, "scale")?,
+                        },
+                    ),
+                };
+
+                Ok(ModelEntry {
+                    path,
+                    position,
+                    rotation,
+                    scale,
+                    animator: model
+                        .get("animation")
+                        .map(Animator::from_json)
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let lights = entries("lights")?
+            .iter()
+            .enumerate()
+            .map(|(index, light)| -> Result<LightEntry, String> {
+                Ok(LightEntry {
+                    position: vector(light, "position")?,
+                    color: vector(light, "color")?,
+                    intensity: match light.get("intensity") {
+                        None => None,
+                        Some(value) => {
+                            Some(value.as_f64().ok_or("intensity should be a number")? as f32)
+                        }
+                    },
+                    animation: light
+                        .get("animation")
+                        .map(|json| LightAnimation::from_json(index, json))
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let camera = match json.get("camera") {
+            None => None,
+            Some(camera) => {
+                let angle = |key: &str| {
+                    camera
+                        .get(key)
+                        .and_then(JsonValue::as_f64)
+                        .map(|degrees| degrees as f32)
+                        .ok_or_else(|| format!("the camera needs a {} in degrees", key))
+                };
+                let distance = |key: &str| match camera.get(key) {
+                    None => Ok(None),
+                    Some(value) => value
+                        .as_f64()
+                        .map(|distance| Some(distance as f32))
+                        .ok_or_else(|| format!("the camera's {} should be a distance", key)),
+                };
+                Some(CameraEntry {
+                    position: vector(camera, "position")?.ok_or("the camera needs a position")?,
+                    yaw: angle("yaw")?,
+                    pitch: angle("pitch")?,
+                    near: distance("near")?,
+                    far: distance("far")?,
+                    fit_z_planes: match camera.get("fit_z_planes") {
+                        None => None,
+                        Some(value) => Some(
+                            value
+                                .as_bool()
+                                .ok_or("fit_z_planes should be true or false")?,
+                        ),
+                    },
+                })
+            }
+        };
+
+        let weather = match json.get("weather") {
+            None => None,
+            Some(weather) => Some(WeatherEntry {
+                precipitation: weather
+                    .get("precipitation")
+                    .and_then(JsonValue::as_str)
+                    .and_then(Precipitation::from_name)
+                    .ok_or("the weather's precipitation should be clear, rain or snow")?,
+                intensity: match weather.get("intensity") {
+                    None => 1.0,
+                    Some(value) => value
+                        .as_f64()
+                        .ok_or("the weather's intensity should be a number")?
+                        as f32,
+                },
+            }),
+        };
+
+        Ok(Self {
+            camera,
+            models,
+            lights,
+            weather,
+        })
+    }
+}
+
+fn vector(json: &JsonValue, key: &str) -> Result<Option<[f32; 3]>, String> {
+    match json.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_f32_array::<3>()
+            .map(Some)
+            .ok_or_else(|| format!("{} should be [x, y, z]", key)),
+    }
+}
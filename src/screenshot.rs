@@ -0,0 +1,28 @@
+// reading a rendered frame back from the gpu and saving it as an image
+
+use crate::readback;
+
+// copies a 2d color texture into an rgba8 image, blocking until the gpu has finished. the
+// texture needs COPY_SRC and an 8 bit rgba or bgra format
+pub fn capture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> anyhow::Result<image::RgbaImage> {
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("screenshot command encoder"),
+    });
+    let readback = readback::Readback::encode(device, &mut command_encoder, texture)?;
+    queue.submit(std::iter::once(command_encoder.finish()));
+    readback.wait(device)
+}
+
+// a file name in the working directory which won't clash with earlier captures, eg
+// screenshot_<millis>.png for a `kind` of screenshot
+pub fn default_path(kind: &str) -> String {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("{}_{}.png", kind, millis)
+}
@@ -0,0 +1,330 @@
+// rhai scripts for building scenes procedurally, moving the camera and automating tests, run
+// with the console's `script <path>` command. only built with the scripting feature, eg
+//
+//     cargo run --features scripting
+//
+// scripts can't hold on to State while they run, so the functions they call queue up commands
+// which State carries out once the script returns, mostly through the same code the remote
+// control server uses. a script that defines `fn update(time)` is called again every frame
+// with the seconds since it started, until it calls stop() or another script replaces it:
+//
+//     let cube = load("src/assets/models/cube-flat.obj");
+//     fn update(time) {
+//         set_camera(10.0 * cos(time), 3.0, 10.0 * sin(time), 0.0, 0.0, 0.0);
+//     }
+//
+// rhai doesn't convert between its integers and floats, so the numbers passed to these
+// functions need a decimal point. load returns a handle to the model, which the other model
+// functions take; handles count the models the script loaded, from 0. there is an example in
+// src/assets/scripts/turntable.rhai
+
+use std::{cell::RefCell, rc::Rc, time::Instant};
+
+use rhai::{AST, Engine, EvalAltResult, FLOAT, INT, Scope};
+
+use crate::{remote::RemoteCommand, scene::ModelId};
+
+const UPDATE_FUNCTION: &str = "update";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCommand {
+    Load(String),
+    // the model handles are resolved to ids by Script::model
+    Unload(usize),
+    Transform {
+        model: usize,
+        translation: Option<[f32; 3]>,
+        rotation: Option<[f32; 4]>,
+        scale: Option<[f32; 3]>,
+    },
+    // angles in degrees
+    Camera {
+        position: [f32; 3],
+        yaw: f32,
+        pitch: f32,
+    },
+    // lights, settings and screenshots, which are the same as the remote's
+    Remote(RemoteCommand),
+    Stop,
+}
+
+pub struct Script {
+    pub path: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    commands: Rc<RefCell<Vec<ScriptCommand>>>,
+    // the ids the script's loads got, by handle. None where the load failed
+    models: Vec<Option<ModelId>>,
+    start: Instant,
+    is_animated: bool,
+}
+
+impl Script {
+    // compiles the script and runs its top level, leaving what it asked for in commands
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read script {}: {}", path, e))?;
+
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let engine = Self::create_engine(&commands);
+        let ast = engine
+            .compile(&source)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        let is_animated = ast
+            .iter_functions()
+            .any(|f| f.name == UPDATE_FUNCTION && f.params.len() == 1);
+
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            engine,
+            ast,
+            scope,
+            commands,
+            models: Vec::new(),
+            start: Instant::now(),
+            is_animated,
+        })
+    }
+
+    fn create_engine(commands: &Rc<RefCell<Vec<ScriptCommand>>>) -> Engine {
+        let mut engine = Engine::new();
+        engine.on_print(|text| log::info!("script: {}", text));
+        engine.on_debug(|text, _, position| log::debug!("script {}: {}", position, text));
+
+        // every function just queues its command, so they are all made the same way
+        let queue = |command: ScriptCommand| {
+            let commands = commands.clone();
+            move || commands.borrow_mut().push(command.clone())
+        };
+        let push = {
+            let commands = commands.clone();
+            move |command: ScriptCommand| commands.borrow_mut().push(command)
+        };
+        let handle = |model: INT| -> Result<usize, Box<EvalAltResult>> {
+            usize::try_from(model).map_err(|_| format!("{} is not a model handle", model).into())
+        };
+
+        // scene
+        let loads = Rc::new(RefCell::new(0));
+        {
+            let push = push.clone();
+            engine.register_fn("load", move |path: &str| -> INT {
+                push(ScriptCommand::Load(path.to_string()));
+                let mut loads = loads.borrow_mut();
+                *loads += 1;
+                *loads - 1
+            });
+        }
+        {
+            let push = push.clone();
+            engine.register_fn("unload", move |model: INT| {
+                push(ScriptCommand::Unload(handle(model)?));
+                Ok::<_, Box<EvalAltResult>>(())
+            });
+        }
+        {
+            let push = push.clone();
+            engine.register_fn(
+                "set_position",
+                move |model: INT, x: FLOAT, y: FLOAT, z: FLOAT| {
+                    push(ScriptCommand::Transform {
+                        model: handle(model)?,
+                        translation: Some([x as f32, y as f32, z as f32]),
+                        rotation: None,
+                        scale: None,
+                    });
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+        {
+            // an angle in degrees around an axis
+            let push = push.clone();
+            engine.register_fn(
+                "set_rotation",
+                move |model: INT, x: FLOAT, y: FLOAT, z: FLOAT, degrees: FLOAT| {
+                    let length = (x * x + y * y + z * z).sqrt();
+                    if length == 0.0 {
+                        return Err("the rotation axis has no length".into());
+                    }
+                    let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+                    let axis = [x, y, z].map(|c| (c / length * sin) as f32);
+                    push(ScriptCommand::Transform {
+                        model: handle(model)?,
+                        translation: None,
+                        rotation: Some([axis[0], axis[1], axis[2], cos as f32]),
+                        scale: None,
+                    });
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+        {
+            let push = push.clone();
+            engine.register_fn("set_scale", move |model: INT, scale: FLOAT| {
+                push(ScriptCommand::Transform {
+                    model: handle(model)?,
+                    translation: None,
+                    rotation: None,
+                    scale: Some([scale as f32; 3]),
+                });
+                Ok::<_, Box<EvalAltResult>>(())
+            });
+        }
+
+        // camera
+        {
+            let push = push.clone();
+            engine.register_fn(
+                "set_camera",
+                move |x: FLOAT,
+                      y: FLOAT,
+                      z: FLOAT,
+                      target_x: FLOAT,
+                      target_y: FLOAT,
+                      target_z: FLOAT| {
+                    let direction = [target_x - x, target_y - y, target_z - z];
+                    let horizontal = direction[0].hypot(direction[2]);
+                    push(ScriptCommand::Camera {
+                        position: [x as f32, y as f32, z as f32],
+                        yaw: direction[2].atan2(direction[0]).to_degrees() as f32,
+                        pitch: direction[1].atan2(horizontal).to_degrees() as f32,
+                    });
+                },
+            );
+        }
+        {
+            let push = push.clone();
+            engine.register_fn(
+                "set_camera_angles",
+                move |x: FLOAT, y: FLOAT, z: FLOAT, yaw: FLOAT, pitch: FLOAT| {
+                    push(ScriptCommand::Camera {
+                        position: [x as f32, y as f32, z as f32],
+                        yaw: yaw as f32,
+                        pitch: pitch as f32,
+                    });
+                },
+            );
+        }
+
+        // lights
+        let light = |index: INT| -> Result<usize, Box<EvalAltResult>> {
+            usize::try_from(index).map_err(|_| format!("there is no point light {}", index).into())
+        };
+        {
+            let push = push.clone();
+            engine.register_fn(
+                "set_light_position",
+                move |index: INT, x: FLOAT, y: FLOAT, z: FLOAT| {
+                    push(ScriptCommand::Remote(RemoteCommand::SetLight {
+                        index: light(index)?,
+                        position: Some([x as f32, y as f32, z as f32]),
+                        color: None,
+                        intensity: None,
+                    }));
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+        {
+            let push = push.clone();
+            engine.register_fn(
+                "set_light_color",
+                move |index: INT, r: FLOAT, g: FLOAT, b: FLOAT| {
+                    push(ScriptCommand::Remote(RemoteCommand::SetLight {
+                        index: light(index)?,
+                        position: None,
+                        color: Some([r as f32, g as f32, b as f32]),
+                        intensity: None,
+                    }));
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+        {
+            let push = push.clone();
+            engine.register_fn(
+                "set_light_intensity",
+                move |index: INT, intensity: FLOAT| {
+                    push(ScriptCommand::Remote(RemoteCommand::SetLight {
+                        index: light(index)?,
+                        position: None,
+                        color: None,
+                        intensity: Some(intensity as f32),
+                    }));
+                    Ok::<_, Box<EvalAltResult>>(())
+                },
+            );
+        }
+
+        // settings, screenshots and the timeline
+        {
+            let push = push.clone();
+            engine.register_fn("set", move |name: &str, value: FLOAT| {
+                push(ScriptCommand::Remote(RemoteCommand::Set {
+                    name: name.to_string(),
+                    value: value as f32,
+                }));
+            });
+        }
+        {
+            let push = push.clone();
+            engine.register_fn("screenshot", move |path: &str| {
+                push(ScriptCommand::Remote(RemoteCommand::Screenshot {
+                    path: Some(path.to_string()),
+                }));
+            });
+        }
+        engine.register_fn(
+            "screenshot",
+            queue(ScriptCommand::Remote(RemoteCommand::Screenshot {
+                path: None,
+            })),
+        );
+        engine.register_fn("stop", queue(ScriptCommand::Stop));
+
+        engine
+    }
+
+    // whether update should be called every frame
+    pub fn is_animated(&self) -> bool {
+        self.is_animated
+    }
+
+    // calls the script's update function with the seconds since it started
+    pub fn update(&mut self) -> anyhow::Result<()> {
+        let time = self.start.elapsed().as_secs_f64() as FLOAT;
+        self.engine
+            .call_fn::<rhai::Dynamic>(&mut self.scope, &self.ast, UPDATE_FUNCTION, (time,))
+            .map(drop)
+            .map_err(|e| anyhow::anyhow!("{}: {}", self.path, e))
+    }
+
+    // the commands queued since the last call, in the order the script made them
+    pub fn take_commands(&self) -> Vec<ScriptCommand> {
+        std::mem::take(&mut *self.commands.borrow_mut())
+    }
+
+    // records what the script's next load became, so its handle can be resolved
+    pub fn push_model(&mut self, id: Option<ModelId>) {
+        self.models.push(id);
+    }
+
+    pub fn model(&self, handle: usize) -> anyhow::Result<ModelId> {
+        match self.models.get(handle) {
+            Some(Some(id)) => Ok(*id),
+            Some(None) => anyhow::bail!("model {} failed to load", handle),
+            None => anyhow::bail!("there is no model {}", handle),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.is_animated = false;
+    }
+}
@@ -0,0 +1,320 @@
+// reflection of user declared shader parameters
+//
+// a shader opts in by marking a uniform struct with `// @params`, eg:
+//
+//     // @params
+//     struct Params {
+//         shininess: f32,    // range(1.0, 256.0) default(64.0)
+//         tint: vec3f,       // default(1.0, 1.0, 1.0)
+//         use_tint: u32,     // default(0)
+//     }
+//
+// f32 fields become sliders, vec3f/vec4f fields become colors and u32 fields become toggles.
+// every material owns a small uniform buffer holding its values for the fields
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterKind {
+    Float { min: f32, max: f32 },
+    Color { components: usize },
+    Toggle,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShaderParameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    // byte offset of the field inside the uniform struct
+    pub offset: usize,
+    pub default: [f32; 4],
+}
+
+#[derive(Debug)]
+pub enum ParameterReflectError {
+    UnknownType(String, String),
+    BadAnnotation(String, String),
+    TooLarge(usize),
+}
+
+impl std::fmt::Display for ParameterReflectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParameterReflectError::UnknownType(field, ty) => write!(
+                f,
+                "shader parameter {} has unsupported type {} (expected f32, u32, vec3f or vec4f)",
+                field, ty
+            ),
+            ParameterReflectError::BadAnnotation(field, annotation) => {
+                write!(f, "could not parse annotation on {}: {}", field, annotation)
+            }
+            ParameterReflectError::TooLarge(size) => write!(
+                f,
+                "shader parameter block is {} bytes, the limit is {}",
+                size,
+                ParameterLayout::MAX_SIZE
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParameterLayout {
+    pub parameters: Vec<ShaderParameter>,
+    pub size: usize,
+}
+
+impl ParameterLayout {
+    // every material's parameter buffer has this size so it can be created before any shader
+    // has been reflected
+    pub const MAX_SIZE: usize = 256;
+
+    // returns an empty layout if the shader doesn't declare a parameter block
+    pub fn reflect(source: &str) -> Result<Self, ParameterReflectError> {
+        let mut lines = source.lines().skip_while(|l| l.trim() != "// @params");
+
+        if lines.next().is_none() {
+            return Ok(Self::default());
+        }
+
+        let mut layout = Self::default();
+
+        for line in lines
+            .skip_while(|l| !l.trim_start().starts_with("struct"))
+            .skip(1)
+        {
+            let line = line.trim();
+            if line.starts_with('}') {
+                break;
+            }
+
+            let (declaration, annotation) = match line.split_once("//") {
+                Some((declaration, annotation)) => (declaration.trim(), annotation.trim()),
+                None => (line, ""),
+            };
+
+            let Some((name, ty)) = declaration.trim_end_matches(',').split_once(':') else {
+                continue;
+            };
+            let (name, ty) = (name.trim(), ty.trim());
+
+            // uniform layout rules: scalars align to 4 bytes, vec3/vec4 to 16
+            let (kind, size, align) = match ty {
+                "f32" => (ParameterKind::Float { min: 0.0, max: 1.0 }, 4, 4),
+                "u32" => (ParameterKind::Toggle, 4, 4),
+                "vec3f" | "vec3<f32>" => (ParameterKind::Color { components: 3 }, 12, 16),
+                "vec4f" | "vec4<f32>" => (ParameterKind::Color { components: 4 }, 16, 16),
+                _ => {
+                    return Err(ParameterReflectError::UnknownType(
+                        name.to_string(),
+                        ty.to_string(),
+                    ));
+                }
+            };
+
+            let offset = layout.size.next_multiple_of(align);
+            let mut parameter = ShaderParameter {
+                name: name.to_string(),
+                kind,
+                offset,
+                default: [0.0, 0.0, 0.0, 1.0],
+            };
+            parse_annotation(&mut parameter, annotation).ok_or_else(|| {
+                ParameterReflectError::BadAnnotation(name.to_string(), annotation.to_string())
+            })?;
+
+            layout.parameters.push(parameter);
+            layout.size = offset + size;
+        }
+
+        // uniform structs are rounded up to their alignment, which is at most 16 here
+        layout.size = layout.size.next_multiple_of(16);
+        if layout.size > Self::MAX_SIZE {
+            return Err(ParameterReflectError::TooLarge(layout.size));
+        }
+
+        Ok(layout)
+    }
+
+    pub fn default_values(&self) -> Vec<u32> {
+        let mut values = vec![0; Self::MAX_SIZE / 4];
+        for parameter in &self.parameters {
+            let word = parameter.offset / 4;
+            match parameter.kind {
+                ParameterKind::Float { .. } => values[word] = parameter.default[0].to_bits(),
+                ParameterKind::Toggle => values[word] = parameter.default[0] as u32,
+                ParameterKind::Color { components } => {
+                    for c in 0..components {
+                        values[word + c] = parameter.default[c].to_bits();
+                    }
+                }
+            }
+        }
+        values
+    }
+}
+
+// handles `range(min, max)` and `default(a, b, ...)`
+fn parse_annotation(parameter: &mut ShaderParameter, annotation: &str) -> Option<()> {
+    let mut rest = annotation;
+
+    while let Some(open) = rest.find('(') {
+        let close = rest.find(')')?;
+        let keyword = rest[..open].trim();
+        let arguments = rest[open + 1..close]
+            .split(',')
+            .map(|a| a.trim().parse::<f32>().ok())
+            .collect::<Option<Vec<f32>>>()?;
+
+        match keyword {
+            "range" => {
+                let [min, max] = arguments[..] else {
+                    return None;
+                };
+                if let ParameterKind::Float { .. } = parameter.kind {
+                    parameter.kind = ParameterKind::Float { min, max };
+                }
+            }
+            "default" => {
+                for (i, value) in arguments.into_iter().take(4).enumerate() {
+                    parameter.default[i] = value;
+                }
+            }
+            _ => return None,
+        }
+
+        rest = &rest[close + 1..];
+    }
+
+    Some(())
+}
+
+// one material's copy of the parameter values, plus the uniform buffer they live in
+pub struct ParameterBlock {
+    pub buffer: wgpu::Buffer,
+    values: Vec<u32>,
+    dirty: bool,
+}
+
+impl ParameterBlock {
+    pub fn new(device: &wgpu::Device, label: &str) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&(label.to_string() + " parameter buffer")),
+            size: ParameterLayout::MAX_SIZE as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            values: vec![0; ParameterLayout::MAX_SIZE / 4],
+            dirty: true,
+        }
+    }
+
+    pub fn reset(&mut self, layout: &ParameterLayout) {
+        self.values = layout.default_values();
+        self.dirty = true;
+    }
+
+    pub fn get(&self, parameter: &ShaderParameter, component: usize) -> f32 {
+        let word = self.values[parameter.offset / 4 + component];
+        match parameter.kind {
+            ParameterKind::Toggle => word as f32,
+            _ => f32::from_bits(word),
+        }
+    }
+
+    // the raw words of every parameter, eg to tell whether two materials have the same values
+    pub fn values(&self) -> &[u32] {
+        &self.values
+    }
+
+    pub fn set(&mut self, parameter: &ShaderParameter, component: usize, value: f32) {
+        let word = &mut self.values[parameter.offset / 4 + component];
+        *word = match parameter.kind {
+            ParameterKind::Float { min, max } => value.clamp(min, max).to_bits(),
+            ParameterKind::Color { .. } => value.clamp(0.0, 1.0).to_bits(),
+            ParameterKind::Toggle => (value != 0.0) as u32,
+        };
+        self.dirty = true;
+    }
+
+    pub fn upload(&mut self, uploads: &mut crate::uploads::UploadBelt) {
+        if self.dirty {
+            uploads.write(&self.buffer, 0, bytemuck::cast_slice(&self.values));
+            self.dirty = false;
+        }
+    }
+}
+
+// the generated controls: one slider per float, one per color channel and one per toggle.
+// `step` nudges the selected control up or down, toggles flip regardless of direction
+#[derive(Debug, Default)]
+pub struct ParameterEditor {
+    selected: usize,
+}
+
+impl ParameterEditor {
+    const STEPS_PER_RANGE: f32 = 20.0;
+
+    fn controls(layout: &ParameterLayout) -> impl Iterator<Item = (&ShaderParameter, usize)> {
+        layout.parameters.iter().flat_map(|p| {
+            let components = match p.kind {
+                ParameterKind::Color { components } => components,
+                _ => 1,
+            };
+            (0..components).map(move |c| (p, c))
+        })
+    }
+
+    pub fn select_next(&mut self, layout: &ParameterLayout, forward: bool) {
+        let count = Self::controls(layout).count();
+        if count == 0 {
+            return;
+        }
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+
+    pub fn step(&self, layout: &ParameterLayout, block: &mut ParameterBlock, increase: bool) {
+        let Some((parameter, component)) = Self::controls(layout).nth(self.selected) else {
+            return;
+        };
+
+        let current = block.get(parameter, component);
+        let direction = if increase { 1.0 } else { -1.0 };
+        let value = match parameter.kind {
+            ParameterKind::Float { min, max } => {
+                current + direction * (max - min) / Self::STEPS_PER_RANGE
+            }
+            ParameterKind::Color { .. } => current + direction / Self::STEPS_PER_RANGE,
+            ParameterKind::Toggle => 1.0 - current,
+        };
+        block.set(parameter, component, value);
+    }
+
+    // a short description of the selected control for the window title
+    pub fn describe(&self, layout: &ParameterLayout, block: &ParameterBlock) -> String {
+        let Some((parameter, component)) = Self::controls(layout).nth(self.selected) else {
+            return String::new();
+        };
+
+        let value = block.get(parameter, component);
+        match parameter.kind {
+            ParameterKind::Color { .. } => format!(
+                "{}.{} = {:.2}",
+                parameter.name,
+                ["r", "g", "b", "a"][component],
+                value
+            ),
+            ParameterKind::Toggle => format!(
+                "{} = {}",
+                parameter.name,
+                if value != 0.0 { "on" } else { "off" }
+            ),
+            ParameterKind::Float { .. } => format!("{} = {:.3}", parameter.name, value),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// a small preprocessor run over the wgsl before it's compiled, so shaders can share structs and
+// functions instead of copying them, and so one source can be compiled with features turned on
+// and off. the directives each take a whole line:
+//
+//     #include "lights.wgsl"      pastes in one of INCLUDES, once however often it's included
+//     #define NUM_LIGHTS 16       replaces the identifier NUM_LIGHTS in every line after it
+//     #ifdef HAS_NORMAL_MAP       keeps the lines up to the matching #else or #endif only when
+//     #ifndef HAS_NORMAL_MAP      the name is (or isn't) defined, in the source or by the caller
+//     #else
+//     #endif
+//
+// the line numbers in wgpu's errors are the preprocessed source's, so they are off by whatever
+// the includes added above the line
+
+use std::collections::{HashMap, HashSet};
+
+// the files #include can name, in src/shaders/include
+const INCLUDES: &[(&str, &str)] = &[
+    ("camera.wgsl", include_str!("shaders/include/camera.wgsl")),
+    (
+        "denoiser.wgsl",
+        include_str!("shaders/include/denoiser.wgsl"),
+    ),
+    ("fog.wgsl", include_str!("shaders/include/fog.wgsl")),
+    ("lights.wgsl", include_str!("shaders/include/lights.wgsl")),
+    (
+        "model_transformation.wgsl",
+        include_str!("shaders/include/model_transformation.wgsl"),
+    ),
+    (
+        "ray_queries.wgsl",
+        include_str!("shaders/include/ray_queries.wgsl"),
+    ),
+    ("shadows.wgsl", include_str!("shaders/include/shadows.wgsl")),
+    (
+        "voxel_gi.wgsl",
+        include_str!("shaders/include/voxel_gi.wgsl"),
+    ),
+];
+
+#[derive(Debug, thiserror::Error)]
+#[error("{file}:{line}: {message}")]
+pub struct ShaderError {
+    pub file: String,
+    // counted from 1
+    pub line: usize,
+    pub message: String,
+}
+
+// the source with its directives carried out. the defines are (name, value) pairs, eg
+// [("HAS_NORMAL_MAP", ""), ("NUM_LIGHTS", "16")], and the file name is only for errors
+pub fn preprocess(
+    file: &str,
+    source: &str,
+    defines: &[(&str, &str)],
+) -> Result<String, ShaderError> {
+    let mut preprocessor = Preprocessor {
+        defines: defines
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        included: HashSet::new(),
+        output: String::with_capacity(source.len()),
+    };
+    preprocessor.process(file, source)?;
+    Ok(preprocessor.output)
+}
+
+// a descriptor for one of the shaders built into the binary, with nothing defined. errors in
+// those are bugs, like invalid wgsl would be, so they panic the same way wgpu does
+pub fn wgsl(file: &'static str, source: &str) -> wgpu::ShaderModuleDescriptor<'static> {
+    wgsl_with_defines(file, source, &[])
+}
+
+// the same, with names defined for a variant of the shader
+pub fn wgsl_with_defines(
+    file: &'static str,
+    source: &str,
+    defines: &[(&str, &str)],
+) -> wgpu::ShaderModuleDescriptor<'static> {
+    let source = preprocess(file, source, defines).unwrap_or_else(|e| panic!("{}", e));
+    wgpu::ShaderModuleDescriptor {
+        label: Some(file),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    }
+}
+
+struct Preprocessor {
+    defines: HashMap<String, String>,
+    included: HashSet<&'static str>,
+    output: String,
+}
+
+impl Preprocessor {
+    fn process(&mut self, file: &str, source: &str) -> Result<(), ShaderError> {
+        let error = |line: usize, message: &str| ShaderError {
+            file: file.to_string(),
+            line: line + 1,
+            message: message.to_string(),
+        };
+
+        // one for each #ifdef being read, whether its lines are kept
+        let mut conditions: Vec<bool> = Vec::new();
+        let mut last_line = 0;
+        for (line_number, line) in source.lines().enumerate() {
+            last_line = line_number;
+            let is_kept = conditions.iter().all(|&c| c);
+            let Some(directive) = line.trim().strip_prefix('#') else {
+                if is_kept {
+                    self.substitute(line);
+                    self.output.push('\n');
+                }
+                continue;
+            };
+
+            let (keyword, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(k, a)| (k, a.trim()));
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    if argument.is_empty() {
+                        return Err(error(line_number, "expected a name to test"));
+                    }
+                    let is_defined = self.defines.contains_key(argument);
+                    conditions.push(is_defined == (keyword == "ifdef"));
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .ok_or_else(|| error(line_number, "#else without #ifdef"))?;
+                    *condition = !*condition;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error(line_number, "#endif without #ifdef"))?;
+                }
+                // anything else is skipped along with the lines around it
+                _ if !is_kept => {}
+                "define" => {
+                    let (name, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(n, v)| (n, v.trim()));
+                    if name.is_empty() {
+                        return Err(error(line_number, "expected a name to define"));
+                    }
+                    self.defines.insert(name.to_string(), value.to_string());
+                }
+                "include" => {
+                    let name = argument.trim_matches('"');
+                    let &(name, contents) = INCLUDES
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .ok_or_else(|| error(line_number, &format!("no include {}", name)))?;
+                    if self.included.insert(name) {
+                        self.process(name, contents)?;
+                    }
+                }
+                _ => {
+                    return Err(error(
+                        line_number,
+                        &format!("unknown directive #{}", keyword),
+                    ));
+                }
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(error(last_line, "#ifdef without #endif"));
+        }
+        Ok(())
+    }
+
+    // the line with every defined identifier replaced by its value
+    fn substitute(&mut self, line: &str) {
+        if self.defines.is_empty() {
+            self.output.push_str(line);
+            return;
+        }
+
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let (before, word) = rest.split_at(start);
+            let end = word
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(word.len());
+            // a digit before it makes it part of a number, eg the u in 16u
+            let is_in_number = before.ends_with(|c: char| c.is_ascii_digit());
+            self.output.push_str(before);
+            match self.defines.get(&word[..end]) {
+                Some(value) if !is_in_number => self.output.push_str(value),
+                _ => self.output.push_str(&word[..end]),
+            }
+            rest = &word[end..];
+        }
+        self.output.push_str(rest);
+    }
+}
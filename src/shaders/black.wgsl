@@ -1,10 +1,7 @@
 
 // vertex shader
 
-struct Camera {
-    view_pos: vec4f,
-    view_proj: mat4x4f,
-}
+#include "camera.wgsl"
 
 struct Light {
     position: vec3f,
@@ -18,12 +15,7 @@ var<uniform> camera: Camera;
 @group(0) @binding(1)
 var<uniform> light: Light;
 
-struct ModelTransformation {
-    model_transform_col0: vec4f,
-    model_transform_col1: vec4f,
-    model_transform_col2: vec4f,
-    model_transform_col3: vec4f,
-}
+#include "model_transformation.wgsl"
 
 @group(2) @binding(0)
 var<uniform> model_transformation: ModelTransformation;
@@ -32,8 +24,7 @@ struct VertexInput {
     @location(0) position: vec3f,
     @location(1) tex_coords: vec2f,
     @location(2) normal: vec3f,
-    @location(3) tangent: vec3f,
-    @location(4) bitangent: vec3f,
+    @location(3) tangent: vec4f,
 }
 
 struct VertexOutput {
@@ -51,8 +42,11 @@ fn vertex_main(vertex: VertexInput) -> VertexOutput {
         model_transformation.model_transform_col3
     );
 
-    // TODO this only works if the model transformation is orthogonal ie no stretching/skewing
-    let normal_transformation_matrix = mat3x3f(model_transformation_matrix[0].xyz, model_transformation_matrix[1].xyz, model_transformation_matrix[2].xyz);
+    let normal_transformation_matrix = mat3x3f(
+        model_transformation.normal_transform_col0.xyz,
+        model_transformation.normal_transform_col1.xyz,
+        model_transformation.normal_transform_col2.xyz
+    );
 
     let world_position_h = model_transformation_matrix * vec4f(vertex.position, 1.0);
 
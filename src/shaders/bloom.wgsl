@@ -0,0 +1,104 @@
+// bloom over the finished frame, see bloom.rs. the bright parts are pulled out into a chain of
+// successively halved textures, blurred on the way down and added back up, then the result is
+// added over the frame
+
+struct Bloom {
+    // brightness where bloom starts, and how softly it fades in below that
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Bloom;
+@group(0) @binding(1)
+var input: texture_2d<f32>;
+@group(0) @binding(2)
+var input_sampler: sampler;
+// only bound for the composite, where input is the frame
+@group(0) @binding(3)
+var bloom: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle twice the size of the screen, so the screen is exactly the part where uv is 0..1
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+fn sample_input(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input, input_sampler, uv, 0.0).rgb;
+}
+
+// the 13 tap box filter from call of duty's bloom: five overlapping 4x4 boxes, which keeps
+// bright single pixels from flickering as the camera moves
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    let a = sample_input(uv + texel * vec2<f32>(-2.0, -2.0));
+    let b = sample_input(uv + texel * vec2<f32>(0.0, -2.0));
+    let c = sample_input(uv + texel * vec2<f32>(2.0, -2.0));
+    let d = sample_input(uv + texel * vec2<f32>(-2.0, 0.0));
+    let e = sample_input(uv);
+    let f = sample_input(uv + texel * vec2<f32>(2.0, 0.0));
+    let g = sample_input(uv + texel * vec2<f32>(-2.0, 2.0));
+    let h = sample_input(uv + texel * vec2<f32>(0.0, 2.0));
+    let i = sample_input(uv + texel * vec2<f32>(2.0, 2.0));
+    let j = sample_input(uv + texel * vec2<f32>(-1.0, -1.0));
+    let k = sample_input(uv + texel * vec2<f32>(1.0, -1.0));
+    let l = sample_input(uv + texel * vec2<f32>(-1.0, 1.0));
+    let m = sample_input(uv + texel * vec2<f32>(1.0, 1.0));
+
+    return e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+// keeps what's brighter than the threshold, with a quadratic knee below it so bloom fades in
+// rather than switching on
+fn bright_part(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = max(settings.knee, 1e-4);
+    var soft = clamp(brightness - settings.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - settings.threshold) / max(brightness, 1e-4);
+    return color * contribution;
+}
+
+@fragment
+fn prefilter_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(bright_part(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn downsample_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// a 3x3 tent, added onto the level above by the pipeline's blending
+@fragment
+fn upsample_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    var color = sample_input(in.uv) * 4.0;
+    color += (sample_input(in.uv + vec2<f32>(-texel.x, 0.0))
+        + sample_input(in.uv + vec2<f32>(texel.x, 0.0))
+        + sample_input(in.uv + vec2<f32>(0.0, -texel.y))
+        + sample_input(in.uv + vec2<f32>(0.0, texel.y))) * 2.0;
+    color += sample_input(in.uv + vec2<f32>(-texel.x, -texel.y))
+        + sample_input(in.uv + vec2<f32>(texel.x, -texel.y))
+        + sample_input(in.uv + vec2<f32>(-texel.x, texel.y))
+        + sample_input(in.uv + vec2<f32>(texel.x, texel.y));
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+@fragment
+fn composite_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let frame = textureSampleLevel(input, input_sampler, in.uv, 0.0);
+    let glow = textureSampleLevel(bloom, input_sampler, in.uv, 0.0).rgb;
+    return vec4<f32>(frame.rgb + glow * settings.intensity, frame.a);
+}
@@ -0,0 +1,38 @@
+// one frame's debug lines, see debug_draw.rs
+
+#include "camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) color: vec3f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) color: vec3f,
+}
+
+@vertex
+fn vertex_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4f(vertex.position, 1.0);
+    out.color = vertex.color;
+    return out;
+}
+
+// overlay lines are already in clip space
+@vertex
+fn overlay_vertex_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4f(vertex.position.xy, 0.5, 1.0);
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    return vec4f(in.color, 1.0);
+}
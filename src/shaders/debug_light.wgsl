@@ -1,10 +1,7 @@
 
 // vertex shader
 
-struct Camera {
-    view_pos: vec4f,
-    view_proj: mat4x4f,
-}
+#include "camera.wgsl"
 
 struct Light {
     position: vec3f,
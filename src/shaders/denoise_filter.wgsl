@@ -0,0 +1,145 @@
+// the denoiser's filters, see denoise.rs. the temporal pass blends each pixel's noisy ambient
+// occlusion into its history, found where the motion vectors say the surface was last frame,
+// and the a-trous passes (dammertz et al. 2010) blur what's left of the noise with a 5x5
+// kernel spread out further each pass, stopping at edges in depth and normal
+
+#include "denoiser.wgsl"
+
+@group(0) @binding(0)
+var<uniform> denoiser: Denoiser;
+@group(0) @binding(1)
+var positions: texture_2d<f32>;
+@group(0) @binding(2)
+var normals: texture_2d<f32>;
+// the temporal pass's input is the prepass's noisy ao and its motion vectors, the a-trous
+// passes' the last pass's output. the rest are only bound for the temporal pass
+@group(0) @binding(3)
+var input: texture_2d<f32>;
+@group(0) @binding(4)
+var output: texture_storage_2d<rg32float, write>;
+@group(0) @binding(5)
+var motion: texture_2d<f32>;
+@group(0) @binding(6)
+var previous_positions: texture_2d<f32>;
+@group(0) @binding(7)
+var previous_history: texture_2d<f32>;
+
+// how many frames of history a pixel's ao averages at most. more is smoother but slower to
+// catch up when something moves
+const MAX_HISTORY: f32 = 32.0;
+// how far the reprojected surface can be from where it was, relative to its distance from
+// the camera, before the history there belongs to something else
+const DISOCCLUSION_DISTANCE: f32 = 0.02;
+// how sharply the a-trous weights fall off across normals and off the pixel's plane
+const NORMAL_POWER: f32 = 32.0;
+const PLANE_DISTANCE: f32 = 0.01;
+
+fn is_outside(texel: vec2i, size: vec2i) -> bool {
+    return any(texel < vec2i(0)) || any(texel >= size);
+}
+
+// the history keeps the ao in x and how many frames it averages in y
+@compute @workgroup_size(8, 8)
+fn temporal_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = vec2i(textureDimensions(positions));
+    let texel = vec2i(id.xy);
+    if is_outside(texel, size) {
+        return;
+    }
+    let position = textureLoad(positions, texel, 0);
+    let noisy = textureLoad(input, texel, 0).x;
+    if position.w <= 0.0 {
+        textureStore(output, texel, vec4f(1.0, 0.0, 0.0, 0.0));
+        return;
+    }
+
+    // the 2x2 texels of last frame's history around where the surface was, leaving out the
+    // ones which saw something else
+    var history = vec2f(0.0);
+    if denoiser.is_history_valid != 0u {
+        let uv = (vec2f(texel) + 0.5) / vec2f(size);
+        let previous = (uv + textureLoad(motion, texel, 0).xy) * vec2f(size) - 0.5;
+        let corner = vec2i(floor(previous));
+        let blend = fract(previous);
+        var weight_sum = 0.0;
+        for (var i = 0u; i < 4u; i++) {
+            let offset = vec2i(i32(i & 1u), i32(i >> 1u));
+            let tap = corner + offset;
+            if is_outside(tap, size) {
+                continue;
+            }
+            let previous_position = textureLoad(previous_positions, tap, 0).xyz;
+            if distance(previous_position, position.xyz) > DISOCCLUSION_DISTANCE * position.w {
+                continue;
+            }
+            let weights = select(1.0 - blend, blend, offset == vec2i(1));
+            let weight = weights.x * weights.y;
+            history += textureLoad(previous_history, tap, 0).xy * weight;
+            weight_sum += weight;
+        }
+        history = select(vec2f(0.0), history / weight_sum, weight_sum > 0.001);
+    }
+
+    let frames = min(history.y + 1.0, MAX_HISTORY);
+    textureStore(output, texel, vec4f(mix(history.x, noisy, 1.0 / frames), frames, 0.0, 0.0));
+}
+
+// the b3 spline's weights, 1/16 1/4 3/8 1/4 1/16
+fn kernel(offset: i32) -> f32 {
+    return select(select(0.0625, 0.25, abs(offset) == 1), 0.375, offset == 0);
+}
+
+// the output keeps the ao in x and the pixel's distance from the camera in y, which the scene
+// pass checks its surfaces against
+fn a_trous(id: vec2u, step: i32) {
+    let size = vec2i(textureDimensions(positions));
+    let texel = vec2i(id);
+    if is_outside(texel, size) {
+        return;
+    }
+    let position = textureLoad(positions, texel, 0);
+    if position.w <= 0.0 {
+        textureStore(output, texel, vec4f(1.0, 0.0, 0.0, 0.0));
+        return;
+    }
+    let normal = textureLoad(normals, texel, 0).xyz;
+
+    var sum = 0.0;
+    var weight_sum = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let tap = texel + vec2i(x, y) * step;
+            if is_outside(tap, size) {
+                continue;
+            }
+            let tap_position = textureLoad(positions, tap, 0);
+            if tap_position.w <= 0.0 {
+                continue;
+            }
+            let tap_normal = textureLoad(normals, tap, 0).xyz;
+            let normal_weight = pow(max(dot(normal, tap_normal), 0.0), NORMAL_POWER);
+            let plane_distance = abs(dot(normal, tap_position.xyz - position.xyz));
+            let plane_weight = exp(-plane_distance / (PLANE_DISTANCE * position.w));
+            let weight = kernel(x) * kernel(y) * normal_weight * plane_weight;
+            sum += textureLoad(input, tap, 0).x * weight;
+            weight_sum += weight;
+        }
+    }
+    // the center always counts fully, so there's something to divide by
+    textureStore(output, texel, vec4f(sum / weight_sum, position.w, 0.0, 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn a_trous_1(@builtin(global_invocation_id) id: vec3u) {
+    a_trous(id.xy, 1);
+}
+
+@compute @workgroup_size(8, 8)
+fn a_trous_2(@builtin(global_invocation_id) id: vec3u) {
+    a_trous(id.xy, 2);
+}
+
+@compute @workgroup_size(8, 8)
+fn a_trous_4(@builtin(global_invocation_id) id: vec3u) {
+    a_trous(id.xy, 4);
+}
@@ -0,0 +1,79 @@
+enable wgpu_ray_query;
+
+// the denoiser's prepass, see denoise.rs. draws the opaque meshes into the targets the filters
+// read: where each pixel's surface is and which way it faces, where it was on screen the last
+// time the denoiser ran, and its noisy ray traced ambient occlusion
+
+#include "camera.wgsl"
+#include "model_transformation.wgsl"
+#include "ray_queries.wgsl"
+#include "denoiser.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> model_transformation: ModelTransformation;
+
+@group(2) @binding(0)
+var<uniform> denoiser: Denoiser;
+
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(2) normal: vec3f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) world_position: vec3f,
+    @location(1) world_normal: vec3f,
+}
+
+struct FragmentOutput {
+    // xyz, and the distance from the camera in w. the clear's 0 there marks the background
+    @location(0) position: vec4f,
+    @location(1) normal: vec4f,
+    // how far the pixel's surface has moved on screen since the last frame, in uv
+    @location(2) motion: vec2f,
+    @location(3) ambient_occlusion: f32,
+}
+
+@vertex
+fn vertex_main(vertex: VertexInput) -> VertexOutput {
+    let model_transformation_matrix = mat4x4(
+        model_transformation.model_transform_col0,
+        model_transformation.model_transform_col1,
+        model_transformation.model_transform_col2,
+        model_transformation.model_transform_col3
+    );
+    let normal_matrix = mat3x3(
+        model_transformation.normal_transform_col0.xyz,
+        model_transformation.normal_transform_col1.xyz,
+        model_transformation.normal_transform_col2.xyz
+    );
+
+    var out: VertexOutput;
+    out.world_position = (model_transformation_matrix * vec4f(vertex.position, 1.0)).xyz;
+    out.world_normal = normal_matrix * vertex.normal;
+    out.clip_position = camera.view_proj * vec4f(out.world_position, 1.0);
+    return out;
+}
+
+fn screen_uv(view_proj: mat4x4f, position: vec3f) -> vec2f {
+    let clip = view_proj * vec4f(position, 1.0);
+    return clip.xy / clip.w * vec2f(0.5, -0.5) + 0.5;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> FragmentOutput {
+    let normal = normalize(in.world_normal);
+
+    var out: FragmentOutput;
+    out.position = vec4f(in.world_position, distance(in.world_position, camera.view_pos.xyz));
+    out.normal = vec4f(normal, 0.0);
+    // only the camera's movement, so moving models lean on the filters' disocclusion test
+    out.motion = screen_uv(denoiser.previous_view_proj, in.world_position)
+        - screen_uv(camera.view_proj, in.world_position);
+    out.ambient_occlusion = traced_ambient_occlusion(in.world_position, normal, denoiser.frame_index);
+    return out;
+}
@@ -0,0 +1,87 @@
+// rain droplets running down the finished frame, see weather.rs. the screen is split into cells,
+// a few sizes of them over each other, and each cell has a droplet which appears somewhere at
+// random, slides down its cell and fades. inside a droplet the frame is bent outwards from its
+// center, like a little lens, and lightly blurred
+
+struct Droplets {
+    time: f32,
+    strength: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> droplets: Droplets;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+@group(0) @binding(2)
+var source_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle twice the size of the screen, so the screen is exactly the part where uv is 0..1
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// two values from 0 to 1 for a cell
+fn hash2(cell: vec2<f32>) -> vec2<f32> {
+    let p = vec2<f32>(dot(cell, vec2<f32>(127.1, 311.7)), dot(cell, vec2<f32>(269.5, 183.3)));
+    return fract(sin(p) * 43758.5453);
+}
+
+// the droplet over a point of one layer of cells, as how far to bend the frame there and how
+// much of a droplet it is
+fn droplet(uv: vec2<f32>, cells: vec2<f32>, layer: f32) -> vec3<f32> {
+    let position = uv * cells;
+    let cell = floor(position);
+    let local = fract(position) - 0.5;
+    let random = hash2(cell + layer * 17.0);
+
+    // each cell's droplet comes round at its own pace, starting at a random point of its life
+    let life = fract(droplets.time * (0.1 + 0.15 * random.x) + random.y);
+    // sliding down the cell, faster as it gets heavier
+    let center = vec2<f32>((random.x - 0.5) * 0.5, -0.3 + life * life * 0.6);
+    let radius = 0.12 + 0.12 * random.y;
+    let to_point = (local - center) / radius;
+    let distance = length(to_point);
+    // fading in quickly and out slowly
+    let presence = smoothstep(0.0, 0.1, life) * (1.0 - life);
+    let coverage = (1.0 - smoothstep(0.8, 1.0, distance)) * presence;
+    return vec3<f32>(to_point * coverage / cells, coverage);
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(source));
+    // square cells however wide the screen is
+    let aspect = vec2<f32>(size.x / size.y, 1.0);
+
+    var bend = vec2<f32>(0.0);
+    var coverage = 0.0;
+    for (var layer = 0; layer < 3; layer++) {
+        let scale = 6.0 + 5.0 * f32(layer);
+        let d = droplet(in.uv, scale * aspect, f32(layer));
+        bend += d.xy;
+        coverage = max(coverage, d.z);
+    }
+    bend *= droplets.strength;
+    coverage *= droplets.strength;
+
+    let frame = textureSampleLevel(source, source_sampler, in.uv, 0.0);
+    // the view through the droplet, upside down and blurred as through a lens
+    let through = in.uv - bend * 0.5;
+    let texel = 1.0 / size;
+    var lensed = vec3<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        let offset = vec2<f32>(f32(i & 1) - 0.5, f32(i >> 1u) - 0.5) * texel * 2.0;
+        lensed += textureSampleLevel(source, source_sampler, through + offset, 0.0).rgb;
+    }
+    lensed *= 0.25;
+    return vec4<f32>(mix(frame.rgb, lensed, coverage), frame.a);
+}
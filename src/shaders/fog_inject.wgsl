@@ -0,0 +1,170 @@
+// fills every froxel of the fog with its density and the light it scatters towards the camera,
+// for fog_integrate.wgsl to add up. see fog.rs
+
+#include "lights.wgsl"
+#include "shadows.wgsl"
+#include "fog.wgsl"
+
+@group(0) @binding(1)
+var<storage, read> lights: array<Light>;
+@group(0) @binding(2)
+var<uniform> light_metadata: LightMetadata;
+@group(0) @binding(5)
+var<uniform> shadows: Shadows;
+@group(0) @binding(6)
+var shadow_maps: texture_depth_2d_array;
+@group(0) @binding(7)
+var shadow_sampler: sampler_comparison;
+@group(0) @binding(8)
+var point_shadow_maps: texture_depth_2d_array;
+
+@group(1) @binding(0)
+var<uniform> fog: Fog;
+@group(1) @binding(1)
+var scattering_volume: texture_storage_3d<rgba16float, write>;
+
+const PI: f32 = 3.14159265;
+
+// value noise from 0 to 1, smooth between the corners of a unit grid
+fn hash3(cell: vec3f) -> f32 {
+    return fract(sin(dot(cell, vec3f(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+fn value_noise(position: vec3f) -> f32 {
+    let cell = floor(position);
+    let f = fract(position);
+    let t = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(hash3(cell), hash3(cell + vec3f(1.0, 0.0, 0.0)), t.x);
+    let x10 = mix(hash3(cell + vec3f(0.0, 1.0, 0.0)), hash3(cell + vec3f(1.0, 1.0, 0.0)), t.x);
+    let x01 = mix(hash3(cell + vec3f(0.0, 0.0, 1.0)), hash3(cell + vec3f(1.0, 0.0, 1.0)), t.x);
+    let x11 = mix(hash3(cell + vec3f(0.0, 1.0, 1.0)), hash3(cell + vec3f(1.0)), t.x);
+    return mix(mix(x00, x10, t.y), mix(x01, x11, t.y), t.z);
+}
+
+// the fraction of light lost per world unit at a point: thickest below the fog's height, thinning
+// exponentially above it, and broken into patches drifting on the wind
+fn fog_density(position: vec3f) -> f32 {
+    let height = exp(-max(position.y - fog.height, 0.0) * fog.height_falloff);
+    let drifted = (position - fog.wind * fog.time) / fog.noise_scale;
+    let patches = value_noise(drifted) * 0.65 + value_noise(drifted * 2.7) * 0.35;
+    return fog.density * height * mix(1.0, patches * 2.0, fog.noise);
+}
+
+// henyey greenstein, for the angle between the way the light travels and the way it's scattered
+fn phase(cos_theta: f32) -> f32 {
+    let g = fog.anisotropy;
+    let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+    return (1.0 - g * g) / (4.0 * PI * denominator * sqrt(denominator));
+}
+
+// the light from all around scattered along `view`. convolving with the phase function scales
+// each band l of the spherical harmonics by g^l, so only the three bands are needed
+fn sky_light(view: vec3f) -> vec3f {
+    let g = fog.anisotropy;
+    // the light arriving from d travels along -d, so it's scattered towards the camera from -view
+    let d = -view;
+    let bands = vec3f(1.0, g, g * g);
+    var light = fog.sky[0].rgb * 0.282095 * bands.x;
+    light += (fog.sky[1].rgb * d.y + fog.sky[2].rgb * d.z + fog.sky[3].rgb * d.x) * 0.488603 * bands.y;
+    light += (fog.sky[4].rgb * 1.092548 * d.x * d.y
+        + fog.sky[5].rgb * 1.092548 * d.y * d.z
+        + fog.sky[6].rgb * 0.315392 * (3.0 * d.z * d.z - 1.0)
+        + fog.sky[7].rgb * 1.092548 * d.x * d.z
+        + fog.sky[8].rgb * 0.546274 * (d.x * d.x - d.y * d.y)) * bands.z;
+    return max(light, vec3f(0.0));
+}
+
+// how much of the first directional light reaches a point, from the first cascade covering it.
+// there's no surface to filter across or push away from, so a single comparison does
+fn sun_shadow(position: vec3f) -> f32 {
+    if shadows.is_enabled == 0 {
+        return 1.0;
+    }
+    for (var i = 0u; i < shadows.cascade_count; i++) {
+        let clip = shadows.cascades[i] * vec4f(position, 1.0);
+        if all(abs(clip.xy) < vec2f(1.0)) && clip.z > 0.0 && clip.z < 1.0 {
+            let uv = clip.xy * vec2f(0.5, -0.5) + 0.5;
+            return textureSampleCompareLevel(shadow_maps, shadow_sampler, uv, i, clip.z);
+        }
+    }
+    return 1.0;
+}
+
+// how much of the shadow casting point light reaches a point, as in shader.wgsl's point_shadow
+fn point_shadow(position: vec3f) -> f32 {
+    let range = shadows.point_light.w;
+    let direction = position - shadows.point_light.xyz;
+    if shadows.point_is_enabled == 0 || length(direction) >= range {
+        return 1.0;
+    }
+
+    let magnitude = abs(direction);
+    var face = 0u;
+    if magnitude.x >= magnitude.y && magnitude.x >= magnitude.z {
+        face = select(0u, 1u, direction.x < 0.0);
+    } else if magnitude.y >= magnitude.z {
+        face = select(2u, 3u, direction.y < 0.0);
+    } else {
+        face = select(4u, 5u, direction.z < 0.0);
+    }
+
+    let clip = shadows.point_faces[face] * vec4f(position, 1.0);
+    let uv = clip.xy / clip.w * vec2f(0.5, -0.5) + 0.5;
+    let reference = length(direction) / range - shadows.point_bias;
+    return textureSampleCompareLevel(point_shadow_maps, shadow_sampler, uv, face, reference);
+}
+
+// how much of a light at `light_position` a point scatters along `view`, from the point towards
+// the camera, fading with the distance from the light
+fn local_light(light_position: vec3f, position: vec3f, view: vec3f) -> f32 {
+    let to_point = position - light_position;
+    let length_squared = max(dot(to_point, to_point), 1e-8);
+    return phase(dot(to_point * inverseSqrt(length_squared), view)) / (1.0 + length_squared);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(scattering_volume);
+    if any(id >= size) {
+        return;
+    }
+
+    let view_to_point = froxel_direction(fog, (vec2f(id.xy) + 0.5) / vec2f(size.xy));
+    let froxel_distance = slice_distance(fog, (f32(id.z) + 0.5) / f32(size.z));
+    let position = fog.camera_position + view_to_point * froxel_distance;
+    let view = -view_to_point;
+
+    let density = fog_density(position);
+    if density <= 0.0 {
+        textureStore(scattering_volume, id, vec4f(0.0));
+        return;
+    }
+
+    var in_scattered = sky_light(view) * fog.ambient;
+
+    // the cascades are the first directional light's
+    for (var i = 0u; i < light_metadata.directional_light_count; i++) {
+        let light = lights[light_metadata.directional_light_offset + i];
+        let visibility = select(1.0, sun_shadow(position), i == 0u);
+        in_scattered += light.color * phase(dot(normalize(light.direction), view)) * visibility;
+    }
+
+    // and the cube of shadow maps is whichever point light is at its position
+    for (var i = 0u; i < light_metadata.point_light_count; i++) {
+        let light = lights[light_metadata.point_light_offset + i];
+        let casts_shadow = distance(light.position, shadows.point_light.xyz) < 1e-3;
+        let visibility = select(1.0, point_shadow(position), casts_shadow);
+        in_scattered += light.color * local_light(light.position, position, view) * visibility;
+    }
+
+    for (var i = 0u; i < light_metadata.spot_light_count; i++) {
+        let light = lights[light_metadata.spot_light_offset + i];
+        let to_point = normalize(position - light.position);
+        // params.x and params.y are the cosines of the inner and outer cone angles
+        let cone = smoothstep(light.params.y, light.params.x, dot(to_point, normalize(light.direction)));
+        in_scattered += light.color * local_light(light.position, position, view) * cone;
+    }
+
+    // the fog scatters its color's share of the light it takes out, and absorbs the rest
+    textureStore(scattering_volume, id, vec4f(in_scattered * fog.color * density, density));
+}
@@ -0,0 +1,39 @@
+// marches each column of froxels away from the camera, storing in each the light scattered
+// towards the camera up to its far side and the transmittance, the fraction of whatever's behind
+// it that still gets through. see fog.rs
+
+#include "fog.wgsl"
+
+@group(0) @binding(0)
+var<uniform> fog: Fog;
+@group(0) @binding(1)
+var scattering_volume: texture_3d<f32>;
+@group(0) @binding(2)
+var integrated_volume: texture_storage_3d<rgba16float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(integrated_volume);
+    if any(id.xy >= size.xy) {
+        return;
+    }
+
+    var scattered = vec3f(0.0);
+    var transmittance = 1.0;
+    var slice_start = fog.near;
+    for (var z = 0u; z < size.z; z++) {
+        let texel = vec3u(id.xy, z);
+        let froxel = textureLoad(scattering_volume, texel, 0);
+        let slice_end = slice_distance(fog, f32(z + 1u) / f32(size.z));
+        let extinction = max(froxel.a, 1e-6);
+        let slice_transmittance = exp(-extinction * (slice_end - slice_start));
+
+        // the scattered light integrated across the slice, dimmed by the fog in front of it as
+        // it goes, rather than taken at one point (hillaire, "physically based and unified
+        // volumetric rendering in frostbite")
+        scattered += transmittance * (froxel.rgb - froxel.rgb * slice_transmittance) / extinction;
+        transmittance *= slice_transmittance;
+        textureStore(integrated_volume, texel, vec4f(scattered, transmittance));
+        slice_start = slice_end;
+    }
+}
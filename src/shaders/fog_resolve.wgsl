@@ -0,0 +1,50 @@
+// adds the fog over the frame: each pixel looks up the light scattered in front of whatever's
+// drawn there, and how much of it gets through, from its distance. see fog.rs
+
+#include "fog.wgsl"
+
+@group(0) @binding(0)
+var<uniform> fog: Fog;
+@group(0) @binding(1)
+var integrated_volume: texture_3d<f32>;
+@group(0) @binding(2)
+var volume_sampler: sampler;
+@group(0) @binding(3)
+var depth_texture: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle twice the size of the screen, so the screen is exactly the part where uv is 0..1
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(depth_texture, vec2i(in.clip_position.xy), 0);
+    let ndc = vec4f(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0, depth, 1.0);
+    let world = fog.inverse_view_proj * ndc;
+
+    // the sky is infinitely far away with a reversed depth buffer, or at the far plane without,
+    // and fogged as much as the furthest froxels either way
+    var surface_distance = fog.far;
+    if abs(world.w) > 1e-6 {
+        surface_distance = min(distance(world.xyz / world.w, fog.camera_position), fog.far);
+    }
+
+    // each froxel holds the fog up to its far side, so the texel centers are half a slice on
+    let slices = f32(textureDimensions(integrated_volume).z);
+    let w = distance_fraction(fog, surface_distance) - 0.5 / slices;
+    let integrated = textureSampleLevel(integrated_volume, volume_sampler, vec3f(in.uv, w), 0.0);
+    // across the first slice the fog thins out to none at the near distance, rather than
+    // filling everything nearer with the whole slice's
+    let nearness = saturate(distance_fraction(fog, surface_distance) * slices);
+    return vec4f(integrated.rgb * nearness, mix(1.0, integrated.a, nearness));
+}
@@ -0,0 +1,156 @@
+// fast approximate anti-aliasing over the finished frame, see antialiasing.rs. each pixel with
+// enough local contrast finds the direction of the edge through it, walks along the edge to
+// both of its ends, and blends across it by how close it is to the nearer end
+
+struct Fxaa {
+    // the smallest contrast treated as an edge, relative to the brightest neighbour
+    relative_threshold: f32,
+    // and absolutely, so dark noise is left alone
+    absolute_threshold: f32,
+    // how much of the blend the 3x3 neighbourhood's average pulls in
+    subpixel_blend: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Fxaa;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+@group(0) @binding(2)
+var source_sampler: sampler;
+
+const SEARCH_STEPS: i32 = 10;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle twice the size of the screen, so the screen is exactly the part where uv is 0..1
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// perceived brightness. the source is sampled as linear color, and the square root is close
+// enough to the gamma curve for judging contrast
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(source, source_sampler, uv, 0.0).rgb);
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    let center = textureSampleLevel(source, source_sampler, in.uv, 0.0);
+
+    let m = luma(center.rgb);
+    let n = luma_at(in.uv + vec2<f32>(0.0, -texel.y));
+    let s = luma_at(in.uv + vec2<f32>(0.0, texel.y));
+    let e = luma_at(in.uv + vec2<f32>(texel.x, 0.0));
+    let w = luma_at(in.uv + vec2<f32>(-texel.x, 0.0));
+
+    let highest = max(max(max(n, s), max(e, w)), m);
+    let lowest = min(min(min(n, s), min(e, w)), m);
+    let contrast = highest - lowest;
+    if contrast < max(settings.absolute_threshold, settings.relative_threshold * highest) {
+        return center;
+    }
+
+    let ne = luma_at(in.uv + vec2<f32>(texel.x, -texel.y));
+    let nw = luma_at(in.uv + vec2<f32>(-texel.x, -texel.y));
+    let se = luma_at(in.uv + vec2<f32>(texel.x, texel.y));
+    let sw = luma_at(in.uv + vec2<f32>(-texel.x, texel.y));
+
+    // how far the pixel stands out from its neighbourhood, for blending single pixel details
+    let average = (2.0 * (n + s + e + w) + ne + nw + se + sw) / 12.0;
+    let subpixel = smoothstep(0.0, 1.0, saturate(abs(average - m) / contrast));
+    let subpixel_blend = subpixel * subpixel * settings.subpixel_blend;
+
+    // a horizontal edge changes most vertically
+    let horizontal = 2.0 * abs(n + s - 2.0 * m) + abs(ne + se - 2.0 * e) + abs(nw + sw - 2.0 * w);
+    let vertical = 2.0 * abs(e + w - 2.0 * m) + abs(ne + nw - 2.0 * n) + abs(se + sw - 2.0 * s);
+    let is_horizontal = horizontal >= vertical;
+
+    // step across the edge towards whichever side differs more
+    let positive = select(e, s, is_horizontal);
+    let negative = select(w, n, is_horizontal);
+    let positive_gradient = abs(positive - m);
+    let negative_gradient = abs(negative - m);
+    var step_across = select(texel.x, texel.y, is_horizontal);
+    var opposite = positive;
+    var gradient = positive_gradient;
+    if negative_gradient > positive_gradient {
+        step_across = -step_across;
+        opposite = negative;
+        gradient = negative_gradient;
+    }
+
+    // walk along the middle of the edge in both directions until the luma leaves it
+    let along = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var edge_uv = in.uv;
+    if is_horizontal {
+        edge_uv.y += step_across * 0.5;
+    } else {
+        edge_uv.x += step_across * 0.5;
+    }
+    // further steps get longer, since long edges are smooth anyway
+    var step_sizes = array<f32, 10>(1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 4.0, 8.0);
+    let edge_luma = (m + opposite) * 0.5;
+    let gradient_threshold = gradient * 0.25;
+
+    var positive_uv = edge_uv + along;
+    var positive_delta = luma_at(positive_uv) - edge_luma;
+    var positive_end = abs(positive_delta) >= gradient_threshold;
+    var negative_uv = edge_uv - along;
+    var negative_delta = luma_at(negative_uv) - edge_luma;
+    var negative_end = abs(negative_delta) >= gradient_threshold;
+    for (var i = 1; i < SEARCH_STEPS && !(positive_end && negative_end); i++) {
+        if !positive_end {
+            positive_uv += along * step_sizes[i];
+            positive_delta = luma_at(positive_uv) - edge_luma;
+            positive_end = abs(positive_delta) >= gradient_threshold;
+        }
+        if !negative_end {
+            negative_uv -= along * step_sizes[i];
+            negative_delta = luma_at(negative_uv) - edge_luma;
+            negative_end = abs(negative_delta) >= gradient_threshold;
+        }
+    }
+
+    var positive_distance: f32;
+    var negative_distance: f32;
+    if is_horizontal {
+        positive_distance = positive_uv.x - in.uv.x;
+        negative_distance = in.uv.x - negative_uv.x;
+    } else {
+        positive_distance = positive_uv.y - in.uv.y;
+        negative_distance = in.uv.y - negative_uv.y;
+    }
+
+    // only blend when the nearer end bends the edge towards this pixel's side
+    var distance_to_end = positive_distance;
+    var end_delta = positive_delta;
+    if negative_distance < positive_distance {
+        distance_to_end = negative_distance;
+        end_delta = negative_delta;
+    }
+    var edge_blend = 0.0;
+    if (end_delta >= 0.0) != (m - edge_luma >= 0.0) {
+        edge_blend = 0.5 - distance_to_end / (positive_distance + negative_distance);
+    }
+
+    let blend = max(edge_blend, subpixel_blend);
+    var blend_uv = in.uv;
+    if is_horizontal {
+        blend_uv.y += step_across * blend;
+    } else {
+        blend_uv.x += step_across * blend;
+    }
+    return textureSampleLevel(source, source_sampler, blend_uv, 0.0);
+}
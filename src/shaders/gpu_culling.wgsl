@@ -0,0 +1,94 @@
+// tests every instance's bounding sphere against the camera's frustum, and appends the ones
+// inside it to their draw's indirect arguments. see gpu_culling.rs
+
+#include "camera.wgsl"
+#include "model_transformation.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// one mesh of one object, see gpu_culling::CullInstance
+struct Instance {
+    // the mesh's bounding sphere, in model space
+    center: vec3f,
+    radius: f32,
+    object: u32,
+    index_count: u32,
+    draw: u32,
+    first_argument: u32,
+}
+
+// laid out like wgpu::util::DrawIndexedIndirectArgs
+struct DrawArguments {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(1) @binding(0)
+var<storage, read> object_transforms: array<ObjectTransform>;
+@group(1) @binding(1)
+var<storage, read> instances: array<Instance>;
+@group(1) @binding(2)
+var<storage, read_write> arguments: array<DrawArguments>;
+// how many of each draw's arguments have been filled in, cleared before the pass
+@group(1) @binding(3)
+var<storage, read_write> counts: array<atomic<u32>>;
+
+// whether any of the sphere is on the side of the plane its normal points to
+fn is_inside(plane: vec4f, center: vec3f, radius: f32) -> bool {
+    let normal_length = length(plane.xyz);
+    // the far plane of an infinite projection is at infinity, so it culls nothing
+    if normal_length < 1e-6 {
+        return true;
+    }
+    return dot(plane.xyz, center) + plane.w >= -radius * normal_length;
+}
+
+@compute @workgroup_size(64)
+fn cull_main(@builtin(global_invocation_id) id: vec3u) {
+    if id.x >= arrayLength(&instances) {
+        return;
+    }
+    let instance = instances[id.x];
+
+    let transform = object_transforms[instance.object].transformation;
+    let model_transformation_matrix = mat4x4(
+        transform.model_transform_col0,
+        transform.model_transform_col1,
+        transform.model_transform_col2,
+        transform.model_transform_col3
+    );
+    let center = (model_transformation_matrix * vec4f(instance.center, 1.0)).xyz;
+    // grown by the most any axis is stretched, so it still holds the whole mesh
+    let scale = max(
+        length(transform.model_transform_col0.xyz),
+        max(length(transform.model_transform_col1.xyz), length(transform.model_transform_col2.xyz))
+    );
+    let radius = instance.radius * scale;
+
+    // the frustum's planes in world space, from sums of the view projection's rows. left,
+    // right, bottom, top, then z >= 0 and z <= w, which are near and far in either depth mode
+    let rows = transpose(camera.view_proj);
+    var planes = array<vec4f, 6>(
+        rows[3] + rows[0],
+        rows[3] - rows[0],
+        rows[3] + rows[1],
+        rows[3] - rows[1],
+        rows[2],
+        rows[3] - rows[2],
+    );
+    for (var i = 0u; i < 6u; i++) {
+        if !is_inside(planes[i], center, radius) {
+            return;
+        }
+    }
+
+    // survivors are packed at the start of their draw's arguments, in whatever order they
+    // finish in
+    let slot = atomicAdd(&counts[instance.draw], 1u);
+    arguments[instance.first_argument + slot] =
+        DrawArguments(instance.index_count, 1u, 0u, 0, instance.object);
+}
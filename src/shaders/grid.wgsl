@@ -0,0 +1,77 @@
+// the ground grid, see grid.rs
+
+#include "camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// the half width of the grid's square, far enough out that it has faded away by its edge
+const GRID_EXTENT: f32 = 500.0;
+// where the grid has faded out completely
+const FADE_DISTANCE: f32 = 150.0;
+const MINOR_SPACING: f32 = 1.0;
+const MAJOR_SPACING: f32 = 10.0;
+const LINE_COLOR: vec3f = vec3f(0.5);
+const X_AXIS_COLOR: vec3f = vec3f(0.9, 0.2, 0.2);
+const Z_AXIS_COLOR: vec3f = vec3f(0.2, 0.3, 0.9);
+
+struct GridOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) world_position: vec3f,
+}
+
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> GridOutput {
+    // two triangles, centered under the camera
+    var corners = array<vec2f, 6>(
+        vec2f(-1.0, -1.0),
+        vec2f(1.0, -1.0),
+        vec2f(1.0, 1.0),
+        vec2f(-1.0, -1.0),
+        vec2f(1.0, 1.0),
+        vec2f(-1.0, 1.0),
+    );
+    let corner = camera.view_pos.xz + corners[index] * GRID_EXTENT;
+    let world_position = vec3f(corner.x, 0.0, corner.y);
+
+    var out: GridOutput;
+    out.clip_position = camera.view_proj * vec4f(world_position, 1.0);
+    out.world_position = world_position;
+    return out;
+}
+
+// how much of a line every spacing units covers this pixel, about a pixel wide whatever the
+// distance. the width in grid cells of a pixel comes from the screen space derivatives
+fn line_coverage(coord: vec2f, derivative: vec2f) -> vec2f {
+    let distance = abs(fract(coord - 0.5) - 0.5) / max(derivative, vec2f(1e-6));
+    return 1.0 - min(distance, vec2f(1.0));
+}
+
+@fragment
+fn fragment_main(in: GridOutput) -> @location(0) vec4f {
+    let coord = in.world_position.xz;
+    let derivative = fwidth(coord);
+
+    let minor = line_coverage(coord / MINOR_SPACING, derivative / MINOR_SPACING);
+    let major = line_coverage(coord / MAJOR_SPACING, derivative / MAJOR_SPACING);
+    // where the cells get smaller than a few pixels the minor lines would blur into a flat
+    // grey, so they fade out first
+    let minor_fade = 1.0 - smoothstep(0.1, 0.4, max(derivative.x, derivative.y) / MINOR_SPACING);
+    var alpha = max(max(minor.x, minor.y) * 0.4 * minor_fade, max(major.x, major.y) * 0.8);
+    var color = LINE_COLOR;
+
+    // the x axis runs along z = 0 and the z axis along x = 0
+    let axis = line_coverage(coord / MAJOR_SPACING, derivative / MAJOR_SPACING * 2.0);
+    if abs(coord.y) < derivative.y * 2.0 {
+        color = X_AXIS_COLOR;
+        alpha = max(alpha, axis.y);
+    }
+    if abs(coord.x) < derivative.x * 2.0 {
+        color = Z_AXIS_COLOR;
+        alpha = max(alpha, axis.x);
+    }
+
+    let distance = length(in.world_position - camera.view_pos.xyz);
+    alpha *= 1.0 - smoothstep(FADE_DISTANCE * 0.5, FADE_DISTANCE, distance);
+    return vec4f(color, alpha);
+}
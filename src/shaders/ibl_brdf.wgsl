@@ -0,0 +1,58 @@
+// the split sum's second half: the scale and bias to the fresnel reflectance at normal
+// incidence of the ggx brdf integrated over the hemisphere, against n dot v along x and
+// roughness along y. it doesn't depend on the environment, so it's built once. see ibl.rs
+
+@group(0) @binding(0)
+var destination: texture_storage_2d<rgba16float, write>;
+
+const PI: f32 = 3.14159265;
+const SAMPLE_COUNT: u32 = 256u;
+
+fn hammersley(i: u32, count: u32) -> vec2f {
+    return vec2f(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064e-10);
+}
+
+// smith's shadowing with the k image based lighting uses
+fn geometry(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(destination);
+    if any(id.xy >= size) {
+        return;
+    }
+
+    let n_dot_v = max((f32(id.x) + 0.5) / f32(size.x), 1e-3);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+    let alpha = roughness * roughness;
+    // the normal is +z, with the view in the xz plane
+    let v = vec3f(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let xi = hammersley(i, SAMPLE_COUNT);
+        let phi = 2.0 * PI * xi.y;
+        let cos_theta = sqrt((1.0 - xi.x) / (1.0 + (alpha * alpha - 1.0) * xi.x));
+        let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        let h = vec3f(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+        let l = reflect(-v, h);
+
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        let v_dot_h = max(dot(v, h), 0.0);
+        if n_dot_l > 0.0 {
+            let visibility = geometry(n_dot_v, n_dot_l, roughness) * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    textureStore(destination, id.xy, vec4f(vec2f(scale, bias) / f32(SAMPLE_COUNT), 0.0, 1.0));
+}
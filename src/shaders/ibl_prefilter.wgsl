@@ -0,0 +1,114 @@
+// convolves an equirectangular environment into one mip of a cube: the cosine weighted
+// irradiance for diffuse light, or the ggx prefiltered radiance at one roughness for specular
+// light. see ibl.rs
+
+struct Prefilter {
+    // 0 irradiance, 1 specular
+    kind: u32,
+    roughness: f32,
+    sample_count: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var environment: texture_2d<f32>;
+@group(0) @binding(1)
+var environment_sampler: sampler;
+@group(0) @binding(2)
+var destination: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(3)
+var<uniform> prefilter: Prefilter;
+
+const PI: f32 = 3.14159265;
+
+// the direction through a texel of a cube face, in the +x, -x, +y, -y, +z, -z order and the
+// orientation cube views sample with
+fn cube_direction(face: u32, uv: vec2f) -> vec3f {
+    let st = uv * 2.0 - 1.0;
+    switch face {
+        case 0u: { return normalize(vec3f(1.0, -st.y, -st.x)); }
+        case 1u: { return normalize(vec3f(-1.0, -st.y, st.x)); }
+        case 2u: { return normalize(vec3f(st.x, 1.0, st.y)); }
+        case 3u: { return normalize(vec3f(st.x, -1.0, -st.y)); }
+        case 4u: { return normalize(vec3f(st.x, -st.y, 1.0)); }
+        default: { return normalize(vec3f(-st.x, -st.y, -1.0)); }
+    }
+}
+
+// the same mapping panorama.rs saves with: +y up, the center looking along -z and the right
+// half turning towards +x
+fn sample_environment(direction: vec3f) -> vec3f {
+    let longitude = atan2(direction.x, -direction.z);
+    let latitude = asin(clamp(direction.y, -1.0, 1.0));
+    let uv = vec2f(longitude / (2.0 * PI) + 0.5, 0.5 - latitude / PI);
+    return textureSampleLevel(environment, environment_sampler, uv, 0.0).rgb;
+}
+
+fn hammersley(i: u32, count: u32) -> vec2f {
+    return vec2f(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064e-10);
+}
+
+// a basis around n, for turning tangent space samples into world directions
+fn tangent_frame(n: vec3f) -> mat3x3f {
+    let helper = select(vec3f(0.0, 0.0, 1.0), vec3f(1.0, 0.0, 0.0), abs(n.z) > 0.999);
+    let tangent = normalize(cross(helper, n));
+    let bitangent = cross(n, tangent);
+    return mat3x3f(tangent, bitangent, n);
+}
+
+// cosine weighted samples make the cosine term and the pdf cancel, leaving a plain average
+fn irradiance(n: vec3f) -> vec3f {
+    let frame = tangent_frame(n);
+    var sum = vec3f(0.0);
+    for (var i = 0u; i < prefilter.sample_count; i++) {
+        let xi = hammersley(i, prefilter.sample_count);
+        let radius = sqrt(xi.x);
+        let phi = 2.0 * PI * xi.y;
+        let local = vec3f(radius * cos(phi), radius * sin(phi), sqrt(1.0 - xi.x));
+        sum += sample_environment(frame * local);
+    }
+    return sum / f32(prefilter.sample_count);
+}
+
+// the split sum's first half, assuming the view is along the normal: ggx importance sampled
+// reflections weighted by n dot l
+fn prefiltered_radiance(n: vec3f) -> vec3f {
+    let alpha = prefilter.roughness * prefilter.roughness;
+    let frame = tangent_frame(n);
+    var sum = vec3f(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < prefilter.sample_count; i++) {
+        let xi = hammersley(i, prefilter.sample_count);
+        let phi = 2.0 * PI * xi.y;
+        let cos_theta = sqrt((1.0 - xi.x) / (1.0 + (alpha * alpha - 1.0) * xi.x));
+        let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        let h = frame * vec3f(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+        let l = reflect(-n, h);
+        let n_dot_l = dot(n, l);
+        if n_dot_l > 0.0 {
+            sum += sample_environment(l) * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    return sum / max(weight, 1e-4);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(destination);
+    if any(id.xy >= size) {
+        return;
+    }
+
+    let uv = (vec2f(id.xy) + 0.5) / vec2f(size);
+    let direction = cube_direction(id.z, uv);
+    var color: vec3f;
+    if prefilter.kind == 0u {
+        color = irradiance(direction);
+    } else if prefilter.roughness == 0.0 {
+        color = sample_environment(direction);
+    } else {
+        color = prefiltered_radiance(direction);
+    }
+    textureStore(destination, id.xy, id.z, vec4f(color, 1.0));
+}
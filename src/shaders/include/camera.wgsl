@@ -0,0 +1,7 @@
+// the per frame camera, bound at group 0 binding 0 wherever it's used. see
+// uniforms::CameraUniform
+
+struct Camera {
+    view_pos: vec4f,
+    view_proj: mat4x4f,
+}
@@ -0,0 +1,12 @@
+// the denoiser's settings for the frame, see denoise::DenoiserUniform
+
+struct Denoiser {
+    // where the camera was the last time the denoiser ran, for the motion vectors
+    previous_view_proj: mat4x4f,
+    frame_index: u32,
+    // whether the denoised ao is this frame's, so the scene pass can read it
+    is_enabled: u32,
+    // whether there is a last frame to reproject, rather than stale history
+    is_history_valid: u32,
+    _padding: u32,
+}
@@ -0,0 +1,39 @@
+// the volumetric fog's settings and where its froxels are, shared by its passes. see fog.rs
+
+struct Fog {
+    inverse_view_proj: mat4x4f,
+    camera_position: vec3f,
+    time: f32,
+    color: vec3f,
+    density: f32,
+    wind: vec3f,
+    noise: f32,
+    height: f32,
+    height_falloff: f32,
+    anisotropy: f32,
+    noise_scale: f32,
+    // the distances the froxels start and end at
+    near: f32,
+    far: f32,
+    ambient: f32,
+    // the light from all around as spherical harmonics, see ibl.rs's sh_basis
+    sky: array<vec4f, 9>,
+}
+
+// the direction from the camera through a point on the screen, from 0 to 1 across and down it
+fn froxel_direction(fog: Fog, uv: vec2f) -> vec3f {
+    let ndc = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.5, 1.0);
+    let world = fog.inverse_view_proj * ndc;
+    return normalize(world.xyz / world.w - fog.camera_position);
+}
+
+// the distance from the camera a fraction of the way through the slices of froxels. they're
+// spaced exponentially, so each is as deep as it is wide on screen
+fn slice_distance(fog: Fog, fraction: f32) -> f32 {
+    return fog.near * pow(fog.far / fog.near, fraction);
+}
+
+// the inverse of slice_distance
+fn distance_fraction(fog: Fog, distance: f32) -> f32 {
+    return log(max(distance, fog.near) / fog.near) / log(fog.far / fog.near);
+}
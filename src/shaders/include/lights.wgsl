@@ -0,0 +1,18 @@
+// every light in one storage array and where each kind starts in it. see uniforms::LightUniform
+// and uniforms::LightMetadataUniform
+
+struct Light {
+    position: vec3f,
+    direction: vec3f,
+    color: vec3f,
+    params: vec4f,
+}
+
+struct LightMetadata {
+    point_light_count: u32,
+    point_light_offset: u32,
+    directional_light_count: u32,
+    directional_light_offset: u32,
+    spot_light_count: u32,
+    spot_light_offset: u32,
+}
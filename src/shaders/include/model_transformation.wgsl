@@ -0,0 +1,20 @@
+// one object's transform, from the per object bind group. see model::ModelTransformationUniform
+
+struct ModelTransformation {
+    model_transform_col0: vec4f,
+    model_transform_col1: vec4f,
+    model_transform_col2: vec4f,
+    model_transform_col3: vec4f,
+    // inverse transpose of the upper 3x3, so normals survive non-uniform scaling
+    normal_transform_col0: vec4f,
+    normal_transform_col1: vec4f,
+    normal_transform_col2: vec4f,
+}
+
+#ifdef TRANSFORM_STRIDE
+// one slot of the scene's whole transform buffer, for reading any object's transform by its
+// index from storage. the slots are padded out to the scene's stride, see Scene::transforms
+struct ObjectTransform {
+    @size(TRANSFORM_STRIDE) transformation: ModelTransformation,
+}
+#endif
@@ -0,0 +1,49 @@
+// the ray queries behind the ray traced shadows and ambient occlusion, shared by raytraced.wgsl
+// and the denoiser's prepass (see denoise.rs). both bind the scene's tlas at group 0 binding 4,
+// and need `enable wgpu_ray_query;` at the top of their source
+
+@group(0) @binding(4)
+var scene: acceleration_structure;
+
+const AO_RAY_COUNT: u32 = 8;
+const AO_DISTANCE: f32 = 1.0;
+// rays start this far off the surface so they don't hit the triangle they start on
+const RAY_OFFSET: f32 = 0.01;
+
+fn trace_any_hit(origin: vec3f, direction: vec3f, max_distance: f32) -> bool {
+    var query: ray_query;
+    rayQueryInitialize(&query, scene, RayDesc(RAY_FLAG_TERMINATE_ON_FIRST_HIT, 0xffu, 0.0, max_distance, origin, direction));
+    rayQueryProceed(&query);
+    return rayQueryGetCommittedIntersection(&query).kind != RAY_QUERY_INTERSECTION_NONE;
+}
+
+fn hash(p: vec3f) -> f32 {
+    return fract(sin(dot(p, vec3f(12.9898, 78.233, 37.719))) * 43758.5453);
+}
+
+// the fraction of AO_RAY_COUNT rays over the hemisphere which escape within AO_DISTANCE. noisy,
+// since the rays change with the frame
+fn traced_ambient_occlusion(position: vec3f, normal: vec3f, frame_index: u32) -> f32 {
+    let helper = select(vec3f(0.0, 0.0, 1.0), vec3f(1.0, 0.0, 0.0), abs(normal.z) > 0.9);
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+
+    // a fixed cosine weighted pattern, rotated per pixel and per frame to trade banding for noise
+    let rotation = hash(position + f32(frame_index)) * 6.2831853;
+
+    var unoccluded = 0.0;
+    for (var i = 0u; i < AO_RAY_COUNT; i++) {
+        // a golden angle spiral over the unit disk, projected up onto the hemisphere
+        let radius = sqrt((f32(i) + 0.5) / f32(AO_RAY_COUNT));
+        let angle = f32(i) * 2.3999632 + rotation;
+        let direction = normalize(
+            tangent * radius * cos(angle)
+            + bitangent * radius * sin(angle)
+            + normal * sqrt(1.0 - radius * radius)
+        );
+        if !trace_any_hit(position + normal * RAY_OFFSET, direction, AO_DISTANCE) {
+            unoccluded += 1.0;
+        }
+    }
+    return unoccluded / f32(AO_RAY_COUNT);
+}
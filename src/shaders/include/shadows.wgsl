@@ -0,0 +1,33 @@
+// the first directional light's cascaded shadow maps and the first point light's cube of
+// shadow maps, bound at group 0 binding 5 wherever they're used. see shadows.rs
+
+struct Shadows {
+    cascades: array<mat4x4f, 4>,
+    // the world space size of a texel in each cascade
+    texel_sizes: vec4f,
+    // the world space distance between each cascade's near and far planes
+    depth_ranges: vec4f,
+    cascade_count: u32,
+    // 0 without a directional light to cast them
+    is_enabled: u32,
+    // 1 tints each cascade its own color
+    debug_cascades: u32,
+    // how far each point is pushed along its normal before the lookup, in texels
+    normal_offset: f32,
+    // in the +x, -x, +y, -y, +z, -z order of panorama::FACES
+    point_faces: array<mat4x4f, 6>,
+    // the point light's position and range in w
+    point_light: vec4f,
+    // 0 without a point light to cast them
+    point_is_enabled: u32,
+    // taken off the distance before comparing, as a fraction of the range
+    point_bias: f32,
+    // 0 hard, 1 pcf 3x3, 2 pcf 5x5, 3 pcss
+    quality: u32,
+    // how far pcss looks for occluders, in texels
+    search_radius: f32,
+    // the tangent of the sun's angular radius
+    sun_size: f32,
+    // the point light's radius in world units
+    point_size: f32,
+}
@@ -0,0 +1,10 @@
+// the voxel grid's placement and settings, see vxgi.rs
+
+struct VoxelGI {
+    grid_min: vec3f,
+    voxel_size: f32,
+    resolution: u32,
+    // 0 = off, 1 = direct + indirect, 2 = indirect only
+    mode: u32,
+    mip_count: u32,
+}
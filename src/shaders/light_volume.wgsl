@@ -0,0 +1,61 @@
+
+// translucent volumes around lights, see light_volumes.rs
+
+#include "camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) normal: vec3f,
+}
+
+struct InstanceInput {
+    @location(2) transform_col0: vec4f,
+    @location(3) transform_col1: vec4f,
+    @location(4) transform_col2: vec4f,
+    @location(5) transform_col3: vec4f,
+    @location(6) scale: vec4f,
+    @location(7) color: vec4f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) world_position: vec3f,
+    @location(1) world_normal: vec3f,
+    @location(2) color: vec4f,
+}
+
+@vertex
+fn vertex_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let transform = mat4x4f(
+        instance.transform_col0,
+        instance.transform_col1,
+        instance.transform_col2,
+        instance.transform_col3,
+    );
+    // the scale is kept out of the transform, which only rotates and translates, so normals
+    // just need the inverse scale
+    let scale = max(instance.scale.xyz, vec3f(1e-6));
+    let world_position = transform * vec4f(vertex.position * scale, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.world_normal = (transform * vec4f(vertex.normal / scale, 0.0)).xyz;
+    out.color = instance.color;
+    return out;
+}
+
+// fragment shader
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    // faint where the surface faces the camera and stronger towards the silhouette, so the
+    // outline of the volume reads clearly without hiding what's inside it
+    let view_direction = normalize(camera.view_pos.xyz - in.world_position);
+    let facing = abs(dot(normalize(in.world_normal), view_direction));
+    let rim = pow(1.0 - facing, 2.0);
+    return vec4f(in.color.rgb, in.color.a * mix(0.15, 1.0, rim));
+}
@@ -0,0 +1,37 @@
+// texture array versions of the material bindings in shader.wgsl, spliced over its
+// `@hook material` block by material_atlas.rs. every batched material shares this bind group:
+// draws pick their material with the dynamic offset of binding 4, whose entry also says which
+// layers of the arrays hold the material's maps
+
+struct AtlasMaterial {
+    material: Material,
+    // x is the diffuse map's layer, y the normal map's
+    layers: vec4i,
+}
+
+@group(1) @binding(0)
+var diffuse_textures: texture_2d_array<f32>;
+@group(1) @binding(1)
+var diffuse_sampler: sampler;
+@group(1) @binding(2)
+var normal_textures: texture_2d_array<f32>;
+@group(1) @binding(3)
+var normal_sampler: sampler;
+@group(1) @binding(4)
+var<uniform> atlas_material: AtlasMaterial;
+
+fn current_material() -> Material {
+    return atlas_material.material;
+}
+
+fn sample_diffuse(tex_coords: vec2f) -> vec4f {
+    return textureSample(diffuse_textures, diffuse_sampler, tex_coords, atlas_material.layers.x);
+}
+
+fn sample_normal(tex_coords: vec2f) -> vec4f {
+    return textureSample(normal_textures, normal_sampler, tex_coords, atlas_material.layers.y);
+}
+
+fn diffuse_size() -> vec2f {
+    return vec2f(textureDimensions(diffuse_textures).xy);
+}
@@ -0,0 +1,18 @@
+// copies the scene pass's multisampled depth into the single sampled depth texture, for the
+// passes after it which read the depth, see msaa.rs. each pixel takes its first sample, so
+// the depth at an edge is one of the surfaces there rather than a blend of both
+
+@group(0) @binding(0)
+var multisampled_depth: texture_depth_multisampled_2d;
+
+// a triangle twice the size of the screen, covering it
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fragment_main(@builtin(position) position: vec4<f32>) -> @builtin(frag_depth) f32 {
+    return textureLoad(multisampled_depth, vec2<i32>(position.xy), 0);
+}
@@ -0,0 +1,28 @@
+
+// the weighted blended transparency entry point, appended to shader.wgsl in place of its own
+// fragment_main, which is renamed to shade. see oit.rs
+
+struct OitOutput {
+    // premultiplied color and alpha, weighted, summed over every fragment
+    @location(0) accumulation: vec4f,
+    // the alpha, which the blend state multiplies into the background's remaining coverage
+    @location(1) revealage: f32,
+}
+
+// mcguire and bavoil's distance based weight, so nearer surfaces dominate the average. it uses
+// the distance to the camera rather than the depth, which means the same in either depth mode
+fn oit_weight(alpha: f32, distance: f32) -> f32 {
+    let falloff = 10.0 / (1e-5 + pow(distance / 5.0, 2.0) + pow(distance / 200.0, 6.0));
+    return alpha * clamp(falloff, 1e-2, 3e3);
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> OitOutput {
+    let color = shade(in);
+    let weight = oit_weight(color.a, distance(camera.view_pos.xyz, in.world_position));
+
+    var out: OitOutput;
+    out.accumulation = vec4f(color.rgb * color.a, color.a) * weight;
+    out.revealage = color.a;
+    return out;
+}
@@ -0,0 +1,34 @@
+// composites the weighted blended transparency targets over the scene, see oit.rs
+
+@group(0) @binding(0)
+var accumulation_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var revealage_texture: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+}
+
+// a triangle twice the size of the screen, covering all of it
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// the average transparent color, blended over the scene by how much of the scene is covered
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    let pixel = vec2i(in.clip_position.xy);
+    let revealage = textureLoad(revealage_texture, pixel, 0).r;
+    // nothing transparent was drawn here
+    if revealage >= 1.0 {
+        discard;
+    }
+
+    let accumulation = textureLoad(accumulation_texture, pixel, 0);
+    let average = accumulation.rgb / clamp(accumulation.a, 1e-4, 5e4);
+    return vec4f(average, 1.0 - revealage);
+}
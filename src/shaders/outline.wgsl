@@ -0,0 +1,55 @@
+// the selected model's outline, see outline.rs. the mark pass writes the model's silhouette
+// into the stencil, then the outline pass draws the model pushed out along its normals
+// wherever the stencil wasn't marked, which leaves a rim around the silhouette
+
+#include "camera.wgsl"
+#include "model_transformation.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> model_transformation: ModelTransformation;
+
+// how far the outline reaches past the silhouette, as a fraction of the distance to the
+// camera, which keeps it about the same width on screen however far away the model is
+const OUTLINE_WIDTH: f32 = 0.006;
+const OUTLINE_COLOR: vec3f = vec3f(1.0, 0.55, 0.1);
+
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(2) normal: vec3f,
+}
+
+fn world_position(position: vec3f) -> vec3f {
+    let model_transformation_matrix = mat4x4(
+        model_transformation.model_transform_col0,
+        model_transformation.model_transform_col1,
+        model_transformation.model_transform_col2,
+        model_transformation.model_transform_col3
+    );
+    return (model_transformation_matrix * vec4f(position, 1.0)).xyz;
+}
+
+@vertex
+fn mark_vertex_main(vertex: VertexInput) -> @builtin(position) vec4f {
+    return camera.view_proj * vec4f(world_position(vertex.position), 1.0);
+}
+
+@vertex
+fn outline_vertex_main(vertex: VertexInput) -> @builtin(position) vec4f {
+    let normal_matrix = mat3x3(
+        model_transformation.normal_transform_col0.xyz,
+        model_transformation.normal_transform_col1.xyz,
+        model_transformation.normal_transform_col2.xyz
+    );
+    let world_normal = normalize(normal_matrix * vertex.normal);
+    let position = world_position(vertex.position);
+    let width = OUTLINE_WIDTH * distance(position, camera.view_pos.xyz);
+    return camera.view_proj * vec4f(position + world_normal * width, 1.0);
+}
+
+@fragment
+fn fragment_main() -> @location(0) vec4f {
+    return vec4f(OUTLINE_COLOR, 1.0);
+}
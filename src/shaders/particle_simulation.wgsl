@@ -0,0 +1,181 @@
+// moves every emitter's particles on by a frame, respawning the ones it emits and colliding the
+// rest with the last frame's depth buffer. see particles.rs
+
+struct Simulation {
+    previous_view_proj: mat4x4f,
+    previous_inverse_view_proj: mat4x4f,
+    previous_camera_position: vec3f,
+    delta_seconds: f32,
+    seed: u32,
+    emitter_count: u32,
+    has_previous_depth: u32,
+    clear_depth: f32,
+    collision_thickness: f32,
+}
+
+// see particles::EmitterUniform
+struct Emitter {
+    position: vec3f,
+    lifetime: f32,
+    extent: vec3f,
+    size: f32,
+    velocity: vec3f,
+    spread: f32,
+    color: vec4f,
+    gravity: f32,
+    collision: u32,
+    restitution: f32,
+    first: u32,
+    capacity: u32,
+    spawn_start: u32,
+    spawn_count: u32,
+}
+
+// dead once its age reaches its lifetime, which a zeroed particle starts out as
+struct Particle {
+    position: vec3f,
+    age: f32,
+    velocity: vec3f,
+    lifetime: f32,
+    color: vec4f,
+    size: f32,
+}
+
+const COLLISION_BOUNCE: u32 = 1u;
+const COLLISION_KILL: u32 = 2u;
+
+@group(0) @binding(0)
+var<uniform> simulation: Simulation;
+@group(0) @binding(1)
+var<storage, read> emitters: array<Emitter>;
+@group(0) @binding(2)
+var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(3)
+var depth_texture: texture_depth_2d;
+
+// pcg, from "hash functions for gpu rendering" (jarzynski and olano)
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// from -1 to 1 along each axis, different for each particle, frame and `salt`
+fn random3(index: u32, salt: u32) -> vec3f {
+    let h = hash(index ^ hash(simulation.seed ^ hash(salt)));
+    let values = vec3u(h, hash(h), hash(hash(h)));
+    return vec3f(values & vec3u(0xffffu)) / 32767.5 - 1.0;
+}
+
+fn spawn(emitter: Emitter, index: u32) -> Particle {
+    var particle: Particle;
+    particle.position = emitter.position + emitter.extent * random3(index, 0u);
+    particle.velocity = emitter.velocity + emitter.spread * random3(index, 1u);
+    particle.age = 0.0;
+    particle.lifetime = emitter.lifetime;
+    particle.color = emitter.color;
+    particle.size = emitter.size;
+    return particle;
+}
+
+// the world position drawn at a texel of the last frame's depth buffer
+fn surface_position(texel: vec2i, size: vec2i) -> vec3f {
+    let clamped = clamp(texel, vec2i(0), size - 1);
+    let depth = textureLoad(depth_texture, clamped, 0);
+    let uv = (vec2f(clamped) + 0.5) / vec2f(size);
+    let ndc = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = simulation.previous_inverse_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+// the surface the particle has gone into, as its position and its normal facing the camera, or
+// a zero normal when it hasn't hit anything on screen
+fn hit_surface(position: vec3f) -> array<vec3f, 2> {
+    let none = array<vec3f, 2>(vec3f(0.0), vec3f(0.0));
+    let clip = simulation.previous_view_proj * vec4f(position, 1.0);
+    if clip.w <= 0.0 {
+        return none;
+    }
+    let ndc = clip.xyz / clip.w;
+    if any(abs(ndc.xy) > vec2f(1.0)) {
+        return none;
+    }
+
+    let size = vec2i(textureDimensions(depth_texture));
+    let uv = vec2f(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let texel = min(vec2i(uv * vec2f(size)), size - 1);
+    if textureLoad(depth_texture, texel, 0) == simulation.clear_depth {
+        // the sky, which nothing hits
+        return none;
+    }
+
+    // behind the surface, but not so far that it's passing behind it instead
+    let surface = surface_position(texel, size);
+    let camera = simulation.previous_camera_position;
+    let depth_behind = distance(position, camera) - distance(surface, camera);
+    if depth_behind < 0.0 || depth_behind > simulation.collision_thickness {
+        return none;
+    }
+
+    let right = surface_position(texel + vec2i(1, 0), size) - surface;
+    let down = surface_position(texel + vec2i(0, 1), size) - surface;
+    var normal = cross(right, down);
+    if dot(normal, normal) < 1e-12 {
+        return none;
+    }
+    normal = normalize(normal);
+    if dot(normal, camera - surface) < 0.0 {
+        normal = -normal;
+    }
+    return array<vec3f, 2>(surface, normal);
+}
+
+@compute @workgroup_size(64)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    let index = id.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+
+    for (var e = 0u; e < simulation.emitter_count; e++) {
+        let emitter = emitters[e];
+        if index < emitter.first || index >= emitter.first + emitter.capacity {
+            continue;
+        }
+
+        // this frame's spawns take the slots from spawn_start on, wrapping around the range
+        let local = index - emitter.first;
+        let since_start = (local + emitter.capacity - emitter.spawn_start) % emitter.capacity;
+        if since_start < emitter.spawn_count {
+            particles[index] = spawn(emitter, index);
+            return;
+        }
+
+        var particle = particles[index];
+        if particle.age >= particle.lifetime {
+            return;
+        }
+        let dt = simulation.delta_seconds;
+        particle.age += dt;
+        particle.velocity.y += emitter.gravity * dt;
+        particle.position += particle.velocity * dt;
+
+        if emitter.collision != 0u && simulation.has_previous_depth != 0u {
+            let hit = hit_surface(particle.position);
+            let normal = hit[1];
+            // only when moving into the surface, so a bounced particle can leave it
+            if any(normal != vec3f(0.0)) && dot(particle.velocity, normal) < 0.0 {
+                if emitter.collision == COLLISION_KILL {
+                    particle.age = particle.lifetime;
+                } else if emitter.collision == COLLISION_BOUNCE {
+                    let into = dot(particle.velocity, normal) * normal;
+                    particle.velocity -= (1.0 + emitter.restitution) * into;
+                    // back out onto the surface, so it isn't caught behind it next frame
+                    particle.position += normal * dot(hit[0] - particle.position, normal);
+                }
+            }
+        }
+        particles[index] = particle;
+        return;
+    }
+}
@@ -0,0 +1,74 @@
+// draws each particle as a disc facing the camera, fading out as it ages. see particles.rs
+
+#include "camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// see particle_simulation.wgsl
+struct Particle {
+    position: vec3f,
+    age: f32,
+    velocity: vec3f,
+    lifetime: f32,
+    color: vec4f,
+    size: f32,
+}
+
+@group(1) @binding(0)
+var<storage, read> particles: array<Particle>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    // from -1 to 1 across the quad
+    @location(0) offset: vec2f,
+    @location(1) color: vec4f,
+}
+
+@vertex
+fn vertex_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let particle = particles[instance_index];
+    if particle.age >= particle.lifetime {
+        // dead, so outside clip space where nothing is drawn
+        out.clip_position = vec4f(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    // two triangles, a corner at a time
+    var corners = array<vec2f, 6>(
+        vec2f(-1.0, -1.0), vec2f(1.0, -1.0), vec2f(1.0, 1.0),
+        vec2f(-1.0, -1.0), vec2f(1.0, 1.0), vec2f(-1.0, 1.0),
+    );
+    let offset = corners[vertex_index];
+
+    // turned to face the camera
+    let forward = normalize(particle.position - camera.view_pos.xyz);
+    var right = cross(forward, vec3f(0.0, 1.0, 0.0));
+    if dot(right, right) < 1e-6 {
+        right = vec3f(1.0, 0.0, 0.0);
+    }
+    right = normalize(right);
+    let up = cross(right, forward);
+    let radius = particle.size * 0.5;
+    let position = particle.position + (right * offset.x + up * offset.y) * radius;
+
+    out.clip_position = camera.view_proj * vec4f(position, 1.0);
+    out.offset = offset;
+    out.color = particle.color;
+    out.color.a *= 1.0 - particle.age / particle.lifetime;
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    let distance_squared = dot(in.offset, in.offset);
+    if distance_squared > 1.0 {
+        discard;
+    }
+    // softened towards the edge
+    return vec4f(in.color.rgb, in.color.a * (1.0 - distance_squared));
+}
@@ -0,0 +1,38 @@
+// ray query versions of the visibility functions in shader.wgsl, spliced over its
+// `@hook visibility` block by raytracing.rs when the adapter supports ray queries
+
+#include "ray_queries.wgsl"
+#include "denoiser.wgsl"
+
+// the denoised ambient occlusion and the view distance it was traced at, see denoise.rs
+@group(0) @binding(14)
+var denoised_ao: texture_2d<f32>;
+@group(0) @binding(15)
+var<uniform> denoiser: Denoiser;
+
+fn shadow_visibility(position: vec3f, normal: vec3f, to_light: vec3f) -> f32 {
+    let distance = length(to_light);
+    if trace_any_hit(position + normal * RAY_OFFSET, to_light / distance, distance) {
+        return 0.0;
+    }
+    return 1.0;
+}
+
+// the denoised ao where the denoiser's prepass saw this surface at this pixel. anything it
+// didn't see, eg transparent surfaces, or whenever it's off, traces its own noisy rays
+fn ambient_occlusion(position: vec3f, normal: vec3f) -> f32 {
+    if denoiser.is_enabled != 0u {
+        let clip = camera.view_proj * vec4f(position, 1.0);
+        let uv = clip.xy / clip.w * vec2f(0.5, -0.5) + 0.5;
+        let size = vec2i(textureDimensions(denoised_ao));
+        let texel = vec2i(floor(uv * vec2f(size)));
+        if all(texel >= vec2i(0)) && all(texel < size) {
+            let denoised = textureLoad(denoised_ao, texel, 0);
+            let view_distance = distance(position, camera.view_pos.xyz);
+            if abs(denoised.y - view_distance) < 0.01 * view_distance {
+                return denoised.x;
+            }
+        }
+    }
+    return traced_ambient_occlusion(position, normal, time.frame_index);
+}
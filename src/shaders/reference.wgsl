@@ -0,0 +1,36 @@
+// the reference image overlay, see reference.rs
+
+struct ReferenceUniform {
+    opacity: f32,
+    split: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: ReferenceUniform;
+@group(0) @binding(1)
+var reference_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var reference_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle twice the size of the screen, so the screen is exactly the part where uv is 0..1
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(reference_texture, reference_sampler, in.uv);
+    if in.uv.x > settings.split {
+        discard;
+    }
+    return vec4<f32>(color.rgb, color.a * settings.opacity);
+}
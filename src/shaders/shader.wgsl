@@ -6,29 +6,18 @@
 // - performance tracking
 // 
 
-struct Camera {
-    view_pos: vec4f,
-    view_proj: mat4x4f,
-}
-
-struct Light {
-    position: vec3f,
-    direction: vec3f,
-    color: vec3f,
-    params: vec4f,
-}
-
-struct LightMetadata {
-    point_light_count: u32,
-    point_light_offset: u32,
-    directional_light_count: u32,
-    directional_light_offset: u32,
-    spot_light_count: u32,
-    spot_light_offset: u32,
-}
+#include "camera.wgsl"
+#include "lights.wgsl"
+#include "shadows.wgsl"
 
 struct Time {
-    millis: u32,
+    seconds: f32,
+    delta_seconds: f32,
+    frame_index: u32,
+    sin_time: f32,
+    cos_time: f32,
+    // see uniforms::DebugView
+    debug_view: u32,
 }
 
 @group(0) @binding(0)
@@ -40,22 +29,55 @@ var<uniform> light_metadata: LightMetadata;
 @group(0) @binding(3)
 var<uniform> time: Time;
 
-struct ModelTransformation {
-    model_transform_col0: vec4f,
-    model_transform_col1: vec4f,
-    model_transform_col2: vec4f,
-    model_transform_col3: vec4f,
+@group(0) @binding(5)
+var<uniform> shadows: Shadows;
+@group(0) @binding(6)
+var shadow_maps: texture_depth_2d_array;
+@group(0) @binding(7)
+var shadow_sampler: sampler_comparison;
+@group(0) @binding(8)
+var point_shadow_maps: texture_depth_2d_array;
+
+// the environment prefiltered for image based lighting, see ibl.rs
+struct Environment {
+    intensity: f32,
+    // 0 falls back to the flat ambient color
+    is_enabled: u32,
+    specular_mip_count: u32,
+    // from 0 for dry to 1 for soaked, see weather.rs
+    wetness: f32,
 }
 
+@group(0) @binding(9)
+var<uniform> environment: Environment;
+@group(0) @binding(10)
+var irradiance_map: texture_cube<f32>;
+@group(0) @binding(11)
+var specular_map: texture_cube<f32>;
+@group(0) @binding(12)
+var brdf_lut: texture_2d<f32>;
+@group(0) @binding(13)
+var environment_sampler: sampler;
+
+#include "model_transformation.wgsl"
+
+#ifdef GPU_CULLING
+// every object's transform, picked by the first instance the culling pass gave the draw. see
+// gpu_culling.rs
+@group(2) @binding(0)
+var<storage, read> object_transforms: array<ObjectTransform>;
+#else
 @group(2) @binding(0)
 var<uniform> model_transformation: ModelTransformation;
+#endif
 
 struct VertexInput {
     @location(0) position: vec3f,
     @location(1) tex_coords: vec2f,
     @location(2) normal: vec3f,
-    @location(3) tangent: vec3f,
-    @location(4) bitangent: vec3f,
+    // xyz is the tangent, w is the handedness of the bitangent (1 or -1)
+    @location(3) tangent: vec4f,
+    @location(4) color: vec3f,
 }
 
 struct VertexOutput {
@@ -65,11 +87,15 @@ struct VertexOutput {
     @location(2) world_tangent: vec3f,
     @location(3) world_bitangent: vec3f,
     @location(4) world_normal: vec3f,
+    @location(5) color: vec3f,
 }
 
 @vertex
-fn vertex_main(vertex: VertexInput) -> VertexOutput {
+fn vertex_main(vertex: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
     var out: VertexOutput;
+#ifdef GPU_CULLING
+    let model_transformation = object_transforms[instance].transformation;
+#endif
 
     let model_transformation_matrix = mat4x4(
         model_transformation.model_transform_col0,
@@ -78,19 +104,25 @@ fn vertex_main(vertex: VertexInput) -> VertexOutput {
         model_transformation.model_transform_col3
     );
 
-    // TODO this only works if the model transformation is orthogonal ie no stretching/skewing
-    let normal_transformation_matrix = mat3x3f(model_transformation_matrix[0].xyz, model_transformation_matrix[1].xyz, model_transformation_matrix[2].xyz);
+    let normal_transformation_matrix = mat3x3f(
+        model_transformation.normal_transform_col0.xyz,
+        model_transformation.normal_transform_col1.xyz,
+        model_transformation.normal_transform_col2.xyz
+    );
 
     let world_position_h = model_transformation_matrix * vec4f(vertex.position, 1.0);
 
     out.clip_position = camera.view_proj * world_position_h;
     out.tex_coords = vertex.tex_coords;
+    out.color = vertex.color;
 
     out.world_position = world_position_h.xyz;
 
     out.world_normal = normalize(normal_transformation_matrix * vertex.normal);
-    out.world_tangent = normalize(normal_transformation_matrix * vertex.tangent);
-    out.world_bitangent = normalize(normal_transformation_matrix * vertex.bitangent);
+    // tangents lie in the surface so they follow the model matrix rather than the normal matrix
+    out.world_tangent = normalize((model_transformation_matrix * vec4f(vertex.tangent.xyz, 0.0)).xyz);
+    // mirrored uvs flip the bitangent, which the tangent's w carries
+    out.world_bitangent = cross(out.world_normal, out.world_tangent) * vertex.tangent.w;
 
     // out.tangent_position       = world_normal;
     // out.tangent_view_position  = vertex.tangent;
@@ -104,14 +136,41 @@ fn vertex_main(vertex: VertexInput) -> VertexOutput {
 struct Material {
     @size(16) ambient_color: vec3f,
     @size(16) diffuse_color: vec3f,
-    @size(16) specular_color: vec3f,
+    specular_color: vec3f,
+    shininess: f32,
 
     has_diffuse_texture: u32,
     has_normal_texture: u32,
-
-    @size(8) _tail_pad: u32,
+    has_specular_texture: u32,
+    has_shininess_texture: u32,
+    has_alpha_texture: u32,
+    has_emissive_texture: u32,
+    dissolve: f32,
+    bump_multiplier: f32,
+
+    uv_offset: vec2f,
+    uv_scale: vec2f,
+
+    emissive_color: vec3f,
+    roughness: f32,
+    metallic: f32,
+    sheen: f32,
+    has_roughness_texture: u32,
+    has_metallic_texture: u32,
+    use_roughness: u32,
+    // 1 for directx style normal maps
+    flip_normal_y: u32,
+
+    has_height_texture: u32,
+    // the depth of the height map's black below its white, in uv units
+    height_scale: f32,
+    parallax_steps: u32,
+    @size(12) _tail_pad: u32,
 }
 
+// @hook material
+// each material has its own bind group. material_atlas.rs replaces this block with
+// material_atlas.wgsl, which samples the maps from layers of texture arrays instead
 @group(1) @binding(0)
 var diffuse_texture: texture_2d<f32>;
 @group(1) @binding(1)
@@ -121,33 +180,505 @@ var normal_texture: texture_2d<f32>;
 @group(1) @binding(3)
 var normal_sampler: sampler;
 @group(1) @binding(4)
-var<uniform> material: Material;
+var<uniform> bound_material: Material;
+
+fn current_material() -> Material {
+    return bound_material;
+}
+
+fn sample_diffuse(tex_coords: vec2f) -> vec4f {
+    return textureSample(diffuse_texture, diffuse_sampler, tex_coords);
+}
+
+fn sample_normal(tex_coords: vec2f) -> vec4f {
+    return textureSample(normal_texture, normal_sampler, tex_coords);
+}
+
+fn diffuse_size() -> vec2f {
+    return vec2f(textureDimensions(diffuse_texture));
+}
+// @end hook
+
+@group(1) @binding(6)
+var specular_texture: texture_2d<f32>;
+@group(1) @binding(7)
+var specular_sampler: sampler;
+@group(1) @binding(8)
+var shininess_texture: texture_2d<f32>;
+@group(1) @binding(9)
+var shininess_sampler: sampler;
+@group(1) @binding(10)
+var alpha_texture: texture_2d<f32>;
+@group(1) @binding(11)
+var alpha_sampler: sampler;
+@group(1) @binding(12)
+var emissive_texture: texture_2d<f32>;
+@group(1) @binding(13)
+var emissive_sampler: sampler;
+@group(1) @binding(14)
+var roughness_texture: texture_2d<f32>;
+@group(1) @binding(15)
+var roughness_sampler: sampler;
+@group(1) @binding(16)
+var metallic_texture: texture_2d<f32>;
+@group(1) @binding(17)
+var metallic_sampler: sampler;
+@group(1) @binding(18)
+var height_texture: texture_2d<f32>;
+@group(1) @binding(19)
+var height_sampler: sampler;
+
+// @params
+struct Params {
+    specular_strength: f32, // range(0.0, 4.0) default(1.0)
+    tint: vec3f,            // default(1.0, 1.0, 1.0)
+    use_tint: u32,          // default(0)
+}
+
+@group(1) @binding(5)
+var<uniform> params: Params;
+
+// the finest cascade covering a world position, or cascade_count when none of them do
+fn shadow_cascade(position: vec3f) -> u32 {
+    for (var i = 0u; i < shadows.cascade_count; i++) {
+        let clip = shadows.cascades[i] * vec4f(position, 1.0);
+        // a little way in from the edges, so the 2x2 filter stays inside the cascade
+        if all(abs(clip.xy) < vec2f(0.99)) && clip.z > 0.0 && clip.z < 1.0 {
+            return i;
+        }
+    }
+    return shadows.cascade_count;
+}
+
+// the nearest texel's comparison, with no filtering at all
+fn shadow_hard(maps: texture_depth_2d_array, uv: vec2f, layer: u32, reference: f32) -> f32 {
+    let size = vec2i(textureDimensions(maps));
+    let texel = clamp(vec2i(uv * vec2f(size)), vec2i(0), size - 1);
+    return select(0.0, 1.0, reference <= textureLoad(maps, texel, layer, 0));
+}
+
+// the average of a (2 * radius + 1)^2 grid of comparisons `spacing` texels apart. each one is
+// already a bilinear 2x2, so spacings up to a texel stay smooth
+fn shadow_pcf(
+    maps: texture_depth_2d_array,
+    uv: vec2f,
+    layer: u32,
+    reference: f32,
+    radius: i32,
+    spacing: f32,
+) -> f32 {
+    let texel_size = spacing / vec2f(textureDimensions(maps));
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2f(f32(x), f32(y)) * texel_size;
+            lit += textureSampleCompareLevel(maps, shadow_sampler, uv + offset, layer, reference);
+        }
+    }
+    let side = f32(2 * radius + 1);
+    return lit / (side * side);
+}
+
+// the average depth of the occluders within the search radius, or -1 when nothing is nearer
+// the light than the reference
+fn shadow_blocker_depth(
+    maps: texture_depth_2d_array,
+    uv: vec2f,
+    layer: u32,
+    reference: f32,
+) -> f32 {
+    let size = vec2i(textureDimensions(maps));
+    let center = uv * vec2f(size);
+    let spacing = shadows.search_radius / 2.0;
+    var total = 0.0;
+    var count = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let position = center + vec2f(f32(x), f32(y)) * spacing;
+            let texel = clamp(vec2i(position), vec2i(0), size - 1);
+            let depth = textureLoad(maps, texel, layer, 0);
+            if depth < reference {
+                total += depth;
+                count += 1.0;
+            }
+        }
+    }
+    if count == 0.0 {
+        return -1.0;
+    }
+    return total / count;
+}
+
+// filters a comparison against one layer with the quality the uniform asks for. pcss turns the
+// gap between the receiver and its average occluder into a penumbra width in texels by
+// multiplying by `penumbra_scale`, also dividing by the occluder's depth when the map is a
+// perspective one, since then the light's size shrinks with distance from it
+fn filtered_shadow(
+    maps: texture_depth_2d_array,
+    uv: vec2f,
+    layer: u32,
+    reference: f32,
+    penumbra_scale: f32,
+    is_perspective: bool,
+) -> f32 {
+    switch shadows.quality {
+        case 0u: {
+            return shadow_hard(maps, uv, layer, reference);
+        }
+        case 1u: {
+            return shadow_pcf(maps, uv, layer, reference, 1, 1.0);
+        }
+        case 2u: {
+            return shadow_pcf(maps, uv, layer, reference, 2, 1.0);
+        }
+        default: {
+            let blocker = shadow_blocker_depth(maps, uv, layer, reference);
+            if blocker < 0.0 {
+                return 1.0;
+            }
+            var penumbra = (reference - blocker) * penumbra_scale;
+            if is_perspective {
+                penumbra /= max(blocker, 1e-4);
+            }
+            penumbra = clamp(penumbra, 1.0, shadows.search_radius);
+            return shadow_pcf(maps, uv, layer, reference, 2, penumbra / 2.0);
+        }
+    }
+}
+
+// how much of the directional light reaches a point, from its cascaded shadow maps
+fn cascaded_shadow(position: vec3f, normal: vec3f) -> f32 {
+    if shadows.is_enabled == 0 {
+        return 1.0;
+    }
+    let cascade = shadow_cascade(position);
+    if cascade >= shadows.cascade_count {
+        return 1.0;
+    }
+
+    let offset = normal * shadows.texel_sizes[cascade] * shadows.normal_offset;
+    let clip = shadows.cascades[cascade] * vec4f(position + offset, 1.0);
+    let uv = clip.xy * vec2f(0.5, -0.5) + 0.5;
+    // a sun of angular radius a spreads a shadow by a world units per unit of depth
+    let penumbra_scale =
+        shadows.depth_ranges[cascade] * shadows.sun_size / shadows.texel_sizes[cascade];
+    return filtered_shadow(shadow_maps, uv, cascade, clip.z, penumbra_scale, false);
+}
+
+// how much of the point light reaches a point. the face is the one the direction from the light
+// points out of, and the map holds the distance to the nearest caster over the light's range
+fn point_shadow(position: vec3f, normal: vec3f) -> f32 {
+    if shadows.point_is_enabled == 0 {
+        return 1.0;
+    }
+
+    let range = shadows.point_light.w;
+    let to_point = position - shadows.point_light.xyz;
+    let distance_to_light = length(to_point);
+    if distance_to_light >= range {
+        return 1.0;
+    }
+
+    // a texel of a 90 degree face is about 2 * distance / resolution wide
+    let texel_size = 2.0 * distance_to_light / f32(textureDimensions(point_shadow_maps).x);
+    let offset_position = position + normal * texel_size * shadows.normal_offset;
+    let direction = offset_position - shadows.point_light.xyz;
+
+    let magnitude = abs(direction);
+    var face = 0u;
+    if magnitude.x >= magnitude.y && magnitude.x >= magnitude.z {
+        face = select(0u, 1u, direction.x < 0.0);
+    } else if magnitude.y >= magnitude.z {
+        face = select(2u, 3u, direction.y < 0.0);
+    } else {
+        face = select(4u, 5u, direction.z < 0.0);
+    }
+
+    let clip = shadows.point_faces[face] * vec4f(offset_position, 1.0);
+    let uv = clip.xy / clip.w * vec2f(0.5, -0.5) + 0.5;
+    let reference = length(direction) / range - shadows.point_bias;
+    // the distances are over the range, so the range cancels out of (receiver - blocker) /
+    // blocker, leaving the light's size over a texel at the receiver
+    let penumbra_scale = shadows.point_size / texel_size;
+    return filtered_shadow(point_shadow_maps, uv, face, reference, penumbra_scale, true);
+}
+
+// red, green, blue and yellow from the nearest cascade out, untinted outside all of them
+fn cascade_debug_tint(position: vec3f) -> vec3f {
+    var tints = array<vec3f, 5>(
+        vec3f(1.0, 0.5, 0.5),
+        vec3f(0.5, 1.0, 0.5),
+        vec3f(0.5, 0.5, 1.0),
+        vec3f(1.0, 1.0, 0.5),
+        vec3f(1.0),
+    );
+    return tints[min(shadow_cascade(position), 4u)];
+}
+
+// @hook visibility
+// without ray queries there is no ambient occlusion, and only the directional and point lights'
+// shadow maps for shadows. raytracing.rs replaces this block with raytraced.wgsl when the
+// adapter supports them
+fn shadow_visibility(position: vec3f, normal: vec3f, to_light: vec3f) -> f32 {
+    return cascaded_shadow(position, normal) * point_shadow(position, normal);
+}
+
+fn ambient_occlusion(position: vec3f, normal: vec3f) -> f32 {
+    return 1.0;
+}
+// @end hook
+
+#include "voxel_gi.wgsl"
+
+@group(3) @binding(0)
+var<uniform> gi: VoxelGI;
+@group(3) @binding(1)
+var radiance_volume: texture_3d<f32>;
+@group(3) @binding(2)
+var radiance_sampler: sampler;
+
+// marches a cone through the radiance volume, sampling coarser mips as it widens, and blends
+// front to back. `aperture` is the tangent of the cone's half angle. returns light and occlusion
+fn trace_cone(origin: vec3f, direction: vec3f, aperture: f32) -> vec4f {
+    let grid_size = gi.voxel_size * f32(gi.resolution);
+
+    var color = vec3f(0.0);
+    var occlusion = 0.0;
+    // start a voxel out so the surface doesn't pick up its own voxel
+    var distance = gi.voxel_size;
+
+    while distance < grid_size && occlusion < 0.95 {
+        let diameter = max(gi.voxel_size, 2.0 * aperture * distance);
+        let mip = min(log2(diameter / gi.voxel_size), f32(gi.mip_count - 1));
+        let uvw = (origin + direction * distance - gi.grid_min) / grid_size;
+        if any(uvw < vec3f(0.0)) || any(uvw > vec3f(1.0)) {
+            break;
+        }
+
+        let voxel = textureSampleLevel(radiance_volume, radiance_sampler, uvw, mip);
+        color += (1.0 - occlusion) * voxel.rgb;
+        occlusion += (1.0 - occlusion) * voxel.a;
+        distance += diameter * 0.5;
+    }
+
+    return vec4f(color, occlusion);
+}
+
+// six 60 degree cones covering the hemisphere, weighted towards the normal
+fn indirect_diffuse(position: vec3f, normal: vec3f) -> vec3f {
+    let helper = select(vec3f(0.0, 0.0, 1.0), vec3f(1.0, 0.0, 0.0), abs(normal.z) > 0.9);
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+    let aperture = 0.577; // tan(30 degrees)
+
+    var light = trace_cone(position, normal, aperture).rgb * 0.25;
+    for (var i = 0; i < 5; i++) {
+        let angle = f32(i) * 1.2566371; // 2 pi / 5
+        let side = tangent * cos(angle) + bitangent * sin(angle);
+        let direction = normalize(normal * 0.5 + side * 0.866);
+        light += trace_cone(position, direction, aperture).rgb * 0.15;
+    }
+    return light;
+}
+
+// one cone along the reflection, narrower for shinier materials
+fn indirect_specular(position: vec3f, normal: vec3f, view: vec3f, shininess: f32) -> vec3f {
+    let aperture = clamp(sqrt(2.0 / (shininess + 2.0)), 0.03, 1.0);
+    return trace_cone(position, reflect(-view, normal), aperture).rgb;
+}
+
+// the environment's light reflected towards the viewer, diffuse from the irradiance and specular
+// from the split sum: the prefiltered radiance at the material's roughness, scaled and biased
+// by the brdf lookup table
+fn environment_light(
+    normal: vec3f,
+    view: vec3f,
+    diffuse_color: vec3f,
+    specular_color: vec3f,
+    shininess: f32,
+) -> vec3f {
+    // back from the blinn phong exponent to a perceptual roughness
+    let roughness = sqrt(sqrt(2.0 / (shininess + 2.0)));
+    let n_dot_v = max(dot(normal, view), 0.0);
+
+    let irradiance = textureSampleLevel(irradiance_map, environment_sampler, normal, 0.0).rgb;
+    let level = roughness * f32(environment.specular_mip_count - 1u);
+    let reflection = reflect(-view, normal);
+    let radiance = textureSampleLevel(specular_map, environment_sampler, reflection, level).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2f(n_dot_v, roughness), 0.0).rg;
+
+    let specular = radiance * (specular_color * brdf.x + brdf.y) * params.specular_strength;
+    return (irradiance * diffuse_color + specular) * environment.intensity;
+}
+
+// parallax occlusion mapping: marches the tangent space view ray down through the height map
+// in equal layers until it ends up below the surface, then interpolates between the last two
+// layers for where it crossed. returns the uv the ray hit the surface at
+fn parallax_occlusion(tex_coords: vec2f, view: vec3f, scale: f32, steps: u32) -> vec2f {
+    // the loop isn't uniform control flow, so the derivatives are taken outside it
+    let dx = dpdx(tex_coords);
+    let dy = dpdy(tex_coords);
+
+    let layer_depth = 1.0 / f32(steps);
+    // clamped so grazing views don't shift across the whole map
+    let shift = view.xy / max(view.z, 0.1) * scale * layer_depth;
+
+    var uv = tex_coords;
+    var depth = 0.0;
+    var surface_depth = 1.0 - textureSampleGrad(height_texture, height_sampler, uv, dx, dy).x;
+    for (var i = 0u; i < steps && depth < surface_depth; i++) {
+        uv -= shift;
+        depth += layer_depth;
+        surface_depth = 1.0 - textureSampleGrad(height_texture, height_sampler, uv, dx, dy).x;
+    }
+
+    let previous_uv = uv + shift;
+    let after = surface_depth - depth;
+    let before = 1.0 - textureSampleGrad(height_texture, height_sampler, previous_uv, dx, dy).x
+        - (depth - layer_depth);
+    let denominator = after - before;
+    if abs(denominator) < 1e-5 {
+        return uv;
+    }
+    return mix(uv, previous_uv, after / denominator);
+}
+
+// blue through green to red as t goes from 0 to 1
+fn heat(t: f32) -> vec3f {
+    let x = clamp(t, 0.0, 1.0);
+    return clamp(vec3f(2.0 * x - 1.0, 1.0 - abs(2.0 * x - 1.0), 1.0 - 2.0 * x), vec3f(0.0), vec3f(1.0));
+}
+
+// what the debug views show in place of the shading
+fn debug_view_color(in: VertexOutput, tex_coords: vec2f) -> vec3f {
+    switch time.debug_view {
+        case 1u: {
+            return normalize(in.world_normal) * 0.5 + 0.5;
+        }
+        case 2u: {
+            return vec3f(fract(tex_coords), 0.0);
+        }
+        case 3u: {
+            return normalize(in.world_tangent) * 0.5 + 0.5;
+        }
+        case 4u: {
+            // how many texels a pixel covers, the same way the hardware picks the level
+            let texels = tex_coords * diffuse_size();
+            let dx = dpdx(texels);
+            let dy = dpdy(texels);
+            let level = max(0.5 * log2(max(dot(dx, dx), dot(dy, dy))), 0.0);
+            return heat(level / 8.0);
+        }
+        case 5u: {
+            let distance = length(camera.view_pos.xyz - in.world_position);
+            return vec3f(1.0 - log2(1.0 + distance) / log2(1.0 + 1000.0));
+        }
+        default: {
+            return vec3f(1.0, 0.0, 1.0);
+        }
+    }
+}
+
+// every surface adds a little, so the more of them cover a pixel the brighter it gets
+@fragment
+fn fragment_overdraw(in: VertexOutput) -> @location(0) vec4f {
+    return vec4f(0.1, 0.04, 0.01, 1.0);
+}
 
 @fragment
 fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
+    let material = current_material();
+
+    let TBN = transpose(mat3x3f(
+        normalize(in.world_tangent), 
+        normalize(in.world_bitangent), 
+        normalize(in.world_normal)
+    ));
+
+    var tex_coords = in.tex_coords * material.uv_scale + material.uv_offset;
+    if material.has_height_texture == 1 {
+        let tangent_view = normalize(TBN * (camera.view_pos.xyz - in.world_position));
+        tex_coords = parallax_occlusion(
+            tex_coords,
+            tangent_view,
+            material.height_scale,
+            material.parallax_steps,
+        );
+    }
+
+    if time.debug_view != 0 {
+        return vec4f(debug_view_color(in, tex_coords), 1.0);
+    }
 
     var material_diffuse_color: vec3f;
 
     if material.has_diffuse_texture == 1 {
-        material_diffuse_color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords).xyz;
+        material_diffuse_color = sample_diffuse(tex_coords).xyz;
     } else {
         material_diffuse_color = material.diffuse_color;
     }
+    material_diffuse_color *= in.color;
 
 
     var material_normal: vec3f;
 
     if material.has_normal_texture == 1 {
-        material_normal = textureSample(normal_texture, normal_sampler, in.tex_coords).xyz * 2.0 - 1;
+        material_normal = sample_normal(tex_coords).xyz * 2.0 - 1;
+        if material.flip_normal_y == 1 {
+            material_normal.y = -material_normal.y;
+        }
+        material_normal = vec3f(material_normal.xy * material.bump_multiplier, material_normal.z);
     } else {
         material_normal = vec3f(0.0, 0.0, 1.0);
     }
 
-    let TBN = transpose(mat3x3f(
-        normalize(in.world_tangent), 
-        normalize(in.world_bitangent), 
-        normalize(in.world_normal)
-    ));
+    var material_specular_color = material.specular_color;
+    if material.has_specular_texture == 1 {
+        material_specular_color *= textureSample(specular_texture, specular_sampler, tex_coords).xyz;
+    }
+
+    // map_Ns is stored normalized, scale it back up by the material's Ns
+    var material_shininess = material.shininess;
+    if material.has_shininess_texture == 1 {
+        material_shininess *= textureSample(shininess_texture, shininess_sampler, tex_coords).x;
+    }
+
+    // Pr overrides Ns, converted with the usual ggx alpha -> blinn phong exponent mapping
+    if material.use_roughness == 1 {
+        var roughness = material.roughness;
+        if material.has_roughness_texture == 1 {
+            roughness *= textureSample(roughness_texture, roughness_sampler, tex_coords).x;
+        }
+        roughness = clamp(roughness, 0.02, 1.0);
+        let alpha = roughness * roughness;
+        material_shininess = 2.0 / (alpha * alpha) - 2.0;
+    }
+    material_shininess = max(material_shininess, 1.0);
+
+    // rain pools on whatever faces up: wet surfaces are darker, since less light scatters back
+    // out of them, and glossier, with a film of water over them
+    let wetness = environment.wetness * smoothstep(0.0, 0.7, normalize(in.world_normal).y);
+    material_diffuse_color *= 1.0 - 0.5 * wetness;
+    material_shininess = mix(material_shininess, max(material_shininess, 512.0), wetness);
+    material_specular_color = max(material_specular_color, vec3f(0.3 * wetness));
+
+    var material_metallic = material.metallic;
+    if material.has_metallic_texture == 1 {
+        material_metallic *= textureSample(metallic_texture, metallic_sampler, tex_coords).x;
+    }
+    // metals have no diffuse term and tint their highlights with the base color
+    material_specular_color = mix(material_specular_color, material_diffuse_color, material_metallic);
+    material_diffuse_color *= 1.0 - material_metallic;
+
+    var material_alpha = material.dissolve;
+    if material.has_alpha_texture == 1 {
+        material_alpha *= textureSample(alpha_texture, alpha_sampler, tex_coords).x;
+    }
+
+    var material_emissive = material.emissive_color;
+    if material.has_emissive_texture == 1 {
+        material_emissive *= textureSample(emissive_texture, emissive_sampler, tex_coords).xyz;
+    }
 
     let light_dir_world = light.position - in.world_position;
     let view_dir_world  = camera.view_pos.xyz - in.world_position;
@@ -164,14 +695,18 @@ fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
     // let reflect_direction = normalize(reflect(-light_direction, view_direction));
 
 
-    let diffuse_strength = max(dot(normal, light_direction), 0.0);
+    let geometric_normal = normalize(in.world_normal);
+    let shadow = shadow_visibility(in.world_position, geometric_normal, light_dir_world);
+    let occlusion = ambient_occlusion(in.world_position, geometric_normal);
+
+    let diffuse_strength = max(dot(normal, light_direction), 0.0) * shadow;
     let light_diffuse = light.diffuse_color * diffuse_strength;
 
     // let reflect_direction = reflect(-light_direction, normal);
-    // let specular_exponent = ((sin(f32(time.millis) / 1000.0) + 1.0) * 0.5) * 256.0 + 1.0;
+    // let specular_exponent = ((time.sin_time + 1.0) * 0.5) * 256.0 + 1.0;
     // let specular_strength = pow(max(dot(view_direction, reflect_direction), 0.0), 128.0); // just phong
-    let specular_strength = pow(max(dot(normal, half_direction), 0.0), 64.0) * diffuse_strength; // blinn phong
-    let light_specular = light.specular_color * specular_strength;
+    let specular_strength = pow(max(dot(normal, half_direction), 0.0), material_shininess) * diffuse_strength; // blinn phong
+    let light_specular = light.specular_color * material_specular_color * specular_strength * params.specular_strength;
     // let specular_strength = 0.0;
 
     let angle = (dot(normal, half_direction) + 1.0) * 0.5;
@@ -186,7 +721,28 @@ fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
 
     // let output_color = vec3f(angle, 0.0, 1.0-angle);
 
-    let output_color = (light.ambient_color + light_diffuse + light_specular) * material_diffuse_color;
+    // a cheap sheen: a grazing angle rim in the diffuse color
+    let sheen = material.sheen * pow(1.0 - max(dot(normal, view_direction), 0.0), 5.0) * diffuse_strength;
+
+    // the flat ambient color, or the environment's light in its place
+    var ambient = light.ambient_color * occlusion * material_diffuse_color;
+    if environment.is_enabled == 1 {
+        // TBN is the world -> tangent matrix, so its transpose takes the normal back to world space
+        let world_normal = normalize(transpose(TBN) * normal);
+        ambient = environment_light(
+            world_normal,
+            normalize(view_dir_world),
+            material_diffuse_color,
+            material_specular_color,
+            material_shininess,
+        ) * occlusion;
+    }
+
+    var output_color = ambient + (light_diffuse + sheen) * material_diffuse_color + light_specular;
+
+    if params.use_tint == 1 {
+        output_color *= params.tint;
+    }
     // let output_color = (light_diffuse) * material_diffuse_color;
     // var output_color = vec3f(specular_strength, 0.0, 1.0 - specular_strength);
 
@@ -198,5 +754,26 @@ fn fragment_main(in: VertexOutput) -> @location(0) vec4f {
     //     output_color = vec3f(0.0, 1.0, 0.0);
     // }
 
-    return vec4f(output_color, 1.0);
+    if gi.mode != 0 {
+        // TBN is the world -> tangent matrix, so its transpose takes the normal back to world space
+        let world_normal = normalize(transpose(TBN) * normal);
+        let origin = in.world_position + world_normal * gi.voxel_size;
+        let indirect = indirect_diffuse(origin, world_normal) * material_diffuse_color
+            + indirect_specular(origin, world_normal, normalize(view_dir_world), material_shininess)
+                * material_specular_color * params.specular_strength;
+
+        if gi.mode == 2 {
+            output_color = indirect;
+        } else {
+            output_color += indirect;
+        }
+    }
+
+    output_color += material_emissive;
+
+    if shadows.debug_cascades == 1 {
+        output_color *= cascade_debug_tint(in.world_position);
+    }
+
+    return vec4f(output_color, material_alpha);
 }
@@ -0,0 +1,43 @@
+// the scene seen from a light, through one shadow cascade or point light cube face. the
+// cascades only need depth, but the cube faces store the distance to the light over its range,
+// since that's the same whichever face a point lands on. see shadows.rs
+
+struct ShadowView {
+    view_proj: mat4x4f,
+    // the point light's position and range in w
+    light: vec4f,
+}
+
+@group(0) @binding(0)
+var<uniform> shadow_view: ShadowView;
+
+#include "model_transformation.wgsl"
+
+@group(1) @binding(0)
+var<uniform> model_transformation: ModelTransformation;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) world_position: vec3f,
+}
+
+@vertex
+fn vertex_main(@location(0) position: vec3f) -> VertexOutput {
+    let model_transformation_matrix = mat4x4(
+        model_transformation.model_transform_col0,
+        model_transformation.model_transform_col1,
+        model_transformation.model_transform_col2,
+        model_transformation.model_transform_col3
+    );
+    let world_position = model_transformation_matrix * vec4f(position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = shadow_view.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+@fragment
+fn fragment_distance(in: VertexOutput) -> @builtin(frag_depth) f32 {
+    return saturate(distance(in.world_position, shadow_view.light.xyz) / shadow_view.light.w);
+}
@@ -0,0 +1,44 @@
+// combines the two eyes of stereo output, see stereo.rs
+
+struct StereoUniform {
+    // 0 for a red/cyan anaglyph, 1 for half width side by side
+    mode: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: StereoUniform;
+@group(0) @binding(1)
+var left_eye: texture_2d<f32>;
+@group(0) @binding(2)
+var right_eye: texture_2d<f32>;
+@group(0) @binding(3)
+var eye_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// a triangle twice the size of the screen, so the screen is exactly the part where uv is 0..1
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(out.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// explicit lod sampling, since which eye is sampled depends on the pixel
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if settings.mode == 0u {
+        let left = textureSampleLevel(left_eye, eye_sampler, in.uv, 0.0);
+        let right = textureSampleLevel(right_eye, eye_sampler, in.uv, 0.0);
+        return vec4<f32>(left.r, right.g, right.b, max(left.a, right.a));
+    }
+
+    if in.uv.x < 0.5 {
+        return textureSampleLevel(left_eye, eye_sampler, vec2<f32>(in.uv.x * 2.0, in.uv.y), 0.0);
+    }
+    return textureSampleLevel(right_eye, eye_sampler, vec2<f32>(in.uv.x * 2.0 - 1.0, in.uv.y), 0.0);
+}
@@ -0,0 +1,91 @@
+// lights the voxelized scene: every occupied voxel gets its albedo times the direct light
+// reaching it, with shadows found by marching the occupancy volume towards each light
+
+#include "lights.wgsl"
+#include "voxel_gi.wgsl"
+
+@group(0) @binding(1)
+var<storage, read> lights: array<Light>;
+@group(0) @binding(2)
+var<uniform> light_metadata: LightMetadata;
+
+@group(1) @binding(0)
+var<uniform> gi: VoxelGI;
+@group(1) @binding(1)
+var albedo_volume: texture_3d<f32>;
+@group(1) @binding(2)
+var normal_volume: texture_3d<f32>;
+@group(1) @binding(3)
+var radiance_volume: texture_storage_3d<rgba16float, write>;
+
+fn is_occupied(position: vec3f) -> bool {
+    let voxel = vec3i(floor((position - gi.grid_min) / gi.voxel_size));
+    if any(voxel < vec3i(0)) || any(voxel >= vec3i(i32(gi.resolution))) {
+        return false;
+    }
+    return textureLoad(albedo_volume, voxel, 0).a > 0.0;
+}
+
+// 1.0 if nothing blocks the way from origin to the light, otherwise 0.0
+fn visibility(origin: vec3f, direction: vec3f, max_distance: f32) -> f32 {
+    let grid_size = gi.voxel_size * f32(gi.resolution);
+    let distance_limit = min(max_distance, grid_size * sqrt(3.0));
+
+    // start a little way out so the voxel doesn't shadow itself
+    var distance = gi.voxel_size * 1.5;
+    while distance < distance_limit {
+        if is_occupied(origin + direction * distance) {
+            return 0.0;
+        }
+        distance += gi.voxel_size * 0.5;
+    }
+    return 1.0;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    if any(id >= vec3u(gi.resolution)) {
+        return;
+    }
+
+    let albedo = textureLoad(albedo_volume, id, 0);
+    if albedo.a == 0.0 {
+        textureStore(radiance_volume, id, vec4f(0.0));
+        return;
+    }
+
+    let normal = normalize(textureLoad(normal_volume, id, 0).xyz);
+    let position = gi.grid_min + (vec3f(id) + 0.5) * gi.voxel_size;
+
+    var radiance = vec3f(0.0);
+
+    for (var i = 0u; i < light_metadata.point_light_count; i++) {
+        let light = lights[light_metadata.point_light_offset + i];
+        let to_light = light.position - position;
+        let distance = length(to_light);
+        let direction = to_light / distance;
+        let n_dot_l = max(dot(normal, direction), 0.0);
+        radiance += light.color * n_dot_l * visibility(position, direction, distance);
+    }
+
+    for (var i = 0u; i < light_metadata.directional_light_count; i++) {
+        let light = lights[light_metadata.directional_light_offset + i];
+        let direction = -normalize(light.direction);
+        let n_dot_l = max(dot(normal, direction), 0.0);
+        radiance += light.color * n_dot_l * visibility(position, direction, 1e9);
+    }
+
+    for (var i = 0u; i < light_metadata.spot_light_count; i++) {
+        let light = lights[light_metadata.spot_light_offset + i];
+        let to_light = light.position - position;
+        let distance = length(to_light);
+        let direction = to_light / distance;
+        // params.x and params.y are the cosines of the inner and outer cone angles
+        let cone = smoothstep(light.params.y, light.params.x, dot(-direction, normalize(light.direction)));
+        let n_dot_l = max(dot(normal, direction), 0.0);
+        radiance += light.color * n_dot_l * cone * visibility(position, direction, distance);
+    }
+
+    // alpha is coverage, which the mips average into opacity for the cone tracing
+    textureStore(radiance_volume, id, vec4f(albedo.rgb * radiance, 1.0));
+}
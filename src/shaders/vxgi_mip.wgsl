@@ -0,0 +1,21 @@
+// builds one mip level of the radiance volume by averaging 2x2x2 blocks of the level above
+
+@group(0) @binding(0)
+var source: texture_3d<f32>;
+@group(0) @binding(1)
+var destination: texture_storage_3d<rgba16float, write>;
+
+@compute @workgroup_size(4, 4, 4)
+fn compute_main(@builtin(global_invocation_id) id: vec3u) {
+    if any(id >= textureDimensions(destination)) {
+        return;
+    }
+
+    var sum = vec4f(0.0);
+    for (var i = 0u; i < 8u; i++) {
+        let offset = vec3u(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u);
+        sum += textureLoad(source, id * 2u + offset, 0);
+    }
+
+    textureStore(destination, id, sum / 8.0);
+}
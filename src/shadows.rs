@@ -0,0 +1,603 @@
+// cascaded shadow maps for the first directional light, and a cube of shadow maps for the
+// first point light
+//
+// the view frustum is split into CASCADE_COUNT slices, nearer ones thinner, and each slice gets
+// an orthographic view from the light fitted around it, rendered into its own layer of a depth
+// texture array. the scene shader picks the finest cascade covering a fragment and compares
+// against it, so nearby shadows get most of the resolution while distant ones are still there.
+// the point light renders the six faces of a cube around itself, storing the distance to the
+// light rather than the depth, which the shader compares the fragment's own distance against
+
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Zero};
+
+use crate::{
+    DirectionalLight, PointLight, camera, light_volumes, model, panorama,
+    render_stats::{CountingRenderPass, RenderStats},
+    scene, texture,
+    uploads::UploadBelt,
+};
+
+pub const CASCADE_COUNT: usize = 4;
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+// how far from the camera shadows are drawn, since the far plane can be at infinity
+pub const DEFAULT_SHADOW_DISTANCE: f32 = 100.0;
+// 0 splits the distance evenly, 1 logarithmically. in between keeps the near cascades small
+// without leaving the far ones huge
+const SPLIT_BLEND: f32 = 0.75;
+// how far behind each cascade the light's view starts, so things outside the slice can still
+// cast shadows into it
+const CASTER_DISTANCE: f32 = 50.0;
+// how far each point is pushed along its normal before the lookup, in texels of its cascade
+const NORMAL_OFFSET: f32 = 1.5;
+pub const POINT_SHADOW_MAP_SIZE: u32 = 1024;
+// the cube faces' near plane; anything closer to the light than this doesn't cast a shadow
+const POINT_SHADOW_NEAR: f32 = 0.05;
+// taken off the fragment's distance to the light before comparing, as a fraction of the
+// light's range
+pub const DEFAULT_POINT_BIAS: f32 = 0.005;
+// the sun's angular radius, as its tangent, and the point light's radius in world units. the
+// bigger the light, the wider the penumbra pcss gives a shadow
+pub const DEFAULT_SUN_SIZE: f32 = 0.02;
+pub const DEFAULT_POINT_SIZE: f32 = 0.1;
+// how far pcss looks for occluders around a point, and so the widest penumbra, in texels
+const PCSS_SEARCH_RADIUS: f32 = 8.0;
+// the cascades' views first, then the point light's cube faces in the order of panorama::FACES
+const VIEW_COUNT: usize = CASCADE_COUNT + panorama::FACES.len();
+
+// how shadow edges are filtered, cycled with J
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShadowQuality {
+    // one texel, so edges are aliased
+    Hard,
+    Pcf3x3,
+    Pcf5x5,
+    // percentage closer soft shadows: the kernel grows with the distance from the occluder, so
+    // contact shadows stay sharp and long ones soften
+    Pcss,
+}
+
+impl ShadowQuality {
+    pub fn next(self) -> Self {
+        match self {
+            ShadowQuality::Hard => ShadowQuality::Pcf3x3,
+            ShadowQuality::Pcf3x3 => ShadowQuality::Pcf5x5,
+            ShadowQuality::Pcf5x5 => ShadowQuality::Pcss,
+            ShadowQuality::Pcss => ShadowQuality::Hard,
+        }
+    }
+
+    // as it's written in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadowQuality::Hard => "hard",
+            ShadowQuality::Pcf3x3 => "pcf3x3",
+            ShadowQuality::Pcf5x5 => "pcf5x5",
+            ShadowQuality::Pcss => "pcss",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ShadowQuality::Hard,
+            ShadowQuality::Pcf3x3,
+            ShadowQuality::Pcf5x5,
+            ShadowQuality::Pcss,
+        ]
+        .into_iter()
+        .find(|quality| quality.name() == name)
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            ShadowQuality::Hard => "[HARD SHADOWS]",
+            ShadowQuality::Pcf3x3 => "",
+            ShadowQuality::Pcf5x5 => "[PCF 5X5]",
+            ShadowQuality::Pcss => "[PCSS]",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    cascades: [[[f32; 4]; 4]; CASCADE_COUNT],
+    // the world space size of a texel in each cascade
+    texel_sizes: [f32; CASCADE_COUNT],
+    // the world space distance between each cascade's near and far planes
+    depth_ranges: [f32; CASCADE_COUNT],
+    cascade_count: u32,
+    is_enabled: u32,
+    debug_cascades: u32,
+    normal_offset: f32,
+    point_faces: [[[f32; 4]; 4]; 6],
+    // the point light's position, and its range in w
+    point_light: [f32; 4],
+    point_is_enabled: u32,
+    point_bias: f32,
+    // 0 hard, 1 pcf 3x3, 2 pcf 5x5, 3 pcss
+    quality: u32,
+    search_radius: f32,
+    sun_size: f32,
+    point_size: f32,
+    _padding: [u32; 2],
+}
+
+// what one shadow pass renders from, at its own dynamic offset
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowView {
+    view_proj: [[f32; 4]; 4],
+    // the light's position and range for the cube faces, which store distances. unused by the
+    // cascades, which store depth
+    light: [f32; 4],
+}
+
+pub struct CascadedShadows {
+    // tints each cascade its own color in the scene
+    pub is_debug_visible: bool,
+    quality: ShadowQuality,
+    distance: f32,
+    uniform: ShadowUniform,
+    // read by the scene shader through the per frame bind group
+    pub uniform_buffer: wgpu::Buffer,
+    pub depth_texture: texture::Texture,
+    layer_views: Vec<wgpu::TextureView>,
+    // the cube's faces as a plain array rather than a cube, since the shader projects through
+    // the same matrices the faces were rendered with instead of relying on cube conventions
+    pub point_texture: texture::Texture,
+    point_face_views: Vec<wgpu::TextureView>,
+    // every pass's ShadowView, at a dynamic offset per pass
+    view_buffer: wgpu::Buffer,
+    view_stride: u32,
+    view_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    point_pipeline: wgpu::RenderPipeline,
+}
+
+impl CascadedShadows {
+    pub fn new(device: &wgpu::Device, per_object_layout: &wgpu::BindGroupLayout) -> Self {
+        let uniform = ShadowUniform {
+            cascades: [Matrix4::identity().into(); CASCADE_COUNT],
+            texel_sizes: [0.0; CASCADE_COUNT],
+            depth_ranges: [0.0; CASCADE_COUNT],
+            cascade_count: CASCADE_COUNT as u32,
+            is_enabled: 0,
+            debug_cascades: 0,
+            normal_offset: NORMAL_OFFSET,
+            point_faces: [Matrix4::identity().into(); 6],
+            point_light: [0.0; 4],
+            point_is_enabled: 0,
+            point_bias: DEFAULT_POINT_BIAS,
+            quality: 1,
+            search_radius: PCSS_SEARCH_RADIUS,
+            sun_size: DEFAULT_SUN_SIZE,
+            point_size: DEFAULT_POINT_SIZE,
+            _padding: [0; 2],
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("shadow uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let depth_texture =
+            texture::Texture::builder("shadow maps", SHADOW_MAP_SIZE, SHADOW_MAP_SIZE)
+                .format(texture::Texture::SHADOW_DEPTH_FORMAT)
+                .usage(
+                    wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                )
+                .array_layers(CASCADE_COUNT as u32)
+                .sampler(wgpu::SamplerDescriptor {
+                    label: Some("shadow sampler"),
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    // linear filtering of a comparison is a free 2x2 pcf
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+                    compare: Some(wgpu::CompareFunction::LessEqual),
+                    ..Default::default()
+                })
+                .build(device);
+        let layer_views = (0..CASCADE_COUNT as u32)
+            .map(|layer| depth_texture.layer_view(layer))
+            .collect();
+
+        let point_texture = texture::Texture::builder(
+            "point shadow map",
+            POINT_SHADOW_MAP_SIZE,
+            POINT_SHADOW_MAP_SIZE,
+        )
+        .format(texture::Texture::SHADOW_DEPTH_FORMAT)
+        .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        .array_layers(panorama::FACES.len() as u32)
+        .build(device);
+        let point_face_views = (0..panorama::FACES.len() as u32)
+            .map(|face| point_texture.layer_view(face))
+            .collect();
+
+        let view_size = std::mem::size_of::<ShadowView>() as u32;
+        let view_stride =
+            view_size.next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow view buffer"),
+            size: (view_stride as usize * VIEW_COUNT) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let view_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shadow view bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(view_size as u64),
+                },
+                count: None,
+            }],
+        });
+        let view_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow view bind group"),
+            layout: &view_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &view_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(view_size as u64),
+                }),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shadow pipeline layout"),
+            bind_group_layouts: &[&view_layout, per_object_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(crate::shaders::wgsl(
+            "shadow_depth.wgsl",
+            include_str!("shaders/shadow_depth.wgsl"),
+        ));
+        let create_pipeline = |label, fragment_entry_point: Option<&str>, bias| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vertex_main"),
+                    buffers: &[<model::ModelVertex as model::Vertex>::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: fragment_entry_point.map(|entry_point| wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    // both faces, so single sided surfaces like planes still cast shadows
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::SHADOW_DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    // the shadow maps always use standard depth, whatever the scene's depth mode
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias,
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+        // depth only, biased to keep surfaces from shadowing themselves where they face away
+        // from the light
+        let pipeline = create_pipeline(
+            "shadow pipeline",
+            None,
+            wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        );
+        // the fragment shader writes the distance, which rasterizer bias doesn't apply to, so
+        // the point shadows are biased when they're compared instead
+        let point_pipeline = create_pipeline(
+            "point shadow pipeline",
+            Some("fragment_distance"),
+            wgpu::DepthBiasState::default(),
+        );
+
+        Self {
+            is_debug_visible: false,
+            quality: ShadowQuality::Pcf3x3,
+            distance: DEFAULT_SHADOW_DISTANCE,
+            uniform,
+            uniform_buffer,
+            depth_texture,
+            layer_views,
+            point_texture,
+            point_face_views,
+            view_buffer,
+            view_stride,
+            view_bind_group,
+            pipeline,
+            point_pipeline,
+        }
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.max(1.0);
+    }
+
+    // as a fraction of the point light's range
+    pub fn set_point_bias(&mut self, bias: f32) {
+        self.uniform.point_bias = bias.max(0.0);
+    }
+
+    pub fn quality(&self) -> ShadowQuality {
+        self.quality
+    }
+
+    pub fn set_quality(&mut self, quality: ShadowQuality) {
+        self.quality = quality;
+        self.uniform.quality = match quality {
+            ShadowQuality::Hard => 0,
+            ShadowQuality::Pcf3x3 => 1,
+            ShadowQuality::Pcf5x5 => 2,
+            ShadowQuality::Pcss => 3,
+        };
+    }
+
+    // the tangent of the sun's angular radius, for pcss
+    pub fn set_sun_size(&mut self, size: f32) {
+        self.uniform.sun_size = size.max(0.0);
+    }
+
+    // the point light's radius in world units, for pcss
+    pub fn set_point_size(&mut self, size: f32) {
+        self.uniform.point_size = size.max(0.0);
+    }
+
+    // fits the cascades to the camera's frustum and the cube faces around the point light.
+    // either is turned off when there's no light of its kind to cast it
+    pub fn update(
+        &mut self,
+        uploads: &mut UploadBelt,
+        camera: &camera::Camera,
+        projection: &camera::Projection,
+        directional_light: Option<&DirectionalLight>,
+        point_light: Option<&PointLight>,
+    ) {
+        self.uniform.debug_cascades = self.is_debug_visible as u32;
+        self.uniform.is_enabled = directional_light.is_some() as u32;
+        self.uniform.point_is_enabled = point_light.is_some() as u32;
+
+        let mut views = [ShadowView {
+            view_proj: Matrix4::identity().into(),
+            light: [0.0; 4],
+        }; VIEW_COUNT];
+
+        if let Some(light) = directional_light {
+            let direction = light.transform.forward().normalize();
+            let splits = split_distances(projection.z_plane_near(), self.distance);
+
+            for (i, view) in views[..CASCADE_COUNT].iter_mut().enumerate() {
+                let corners = projection.frustum_corners(camera, splits[i], splits[i + 1]);
+                let (matrix, texel_size) = fit_cascade(&corners, direction);
+                self.uniform.cascades[i] = matrix.into();
+                self.uniform.texel_sizes[i] = texel_size;
+                // fit_cascade covers the slice's diameter plus the casters behind it
+                self.uniform.depth_ranges[i] =
+                    texel_size * SHADOW_MAP_SIZE as f32 + CASTER_DISTANCE;
+                view.view_proj = self.uniform.cascades[i];
+            }
+        }
+
+        if let Some(light) = point_light {
+            let position = light.transform.translation();
+            let brightness = light.color.into_iter().fold(0.0, f32::max) * light.intensity;
+            // out to where the light stops counting, so everything it lights can be shadowed
+            let range = light_volumes::effective_radius(brightness).max(POINT_SHADOW_NEAR * 2.0);
+            let face_projection = camera::OPENGL_TO_WGPU_MATRIX
+                * cgmath::perspective(Deg(90.0), 1.0, POINT_SHADOW_NEAR, range);
+
+            self.uniform.point_light = position.extend(range).into();
+            for (face, (forward, up)) in panorama::FACES.into_iter().enumerate() {
+                let matrix = face_projection
+                    * Matrix4::look_to_rh(Point3::from_vec(position), forward.into(), up.into());
+                self.uniform.point_faces[face] = matrix.into();
+                views[CASCADE_COUNT + face] = ShadowView {
+                    view_proj: matrix.into(),
+                    light: self.uniform.point_light,
+                };
+            }
+        }
+
+        if directional_light.is_some() || point_light.is_some() {
+            let mut data = vec![0u8; self.view_stride as usize * VIEW_COUNT];
+            for (slot, view) in data.chunks_exact_mut(self.view_stride as usize).zip(&views) {
+                slot[..std::mem::size_of::<ShadowView>()].copy_from_slice(bytemuck::bytes_of(view));
+            }
+            uploads.write(&self.view_buffer, 0, &data);
+        }
+
+        uploads.write(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // renders every opaque mesh into each cascade and cube face whose light is there. the
+    // scene's transforms have to be uploaded first
+    pub fn encode(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        stats: &mut RenderStats,
+        scene: &scene::Scene,
+        materials: &[model::Material],
+    ) {
+        if self.uniform.is_enabled == 1 {
+            command_encoder.push_debug_group("cascaded shadow maps");
+            for (cascade, view) in self.layer_views.iter().enumerate() {
+                self.encode_pass(
+                    command_encoder,
+                    stats,
+                    scene,
+                    materials,
+                    &format!("cascade {}", cascade),
+                    view,
+                    &self.pipeline,
+                    cascade,
+                );
+            }
+            command_encoder.pop_debug_group();
+        }
+
+        if self.uniform.point_is_enabled == 1 {
+            command_encoder.push_debug_group("point shadow map");
+            for (face, view) in self.point_face_views.iter().enumerate() {
+                self.encode_pass(
+                    command_encoder,
+                    stats,
+                    scene,
+                    materials,
+                    &format!("face {}", face),
+                    view,
+                    &self.point_pipeline,
+                    CASCADE_COUNT + face,
+                );
+            }
+            command_encoder.pop_debug_group();
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn encode_pass(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        stats: &mut RenderStats,
+        scene: &scene::Scene,
+        materials: &[model::Material],
+        label: &str,
+        target: &wgpu::TextureView,
+        pipeline: &wgpu::RenderPipeline,
+        view_index: usize,
+    ) {
+        let render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        let mut render_pass = CountingRenderPass::new(render_pass, stats);
+
+        render_pass.push_debug_group(label);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(
+            0,
+            &self.view_bind_group,
+            &[view_index as u32 * self.view_stride],
+        );
+        for (i, object) in scene.objects().iter().enumerate() {
+            render_pass.set_bind_group(1, &scene.bind_group, &[scene.transform_offset(i)]);
+            for mesh in &object.model.meshes {
+                if !mesh.is_visible || materials[mesh.material].is_transparent() {
+                    continue;
+                }
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+                render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+            }
+        }
+        render_pass.pop_debug_group();
+    }
+
+    pub fn describe(&self) -> String {
+        let debug = if self.is_debug_visible {
+            "[CASCADES]"
+        } else {
+            ""
+        };
+        format!("{}{}", debug, self.quality.describe())
+    }
+}
+
+// the view distances the cascades start and end at, from the near plane out to `distance`
+fn split_distances(near: f32, distance: f32) -> [f32; CASCADE_COUNT + 1] {
+    let mut splits = [near; CASCADE_COUNT + 1];
+    for (i, split) in splits.iter_mut().enumerate().skip(1) {
+        let fraction = i as f32 / CASCADE_COUNT as f32;
+        let uniform = near + (distance - near) * fraction;
+        let logarithmic = near * (distance / near).powf(fraction);
+        *split = uniform + (logarithmic - uniform) * SPLIT_BLEND;
+    }
+    splits
+}
+
+// an orthographic view along the light around a slice of the frustum, and the world space size
+// of one of its texels. it's fitted around the slice's bounding sphere and moved in whole
+// texels, so its size and texel grid stay put as the camera turns and moves, which keeps the
+// edges of shadows from crawling
+fn fit_cascade(corners: &[Point3<f32>; 8], direction: Vector3<f32>) -> (Matrix4<f32>, f32) {
+    let center = corners
+        .iter()
+        .fold(Vector3::zero(), |sum, corner| sum + corner.to_vec())
+        / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|corner| (corner.to_vec() - center).magnitude())
+        .fold(0.0, f32::max)
+        .max(1e-3);
+    let texel_size = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+
+    // snap the center to the texel grid of the light's view from the origin
+    let light_rotation = Matrix4::look_to_rh(Point3::origin(), direction, up);
+    let mut light_center = Point3::from_homogeneous(light_rotation * center.extend(1.0));
+    light_center.x = (light_center.x / texel_size).floor() * texel_size;
+    light_center.y = (light_center.y / texel_size).floor() * texel_size;
+    let center = Point3::from_homogeneous(
+        light_rotation.invert().unwrap_or(Matrix4::identity()) * light_center.to_homogeneous(),
+    );
+
+    let eye = center - direction * (radius + CASTER_DISTANCE);
+    let view = Matrix4::look_to_rh(eye, direction, up);
+    let projection = camera::OPENGL_TO_WGPU_MATRIX
+        * cgmath::ortho(
+            -radius,
+            radius,
+            -radius,
+            radius,
+            0.0,
+            2.0 * radius + CASTER_DISTANCE,
+        );
+
+    (projection * view, texel_size)
+}
@@ -1,69 +1,363 @@
-use anyhow::*;
 use image::{GenericImageView, ImageBuffer, Rgb, Rgba};
 
+use std::sync::Arc;
+
+use crate::{error::AssetError, exr, ktx2, render_stats};
+
+// requested when the adapter has them: the compressed formats let ktx2 files in them be
+// uploaded, and filterable 32 bit floats keep the full precision of float exr files
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC)
+    .union(wgpu::Features::FLOAT32_FILTERABLE);
+
+// the handles are reference counted, so clones share the same gpu texture and sampler
+#[derive(Clone)]
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    // counts the texture's memory while any clone of it is alive
+    _allocation: Arc<render_stats::Allocation>,
 }
 
-impl Texture {
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// generated maps, bound in place of the ones a material doesn't have. the shader only samples
+// them when a map was given but its file is missing, or when the uv grid is shown, so they're
+// made to stand out rather than to blend in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Placeholder {
+    // magenta and black squares, for missing color maps
+    Checker,
+    // (0.5, 0.5, 1), which leaves the surface normal as it is
+    FlatNormal,
+    // white, which leaves the values that scalar maps multiply as they are
+    White,
+    // cells tinted red along u and green along v, with lines every eighth, for checking a
+    // mesh's uv layout
+    UvGrid,
+}
 
-    pub fn from_bytes(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        bytes: &[u8],
-        label: &str,
-        is_linear: bool,
-    ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label), is_linear)
+impl Placeholder {
+    fn image(self) -> image::RgbaImage {
+        match self {
+            Placeholder::Checker => image::RgbaImage::from_fn(64, 64, |x, y| {
+                if (x / 8 + y / 8) % 2 == 0 {
+                    Rgba([255, 0, 255, 255])
+                } else {
+                    Rgba([0, 0, 0, 255])
+                }
+            }),
+            Placeholder::FlatNormal => {
+                image::RgbaImage::from_pixel(1, 1, Rgba([128, 128, 255, 255]))
+            }
+            Placeholder::White => image::RgbaImage::from_pixel(1, 1, Rgba([255; 4])),
+            Placeholder::UvGrid => image::RgbaImage::from_fn(256, 256, |x, y| {
+                // a line at the start of every cell, so the texture's edges get one too once
+                // it repeats
+                if x % 32 == 0 || y % 32 == 0 {
+                    return Rgba([255, 255, 255, 255]);
+                }
+                let shade = if (x / 32 + y / 32) % 2 == 0 { 1.0 } else { 0.6 };
+                let cell = |c: u32| ((c / 32) as f32 + 0.5) / 8.0 * 255.0 * shade;
+                Rgba([cell(x) as u8, cell(y) as u8, (64.0 * shade) as u8, 255])
+            }),
+        }
     }
 
-    pub fn dummy(device: &wgpu::Device, label: &str) -> Self {
-        let size = wgpu::Extent3d {
-            width: 1,
-            height: 1,
-            depth_or_array_layers: 1,
+    fn is_linear(self) -> bool {
+        matches!(self, Placeholder::FlatNormal | Placeholder::White)
+    }
+}
+
+// one of each placeholder, shared by every material
+pub struct Placeholders {
+    pub checker: Texture,
+    pub flat_normal: Texture,
+    pub white: Texture,
+    pub uv_grid: Texture,
+}
+
+impl Placeholders {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            checker: Texture::placeholder(
+                device,
+                queue,
+                Placeholder::Checker,
+                "checker placeholder",
+            ),
+            flat_normal: Texture::placeholder(
+                device,
+                queue,
+                Placeholder::FlatNormal,
+                "flat normal placeholder",
+            ),
+            white: Texture::placeholder(device, queue, Placeholder::White, "white placeholder"),
+            uv_grid: Texture::placeholder(
+                device,
+                queue,
+                Placeholder::UvGrid,
+                "uv grid placeholder",
+            ),
+        }
+    }
+
+    // what to bind in place of a material's map, by the map's name in a MaterialTextures
+    pub fn for_map(&self, map: &str) -> &Texture {
+        match map {
+            "diffuse" | "specular" | "emissive" => &self.checker,
+            "normal" => &self.flat_normal,
+            _ => &self.white,
+        }
+    }
+}
+
+// describes a texture for Texture::builder, starting from a single 2d rgba8 layer which can be
+// sampled and written to, with a default sampler. the view covers every layer and level, as
+// an array or cube when the texture is one. eg a cubemap to render an environment into:
+//
+//     Texture::builder("environment", 512, 512)
+//         .format(wgpu::TextureFormat::Rgba16Float)
+//         .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+//         .cube()
+//         .build(device)
+pub struct TextureBuilder<'a> {
+    label: &'a str,
+    size: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    mip_level_count: u32,
+    sample_count: u32,
+    view_dimension: wgpu::TextureViewDimension,
+    view_formats: &'a [wgpu::TextureFormat],
+    sampler: wgpu::SamplerDescriptor<'a>,
+}
+
+impl<'a> TextureBuilder<'a> {
+    pub fn format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn mip_levels(mut self, mip_level_count: u32) -> Self {
+        self.mip_level_count = mip_level_count;
+        self
+    }
+
+    // for multisampled render targets, which can't be sampled the usual way
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    // a 2d array of `layers`, or of `layers` cubes after cube()
+    pub fn array_layers(mut self, layers: u32) -> Self {
+        self.view_dimension = match self.view_dimension {
+            wgpu::TextureViewDimension::Cube | wgpu::TextureViewDimension::CubeArray => {
+                self.size.depth_or_array_layers = layers * 6;
+                wgpu::TextureViewDimension::CubeArray
+            }
+            _ => {
+                self.size.depth_or_array_layers = layers;
+                wgpu::TextureViewDimension::D2Array
+            }
         };
+        self
+    }
+
+    // six square layers in the +x, -x, +y, -y, +z, -z order cube views expect
+    #[allow(dead_code)]
+    pub fn cube(mut self) -> Self {
+        self.size.depth_or_array_layers = 6;
+        self.view_dimension = wgpu::TextureViewDimension::Cube;
+        self
+    }
+
+    // other formats the texture can be viewed as, eg its srgb or linear twin
+    #[allow(dead_code)]
+    pub fn view_formats(mut self, view_formats: &'a [wgpu::TextureFormat]) -> Self {
+        self.view_formats = view_formats;
+        self
+    }
 
+    pub fn sampler(mut self, sampler: wgpu::SamplerDescriptor<'a>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> Texture {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(label),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
+            label: Some(self.label),
+            size: self.size,
+            mip_level_count: self.mip_level_count,
+            sample_count: self.sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
+            format: self.format,
+            usage: self.usage,
+            view_formats: self.view_formats,
         });
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(self.view_dimension),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&self.sampler);
+        let allocation = render_stats::Allocation::texture(&texture);
 
-        Self {
+        Texture {
             texture,
             view,
             sampler,
+            _allocation: allocation,
+        }
+    }
+}
+
+impl Texture {
+    // the scene's depth, with a stencil for outlining the selected model, see outline.rs
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+    // the shadow maps are sampled, which is simpler without a stencil aspect to leave out
+    pub const SHADOW_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn builder(label: &str, width: u32, height: u32) -> TextureBuilder<'_> {
+        TextureBuilder {
+            label,
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            mip_level_count: 1,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            view_formats: &[],
+            sampler: wgpu::SamplerDescriptor::default(),
         }
     }
 
+    // a 2d view of one layer, eg a single cube face to render into
+    pub fn layer_view(&self, layer: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+        is_linear: bool,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<Self, AssetError> {
+        if ktx2::is_ktx2(bytes) {
+            return Self::from_ktx2(device, queue, bytes, label, is_linear, address_mode);
+        }
+
+        // high dynamic range images are always linear, so is_linear doesn't matter for them
+        if exr::is_exr(bytes) {
+            let image = exr::parse(bytes).map_err(|message| AssetError::BadData {
+                path: label.to_string(),
+                message,
+            })?;
+            return Self::from_float_pixels(
+                device,
+                queue,
+                (image.width, image.height),
+                &image.pixels,
+                image.has_float_channels,
+                label,
+                address_mode,
+            );
+        }
+
+        let img = image::load_from_memory(bytes).map_err(|source| AssetError::BadImage {
+            path: label.to_string(),
+            source,
+        })?;
+
+        if let Ok(image::ImageFormat::Hdr) = image::guess_format(bytes) {
+            let rgba = img.to_rgba32f();
+            let pixels = rgba.pixels().map(|p| p.0).collect::<Vec<_>>();
+            // radiance files only have 8 bits of mantissa, which half floats hold exactly, but
+            // their shared exponent reaches far past the largest half float. the sun in a sky
+            // often does, so those need full precision, or are clamped without it
+            let half_max = half::f16::MAX.to_f32();
+            let exceeds_half = pixels.iter().flatten().any(|&v| v > half_max);
+            return Self::from_float_pixels(
+                device,
+                queue,
+                rgba.dimensions(),
+                &pixels,
+                exceeds_half,
+                label,
+                address_mode,
+            );
+        }
+
+        Self::from_image(device, queue, &img, Some(label), is_linear, address_mode)
+    }
+
+    // a generated stand in for a map, see Placeholder
+    pub fn placeholder(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        placeholder: Placeholder,
+        label: &str,
+    ) -> Self {
+        let rgba = placeholder.image();
+        let format = if placeholder.is_linear() {
+            wgpu::TextureFormat::Rgba8Unorm
+        } else {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        };
+
+        // usable in the material atlas like any map loaded from a file
+        let texture = Self::builder(label, rgba.width(), rgba.height())
+            .format(format)
+            .usage(
+                wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC,
+            )
+            .sampler(Self::material_sampler_descriptor(wgpu::AddressMode::Repeat))
+            .build(device);
+        Self::write_rgba(queue, &texture.texture, &rgba);
+        texture
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
         is_linear: bool,
-    ) -> Result<Self> {
+        address_mode: wgpu::AddressMode,
+    ) -> Result<Self, AssetError> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
 
-        let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
-        };
+        // wgpu would only report this through its error callback, after creating an invalid texture
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if dimensions.0 > max_dimension || dimensions.1 > max_dimension {
+            return Err(AssetError::GpuUpload {
+                label: label.unwrap_or("texture").to_string(),
+                message: format!(
+                    "{}x{} is larger than the device limit of {}",
+                    dimensions.0, dimensions.1, max_dimension
+                ),
+            });
+        }
 
         let format = if is_linear {
             wgpu::TextureFormat::Rgba8Unorm
@@ -71,94 +365,264 @@ impl Texture {
             wgpu::TextureFormat::Rgba8UnormSrgb
         };
 
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label,
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+        let texture = Self::builder(label.unwrap_or("texture"), dimensions.0, dimensions.1)
+            .format(format)
+            // copy source for packing into the material atlas
+            .usage(
+                wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC,
+            )
+            .sampler(Self::material_sampler_descriptor(address_mode))
+            .build(device);
 
+        Self::write_rgba(queue, &texture.texture, &rgba);
+
+        Ok(texture)
+    }
+
+    fn write_rgba(queue: &wgpu::Queue, texture: &wgpu::Texture, rgba: &image::RgbaImage) {
         queue.write_texture(
             wgpu::TexelCopyTextureInfo {
                 aspect: wgpu::TextureAspect::All,
-                texture: &texture,
+                texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(4 * rgba.width()),
+                rows_per_image: Some(rgba.height()),
             },
-            size,
+            texture.size(),
         );
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
-            ..Default::default()
-        });
-
-        Ok(Self {
-            texture,
-            view,
-            sampler,
-        })
     }
 
-    pub fn create_depth_texture(
+    // uploads the levels of a ktx2 file as they are, without decoding. the file's format is
+    // kept, apart from switching it to srgb or linear to match how the map is used
+    fn from_ktx2(
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
         label: &str,
-    ) -> Self {
+        is_linear: bool,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<Self, AssetError> {
+        let ktx = ktx2::parse(bytes).map_err(|message| AssetError::BadData {
+            path: label.to_string(),
+            message,
+        })?;
+        let upload_error = |message: String| AssetError::GpuUpload {
+            label: label.to_string(),
+            message,
+        };
+
+        let format = if is_linear {
+            ktx.format.remove_srgb_suffix()
+        } else {
+            ktx.format.add_srgb_suffix()
+        };
+
+        let missing_features = format.required_features() - device.features();
+        if !missing_features.is_empty() {
+            return Err(upload_error(format!(
+                "{:?} needs {:?}, which this adapter doesn't support",
+                format, missing_features
+            )));
+        }
+
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if ktx.width > max_dimension || ktx.height > max_dimension {
+            return Err(upload_error(format!(
+                "{}x{} is larger than the device limit of {}",
+                ktx.width, ktx.height, max_dimension
+            )));
+        }
+
+        // compressed textures have to be a whole number of blocks
+        let (block_width, block_height) = format.block_dimensions();
+        if ktx.width % block_width != 0 || ktx.height % block_height != 0 {
+            return Err(upload_error(format!(
+                "{}x{} isn't a multiple of the {}x{} blocks of {:?}",
+                ktx.width, ktx.height, block_width, block_height, format
+            )));
+        }
+        let block_size = format
+            .block_copy_size(None)
+            .ok_or_else(|| upload_error(format!("{:?} can't be copied into", format)))?;
+
         let size = wgpu::Extent3d {
-            width: config.width.max(1),
-            height: config.height.max(1),
+            width: ktx.width,
+            height: ktx.height,
             depth_or_array_layers: 1,
         };
 
-        let descriptor = wgpu::TextureDescriptor {
-            label: Some(label),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
+        let texture = Self::builder(label, ktx.width, ktx.height)
+            .format(format)
+            .mip_levels(ktx.levels.len() as u32)
+            .sampler(Self::material_sampler_descriptor(address_mode))
+            .build(device);
+
+        for (mip_level, data) in ktx.levels.iter().enumerate() {
+            let mip_size = size
+                .mip_level_size(mip_level as u32, wgpu::TextureDimension::D2)
+                .physical_size(format);
+            let bytes_per_row = mip_size.width / block_width * block_size;
+            let rows = mip_size.height / block_height;
+            if data.len() < (bytes_per_row * rows) as usize {
+                return Err(AssetError::BadData {
+                    path: label.to_string(),
+                    message: format!("level {} is too short for its size", mip_level),
+                });
+            }
+
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture.texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+                mip_size,
+            );
+        }
+
+        Ok(texture)
+    }
+
+    // uploads linear rgba floats as Rgba16Float, or as Rgba32Float when the source needs the
+    // precision and the device can filter 32 bit float textures, which material bind groups
+    // expect. values beyond the largest half float are clamped to it rather than becoming
+    // infinite. these are sampled linearly, unlike the other material textures
+    pub fn from_float_pixels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: (u32, u32),
+        pixels: &[[f32; 4]],
+        wants_full_precision: bool,
+        label: &str,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<Self, AssetError> {
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if dimensions.0 > max_dimension || dimensions.1 > max_dimension {
+            return Err(AssetError::GpuUpload {
+                label: label.to_string(),
+                message: format!(
+                    "{}x{} is larger than the device limit of {}",
+                    dimensions.0, dimensions.1, max_dimension
+                ),
+            });
+        }
+
+        let is_full_precision = wants_full_precision
+            && device
+                .features()
+                .contains(wgpu::Features::FLOAT32_FILTERABLE);
+        let (format, data) = if is_full_precision {
+            (
+                wgpu::TextureFormat::Rgba32Float,
+                bytemuck::cast_slice::<_, u8>(pixels).to_vec(),
+            )
+        } else {
+            let half_max = half::f16::MAX.to_f32();
+            let halves = pixels
+                .iter()
+                .flatten()
+                .map(|&v| half::f16::from_f32(v.clamp(-half_max, half_max)).to_bits())
+                .collect::<Vec<_>>();
+            (
+                wgpu::TextureFormat::Rgba16Float,
+                bytemuck::cast_slice::<_, u8>(&halves).to_vec(),
+            )
         };
+        let pixel_size = format.block_copy_size(None).unwrap_or(8);
 
-        let texture = device.create_texture(&descriptor);
+        let texture = Self::builder(label, dimensions.0, dimensions.1)
+            .format(format)
+            .sampler(wgpu::SamplerDescriptor {
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+                ..Default::default()
+            })
+            .build(device);
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(pixel_size * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            texture.texture.size(),
+        );
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
+        Ok(texture)
+    }
+
+    pub fn material_sampler_descriptor(
+        address_mode: wgpu::AddressMode,
+    ) -> wgpu::SamplerDescriptor<'static> {
+        wgpu::SamplerDescriptor {
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
             mipmap_filter: wgpu::MipmapFilterMode::Nearest,
-            compare: Some(wgpu::CompareFunction::LessEqual), // this is the important part
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 100.0,
             ..Default::default()
-        });
-
-        Self {
-            texture,
-            view,
-            sampler,
         }
     }
+
+    // a color target matching the surface which keeps its contents between frames, so that
+    // partial redraws and re-presenting an unchanged frame have something to copy from
+    pub fn create_frame_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        Self::builder(label, config.width, config.height)
+            .format(config.format)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC)
+            .build(device)
+    }
+
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        Self::builder(label, config.width, config.height)
+            .format(Self::DEPTH_FORMAT)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT)
+            .sampler(wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual), // this is the important part
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            })
+            .build(device)
+    }
 }
@@ -26,4 +26,313 @@ impl RollingAverage {
     pub fn get(&self) -> f32 {
         self.running_avg
     }
-}
\ No newline at end of file
+}
+
+// rolling averages for the named parts of a frame, kept in the order they were first timed so
+// the breakdown is stable from frame to frame
+pub struct SubsystemTimings {
+    window_size: usize,
+    subsystems: Vec<(&'static str, RollingAverage)>,
+}
+
+impl SubsystemTimings {
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            window_size,
+            subsystems: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, name: &'static str, elapsed: std::time::Duration) {
+        let index = match self.subsystems.iter().position(|(n, _)| *n == name) {
+            Some(index) => index,
+            None => {
+                self.subsystems
+                    .push((name, RollingAverage::new(self.window_size)));
+                self.subsystems.len() - 1
+            }
+        };
+
+        self.subsystems[index].1.push(elapsed.as_micros() as f32);
+    }
+
+    // each subsystem's average in microseconds and its share of the total
+    pub fn describe(&self) -> String {
+        let total = self
+            .subsystems
+            .iter()
+            .map(|(_, avg)| avg.get())
+            .sum::<f32>()
+            .max(1.0);
+
+        self.subsystems
+            .iter()
+            .map(|(name, avg)| {
+                format!(
+                    "{} {} us {}%",
+                    name,
+                    avg.get() as u32,
+                    (avg.get() / total * 100.0) as u32
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+// the spread of the last frame times, since an average hides the odd slow frame. unlike
+// RollingAverage the window starts empty, so the first frames aren't mixed with zeros.
+// times are in milliseconds
+pub struct FrameStats {
+    samples: VecDeque<f32>,
+    window_size: usize,
+}
+
+impl FrameStats {
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window_size),
+            window_size: window_size.max(1),
+        }
+    }
+
+    pub fn push(&mut self, frame_time: std::time::Duration) {
+        if self.samples.len() >= self.window_size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(frame_time.as_secs_f32() * 1000.0);
+    }
+
+    // oldest first
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied()
+    }
+
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+
+    pub fn min(&self) -> f32 {
+        self.samples().reduce(f32::min).unwrap_or(0.0)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples().reduce(f32::max).unwrap_or(0.0)
+    }
+
+    // the frame times that each p percent of the window is at or below, eg 99 for the slowest
+    // 1%, from one sort
+    pub fn percentiles<const N: usize>(&self, ps: [f32; N]) -> [f32; N] {
+        let mut sorted = self.samples().collect::<Vec<_>>();
+        sorted.sort_by(f32::total_cmp);
+        ps.map(|p| {
+            let index = (sorted.len() as f32 * p / 100.0) as usize;
+            sorted
+                .get(index.min(sorted.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or(0.0)
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let [p50, p95, p99] = self.percentiles([50.0, 95.0, 99.0]);
+        format!(
+            "min {:.1} p50 {:.1} p95 {:.1} p99 {:.1} max {:.1} ms",
+            self.min(),
+            p50,
+            p95,
+            p99,
+            self.max(),
+        )
+    }
+}
+
+// records the frame times of a fixed number of frames, eg for the console's bench command
+pub struct Benchmark {
+    frame_count: usize,
+    frame_times: Vec<std::time::Duration>,
+}
+
+impl Benchmark {
+    pub fn new(frame_count: u32) -> Self {
+        Self {
+            frame_count: frame_count.max(1) as usize,
+            frame_times: Vec::with_capacity(frame_count as usize),
+        }
+    }
+
+    pub fn push(&mut self, frame_time: std::time::Duration) {
+        if !self.is_finished() {
+            self.frame_times.push(frame_time);
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame_times.len() >= self.frame_count
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.is_finished() {
+            return format!("bench {}/{}", self.frame_times.len(), self.frame_count);
+        }
+
+        let mut sorted = self.frame_times.clone();
+        sorted.sort();
+        let ms = |d: std::time::Duration| d.as_secs_f32() * 1000.0;
+        let average = sorted.iter().sum::<std::time::Duration>() / sorted.len() as u32;
+        let p99 = sorted[(sorted.len() * 99 / 100).min(sorted.len() - 1)];
+
+        format!(
+            "bench {} frames: avg {:.2} ms ({:.0} fps)  min {:.2} ms  p99 {:.2} ms  max {:.2} ms",
+            sorted.len(),
+            ms(average),
+            1.0 / average.as_secs_f32().max(f32::EPSILON),
+            ms(sorted[0]),
+            ms(p99),
+            ms(sorted[sorted.len() - 1]),
+        )
+    }
+}
+
+// holds frames back to a maximum rate on the cpu, for present modes which don't wait for the
+// display, like mailbox and immediate. each frame is due a frame time after the one before it,
+// or straight away when that one was late, so a slow frame isn't made up for with quick ones
+pub struct FrameLimiter {
+    frame_time: Option<std::time::Duration>,
+    next_frame: std::time::Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<f32>) -> Self {
+        let mut limiter = Self {
+            frame_time: None,
+            next_frame: std::time::Instant::now(),
+        };
+        limiter.set_max_fps(max_fps);
+        limiter
+    }
+
+    // None or 0 takes the limit off
+    pub fn set_max_fps(&mut self, fps: Option<f32>) {
+        self.frame_time = fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| std::time::Duration::from_secs_f32(1.0 / fps));
+        self.next_frame = std::time::Instant::now();
+    }
+
+    pub fn max_fps(&self) -> Option<f32> {
+        self.frame_time
+            .map(|frame_time| 1.0 / frame_time.as_secs_f32())
+    }
+
+    // sleeps until the next frame is due. the browser can't be blocked, so on the web this
+    // does nothing and frames come at the browser's pace
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+        let now = std::time::Instant::now();
+        if now < self.next_frame {
+            #[cfg(not(target_arch = "wasm32"))]
+            std::thread::sleep(self.next_frame - now);
+            self.next_frame += frame_time;
+        } else {
+            self.next_frame = now + frame_time;
+        }
+    }
+
+    // eg [CAP 60], or nothing without a limit
+    pub fn describe(&self) -> String {
+        self.max_fps()
+            .map_or(String::new(), |fps| format!("[CAP {:.0}]", fps))
+    }
+}
+
+// the scene's own time, which animations and the shaders' timestamp follow. it can run slower
+// than real time, or be paused and stepped a frame at a time, for looking at animated effects
+// frame by frame. the camera keeps to real time, so a paused scene can still be looked around
+pub struct SceneClock {
+    // as a fraction of real time
+    scale: f32,
+    is_paused: bool,
+    // asked for while paused, taken by the next frame
+    is_step_pending: bool,
+    elapsed: std::time::Duration,
+    // the frames the scene's time moved in
+    frame_index: u64,
+}
+
+impl Default for SceneClock {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            is_paused: false,
+            is_step_pending: false,
+            elapsed: std::time::Duration::ZERO,
+            frame_index: 0,
+        }
+    }
+}
+
+impl SceneClock {
+    // the speeds cycled through
+    const SCALES: [f32; 3] = [1.0, 0.5, 0.1];
+    // how far a step moves the time, at a typical display's rate
+    const STEP: std::time::Duration = std::time::Duration::from_nanos(1_000_000_000 / 60);
+
+    // moves the scene's time on by real time dt, returning how far it moved
+    pub fn advance(&mut self, dt: std::time::Duration) -> std::time::Duration {
+        let scene_dt = if !self.is_paused {
+            dt.mul_f32(self.scale)
+        } else if std::mem::take(&mut self.is_step_pending) {
+            Self::STEP
+        } else {
+            return std::time::Duration::ZERO;
+        };
+        self.elapsed += scene_dt;
+        self.frame_index += 1;
+        scene_dt
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.elapsed
+    }
+
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.is_paused = !self.is_paused;
+        self.is_step_pending = false;
+    }
+
+    // pauses, if it isn't already, and moves on a single frame
+    pub fn step(&mut self) {
+        self.is_paused = true;
+        self.is_step_pending = true;
+    }
+
+    // full speed, half and a tenth in turn
+    pub fn cycle_scale(&mut self) {
+        let current = Self::SCALES
+            .iter()
+            .position(|scale| *scale == self.scale)
+            .unwrap_or(0);
+        self.scale = Self::SCALES[(current + 1) % Self::SCALES.len()];
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    // eg [PAUSED], [0.5x] or [PAUSED 0.1x], or nothing at full speed
+    pub fn describe(&self) -> String {
+        match (self.is_paused, self.scale == 1.0) {
+            (false, true) => String::new(),
+            (false, false) => format!("[{}x]", self.scale),
+            (true, true) => "[PAUSED]".to_string(),
+            (true, false) => format!("[PAUSED {}x]", self.scale),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+// a small toml reader and writer for the config file. only what the config needs is read:
+// [tables], key = value lines, and strings, numbers, booleans and arrays of them on one line.
+// keys come back prefixed with their table, eg window.width, in the order they were read
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    String(String),
+    // integers and floats alike
+    Number(f64),
+    Bool(bool),
+    Array(Vec<TomlValue>),
+}
+
+impl TomlValue {
+    // a single value, eg from the command line
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { text, offset: 0 };
+        let value = parser.value()?;
+        parser.end()?;
+        Ok(value)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            TomlValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TomlValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[TomlValue]> {
+        match self {
+            TomlValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TomlValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TomlValue::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            TomlValue::Number(n) => write!(f, "{}", n),
+            TomlValue::Bool(b) => write!(f, "{}", b),
+            TomlValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+// every key = value in the text, with its table's name in front of the key
+pub fn parse(text: &str) -> Result<Vec<(String, TomlValue)>, String> {
+    let mut entries = Vec::new();
+    let mut table = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |e: String| format!("line {}: {}", i + 1, e);
+
+        if let Some(name) = line.strip_prefix('[') {
+            let (name, rest) = name
+                .split_once(']')
+                .ok_or_else(|| error("the table's name has no closing ]".into()))?;
+            if !rest.trim().is_empty() && !rest.trim().starts_with('#') {
+                return Err(error(format!(
+                    "unexpected {:?} after the table",
+                    rest.trim()
+                )));
+            }
+            table = name.trim().to_string();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected key = value".into()))?;
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-".contains(c))
+        {
+            return Err(error(format!("bad key {:?}", key)));
+        }
+        let mut parser = Parser {
+            text: value,
+            offset: 0,
+        };
+        let value = parser.value().and_then(|value| parser.end().map(|_| value));
+        let name = if table.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", table, key)
+        };
+        entries.push((name, value.map_err(error)?));
+    }
+    Ok(entries)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("unexpected end of the line")?;
+        self.offset += c.len_utf8();
+        Ok(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_whitespace() {
+                break;
+            }
+            self.offset += 1;
+        }
+    }
+
+    // only whitespace or a comment can follow the value
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('#') => Ok(()),
+            Some(_) => Err(format!(
+                "unexpected {:?} after the value",
+                &self.text[self.offset..]
+            )),
+        }
+    }
+
+    fn value(&mut self) -> Result<TomlValue, String> {
+        self.skip_whitespace();
+        match self.peek().ok_or("expected a value")? {
+            '[' => self.array(),
+            '"' => self.string().map(TomlValue::String),
+            _ => {
+                let start = self.offset;
+                while let Some(c) = self.peek() {
+                    if !(c.is_ascii_alphanumeric() || "+-._".contains(c)) {
+                        break;
+                    }
+                    self.offset += c.len_utf8();
+                }
+                match &self.text[start..self.offset] {
+                    "true" => Ok(TomlValue::Bool(true)),
+                    "false" => Ok(TomlValue::Bool(false)),
+                    word => word
+                        .replace('_', "")
+                        .parse()
+                        .map(TomlValue::Number)
+                        .map_err(|_| format!("{:?} isn't a string, number or boolean", word)),
+                }
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<TomlValue, String> {
+        self.next()?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            // a trailing comma is allowed
+            if self.peek() == Some(']') {
+                self.offset += 1;
+                return Ok(TomlValue::Array(items));
+            }
+            items.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                ']' => return Ok(TomlValue::Array(items)),
+                c => return Err(format!("unexpected {:?} in an array", c)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.next()?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => match self.next()? {
+                    '"' => string.push('"'),
+                    '\\' => string.push('\\'),
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    c => return Err(format!("unknown escape \\{}", c)),
+                },
+                c => string.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_prefixed_with_their_table() {
+        let entries =
+            parse("top = 1\n[window]\nwidth = 1280\n\n[ render ]\nfxaa = true\n").unwrap();
+        let names: Vec<_> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["top", "window.width", "render.fxaa"]);
+    }
+
+    #[test]
+    fn strings_unescape() {
+        assert_eq!(
+            TomlValue::parse(r#""a \"b\" \\ c\n\td""#),
+            Ok(TomlValue::String("a \"b\" \\ c\n\td".into()))
+        );
+        assert!(TomlValue::parse(r#""\q""#).is_err());
+        assert!(TomlValue::parse(r#""unterminated"#).is_err());
+    }
+
+    #[test]
+    fn strings_write_back_the_same() {
+        let value = TomlValue::String("say \"hi\"\\\n".into());
+        assert_eq!(TomlValue::parse(&value.to_string()), Ok(value));
+    }
+
+    #[test]
+    fn bools_and_numbers() {
+        assert_eq!(TomlValue::parse("true"), Ok(TomlValue::Bool(true)));
+        assert_eq!(TomlValue::parse("false"), Ok(TomlValue::Bool(false)));
+        assert_eq!(TomlValue::parse("10"), Ok(TomlValue::Number(10.0)));
+        assert_eq!(TomlValue::parse("-1.5"), Ok(TomlValue::Number(-1.5)));
+        assert_eq!(TomlValue::parse("+2e3"), Ok(TomlValue::Number(2000.0)));
+        assert_eq!(TomlValue::parse("1_000"), Ok(TomlValue::Number(1000.0)));
+        assert!(TomlValue::parse("yes").is_err());
+    }
+
+    #[test]
+    fn arrays_allow_a_trailing_comma() {
+        assert_eq!(
+            TomlValue::parse(r#"["KeyW", 2, false,]"#),
+            Ok(TomlValue::Array(vec![
+                TomlValue::String("KeyW".into()),
+                TomlValue::Number(2.0),
+                TomlValue::Bool(false),
+            ]))
+        );
+        assert_eq!(TomlValue::parse("[]"), Ok(TomlValue::Array(vec![])));
+        assert!(TomlValue::parse("[1 2]").is_err());
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let entries = parse(
+            "# a comment\n  # an indented one\n[camera] # after a table\nspeed = 10 # after a value\nname = \"a # b\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            entries,
+            [
+                ("camera.speed".to_string(), TomlValue::Number(10.0)),
+                ("camera.name".to_string(), TomlValue::String("a # b".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_say_where() {
+        assert_eq!(
+            parse("a = 1\nb\n"),
+            Err("line 2: expected key = value".into())
+        );
+        assert!(parse("[window\n").unwrap_err().starts_with("line 1:"));
+        assert!(parse("[window] x\n").is_err());
+        assert!(parse("bad key = 1\n").is_err());
+        assert!(parse("a = 1 2\n").is_err());
+        assert!(parse("a =\n").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use cgmath::SquareMatrix;
 
 use crate::{DirectionalLight, PointLight, SpotLight, camera};
@@ -10,6 +12,8 @@ pub struct CameraUniform {
 }
 
 impl CameraUniform {
+    pub const SIZE: u64 = std::mem::size_of::<Self>() as u64;
+
     pub fn new() -> Self {
         Self {
             position: [0.0; 4],
@@ -22,6 +26,30 @@ impl CameraUniform {
         self.view_projection_matrix =
             (projection.perspective_matrix() * camera.view_matrix()).into()
     }
+
+    // for views with projections of their own, like the quad view's orthographic ones
+    pub fn set(
+        &mut self,
+        position: cgmath::Point3<f32>,
+        view_projection_matrix: cgmath::Matrix4<f32>,
+    ) {
+        self.position = position.to_homogeneous().into();
+        self.view_projection_matrix = view_projection_matrix.into();
+    }
+
+    // for views the yaw/pitch camera can't express, like looking straight up
+    pub fn update_view_proj_to(
+        &mut self,
+        position: cgmath::Point3<f32>,
+        forward: cgmath::Vector3<f32>,
+        up: cgmath::Vector3<f32>,
+        projection: &camera::Projection,
+    ) {
+        self.position = position.to_homogeneous().into();
+        self.view_projection_matrix = (projection.perspective_matrix()
+            * cgmath::Matrix4::look_to_rh(position, forward, up))
+        .into()
+    }
 }
 
 pub fn create_light_uniforms(
@@ -83,11 +111,11 @@ pub struct LightUniform {
 impl From<PointLight> for LightUniform {
     fn from(value: PointLight) -> Self {
         Self {
-            position: value.position,
+            position: value.transform.translation().into(),
             _padding1: 0,
             direction: [0.0; 3],
             _padding2: 0,
-            color: value.color,
+            color: value.color.map(|c| c * value.intensity),
             _padding3: 0,
             params: [0.0; 4],
         }
@@ -99,7 +127,7 @@ impl From<DirectionalLight> for LightUniform {
         Self {
             position: [0.0; 3],
             _padding1: 0,
-            direction: value.direction,
+            direction: value.transform.forward().into(),
             _padding2: 0,
             color: value.color,
             _padding3: 0,
@@ -111,9 +139,9 @@ impl From<DirectionalLight> for LightUniform {
 impl From<SpotLight> for LightUniform {
     fn from(value: SpotLight) -> Self {
         Self {
-            position: value.position,
+            position: value.transform.translation().into(),
             _padding1: 0,
-            direction: value.direction,
+            direction: value.transform.forward().into(),
             _padding2: 0,
             color: value.color,
             _padding3: 0,
@@ -145,5 +173,86 @@ pub struct LightMetadataUniform {
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct TimestampUniform {
-    pub time: u32,
+    seconds: f32,
+    delta_seconds: f32,
+    frame_index: u32,
+    // computed in f64 on the cpu, so these stay smooth long after `seconds` has lost precision
+    sin_time: f32,
+    cos_time: f32,
+    // what the main shader shows in place of the shading, see DebugView
+    debug_view: u32,
+    _padding: [u32; 2],
+}
+
+impl TimestampUniform {
+    pub fn new() -> Self {
+        Self {
+            seconds: 0.0,
+            delta_seconds: 0.0,
+            frame_index: 0,
+            sin_time: 0.0,
+            cos_time: 1.0,
+            debug_view: 0,
+            _padding: [0; 2],
+        }
+    }
+
+    pub fn update(&mut self, elapsed: Duration, dt: Duration, frame_index: u64) {
+        let seconds = elapsed.as_secs_f64();
+        let (sin_time, cos_time) = seconds.sin_cos();
+
+        self.seconds = seconds as f32;
+        self.delta_seconds = dt.as_secs_f32();
+        self.frame_index = frame_index as u32;
+        self.sin_time = sin_time as f32;
+        self.cos_time = cos_time as f32;
+    }
+
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_view = view as u32;
+    }
+}
+
+// the main shader's views of what goes into the shading, cycled with C. the numbers are what
+// shader.wgsl's debug_view_color switches on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    Shaded = 0,
+    Normals = 1,
+    Uvs = 2,
+    Tangents = 3,
+    // the diffuse map's, from blue at the full size map to red at the smallest ones
+    MipLevel = 4,
+    // the distance from the camera, on a log scale
+    Depth = 5,
+    // how many surfaces cover each pixel, drawn with a pipeline of its own that adds up every
+    // mesh without depth testing
+    Overdraw = 6,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Shaded => DebugView::Normals,
+            DebugView::Normals => DebugView::Uvs,
+            DebugView::Uvs => DebugView::Tangents,
+            DebugView::Tangents => DebugView::MipLevel,
+            DebugView::MipLevel => DebugView::Depth,
+            DebugView::Depth => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Shaded,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "",
+            DebugView::Normals => "[NORMALS]",
+            DebugView::Uvs => "[UVS]",
+            DebugView::Tangents => "[TANGENTS]",
+            DebugView::MipLevel => "[MIP LEVEL]",
+            DebugView::Depth => "[DEPTH]",
+            DebugView::Overdraw => "[OVERDRAW]",
+        }
+    }
 }
@@ -0,0 +1,98 @@
+// collects the buffer writes made while a frame is updated and sends them to the gpu together,
+// instead of one queue.write_buffer each. the data is packed into one staging buffer with a
+// single write, then copied out to where it goes by a command buffer submitted ahead of the
+// frame's, so everything written in update() is there before anything is drawn.
+// writes made while rendering, eg a stereo eye's camera between two submissions, still go
+// straight to the queue through RenderStats::write_buffer, since they have to land between
+// particular submissions
+
+struct PendingCopy {
+    buffer: wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    staging_offset: wgpu::BufferAddress,
+    size: wgpu::BufferAddress,
+}
+
+pub struct UploadBelt {
+    staging_buffer: wgpu::Buffer,
+    // this frame's writes, one after another at copy alignment
+    data: Vec<u8>,
+    copies: Vec<PendingCopy>,
+}
+
+impl UploadBelt {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            staging_buffer: create_staging_buffer(device, 64 * 1024),
+            data: Vec::new(),
+            copies: Vec::new(),
+        }
+    }
+
+    // like queue.write_buffer, the data's size has to be a multiple of 4 and the buffer needs
+    // COPY_DST
+    pub fn write(&mut self, buffer: &wgpu::Buffer, offset: wgpu::BufferAddress, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let staging_offset = self.data.len() as wgpu::BufferAddress;
+        self.data.extend_from_slice(data);
+        // keeps the next write's offset aligned too
+        let padded =
+            (self.data.len() as wgpu::BufferAddress).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        self.data.resize(padded as usize, 0);
+
+        self.copies.push(PendingCopy {
+            buffer: buffer.clone(),
+            offset,
+            staging_offset,
+            size: data.len() as wgpu::BufferAddress,
+        });
+    }
+
+    // sends everything written since the last flush, growing the staging buffer when it's
+    // outgrown. called once at the end of update()
+    pub fn flush(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        render_stats: &mut crate::render_stats::RenderStats,
+    ) {
+        if self.copies.is_empty() {
+            return;
+        }
+
+        let size = self.data.len() as wgpu::BufferAddress;
+        if size > self.staging_buffer.size() {
+            self.staging_buffer = create_staging_buffer(device, size.next_power_of_two());
+        }
+        // counted as the writes they stand for, so the count means the same as before
+        render_stats.buffer_writes += self.copies.len() as u32;
+        queue.write_buffer(&self.staging_buffer, 0, &self.data);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("upload command encoder"),
+        });
+        for copy in self.copies.drain(..) {
+            encoder.copy_buffer_to_buffer(
+                &self.staging_buffer,
+                copy.staging_offset,
+                &copy.buffer,
+                copy.offset,
+                copy.size,
+            );
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        self.data.clear();
+    }
+}
+
+fn create_staging_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("upload staging buffer"),
+        size,
+        usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
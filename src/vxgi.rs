@@ -0,0 +1,655 @@
+// voxel cone traced global illumination, an experimental alternative to the flat ambient term
+//
+// the model is voxelized once at load (so only static content is supported), then every frame
+// the lights are injected into a radiance volume which is mipmapped, and the main shader cone
+// traces that volume for indirect diffuse and specular light
+
+use cgmath::{ElementWise, EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+use crate::model;
+
+// voxels along each side of the (cubic) grid
+pub const RESOLUTION: u32 = 64;
+const MIP_LEVEL_COUNT: u32 = RESOLUTION.ilog2() + 1;
+const WORKGROUP_SIZE: u32 = 4;
+
+const RADIANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GIMode {
+    // the standard path, with only the flat ambient term
+    Off,
+    // direct lighting plus cone traced indirect lighting
+    Full,
+    // only the cone traced indirect lighting, for inspecting the volume
+    IndirectOnly,
+}
+
+impl GIMode {
+    pub fn next(self) -> Self {
+        match self {
+            GIMode::Off => GIMode::Full,
+            GIMode::Full => GIMode::IndirectOnly,
+            GIMode::IndirectOnly => GIMode::Off,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            GIMode::Off => "",
+            GIMode::Full => "[VXGI]",
+            GIMode::IndirectOnly => "[VXGI INDIRECT]",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelGIUniform {
+    grid_min: [f32; 3],
+    voxel_size: f32,
+    resolution: u32,
+    mode: u32,
+    mip_count: u32,
+    _padding: u32,
+}
+
+pub struct VoxelGI {
+    mode: GIMode,
+    uniform: VoxelGIUniform,
+    uniform_buffer: wgpu::Buffer,
+
+    // the voxelized scene, rewritten in place when the scene changes
+    albedo_texture: wgpu::Texture,
+    normal_texture: wgpu::Texture,
+
+    inject_pipeline: wgpu::ComputePipeline,
+    inject_bind_group: wgpu::BindGroup,
+    mip_pipeline: wgpu::ComputePipeline,
+    // one per mip level after the first, each reading the level above it
+    mip_bind_groups: Vec<wgpu::BindGroup>,
+
+    // bound as group 3 by the main pipeline
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+// the voxelized surfaces: an rgba8 albedo volume with occupancy in alpha and an rgba8 snorm
+// volume of averaged normals
+struct Voxels {
+    grid_min: Point3<f32>,
+    voxel_size: f32,
+    albedo: Vec<[u8; 4]>,
+    normals: Vec<[i8; 4]>,
+}
+
+impl VoxelGI {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        models: &[&model::Model],
+        materials: &[model::Material],
+        per_frame_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let voxels = voxelize(models, materials);
+
+        let uniform = VoxelGIUniform {
+            grid_min: voxels.grid_min.into(),
+            voxel_size: voxels.voxel_size,
+            resolution: RESOLUTION,
+            mode: 0,
+            mip_count: MIP_LEVEL_COUNT,
+            _padding: 0,
+        };
+
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("vxgi uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let albedo_texture = create_volume(
+            device,
+            "vxgi albedo volume",
+            wgpu::TextureFormat::Rgba8Unorm,
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        write_volume(queue, &albedo_texture, bytemuck::cast_slice(&voxels.albedo));
+
+        let normal_texture = create_volume(
+            device,
+            "vxgi normal volume",
+            wgpu::TextureFormat::Rgba8Snorm,
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        write_volume(
+            queue,
+            &normal_texture,
+            bytemuck::cast_slice(&voxels.normals),
+        );
+
+        let radiance_texture = create_volume(
+            device,
+            "vxgi radiance volume",
+            RADIANCE_FORMAT,
+            MIP_LEVEL_COUNT,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        );
+
+        let radiance_mip_views = (0..MIP_LEVEL_COUNT)
+            .map(|level| {
+                radiance_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("vxgi radiance mip view"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // MARK: LIGHT INJECTION
+
+        let inject_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vxgi inject bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // albedo and occupancy
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // normals
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // the first level of the radiance volume
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: RADIANCE_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let inject_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vxgi inject bind group"),
+            layout: &inject_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &albedo_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &normal_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&radiance_mip_views[0]),
+                },
+            ],
+        });
+
+        let inject_pipeline = create_compute_pipeline(
+            device,
+            "vxgi inject pipeline",
+            &[per_frame_layout, &inject_layout],
+            crate::shaders::wgsl("vxgi_inject.wgsl", include_str!("shaders/vxgi_inject.wgsl")),
+        );
+
+        // MARK: MIPMAPPING
+
+        let mip_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vxgi mip bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: RADIANCE_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let mip_bind_groups = radiance_mip_views
+            .windows(2)
+            .map(|views| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("vxgi mip bind group"),
+                    layout: &mip_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[0]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&views[1]),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let mip_pipeline = create_compute_pipeline(
+            device,
+            "vxgi mip pipeline",
+            &[&mip_layout],
+            wgpu::include_wgsl!("shaders/vxgi_mip.wgsl"),
+        );
+
+        // MARK: CONE TRACING
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vxgi bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let radiance_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("vxgi radiance sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vxgi bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &radiance_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&radiance_sampler),
+                },
+            ],
+        });
+
+        Self {
+            mode: GIMode::Off,
+            uniform,
+            uniform_buffer,
+            albedo_texture,
+            normal_texture,
+            inject_pipeline,
+            inject_bind_group,
+            mip_pipeline,
+            mip_bind_groups,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    // voxelizes a new scene into the existing volumes, keeping the current mode
+    pub fn revoxelize(
+        &mut self,
+        queue: &wgpu::Queue,
+        models: &[&model::Model],
+        materials: &[model::Material],
+    ) {
+        let voxels = voxelize(models, materials);
+        write_volume(
+            queue,
+            &self.albedo_texture,
+            bytemuck::cast_slice(&voxels.albedo),
+        );
+        write_volume(
+            queue,
+            &self.normal_texture,
+            bytemuck::cast_slice(&voxels.normals),
+        );
+
+        self.uniform.grid_min = voxels.grid_min.into();
+        self.uniform.voxel_size = voxels.voxel_size;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    pub fn mode(&self) -> GIMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: GIMode, queue: &wgpu::Queue) {
+        self.mode = mode;
+        self.uniform.mode = match mode {
+            GIMode::Off => 0,
+            GIMode::Full => 1,
+            GIMode::IndirectOnly => 2,
+        };
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // injects the current lights and rebuilds the radiance mips. does nothing while gi is off
+    pub fn encode(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        per_frame_bind_group: &wgpu::BindGroup,
+        camera_offset: u32,
+    ) {
+        if self.mode == GIMode::Off {
+            return;
+        }
+
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("vxgi compute pass"),
+            timestamp_writes: None,
+        });
+
+        let workgroups = RESOLUTION.div_ceil(WORKGROUP_SIZE);
+        compute_pass.push_debug_group("inject light");
+        compute_pass.set_pipeline(&self.inject_pipeline);
+        compute_pass.set_bind_group(0, per_frame_bind_group, &[camera_offset]);
+        compute_pass.set_bind_group(1, &self.inject_bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+        compute_pass.pop_debug_group();
+
+        compute_pass.push_debug_group("downsample mips");
+        compute_pass.set_pipeline(&self.mip_pipeline);
+        for (i, mip_bind_group) in self.mip_bind_groups.iter().enumerate() {
+            let workgroups = (RESOLUTION >> (i + 1)).div_ceil(WORKGROUP_SIZE);
+            compute_pass.insert_debug_marker(&format!("mip {}", i + 1));
+            compute_pass.set_bind_group(0, mip_bind_group, &[]);
+            compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+        }
+        compute_pass.pop_debug_group();
+    }
+}
+
+fn create_volume(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: RESOLUTION,
+            height: RESOLUTION,
+            depth_or_array_layers: RESOLUTION,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+// both voxel volumes are 4 bytes per voxel
+fn write_volume(queue: &wgpu::Queue, texture: &wgpu::Texture, data: &[u8]) {
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            aspect: wgpu::TextureAspect::All,
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        data,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * RESOLUTION),
+            rows_per_image: Some(RESOLUTION),
+        },
+        texture.size(),
+    );
+}
+
+pub fn create_compute_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    shader_descriptor: wgpu::ShaderModuleDescriptor,
+) -> wgpu::ComputePipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts,
+        immediate_size: 0,
+    });
+    let shader = device.create_shader_module(shader_descriptor);
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        module: &shader,
+        entry_point: Some("compute_main"),
+        compilation_options: Default::default(),
+        cache: None,
+    })
+}
+
+// splats every triangle of the models into a grid fitted around them. albedo comes from the
+// flat diffuse color of each material (textures aren't kept on the cpu) times the vertex color
+fn voxelize(models: &[&model::Model], materials: &[model::Material]) -> Voxels {
+    let to_world = |matrix: Matrix4<f32>, v: &model::ModelVertex| {
+        Point3::from_homogeneous(matrix * Point3::from(v.position).to_homogeneous())
+    };
+
+    let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+    for model in models {
+        let matrix = model.transform.matrix();
+        for position in model
+            .meshes
+            .iter()
+            .filter(|m| m.is_visible)
+            .flat_map(|m| m.verts.iter())
+        {
+            let position = to_world(matrix, position);
+            min = Point3::new(
+                min.x.min(position.x),
+                min.y.min(position.y),
+                min.z.min(position.z),
+            );
+            max = Point3::new(
+                max.x.max(position.x),
+                max.y.max(position.y),
+                max.z.max(position.z),
+            );
+        }
+    }
+    if min.x > max.x {
+        min = Point3::origin();
+        max = Point3::new(1.0, 1.0, 1.0);
+    }
+
+    // a cube around the bounds with a little padding so cones can leave the surface
+    let extent = (max - min)
+        .x
+        .max((max - min).y)
+        .max((max - min).z)
+        .max(1e-3)
+        * 1.1;
+    let center = min.midpoint(max);
+    let grid_min = center - Vector3::new(extent, extent, extent) * 0.5;
+    let voxel_size = extent / RESOLUTION as f32;
+
+    let voxel_count = (RESOLUTION * RESOLUTION * RESOLUTION) as usize;
+    let mut color_sums = vec![Vector3::new(0.0, 0.0, 0.0); voxel_count];
+    let mut normal_sums = vec![Vector3::new(0.0, 0.0, 0.0); voxel_count];
+    let mut counts = vec![0u32; voxel_count];
+
+    let mut splat = |position: Point3<f32>, color: Vector3<f32>, normal: Vector3<f32>| {
+        let voxel = (position - grid_min) / voxel_size;
+        let [x, y, z] = [voxel.x, voxel.y, voxel.z].map(|c| c as i64);
+        let range = 0..RESOLUTION as i64;
+        if range.contains(&x) && range.contains(&y) && range.contains(&z) {
+            let index = (x + y * RESOLUTION as i64 + z * (RESOLUTION * RESOLUTION) as i64) as usize;
+            color_sums[index] += color;
+            normal_sums[index] += normal;
+            counts[index] += 1;
+        }
+    };
+
+    for (model, mesh) in models
+        .iter()
+        .flat_map(|model| model.meshes.iter().map(move |mesh| (model, mesh)))
+        .filter(|(_, mesh)| mesh.is_visible)
+    {
+        let matrix = model.transform.matrix();
+        let normal_matrix = model.transform.normal_matrix();
+        let diffuse = materials
+            .get(mesh.material)
+            .map_or(Vector3::new(1.0, 1.0, 1.0), |m| {
+                Vector3::from(m.properties.diffuse_color)
+            });
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &mesh.verts[triangle[i] as usize]);
+            let [pa, pb, pc] = [a, b, c].map(|v| to_world(matrix, v));
+
+            // sample the triangle densely enough that every voxel it crosses gets a sample
+            let longest_edge = (pb - pa)
+                .magnitude()
+                .max((pc - pb).magnitude())
+                .max((pa - pc).magnitude());
+            let steps = ((longest_edge / (voxel_size * 0.5)).ceil() as u32).max(1);
+
+            // the material color times the average vertex color of the triangle
+            let color = diffuse.mul_element_wise(
+                (Vector3::from(a.color) + Vector3::from(b.color) + Vector3::from(c.color)) / 3.0,
+            );
+            let normal = (normal_matrix
+                * (Vector3::from(a.normal) + Vector3::from(b.normal) + Vector3::from(c.normal)))
+            .normalize();
+
+            for i in 0..=steps {
+                for j in 0..=(steps - i) {
+                    let (u, v) = (i as f32 / steps as f32, j as f32 / steps as f32);
+                    splat(pa + (pb - pa) * u + (pc - pa) * v, color, normal);
+                }
+            }
+        }
+    }
+
+    let albedo = (0..voxel_count)
+        .map(|i| match counts[i] {
+            0 => [0; 4],
+            count => {
+                let color = color_sums[i] / count as f32;
+                [color.x, color.y, color.z, 1.0].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+            }
+        })
+        .collect();
+
+    let normals = normal_sums
+        .iter()
+        .map(|&sum| {
+            let normal = if sum.magnitude2() > 0.0 {
+                sum.normalize()
+            } else {
+                sum
+            };
+            [normal.x, normal.y, normal.z, 0.0].map(|c| (c * 127.0) as i8)
+        })
+        .collect();
+
+    Voxels {
+        grid_min,
+        voxel_size,
+        albedo,
+        normals,
+    }
+}
@@ -0,0 +1,84 @@
+// walking over the scene rather than flying through it, for walkthroughs of buildings and
+// terrain. the camera keeps moving as the controller moves it across the ground, but its height
+// is taken over: its feet fall under gravity until they land on whatever is below them, found
+// with a ray cast down into the scene, and the eyes stay a person's height above them. steps up
+// to a little below the knees are climbed, anything taller is walked into. there are no walls,
+// so it's only the ground which is collided with
+
+use cgmath::{Point3, Vector3};
+
+use crate::{camera, math, scene};
+
+pub struct Walk {
+    // the height of the feet, which the eyes are eye_height above
+    feet: f32,
+    vertical_velocity: f32,
+    is_grounded: bool,
+    pub eye_height: f32,
+    pub gravity: f32,
+}
+
+impl Walk {
+    pub const DEFAULT_EYE_HEIGHT: f32 = 1.7;
+    pub const DEFAULT_GRAVITY: f32 = 9.81;
+    // the highest step that's climbed, rather than stopping the feet dropping onto it
+    const STEP_HEIGHT: f32 = 0.4;
+    const JUMP_SPEED: f32 = 4.5;
+
+    // starts with the feet under the camera where it is, so it drops to the ground from there
+    pub fn new(camera: &camera::Camera, eye_height: f32, gravity: f32) -> Self {
+        Self {
+            feet: camera.position.y - eye_height,
+            vertical_velocity: 0.0,
+            is_grounded: false,
+            eye_height,
+            gravity,
+        }
+    }
+
+    // only from the ground
+    pub fn jump(&mut self) {
+        if self.is_grounded {
+            self.vertical_velocity = Self::JUMP_SPEED;
+            self.is_grounded = false;
+        }
+    }
+
+    // after the controller has moved the camera, puts it back at eye height over the ground.
+    // with nothing under it, the camera stays at the height it's at rather than falling forever
+    pub fn update(&mut self, camera: &mut camera::Camera, scene: &scene::Scene, dt: f32) {
+        let ray = math::Ray::new(
+            Point3::new(
+                camera.position.x,
+                self.feet + Self::STEP_HEIGHT,
+                camera.position.z,
+            ),
+            -Vector3::unit_y(),
+        );
+        match scene.raycast(&ray) {
+            Some(hit) => {
+                let ground = hit.position.y;
+                self.vertical_velocity -= self.gravity * dt;
+                self.feet += self.vertical_velocity * dt;
+                self.is_grounded = self.feet <= ground;
+                if self.is_grounded {
+                    self.feet = ground;
+                    self.vertical_velocity = 0.0;
+                }
+            }
+            None => {
+                self.vertical_velocity = 0.0;
+                self.is_grounded = false;
+            }
+        }
+        camera.position.y = self.feet + self.eye_height;
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.is_grounded {
+            "[WALK]"
+        } else {
+            "[WALK FALLING]"
+        }
+    }
+}
@@ -0,0 +1,447 @@
+// rain and snow. the precipitation is a particle emitter (see particles.rs) kept over the
+// camera, so it's always falling wherever the camera goes, and dies wherever it lands. rain
+// also wets the scene, darkening surfaces facing up and making them glossier, slowly as it
+// starts and more slowly still as it dries once it stops, and runs down the screen as droplets
+// in a post process between bloom and fxaa. set from scene files' "weather"
+
+use crate::{
+    camera,
+    particles::{Collision, Emitter, EmitterId, ParticleSystem},
+    texture,
+    uploads::UploadBelt,
+};
+
+// how far above the camera the precipitation starts, and how far to each side
+const EMITTER_HEIGHT: f32 = 8.0;
+const EMITTER_RADIUS: f32 = 15.0;
+// the seconds surfaces take to get fully wet in full rain, and to dry again
+const WETTING_TIME: f32 = 20.0;
+const DRYING_TIME: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precipitation {
+    None,
+    Rain,
+    Snow,
+}
+
+impl Precipitation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Precipitation::None => "clear",
+            Precipitation::Rain => "rain",
+            Precipitation::Snow => "snow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clear" | "none" => Some(Precipitation::None),
+            "rain" => Some(Precipitation::Rain),
+            "snow" => Some(Precipitation::Snow),
+            _ => None,
+        }
+    }
+
+    // at full intensity, over a circle as wide as the emitter's. 0 intensity falls as nothing
+    fn emitter(self, intensity: f32) -> Option<Emitter> {
+        let emitter = match self {
+            Precipitation::None => return None,
+            // straight down at its terminal speed, so there's no gravity to speed it up
+            Precipitation::Rain => Emitter {
+                velocity: [0.0, -12.0, 0.0],
+                spread: 0.3,
+                rate: 8000.0,
+                lifetime: 1.5,
+                size: 0.02,
+                color: [0.7, 0.75, 0.8, 0.5],
+                ..Default::default()
+            },
+            // drifting down slowly, swaying with the spread
+            Precipitation::Snow => Emitter {
+                velocity: [0.0, -1.2, 0.0],
+                spread: 0.6,
+                rate: 2000.0,
+                lifetime: 12.0,
+                size: 0.05,
+                color: [1.0, 1.0, 1.0, 0.9],
+                ..Default::default()
+            },
+        };
+        Some(Emitter {
+            extent: [EMITTER_RADIUS, 0.0, EMITTER_RADIUS],
+            rate: emitter.rate * intensity,
+            gravity: 0.0,
+            collision: Collision::Kill,
+            ..emitter
+        })
+    }
+}
+
+pub struct Weather {
+    precipitation: Precipitation,
+    // from 0 to 1, scaling how much falls and how wet things get
+    intensity: f32,
+    // None without precipitation, or without compute shaders to simulate it
+    emitter: Option<(EmitterId, Emitter)>,
+    // from 0 for dry to 1 for soaked, see shader.wgsl
+    wetness: f32,
+    // only while it rains
+    droplets: Option<ScreenDroplets>,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            precipitation: Precipitation::None,
+            intensity: 0.0,
+            emitter: None,
+            wetness: 0.0,
+            droplets: None,
+        }
+    }
+}
+
+impl Weather {
+    pub fn precipitation(&self) -> Precipitation {
+        self.precipitation
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn wetness(&self) -> f32 {
+        self.wetness
+    }
+
+    // while there's anything changing from frame to frame
+    pub fn is_active(&self) -> bool {
+        self.precipitation != Precipitation::None || self.wetness > 0.0
+    }
+
+    pub fn set(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        particles: Option<&mut ParticleSystem>,
+        precipitation: Precipitation,
+        intensity: f32,
+    ) -> anyhow::Result<()> {
+        let intensity = intensity.clamp(0.0, 1.0);
+        match particles {
+            Some(particles) => {
+                if let Some((id, _)) = self.emitter.take() {
+                    particles.remove(id);
+                }
+                if let Some(emitter) = precipitation.emitter(intensity) {
+                    self.emitter = Some((particles.add(emitter)?, emitter));
+                }
+            }
+            None if precipitation != Precipitation::None => {
+                log::warn!(
+                    "particles need compute shaders, so the {} won't be seen falling",
+                    precipitation.name()
+                );
+            }
+            None => {}
+        }
+
+        self.droplets = match precipitation {
+            Precipitation::Rain => self
+                .droplets
+                .take()
+                .or_else(|| Some(ScreenDroplets::new(device, config))),
+            _ => None,
+        };
+        self.precipitation = precipitation;
+        self.intensity = intensity;
+        Ok(())
+    }
+
+    // moves the emitter over the camera and wets or dries the scene by `dt`, the scene's time
+    pub fn update(
+        &mut self,
+        uploads: &mut UploadBelt,
+        particles: Option<&mut ParticleSystem>,
+        camera: &camera::Camera,
+        dt: f32,
+    ) {
+        let target = match self.precipitation {
+            Precipitation::Rain => self.intensity,
+            _ => 0.0,
+        };
+        let rate = if target > self.wetness {
+            1.0 / WETTING_TIME
+        } else {
+            -1.0 / DRYING_TIME
+        };
+        self.wetness = if rate > 0.0 {
+            (self.wetness + rate * dt).min(target)
+        } else {
+            (self.wetness + rate * dt).max(target)
+        };
+
+        if let (Some((id, emitter)), Some(particles)) = (&mut self.emitter, particles) {
+            emitter.position = [
+                camera.position.x,
+                camera.position.y + EMITTER_HEIGHT,
+                camera.position.z,
+            ];
+            // the same rate and lifetime as before, so it keeps its slots and can't fail
+            let _ = particles.set(*id, *emitter);
+        }
+
+        if let Some(droplets) = &mut self.droplets {
+            droplets.update(uploads, dt, self.intensity);
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        if let Some(droplets) = &mut self.droplets {
+            droplets.resize(device, config);
+        }
+    }
+
+    pub fn droplets(&self) -> Option<&ScreenDroplets> {
+        self.droplets.as_ref()
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self.precipitation {
+            Precipitation::None => "",
+            Precipitation::Rain => "[RAIN]",
+            Precipitation::Snow => "[SNOW]",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DropletsUniform {
+    // seconds of rain, which the droplets run down the screen with
+    time: f32,
+    // from 0 for none to 1 for the most
+    strength: f32,
+    _padding: [f32; 2],
+}
+
+// rain running down the screen as a post process, like a lens out in the rain. the frame is
+// drawn into a source texture, and each droplet bends it like a little lens as it's copied to
+// the target, see droplets.wgsl
+pub struct ScreenDroplets {
+    uniform: DropletsUniform,
+    uniform_buffer: wgpu::Buffer,
+    // what the frame is drawn into before the pass
+    pub source_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ScreenDroplets {
+    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let uniform = DropletsUniform {
+            time: 0.0,
+            strength: 0.0,
+            _padding: [0.0; 2],
+        };
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("droplets uniform buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        // the droplets bend the frame by fractions of a texel
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("droplets source sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("droplets bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("droplets pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/droplets.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("droplets pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // a single triangle covering the screen, generated in the vertex shader
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let source_view = Self::create_source_view(device, config);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &source_view,
+            &sampler,
+        );
+
+        Self {
+            uniform,
+            uniform_buffer,
+            source_view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    // a color target matching the surface, which the pass samples
+    fn create_source_view(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::TextureView {
+        texture::Texture::builder("droplets source texture", config.width, config.height)
+            .format(config.format)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .build(device)
+            .view
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        source_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("droplets bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.source_view = Self::create_source_view(device, config);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.source_view,
+            &self.sampler,
+        );
+    }
+
+    fn update(&mut self, uploads: &mut UploadBelt, dt: f32, strength: f32) {
+        self.uniform.time += dt;
+        self.uniform.strength = strength;
+        uploads.write(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // bends the source into the target, replacing what was there
+    pub fn encode(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) {
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("droplets pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}